@@ -0,0 +1,320 @@
+// src/api/audit_search.rs
+//! 统一审计检索
+//!
+//! `GET /audit/search`（仅管理员）合并安全审计日志（`AuditLogManager`）与权重变更审计
+//! （`WeightAuditSystem`）中的记录，转换为统一的条目格式后按时间倒序分页返回。
+//! 支持按时间范围、操作者（权重变更的 operator 或审计日志的用户标识）、事件类型与来源筛选
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use warp::{Filter, Rejection, Reply};
+use crate::api::auth::{admin_only, AuthState};
+use crate::api::config::ApiResponse;
+use crate::load_balancer::audit::{AuditQuery, WeightAuditSystem, WeightChangeRecord};
+use crate::security::{AuditEventType, AuditLogEntry, SharedAuditLog};
+
+/// 默认每页条数
+const DEFAULT_PAGE_SIZE: usize = 50;
+/// 每页条数上限
+const MAX_PAGE_SIZE: usize = 500;
+
+/// 审计记录来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditSource {
+    /// 安全审计日志
+    Security,
+    /// 权重变更审计
+    Weight,
+}
+
+/// 统一的审计条目
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditSearchEntry {
+    pub id: String,
+    pub source: AuditSource,
+    pub timestamp: DateTime<Utc>,
+    /// 事件类型：权重变更为 `weight_change`，审计日志为其事件类型（如 `authentication`）
+    pub event_type: String,
+    /// 操作者：权重变更的 operator 或审计日志的用户标识
+    pub operator: Option<String>,
+    pub action: String,
+    pub resource: String,
+    pub result: Option<String>,
+    pub details: Option<String>,
+}
+
+/// 检索条件
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditSearchQuery {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// 操作者（精确匹配）
+    pub operator: Option<String>,
+    /// 事件类型（不区分大小写）
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    pub source: Option<AuditSource>,
+    /// 页码，从 1 开始
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+}
+
+/// 检索结果
+#[derive(Debug, Serialize)]
+pub struct AuditSearchResponse {
+    pub entries: Vec<AuditSearchEntry>,
+    /// 满足条件的总条数
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+}
+
+/// 审计检索状态
+#[derive(Clone, Default)]
+pub struct AuditSearchState {
+    audit_log: Option<SharedAuditLog>,
+    weight_audit: Option<Arc<RwLock<WeightAuditSystem>>>,
+}
+
+impl AuditSearchState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 关联安全审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 关联权重变更审计
+    pub fn with_weight_audit(mut self, weight_audit: Arc<RwLock<WeightAuditSystem>>) -> Self {
+        self.weight_audit = Some(weight_audit);
+        self
+    }
+
+    /// 合并两个来源的记录并筛选、分页
+    pub async fn search(&self, query: &AuditSearchQuery) -> AuditSearchResponse {
+        let mut entries = Vec::new();
+        if query.includes(AuditSource::Security) {
+            if let Some(audit_log) = &self.audit_log {
+                let audit_log = audit_log.lock().await;
+                entries.extend(audit_log.get_recent_logs(usize::MAX).into_iter().map(security_entry));
+            }
+        }
+        if query.includes(AuditSource::Weight) {
+            if let Some(weight_audit) = &self.weight_audit {
+                let records = weight_audit
+                    .read()
+                    .await
+                    .query_audit_records(&AuditQuery {
+                        start_time: None,
+                        end_time: None,
+                        operator: None,
+                        operation_type: None,
+                        target_key_id: None,
+                        source: None,
+                        limit: None,
+                        offset: None,
+                    })
+                    .await;
+                entries.extend(records.iter().map(weight_entry));
+            }
+        }
+
+        entries.retain(|entry| query.matches(entry));
+        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+        let page = query.page.unwrap_or(1).max(1);
+        let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let total = entries.len();
+        let entries = entries.into_iter().skip((page - 1) * page_size).take(page_size).collect();
+        AuditSearchResponse {
+            entries,
+            total,
+            page,
+            page_size,
+        }
+    }
+}
+
+impl AuditSearchQuery {
+    /// 是否检索该来源
+    fn includes(&self, source: AuditSource) -> bool {
+        self.source.is_none() || self.source == Some(source)
+    }
+
+    fn matches(&self, entry: &AuditSearchEntry) -> bool {
+        if let Some(start_time) = self.start_time {
+            if entry.timestamp < start_time {
+                return false;
+            }
+        }
+        if let Some(end_time) = self.end_time {
+            if entry.timestamp > end_time {
+                return false;
+            }
+        }
+        if let Some(operator) = &self.operator {
+            if entry.operator.as_ref() != Some(operator) {
+                return false;
+            }
+        }
+        if let Some(event_type) = &self.event_type {
+            if !entry.event_type.eq_ignore_ascii_case(event_type) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn security_entry(entry: &AuditLogEntry) -> AuditSearchEntry {
+    let event_type = match entry.event_type {
+        AuditEventType::ApiCall => "api_call",
+        AuditEventType::ConfigChange => "config_change",
+        AuditEventType::Authentication => "authentication",
+        AuditEventType::SecurityEvent => "security_event",
+        AuditEventType::SystemOperation => "system_operation",
+        AuditEventType::ErrorEvent => "error_event",
+    };
+    AuditSearchEntry {
+        id: entry.id.clone(),
+        source: AuditSource::Security,
+        timestamp: entry.timestamp,
+        event_type: event_type.to_string(),
+        operator: entry.user_identifier.clone(),
+        action: entry.action.clone(),
+        resource: entry.resource.clone(),
+        result: Some(format!("{:?}", entry.result)),
+        details: entry.details.clone(),
+    }
+}
+
+fn weight_entry(record: &WeightChangeRecord) -> AuditSearchEntry {
+    AuditSearchEntry {
+        id: record.id.clone(),
+        source: AuditSource::Weight,
+        timestamp: Utc
+            .timestamp_opt(record.timestamp as i64, 0)
+            .single()
+            .unwrap_or_default(),
+        event_type: "weight_change".to_string(),
+        operator: Some(record.operator.clone()),
+        action: format!(
+            "权重变更（{:?}）: {} -> {}",
+            record.operation_type, record.old_weight, record.new_weight
+        ),
+        resource: record.target_key_id.clone(),
+        result: None,
+        details: Some(record.reason.clone()).filter(|reason| !reason.is_empty()),
+    }
+}
+
+/// 审计检索 API 路由
+pub fn audit_search_routes(
+    state: AuditSearchState,
+    auth_state: AuthState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let search_state = warp::any().map(move || state.clone());
+
+    // GET /audit/search?operator=&type=&source=&start_time=&end_time=&page=&page_size= - 检索审计记录
+    warp::path!("audit" / "search")
+        .and(warp::get())
+        .and(admin_only(auth_state))
+        .and(warp::query::<AuditSearchQuery>())
+        .and(search_state)
+        .and_then(search_handler)
+}
+
+async fn search_handler(query: AuditSearchQuery, state: AuditSearchState) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&ApiResponse::success(state.search(&query).await)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProxyConfig;
+    use crate::load_balancer::audit::{AuditConfig as WeightAuditConfig, ChangeSource, OperationType};
+    use crate::security::{AuditConfig, AuditLogManager, AuditResult};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    async fn test_state() -> AuditSearchState {
+        let weight_audit = WeightAuditSystem::new(WeightAuditConfig::default());
+        for (operator, key_id) in [("alice", "key1"), ("bob", "key2")] {
+            weight_audit
+                .record_weight_change(operator, OperationType::Manual, key_id, 100, 150, "扩容", ChangeSource::API, None)
+                .await
+                .unwrap();
+        }
+
+        let mut audit_log = AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        });
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        audit_log
+            .log_auth_event(ip, Some("alice".to_string()), "jwt", AuditResult::Success, None)
+            .await
+            .unwrap();
+        audit_log
+            .log_auth_event(ip, Some("mallory".to_string()), "jwt", AuditResult::Failure, None)
+            .await
+            .unwrap();
+
+        AuditSearchState::new()
+            .with_audit_log(Arc::new(tokio::sync::Mutex::new(audit_log)))
+            .with_weight_audit(Arc::new(RwLock::new(weight_audit)))
+    }
+
+    #[tokio::test]
+    async fn test_search_merges_sources_and_filters_by_operator() {
+        let auth_state = AuthState::new(Arc::new(ProxyConfig::for_test(&["key1"])));
+        let session_id = auth_state.create_session("admin").await;
+        let token = auth_state.generate_token(&session_id).unwrap();
+        let routes = audit_search_routes(test_state().await, auth_state)
+            .recover(crate::api::handlers::handle_rejection);
+        let search = |query: &'static str| {
+            let token = token.clone();
+            let routes = routes.clone();
+            async move {
+                let response = warp::test::request()
+                    .path(&format!("/audit/search{}", query))
+                    .header("authorization", format!("Bearer {}", token))
+                    .reply(&routes)
+                    .await;
+                assert_eq!(response.status(), 200);
+                serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()["data"].clone()
+            }
+        };
+
+        // 两个来源的记录都出现
+        let all = search("").await;
+        assert_eq!(all["total"], 4);
+        let sources: Vec<&str> = all["entries"].as_array().unwrap().iter().map(|e| e["source"].as_str().unwrap()).collect();
+        assert!(sources.contains(&"weight") && sources.contains(&"security"));
+
+        // 按操作者筛选只保留 alice 的权重变更与认证事件
+        let alice = search("?operator=alice").await;
+        assert_eq!(alice["total"], 2);
+        for entry in alice["entries"].as_array().unwrap() {
+            assert_eq!(entry["operator"], "alice");
+        }
+        let types: Vec<&str> = alice["entries"].as_array().unwrap().iter().map(|e| e["event_type"].as_str().unwrap()).collect();
+        assert!(types.contains(&"weight_change") && types.contains(&"authentication"));
+
+        // 按类型筛选与分页
+        let weight = search("?type=weight_change&page=2&page_size=1").await;
+        assert_eq!(weight["total"], 2);
+        assert_eq!(weight["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(weight["page"], 2);
+        assert_eq!(search("?source=security&operator=bob").await["total"], 0);
+
+        // 未认证的请求被拒绝
+        let response = warp::test::request().path("/audit/search").reply(&routes).await;
+        assert_eq!(response.status(), 401);
+    }
+}
@@ -1,15 +1,18 @@
 // src/api/auth.rs
-use crate::config::ProxyConfig;
+use crate::auth::client_keys::{presented_credential, ClientIdentity, ClientKeyStore, Credential, FailureLimit, PROXY_KEY_HEADER};
+use crate::config::{ProxyConfig, RouteAccess};
+use crate::metrics::MetricsCollector;
+use crate::security::ip_rules::TrustedProxies;
+use crate::security::SharedAuditLog;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{encode, decode, Header, Algorithm, EncodingKey, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use warp::path::FullPath;
 use warp::{Filter, Reply};
-// 暂时注释掉未使用的导入
-// use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-// use argon2::password_hash::{rand_core::OsRng, SaltString};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -57,7 +60,6 @@ pub struct Session {
     pub id: String,
     #[allow(dead_code)]
     pub user_id: String,
-    #[allow(dead_code)]
     pub created_at: chrono::DateTime<Utc>,
     pub last_activity: chrono::DateTime<Utc>,
     pub is_active: bool,
@@ -69,15 +71,66 @@ pub struct AuthState {
     login_attempts: Arc<RwLock<HashMap<String, LoginAttempt>>>,
     active_sessions: Arc<RwLock<HashMap<String, Session>>>,
     refresh_tokens: Arc<RwLock<HashMap<String, String>>>, // refresh_token -> session_id
+    client_keys: Arc<ClientKeyStore>,
+    audit_log: Option<SharedAuditLog>,
+    /// 受信任的反向代理，只有来自这些地址的请求才采用 `X-Forwarded-For`
+    trusted_proxies: TrustedProxies,
+    /// 会话指标收集器（可选）：活跃会话数与会话年龄分布
+    metrics: Option<Arc<MetricsCollector>>,
 }
 
 impl AuthState {
     pub fn new(config: Arc<ProxyConfig>) -> Self {
         Self {
-            config,
             login_attempts: Arc::new(RwLock::new(HashMap::new())),
             active_sessions: Arc::new(RwLock::new(HashMap::new())),
             refresh_tokens: Arc::new(RwLock::new(HashMap::new())),
+            client_keys: Arc::new(
+                ClientKeyStore::from_config(&config.auth.api_keys)
+                    .with_failure_limit(FailureLimit::from_auth_config(&config.auth)),
+            ),
+            trusted_proxies: TrustedProxies::from_config(&config.security.trusted_proxies).unwrap_or_else(|e| {
+                tracing::warn!("忽略无效的受信任代理配置: {}", e);
+                TrustedProxies::default()
+            }),
+            config,
+            audit_log: None,
+            metrics: None,
+        }
+    }
+
+    /// 客户端地址：对端是受信任的代理时采用 `X-Forwarded-For`，否则使用对端地址；无法确定时为未指定地址
+    fn client_ip(&self, remote: Option<SocketAddr>, headers: &warp::http::HeaderMap) -> IpAddr {
+        let forwarded_for = headers.get("x-forwarded-for").and_then(|hv| hv.to_str().ok());
+        self.trusted_proxies
+            .client_ip(remote.map(|addr| addr.ip()), forwarded_for)
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+
+    /// 代理密钥认证结果写入审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 设置会话指标收集器
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// 会话是否已过期：超过空闲超时，或超过绝对最长存活时间（`auth.session_max_lifetime_minutes`）
+    fn is_session_expired(&self, session: &Session, now: chrono::DateTime<Utc>) -> bool {
+        let timeout = Duration::minutes(self.config.auth.session_timeout_minutes as i64);
+        let max_lifetime = self.config.auth.session_max_lifetime_minutes;
+        now - session.last_activity >= timeout
+            || (max_lifetime > 0 && now - session.created_at >= Duration::minutes(max_lifetime as i64))
+    }
+
+    /// 记录会话结束（超时、到达最长存活时间或登出）
+    async fn record_session_ended(&self, session: &Session, now: chrono::DateTime<Utc>) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_session_ended((now - session.created_at).to_std().unwrap_or_default()).await;
         }
     }
 
@@ -129,14 +182,19 @@ impl AuthState {
 
     // 生成JWT token
     pub fn generate_token(&self, session_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
+        self.generate_token_with_role(session_id, "admin", "admin")
+    }
+
+    // 生成指定用户与角色的JWT token（角色决定可访问的路由，见 `auth.route_policies`）
+    pub fn generate_token_with_role(&self, session_id: &str, user_id: &str, role: &str) -> Result<String, jsonwebtoken::errors::Error> {
         let expiration = Utc::now()
             + Duration::hours(self.config.auth.token_expiry_hours as i64);
         
         let claims = Claims {
-            sub: "admin".to_string(),
+            sub: user_id.to_string(),
             exp: expiration.timestamp() as usize,
             iat: Utc::now().timestamp() as usize,
-            role: "admin".to_string(),
+            role: role.to_string(),
             session_id: session_id.to_string(),
         };
 
@@ -176,6 +234,9 @@ impl AuthState {
         };
 
         self.active_sessions.write().await.insert(session_id.clone(), session);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_session_created().await;
+        }
         session_id
     }
 
@@ -185,24 +246,40 @@ impl AuthState {
         if let Some(session) = sessions.get_mut(session_id) {
             if session.is_active {
                 let now = Utc::now();
-                let timeout = Duration::minutes(self.config.auth.session_timeout_minutes as i64);
                 
-                if now - session.last_activity < timeout {
+                if !self.is_session_expired(session, now) {
                     // 更新最后活动时间
                     session.last_activity = now;
                     return true;
                 } else {
-                    // 会话超时
+                    // 会话超时或到达最长存活时间
                     session.is_active = false;
+                    self.record_session_ended(session, now).await;
                 }
             }
         }
         false
     }
 
+    // 刷新会话：验证会话并记录刷新时的会话年龄
+    pub async fn refresh_session(&self, session_id: &str) -> bool {
+        if !self.validate_session(session_id).await {
+            return false;
+        }
+        if let Some(metrics) = &self.metrics {
+            if let Some(session) = self.active_sessions.read().await.get(session_id) {
+                metrics.record_session_refreshed((Utc::now() - session.created_at).to_std().unwrap_or_default()).await;
+            }
+        }
+        true
+    }
+
     // 删除会话
     pub async fn remove_session(&self, session_id: &str) {
-        self.active_sessions.write().await.remove(session_id);
+        let removed = self.active_sessions.write().await.remove(session_id);
+        if let Some(session) = removed.filter(|session| session.is_active) {
+            self.record_session_ended(&session, Utc::now()).await;
+        }
         
         // 同时删除相关的刷新token
         let mut refresh_tokens = self.refresh_tokens.write().await;
@@ -214,36 +291,28 @@ impl AuthState {
     pub async fn cleanup_expired_sessions(&self) {
         let mut sessions = self.active_sessions.write().await;
         let now = Utc::now();
-        let timeout = Duration::minutes(self.config.auth.session_timeout_minutes as i64);
-        
-        sessions.retain(|_, session| {
-            if now - session.last_activity < timeout {
-                true
-            } else {
-                false
+        let expired: Vec<String> = sessions
+            .iter()
+            .filter(|(_, session)| self.is_session_expired(session, now))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for session_id in expired {
+            if let Some(session) = sessions.remove(&session_id).filter(|session| session.is_active) {
+                self.record_session_ended(&session, now).await;
             }
-        });
+        }
     }
 }
 
-// 获取客户端IP
-fn get_client_ip(headers: &warp::http::HeaderMap) -> String {
-    headers
-        .get("x-forwarded-for")
-        .and_then(|hv| hv.to_str().ok())
-        .and_then(|s| s.split(',').next())
-        .unwrap_or("127.0.0.1")
-        .trim()
-        .to_string()
-}
-
 // 登录处理
 async fn handle_login(
     login_req: LoginRequest,
     auth_state: AuthState,
+    remote: Option<SocketAddr>,
     headers: warp::http::HeaderMap,
 ) -> Result<impl Reply, warp::Rejection> {
-    let client_ip = get_client_ip(&headers);
+    let client_ip = auth_state.client_ip(remote, &headers).to_string();
     
     // 检查是否被锁定
     if auth_state.is_locked(&client_ip).await {
@@ -328,7 +397,7 @@ async fn handle_refresh(
     let refresh_tokens = auth_state.refresh_tokens.read().await;
     
     if let Some(session_id) = refresh_tokens.get(&refresh_req.refresh_token) {
-        if auth_state.validate_session(session_id).await {
+        if auth_state.refresh_session(session_id).await {
             match auth_state.generate_token(session_id) {
                 Ok(new_token) => {
                     return Ok(warp::reply::with_status(
@@ -410,38 +479,109 @@ async fn handle_verify(
     }
 }
 
-// 认证中间件
-pub fn auth_middleware(
+// 校验 Authorization 头中的 Bearer 令牌及其会话
+async fn authorize(auth_state: &AuthState, auth_header: Option<&str>) -> Result<Claims, warp::Rejection> {
+    let token = auth_header
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or_else(|| warp::reject::custom(AuthError::MissingToken))?;
+    let claims = auth_state
+        .verify_token(token)
+        .map_err(|_| warp::reject::custom(AuthError::InvalidToken))?;
+    if auth_state.validate_session(&claims.session_id).await {
+        Ok(claims)
+    } else {
+        Err(warp::reject::custom(AuthError::SessionExpired))
+    }
+}
+
+// 校验 JWT 或代理 API 密钥（`X-Proxy-Key` / `Authorization: Bearer`），任一方式通过即可
+async fn authenticate(
+    auth_state: &AuthState,
+    remote: Option<SocketAddr>,
+    headers: &warp::http::HeaderMap,
+) -> Result<ClientIdentity, warp::Rejection> {
+    let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+    match presented_credential(header(PROXY_KEY_HEADER), header("authorization")) {
+        None => Err(warp::reject::custom(AuthError::MissingToken)),
+        Some(Credential::Jwt(token)) => {
+            let claims = authorize(auth_state, Some(&format!("Bearer {}", token))).await?;
+            Ok(ClientIdentity {
+                id: claims.sub,
+                permissions: vec![claims.role],
+            })
+        }
+        Some(Credential::ProxyKey(secret)) => {
+            let client_ip = auth_state.client_ip(remote, headers);
+            auth_state
+                .client_keys
+                .authenticate(&secret, client_ip, auth_state.audit_log.as_ref())
+                .await
+                .ok_or_else(|| warp::reject::custom(AuthError::InvalidProxyKey))
+        }
+    }
+}
+
+// JWT 与代理 API 密钥组合认证，提取客户端身份
+pub fn jwt_or_proxy_key(
     auth_state: AuthState,
-) -> impl Filter<Extract = (Claims,), Error = warp::Rejection> + Clone {
-    warp::header::<String>("authorization")
-        .and_then(move |auth_header: String| {
+) -> impl Filter<Extract = (ClientIdentity,), Error = warp::Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::headers_cloned())
+        .and_then(move |remote: Option<SocketAddr>, headers: warp::http::HeaderMap| {
+            let auth_state = auth_state.clone();
+            async move { authenticate(&auth_state, remote, &headers).await }
+        })
+}
+
+// 按 `auth.route_policies` 校验路由访问权限，未匹配任何前缀的路由直接放行
+pub fn route_policy(
+    auth_state: AuthState,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::path::full()
+        .and(warp::addr::remote())
+        .and(warp::header::headers_cloned())
+        .and_then(move |path: FullPath, remote: Option<SocketAddr>, headers: warp::http::HeaderMap| {
             let auth_state = auth_state.clone();
             async move {
-                if let Some(token) = auth_header.strip_prefix("Bearer ") {
-                    match auth_state.verify_token(token) {
-                        Ok(claims) => {
-                            if auth_state.validate_session(&claims.session_id).await {
-                                Ok(claims)
-                            } else {
-                                Err(warp::reject::custom(AuthError::SessionExpired))
-                            }
+                match auth_state.config.auth.route_access(path.as_str()) {
+                    None | Some(RouteAccess::Public) => Ok(()),
+                    Some(access) => {
+                        let identity = authenticate(&auth_state, remote, &headers).await?;
+                        if identity.permissions.iter().any(|permission| access.allows(permission)) {
+                            Ok(())
+                        } else {
+                            Err(warp::reject::custom(AuthError::Forbidden))
                         }
-                        Err(_) => Err(warp::reject::custom(AuthError::InvalidToken)),
                     }
-                } else {
-                    Err(warp::reject::custom(AuthError::MissingToken))
                 }
             }
         })
+        .untuple_one()
+}
+
+// 仅允许管理员访问（admin 角色的 JWT 或具备 admin 权限的代理密钥）
+pub fn admin_only(
+    auth_state: AuthState,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    jwt_or_proxy_key(auth_state)
+        .and_then(|identity: ClientIdentity| async move {
+            if identity.has_permission("admin") {
+                Ok(())
+            } else {
+                Err(warp::reject::custom(AuthError::Forbidden))
+            }
+        })
+        .untuple_one()
 }
 
 // 认证错误类型
 #[derive(Debug)]
 pub enum AuthError {
     InvalidToken,
+    InvalidProxyKey,
     MissingToken,
     SessionExpired,
+    Forbidden,
 }
 
 impl warp::reject::Reject for AuthError {}
@@ -456,6 +596,7 @@ pub fn auth_routes(
         .and(warp::post())
         .and(warp::body::json())
         .and(auth_state_filter.clone())
+        .and(warp::addr::remote())
         .and(warp::header::headers_cloned())
         .and_then(handle_login);
 
@@ -491,4 +632,187 @@ pub fn auth_routes(
 
     warp::path("auth")
         .and(login.or(refresh).or(logout).or(verify))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> ProxyConfig {
+        let mut config = ProxyConfig::for_test(&["key1"]);
+        config.auth.route_policies = HashMap::from([
+            ("/api/config/*".to_string(), RouteAccess::Admin),
+            ("/api/stats/*".to_string(), RouteAccess::Read),
+            ("/api/stats/public".to_string(), RouteAccess::Public),
+        ]);
+        config
+    }
+
+    async fn token(auth_state: &AuthState, role: &str) -> String {
+        let session_id = auth_state.create_session(role).await;
+        let token = auth_state.generate_token_with_role(&session_id, role, role).unwrap();
+        format!("Bearer {}", token)
+    }
+
+    #[tokio::test]
+    async fn test_route_policy_read_token_reaches_stats_not_config() {
+        let auth_state = AuthState::new(Arc::new(test_config()));
+        let routes = route_policy(auth_state.clone())
+            .and(warp::path("api"))
+            .and(warp::path::tail())
+            .map(|_| "ok")
+            .recover(crate::api::handlers::handle_rejection);
+
+        let read = token(&auth_state, "read").await;
+        let admin = token(&auth_state, "admin").await;
+        let status = |path: &'static str, authorization: Option<String>| {
+            let routes = routes.clone();
+            async move {
+                let mut request = warp::test::request().path(path);
+                if let Some(authorization) = authorization {
+                    request = request.header("authorization", authorization);
+                }
+                request.reply(&routes).await.status().as_u16()
+            }
+        };
+
+        assert_eq!(status("/api/stats/summary", Some(read.clone())).await, 200);
+        assert_eq!(status("/api/stats", Some(read.clone())).await, 200);
+        assert_eq!(status("/api/config", Some(read.clone())).await, 403);
+        assert_eq!(status("/api/config/reload", Some(read)).await, 403);
+        assert_eq!(status("/api/config", Some(admin.clone())).await, 200);
+        assert_eq!(status("/api/stats/summary", Some(admin)).await, 200);
+
+        // 缺少令牌时受保护路由返回 401；更长的 public 前缀与未列出的路由直接放行
+        assert_eq!(status("/api/stats/summary", None).await, 401);
+        assert_eq!(status("/api/stats/public", None).await, 200);
+        assert_eq!(status("/api/configs", None).await, 200);
+        assert_eq!(status("/api/weights", None).await, 200);
+    }
+
+    #[tokio::test]
+    async fn test_route_policy_accepts_proxy_key_with_read_permission() {
+        let mut config = test_config();
+        config.auth.api_keys.push(crate::config::ClientApiKeyConfig {
+            id: "dashboard".to_string(),
+            secret_hash: crate::auth::client_keys::hash_client_secret("pk-dashboard").unwrap(),
+            permissions: vec!["read".to_string()],
+        });
+        let auth_state = AuthState::new(Arc::new(config));
+        let routes = route_policy(auth_state)
+            .and(warp::path("api"))
+            .and(warp::path::tail())
+            .map(|_| "ok")
+            .recover(crate::api::handlers::handle_rejection);
+        let status = |path: &'static str, header: &'static str, value: &'static str| {
+            let routes = routes.clone();
+            async move {
+                warp::test::request()
+                    .path(path)
+                    .header(header, value)
+                    .reply(&routes)
+                    .await
+                    .status()
+                    .as_u16()
+            }
+        };
+
+        assert_eq!(status("/api/stats/summary", "x-proxy-key", "dashboard:pk-dashboard").await, 200);
+        assert_eq!(status("/api/stats/summary", "authorization", "Bearer dashboard:pk-dashboard").await, 200);
+        assert_eq!(status("/api/config", "x-proxy-key", "dashboard:pk-dashboard").await, 403);
+        assert_eq!(status("/api/stats/summary", "x-proxy-key", "dashboard:pk-unknown").await, 401);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_key_failure_limit_ignores_untrusted_forwarded_for() {
+        let mut config = test_config();
+        config.auth.max_login_attempts = 2;
+        config.auth.api_keys.push(crate::config::ClientApiKeyConfig {
+            id: "dashboard".to_string(),
+            secret_hash: crate::auth::client_keys::hash_client_secret("pk-dashboard").unwrap(),
+            permissions: vec!["read".to_string()],
+        });
+        config.security.trusted_proxies = vec!["10.0.0.1".to_string()];
+        let auth_state = AuthState::new(Arc::new(config));
+        let routes = route_policy(auth_state)
+            .and(warp::path("api"))
+            .and(warp::path::tail())
+            .map(|_| "ok")
+            .recover(crate::api::handlers::handle_rejection);
+        let status = |remote: &'static str, forwarded_for: &'static str, key: &'static str| {
+            let routes = routes.clone();
+            async move {
+                warp::test::request()
+                    .path("/api/stats/summary")
+                    .remote_addr(remote.parse().unwrap())
+                    .header("x-forwarded-for", forwarded_for)
+                    .header("x-proxy-key", key)
+                    .reply(&routes)
+                    .await
+                    .status()
+                    .as_u16()
+            }
+        };
+
+        // 不受信任的对端轮换 X-Forwarded-For 也按对端地址计数
+        assert_eq!(status("203.0.113.9:40000", "198.51.100.1", "dashboard:guess-1").await, 401);
+        assert_eq!(status("203.0.113.9:40001", "198.51.100.2", "dashboard:guess-2").await, 401);
+        assert_eq!(status("203.0.113.9:40002", "198.51.100.3", "dashboard:pk-dashboard").await, 401);
+
+        // 受信任代理转发的请求按 X-Forwarded-For 中的客户端地址分别计数
+        assert_eq!(status("10.0.0.1:40000", "198.51.100.7", "dashboard:guess-3").await, 401);
+        assert_eq!(status("10.0.0.1:40001", "198.51.100.7", "dashboard:guess-4").await, 401);
+        assert_eq!(status("10.0.0.1:40002", "198.51.100.8", "dashboard:pk-dashboard").await, 200);
+    }
+
+    #[tokio::test]
+    async fn test_session_metrics_follow_login_refresh_and_logout() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let auth_state = AuthState::new(Arc::new(test_config())).with_metrics(metrics.clone());
+
+        let first = auth_state.create_session("admin").await;
+        let second = auth_state.create_session("admin").await;
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_session_active 2"));
+        assert!(output.contains("gemini_proxy_session_age_seconds_count 0"));
+
+        // 普通请求的会话校验不计入年龄分布，刷新时记录
+        assert!(auth_state.validate_session(&first).await);
+        assert!(auth_state.refresh_session(&first).await);
+        assert!(metrics.get_metrics().contains("gemini_proxy_session_age_seconds_count 1"));
+
+        // 登出结束会话，重复登出不会重复计数
+        auth_state.remove_session(&second).await;
+        auth_state.remove_session(&second).await;
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_session_active 1"));
+        assert!(output.contains("gemini_proxy_session_age_seconds_count 2"));
+    }
+
+    #[tokio::test]
+    async fn test_session_expires_at_max_lifetime_despite_activity() {
+        let mut config = test_config();
+        config.auth.session_max_lifetime_minutes = 60;
+        let metrics = Arc::new(MetricsCollector::new());
+        let auth_state = AuthState::new(Arc::new(config)).with_metrics(metrics.clone());
+
+        let session_id = auth_state.create_session("admin").await;
+        {
+            // 会话两小时前创建且刚刚活跃
+            let mut sessions = auth_state.active_sessions.write().await;
+            let session = sessions.get_mut(&session_id).unwrap();
+            session.created_at = Utc::now() - Duration::hours(2);
+            session.last_activity = Utc::now();
+        }
+
+        assert!(!auth_state.refresh_session(&session_id).await);
+        assert!(!auth_state.validate_session(&session_id).await);
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_session_active 0"));
+        assert!(output.contains("gemini_proxy_session_age_seconds_count 1"));
+
+        // 过期后登出不再重复计数
+        auth_state.remove_session(&session_id).await;
+        assert!(metrics.get_metrics().contains("gemini_proxy_session_active 0"));
+    }
+}
@@ -0,0 +1,118 @@
+// src/api/cache.rs
+//! 缓存管理
+//!
+//! `POST /cache/clear`（仅管理员）手动清空代理的缓存（目前为响应缓存），与错误恢复中的
+//! `ClearCache` 自愈操作清理同一组缓存。清空后的请求重新访问上游并重新填充缓存
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+use crate::api::auth::{admin_only, AuthState};
+use crate::api::config::ApiResponse;
+use crate::error::recovery::CacheClearer;
+
+/// 清空结果
+#[derive(Debug, Serialize)]
+pub struct CacheClearResponse {
+    /// 各缓存清理的条目数
+    pub cleared: HashMap<String, usize>,
+    pub total_cleared: usize,
+}
+
+/// 缓存管理状态
+#[derive(Clone, Default)]
+pub struct CacheState {
+    caches: Vec<Arc<dyn CacheClearer>>,
+}
+
+impl CacheState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加可清空的缓存
+    pub fn with_cache(mut self, cache: Arc<dyn CacheClearer>) -> Self {
+        self.caches.push(cache);
+        self
+    }
+
+    /// 清空所有缓存
+    pub fn clear(&self) -> CacheClearResponse {
+        let cleared: HashMap<String, usize> = self
+            .caches
+            .iter()
+            .map(|cache| (cache.cache_name().to_string(), cache.clear_cache()))
+            .collect();
+        let total_cleared = cleared.values().sum();
+        tracing::info!(total_cleared, "已手动清空缓存");
+        CacheClearResponse { cleared, total_cleared }
+    }
+}
+
+/// 缓存管理 API 路由
+pub fn cache_routes(
+    state: CacheState,
+    auth_state: AuthState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let cache_state = warp::any().map(move || state.clone());
+
+    // POST /cache/clear - 清空缓存
+    warp::path!("cache" / "clear")
+        .and(warp::post())
+        .and(admin_only(auth_state))
+        .and(cache_state)
+        .and_then(clear_cache_handler)
+}
+
+async fn clear_cache_handler(state: CacheState) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&ApiResponse::success(state.clear())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ProxyConfig, ResponseCacheConfig};
+    use crate::proxy::response_cache::{CachedResponse, ResponseCache};
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn test_clear_endpoint_flushes_response_cache() {
+        let cache = Arc::new(ResponseCache::new(&ResponseCacheConfig {
+            enabled: true,
+            ..ResponseCacheConfig::default()
+        }));
+        for key in ["a", "b"] {
+            cache.insert(
+                key.to_string(),
+                CachedResponse {
+                    content_type: None,
+                    content_encoding: None,
+                    body: Bytes::from_static(b"{}"),
+                },
+            );
+        }
+
+        let auth_state = AuthState::new(Arc::new(ProxyConfig::for_test(&["key1"])));
+        let session_id = auth_state.create_session("admin").await;
+        let token = auth_state.generate_token(&session_id).unwrap();
+        let routes = cache_routes(CacheState::new().with_cache(cache.clone()), auth_state)
+            .recover(crate::api::handlers::handle_rejection);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/cache/clear")
+            .header("authorization", format!("Bearer {}", token))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["data"]["cleared"]["response_cache"], 2);
+        assert_eq!(body["data"]["total_cleared"], 2);
+        assert_eq!(cache.len(), 0);
+
+        // 未认证的请求被拒绝
+        let response = warp::test::request().method("POST").path("/cache/clear").reply(&routes).await;
+        assert_eq!(response.status(), 401);
+    }
+}
@@ -0,0 +1,232 @@
+// src/api/client_tokens.rs
+//! 客户端令牌吊销
+//!
+//! `POST /tokens/revoke`（仅管理员）吊销代理请求使用的 JWT：令牌立即从校验缓存中移除，
+//! 即使仍在有效期内，之后出示该令牌的代理请求也一律拒绝。每次吊销记录审计日志，日志中只保存令牌摘要。
+//! `GET /tokens/stats`（仅管理员）返回校验缓存命中次数与已吊销的令牌数
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+use crate::api::auth::{admin_only, AuthState};
+use crate::api::config::ApiResponse;
+use crate::auth::AuthHandler;
+use crate::security::SharedAuditLog;
+
+/// 吊销请求
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokenRequest {
+    pub token: String,
+}
+
+/// 吊销结果
+#[derive(Debug, Serialize)]
+pub struct RevokeTokenResponse {
+    /// 令牌摘要（SHA-256 前 16 位十六进制），用于与审计日志对照
+    pub fingerprint: String,
+    /// 令牌此前是否未被吊销（重复吊销时为 false）
+    pub newly_revoked: bool,
+}
+
+/// 令牌校验统计
+#[derive(Debug, Serialize)]
+pub struct TokenStatsResponse {
+    pub verification_cache_hits: u64,
+    pub revoked_tokens: usize,
+}
+
+/// 客户端令牌吊销状态
+#[derive(Clone)]
+pub struct ClientTokenState {
+    auth_handler: Arc<AuthHandler>,
+    audit_log: Option<SharedAuditLog>,
+}
+
+impl ClientTokenState {
+    pub fn new(auth_handler: Arc<AuthHandler>) -> Self {
+        Self {
+            auth_handler,
+            audit_log: None,
+        }
+    }
+
+    /// 记录吊销审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 吊销令牌并记录审计日志
+    pub async fn revoke(&self, token: &str) -> RevokeTokenResponse {
+        let newly_revoked = self.auth_handler.revoke_token(token).await;
+        let fingerprint: String = openssl::sha::sha256(token.as_bytes())
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        if let Some(audit_log) = &self.audit_log {
+            if let Err(e) = audit_log
+                .lock()
+                .await
+                .log_config_change(
+                    None,
+                    Some("admin".to_string()),
+                    "auth.revoked_tokens",
+                    if newly_revoked { "active" } else { "revoked" },
+                    &format!("revoked: {}", fingerprint),
+                    "token_revoke",
+                )
+                .await
+            {
+                tracing::warn!("记录令牌吊销审计日志失败: {}", e);
+            }
+        }
+
+        tracing::info!(fingerprint = %fingerprint, newly_revoked, "客户端令牌已吊销");
+        RevokeTokenResponse { fingerprint, newly_revoked }
+    }
+
+    /// 令牌校验统计
+    pub async fn stats(&self) -> TokenStatsResponse {
+        TokenStatsResponse {
+            verification_cache_hits: self.auth_handler.verification_cache_hits(),
+            revoked_tokens: self.auth_handler.revoked_token_count().await,
+        }
+    }
+}
+
+/// 客户端令牌吊销 API 路由（仅管理员）
+pub fn client_token_routes(
+    state: ClientTokenState,
+    auth_state: AuthState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let token_state = warp::any().map(move || state.clone());
+
+    // POST /tokens/revoke - 吊销客户端令牌
+    let revoke = warp::path!("tokens" / "revoke")
+        .and(warp::post())
+        .and(admin_only(auth_state.clone()))
+        .and(warp::body::json())
+        .and(token_state.clone())
+        .and_then(revoke_token_handler);
+
+    // GET /tokens/stats - 令牌校验统计
+    let stats = warp::path!("tokens" / "stats")
+        .and(warp::get())
+        .and(admin_only(auth_state))
+        .and(token_state)
+        .and_then(token_stats_handler);
+
+    revoke.or(stats)
+}
+
+async fn revoke_token_handler(request: RevokeTokenRequest, state: ClientTokenState) -> Result<impl Reply, Rejection> {
+    if request.token.trim().is_empty() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()>::error("token 不能为空".to_string())),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    Ok(warp::reply::with_status(
+        warp::reply::json(&ApiResponse::success(state.revoke(request.token.trim()).await)),
+        StatusCode::OK,
+    ))
+}
+
+async fn token_stats_handler(state: ClientTokenState) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&ApiResponse::success(state.stats().await)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProxyConfig;
+    use crate::security::{AuditConfig, AuditEventType, AuditLogManager};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    const PROXY_JWT_SECRET: &str = "a-valid-secret-that-is-long-enough-1234";
+
+    fn issue_proxy_token(subject: &str) -> String {
+        let claims = serde_json::json!({
+            "sub": subject,
+            "exp": chrono::Utc::now().timestamp() + 3600,
+        });
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(PROXY_JWT_SECRET.as_ref()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_revoke_endpoint_rejects_cached_token() {
+        let client_ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let auth_handler = Arc::new(
+            AuthHandler::new(PROXY_JWT_SECRET.to_string(), 60).with_verification_cache(Duration::from_secs(30), 100),
+        );
+        let audit_log: SharedAuditLog = Arc::new(tokio::sync::Mutex::new(AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        })));
+        let revoked = issue_proxy_token("client-a");
+        let other = issue_proxy_token("client-b");
+        // 两个令牌都已通过校验并进入缓存
+        for token in [&revoked, &other] {
+            assert!(auth_handler.authorize_token(token, client_ip).await);
+        }
+        assert!(auth_handler.authorize_token(&revoked, client_ip).await);
+
+        let auth_state = AuthState::new(Arc::new(ProxyConfig::for_test(&["key1"])));
+        let session_id = auth_state.create_session("admin").await;
+        let admin_token = auth_state.generate_token(&session_id).unwrap();
+        let state = ClientTokenState::new(auth_handler.clone()).with_audit_log(audit_log.clone());
+        let routes = client_token_routes(state, auth_state).recover(crate::api::handlers::handle_rejection);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/tokens/revoke")
+            .header("authorization", format!("Bearer {}", admin_token))
+            .json(&serde_json::json!({ "token": revoked }))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["data"]["newly_revoked"], true);
+
+        // 缓存中的令牌被吊销后立即拒绝，其他令牌不受影响
+        assert!(!auth_handler.authorize_token(&revoked, client_ip).await);
+        assert!(auth_handler.authorize_token(&other, client_ip).await);
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/tokens/stats")
+            .header("authorization", format!("Bearer {}", admin_token))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["data"]["revoked_tokens"], 1);
+        assert_eq!(body["data"]["verification_cache_hits"], 2);
+
+        {
+            let audit_log = audit_log.lock().await;
+            let changes = audit_log.get_logs_by_type(AuditEventType::ConfigChange, 10);
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].resource, "auth.revoked_tokens");
+        }
+
+        // 非管理员请求被拒绝
+        let response = warp::test::request()
+            .method("POST")
+            .path("/tokens/revoke")
+            .json(&serde_json::json!({ "token": other }))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 401);
+        assert!(auth_handler.authorize_token(&other, client_ip).await);
+    }
+}
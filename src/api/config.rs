@@ -1,9 +1,16 @@
 // src/api/config.rs
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use warp::{Filter, Rejection, Reply};
-use crate::config::ProxyConfig;
+use crate::config::{ConfigValidator, GeminiConfig, LiveConfig, ProxyConfig};
+use crate::error::recovery::ConfigReloader;
+use crate::error::ValidationError;
+use crate::load_balancer::key_manager::ApiKey;
+use crate::load_balancer::{AdaptiveRateLimit, FailurePenalty, RateLimitCooldown, RegionPreference, UnifiedKeyManager};
+use crate::metrics::MetricsCollector;
+use crate::security::{SecurityAuditReport, SecurityConfigValidator};
 
 // API 响应结构
 #[derive(Debug, Serialize)]
@@ -31,11 +38,60 @@ impl<T> ApiResponse<T> {
     }
 }
 
+/// 配置校验（演练）报告
+#[derive(Debug, Serialize)]
+pub struct ConfigValidationReport {
+    /// 无验证错误且无严重安全问题时为 true
+    pub valid: bool,
+    /// 配置验证错误
+    pub errors: Vec<ValidationError>,
+    /// 安全审计报告（包含警告与安全评分）
+    pub security: SecurityAuditReport,
+}
+
+/// 校验候选配置，不修改运行中的配置，也不写入文件
+pub fn validate_candidate_config(config: &ProxyConfig) -> ConfigValidationReport {
+    let errors = ConfigValidator::collect_errors(config);
+    let security = SecurityConfigValidator::audit_security(config);
+
+    ConfigValidationReport {
+        valid: errors.is_empty() && !security.has_critical_issues(),
+        errors,
+        security,
+    }
+}
+
+/// 一次配置重新加载的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigReloadAttempt {
+    /// 触发来源（例如 `api`）
+    pub source: String,
+    pub success: bool,
+    pub timestamp: DateTime<Utc>,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+/// 最近的配置重新加载状态
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConfigReloadStatus {
+    /// 最近一次重新加载
+    pub last_reload: Option<ConfigReloadAttempt>,
+    /// 最近一次失败的重新加载（之后成功也保留）
+    pub last_error: Option<ConfigReloadAttempt>,
+}
+
 // 配置管理状态
 #[derive(Clone)]
 pub struct ConfigState {
     config: Arc<RwLock<ProxyConfig>>,
     config_path: String,
+    reload_status: Arc<RwLock<ConfigReloadStatus>>,
+    metrics: Option<Arc<MetricsCollector>>,
+    /// 重新加载时替换密钥集合的密钥管理器
+    key_manager: Option<Arc<UnifiedKeyManager>>,
+    /// 重新加载时整体替换的代理运行配置
+    live_gemini_config: Option<Arc<LiveConfig<GeminiConfig>>>,
 }
 
 impl ConfigState {
@@ -43,9 +99,125 @@ impl ConfigState {
         Self {
             config: Arc::new(RwLock::new(config)),
             config_path,
+            reload_status: Arc::new(RwLock::new(ConfigReloadStatus::default())),
+            metrics: None,
+            key_manager: None,
+            live_gemini_config: None,
+        }
+    }
+
+    /// 关联指标收集器，记录配置重新加载次数与结果
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// 关联密钥管理器，重新加载时替换运行中的密钥集合
+    pub fn with_key_manager(mut self, key_manager: Arc<UnifiedKeyManager>) -> Self {
+        self.key_manager = Some(key_manager);
+        self
+    }
+
+    /// 关联代理使用的运行配置，重新加载时整体替换
+    pub fn with_live_gemini_config(mut self, live_gemini_config: Arc<LiveConfig<GeminiConfig>>) -> Self {
+        self.live_gemini_config = Some(live_gemini_config);
+        self
+    }
+
+    /// 从配置文件重新加载配置，所有重新加载途径都应通过该方法以记录指标与结果
+    ///
+    /// 新配置需通过配置验证与安全检查才会生效；失败时返回原因，运行中的配置与密钥保持不变
+    pub async fn reload(&self, source: &str) -> Result<(), String> {
+        let result = self.load_validated();
+        if let Ok(new_config) = &result {
+            self.apply(new_config).await;
+        }
+
+        let attempt = ConfigReloadAttempt {
+            source: source.to_string(),
+            success: result.is_ok(),
+            timestamp: Utc::now(),
+            error: result.as_ref().err().cloned(),
+        };
+        {
+            let mut status = self.reload_status.write().await;
+            if !attempt.success {
+                status.last_error = Some(attempt.clone());
+            }
+            status.last_reload = Some(attempt);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_config_reload(result.is_ok());
+        }
+
+        match result {
+            Ok(_) => {
+                tracing::info!("配置已重新加载（来源: {}）", source);
+                Ok(())
+            }
+            Err(e) => {
+                tracing::warn!("重新加载配置失败（来源: {}）: {}", source, e);
+                Err(e)
+            }
         }
     }
 
+    /// 读取配置文件并执行配置验证与安全检查
+    fn load_validated(&self) -> Result<ProxyConfig, String> {
+        let config = ProxyConfig::from_file(&self.config_path).map_err(|e| e.to_string())?;
+        ConfigValidator::validate_proxy_config(&config).map_err(|e| format!("配置验证失败: {e}"))?;
+        SecurityConfigValidator::validate_security(&config).map_err(|e| format!("严重安全问题: {e}"))?;
+        Ok(config)
+    }
+
+    /// 让已验证的配置生效
+    ///
+    /// 未变化的密钥保留运行时状态，新增密钥从初始状态开始；被移除的密钥不再被选中，
+    /// 已分配给它的进行中请求照常完成
+    async fn apply(&self, new_config: &ProxyConfig) {
+        if let Some(key_manager) = &self.key_manager {
+            let gemini = &new_config.gemini;
+            // 先更新账号配额，替换密钥时按新配额分配各密钥限额
+            key_manager.set_account_quota(gemini.account_quota_per_minute).await;
+            key_manager.set_soft_limit_ratio(gemini.key_soft_limit_ratio).await;
+            key_manager.set_stickiness_window_ms(gemini.key_stickiness_window_ms).await;
+            key_manager.set_weight_ramp_window_ms(gemini.weight_change.effective_ramp_window_ms()).await;
+            key_manager.set_scheduling_mode((&gemini.scheduling).into()).await;
+            key_manager.set_failure_penalty(FailurePenalty::from_config(&gemini.failure_penalty)).await;
+            key_manager.set_adaptive_rate_limit(AdaptiveRateLimit::from_config(&gemini.adaptive_rate_limit)).await;
+            key_manager.set_rate_limit_cooldown(RateLimitCooldown::from_config(&gemini.key_cooldown)).await;
+            key_manager.set_region_preference(RegionPreference::from_config(&gemini.region_routing)).await;
+            key_manager.set_preferred_model_boost(gemini.preferred_model_boost).await;
+            // 替换密钥前更新权重上下限，新密钥按新范围钳制
+            key_manager.set_weight_bounds(gemini.weight_bounds).await;
+            if gemini.key_source.is_some() {
+                // 外部密钥来源从运行配置读取静态密钥，在下次刷新时与来源中的密钥合并
+                tracing::info!("配置了外部密钥来源，静态密钥在下次刷新时合并");
+            } else {
+                let old_ids: Vec<String> = key_manager.get_all_keys().await.into_iter().map(|k| k.id).collect();
+                let added = gemini.api_keys.iter().filter(|k| !old_ids.contains(&k.id)).count();
+                let removed = old_ids.iter().filter(|id| !gemini.api_keys.iter().any(|k| &k.id == *id)).count();
+                if key_manager.replace_keys(gemini.api_keys.iter().map(ApiKey::from).collect()).await {
+                    tracing::info!(added, removed, total = gemini.api_keys.len(), "重新加载后替换密钥集合");
+                }
+            }
+            // 手动停用状态以配置为准（停用/启用 API 会同步写回 `disabled_keys`）
+            key_manager.set_disabled_keys(&gemini.disabled_keys).await;
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.set_key_labels(&new_config.gemini.api_keys);
+        }
+        if let Some(live_gemini_config) = &self.live_gemini_config {
+            live_gemini_config.store(new_config.gemini.clone());
+        }
+        *self.config.write().await = new_config.clone();
+    }
+
+    /// 最近的配置重新加载状态
+    pub async fn reload_status(&self) -> ConfigReloadStatus {
+        self.reload_status.read().await.clone()
+    }
+
     pub async fn get_config(&self) -> ProxyConfig {
         self.config.read().await.clone()
     }
@@ -54,8 +226,12 @@ impl ConfigState {
         // 验证配置
         self.validate_config(&new_config)?;
         
-        // 保存到文件
-        let yaml_content = serde_yaml::to_string(&new_config)?;
+        // 保存到文件：只写回变化的字段，保留原文件中的环境变量占位符
+        let yaml_content = match tokio::fs::read_to_string(&self.config_path).await {
+            Ok(source) => new_config.to_source_yaml(&source)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => serde_yaml::to_string(&new_config)?,
+            Err(e) => return Err(e.into()),
+        };
         tokio::fs::write(&self.config_path, yaml_content).await?;
         
         // 更新内存中的配置
@@ -95,6 +271,14 @@ impl ConfigState {
     }
 }
 
+/// 自愈操作 `ReloadConfig` 走与管理接口相同的重新加载路径（验证、指标与结果记录）
+#[async_trait::async_trait]
+impl ConfigReloader for ConfigState {
+    async fn reload_config(&self) -> Result<(), String> {
+        self.reload("self_healing").await
+    }
+}
+
 // API 路由
 pub fn config_routes(
     state: ConfigState,
@@ -120,7 +304,19 @@ pub fn config_routes(
         .and(config_state.clone())
         .and_then(reload_config_handler);
 
-    get_config.or(put_config).or(reload_config)
+    // GET /config/last-reload - 最近的配置重新加载结果
+    let last_reload = warp::path!("config" / "last-reload")
+        .and(warp::get())
+        .and(config_state.clone())
+        .and_then(last_reload_handler);
+
+    // POST /config/validate - 校验候选配置（演练，不生效）
+    let validate_config = warp::path!("config" / "validate")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(validate_config_handler);
+
+    get_config.or(put_config).or(reload_config).or(last_reload).or(validate_config)
 }
 
 // 处理函数
@@ -147,9 +343,8 @@ async fn update_config_handler(
 }
 
 async fn reload_config_handler(state: ConfigState) -> Result<impl Reply, Rejection> {
-    match ProxyConfig::from_file(&state.config_path) {
-        Ok(new_config) => {
-            *state.config.write().await = new_config;
+    match state.reload("api").await {
+        Ok(()) => {
             let response = ApiResponse::success(());
             Ok(warp::reply::json(&response))
         }
@@ -158,4 +353,301 @@ async fn reload_config_handler(state: ConfigState) -> Result<impl Reply, Rejecti
             Ok(warp::reply::json(&response))
         }
     }
-}
\ No newline at end of file
+}
+
+async fn last_reload_handler(state: ConfigState) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&ApiResponse::success(state.reload_status().await)))
+}
+
+async fn validate_config_handler(candidate: ProxyConfig) -> Result<impl Reply, Rejection> {
+    let report = validate_candidate_config(&candidate);
+    Ok(warp::reply::json(&ApiResponse::success(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD_CONFIG: &str = r#"
+server:
+  host: "127.0.0.1"
+  port: 8080
+  workers: 4
+  max_connections: 1000
+  tls:
+    enabled: false
+    cert_path: ""
+    key_path: ""
+gemini:
+  api_keys:
+    - id: "primary"
+      key: "AIzaSyD-valid-test-key-1234567890"
+      weight: 100
+      max_requests_per_minute: 60
+  base_url: "https://generativelanguage.googleapis.com"
+  timeout_seconds: 30
+auth:
+  enabled: true
+  jwt_secret: "Xk9#mP2$vL7@qR4!nW8&zT1^bY6*cF3%"
+  rate_limit_per_minute: 60
+  admin_password: "Str0ng-Admin-Passw0rd"
+  token_expiry_hours: 8
+  refresh_token_enabled: true
+  session_timeout_minutes: 30
+  max_login_attempts: 5
+  lockout_duration_minutes: 15
+metrics:
+  enabled: true
+  prometheus_port: 9090
+"#;
+
+    fn good_config() -> ProxyConfig {
+        serde_yaml::from_str(GOOD_CONFIG).unwrap()
+    }
+
+    #[test]
+    fn test_validate_good_config_passes() {
+        let report = validate_candidate_config(&good_config());
+
+        assert!(report.valid);
+        assert!(report.errors.is_empty());
+        assert!(!report.security.has_critical_issues());
+    }
+
+    #[test]
+    fn test_validate_weak_secret_returns_issues() {
+        let good_score = validate_candidate_config(&good_config()).security.security_score;
+
+        let mut config = good_config();
+        config.auth.jwt_secret = "secret".to_string();
+        let report = validate_candidate_config(&config);
+
+        assert!(!report.valid);
+        assert!(report.errors.iter().any(|e| e.field == "auth.jwt_secret"));
+        assert!(report.security.has_critical_issues());
+        assert!(report.security.issues.iter().any(|i| i.affected_field == "auth.jwt_secret"));
+        assert!(report.security.security_score < good_score);
+    }
+
+    #[tokio::test]
+    async fn test_validate_endpoint_does_not_touch_running_config() {
+        let state = ConfigState::new(good_config(), "/nonexistent/proxy.yaml".to_string());
+        let routes = config_routes(state.clone());
+
+        let mut candidate = good_config();
+        candidate.auth.jwt_secret = "secret".to_string();
+        let response = warp::test::request()
+            .method("POST")
+            .path("/config/validate")
+            .json(&candidate)
+            .reply(&routes)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["data"]["valid"], false);
+        assert_eq!(state.get_config().await.auth.jwt_secret, good_config().auth.jwt_secret);
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_records_failure() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let state = ConfigState::new(good_config(), "/nonexistent/proxy.yaml".to_string())
+            .with_metrics(metrics.clone());
+        let routes = config_routes(state.clone());
+
+        let response = warp::test::request().method("POST").path("/config/reload").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["success"], false);
+
+        assert_eq!(metrics.get_config_reload_count("failure"), 1);
+        assert_eq!(metrics.get_config_reload_count("success"), 0);
+        assert!(metrics.get_metrics().contains("gemini_proxy_config_reload_total{result=\"failure\"} 1"));
+
+        let response = warp::test::request().method("GET").path("/config/last-reload").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["data"]["last_reload"]["success"], false);
+        assert_eq!(body["data"]["last_reload"]["source"], "api");
+        assert!(!body["data"]["last_error"]["error"].as_str().unwrap().is_empty());
+        // 运行中的配置保持不变
+        assert_eq!(state.get_config().await.auth.jwt_secret, good_config().auth.jwt_secret);
+    }
+
+    /// 在基础配置上追加一个密钥
+    fn with_extra_key(mut config: ProxyConfig, id: &str, key: &str) -> ProxyConfig {
+        let mut extra = config.gemini.api_keys[0].clone();
+        extra.id = id.to_string();
+        extra.key = key.to_string();
+        config.gemini.api_keys.push(extra);
+        config
+    }
+
+    /// 以两个密钥的配置启动，关联密钥管理器与运行配置
+    fn live_state(path: &std::path::Path) -> (ConfigState, Arc<UnifiedKeyManager>, Arc<LiveConfig<GeminiConfig>>) {
+        let initial = with_extra_key(good_config(), "secondary", "AIzaSyD-valid-test-key-0987654321");
+        let key_manager = Arc::new(UnifiedKeyManager::new(initial.gemini.api_keys.iter().map(ApiKey::from).collect()));
+        let live = Arc::new(LiveConfig::new(initial.gemini.clone()));
+        let state = ConfigState::new(initial, path.to_string_lossy().to_string())
+            .with_key_manager(key_manager.clone())
+            .with_live_gemini_config(live.clone());
+        (state, key_manager, live)
+    }
+
+    fn key_ids(keys: &[crate::load_balancer::UnifiedApiKey]) -> Vec<&str> {
+        let mut ids: Vec<&str> = keys.iter().map(|k| k.id.as_str()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_keys_and_preserves_runtime_state() {
+        let path = std::env::temp_dir().join(format!("gemini-proxy-reload-{}.yaml", uuid::Uuid::new_v4()));
+        let (state, key_manager, live) = live_state(&path);
+        for _ in 0..4 {
+            key_manager.get_next_key().await.unwrap();
+        }
+        key_manager.mark_key_failed("primary").await;
+        let primary_before = key_manager.get_key_states().await.into_iter().find(|k| k.id == "primary").unwrap();
+
+        // 保留 primary，移除 secondary，新增 tertiary
+        let mut updated = with_extra_key(good_config(), "tertiary", "AIzaSyD-valid-test-key-1122334455");
+        updated.gemini.timeout_seconds = 45;
+        std::fs::write(&path, serde_yaml::to_string(&updated).unwrap()).unwrap();
+
+        state.reload("api").await.unwrap();
+        let keys = key_manager.get_key_states().await;
+        assert_eq!(key_ids(&keys), vec!["primary", "tertiary"]);
+
+        let primary = keys.iter().find(|k| k.id == "primary").unwrap();
+        assert_eq!(primary.runtime_state.failure_count, 1);
+        assert_eq!(primary.runtime_state.current_requests, primary_before.runtime_state.current_requests);
+        assert!(primary.runtime_state.current_requests > 0);
+        let tertiary = keys.iter().find(|k| k.id == "tertiary").unwrap();
+        assert_eq!(tertiary.runtime_state.failure_count, 0);
+        assert_eq!(tertiary.runtime_state.current_requests, 0);
+
+        assert_eq!(live.load().timeout_seconds, 45);
+        assert_eq!(state.get_config().await.gemini.timeout_seconds, 45);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_reload_rejected_by_validation_keeps_running_config() {
+        let path = std::env::temp_dir().join(format!("gemini-proxy-reload-{}.yaml", uuid::Uuid::new_v4()));
+        let (state, key_manager, live) = live_state(&path);
+
+        let mut weak = with_extra_key(good_config(), "tertiary", "AIzaSyD-valid-test-key-1122334455");
+        weak.auth.jwt_secret = "secret".to_string();
+        weak.gemini.timeout_seconds = 45;
+        std::fs::write(&path, serde_yaml::to_string(&weak).unwrap()).unwrap();
+
+        let error = state.reload("api").await.unwrap_err();
+        assert!(!error.is_empty());
+        assert_eq!(key_ids(&key_manager.get_key_states().await), vec!["primary", "secondary"]);
+        assert_eq!(live.load().timeout_seconds, 30);
+        assert_eq!(state.get_config().await.auth.jwt_secret, good_config().auth.jwt_secret);
+        assert!(!state.reload_status().await.last_error.unwrap().success);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_allowed_models() {
+        let path = std::env::temp_dir().join(format!("gemini-proxy-reload-{}.yaml", uuid::Uuid::new_v4()));
+        let (state, key_manager, _live) = live_state(&path);
+
+        // 只修改 secondary 允许服务的模型
+        let mut restricted = with_extra_key(good_config(), "secondary", "AIzaSyD-valid-test-key-0987654321");
+        restricted.gemini.api_keys[1].allowed_models = vec!["gemini-1.5-pro".to_string()];
+        std::fs::write(&path, serde_yaml::to_string(&restricted).unwrap()).unwrap();
+        state.reload("api").await.unwrap();
+
+        let secondary = key_manager.get_key_states().await.into_iter().find(|k| k.id == "secondary").unwrap();
+        assert_eq!(secondary.allowed_models, vec!["gemini-1.5-pro"]);
+        for _ in 0..4 {
+            let key = key_manager.get_next_key_for_model(Some("gemini-1.5-flash")).await.unwrap();
+            assert_eq!(key.id, "primary");
+        }
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_disabled_keys_and_cooldown() {
+        let path = std::env::temp_dir().join(format!("gemini-proxy-reload-{}.yaml", uuid::Uuid::new_v4()));
+        let (state, key_manager, _live) = live_state(&path);
+        assert!(key_manager.start_cooldown("primary", None).await.is_none());
+
+        let mut updated = with_extra_key(good_config(), "secondary", "AIzaSyD-valid-test-key-0987654321");
+        updated.gemini.disabled_keys = vec!["secondary".to_string()];
+        updated.gemini.key_cooldown.enabled = true;
+        std::fs::write(&path, serde_yaml::to_string(&updated).unwrap()).unwrap();
+        state.reload("api").await.unwrap();
+
+        for _ in 0..4 {
+            assert_eq!(key_manager.get_next_key().await.unwrap().id, "primary");
+        }
+        assert!(key_manager.start_cooldown("primary", None).await.is_some());
+
+        // 关闭冷却后结束已有的冷却期，移出 disabled_keys 的密钥恢复选择
+        updated.gemini.disabled_keys.clear();
+        updated.gemini.key_cooldown.enabled = false;
+        std::fs::write(&path, serde_yaml::to_string(&updated).unwrap()).unwrap();
+        state.reload("api").await.unwrap();
+
+        assert!(key_manager.cooldowns().await.is_empty());
+        let keys = key_manager.get_key_states().await;
+        assert!(keys.iter().all(|k| k.is_available()));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_key_stickiness_window() {
+        let path = std::env::temp_dir().join(format!("gemini-proxy-reload-{}.yaml", uuid::Uuid::new_v4()));
+        let (state, key_manager, _live) = live_state(&path);
+        let first = key_manager.get_next_key_for_client("client-a", None).await.unwrap();
+        assert_ne!(key_manager.get_next_key_for_client("client-a", None).await.unwrap().id, first.id);
+
+        // 重新加载后开启粘性窗口，同一客户端复用同一密钥
+        let mut sticky = with_extra_key(good_config(), "secondary", "AIzaSyD-valid-test-key-0987654321");
+        sticky.gemini.key_stickiness_window_ms = 60_000;
+        std::fs::write(&path, serde_yaml::to_string(&sticky).unwrap()).unwrap();
+        state.reload("api").await.unwrap();
+
+        let bound = key_manager.get_next_key_for_client("client-a", None).await.unwrap();
+        for _ in 0..5 {
+            assert_eq!(key_manager.get_next_key_for_client("client-a", None).await.unwrap().id, bound.id);
+        }
+
+        // 再次重新加载关闭粘性窗口，恢复轮询
+        sticky.gemini.key_stickiness_window_ms = 0;
+        std::fs::write(&path, serde_yaml::to_string(&sticky).unwrap()).unwrap();
+        state.reload("api").await.unwrap();
+
+        let next = key_manager.get_next_key_for_client("client-a", None).await.unwrap();
+        assert_ne!(key_manager.get_next_key_for_client("client-a", None).await.unwrap().id, next.id);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_update_writes_back_only_changed_fields() {
+        let path = std::env::temp_dir().join(format!("gemini-proxy-update-{}.yaml", uuid::Uuid::new_v4()));
+        let source = GOOD_CONFIG
+            .replace("\"Xk9#mP2$vL7@qR4!nW8&zT1^bY6*cF3%\"", "\"${GEMINI_PROXY_TEST_UNSET_SECRET:-Xk9#mP2$vL7@qR4!nW8&zT1^bY6*cF3%}\"")
+            .replace("\"AIzaSyD-valid-test-key-1234567890\"", "\"${GEMINI_PROXY_TEST_UNSET_KEY:-AIzaSyD-valid-test-key-1234567890}\"");
+        std::fs::write(&path, &source).unwrap();
+        let config = ProxyConfig::parse_file(&path.to_string_lossy()).unwrap();
+        assert_eq!(config.auth.jwt_secret, good_config().auth.jwt_secret);
+        let state = ConfigState::new(config.clone(), path.to_string_lossy().to_string());
+
+        let mut updated = config;
+        updated.gemini.api_keys[0].weight = 42;
+        state.update_config(updated).await.unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("${GEMINI_PROXY_TEST_UNSET_SECRET:-"));
+        assert!(written.contains("${GEMINI_PROXY_TEST_UNSET_KEY:-"));
+        let reloaded = ProxyConfig::parse_file(&path.to_string_lossy()).unwrap();
+        assert_eq!(reloaded.gemini.api_keys[0].weight, 42);
+        assert_eq!(reloaded.auth.jwt_secret, good_config().auth.jwt_secret);
+        let _ = std::fs::remove_file(path);
+    }
+}
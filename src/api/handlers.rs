@@ -1,6 +1,8 @@
 // src/api/handlers.rs
 use warp::{http::StatusCode, Rejection, Reply};
 use serde_json::json;
+use crate::error::GeminiProxyError;
+use crate::proxy::circuit_guard::error_body;
 
 // CORS 处理
 pub fn cors() -> warp::cors::Builder {
@@ -10,42 +12,73 @@ pub fn cors() -> warp::cors::Builder {
         .allow_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
 }
 
-// 错误处理
+// 错误处理：所有拒绝都转换为与代理错误一致的结构化 JSON（`error` 字段，含 error_id）
 pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, std::convert::Infallible> {
-    let code;
-    let message;
+    let (code, error) = rejection_error(&err);
+
+    let mut body = error_body(code.as_u16(), &error);
+    // 保留旧版字段，兼容按 success/message 判断的客户端
+    body["success"] = json!(false);
+    body["message"] = json!(error.to_string());
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&body),
+        code,
+    ))
+}
+
+/// 将 warp 拒绝映射为状态码与对应的代理错误
+fn rejection_error(err: &Rejection) -> (StatusCode, GeminiProxyError) {
+    use crate::api::auth::AuthError;
 
     if err.is_not_found() {
-        code = StatusCode::NOT_FOUND;
-        message = "Not Found";
-    } else if err.find::<warp::filters::body::BodyDeserializeError>().is_some() {
-        code = StatusCode::BAD_REQUEST;
-        message = "Invalid JSON body";
+        (StatusCode::NOT_FOUND, GeminiProxyError::not_found("route", "unknown"))
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        (
+            StatusCode::BAD_REQUEST,
+            GeminiProxyError::validation(format!("Invalid JSON body: {}", e), vec![]),
+        )
+    } else if let Some(e) = err.find::<warp::reject::InvalidQuery>() {
+        (StatusCode::BAD_REQUEST, GeminiProxyError::validation(e.to_string(), vec![]))
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            GeminiProxyError::validation("Payload too large", vec![]),
+        )
+    } else if err.find::<warp::reject::UnsupportedMediaType>().is_some() {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            GeminiProxyError::validation("Unsupported media type", vec![]),
+        )
     } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
-        code = StatusCode::METHOD_NOT_ALLOWED;
-        message = "Method Not Allowed";
-    } else if let Some(auth_error) = err.find::<crate::api::auth::AuthError>() {
-        code = StatusCode::UNAUTHORIZED;
-        message = match auth_error {
-            crate::api::auth::AuthError::InvalidToken => "Invalid JWT token",
-            crate::api::auth::AuthError::MissingToken => "Missing Authorization header",
-            crate::api::auth::AuthError::SessionExpired => "Session expired",
-        };
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            GeminiProxyError::validation("Method Not Allowed", vec![]),
+        )
+    } else if let Some(auth_error) = err.find::<AuthError>() {
+        match auth_error {
+            AuthError::Forbidden => (
+                StatusCode::FORBIDDEN,
+                GeminiProxyError::permission("Admin role required", vec!["admin".to_string()]),
+            ),
+            AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, GeminiProxyError::authentication("Invalid JWT token")),
+            AuthError::InvalidProxyKey => (
+                StatusCode::UNAUTHORIZED,
+                GeminiProxyError::authentication("Invalid proxy API key"),
+            ),
+            AuthError::MissingToken => (
+                StatusCode::UNAUTHORIZED,
+                GeminiProxyError::authentication("Missing Authorization header"),
+            ),
+            AuthError::SessionExpired => (StatusCode::UNAUTHORIZED, GeminiProxyError::authentication("Session expired")),
+        }
     } else {
         tracing::error!("Unhandled rejection: {:?}", err);
-        code = StatusCode::INTERNAL_SERVER_ERROR;
-        message = "Internal Server Error";
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            GeminiProxyError::internal("Internal Server Error"),
+        )
     }
-
-    let json = json!({
-        "success": false,
-        "message": message,
-    });
-
-    Ok(warp::reply::with_status(
-        warp::reply::json(&json),
-        code,
-    ))
 }
 
 // 日志中间件
@@ -60,4 +93,53 @@ pub fn with_logging() -> warp::log::Log<impl Fn(warp::log::Info) + Copy> {
             "API request"
         );
     })
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::Filter;
+
+    #[derive(serde::Deserialize)]
+    struct Payload {
+        name: String,
+    }
+
+    fn routes() -> impl Filter<Extract = impl Reply, Error = std::convert::Infallible> + Clone {
+        warp::path!("items")
+            .and(warp::post())
+            .and(warp::body::json())
+            .map(|payload: Payload| payload.name)
+            .recover(handle_rejection)
+    }
+
+    fn assert_envelope(body: &[u8], code: u16, error_type: &str) {
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["success"], false);
+        assert_eq!(body["error"]["code"], code);
+        assert_eq!(body["error"]["type"], error_type);
+        assert!(!body["error"]["error_id"].as_str().unwrap().is_empty());
+        assert!(body["error"]["message"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_body_returns_envelope() {
+        let response = warp::test::request()
+            .method("POST")
+            .path("/items")
+            .header("content-type", "application/json")
+            .body("{not json")
+            .reply(&routes())
+            .await;
+
+        assert_eq!(response.status(), 400);
+        assert_envelope(response.body(), 400, "Validation");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_route_returns_envelope() {
+        let response = warp::test::request().method("GET").path("/missing").reply(&routes()).await;
+
+        assert_eq!(response.status(), 404);
+        assert_envelope(response.body(), 404, "NotFound");
+    }
+}
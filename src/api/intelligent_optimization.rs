@@ -62,6 +62,7 @@ pub struct OptimizerConfigRequest {
     pub throughput_weight: Option<f64>,
     pub max_adjustment_percent: Option<f64>,
     pub sensitivity: Option<f64>,
+    pub ewma_alpha: Option<f64>,
 }
 
 /// 通用API响应结构
@@ -150,11 +151,12 @@ async fn apply_optimization_handler(
                 .generate_recommendations(&current_weights, request.strategy)
                 .await;
             
-            // 应用到配置
+            // 应用到配置（钳制到各密钥的权重上下限内）
+            let default_bounds = config.gemini.weight_bounds;
             for recommendation in &recommendations.recommendations {
                 for api_key in &mut config.gemini.api_keys {
                     if api_key.id == recommendation.key_id {
-                        api_key.weight = recommendation.recommended_weight;
+                        api_key.weight = api_key.weight_bounds(&default_bounds).clamp(recommendation.recommended_weight);
                         break;
                     }
                 }
@@ -247,6 +249,9 @@ async fn update_optimizer_config_handler(
     if let Some(sensitivity) = request.sensitivity {
         new_config.sensitivity = sensitivity;
     }
+    if let Some(ewma_alpha) = request.ewma_alpha {
+        new_config.ewma_alpha = Some(ewma_alpha);
+    }
     
     drop(current_optimizer); // 释放读锁
     
@@ -0,0 +1,520 @@
+// src/api/key_control.rs
+//! 密钥启用/停用
+//!
+//! `POST /keys/{id}/disable` 与 `POST /keys/{id}/enable`（仅管理员）用于维护时手动下线密钥。
+//! 与失败后的自动停用不同，手动停用不会自动恢复；停用状态写入配置的 `gemini.disabled_keys`
+//! 以便重启后保持，每次变更记录审计日志
+//!
+//! `POST /keys/drain?tenant=X`（或 `?tag=Y`）与 `POST /keys/undrain` 按租户或标签批量排空/恢复一组密钥，
+//! 在同一把写锁内原子完成，并作为一条批量审计记录写入。排空状态仅保存在运行时
+//!
+//! `POST /keys` 新增密钥并写入配置；`shadow_warmup_seconds` 大于 0 时密钥先进入影子预热，
+//! 预热期内只接收镜像流量、不参与真实选择，镜像请求失败会重新开始预热
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+use crate::api::auth::{admin_only, AuthState};
+use crate::api::config::{ApiResponse, ConfigState};
+use crate::config::ApiKeyConfig;
+use crate::load_balancer::key_manager::ApiKey;
+use crate::load_balancer::{KeySelector, UnifiedKeyManager};
+use crate::security::SharedAuditLog;
+
+/// 启用/停用结果
+#[derive(Debug, Serialize)]
+pub struct KeyToggleResponse {
+    pub key_id: String,
+    pub enabled: bool,
+    /// 状态是否发生变化（重复停用/启用时为 false）
+    pub changed: bool,
+}
+
+/// 批量排空选择参数，`tenant` 与 `tag` 必须且只能指定一个
+#[derive(Debug, Deserialize)]
+pub struct DrainQuery {
+    pub tenant: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl DrainQuery {
+    fn selector(self) -> Result<KeySelector, String> {
+        match (self.tenant, self.tag) {
+            (Some(tenant), None) if !tenant.is_empty() => Ok(KeySelector::Tenant(tenant)),
+            (None, Some(tag)) if !tag.is_empty() => Ok(KeySelector::Tag(tag)),
+            _ => Err("必须且只能指定 tenant 或 tag 之一".to_string()),
+        }
+    }
+}
+
+/// 批量排空/恢复结果
+#[derive(Debug, Serialize)]
+pub struct KeyDrainResponse {
+    pub drained: bool,
+    /// 状态发生变化的密钥 ID
+    pub key_ids: Vec<String>,
+}
+
+/// 新增密钥请求
+#[derive(Debug, Deserialize)]
+pub struct AddKeyRequest {
+    #[serde(flatten)]
+    pub key: ApiKeyConfig,
+    /// 影子预热时长（秒），0 表示立即参与选择
+    #[serde(default)]
+    pub shadow_warmup_seconds: u64,
+}
+
+impl AddKeyRequest {
+    fn validate(&self) -> Result<(), String> {
+        if self.key.id.is_empty() || self.key.key.is_empty() {
+            return Err("密钥 id 与 key 不能为空".to_string());
+        }
+        if self.key.weight == 0 || self.key.max_requests_per_minute == 0 {
+            return Err("密钥权重与每分钟最大请求数不能为0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 新增密钥结果
+#[derive(Debug, Serialize)]
+pub struct KeyAddResponse {
+    pub key_id: String,
+    /// 影子预热时长（秒），0 表示已立即参与选择
+    pub shadow_warmup_seconds: u64,
+}
+
+/// 密钥启用/停用状态
+#[derive(Clone)]
+pub struct KeyControlState {
+    key_manager: Arc<UnifiedKeyManager>,
+    config_state: ConfigState,
+    audit_log: Option<SharedAuditLog>,
+}
+
+impl KeyControlState {
+    pub fn new(key_manager: Arc<UnifiedKeyManager>, config_state: ConfigState) -> Self {
+        Self {
+            key_manager,
+            config_state,
+            audit_log: None,
+        }
+    }
+
+    /// 记录启用/停用审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 启用或停用密钥：更新运行时状态、持久化到配置并记录审计日志
+    pub async fn set_enabled(&self, key_id: &str, enabled: bool) -> Result<KeyToggleResponse, String> {
+        let was_enabled = self.key_manager.set_key_enabled(key_id, enabled).await?;
+
+        if let Err(e) = persist_disabled_key(&self.config_state, key_id, enabled).await {
+            tracing::warn!("Failed to persist disabled state of key {}: {}", key_id, e);
+        }
+
+        if let Some(audit_log) = &self.audit_log {
+            let state = |enabled: bool| if enabled { "enabled" } else { "disabled" };
+            if let Err(e) = audit_log
+                .lock()
+                .await
+                .log_config_change(
+                    None,
+                    Some("admin".to_string()),
+                    &format!("gemini.api_keys.{}", key_id),
+                    state(was_enabled),
+                    state(enabled),
+                    if enabled { "key_enable" } else { "key_disable" },
+                )
+                .await
+            {
+                tracing::warn!("记录密钥启用/停用审计日志失败: {}", e);
+            }
+        }
+
+        tracing::info!(key_id, enabled, "密钥已{}", if enabled { "启用" } else { "停用" });
+        Ok(KeyToggleResponse {
+            key_id: key_id.to_string(),
+            enabled,
+            changed: was_enabled != enabled,
+        })
+    }
+
+    /// 新增密钥：加入运行时（可选影子预热）、写入配置并记录审计日志
+    pub async fn add_key(&self, request: AddKeyRequest) -> Result<KeyAddResponse, String> {
+        let warmup = request.shadow_warmup_seconds;
+        let key_id = request.key.id.clone();
+        self.key_manager
+            .add_key(ApiKey::from(&request.key), Some(Duration::from_secs(warmup)))
+            .await?;
+
+        let mut config = self.config_state.get_config().await;
+        if !config.gemini.api_keys.iter().any(|k| k.id == key_id) {
+            config.gemini.api_keys.push(request.key);
+            if let Err(e) = self.config_state.update_config(config).await {
+                tracing::warn!("Failed to persist added key {}: {}", key_id, e);
+            }
+        }
+
+        if let Some(audit_log) = &self.audit_log {
+            let new_state = if warmup > 0 {
+                format!("added (shadow warmup {}s)", warmup)
+            } else {
+                "added".to_string()
+            };
+            if let Err(e) = audit_log
+                .lock()
+                .await
+                .log_config_change(
+                    None,
+                    Some("admin".to_string()),
+                    &format!("gemini.api_keys.{}", key_id),
+                    "absent",
+                    &new_state,
+                    "key_add",
+                )
+                .await
+            {
+                tracing::warn!("记录密钥新增审计日志失败: {}", e);
+            }
+        }
+
+        tracing::info!(key_id = %key_id, shadow_warmup_seconds = warmup, "密钥已新增");
+        Ok(KeyAddResponse {
+            key_id,
+            shadow_warmup_seconds: warmup,
+        })
+    }
+
+    /// 按租户或标签批量排空/恢复密钥，并记录一条批量审计日志
+    pub async fn set_drained(&self, selector: &KeySelector, drained: bool) -> KeyDrainResponse {
+        let key_ids = self.key_manager.set_keys_drained(selector, drained).await;
+
+        if let Some(audit_log) = &self.audit_log {
+            if !key_ids.is_empty() {
+                let (old_state, new_state) = if drained { ("active", "drained") } else { ("drained", "active") };
+                let section = match selector {
+                    KeySelector::Tenant(tenant) => format!("gemini.api_keys[tenant={}]", tenant),
+                    KeySelector::Tag(tag) => format!("gemini.api_keys[tag={}]", tag),
+                };
+                let ids = key_ids.join(",");
+                if let Err(e) = audit_log
+                    .lock()
+                    .await
+                    .log_config_change(
+                        None,
+                        Some("admin".to_string()),
+                        &section,
+                        &format!("{}: {}", old_state, ids),
+                        &format!("{}: {}", new_state, ids),
+                        if drained { "keys_drain" } else { "keys_undrain" },
+                    )
+                    .await
+                {
+                    tracing::warn!("记录密钥批量排空审计日志失败: {}", e);
+                }
+            }
+        }
+
+        tracing::info!(?selector, drained, count = key_ids.len(), "密钥已批量{}", if drained { "排空" } else { "恢复" });
+        KeyDrainResponse { drained, key_ids }
+    }
+}
+
+/// 更新配置中的 `gemini.disabled_keys`
+async fn persist_disabled_key(
+    config_state: &ConfigState,
+    key_id: &str,
+    enabled: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut config = config_state.get_config().await;
+    let disabled_keys = &mut config.gemini.disabled_keys;
+    let listed = disabled_keys.iter().any(|id| id == key_id);
+    match (enabled, listed) {
+        (true, true) => disabled_keys.retain(|id| id != key_id),
+        (false, false) => disabled_keys.push(key_id.to_string()),
+        _ => return Ok(()),
+    }
+    config_state.update_config(config).await
+}
+
+/// 密钥启用/停用 API 路由（仅管理员）
+pub fn key_control_routes(
+    state: KeyControlState,
+    auth_state: AuthState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let control_state = warp::any().map(move || state.clone());
+
+    // POST /keys - 新增密钥（可选影子预热）
+    let add = warp::path!("keys")
+        .and(warp::post())
+        .and(admin_only(auth_state.clone()))
+        .and(warp::body::json())
+        .and(control_state.clone())
+        .and_then(add_key_handler);
+
+    // POST /keys/drain?tenant=X|tag=Y - 批量排空密钥
+    let drain = warp::path!("keys" / "drain")
+        .and(warp::post())
+        .and(admin_only(auth_state.clone()))
+        .and(warp::query::<DrainQuery>())
+        .and(control_state.clone())
+        .and_then(|query: DrainQuery, state: KeyControlState| drain_keys_handler(query, true, state));
+
+    // POST /keys/undrain?tenant=X|tag=Y - 批量恢复密钥
+    let undrain = warp::path!("keys" / "undrain")
+        .and(warp::post())
+        .and(admin_only(auth_state.clone()))
+        .and(warp::query::<DrainQuery>())
+        .and(control_state.clone())
+        .and_then(|query: DrainQuery, state: KeyControlState| drain_keys_handler(query, false, state));
+
+    // POST /keys/{id}/disable - 停用密钥
+    let disable = warp::path!("keys" / String / "disable")
+        .and(warp::post())
+        .and(admin_only(auth_state.clone()))
+        .and(control_state.clone())
+        .and_then(|key_id: String, state: KeyControlState| toggle_key_handler(key_id, false, state));
+
+    // POST /keys/{id}/enable - 启用密钥
+    let enable = warp::path!("keys" / String / "enable")
+        .and(warp::post())
+        .and(admin_only(auth_state))
+        .and(control_state)
+        .and_then(|key_id: String, state: KeyControlState| toggle_key_handler(key_id, true, state));
+
+    add.or(drain).or(undrain).or(disable).or(enable)
+}
+
+async fn add_key_handler(request: AddKeyRequest, state: KeyControlState) -> Result<impl Reply, Rejection> {
+    if let Err(e) = request.validate() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()>::error(e)),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    match state.add_key(request).await {
+        Ok(response) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::success(response)),
+            StatusCode::CREATED,
+        )),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()>::error(e)),
+            StatusCode::CONFLICT,
+        )),
+    }
+}
+
+async fn drain_keys_handler(query: DrainQuery, drained: bool, state: KeyControlState) -> Result<impl Reply, Rejection> {
+    match query.selector() {
+        Ok(selector) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::success(state.set_drained(&selector, drained).await)),
+            StatusCode::OK,
+        )),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()>::error(e)),
+            StatusCode::BAD_REQUEST,
+        )),
+    }
+}
+
+async fn toggle_key_handler(key_id: String, enabled: bool, state: KeyControlState) -> Result<impl Reply, Rejection> {
+    match state.set_enabled(&key_id, enabled).await {
+        Ok(response) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::success(response)),
+            StatusCode::OK,
+        )),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()>::error(e)),
+            StatusCode::NOT_FOUND,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::key_health::KeyHealthState;
+    use crate::config::ProxyConfig;
+    use crate::load_balancer::key_manager::ApiKey;
+    use crate::security::{AuditConfig, AuditEventType, AuditLogManager};
+
+    fn post(path: &str, token: &str) -> warp::test::RequestBuilder {
+        warp::test::request()
+            .method("POST")
+            .path(path)
+            .header("authorization", format!("Bearer {}", token))
+    }
+
+    #[tokio::test]
+    async fn test_disable_excludes_key_and_enable_restores_it() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![
+            ApiKey::for_test("key1"),
+            ApiKey::for_test("key2"),
+        ]));
+        let audit_log: SharedAuditLog = Arc::new(tokio::sync::Mutex::new(AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        })));
+        let state = KeyControlState::new(
+            key_manager.clone(),
+            ConfigState::new(ProxyConfig::for_test(&["key1", "key2"]), "/nonexistent/proxy.yaml".to_string()),
+        )
+        .with_audit_log(audit_log.clone());
+
+        let auth_state = AuthState::new(Arc::new(ProxyConfig::for_test(&["key1", "key2"])));
+        let session_id = auth_state.create_session("admin").await;
+        let token = auth_state.generate_token(&session_id).unwrap();
+        let routes = key_control_routes(state, auth_state).recover(crate::api::handlers::handle_rejection);
+        let key_health = KeyHealthState::new(key_manager.clone());
+
+        assert_eq!(post("/keys/key1/disable", &token).reply(&routes).await.status(), 200);
+        for _ in 0..10 {
+            assert_eq!(key_manager.get_next_key().await.unwrap().id, "key2");
+        }
+        assert_eq!(key_health.ready_key_count().await, 1);
+        let report = key_health.build_report().await;
+        assert_eq!(report.healthy_keys, 1);
+        assert_eq!(report.unhealthy_keys, 0);
+        assert_eq!(report.disabled_keys, 1);
+
+        assert_eq!(post("/keys/key1/enable", &token).reply(&routes).await.status(), 200);
+        assert_eq!(key_health.ready_key_count().await, 2);
+        assert_eq!(key_health.build_report().await.disabled_keys, 0);
+
+        assert_eq!(post("/keys/missing/disable", &token).reply(&routes).await.status(), 404);
+
+        let audit_log = audit_log.lock().await;
+        let changes = audit_log.get_logs_by_type(AuditEventType::ConfigChange, 10);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|entry| entry.resource == "gemini.api_keys.key1"));
+    }
+
+    #[tokio::test]
+    async fn test_drain_by_tag_excludes_tagged_keys_and_undrain_restores_them() {
+        let tagged = |id: &str, tags: &[&str]| ApiKey {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..ApiKey::for_test(id)
+        };
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![
+            tagged("key1", &["pool-a"]),
+            tagged("key2", &["pool-a", "eu"]),
+            tagged("key3", &["pool-b"]),
+        ]));
+        let audit_log: SharedAuditLog = Arc::new(tokio::sync::Mutex::new(AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        })));
+        let state = KeyControlState::new(
+            key_manager.clone(),
+            ConfigState::new(ProxyConfig::for_test(&["key1", "key2"]), "/nonexistent/proxy.yaml".to_string()),
+        )
+        .with_audit_log(audit_log.clone());
+
+        let auth_state = AuthState::new(Arc::new(ProxyConfig::for_test(&["key1", "key2"])));
+        let session_id = auth_state.create_session("admin").await;
+        let token = auth_state.generate_token(&session_id).unwrap();
+        let routes = key_control_routes(state, auth_state).recover(crate::api::handlers::handle_rejection);
+
+        assert_eq!(post("/keys/drain?tag=pool-a", &token).reply(&routes).await.status(), 200);
+        for _ in 0..10 {
+            assert_eq!(key_manager.get_next_key().await.unwrap().id, "key3");
+        }
+
+        assert_eq!(post("/keys/undrain?tag=pool-a", &token).reply(&routes).await.status(), 200);
+        let mut selected = std::collections::HashSet::new();
+        for _ in 0..30 {
+            selected.insert(key_manager.get_next_key().await.unwrap().id);
+        }
+        assert_eq!(selected.len(), 3);
+
+        assert_eq!(post("/keys/drain", &token).reply(&routes).await.status(), 400);
+        assert_eq!(post("/keys/drain?tag=a&tenant=b", &token).reply(&routes).await.status(), 400);
+
+        let audit_log = audit_log.lock().await;
+        let changes = audit_log.get_logs_by_type(AuditEventType::ConfigChange, 10);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|entry| entry.resource == "gemini.api_keys[tag=pool-a]"));
+    }
+
+    #[tokio::test]
+    async fn test_add_key_with_shadow_warmup_defers_real_selection() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")]));
+        let state = KeyControlState::new(
+            key_manager.clone(),
+            ConfigState::new(ProxyConfig::for_test(&["key1", "key2"]), "/nonexistent/proxy.yaml".to_string()),
+        );
+        let auth_state = AuthState::new(Arc::new(ProxyConfig::for_test(&["key1", "key2"])));
+        let session_id = auth_state.create_session("admin").await;
+        let token = auth_state.generate_token(&session_id).unwrap();
+        let routes = key_control_routes(state, auth_state).recover(crate::api::handlers::handle_rejection);
+
+        let add = |body: &str| post("/keys", &token).header("content-type", "application/json").body(body.to_string());
+        let response = add(r#"{"id":"new","key":"test-key-new","shadow_warmup_seconds":3600}"#).reply(&routes).await;
+        assert_eq!(response.status(), 201);
+
+        // 预热期内作为镜像目标，但不参与真实选择
+        let shadow_keys = key_manager.shadow_keys().await;
+        assert_eq!(shadow_keys.len(), 1);
+        assert_eq!(shadow_keys[0].key, "test-key-new");
+        for _ in 0..10 {
+            assert_eq!(key_manager.get_next_key().await.unwrap().id, "key1");
+        }
+
+        assert_eq!(add(r#"{"id":"new","key":"other"}"#).reply(&routes).await.status(), 409);
+        assert_eq!(add(r#"{"id":"","key":"other"}"#).reply(&routes).await.status(), 400);
+
+        // 不指定预热期时立即参与选择
+        assert_eq!(add(r#"{"id":"direct","key":"test-key-direct"}"#).reply(&routes).await.status(), 201);
+        let mut selected = std::collections::HashSet::new();
+        for _ in 0..20 {
+            selected.insert(key_manager.get_next_key().await.unwrap().id);
+        }
+        assert_eq!(selected.len(), 2);
+        assert!(!selected.contains("new"));
+    }
+
+    #[tokio::test]
+    async fn test_toggle_requires_admin_token() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")]));
+        let state = KeyControlState::new(
+            key_manager.clone(),
+            ConfigState::new(ProxyConfig::for_test(&["key1", "key2"]), "/nonexistent/proxy.yaml".to_string()),
+        );
+        let routes = key_control_routes(state, AuthState::new(Arc::new(ProxyConfig::for_test(&["key1", "key2"]))))
+            .recover(crate::api::handlers::handle_rejection);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/keys/key1/disable")
+            .reply(&routes)
+            .await;
+
+        assert_eq!(response.status(), 401);
+        assert!(key_manager.get_next_key().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_state_persisted_to_config() {
+        let config_state = ConfigState::new(ProxyConfig::for_test(&["key1", "key2"]), "/nonexistent/proxy.yaml".to_string());
+        // 启用未停用的密钥无需写入配置
+        assert!(persist_disabled_key(&config_state, "key1", true).await.is_ok());
+
+        let path = std::env::temp_dir().join(format!("gemini-proxy-key-control-{}.yaml", uuid::Uuid::new_v4()));
+        let config_state = ConfigState::new(ProxyConfig::for_test(&["key1", "key2"]), path.to_string_lossy().to_string());
+        persist_disabled_key(&config_state, "key1", false).await.unwrap();
+        assert_eq!(config_state.get_config().await.gemini.disabled_keys, vec!["key1".to_string()]);
+
+        let saved: ProxyConfig = serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.gemini.disabled_keys, vec!["key1".to_string()]);
+
+        persist_disabled_key(&config_state, "key1", true).await.unwrap();
+        assert!(config_state.get_config().await.gemini.disabled_keys.is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
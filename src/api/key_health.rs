@@ -0,0 +1,361 @@
+// src/api/key_health.rs
+//! 密钥健康报告
+//!
+//! 汇总每个密钥的运行时状态（熔断冷却、熔断器状态、最近错误、配额耗尽），
+//! 并为每个问题给出可读的处理建议，便于运维一次调用定位问题密钥
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+use crate::api::config::ApiResponse;
+use crate::error::recovery::{CircuitBreakerState, CircuitSnapshot, ErrorRecoveryManager};
+use crate::load_balancer::{UnifiedApiKey, UnifiedKeyManager};
+
+/// 密钥问题类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyProblemKind {
+    /// 熔断器打开，处于冷却期
+    Cooldown,
+    /// 熔断器半开，正在探测恢复
+    CircuitHalfOpen,
+    /// 上游拒绝认证（401/403）
+    AuthFailure,
+    /// 上游配额耗尽（429）
+    QuotaExhausted,
+    /// 达到本地每分钟请求上限
+    RateLimitReached,
+    /// 连续失败后被停用
+    Deactivated,
+    /// 最近出现上游错误
+    RecentErrors,
+}
+
+/// 单个问题及处理建议
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyHealthProblem {
+    pub kind: KeyProblemKind,
+    pub description: String,
+    pub remediation: String,
+}
+
+/// 单个密钥的健康状态
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyHealthEntry {
+    pub key_id: String,
+    pub healthy: bool,
+    /// 运维手动停用（维护下线），不计入健康或不健康统计
+    pub disabled: bool,
+    pub is_active: bool,
+    pub failure_count: u32,
+    pub last_error_status: Option<u16>,
+    pub current_requests: u32,
+    pub max_requests_per_minute: u32,
+    pub circuit_state: Option<CircuitBreakerState>,
+    pub problems: Vec<KeyHealthProblem>,
+}
+
+/// 密钥健康报告
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyHealthReport {
+    pub generated_at: DateTime<Utc>,
+    pub total_keys: usize,
+    pub healthy_keys: usize,
+    pub unhealthy_keys: usize,
+    pub disabled_keys: usize,
+    pub keys: Vec<KeyHealthEntry>,
+}
+
+/// 根据密钥状态与熔断器快照生成健康报告
+pub fn build_key_health_report(
+    keys: &[UnifiedApiKey],
+    circuits: &HashMap<String, CircuitSnapshot>,
+    now: DateTime<Utc>,
+) -> KeyHealthReport {
+    let entries: Vec<KeyHealthEntry> = keys
+        .iter()
+        .map(|key| {
+            let circuit = circuits.get(&key.id);
+            let disabled = key.runtime_state.disabled;
+            let problems = if disabled { Vec::new() } else { diagnose_key(key, circuit, now) };
+            KeyHealthEntry {
+                key_id: key.id.clone(),
+                healthy: !disabled && problems.is_empty(),
+                disabled,
+                is_active: key.runtime_state.is_active,
+                failure_count: key.runtime_state.failure_count,
+                last_error_status: key.runtime_state.last_error_status,
+                current_requests: key.runtime_state.current_requests,
+                max_requests_per_minute: key.max_requests_per_minute,
+                circuit_state: circuit.map(|c| c.state.clone()),
+                problems,
+            }
+        })
+        .collect();
+
+    let healthy_keys = entries.iter().filter(|e| e.healthy).count();
+    let disabled_keys = entries.iter().filter(|e| e.disabled).count();
+    KeyHealthReport {
+        generated_at: now,
+        total_keys: entries.len(),
+        healthy_keys,
+        unhealthy_keys: entries.len() - healthy_keys - disabled_keys,
+        disabled_keys,
+        keys: entries,
+    }
+}
+
+/// 密钥是否可承接流量（用于就绪检查）：未被手动停用、处于活跃状态、未达到每分钟上限（冷却中），且熔断器未打开
+pub fn is_key_ready(key: &UnifiedApiKey, circuit: Option<&CircuitSnapshot>, now: DateTime<Utc>) -> bool {
+    let tripped = circuit.is_some_and(|c| c.state == CircuitBreakerState::Open && c.next_attempt_time > now);
+    key.is_available() && !tripped
+}
+
+/// 统计可承接流量的密钥数量
+pub fn count_ready_keys(
+    keys: &[UnifiedApiKey],
+    circuits: &HashMap<String, CircuitSnapshot>,
+    now: DateTime<Utc>,
+) -> usize {
+    keys.iter()
+        .filter(|key| is_key_ready(key, circuits.get(&key.id), now))
+        .count()
+}
+
+fn problem(kind: KeyProblemKind, description: String, remediation: &str) -> KeyHealthProblem {
+    KeyHealthProblem {
+        kind,
+        description,
+        remediation: remediation.to_string(),
+    }
+}
+
+fn diagnose_key(key: &UnifiedApiKey, circuit: Option<&CircuitSnapshot>, now: DateTime<Utc>) -> Vec<KeyHealthProblem> {
+    let mut problems = Vec::new();
+
+    if let Some(circuit) = circuit {
+        match circuit.state {
+            CircuitBreakerState::Open if circuit.next_attempt_time > now => {
+                let remaining = (circuit.next_attempt_time - now).num_seconds().max(1);
+                problems.push(problem(
+                    KeyProblemKind::Cooldown,
+                    format!("熔断器已打开（连续失败 {} 次），冷却剩余 {} 秒", circuit.failure_count, remaining),
+                    "冷却结束后会自动进行半开探测；若反复进入冷却，请检查该密钥的上游错误并考虑降低其权重",
+                ));
+            }
+            CircuitBreakerState::Open | CircuitBreakerState::HalfOpen => {
+                problems.push(problem(
+                    KeyProblemKind::CircuitHalfOpen,
+                    "熔断器正在半开探测恢复".to_string(),
+                    "等待探测请求结果即可，探测成功后熔断器会自动关闭",
+                ));
+            }
+            CircuitBreakerState::Closed => {}
+        }
+    }
+
+    match key.runtime_state.last_error_status {
+        Some(status @ (401 | 403)) => problems.push(problem(
+            KeyProblemKind::AuthFailure,
+            format!("上游拒绝认证（HTTP {}）", status),
+            "检查密钥是否已被吊销、过期或未启用 Generative Language API；必要时在配置中轮换该密钥",
+        )),
+        Some(429) => problems.push(problem(
+            KeyProblemKind::QuotaExhausted,
+            "上游配额已耗尽（HTTP 429）".to_string(),
+            "在 Google Cloud 控制台提高该项目配额，或降低该密钥的权重与每分钟请求上限",
+        )),
+        Some(status) if status >= 500 => problems.push(problem(
+            KeyProblemKind::RecentErrors,
+            format!("最近一次请求上游返回 HTTP {}", status),
+            "上游服务异常通常会自行恢复；若持续出现，请检查 Gemini 服务状态",
+        )),
+        Some(status) => problems.push(problem(
+            KeyProblemKind::RecentErrors,
+            format!("最近一次请求上游返回 HTTP {}", status),
+            "检查客户端请求是否有效；若只在该密钥上出现，请核对密钥所属项目的配置",
+        )),
+        None => {}
+    }
+
+    let limit = key.effective_max_requests_per_minute();
+    if key.runtime_state.current_requests >= limit {
+        let description = if limit < key.max_requests_per_minute {
+            format!("已达到每分钟请求上限（{}，因连续 429 从 {} 自适应下调）", limit, key.max_requests_per_minute)
+        } else {
+            format!("已达到每分钟请求上限（{}）", limit)
+        };
+        problems.push(problem(
+            KeyProblemKind::RateLimitReached,
+            description,
+            "计数会在一分钟内自动重置；若经常触发，请提高 max_requests_per_minute 或增加密钥",
+        ));
+    }
+
+    if !key.runtime_state.is_active {
+        problems.push(problem(
+            KeyProblemKind::Deactivated,
+            format!("连续失败 {} 次后已被停用", key.runtime_state.failure_count),
+            "排查上述错误原因后，重新加载配置以恢复该密钥",
+        ));
+    }
+
+    problems
+}
+
+/// 密钥健康报告状态
+#[derive(Clone)]
+pub struct KeyHealthState {
+    key_manager: Arc<UnifiedKeyManager>,
+    recovery_manager: Option<Arc<ErrorRecoveryManager>>,
+}
+
+impl KeyHealthState {
+    pub fn new(key_manager: Arc<UnifiedKeyManager>) -> Self {
+        Self {
+            key_manager,
+            recovery_manager: None,
+        }
+    }
+
+    /// 关联错误恢复管理器，报告中包含各密钥的熔断器状态
+    pub fn with_recovery_manager(mut self, recovery_manager: Arc<ErrorRecoveryManager>) -> Self {
+        self.recovery_manager = Some(recovery_manager);
+        self
+    }
+
+    async fn snapshot(&self) -> (Vec<UnifiedApiKey>, HashMap<String, CircuitSnapshot>) {
+        let keys = self.key_manager.get_key_states().await;
+        let mut circuits = HashMap::new();
+        if let Some(recovery_manager) = &self.recovery_manager {
+            for key in &keys {
+                if let Some(snapshot) = recovery_manager.circuit_snapshot(&key.id).await {
+                    circuits.insert(key.id.clone(), snapshot);
+                }
+            }
+        }
+        (keys, circuits)
+    }
+
+    pub async fn build_report(&self) -> KeyHealthReport {
+        let (keys, circuits) = self.snapshot().await;
+        build_key_health_report(&keys, &circuits, Utc::now())
+    }
+
+    /// 当前可承接流量的密钥数量
+    pub async fn ready_key_count(&self) -> usize {
+        let (keys, circuits) = self.snapshot().await;
+        count_ready_keys(&keys, &circuits, Utc::now())
+    }
+}
+
+/// 密钥健康报告 API 路由
+pub fn key_health_routes(
+    state: KeyHealthState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let health_state = warp::any().map(move || state.clone());
+
+    // GET /keys/health-report - 获取密钥健康报告
+    warp::path!("keys" / "health-report")
+        .and(warp::get())
+        .and(health_state)
+        .and_then(get_key_health_report_handler)
+}
+
+async fn get_key_health_report_handler(state: KeyHealthState) -> Result<impl Reply, Rejection> {
+    let report = state.build_report().await;
+    Ok(warp::reply::json(&ApiResponse::success(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::recovery::create_default_recovery_manager;
+    use crate::load_balancer::key_manager::ApiKey;
+
+    fn kinds(entry: &KeyHealthEntry) -> Vec<KeyProblemKind> {
+        entry.problems.iter().map(|p| p.kind).collect()
+    }
+
+    #[tokio::test]
+    async fn test_report_hints_for_cooling_and_auth_failing_keys() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![
+            ApiKey::for_test("cooling"),
+            ApiKey::for_test("revoked"),
+            ApiKey::for_test("healthy"),
+        ]));
+        let recovery_manager = Arc::new(create_default_recovery_manager());
+
+        // cooling：连续 5xx 导致熔断器打开
+        for _ in 0..5 {
+            recovery_manager.report_operation_result("cooling", false).await;
+        }
+        key_manager.mark_key_failed_with_status("cooling", 503).await;
+        // revoked：上游拒绝认证
+        key_manager.mark_key_failed_with_status("revoked", 401).await;
+        key_manager.mark_key_success("healthy").await;
+
+        let state = KeyHealthState::new(key_manager).with_recovery_manager(recovery_manager);
+        let report = state.build_report().await;
+
+        assert_eq!(report.total_keys, 3);
+        assert_eq!(report.healthy_keys, 1);
+        assert_eq!(report.unhealthy_keys, 2);
+
+        let entry = |id: &str| report.keys.iter().find(|e| e.key_id == id).unwrap();
+
+        let cooling = entry("cooling");
+        assert_eq!(cooling.circuit_state, Some(CircuitBreakerState::Open));
+        assert_eq!(kinds(cooling), vec![KeyProblemKind::Cooldown, KeyProblemKind::RecentErrors]);
+        assert!(cooling.problems[0].remediation.contains("半开探测"));
+
+        let revoked = entry("revoked");
+        assert_eq!(kinds(revoked), vec![KeyProblemKind::AuthFailure]);
+        assert!(revoked.problems[0].description.contains("401"));
+        assert!(revoked.problems[0].remediation.contains("轮换"));
+
+        assert!(entry("healthy").healthy);
+    }
+
+    #[tokio::test]
+    async fn test_ready_key_count_excludes_tripped_and_exhausted_keys() {
+        let mut exhausted = ApiKey::for_test("exhausted");
+        exhausted.current_requests = exhausted.max_requests_per_minute;
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![
+            ApiKey::for_test("healthy"),
+            ApiKey::for_test("tripped"),
+            ApiKey::for_test("failing"),
+            exhausted,
+        ]));
+        let recovery_manager = Arc::new(create_default_recovery_manager());
+        for _ in 0..5 {
+            recovery_manager.report_operation_result("tripped", false).await;
+        }
+        for _ in 0..3 {
+            key_manager.mark_key_failed_with_status("failing", 500).await;
+        }
+
+        let state = KeyHealthState::new(key_manager).with_recovery_manager(recovery_manager);
+        assert_eq!(state.ready_key_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_health_report_endpoint() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")]));
+        key_manager.mark_key_failed_with_status("key1", 429).await;
+        let routes = key_health_routes(KeyHealthState::new(key_manager));
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/keys/health-report")
+            .reply(&routes)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["data"]["unhealthy_keys"], 1);
+        assert_eq!(body["data"]["keys"][0]["problems"][0]["kind"], "quota_exhausted");
+    }
+}
@@ -0,0 +1,180 @@
+// src/api/key_metrics.rs
+//! 单个密钥的指标下钻
+//!
+//! `GET /keys/{id}/metrics` 汇总指标收集器、密钥管理器与错误恢复管理器中该密钥的数据：
+//! 请求/错误计数、延迟分位数、令牌用量、估算成本、本分钟剩余配额、熔断器状态与最近错误。
+//! 未知密钥返回 404
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+use crate::api::config::ApiResponse;
+use crate::api::load_balancing_stats::StatsState;
+use crate::error::recovery::CircuitBreakerState;
+use crate::metrics::key_activity::{KeyActivitySnapshot, LatencyPercentiles, RecentKeyError};
+
+/// 单个密钥的指标
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyMetrics {
+    pub key_id: String,
+    /// 配置中的名称与说明
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub generated_at: DateTime<Utc>,
+    pub total_requests: u64,
+    pub error_requests: u64,
+    pub error_rate: f64,
+    pub latency: LatencyPercentiles,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 最近一分钟内的令牌用量
+    pub tokens_last_minute: u64,
+    pub estimated_cost: f64,
+    /// 当前有效的每分钟请求上限（可能因连续 429 被自适应下调）
+    pub effective_max_requests_per_minute: u32,
+    pub quota_remaining: u32,
+    pub available: bool,
+    pub circuit_state: Option<CircuitBreakerState>,
+    pub recent_errors: Vec<RecentKeyError>,
+}
+
+impl StatsState {
+    /// 汇总单个密钥的指标，未知密钥返回 None
+    pub async fn key_metrics(&self, key_id: &str) -> Option<KeyMetrics> {
+        let key_manager = self.key_manager.as_ref()?;
+        let key = key_manager
+            .get_key_states()
+            .await
+            .into_iter()
+            .find(|k| k.id == key_id)?;
+
+        let activity = self
+            .metrics
+            .as_ref()
+            .map(|m| m.key_activity(key_id))
+            .unwrap_or_default();
+        let estimated_cost = self.metrics.as_ref().map_or(0.0, |m| m.get_estimated_cost(key_id));
+        let labels = self.metrics.as_ref().map(|m| m.key_labels(key_id)).unwrap_or_default();
+        let circuit_state = match &self.recovery_manager {
+            Some(recovery_manager) => recovery_manager.circuit_snapshot(key_id).await.map(|c| c.state),
+            None => None,
+        };
+
+        let KeyActivitySnapshot {
+            total_requests,
+            error_requests,
+            latency,
+            prompt_tokens,
+            completion_tokens,
+            tokens_last_minute,
+            recent_errors,
+        } = activity;
+        let limit = key.effective_max_requests_per_minute();
+        Some(KeyMetrics {
+            key_id: key.id.clone(),
+            name: labels.name,
+            description: labels.description,
+            generated_at: Utc::now(),
+            total_requests,
+            error_requests,
+            error_rate: if total_requests > 0 {
+                error_requests as f64 / total_requests as f64
+            } else {
+                0.0
+            },
+            latency,
+            prompt_tokens,
+            completion_tokens,
+            tokens_last_minute,
+            estimated_cost,
+            effective_max_requests_per_minute: limit,
+            quota_remaining: limit.saturating_sub(key.runtime_state.current_requests),
+            available: key.is_available(),
+            circuit_state,
+            recent_errors,
+        })
+    }
+}
+
+/// 密钥指标下钻 API 路由
+pub fn key_metrics_routes(
+    state: StatsState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let stats_state = warp::any().map(move || state.clone());
+
+    // GET /keys/{id}/metrics - 获取单个密钥的指标
+    warp::path!("keys" / String / "metrics")
+        .and(warp::get())
+        .and(stats_state)
+        .and_then(get_key_metrics_handler)
+}
+
+async fn get_key_metrics_handler(key_id: String, state: StatsState) -> Result<impl Reply, Rejection> {
+    match state.key_metrics(&key_id).await {
+        Some(metrics) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::success(metrics)),
+            StatusCode::OK,
+        )),
+        None => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()>::error(format!("密钥不存在: {}", key_id))),
+            StatusCode::NOT_FOUND,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::recovery::create_default_recovery_manager;
+    use crate::load_balancer::key_manager::ApiKey;
+    use crate::load_balancer::UnifiedKeyManager;
+    use crate::metrics::MetricsCollector;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_drilldown_reflects_key_activity() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")]));
+        let metrics = Arc::new(MetricsCollector::new());
+        let recovery_manager = Arc::new(create_default_recovery_manager());
+
+        // 向 key1 驱动 3 次成功与 1 次 429
+        for ms in [100, 200, 300] {
+            key_manager.get_next_key().await.unwrap();
+            metrics.record_key_response("key1", 200, Duration::from_millis(ms));
+            recovery_manager.report_operation_result("key1", true).await;
+        }
+        metrics.record_key_response("key1", 429, Duration::from_millis(50));
+        metrics.record_key_tokens("key1", "gemini-1.5-flash", 120, 80);
+        metrics.record_estimated_cost("key1", 0.002).await;
+        // 其他密钥的活动不计入
+        metrics.record_key_response("other", 200, Duration::from_millis(10));
+
+        let state = StatsState::new(Some(key_manager))
+            .with_metrics(metrics)
+            .with_recovery_manager(recovery_manager);
+        let routes = key_metrics_routes(state).recover(crate::api::handlers::handle_rejection);
+
+        let response = warp::test::request().path("/keys/key1/metrics").reply(&routes).await;
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["key_id"], "key1");
+        assert_eq!(data["total_requests"], 4);
+        assert_eq!(data["error_requests"], 1);
+        assert_eq!(data["latency"]["samples"], 4);
+        assert_eq!(data["latency"]["p50_ms"], 100.0);
+        assert_eq!(data["latency"]["p99_ms"], 300.0);
+        assert_eq!(data["prompt_tokens"], 120);
+        assert_eq!(data["completion_tokens"], 80);
+        assert_eq!(data["tokens_last_minute"], 200);
+        assert!((data["estimated_cost"].as_f64().unwrap() - 0.002).abs() < 1e-12);
+        assert_eq!(data["circuit_state"], "Closed");
+        assert_eq!(data["recent_errors"][0]["status"], 429);
+        assert_eq!(data["quota_remaining"], 57);
+
+        let response = warp::test::request().path("/keys/missing/metrics").reply(&routes).await;
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -5,7 +5,11 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use warp::{Filter, Rejection, Reply};
 
-use crate::load_balancer::UnifiedKeyManager;
+use crate::config::BusinessMetricsConfig;
+use crate::error::recovery::{CircuitBreakerState, ErrorRecoveryManager};
+use crate::load_balancer::{ModelKeyAvailability, UnifiedKeyManager};
+use crate::metrics::MetricsCollector;
+use prometheus::{Encoder, Gauge, GaugeVec, Opts, Registry, TextEncoder};
 
 /// 负载均衡统计信息
 #[derive(Debug, Serialize, Clone)]
@@ -19,12 +23,17 @@ pub struct LoadBalancingStats {
     pub peak_qps: f64,
     pub uptime_seconds: u64,
     pub distribution_effectiveness: f64,
+    pub total_estimated_cost: f64,
+    /// 按模型的可用密钥数（密钥配置了 `allowed_models` 时按模型分池）
+    pub model_availability: HashMap<String, ModelKeyAvailability>,
 }
 
 /// 单个密钥的请求统计
 #[derive(Debug, Serialize, Clone)]
 pub struct RequestStats {
     pub key_id: String,
+    /// 配置中的名称，未设置时为 None
+    pub name: Option<String>,
     pub total_requests: u64,
     pub successful_requests: u64,
     pub failed_requests: u64,
@@ -34,6 +43,11 @@ pub struct RequestStats {
     pub expected_percentage: f64,
     pub actual_percentage: f64,
     pub effectiveness_score: f64,
+    pub estimated_cost: f64,
+    /// 最近一分钟内的令牌用量（提示与输出之和），上游响应不含用量信息时不计入
+    pub tokens_last_minute: u64,
+    /// 收到 429 后剩余的冷却时间（秒，向上取整），不在冷却期时为 None
+    pub cooldown_remaining_seconds: Option<u64>,
 }
 
 /// 时间段统计
@@ -107,6 +121,9 @@ pub struct StatsState {
     #[allow(dead_code)]  // 保留用于未来功能扩展
     pub stats_data: Arc<RwLock<LoadBalancingStats>>,
     pub start_time: SystemTime,
+    pub metrics: Option<Arc<MetricsCollector>>,
+    pub recovery_manager: Option<Arc<ErrorRecoveryManager>>,
+    pub business_metrics: BusinessMetricsConfig,
 }
 
 impl StatsState {
@@ -115,9 +132,30 @@ impl StatsState {
             key_manager,
             stats_data: Arc::new(RwLock::new(LoadBalancingStats::default())),
             start_time: SystemTime::now(),
+            metrics: None,
+            recovery_manager: None,
+            business_metrics: BusinessMetricsConfig::default(),
         }
     }
 
+    /// 关联指标收集器（用于读取估算成本等真实数据）
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// 关联错误恢复管理器（用于导出熔断状态与恢复统计）
+    pub fn with_recovery_manager(mut self, recovery_manager: Arc<ErrorRecoveryManager>) -> Self {
+        self.recovery_manager = Some(recovery_manager);
+        self
+    }
+
+    /// 设置业务指标导出配置
+    pub fn with_business_metrics(mut self, config: BusinessMetricsConfig) -> Self {
+        self.business_metrics = config;
+        self
+    }
+
     pub async fn get_key_manager(&self) -> Option<Arc<UnifiedKeyManager>> {
         self.key_manager.clone()
     }
@@ -135,6 +173,8 @@ impl Default for LoadBalancingStats {
             peak_qps: 0.0,
             uptime_seconds: 0,
             distribution_effectiveness: 100.0,
+            total_estimated_cost: 0.0,
+            model_availability: HashMap::new(),
         }
     }
 }
@@ -173,6 +213,7 @@ async fn get_load_balancing_stats_handler(
         Some(key_manager) => {
             let weight_stats = key_manager.get_stats().await;
             let all_keys = key_manager.get_all_keys().await;
+            let cooldowns = key_manager.cooldowns().await;
             
             // 生成模拟的负载均衡统计数据
             let mut request_distribution = HashMap::new();
@@ -201,6 +242,7 @@ async fn get_load_balancing_stats_handler(
                 
                 let stats = RequestStats {
                     key_id: key.id.clone(),
+                    name: state.metrics.as_ref().and_then(|m| m.key_labels(&key.id).name),
                     total_requests: actual_requests,
                     successful_requests: (actual_requests as f64 * 0.95) as u64, // 95% 成功率
                     failed_requests: (actual_requests as f64 * 0.05) as u64,
@@ -210,6 +252,14 @@ async fn get_load_balancing_stats_handler(
                     expected_percentage,
                     actual_percentage,
                     effectiveness_score: effectiveness_score.max(0.0),
+                    estimated_cost: state.metrics.as_ref()
+                        .map(|m| m.get_estimated_cost(&key.id))
+                        .unwrap_or(0.0),
+                    tokens_last_minute: state.metrics.as_ref()
+                        .map_or(0, |m| m.key_activity(&key.id).tokens_last_minute),
+                    cooldown_remaining_seconds: cooldowns
+                        .get(&key.id)
+                        .map(|remaining| remaining.as_secs_f64().ceil() as u64),
                 };
                 
                 request_distribution.insert(key.id.clone(), stats);
@@ -230,6 +280,10 @@ async fn get_load_balancing_stats_handler(
                 .map(|s| s.effectiveness_score)
                 .sum::<f64>() / request_distribution.len() as f64;
             
+            let total_estimated_cost = request_distribution.values()
+                .map(|s| s.estimated_cost)
+                .sum::<f64>();
+            
             let stats = LoadBalancingStats {
                 request_distribution,
                 total_requests,
@@ -240,6 +294,8 @@ async fn get_load_balancing_stats_handler(
                 peak_qps: current_qps * 1.5, // 模拟峰值
                 uptime_seconds,
                 distribution_effectiveness,
+                total_estimated_cost,
+                model_availability: key_manager.model_availability().await,
             };
             
             let response = ApiResponse::success(stats);
@@ -337,6 +393,135 @@ async fn get_response_time_stats_handler(
     Ok(warp::reply::json(&response))
 }
 
+/// Jain 公平性指数：按权重归一化各密钥的请求数后计算，1 表示完全按权重分配
+fn fairness_index(normalized_loads: &[f64]) -> f64 {
+    let sum: f64 = normalized_loads.iter().sum();
+    let sum_of_squares: f64 = normalized_loads.iter().map(|x| x * x).sum();
+    if sum_of_squares <= 0.0 {
+        return 1.0;
+    }
+    sum * sum / (normalized_loads.len() as f64 * sum_of_squares)
+}
+
+fn circuit_state_value(state: &CircuitBreakerState) -> f64 {
+    match state {
+        CircuitBreakerState::Closed => 0.0,
+        CircuitBreakerState::HalfOpen => 1.0,
+        CircuitBreakerState::Open => 2.0,
+    }
+}
+
+fn business_gauge(registry: &Registry, name: &str, help: &str) -> prometheus::Result<Gauge> {
+    let gauge = Gauge::with_opts(Opts::new(name, help).namespace("gemini_proxy"))?;
+    registry.register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
+fn business_gauge_vec(registry: &Registry, name: &str, help: &str) -> prometheus::Result<GaugeVec> {
+    let gauge = GaugeVec::new(Opts::new(name, help).namespace("gemini_proxy"), &["key_id"])?;
+    registry.register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
+/// 渲染业务指标（Prometheus 文本格式），按密钥的请求数与配额均为当前分钟窗口内的值
+async fn render_business_metrics(state: &StatsState) -> prometheus::Result<String> {
+    let registry = Registry::new();
+
+    if let Some(key_manager) = &state.key_manager {
+        let keys = key_manager.get_key_states().await;
+        let available: Vec<_> = keys.iter().filter(|k| k.is_available()).collect();
+
+        business_gauge(&registry, "keys_total", "Number of configured API keys")?.set(keys.len() as f64);
+        business_gauge(&registry, "keys_available", "Number of API keys currently eligible for selection")?
+            .set(available.len() as f64);
+        let loads: Vec<f64> = available
+            .iter()
+            .filter(|k| k.weight > 0)
+            .map(|k| k.runtime_state.current_requests as f64 / k.weight as f64)
+            .collect();
+        business_gauge(
+            &registry,
+            "key_fairness_index",
+            "Jain fairness index of weight-normalized requests across available keys (1 = perfectly weighted)",
+        )?
+        .set(fairness_index(&loads));
+        business_gauge(&registry, "quota_remaining", "Requests remaining this minute across available keys")?.set(
+            available
+                .iter()
+                .map(|k| k.effective_max_requests_per_minute().saturating_sub(k.runtime_state.current_requests) as f64)
+                .sum(),
+        );
+
+        if state.business_metrics.per_key {
+            let weight = business_gauge_vec(&registry, "key_weight", "Configured weight per API key")?;
+            let quota = business_gauge_vec(&registry, "key_quota_remaining", "Requests remaining this minute per API key")?;
+            let up = business_gauge_vec(&registry, "key_available", "Whether the API key is eligible for selection")?;
+            let rate_limit = business_gauge_vec(
+                &registry,
+                "key_effective_rate_limit",
+                "Effective requests-per-minute limit per API key (lowered adaptively after repeated 429s)",
+            )?;
+            let circuit = business_gauge_vec(
+                &registry,
+                "key_circuit_state",
+                "Circuit breaker state per API key (0 = closed, 1 = half-open, 2 = open)",
+            )?;
+            for key in &keys {
+                let labels = [key.id.as_str()];
+                weight.with_label_values(&labels).set(key.weight as f64);
+                quota
+                    .with_label_values(&labels)
+                    .set(key.effective_max_requests_per_minute().saturating_sub(key.runtime_state.current_requests) as f64);
+                up.with_label_values(&labels).set(if key.is_available() { 1.0 } else { 0.0 });
+                rate_limit.with_label_values(&labels).set(key.effective_max_requests_per_minute() as f64);
+                if let Some(recovery_manager) = &state.recovery_manager {
+                    let value = recovery_manager
+                        .circuit_snapshot(&key.id)
+                        .await
+                        .map_or(0.0, |snapshot| circuit_state_value(&snapshot.state));
+                    circuit.with_label_values(&labels).set(value);
+                }
+            }
+        }
+    }
+
+    if let Some(recovery_manager) = &state.recovery_manager {
+        let stats = recovery_manager.get_statistics().await;
+        business_gauge(&registry, "recovery_attempts", "Error recovery attempts")?.set(stats.total_recovery_attempts as f64);
+        business_gauge(&registry, "recovery_successes", "Successful error recoveries")?.set(stats.successful_recoveries as f64);
+        business_gauge(&registry, "recovery_failures", "Failed error recoveries")?.set(stats.failed_recoveries as f64);
+        business_gauge(&registry, "recovery_rate", "Ratio of successful error recoveries")?.set(stats.recovery_rate);
+        business_gauge(&registry, "circuit_breaker_trips", "Circuit breaker trips")?.set(stats.circuit_breaker_trips as f64);
+        business_gauge(&registry, "fallback_activations", "Fallback strategy activations")?.set(stats.fallback_activations as f64);
+    }
+
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&registry.gather(), &mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+/// 合并代理指标（与 `/metrics` 相同）和业务指标
+pub async fn render_prometheus(state: &StatsState) -> String {
+    let mut output = state.metrics.as_ref().map(|m| m.get_metrics()).unwrap_or_default();
+    match render_business_metrics(state).await {
+        Ok(business) => output.push_str(&business),
+        Err(e) => tracing::warn!("渲染业务指标失败: {}", e),
+    }
+    output
+}
+
+/// 获取合并后的 Prometheus 指标
+async fn get_prometheus_stats_handler(state: StatsState) -> Result<impl Reply, Rejection> {
+    if !state.business_metrics.enabled {
+        return Err(warp::reject::not_found());
+    }
+    Ok(warp::reply::with_header(
+        render_prometheus(&state).await,
+        "content-type",
+        "text/plain; version=0.0.4",
+    ))
+}
+
 /// 负载均衡统计 API 路由
 pub fn load_balancing_stats_routes(
     state: StatsState,
@@ -361,11 +546,69 @@ pub fn load_balancing_stats_routes(
         .and(stats_state.clone())
         .and_then(get_response_time_stats_handler);
 
+    // GET /stats/prometheus - 合并代理与业务指标的 Prometheus 导出
+    let get_prometheus_stats = warp::path!("stats" / "prometheus")
+        .and(warp::get())
+        .and(stats_state.clone())
+        .and_then(get_prometheus_stats_handler);
+
     get_load_balancing_stats
         .or(get_time_based_stats)
         .or(get_response_time_stats)
+        .or(get_prometheus_stats)
 }
 
 // 需要在 Cargo.toml 中添加这些依赖
 // chrono = { version = "0.4", features = ["serde"] }
-// rand = "0.8"
\ No newline at end of file
+// rand = "0.8"
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_balancer::key_manager::ApiKey;
+
+    #[tokio::test]
+    async fn test_prometheus_output_merges_proxy_and_business_metrics() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![
+            ApiKey::for_test("key1"),
+            ApiKey::for_test("key2"),
+        ]));
+        let metrics = Arc::new(MetricsCollector::new());
+        metrics.increment_request_count("key1").await;
+        for _ in 0..4 {
+            key_manager.get_next_key().await.unwrap();
+        }
+        let state = StatsState::new(Some(key_manager))
+            .with_metrics(metrics)
+            .with_recovery_manager(Arc::new(crate::error::recovery::create_default_recovery_manager()));
+
+        let routes = load_balancing_stats_routes(state);
+        let response = warp::test::request().path("/stats/prometheus").reply(&routes).await;
+        assert_eq!(response.status(), 200);
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+
+        assert!(body.contains("gemini_proxy_proxy_requests_total{api_key_id=\"key1\"} 1"), "{}", body);
+        assert!(body.contains("gemini_proxy_key_fairness_index 1"), "{}", body);
+        assert!(body.contains("gemini_proxy_key_quota_remaining{key_id=\"key2\"} 58"), "{}", body);
+        assert!(body.contains("gemini_proxy_key_circuit_state{key_id=\"key1\"} 0"), "{}", body);
+        assert!(body.contains("gemini_proxy_recovery_attempts 0"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_prometheus_endpoint_can_be_disabled() {
+        let state = StatsState::new(None).with_business_metrics(BusinessMetricsConfig {
+            enabled: false,
+            per_key: true,
+        });
+        let routes = load_balancing_stats_routes(state);
+        let response = warp::test::request().path("/stats/prometheus").reply(&routes).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[test]
+    fn test_fairness_index() {
+        assert_eq!(fairness_index(&[]), 1.0);
+        assert_eq!(fairness_index(&[2.0, 2.0]), 1.0);
+        assert!((fairness_index(&[4.0, 0.0]) - 0.5).abs() < 1e-9);
+    }
+}
@@ -1,10 +1,19 @@
 // src/api/mod.rs
 // 仅导出实际使用的模块，清理未使用的导入
 
+pub mod audit_search;
+pub mod cache;
+pub mod client_tokens;
 pub mod config;
 pub mod handlers;
 pub mod weight_management;
 pub mod load_balancing_stats;
+pub mod key_health;
+pub mod key_control;
+pub mod key_metrics;
+pub mod optimizer_config;
+pub mod read_audit;
+pub mod support_bundle;
 pub mod auth;
 
 // 未来功能模块（暂时保留声明但不导出）
@@ -0,0 +1,271 @@
+// src/api/optimizer_config.rs
+//! 权重优化器配置的查看与运行时调整
+//!
+//! `GET /optimizer/config` 返回当前的优化器参数，`PUT /optimizer/config`（仅管理员）按字段更新
+//! 权重因子、敏感度、最小样本数、最大调整幅度等参数。更新前验证取值范围，验证失败时返回 400
+//! 且保持原配置；更新不会清空已记录的性能历史，每次变更记录审计日志
+
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+use crate::api::auth::{admin_only, AuthState};
+use crate::api::config::ApiResponse;
+use crate::load_balancer::optimizer::{OptimizerConfig, WeightOptimizer};
+use crate::security::SharedAuditLog;
+
+/// 优化器配置更新请求（省略的字段保持不变）
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptimizerConfigUpdate {
+    pub history_days: Option<u32>,
+    pub min_samples: Option<usize>,
+    pub response_time_weight: Option<f64>,
+    pub success_rate_weight: Option<f64>,
+    pub throughput_weight: Option<f64>,
+    pub max_adjustment_percent: Option<f64>,
+    pub sensitivity: Option<f64>,
+    pub ewma_alpha: Option<f64>,
+}
+
+impl OptimizerConfigUpdate {
+    /// 将更新应用到配置副本
+    pub fn apply_to(&self, config: &OptimizerConfig) -> OptimizerConfig {
+        let mut updated = config.clone();
+        if let Some(history_days) = self.history_days {
+            updated.history_days = history_days;
+        }
+        if let Some(min_samples) = self.min_samples {
+            updated.min_samples = min_samples;
+        }
+        if let Some(weight) = self.response_time_weight {
+            updated.response_time_weight = weight;
+        }
+        if let Some(weight) = self.success_rate_weight {
+            updated.success_rate_weight = weight;
+        }
+        if let Some(weight) = self.throughput_weight {
+            updated.throughput_weight = weight;
+        }
+        if let Some(max_adjustment_percent) = self.max_adjustment_percent {
+            updated.max_adjustment_percent = max_adjustment_percent;
+        }
+        if let Some(sensitivity) = self.sensitivity {
+            updated.sensitivity = sensitivity;
+        }
+        if let Some(alpha) = self.ewma_alpha {
+            updated.ewma_alpha = Some(alpha);
+        }
+        updated
+    }
+}
+
+/// 优化器配置 API 状态
+#[derive(Clone)]
+pub struct OptimizerConfigState {
+    optimizer: Arc<RwLock<WeightOptimizer>>,
+    audit_log: Option<SharedAuditLog>,
+}
+
+impl OptimizerConfigState {
+    pub fn new(optimizer: Arc<RwLock<WeightOptimizer>>) -> Self {
+        Self {
+            optimizer,
+            audit_log: None,
+        }
+    }
+
+    /// 记录配置变更审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    pub async fn get_config(&self) -> OptimizerConfig {
+        self.optimizer.read().await.get_config().clone()
+    }
+
+    /// 验证并应用更新，返回更新后的配置
+    pub async fn update_config(&self, update: &OptimizerConfigUpdate) -> Result<OptimizerConfig, String> {
+        let (old_config, new_config) = {
+            let mut optimizer = self.optimizer.write().await;
+            let old_config = optimizer.get_config().clone();
+            let new_config = update.apply_to(&old_config);
+            optimizer.update_config(new_config.clone())?;
+            (old_config, new_config)
+        };
+
+        if let Some(audit_log) = &self.audit_log {
+            let to_json = |config: &OptimizerConfig| serde_json::to_string(config).unwrap_or_default();
+            if let Err(e) = audit_log
+                .lock()
+                .await
+                .log_config_change(
+                    None,
+                    Some("admin".to_string()),
+                    "optimizer",
+                    &to_json(&old_config),
+                    &to_json(&new_config),
+                    "optimizer_update",
+                )
+                .await
+            {
+                tracing::warn!("记录优化器配置审计日志失败: {}", e);
+            }
+        }
+
+        tracing::info!("优化器配置已更新: {:?}", new_config);
+        Ok(new_config)
+    }
+}
+
+/// 优化器配置 API 路由
+pub fn optimizer_config_routes(
+    state: OptimizerConfigState,
+    auth_state: AuthState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let optimizer_state = warp::any().map(move || state.clone());
+
+    // GET /optimizer/config - 查看优化器配置
+    let get_config = warp::path!("optimizer" / "config")
+        .and(warp::get())
+        .and(optimizer_state.clone())
+        .and_then(get_config_handler);
+
+    // PUT /optimizer/config - 更新优化器配置（仅管理员）
+    let update_config = warp::path!("optimizer" / "config")
+        .and(warp::put())
+        .and(admin_only(auth_state))
+        .and(warp::body::json())
+        .and(optimizer_state)
+        .and_then(update_config_handler);
+
+    get_config.or(update_config)
+}
+
+async fn get_config_handler(state: OptimizerConfigState) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&ApiResponse::success(state.get_config().await)))
+}
+
+async fn update_config_handler(update: OptimizerConfigUpdate, state: OptimizerConfigState) -> Result<impl Reply, Rejection> {
+    match state.update_config(&update).await {
+        Ok(config) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::success(config)),
+            StatusCode::OK,
+        )),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()>::error(e)),
+            StatusCode::BAD_REQUEST,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProxyConfig;
+    use crate::load_balancer::optimizer::PerformanceMetric;
+    use crate::security::{AuditConfig, AuditEventType, AuditLogManager};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn metric() -> PerformanceMetric {
+        PerformanceMetric {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            response_time_ms: 200.0,
+            success_rate: 0.99,
+            error_rate: 0.01,
+            throughput_rps: 20.0,
+            concurrent_requests: 1,
+        }
+    }
+
+    async fn setup() -> (Arc<RwLock<WeightOptimizer>>, SharedAuditLog, String, OptimizerConfigState, AuthState) {
+        let optimizer = Arc::new(RwLock::new(WeightOptimizer::new(OptimizerConfig::default())));
+        let audit_log: SharedAuditLog = Arc::new(tokio::sync::Mutex::new(AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        })));
+        let auth_state = AuthState::new(Arc::new(ProxyConfig::for_test(&["key1"])));
+        let session_id = auth_state.create_session("admin").await;
+        let token = auth_state.generate_token(&session_id).unwrap();
+        let state = OptimizerConfigState::new(optimizer.clone()).with_audit_log(audit_log.clone());
+        (optimizer, audit_log, token, state, auth_state)
+    }
+
+    fn put(token: &str, body: serde_json::Value) -> warp::test::RequestBuilder {
+        warp::test::request()
+            .method("PUT")
+            .path("/optimizer/config")
+            .header("authorization", format!("Bearer {}", token))
+            .json(&body)
+    }
+
+    #[tokio::test]
+    async fn test_valid_update_takes_effect() {
+        let (optimizer, audit_log, token, state, auth_state) = setup().await;
+        let routes = optimizer_config_routes(state, auth_state).recover(crate::api::handlers::handle_rejection);
+        for _ in 0..5 {
+            optimizer.read().await.record_performance("key1", metric()).await;
+        }
+        // 默认最小样本数为 100，尚无评分
+        assert!(optimizer.read().await.calculate_performance_score("key1").await.is_none());
+
+        let body = serde_json::json!({
+            "min_samples": 5,
+            "response_time_weight": 0.2,
+            "success_rate_weight": 0.7,
+            "throughput_weight": 0.1,
+            "sensitivity": 0.5
+        });
+        let response = put(&token, body).reply(&routes).await;
+        assert_eq!(response.status(), 200);
+
+        // 新配置立即生效，且保留已记录的历史数据
+        let optimizer = optimizer.read().await;
+        assert_eq!(optimizer.get_config().min_samples, 5);
+        assert_eq!(optimizer.get_config().success_rate_weight, 0.7);
+        assert_eq!(optimizer.get_config().max_adjustment_percent, 50.0);
+        assert!(optimizer.calculate_performance_score("key1").await.is_some());
+
+        let response = warp::test::request().method("GET").path("/optimizer/config").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["data"]["sensitivity"], 0.5);
+
+        let changes = audit_log.lock().await.get_logs_by_type(AuditEventType::ConfigChange, 10).len();
+        assert_eq!(changes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_update_rejected() {
+        let (optimizer, audit_log, token, state, auth_state) = setup().await;
+        let routes = optimizer_config_routes(state, auth_state).recover(crate::api::handlers::handle_rejection);
+
+        let invalid = [
+            serde_json::json!({ "sensitivity": 1.5 }),
+            serde_json::json!({ "response_time_weight": 0.9 }),
+            serde_json::json!({ "max_adjustment_percent": 0.0 }),
+            serde_json::json!({ "min_samples": 0 }),
+            serde_json::json!({ "ewma_alpha": -0.1 }),
+        ];
+        for body in invalid {
+            let response = put(&token, body.clone()).reply(&routes).await;
+            assert_eq!(response.status(), 400, "{}", body);
+        }
+
+        // 原配置保持不变，也没有审计记录
+        let config = optimizer.read().await.get_config().clone();
+        assert_eq!(config.sensitivity, OptimizerConfig::default().sensitivity);
+        assert_eq!(config.response_time_weight, OptimizerConfig::default().response_time_weight);
+        assert!(audit_log.lock().await.get_logs_by_type(AuditEventType::ConfigChange, 10).is_empty());
+
+        // 未携带管理员令牌时拒绝
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/optimizer/config")
+            .json(&serde_json::json!({ "sensitivity": 0.5 }))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 401);
+    }
+}
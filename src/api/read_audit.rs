@@ -0,0 +1,165 @@
+// src/api/read_audit.rs
+//! 只读 API 访问审计
+//!
+//! 部分合规要求记录对敏感端点（配置、密钥、审计）的读取。启用后，对路径匹配配置前缀的
+//! GET/HEAD 请求记录一条 API 调用审计（包含调用者身份与响应状态码）；`/health` 等未列出的
+//! 端点不记录，避免噪声
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Instant;
+use warp::http::Method;
+use warp::path::FullPath;
+use warp::{Filter, Rejection, Reply};
+use crate::api::auth::AuthState;
+use crate::config::ReadAccessAuditConfig;
+use crate::security::{ApiCallRecord, AuditResult, SharedAuditLog};
+
+/// 只读访问审计状态
+#[derive(Clone)]
+pub struct ReadAuditState {
+    config: ReadAccessAuditConfig,
+    audit_log: SharedAuditLog,
+    auth_state: Option<AuthState>,
+}
+
+impl ReadAuditState {
+    pub fn new(config: ReadAccessAuditConfig, audit_log: SharedAuditLog) -> Self {
+        Self {
+            config,
+            audit_log,
+            auth_state: None,
+        }
+    }
+
+    /// 从 Bearer 令牌解析调用者身份
+    pub fn with_auth_state(mut self, auth_state: AuthState) -> Self {
+        self.auth_state = Some(auth_state);
+        self
+    }
+
+    /// 请求是否需要审计
+    pub fn should_audit(&self, method: &Method, path: &str) -> bool {
+        self.config.enabled
+            && (method == Method::GET || method == Method::HEAD)
+            && self.config.endpoints.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+
+    /// 调用者身份（用户 ID 与角色），令牌缺失或无效时为 None
+    fn caller(&self, authorization: Option<&str>) -> Option<(String, String)> {
+        let token = authorization?.strip_prefix("Bearer ")?;
+        let claims = self.auth_state.as_ref()?.verify_token(token).ok()?;
+        Some((claims.sub, claims.role))
+    }
+
+    async fn record(&self, method: &Method, path: &str, authorization: Option<&str>, addr: Option<SocketAddr>, status: u16, duration_ms: u64) {
+        let caller = self.caller(authorization);
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("access".to_string(), "read".to_string());
+        if let Some((_, role)) = &caller {
+            metadata.insert("role".to_string(), role.clone());
+        }
+        let record = ApiCallRecord {
+            source_ip: addr.map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |addr| addr.ip()),
+            user_id: Some(caller.map_or_else(|| "anonymous".to_string(), |(user, _)| user)),
+            method: method.to_string(),
+            resource: path.to_string(),
+            status_code: status,
+            duration_ms,
+            result: match status {
+                401 | 403 => AuditResult::Denied,
+                200..=399 => AuditResult::Success,
+                _ => AuditResult::Failure,
+            },
+            request_id: None,
+            metadata,
+        };
+        if let Err(e) = self.audit_log.lock().await.log_api_call_record(record).await {
+            tracing::warn!("记录只读访问审计日志失败: {}", e);
+        }
+    }
+}
+
+/// 为路由添加只读访问审计
+pub fn with_read_audit<F, R>(
+    routes: F,
+    state: ReadAuditState,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let state = warp::any().map(move || state.clone());
+    let started = warp::any().map(Instant::now);
+
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::addr::remote())
+        .and(started)
+        .and(state)
+        .and(routes)
+        .and_then(
+            |method: Method, path: FullPath, authorization: Option<String>, addr: Option<SocketAddr>, started: Instant, state: ReadAuditState, reply: R| async move {
+                let response = reply.into_response();
+                if state.should_audit(&method, path.as_str()) {
+                    let duration_ms = started.elapsed().as_millis() as u64;
+                    state
+                        .record(&method, path.as_str(), authorization.as_deref(), addr, response.status().as_u16(), duration_ms)
+                        .await;
+                }
+                Ok::<_, Rejection>(response)
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::config::{config_routes, ConfigState};
+    use crate::config::ProxyConfig;
+    use crate::security::{AuditConfig, AuditEventType, AuditLogManager};
+    use std::sync::Arc;
+
+    fn audit_log() -> SharedAuditLog {
+        Arc::new(tokio::sync::Mutex::new(AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        })))
+    }
+
+    async fn get_config(enabled: bool) -> Vec<crate::security::AuditLogEntry> {
+        let audit_log = audit_log();
+        let read_audit = ReadAccessAuditConfig {
+            enabled,
+            ..ReadAccessAuditConfig::default()
+        };
+        let state = ReadAuditState::new(read_audit, audit_log.clone());
+        // 未列出的端点与写操作不记录
+        assert!(!state.should_audit(&Method::GET, "/health"));
+        assert!(!state.should_audit(&Method::PUT, "/api/config"));
+
+        let config_state = ConfigState::new(ProxyConfig::for_test(&["key1"]), "/nonexistent/proxy.yaml".to_string());
+        let routes = with_read_audit(warp::path("api").and(config_routes(config_state)), state);
+        let response = warp::test::request().method("GET").path("/api/config").reply(&routes).await;
+        assert_eq!(response.status(), 200);
+
+        let logs = audit_log.lock().await.get_logs_by_type(AuditEventType::ApiCall, 10).into_iter().cloned().collect();
+        logs
+    }
+
+    #[tokio::test]
+    async fn test_read_audited_when_enabled() {
+        let logs = get_config(true).await;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].resource, "/api/config");
+        assert_eq!(logs[0].status_code, Some(200));
+        assert_eq!(logs[0].result, AuditResult::Success);
+        assert_eq!(logs[0].user_identifier.as_deref(), Some("anonymous"));
+        assert_eq!(logs[0].metadata.get("access").map(String::as_str), Some("read"));
+    }
+
+    #[tokio::test]
+    async fn test_read_not_audited_when_disabled() {
+        assert!(get_config(false).await.is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+// src/api/support_bundle.rs
+//! 支持包导出
+//!
+//! `GET /support-bundle`（仅管理员）汇总生效配置、密钥运行时状态、最近错误、恢复统计、
+//! 安全审计报告与存储统计，便于用户提交问题时附带诊断快照。
+//! 输出前统一经过 [`Redactor`] 脱敏，不包含任何原始密钥
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+use crate::api::auth::{admin_only, AuthState};
+use crate::api::config::{ApiResponse, ConfigState};
+use crate::api::key_health::{KeyHealthReport, KeyHealthState};
+use crate::error::recovery::{ErrorRecoveryManager, RecoveryStats};
+use crate::persistence::{StorageManager, StorageStats};
+use crate::security::{SecurityAuditReport, SecurityConfigValidator};
+use crate::utils::error::{ErrorHandler, ErrorStatistics};
+use crate::utils::redaction::Redactor;
+
+/// 支持包中包含的最近错误条数
+const RECENT_ERROR_LIMIT: usize = 50;
+
+/// 最近错误
+#[derive(Debug, Serialize)]
+pub struct RecentErrors {
+    pub statistics: ErrorStatistics,
+    pub entries: Vec<serde_json::Value>,
+}
+
+/// 诊断支持包
+#[derive(Debug, Serialize)]
+pub struct SupportBundle {
+    pub generated_at: DateTime<Utc>,
+    pub version: String,
+    /// 脱敏后的生效配置
+    pub config: serde_json::Value,
+    pub keys: KeyHealthReport,
+    pub recent_errors: RecentErrors,
+    pub recovery: Option<RecoveryStats>,
+    pub security: SecurityAuditReport,
+    /// 未配置持久化存储时为 None
+    pub storage: Option<StorageStats>,
+}
+
+/// 支持包状态
+#[derive(Clone)]
+pub struct SupportBundleState {
+    config_state: ConfigState,
+    key_health: KeyHealthState,
+    error_handler: Arc<ErrorHandler>,
+    recovery_manager: Option<Arc<ErrorRecoveryManager>>,
+    storage: Option<Arc<StorageManager>>,
+}
+
+impl SupportBundleState {
+    pub fn new(config_state: ConfigState, key_health: KeyHealthState, error_handler: Arc<ErrorHandler>) -> Self {
+        Self {
+            config_state,
+            key_health,
+            error_handler,
+            recovery_manager: None,
+            storage: None,
+        }
+    }
+
+    /// 包含错误恢复统计
+    pub fn with_recovery_manager(mut self, recovery_manager: Arc<ErrorRecoveryManager>) -> Self {
+        self.recovery_manager = Some(recovery_manager);
+        self
+    }
+
+    /// 包含持久化存储统计
+    pub fn with_storage(mut self, storage: Arc<StorageManager>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// 生成脱敏后的支持包
+    pub async fn build(&self) -> SupportBundle {
+        let config = self.config_state.get_config().await;
+        let redactor = Redactor::from_config(&config);
+
+        let mut config_json = serde_json::to_value(redactor.redact_config(&config)).unwrap_or_default();
+        redactor.redact_json(&mut config_json);
+
+        let entries = self
+            .error_handler
+            .get_recent_errors(RECENT_ERROR_LIMIT)
+            .await
+            .into_iter()
+            .map(|entry| {
+                let mut value = serde_json::to_value(entry).unwrap_or_default();
+                redactor.redact_json(&mut value);
+                value
+            })
+            .collect();
+
+        let mut security = SecurityConfigValidator::audit_security(&config);
+        for issue in &mut security.issues {
+            issue.description = redactor.redact_str(&issue.description);
+        }
+
+        let recovery = match &self.recovery_manager {
+            Some(recovery_manager) => Some(recovery_manager.get_statistics().await),
+            None => None,
+        };
+        let storage = match &self.storage {
+            Some(storage) => match storage.get_storage_stats().await {
+                Ok(stats) => Some(stats),
+                Err(e) => {
+                    tracing::warn!("获取存储统计失败: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        SupportBundle {
+            generated_at: Utc::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config: config_json,
+            keys: self.key_health.build_report().await,
+            recent_errors: RecentErrors {
+                statistics: self.error_handler.get_error_statistics().await,
+                entries,
+            },
+            recovery,
+            security,
+            storage,
+        }
+    }
+}
+
+/// 支持包 API 路由（仅管理员）
+pub fn support_bundle_routes(
+    state: SupportBundleState,
+    auth_state: AuthState,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let bundle_state = warp::any().map(move || state.clone());
+
+    // GET /support-bundle - 导出诊断支持包
+    warp::path!("support-bundle")
+        .and(warp::get())
+        .and(admin_only(auth_state))
+        .and(bundle_state)
+        .and_then(get_support_bundle_handler)
+}
+
+async fn get_support_bundle_handler(state: SupportBundleState) -> Result<impl Reply, Rejection> {
+    let bundle = state.build().await;
+    Ok(warp::reply::json(&ApiResponse::success(bundle)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProxyConfig;
+    use crate::error::recovery::create_default_recovery_manager;
+    use crate::load_balancer::key_manager::ApiKey;
+    use crate::load_balancer::UnifiedKeyManager;
+    use crate::persistence::PersistenceConfig;
+    use crate::utils::error::{ErrorContext, ErrorSeverity, ProxyError};
+
+    const API_KEY: &str = "AIzaSyD-support-bundle-raw-key-0001";
+    const JWT_SECRET: &str = "Xk9#mP2$vL7@qR4!nW8&zT1^bY6*cF3%-bundle";
+    const ADMIN_PASSWORD: &str = "Str0ng-Admin-Passw0rd-bundle";
+
+    fn test_config() -> ProxyConfig {
+        let mut config = ProxyConfig::for_test(&["primary"]);
+        config.gemini.api_keys[0].key = API_KEY.to_string();
+        config.auth.jwt_secret = JWT_SECRET.to_string();
+        config.auth.admin_password = ADMIN_PASSWORD.to_string();
+        config
+    }
+
+    async fn test_state() -> SupportBundleState {
+        let config = test_config();
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![ApiKey {
+            key: API_KEY.to_string(),
+            ..ApiKey::for_test("primary")
+        }]));
+        key_manager.mark_key_failed_with_status("primary", 401).await;
+
+        let error_handler = Arc::new(ErrorHandler::new(10));
+        let mut context = ErrorContext::new(
+            ProxyError::ApiKey {
+                key_id: "primary".to_string(),
+                message: format!("上游拒绝密钥 {}", API_KEY),
+            },
+            ErrorSeverity::Medium,
+            "proxy",
+        );
+        context.additional_info.insert("api_key".to_string(), API_KEY.to_string());
+        error_handler.handle_error(context).await;
+
+        // 存储目录不存在时返回空统计
+        let storage = StorageManager::new(PersistenceConfig {
+            data_dir: std::env::temp_dir().join("gemini-proxy-support-bundle-test"),
+            ..PersistenceConfig::default()
+        });
+
+        SupportBundleState::new(
+            ConfigState::new(config, "/nonexistent/proxy.yaml".to_string()),
+            KeyHealthState::new(key_manager),
+            error_handler,
+        )
+        .with_recovery_manager(Arc::new(create_default_recovery_manager()))
+        .with_storage(Arc::new(storage))
+    }
+
+    #[tokio::test]
+    async fn test_bundle_includes_all_sections_without_raw_secrets() {
+        let auth_state = AuthState::new(Arc::new(test_config()));
+        let session_id = auth_state.create_session("admin").await;
+        let token = auth_state.generate_token(&session_id).unwrap();
+        let routes = support_bundle_routes(test_state().await, auth_state)
+            .recover(crate::api::handlers::handle_rejection);
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/support-bundle")
+            .header("authorization", format!("Bearer {}", token))
+            .reply(&routes)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let raw = String::from_utf8(response.body().to_vec()).unwrap();
+        let body: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        let bundle = &body["data"];
+        for section in ["config", "keys", "recent_errors", "recovery", "security", "storage"] {
+            assert!(!bundle[section].is_null(), "缺少 {} 部分", section);
+        }
+        assert_eq!(bundle["keys"]["keys"][0]["key_id"], "primary");
+        assert_eq!(bundle["recent_errors"]["statistics"]["total_errors"], 1);
+
+        for secret in [API_KEY, JWT_SECRET, ADMIN_PASSWORD] {
+            assert!(!raw.contains(secret), "支持包包含原始密钥");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bundle_requires_admin_token() {
+        let auth_state = AuthState::new(Arc::new(test_config()));
+        let routes = support_bundle_routes(test_state().await, auth_state)
+            .recover(crate::api::handlers::handle_rejection);
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/support-bundle")
+            .reply(&routes)
+            .await;
+
+        assert_eq!(response.status(), 401);
+    }
+}
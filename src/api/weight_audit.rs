@@ -237,9 +237,10 @@ async fn rollback_to_snapshot_handler(
             
             // 更新配置文件中的权重
             let mut config = state.config_state.get_config().await;
+            let default_bounds = config.gemini.weight_bounds;
             for api_key in &mut config.gemini.api_keys {
                 if let Some(&new_weight) = weights.get(&api_key.id) {
-                    api_key.weight = new_weight;
+                    api_key.weight = api_key.weight_bounds(&default_bounds).clamp(new_weight);
                 }
             }
             
@@ -1,9 +1,12 @@
 // src/api/weight_management.rs
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use warp::{Filter, Rejection, Reply};
 use crate::load_balancer::UnifiedKeyManager;
+use crate::load_balancer::audit::WeightAuditSystem;
+use crate::persistence::config_history::ConfigHistoryStore;
 use crate::api::config::{ApiResponse, ConfigState};
 
 /// 权重更新请求
@@ -24,6 +27,21 @@ pub struct WeightUpdate {
     pub weight: u32,
 }
 
+/// 权重快照创建请求（记录当前运行中的权重）
+#[derive(Debug, Deserialize)]
+pub struct CreateSnapshotRequest {
+    pub description: String,
+    pub created_by: String,
+}
+
+/// 快照回滚请求
+#[derive(Debug, Deserialize)]
+pub struct RollbackRequest {
+    pub operator: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
 /// 权重分配信息
 #[derive(Debug, Serialize)]
 pub struct WeightDistribution {
@@ -68,6 +86,8 @@ pub struct WeightOptimizationResponse {
 pub struct WeightManagementState {
     config_state: ConfigState,
     key_manager: Arc<RwLock<Option<Arc<UnifiedKeyManager>>>>,
+    weight_audit: Option<Arc<RwLock<WeightAuditSystem>>>,
+    config_history: Option<Arc<ConfigHistoryStore>>,
 }
 
 impl WeightManagementState {
@@ -75,9 +95,23 @@ impl WeightManagementState {
         Self {
             config_state,
             key_manager: Arc::new(RwLock::new(None)),
+            weight_audit: None,
+            config_history: None,
         }
     }
 
+    /// 关联权重审计系统，启用权重快照与回滚
+    pub fn with_weight_audit(mut self, weight_audit: Arc<RwLock<WeightAuditSystem>>) -> Self {
+        self.weight_audit = Some(weight_audit);
+        self
+    }
+
+    /// 关联配置历史，回滚时持久化权重变更
+    pub fn with_config_history(mut self, config_history: Arc<ConfigHistoryStore>) -> Self {
+        self.config_history = Some(config_history);
+        self
+    }
+
     pub async fn set_key_manager(&self, key_manager: Arc<UnifiedKeyManager>) {
         *self.key_manager.write().await = Some(key_manager);
     }
@@ -131,12 +165,28 @@ pub fn weight_management_routes(
         .and(weight_state.clone())
         .and_then(get_weight_distribution_handler);
 
+    // POST /weights/snapshots - 记录当前权重快照
+    let create_snapshot = warp::path!("weights" / "snapshots")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(weight_state.clone())
+        .and_then(create_snapshot_handler);
+
+    // POST /weights/rollback/{snapshot_id} - 回滚到权重快照
+    let rollback = warp::path!("weights" / "rollback" / String)
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(weight_state.clone())
+        .and_then(rollback_to_snapshot_handler);
+
     get_stats
         .or(update_weight)
         .or(batch_update)
         .or(rebalance)
         .or(optimize)
         .or(distribution)
+        .or(create_snapshot)
+        .or(rollback)
 }
 
 // API 处理函数
@@ -197,9 +247,9 @@ async fn update_weight_handler(
     match state.get_key_manager().await {
         Some(key_manager) => {
             match key_manager.update_key_weight(&key_id, request.weight).await {
-                Ok(()) => {
-                    // 同时更新配置文件
-                    if let Err(e) = update_config_weight(&state.config_state, &key_id, request.weight).await {
+                Ok(applied) => {
+                    // 同时更新配置文件（写入钳制后实际生效的权重）
+                    if let Err(e) = update_config_weight(&state.config_state, &key_id, applied).await {
                         tracing::warn!("Failed to update config file: {}", e);
                     }
 
@@ -231,11 +281,11 @@ async fn batch_update_weights_handler(
 
             for update in request.updates {
                 match key_manager.update_key_weight(&update.key_id, update.weight).await {
-                    Ok(()) => {
+                    Ok(applied) => {
                         updated_count += 1;
                         
                         // 同时更新配置文件
-                        if let Err(e) = update_config_weight(&state.config_state, &update.key_id, update.weight).await {
+                        if let Err(e) = update_config_weight(&state.config_state, &update.key_id, applied).await {
                             tracing::warn!("Failed to update config file for key {}: {}", update.key_id, e);
                         }
                     }
@@ -281,11 +331,11 @@ async fn rebalance_weights_handler(state: WeightManagementState) -> Result<impl
             let mut updated_count = 0;
 
             for key in active_keys {
-                if let Ok(()) = key_manager.update_key_weight(&key.id, equal_weight).await {
+                if let Ok(applied) = key_manager.update_key_weight(&key.id, equal_weight).await {
                     updated_count += 1;
                     
                     // 同时更新配置文件
-                    if let Err(e) = update_config_weight(&state.config_state, &key.id, equal_weight).await {
+                    if let Err(e) = update_config_weight(&state.config_state, &key.id, applied).await {
                         tracing::warn!("Failed to update config file for key {}: {}", key.id, e);
                     }
                 }
@@ -385,6 +435,63 @@ async fn get_weight_distribution_handler(
     }
 }
 
+/// 记录当前运行中的权重快照
+async fn create_snapshot_handler(
+    request: CreateSnapshotRequest,
+    state: WeightManagementState,
+) -> Result<impl Reply, Rejection> {
+    let (Some(key_manager), Some(weight_audit)) = (state.get_key_manager().await, state.weight_audit.clone()) else {
+        let response = ApiResponse::<()>::error("KeyManager or weight audit not initialized".to_string());
+        return Ok(warp::reply::json(&response));
+    };
+
+    let weights: HashMap<String, u32> = key_manager
+        .get_all_keys()
+        .await
+        .into_iter()
+        .map(|key| (key.id, key.weight))
+        .collect();
+    match weight_audit.read().await.create_snapshot(&weights, &request.description, &request.created_by).await {
+        Ok(snapshot_id) => Ok(warp::reply::json(&ApiResponse::success(snapshot_id))),
+        Err(e) => Ok(warp::reply::json(&ApiResponse::<()>::error(e))),
+    }
+}
+
+/// 回滚到权重快照：原子地应用快照权重并持久化，随后同步到配置文件
+async fn rollback_to_snapshot_handler(
+    snapshot_id: String,
+    request: RollbackRequest,
+    state: WeightManagementState,
+) -> Result<impl Reply, Rejection> {
+    let (Some(key_manager), Some(weight_audit)) = (state.get_key_manager().await, state.weight_audit.clone()) else {
+        let response = ApiResponse::<()>::error("KeyManager or weight audit not initialized".to_string());
+        return Ok(warp::reply::json(&response));
+    };
+
+    let result = weight_audit
+        .read()
+        .await
+        .rollback_to_snapshot(
+            &snapshot_id,
+            &key_manager,
+            state.config_history.as_deref(),
+            &request.operator,
+            &request.reason,
+        )
+        .await;
+    match result {
+        Ok(weights) => {
+            for (key_id, &weight) in &weights {
+                if let Err(e) = update_config_weight(&state.config_state, key_id, weight).await {
+                    tracing::warn!("Failed to update config file for key {}: {}", key_id, e);
+                }
+            }
+            Ok(warp::reply::json(&ApiResponse::success(weights)))
+        }
+        Err(e) => Ok(warp::reply::json(&ApiResponse::<()>::error(e))),
+    }
+}
+
 // 辅助函数
 
 /// 计算负载均衡有效性评分
@@ -1,16 +1,38 @@
 // src/auth/handler.rs
+use crate::auth::client_keys::{presented_credential, ClientKeyStore, Credential, PROXY_KEY_HEADER, PROXY_PERMISSION};
+use crate::config::AuthFailureMode;
+use crate::security::SharedAuditLog;
+use jsonwebtoken::errors::ErrorKind;
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use pingora::protocols::l4::socket::SocketAddr;
 use pingora::proxy::Session;
 use pingora_error::Result;
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// 令牌摘要（SHA-256），缓存与吊销列表中不保存令牌原文
+type TokenDigest = [u8; 32];
+
 pub struct AuthHandler {
     jwt_secret: String,
     rate_limits: Arc<RwLock<HashMap<String, RateLimit>>>,
     rate_limit_per_minute: u32,
+    failure_mode: AuthFailureMode,
+    audit_log: Option<SharedAuditLog>,
+    /// 校验结果缓存的有效期（为零表示禁用缓存）
+    verification_cache_ttl: Duration,
+    verification_cache_max_entries: usize,
+    /// 已通过完整校验的令牌及其缓存过期时刻
+    verification_cache: Arc<RwLock<HashMap<TokenDigest, Instant>>>,
+    verification_cache_hits: AtomicU64,
+    /// 已吊销的令牌及吊销记录的过期时刻，过期前即使仍在缓存有效期内也拒绝
+    revoked_tokens: Arc<RwLock<HashMap<TokenDigest, Instant>>>,
+    /// 客户端代理 API 密钥（可代替 JWT）
+    client_keys: Arc<ClientKeyStore>,
 }
 
 #[derive(Debug)]
@@ -19,33 +41,238 @@ struct RateLimit {
     reset_time: std::time::Instant,
 }
 
+/// 令牌校验结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenVerification {
+    /// 令牌有效
+    Valid,
+    /// 令牌无效（签名错误、过期、格式错误等）
+    Invalid,
+    /// 校验器内部错误（密钥加载失败等），与令牌本身无关
+    VerifierError(String),
+}
+
 impl AuthHandler {
     pub fn new(jwt_secret: String, rate_limit_per_minute: u32) -> Self {
         Self {
             jwt_secret,
             rate_limits: Arc::new(RwLock::new(HashMap::new())),
             rate_limit_per_minute,
+            failure_mode: AuthFailureMode::default(),
+            audit_log: None,
+            verification_cache_ttl: Duration::ZERO,
+            verification_cache_max_entries: 0,
+            verification_cache: Arc::new(RwLock::new(HashMap::new())),
+            verification_cache_hits: AtomicU64::new(0),
+            revoked_tokens: Arc::new(RwLock::new(HashMap::new())),
+            client_keys: Arc::new(ClientKeyStore::default()),
         }
     }
 
+    /// 允许客户端出示代理 API 密钥（需具备 `proxy` 权限）代替 JWT
+    pub fn with_client_keys(mut self, client_keys: Arc<ClientKeyStore>) -> Self {
+        self.client_keys = client_keys;
+        self
+    }
+
+    /// 启用令牌校验结果缓存：有效期内同一令牌跳过签名与声明校验（缓存不超过令牌自身的过期时间）
+    pub fn with_verification_cache(mut self, ttl: Duration, max_entries: usize) -> Self {
+        self.verification_cache_ttl = ttl;
+        self.verification_cache_max_entries = max_entries;
+        self
+    }
+
+    /// 设置校验器内部错误时的处理方式
+    pub fn with_failure_mode(mut self, failure_mode: AuthFailureMode) -> Self {
+        self.failure_mode = failure_mode;
+        self
+    }
+
+    /// 关联审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 校验请求凭据：JWT 或代理 API 密钥（`X-Proxy-Key` 或非 JWT 的 Bearer 凭据），任一方式通过即可
     pub async fn validate_request(&self, session: &mut Session) -> Result<bool> {
-        let auth_header = session
-            .req_header()
-            .headers
-            .get("authorization")
-            .and_then(|h| h.to_str().ok())
-            .and_then(|s| s.strip_prefix("Bearer "));
-
-        if let Some(token) = auth_header {
-            let key = DecodingKey::from_secret(self.jwt_secret.as_ref());
-            let validation = Validation::new(Algorithm::HS256);
-
-            match decode::<serde_json::Value>(token, &key, &validation) {
-                Ok(_) => Ok(true),
-                Err(_) => Ok(false),
+        let headers = &session.req_header().headers;
+        let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+        let credential = match presented_credential(header(PROXY_KEY_HEADER), header("authorization")) {
+            Some(credential) => credential,
+            None => return Ok(false),
+        };
+
+        let client_ip = self
+            .get_client_id(session)
+            .await
+            .parse()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        match credential {
+            Credential::Jwt(token) => Ok(self.authorize_token(&token, client_ip).await),
+            Credential::ProxyKey(secret) => Ok(self
+                .client_keys
+                .authenticate(&secret, client_ip, self.audit_log.as_ref())
+                .await
+                .is_some_and(|identity| identity.has_permission(PROXY_PERMISSION))),
+        }
+    }
+
+    /// 校验令牌并根据失败模式决定是否放行
+    ///
+    /// 已吊销的令牌总是拒绝；缓存中未过期的令牌直接放行，其余令牌走完整校验，通过后写入缓存
+    pub async fn authorize_token(&self, token: &str, client_ip: IpAddr) -> bool {
+        let digest = openssl::sha::sha256(token.as_bytes());
+        if self.is_revoked(&digest).await {
+            return false;
+        }
+        if self.lookup_verification_cache(&digest).await {
+            self.verification_cache_hits.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+
+        match self.decode_token(token) {
+            Ok(claims) => {
+                self.cache_verification(digest, &claims).await;
+                true
+            }
+            Err(TokenVerification::VerifierError(reason)) => {
+                let allowed = self.failure_mode == AuthFailureMode::Open;
+                self.audit_verifier_error(client_ip, &reason, allowed).await;
+                allowed
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// 校验令牌，区分令牌无效与校验器内部错误
+    pub fn verify_token(&self, token: &str) -> TokenVerification {
+        match self.decode_token(token) {
+            Ok(_) => TokenVerification::Valid,
+            Err(verification) => verification,
+        }
+    }
+
+    /// 完整校验令牌（签名与声明），成功时返回声明
+    fn decode_token(&self, token: &str) -> std::result::Result<serde_json::Value, TokenVerification> {
+        // 未加载到签名密钥属于校验器自身的问题
+        if self.jwt_secret.is_empty() {
+            return Err(TokenVerification::VerifierError("JWT签名密钥未加载".to_string()));
+        }
+
+        let key = DecodingKey::from_secret(self.jwt_secret.as_ref());
+        let validation = Validation::new(Algorithm::HS256);
+
+        match decode::<serde_json::Value>(token, &key, &validation) {
+            Ok(data) => Ok(data.claims),
+            Err(e) => Err(match e.kind() {
+                ErrorKind::InvalidKeyFormat
+                | ErrorKind::InvalidEcdsaKey
+                | ErrorKind::InvalidRsaKey(_)
+                | ErrorKind::RsaFailedSigning
+                | ErrorKind::Crypto(_) => TokenVerification::VerifierError(e.to_string()),
+                _ => TokenVerification::Invalid,
+            }),
+        }
+    }
+
+    /// 吊销令牌：立即从校验缓存中移除，之后的请求一律拒绝；返回令牌此前是否未被吊销
+    ///
+    /// 吊销记录保留到令牌 `exp`（含校验允许的时钟偏差），之后完整校验本身就会拒绝该令牌，
+    /// 记录在下次吊销时清理；读不到 `exp` 的令牌无法通过完整校验，只需覆盖校验缓存有效期
+    pub async fn revoke_token(&self, token: &str) -> bool {
+        let digest = openssl::sha::sha256(token.as_bytes());
+        self.verification_cache.write().await.remove(&digest);
+
+        let retain_for = unverified_expiry(token)
+            .map(|exp| {
+                let leeway = Validation::new(Algorithm::HS256).leeway as i64;
+                Duration::from_secs((exp + leeway - chrono::Utc::now().timestamp()).max(0) as u64)
+            })
+            .unwrap_or(self.verification_cache_ttl);
+
+        let now = Instant::now();
+        let mut revoked = self.revoked_tokens.write().await;
+        revoked.retain(|_, expires_at| now < *expires_at);
+        let newly_revoked = !revoked.contains_key(&digest);
+        if !retain_for.is_zero() {
+            revoked.insert(digest, now + retain_for);
+        }
+        newly_revoked
+    }
+
+    /// 未过期的吊销记录数
+    pub async fn revoked_token_count(&self) -> usize {
+        let now = Instant::now();
+        self.revoked_tokens.read().await.values().filter(|expires_at| now < **expires_at).count()
+    }
+
+    async fn is_revoked(&self, digest: &TokenDigest) -> bool {
+        self.revoked_tokens
+            .read()
+            .await
+            .get(digest)
+            .is_some_and(|expires_at| Instant::now() < *expires_at)
+    }
+
+    /// 通过缓存跳过完整校验的次数
+    pub fn verification_cache_hits(&self) -> u64 {
+        self.verification_cache_hits.load(Ordering::Relaxed)
+    }
+
+    async fn lookup_verification_cache(&self, digest: &TokenDigest) -> bool {
+        if self.verification_cache_ttl.is_zero() {
+            return false;
+        }
+        self.verification_cache
+            .read()
+            .await
+            .get(digest)
+            .is_some_and(|expires_at| Instant::now() < *expires_at)
+    }
+
+    async fn cache_verification(&self, digest: TokenDigest, claims: &serde_json::Value) {
+        if self.verification_cache_ttl.is_zero() {
+            return;
+        }
+        let ttl = match remaining_lifetime(claims) {
+            Some(remaining) => remaining.min(self.verification_cache_ttl),
+            None => self.verification_cache_ttl,
+        };
+        if ttl.is_zero() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut cache = self.verification_cache.write().await;
+        if cache.len() >= self.verification_cache_max_entries {
+            cache.retain(|_, expires_at| now < *expires_at);
+            if cache.len() >= self.verification_cache_max_entries {
+                return;
+            }
+        }
+        cache.insert(digest, now + ttl);
+    }
+
+    /// 将校验器内部错误及放行/拒绝决定记录为安全事件
+    async fn audit_verifier_error(&self, client_ip: IpAddr, reason: &str, allowed: bool) {
+        let decision = if allowed { "放行 (fail-open)" } else { "拒绝 (fail-closed)" };
+        tracing::warn!("令牌校验器内部错误，{}: {}", decision, reason);
+
+        if let Some(audit_log) = &self.audit_log {
+            let result = audit_log
+                .lock()
+                .await
+                .log_security_event(
+                    client_ip,
+                    "令牌校验器内部错误",
+                    &format!("{}; 决定: {}", reason, decision),
+                    if allowed { "Critical" } else { "Warning" },
+                )
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("记录审计日志失败: {}", e);
             }
-        } else {
-            Ok(false)
         }
     }
 
@@ -81,3 +308,175 @@ impl AuthHandler {
             .unwrap_or_else(|| "unknown".to_string())
     }
 }
+
+/// 不校验签名读取令牌的 `exp` 声明，只用于确定吊销记录的保留时间
+fn unverified_expiry(token: &str) -> Option<i64> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.required_spec_claims.clear();
+    let data = decode::<serde_json::Value>(token, &DecodingKey::from_secret(&[]), &validation).ok()?;
+    data.claims.get("exp")?.as_i64()
+}
+
+/// 令牌 `exp` 声明距现在的剩余时间（无 `exp` 时返回 None）
+fn remaining_lifetime(claims: &serde_json::Value) -> Option<Duration> {
+    let exp = claims.get("exp")?.as_i64()?;
+    let remaining = exp.saturating_sub(chrono::Utc::now().timestamp());
+    Some(Duration::from_secs(remaining.max(0) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::{AuditConfig, AuditEventType, AuditLogManager};
+
+    fn create_audit_log() -> SharedAuditLog {
+        Arc::new(tokio::sync::Mutex::new(AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        })))
+    }
+
+    fn test_ip() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))
+    }
+
+    #[tokio::test]
+    async fn test_verifier_error_fail_closed_by_default() {
+        let audit_log = create_audit_log();
+        // 空签名密钥模拟密钥加载失败
+        let handler = AuthHandler::new(String::new(), 60).with_audit_log(audit_log.clone());
+
+        assert!(matches!(handler.verify_token("any-token"), TokenVerification::VerifierError(_)));
+        assert!(!handler.authorize_token("any-token", test_ip()).await);
+
+        let audit = audit_log.lock().await;
+        let events = audit.get_logs_by_type(AuditEventType::SecurityEvent, 10);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].details.as_ref().unwrap().contains("fail-closed"));
+    }
+
+    #[tokio::test]
+    async fn test_verifier_error_fail_open() {
+        let audit_log = create_audit_log();
+        let handler = AuthHandler::new(String::new(), 60)
+            .with_failure_mode(AuthFailureMode::Open)
+            .with_audit_log(audit_log.clone());
+
+        assert!(handler.authorize_token("any-token", test_ip()).await);
+
+        let audit = audit_log.lock().await;
+        let events = audit.get_logs_by_type(AuditEventType::SecurityEvent, 10);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].details.as_ref().unwrap().contains("fail-open"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_token_denied_regardless_of_mode() {
+        let audit_log = create_audit_log();
+        let handler = AuthHandler::new("a-valid-secret-that-is-long-enough-1234".to_string(), 60)
+            .with_failure_mode(AuthFailureMode::Open)
+            .with_audit_log(audit_log.clone());
+
+        assert_eq!(handler.verify_token("not-a-jwt"), TokenVerification::Invalid);
+        assert!(!handler.authorize_token("not-a-jwt", test_ip()).await);
+
+        // 普通的令牌无效不属于校验器错误，不记录安全事件
+        let audit = audit_log.lock().await;
+        assert!(audit.get_logs_by_type(AuditEventType::SecurityEvent, 10).is_empty());
+    }
+
+    fn cached_handler() -> AuthHandler {
+        AuthHandler::new("a-valid-secret-that-is-long-enough-1234".to_string(), 60)
+            .with_verification_cache(Duration::from_secs(30), 100)
+    }
+
+    fn issue_token(handler: &AuthHandler, subject: &str) -> String {
+        issue_token_expiring(handler, subject, chrono::Utc::now().timestamp() + 3600)
+    }
+
+    fn issue_token_expiring(handler: &AuthHandler, subject: &str, exp: i64) -> String {
+        let claims = serde_json::json!({
+            "sub": subject,
+            "exp": exp,
+        });
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(handler.jwt_secret.as_ref()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cached_token_skips_full_verification() {
+        let handler = cached_handler();
+        let token = issue_token(&handler, "client-a");
+
+        assert!(handler.authorize_token(&token, test_ip()).await);
+        assert_eq!(handler.verification_cache_hits(), 0);
+        assert!(handler.authorize_token(&token, test_ip()).await);
+        assert!(handler.authorize_token(&token, test_ip()).await);
+        assert_eq!(handler.verification_cache_hits(), 2);
+
+        // 无效令牌不进入缓存
+        assert!(!handler.authorize_token("not-a-jwt", test_ip()).await);
+        assert!(!handler.authorize_token("not-a-jwt", test_ip()).await);
+        assert_eq!(handler.verification_cache_hits(), 2);
+
+        // 未启用缓存时每次都完整校验
+        let uncached = AuthHandler::new(handler.jwt_secret.clone(), 60);
+        assert!(uncached.authorize_token(&token, test_ip()).await);
+        assert!(uncached.authorize_token(&token, test_ip()).await);
+        assert_eq!(uncached.verification_cache_hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_token_rejected_despite_cache() {
+        let handler = cached_handler();
+        let revoked = issue_token(&handler, "client-a");
+        let other = issue_token(&handler, "client-b");
+
+        assert!(handler.authorize_token(&revoked, test_ip()).await);
+        assert!(handler.authorize_token(&other, test_ip()).await);
+
+        handler.revoke_token(&revoked).await;
+        assert!(!handler.authorize_token(&revoked, test_ip()).await);
+        assert!(handler.authorize_token(&other, test_ip()).await);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_tokens_kept_until_expiry() {
+        let handler = cached_handler();
+        let active = issue_token(&handler, "client-a");
+        assert!(handler.revoke_token(&active).await);
+        assert!(!handler.revoke_token(&active).await);
+
+        // 已过期（超出时钟偏差）的令牌完整校验本就拒绝，不保留吊销记录
+        let expired_at = chrono::Utc::now().timestamp() - 3600;
+        for subject in ["client-b", "client-c", "client-d"] {
+            let expired = issue_token_expiring(&handler, subject, expired_at);
+            assert!(handler.revoke_token(&expired).await);
+            assert!(!handler.authorize_token(&expired, test_ip()).await);
+        }
+        assert_eq!(handler.revoked_tokens.read().await.len(), 1);
+        assert_eq!(handler.revoked_token_count().await, 1);
+
+        // 到期的吊销记录在下次吊销时清理
+        handler.revoked_tokens.write().await.insert([0; 32], Instant::now());
+        handler.revoke_token(&issue_token(&handler, "client-e")).await;
+        assert_eq!(handler.revoked_tokens.read().await.len(), 2);
+        assert!(!handler.authorize_token(&active, test_ip()).await);
+    }
+
+    #[test]
+    fn test_cache_lifetime_bounded_by_token_expiry() {
+        let exp = chrono::Utc::now().timestamp() + 10;
+        let remaining = remaining_lifetime(&serde_json::json!({ "exp": exp })).unwrap();
+        assert!(remaining <= Duration::from_secs(10));
+        assert_eq!(remaining_lifetime(&serde_json::json!({ "exp": 0 })), Some(Duration::ZERO));
+        assert_eq!(remaining_lifetime(&serde_json::json!({ "sub": "x" })), None);
+    }
+}
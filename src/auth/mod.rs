@@ -1,2 +1,3 @@
+pub mod client_keys;
 pub mod handler;
 pub use handler::*;
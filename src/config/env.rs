@@ -0,0 +1,234 @@
+// src/config/env.rs
+//! 配置文件中的环境变量替换
+//!
+//! 先把 YAML 解析为值树，再把字符串值中的 `${NAME}` 与 `${NAME:-default}` 替换为进程环境变量的值，
+//! 变量值不会再被当作 YAML 解析，含 `:`、`#`、引号或换行的值不会破坏配置结构。密钥可以放在环境变量
+//! 或密钥管理服务中，配置文件本身纳入版本控制。`${NAME:-default}` 在变量未设置或为空时使用默认值；
+//! `${NAME}` 的变量未设置时报错。`$${` 表示字面量 `${`，注释不做替换
+//!
+//! 管理接口回写配置时用 [`merge_changed_values`] 只把变化的字段写回未替换的原始内容，
+//! 未变化的占位符保持原样，环境变量中的密钥不会落盘
+
+use crate::error::GeminiProxyError;
+use serde_yaml::Value;
+
+/// 用进程环境变量替换配置值中的占位符
+pub fn expand_env_placeholders(value: &mut Value) -> crate::error::Result<()> {
+    expand_value(value, "", &|name| std::env::var(name).ok())
+}
+
+/// 递归替换值树中所有字符串值的占位符，`path` 用于在错误中指出字段位置
+fn expand_value(value: &mut Value, path: &str, lookup: &impl Fn(&str) -> Option<String>) -> crate::error::Result<()> {
+    match value {
+        Value::String(text) => {
+            if text.contains("${") {
+                *text = expand_str(text, path, lookup)?;
+            }
+        }
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                expand_value(item, &format!("{}[{}]", path, index), lookup)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, item) in mapping.iter_mut() {
+                let key = key.as_str().map(str::to_string).unwrap_or_else(|| format!("{:?}", key));
+                let child = if path.is_empty() { key } else { format!("{}.{}", path, key) };
+                expand_value(item, &child, lookup)?;
+            }
+        }
+        Value::Tagged(tagged) => expand_value(&mut tagged.value, path, lookup)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+fn expand_str(text: &str, path: &str, lookup: &impl Fn(&str) -> Option<String>) -> crate::error::Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        // `$${` 转义为字面量 `${`
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start - 1]);
+            expanded.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            env_error(format!("字段 {} 的环境变量占位符缺少结尾的 '}}'", path), None)
+        })?;
+        let placeholder = &rest[start + 2..start + end];
+        let (name, default) = match placeholder.split_once(":-") {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (placeholder.trim(), None),
+        };
+        if !is_valid_name(name) {
+            return Err(env_error(format!("字段 {} 的环境变量名无效: '{}'", path, name), Some(name)));
+        }
+        let value = match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) => {
+                return Err(env_error(
+                    format!("配置引用的环境变量 {} 未设置（字段 {}），可使用 ${{{}:-默认值}} 提供默认值", name, path, name),
+                    Some(name),
+                ));
+            }
+        };
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// 把 `updated` 相对 `baseline` 变化的字段写入 `source`
+///
+/// `source` 是未替换占位符的原始配置，`baseline` 是它替换并补齐默认值后的完整配置；
+/// 只有值变化的字段会被覆盖，其余字段（包括占位符）保持原样。列表元素按 `id` 对应，
+/// 没有 `id` 时按位置对应
+pub fn merge_changed_values(source: &mut Value, baseline: &Value, updated: &Value) {
+    if baseline == updated {
+        return;
+    }
+    match (source, baseline, updated) {
+        (Value::Mapping(source), Value::Mapping(baseline), Value::Mapping(updated)) => {
+            for (key, new_value) in updated {
+                let old_value = baseline.get(key);
+                if old_value == Some(new_value) {
+                    continue;
+                }
+                match (source.get_mut(key), old_value) {
+                    (Some(source_value), Some(old_value)) => merge_changed_values(source_value, old_value, new_value),
+                    _ => {
+                        source.insert(key.clone(), new_value.clone());
+                    }
+                }
+            }
+            let removed: Vec<Value> = baseline.keys().filter(|key| !updated.contains_key(*key)).cloned().collect();
+            for key in removed {
+                source.remove(&key);
+            }
+        }
+        (Value::Sequence(source), Value::Sequence(baseline), Value::Sequence(updated)) => {
+            let merged = updated
+                .iter()
+                .enumerate()
+                .map(|(index, new_value)| {
+                    let old_index = match item_id(new_value) {
+                        Some(id) => baseline.iter().position(|old| item_id(old) == Some(id)),
+                        None => (index < baseline.len()).then_some(index),
+                    };
+                    match old_index.and_then(|i| source.get(i).map(|s| (s.clone(), &baseline[i]))) {
+                        Some((mut source_value, old_value)) => {
+                            merge_changed_values(&mut source_value, old_value, new_value);
+                            source_value
+                        }
+                        None => new_value.clone(),
+                    }
+                })
+                .collect();
+            *source = merged;
+        }
+        (source, _, updated) => *source = updated.clone(),
+    }
+}
+
+fn item_id(value: &Value) -> Option<&Value> {
+    value.as_mapping().and_then(|mapping| mapping.get("id"))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn env_error(message: String, variable: Option<&str>) -> GeminiProxyError {
+    let error = GeminiProxyError::config_with_context(message, "config", "expand_env");
+    match variable {
+        Some(variable) => error.with_metadata("env_var", variable),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "JWT_SECRET" => Some("from-env-secret".to_string()),
+            "EMPTY" => Some(String::new()),
+            "TRICKY" => Some("a: b # c\n\"quoted\"".to_string()),
+            _ => None,
+        }
+    }
+
+    fn expand(content: &str) -> crate::error::Result<Value> {
+        let mut value: Value = serde_yaml::from_str(content).unwrap();
+        expand_value(&mut value, "", &lookup)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_placeholders_expanded_with_defaults() {
+        let content = "auth:\n  jwt_secret: \"${JWT_SECRET}\"\n  admin_password: \"${ADMIN_PASSWORD:-changeme}\"\n  \
+                       label: \"${EMPTY:-fallback}/${EMPTY}\"\n  literal: \"$${NOT_EXPANDED}\"\n  \
+                       tricky: \"${TRICKY}\"\n  # key: \"${COMMENTED_OUT}\"\n";
+        let expanded = expand(content).unwrap();
+        let auth = &expanded["auth"];
+
+        assert_eq!(auth["jwt_secret"], Value::from("from-env-secret"));
+        assert_eq!(auth["admin_password"], Value::from("changeme"));
+        assert_eq!(auth["label"], Value::from("fallback/"));
+        assert_eq!(auth["literal"], Value::from("${NOT_EXPANDED}"));
+        // 变量值原样成为字符串，不会改变配置结构
+        assert_eq!(auth["tricky"], Value::from("a: b # c\n\"quoted\""));
+        assert_eq!(auth.as_mapping().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_missing_required_variable_names_variable() {
+        let content = "gemini:\n  api_keys:\n    - key: \"${GEMINI_API_KEY_1}\"\n";
+        let error = expand(content).unwrap_err();
+
+        assert!(matches!(error, GeminiProxyError::Config { .. }));
+        assert!(error.to_string().contains("GEMINI_API_KEY_1"));
+        assert!(error.to_string().contains("gemini.api_keys[0].key"));
+        assert!(expand("key: \"${UNTERMINATED\"\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_keeps_placeholders_of_unchanged_fields() {
+        let mut source: Value = serde_yaml::from_str(
+            "gemini:\n  api_keys:\n    - id: a\n      key: \"${KEY_A}\"\n      weight: 1\n    \
+             - id: b\n      key: \"${KEY_B}\"\n      weight: 1\nauth:\n  jwt_secret: \"${JWT_SECRET}\"\n",
+        )
+        .unwrap();
+        let baseline: Value = serde_yaml::from_str(
+            "gemini:\n  api_keys:\n    - id: a\n      key: secret-a\n      weight: 1\n    \
+             - id: b\n      key: secret-b\n      weight: 1\n  timeout_seconds: 30\nauth:\n  jwt_secret: from-env-secret\n",
+        )
+        .unwrap();
+        let updated: Value = serde_yaml::from_str(
+            "gemini:\n  api_keys:\n    - id: b\n      key: secret-b\n      weight: 5\n    \
+             - id: c\n      key: new-key\n      weight: 1\n  timeout_seconds: 30\nauth:\n  jwt_secret: from-env-secret\n",
+        )
+        .unwrap();
+
+        merge_changed_values(&mut source, &baseline, &updated);
+        let written = serde_yaml::to_string(&source).unwrap();
+
+        assert!(!written.contains("secret-b"));
+        assert!(!written.contains("from-env-secret"));
+        assert!(!written.contains("timeout_seconds"));
+        assert_eq!(source["gemini"]["api_keys"][0]["key"], Value::from("${KEY_B}"));
+        assert_eq!(source["gemini"]["api_keys"][0]["weight"], Value::from(5));
+        assert_eq!(source["gemini"]["api_keys"][1]["key"], Value::from("new-key"));
+        assert_eq!(source["gemini"]["api_keys"].as_sequence().unwrap().len(), 2);
+        assert_eq!(source["auth"]["jwt_secret"], Value::from("${JWT_SECRET}"));
+    }
+}
@@ -0,0 +1,29 @@
+// src/config/live.rs
+//! 运行中可整体替换的配置
+//!
+//! 读取方每次取得一份完整配置的快照（`Arc`），替换时整体交换，读取方不会看到新旧字段混合的配置
+
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug)]
+pub struct LiveConfig<T> {
+    current: RwLock<Arc<T>>,
+}
+
+impl<T> LiveConfig<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(value)),
+        }
+    }
+
+    /// 当前配置的快照
+    pub fn load(&self) -> Arc<T> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 整体替换配置，之后的读取立即看到新配置
+    pub fn store(&self, value: T) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(value);
+    }
+}
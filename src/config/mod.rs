@@ -1,5 +1,8 @@
+pub mod env;
+pub mod live;
 pub mod settings;
 pub mod validation;
 
+pub use live::LiveConfig;
 pub use settings::*;
 pub use validation::ConfigValidator;
@@ -1,32 +1,299 @@
+use crate::config::env::{expand_env_placeholders, merge_changed_values};
+use crate::persistence::PersistenceConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 
+/// 代理配置
+///
+/// 只有 `gemini` 部分是必需的；省略 `server`、`auth`、`metrics` 等部分（或其中的字段）时
+/// 使用各自 `Default` 实现中的默认值
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyConfig {
+    #[serde(default)]
     pub server: ServerConfig,
     pub gemini: GeminiConfig,
+    /// 省略时不启用认证（无法为 JWT 密钥和管理员密码提供安全的默认值）
+    #[serde(default)]
     pub auth: AuthConfig,
+    #[serde(default)]
     pub metrics: MetricsConfig,
+    /// 健康检查输出配置
+    #[serde(default)]
+    pub health: HealthConfig,
+    /// 审计日志配置
+    #[serde(default)]
+    pub audit: AuditLogConfig,
+    /// 安全配置
+    #[serde(default)]
+    pub security: SecurityConfig,
+    /// 数据持久化配置
+    #[serde(default)]
+    pub persistence: PersistenceConfig,
 }
 
+/// 安全配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecurityConfig {
+    /// 客户端 IP 访问规则
+    pub ip_rules: IpRulesConfig,
+    /// 受信任的反向代理（CIDR 或单个地址）：只有来自这些地址的管理 API 请求才采用 `X-Forwarded-For` 中的客户端地址
+    pub trusted_proxies: Vec<String>,
+}
+
+/// 客户端 IP 访问规则（CIDR 或单个地址，支持 IPv4 与 IPv6）：拒绝列表优先，允许列表非空时只允许其中的地址
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpRulesConfig {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+/// 审计日志配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLogConfig {
+    /// 审计事件 ID 前缀
+    #[serde(default = "default_audit_event_id_prefix")]
+    pub event_id_prefix: String,
+    /// 存在请求 ID 时直接作为审计事件 ID，便于与错误日志、指标关联
+    #[serde(default)]
+    pub reuse_request_id: bool,
+    /// 审计日志写入失败时的死信文件
+    #[serde(default)]
+    pub dead_letter: DeadLetterConfig,
+    /// 敏感端点的只读访问审计
+    #[serde(default)]
+    pub read_access: ReadAccessAuditConfig,
+}
+
+/// 只读访问审计配置：记录对指定端点的 GET/HEAD 请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadAccessAuditConfig {
+    pub enabled: bool,
+    /// 需要审计的 API 路径前缀
+    pub endpoints: Vec<String>,
+}
+
+impl Default for ReadAccessAuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoints: vec![
+                "/api/config".to_string(),
+                "/api/keys".to_string(),
+                "/api/audit".to_string(),
+            ],
+        }
+    }
+}
+
+/// 死信文件配置：审计/错误日志写入失败的条目保存到本地文件，由后台任务定期重新投递
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadLetterConfig {
+    pub enabled: bool,
+    /// 死信文件路径（JSON Lines）
+    pub path: String,
+    /// 重新投递间隔（秒）
+    pub retry_interval_seconds: u64,
+    /// 最多保留的条目数，超出后丢弃最早的条目
+    pub max_entries: usize,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: "logs/dead_letter.jsonl".to_string(),
+            retry_interval_seconds: 60,
+            max_entries: 10000,
+        }
+    }
+}
+
+fn default_audit_event_id_prefix() -> String {
+    "audit_".to_string()
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            event_id_prefix: default_audit_event_id_prefix(),
+            reuse_request_id: false,
+            dead_letter: DeadLetterConfig::default(),
+            read_access: ReadAccessAuditConfig::default(),
+        }
+    }
+}
+
+/// 健康检查配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// 在 `/health` 中输出组件版本与启用的模块（会暴露内部信息，默认关闭）
+    #[serde(default)]
+    pub verbose: bool,
+    /// 就绪检查的上游连通性探测（默认关闭，仅按健康密钥数判断就绪）
+    #[serde(default)]
+    pub upstream_probe: UpstreamProbeConfig,
+}
+
+/// 上游连通性探测：定期用一个可用密钥请求模型列表，结果缓存供 `/health/ready` 使用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamProbeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 探测间隔（秒）
+    #[serde(default = "default_upstream_probe_interval_seconds")]
+    pub interval_seconds: u64,
+    /// 单次探测超时（秒）
+    #[serde(default = "default_upstream_probe_timeout_seconds")]
+    pub timeout_seconds: u64,
+    /// 探测请求的路径（轻量的模型列表请求）
+    #[serde(default = "default_upstream_probe_path")]
+    pub path: String,
+}
+
+fn default_upstream_probe_interval_seconds() -> u64 {
+    30
+}
+
+fn default_upstream_probe_timeout_seconds() -> u64 {
+    5
+}
+
+fn default_upstream_probe_path() -> String {
+    "/v1beta/models?pageSize=1".to_string()
+}
+
+impl Default for UpstreamProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: default_upstream_probe_interval_seconds(),
+            timeout_seconds: default_upstream_probe_timeout_seconds(),
+            path: default_upstream_probe_path(),
+        }
+    }
+}
+
+/// 服务器配置，默认监听 0.0.0.0:8080、4 个工作线程、1000 个连接，请求头上限 64KB / 100 个，请求体上限 10MB，
+/// 不限制在途请求数，停机时最多等待 30 秒排空在途请求，不启用 TLS
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub workers: usize,
     pub max_connections: usize,
+    /// 入站请求头总字节数上限（名称与值长度之和），超出返回 431
+    pub max_header_bytes: usize,
+    /// 入站请求头数量上限，超出返回 431
+    pub max_header_count: usize,
+    /// 全进程在途请求数上限（与各密钥的限额无关），达到上限时返回 503，0 表示不限制
+    pub max_inflight_requests: usize,
+    /// 入站请求体字节数上限，超出返回 413（按 content-length 提前拒绝，分块请求在转发中途拒绝）
+    pub max_request_body_bytes: usize,
+    /// 用于用量解析的上游响应体缓冲上限，超出后停止缓冲（响应仍原样转发）
+    pub max_response_buffer_bytes: usize,
+    /// 收到 SIGTERM/SIGINT 后等待在途请求完成的最长时间（秒），期间拒绝新请求并在 `/health` 报告 draining
+    pub shutdown_grace_period_seconds: u64,
     pub tls: TlsConfig,
 }
 
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            workers: 4,
+            max_connections: 1000,
+            max_header_bytes: 64 * 1024,
+            max_header_count: 100,
+            max_inflight_requests: 0,
+            max_request_body_bytes: 10 * 1024 * 1024,
+            max_response_buffer_bytes: 10 * 1024 * 1024,
+            shutdown_grace_period_seconds: 30,
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AcmeConfig {
     pub enabled: bool,
     pub domains: Vec<String>,
     pub email: String,
     pub directory_url: String,
+    /// 域名验证的最大并发数
+    #[serde(default = "default_acme_max_concurrent_validations")]
+    pub max_concurrent_validations: usize,
+    /// 验证方式，默认 HTTP-01（需要监听 80 端口）；通配符证书需使用 DNS-01
+    #[serde(default)]
+    pub challenge: AcmeChallengeType,
+    /// DNS-01 验证的 DNS 服务商配置
+    #[serde(default)]
+    pub dns: Option<AcmeDnsConfig>,
+}
+
+fn default_acme_max_concurrent_validations() -> usize {
+    2
+}
+
+/// ACME 验证方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcmeChallengeType {
+    /// 在 80 端口响应 `/.well-known/acme-challenge/` 请求
+    #[default]
+    Http01,
+    /// 在 `_acme-challenge.<域名>` 放置 TXT 记录
+    Dns01,
+}
+
+/// DNS-01 验证配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcmeDnsConfig {
+    pub provider: DnsProviderKind,
+    /// Cloudflare 凭据，`provider: cloudflare` 时必填
+    #[serde(default)]
+    pub cloudflare: Option<CloudflareDnsConfig>,
+    /// 等待 TXT 记录生效的最长时间（秒）
+    #[serde(default = "default_acme_propagation_timeout")]
+    pub propagation_timeout_seconds: u64,
+    /// 检查 TXT 记录是否生效的间隔（秒）
+    #[serde(default = "default_acme_propagation_poll_interval")]
+    pub propagation_poll_interval_seconds: u64,
+}
+
+fn default_acme_propagation_timeout() -> u64 {
+    180
+}
+
+fn default_acme_propagation_poll_interval() -> u64 {
+    10
+}
+
+/// DNS 服务商
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsProviderKind {
+    Cloudflare,
 }
 
+/// Cloudflare DNS 凭据
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudflareDnsConfig {
+    /// 具有 `Zone.DNS:Edit` 权限的 API 令牌
+    pub api_token: String,
+    /// 域名所在区域的 ID
+    pub zone_id: String,
+}
+
+/// TLS 配置，默认不启用，证书路径为 `certs/cert.pem` 与 `certs/key.pem`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TlsConfig {
     pub enabled: bool,
     pub cert_path: String,
@@ -34,22 +301,1253 @@ pub struct TlsConfig {
     pub acme: Option<AcmeConfig>,
 }
 
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: "certs/cert.pem".to_string(),
+            key_path: "certs/key.pem".to_string(),
+            acme: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeminiConfig {
     pub api_keys: Vec<ApiKeyConfig>,
+    #[serde(default = "default_gemini_base_url")]
     pub base_url: String,
+    #[serde(default = "default_gemini_timeout_seconds")]
     pub timeout_seconds: u64,
+    /// 非流式请求的上游超时（秒），未设置时使用 `timeout_seconds`
+    #[serde(default)]
+    pub request_timeout_seconds: Option<u64>,
+    /// 流式请求的上游总时长上限（秒）
+    #[serde(default = "default_stream_timeout_seconds")]
+    pub stream_timeout_seconds: u64,
+    /// 流式请求相邻两个数据块之间的最长间隔（秒）
+    #[serde(default = "default_stream_idle_timeout_seconds")]
+    pub stream_idle_timeout_seconds: u64,
+    /// 密钥粘性窗口（毫秒），同一客户端在窗口内复用同一密钥，0 表示禁用
+    #[serde(default)]
+    pub key_stickiness_window_ms: u64,
+    /// 密钥软限额阈值（每分钟限额的利用率，例如 0.9），达到后优先轮换到其他密钥，0 表示禁用
+    #[serde(default)]
+    pub key_soft_limit_ratio: f64,
+    /// 请求的模型在密钥 `preferred_models` 中时，该密钥有效权重的放大倍数（1 表示不放大）
+    #[serde(default = "default_preferred_model_boost")]
+    pub preferred_model_boost: f64,
+    /// 上游响应状态码改写规则
+    #[serde(default)]
+    pub status_rewrites: Vec<StatusRewriteRule>,
+    /// 外部密钥来源（例如 Kubernetes Secret 挂载目录）
+    #[serde(default)]
+    pub key_source: Option<KeySourceConfig>,
+    /// 模型价格表（按模型名或模型名前缀），用于成本估算
+    #[serde(default)]
+    pub model_costs: HashMap<String, ModelCost>,
+    /// 通过请求头覆盖目标模型（用于 A/B 测试）
+    #[serde(default)]
+    pub model_override: ModelOverrideConfig,
+    /// 流量分类规则（按顺序匹配，首个命中的规则生效）
+    #[serde(default)]
+    pub traffic_classes: Vec<TrafficClassRule>,
+    /// 确定性请求（temperature 为 0）的响应缓存
+    #[serde(default)]
+    pub response_cache: ResponseCacheConfig,
+    /// 重复请求去重窗口（客户端误重试时返回首个请求的响应）
+    #[serde(default)]
+    pub dedup: DedupConfig,
+    /// 按密钥预先建立并保持空闲的上游连接
+    #[serde(default)]
+    pub warm_pool: WarmPoolConfig,
+    /// 就绪检查要求的最少健康密钥数，低于该值时 `/health/ready` 报告未就绪
+    #[serde(default = "default_min_healthy_keys")]
+    pub min_healthy_keys: usize,
+    /// 各类卸载（拒绝请求）原因对应的响应状态码与 Retry-After
+    #[serde(default)]
+    pub shed_responses: ShedResponsesConfig,
+    /// 运维手动停用的密钥 ID（通过 `/api/keys/{id}/disable` 持久化）
+    #[serde(default)]
+    pub disabled_keys: Vec<String>,
+    /// 请求 ID（关联 ID）的读取与向上游传播
+    #[serde(default)]
+    pub request_id: RequestIdConfig,
+    /// 账号级每分钟请求配额；设置后各密钥的每分钟限额按权重比例从该配额分配
+    /// （忽略密钥自身的 `max_requests_per_minute`），权重变化时重新计算
+    #[serde(default)]
+    pub account_quota_per_minute: Option<u32>,
+    /// 上下文窗口预检：估算提示词 token 数，拒绝超过模型上下文上限的请求
+    #[serde(default)]
+    pub context_preflight: ContextPreflightConfig,
+    /// 运行中调整密钥权重时的生效方式（立即生效或在窗口内逐步过渡）
+    #[serde(default)]
+    pub weight_change: WeightChangeConfig,
+    /// 请求方法与路径允许列表，启用后拒绝（403）未命中任何规则的请求
+    #[serde(default)]
+    pub route_allowlist: RouteAllowlistConfig,
+    /// 每个请求必须携带的请求头（例如 `X-Team`），缺少任一请求头时返回 400
+    #[serde(default)]
+    pub required_headers: Vec<String>,
+    /// 从非流式 JSON 响应中移除的字段路径（`.` 分隔，`*` 匹配数组元素或对象的值）
+    #[serde(default)]
+    pub strip_response_fields: Vec<String>,
+    /// 按请求字段哈希选择密钥（提高上游缓存命中率）
+    #[serde(default)]
+    pub hash_routing: HashRoutingConfig,
+    /// 多区域部署时按测得延迟优先选择区域
+    #[serde(default)]
+    pub region_routing: RegionRoutingConfig,
+    /// 上游返回 200 但响应体为空、为错误结构或被拦截时按软失败处理
+    #[serde(default)]
+    pub soft_failure: SoftFailureConfig,
+    /// 将 Gemini 错误响应（配额、安全拦截、参数错误等）映射为稳定的规范化错误码
+    #[serde(default)]
+    pub error_codes: ErrorCodesConfig,
+    /// 全局默认的权重上下限，密钥未单独设置 `weight_min`/`weight_max` 时使用
+    #[serde(default)]
+    pub weight_bounds: WeightBounds,
+    /// 每个请求结束后输出一条汇总调用方、密钥、模型与令牌用量的记录（成本归属）
+    #[serde(default)]
+    pub request_record: RequestRecordConfig,
+    /// 上游响应中重复头与 trailer 的规范化
+    #[serde(default)]
+    pub response_headers: ResponseHeaderConfig,
+    /// 根据上游 429 自适应调整各密钥的每分钟限额
+    #[serde(default)]
+    pub adaptive_rate_limit: AdaptiveRateLimitConfig,
+    /// 上游返回可重试状态时在同一请求内换用其他密钥重试
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    /// 上游返回 429 时让密钥暂时退出选择
+    #[serde(default)]
+    pub key_cooldown: KeyCooldownConfig,
+    /// 最近失败的密钥在衰减窗口内降低选择权重
+    #[serde(default)]
+    pub failure_penalty: FailurePenaltyConfig,
+    /// 模型持续过载时按降级链改用其他模型
+    #[serde(default)]
+    pub model_fallback_chain: ModelFallbackConfig,
+    /// 按密钥的熔断器参数
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// 流式响应的合并转发阈值
+    #[serde(default)]
+    pub streaming_buffer: StreamingBufferConfig,
+    /// 密钥调度方式
+    #[serde(default)]
+    pub scheduling: SchedulingConfig,
+}
+
+/// 请求汇总记录配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestRecordConfig {
+    /// 是否输出到访问日志（target 为 `gemini_proxy::request_record`）
+    #[serde(default = "default_request_record_enabled")]
+    pub enabled: bool,
+    /// 是否同时累加 `gemini_request_tokens_total{caller,key_id,model,kind}` 指标（调用方较多时注意标签基数）
+    #[serde(default)]
+    pub metrics: bool,
+}
+
+fn default_preferred_model_boost() -> f64 {
+    3.0
+}
+
+fn default_request_record_enabled() -> bool {
+    true
+}
+
+impl Default for RequestRecordConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_request_record_enabled(),
+            metrics: false,
+        }
+    }
+}
+
+/// 权重上下限，未设置的一侧不限制；所有权重变更（手动设置、归一化、优化器应用等）都会被钳制到该范围内
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightBounds {
+    #[serde(default)]
+    pub min: Option<u32>,
+    #[serde(default)]
+    pub max: Option<u32>,
+}
+
+impl WeightBounds {
+    /// 密钥自身的上下限优先，未设置的一侧回退到默认值
+    pub fn resolve(key_min: Option<u32>, key_max: Option<u32>, defaults: &WeightBounds) -> Self {
+        Self {
+            min: key_min.or(defaults.min),
+            max: key_max.or(defaults.max),
+        }
+    }
+
+    /// 将权重钳制到上下限内
+    pub fn clamp(&self, weight: u32) -> u32 {
+        let weight = self.min.map_or(weight, |min| weight.max(min));
+        self.max.map_or(weight, |max| weight.min(max))
+    }
+}
+
+/// 上游响应中重复出现的响应头的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateHeaderPolicy {
+    /// 原样转发
+    #[default]
+    Keep,
+    /// 合并为一个以 `, ` 分隔的值（`set-cookie` 除外）
+    Merge,
+    /// 只保留第一个值
+    First,
+    /// 只保留最后一个值
+    Last,
+}
+
+/// 上游响应头规范化配置，默认原样转发重复头与 trailer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseHeaderConfig {
+    /// 重复响应头的处理方式
+    #[serde(default)]
+    pub duplicates: DuplicateHeaderPolicy,
+    /// 总是从上游响应中移除的响应头（不区分大小写）
+    #[serde(default)]
+    pub drop: Vec<String>,
+    /// 是否向客户端转发上游的 trailer
+    #[serde(default = "default_forward_trailers")]
+    pub forward_trailers: bool,
+}
+
+fn default_forward_trailers() -> bool {
+    true
+}
+
+impl Default for ResponseHeaderConfig {
+    fn default() -> Self {
+        Self {
+            duplicates: DuplicateHeaderPolicy::default(),
+            drop: Vec::new(),
+            forward_trailers: default_forward_trailers(),
+        }
+    }
+}
+
+/// 上游软失败配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoftFailureConfig {
+    /// 是否启用检测；启用后软失败计入密钥失败与 `gemini_upstream_soft_failure_total` 指标
+    #[serde(default)]
+    pub enabled: bool,
+    /// 是否将提示词被拦截（`promptFeedback.blockReason`）的响应视为软失败
+    #[serde(default = "default_soft_failure_detect_blocked")]
+    pub detect_blocked: bool,
+    /// 是否仍把软失败响应返回给客户端；为 false 时声明为空响应体的 200 改写为可重试的 502
+    #[serde(default = "default_soft_failure_return_to_client")]
+    pub return_to_client: bool,
+}
+
+fn default_soft_failure_detect_blocked() -> bool {
+    true
+}
+
+fn default_soft_failure_return_to_client() -> bool {
+    true
+}
+
+impl Default for SoftFailureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            detect_blocked: default_soft_failure_detect_blocked(),
+            return_to_client: default_soft_failure_return_to_client(),
+        }
+    }
+}
+
+/// 错误码规范化配置
+///
+/// 规则按顺序匹配，首个命中的规则生效；错误响应未命中任何规则时使用 `fallback_code`。
+/// 配置 `rules` 会整体替换内置规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorCodesConfig {
+    /// 是否启用；启用后错误响应体注入 `error.normalized_code` 并计入 `gemini_errors_total{code}` 指标
+    #[serde(default = "default_error_codes_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_error_code_rules")]
+    pub rules: Vec<ErrorCodeRule>,
+    /// 未命中任何规则的错误使用的错误码
+    #[serde(default = "default_error_fallback_code")]
+    pub fallback_code: String,
+}
+
+/// 错误码映射规则，设置的条件全部满足时命中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ErrorCodeRule {
+    /// 规范化错误码
+    pub code: String,
+    /// HTTP 状态码（响应体为错误结构时取 `error.code`）
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Gemini 错误状态 `error.status`，例如 `RESOURCE_EXHAUSTED`（不区分大小写）
+    #[serde(default)]
+    pub gemini_status: Option<String>,
+    /// `error.message` 包含的文本（不区分大小写）
+    #[serde(default)]
+    pub message_contains: Option<String>,
+    /// 提示词拦截原因 `promptFeedback.blockReason`，`*` 匹配任意原因
+    #[serde(default)]
+    pub block_reason: Option<String>,
+    /// 候选结果的结束原因 `candidates[].finishReason`，例如 `SAFETY`
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+impl ErrorCodeRule {
+    /// 规则是否至少设置了一个条件
+    pub fn has_condition(&self) -> bool {
+        self.status.is_some()
+            || self.gemini_status.is_some()
+            || self.message_contains.is_some()
+            || self.block_reason.is_some()
+            || self.finish_reason.is_some()
+    }
+}
+
+fn default_error_codes_enabled() -> bool {
+    true
+}
+
+fn default_error_fallback_code() -> String {
+    "upstream_error".to_string()
+}
+
+fn default_error_code_rules() -> Vec<ErrorCodeRule> {
+    let by_gemini_status = |code: &str, gemini_status: &str| ErrorCodeRule {
+        code: code.to_string(),
+        gemini_status: Some(gemini_status.to_string()),
+        ..ErrorCodeRule::default()
+    };
+    let by_status = |code: &str, status: u16| ErrorCodeRule {
+        code: code.to_string(),
+        status: Some(status),
+        ..ErrorCodeRule::default()
+    };
+    vec![
+        by_gemini_status("quota_exceeded", "RESOURCE_EXHAUSTED"),
+        by_status("quota_exceeded", 429),
+        ErrorCodeRule {
+            code: "safety_blocked".to_string(),
+            block_reason: Some("SAFETY".to_string()),
+            ..ErrorCodeRule::default()
+        },
+        ErrorCodeRule {
+            code: "safety_blocked".to_string(),
+            finish_reason: Some("SAFETY".to_string()),
+            ..ErrorCodeRule::default()
+        },
+        ErrorCodeRule {
+            code: "prompt_blocked".to_string(),
+            block_reason: Some("*".to_string()),
+            ..ErrorCodeRule::default()
+        },
+        ErrorCodeRule {
+            code: "invalid_api_key".to_string(),
+            message_contains: Some("API key not valid".to_string()),
+            ..ErrorCodeRule::default()
+        },
+        by_gemini_status("invalid_argument", "INVALID_ARGUMENT"),
+        by_gemini_status("failed_precondition", "FAILED_PRECONDITION"),
+        by_gemini_status("unauthenticated", "UNAUTHENTICATED"),
+        by_gemini_status("permission_denied", "PERMISSION_DENIED"),
+        by_gemini_status("not_found", "NOT_FOUND"),
+        by_gemini_status("upstream_timeout", "DEADLINE_EXCEEDED"),
+        by_status("upstream_timeout", 504),
+        by_gemini_status("upstream_unavailable", "UNAVAILABLE"),
+        by_status("upstream_unavailable", 503),
+        by_gemini_status("upstream_internal", "INTERNAL"),
+        by_status("upstream_internal", 500),
+        by_status("invalid_argument", 400),
+    ]
+}
+
+impl Default for ErrorCodesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_error_codes_enabled(),
+            rules: default_error_code_rules(),
+            fallback_code: default_error_fallback_code(),
+        }
+    }
+}
+
+/// 区域延迟优先配置：优先选择测得延迟最低的区域，该区域密钥不可用或延迟升高时转移到其他区域
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionRoutingConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 延迟不超过最优区域该倍数的区域视为同等优先（按权重分配流量），需不小于 1
+    #[serde(default = "default_region_latency_tolerance_ratio")]
+    pub latency_tolerance_ratio: f64,
+    /// 区域延迟的指数移动平均平滑系数，取值 (0, 1]，越大越快反映最新延迟
+    #[serde(default = "default_region_latency_smoothing")]
+    pub latency_smoothing: f64,
+}
+
+fn default_region_latency_tolerance_ratio() -> f64 {
+    1.2
+}
+
+fn default_region_latency_smoothing() -> f64 {
+    0.3
+}
+
+impl Default for RegionRoutingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latency_tolerance_ratio: default_region_latency_tolerance_ratio(),
+            latency_smoothing: default_region_latency_smoothing(),
+        }
+    }
+}
+
+/// 自适应每分钟限额配置（AIMD）：连续收到 429 后按因子下调密钥的生效限额，
+/// 每个没有 429 的平稳期按步长上调，最高恢复到配置的 `max_requests_per_minute`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdaptiveRateLimitConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 连续收到多少次 429 后下调一次
+    #[serde(default = "default_adaptive_429_threshold")]
+    pub threshold: u32,
+    /// 下调时生效限额乘以的因子，取值 (0, 1)
+    #[serde(default = "default_adaptive_decrease_factor")]
+    pub decrease_factor: f64,
+    /// 每个平稳期上调的请求数
+    #[serde(default = "default_adaptive_increase_step")]
+    pub increase_step: u32,
+    /// 生效限额的下限
+    #[serde(default = "default_adaptive_min_requests_per_minute")]
+    pub min_requests_per_minute: u32,
+    /// 平稳期长度（秒）
+    #[serde(default = "default_adaptive_clean_period_seconds")]
+    pub clean_period_seconds: u64,
+}
+
+fn default_adaptive_429_threshold() -> u32 {
+    3
+}
+
+fn default_adaptive_decrease_factor() -> f64 {
+    0.5
+}
+
+fn default_adaptive_increase_step() -> u32 {
+    5
+}
+
+fn default_adaptive_min_requests_per_minute() -> u32 {
+    1
+}
+
+fn default_adaptive_clean_period_seconds() -> u64 {
+    60
+}
+
+impl Default for AdaptiveRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: default_adaptive_429_threshold(),
+            decrease_factor: default_adaptive_decrease_factor(),
+            increase_step: default_adaptive_increase_step(),
+            min_requests_per_minute: default_adaptive_min_requests_per_minute(),
+            clean_period_seconds: default_adaptive_clean_period_seconds(),
+        }
+    }
+}
+
+/// 请求内换密钥重试配置：上游返回可重试状态码时，按失败记录当前密钥并换用下一个密钥重放请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailoverConfig {
+    /// 单个请求最多重试次数，0 表示不重试
+    pub max_retries: u32,
+    /// 首次重试前的等待时间（毫秒），之后每次翻倍
+    pub backoff_ms: u64,
+    /// 重试等待时间上限（毫秒）
+    pub max_backoff_ms: u64,
+    /// 单个请求各次重试等待时间之和的上限（毫秒），下一次等待会超出时不再重试，0 表示不限制
+    pub max_total_backoff_ms: u64,
+    /// 触发换密钥重试的上游状态码
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff_ms: 50,
+            max_backoff_ms: 1000,
+            max_total_backoff_ms: 0,
+            retry_statuses: vec![429, 500, 502, 503, 504],
+        }
+    }
+}
+
+/// 429 冷却配置：上游返回 429 的密钥在冷却期内不参与选择，冷却期结束后自动恢复
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyCooldownConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 响应未携带 Retry-After 时的冷却时长（秒）
+    pub default_seconds: u64,
+    /// 冷却时长上限（秒），Retry-After 超过时截断
+    pub max_seconds: u64,
+}
+
+impl Default for KeyCooldownConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_seconds: 60,
+            max_seconds: 600,
+        }
+    }
+}
+
+/// 失败惩罚配置：密钥每次失败后有效权重按比例降低，惩罚在窗口内线性衰减到零，
+/// 熔断或停用之前就让近期不稳定的密钥少分到流量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailurePenaltyConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 每次失败增加的惩罚比例（0~1，占有效权重）
+    pub penalty_per_failure: f64,
+    /// 惩罚比例上限（0~1）
+    pub max_penalty: f64,
+    /// 惩罚从最近一次失败起衰减到零的时长（秒）
+    pub decay_seconds: u64,
+}
+
+impl Default for FailurePenaltyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            penalty_per_failure: 0.5,
+            max_penalty: 0.9,
+            decay_seconds: 60,
+        }
+    }
+}
+
+/// 流式响应合并转发配置：小数据块累积到 `buffer_bytes` 或最早的数据块已暂存 `max_hold_ms` 后一并转发，
+/// 流结束时总是立即转发；`buffer_bytes` 为 0 时逐块转发
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingBufferConfig {
+    /// 累积多少字节后转发，0 表示不合并
+    pub buffer_bytes: usize,
+    /// 数据块最长暂存时间（毫秒）
+    pub max_hold_ms: u64,
+}
+
+impl Default for StreamingBufferConfig {
+    fn default() -> Self {
+        Self {
+            buffer_bytes: 0,
+            max_hold_ms: 50,
+        }
+    }
+}
+
+/// 密钥调度方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySchedulingMode {
+    /// 平滑加权轮询
+    #[default]
+    WeightedRoundRobin,
+    /// 一致性哈希：同一客户端（按 `hash_attribute`）固定路由到同一个密钥，便于按密钥的提示缓存命中
+    ConsistentHashing,
+}
+
+/// 一致性哈希使用的请求属性
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAttribute {
+    /// 客户端 IP
+    #[default]
+    ClientIp,
+    /// `hash_header` 指定的请求头
+    Header,
+    /// JWT 的 `sub` 声明
+    JwtSub,
+}
+
+/// 密钥调度配置，默认平滑加权轮询；属性缺失的请求使用加权轮询
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulingConfig {
+    pub mode: KeySchedulingMode,
+    /// 一致性哈希的请求属性
+    pub hash_attribute: HashAttribute,
+    /// `hash_attribute` 为 header 时使用的请求头
+    pub hash_header: String,
+    /// 一致性哈希环的虚拟节点总数，各密钥按权重分配
+    pub ring_size: usize,
+}
+
+impl Default for SchedulingConfig {
+    fn default() -> Self {
+        Self {
+            mode: KeySchedulingMode::default(),
+            hash_attribute: HashAttribute::default(),
+            hash_header: "x-session-id".to_string(),
+            ring_size: crate::load_balancer::hash_ring::DEFAULT_RING_SIZE,
+        }
+    }
+}
+
+/// 按密钥的熔断器配置：连续失败达到阈值后熔断器打开，该密钥不参与选择；恢复时间过后进入半开，
+/// 放行有限的探测请求，探测成功关闭熔断器，失败重新打开
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// 连续失败多少次后打开熔断器
+    pub failure_threshold: u32,
+    /// 打开后多久进入半开（秒）
+    pub recovery_timeout_seconds: u64,
+    /// 半开状态下最多放行的探测请求数
+    pub half_open_max_calls: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            recovery_timeout_seconds: 60,
+            half_open_max_calls: 3,
+        }
+    }
+}
+
+/// 模型降级链配置：请求的模型连续返回过载状态后，后续请求改写到链中下一个可用模型，
+/// 恢复窗口过后重新尝试原模型
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelFallbackConfig {
+    /// 各模型的降级链（按顺序尝试），未配置的模型不降级
+    pub chains: HashMap<String, Vec<String>>,
+    /// 连续多少次过载响应后视为持续失败
+    pub failure_threshold: u32,
+    /// 视为过载的上游状态码
+    pub overload_statuses: Vec<u16>,
+    /// 模型被降级后多久重新尝试（秒）
+    pub recovery_seconds: u64,
+}
+
+impl Default for ModelFallbackConfig {
+    fn default() -> Self {
+        Self {
+            chains: HashMap::new(),
+            failure_threshold: 3,
+            overload_statuses: vec![503],
+            recovery_seconds: 30,
+        }
+    }
+}
+
+/// 哈希路由配置：相同字段取值的请求确定性地路由到同一个密钥
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashRoutingConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 参与哈希的请求体字段（点分隔路径，数组使用下标）
+    #[serde(default = "default_hash_routing_field")]
+    pub field: String,
+    /// 仅对以这些后缀结尾的请求路径生效，为空时对所有请求生效
+    #[serde(default = "default_hash_routing_path_suffixes")]
+    pub path_suffixes: Vec<String>,
+}
+
+fn default_hash_routing_field() -> String {
+    "content.parts.0.text".to_string()
+}
+
+fn default_hash_routing_path_suffixes() -> Vec<String> {
+    vec![":embedContent".to_string()]
+}
+
+impl Default for HashRoutingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            field: default_hash_routing_field(),
+            path_suffixes: default_hash_routing_path_suffixes(),
+        }
+    }
+}
+
+/// 权重调整的生效策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeightChangePolicy {
+    /// 新权重立即用于选择
+    #[default]
+    Immediate,
+    /// 有效权重在 `ramp_window_ms` 内从旧值线性过渡到新值
+    Ramp,
+}
+
+/// 权重调整配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightChangeConfig {
+    #[serde(default)]
+    pub policy: WeightChangePolicy,
+    /// 渐变窗口（毫秒），仅 `ramp` 策略使用
+    #[serde(default = "default_weight_ramp_window_ms")]
+    pub ramp_window_ms: u64,
+}
+
+fn default_weight_ramp_window_ms() -> u64 {
+    30_000
+}
+
+impl WeightChangeConfig {
+    /// 实际使用的渐变窗口（毫秒），立即生效时为 0
+    pub fn effective_ramp_window_ms(&self) -> u64 {
+        match self.policy {
+            WeightChangePolicy::Immediate => 0,
+            WeightChangePolicy::Ramp => self.ramp_window_ms,
+        }
+    }
+}
+
+impl Default for WeightChangeConfig {
+    fn default() -> Self {
+        Self {
+            policy: WeightChangePolicy::Immediate,
+            ramp_window_ms: default_weight_ramp_window_ms(),
+        }
+    }
+}
+
+/// 上下文窗口预检配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextPreflightConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 各模型的上下文上限（token 数，按模型名或模型名前缀匹配），未配置的模型不做预检
+    #[serde(default)]
+    pub context_limits: HashMap<String, u64>,
+    /// 默认估算器中每个 token 对应的字符数
+    #[serde(default = "default_preflight_chars_per_token")]
+    pub chars_per_token: f64,
+    /// 预检最多缓冲的请求体字节数，超出时跳过预检直接转发
+    #[serde(default = "default_preflight_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+fn default_preflight_chars_per_token() -> f64 {
+    4.0
+}
+
+fn default_preflight_max_body_bytes() -> usize {
+    8 * 1024 * 1024
+}
+
+impl Default for ContextPreflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            context_limits: HashMap::new(),
+            chars_per_token: default_preflight_chars_per_token(),
+            max_body_bytes: default_preflight_max_body_bytes(),
+        }
+    }
+}
+
+/// 请求 ID 传播配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestIdConfig {
+    /// 读取并向上游传播的请求头（按顺序取第一个非空值作为请求 ID），
+    /// 例如 `x-request-id`、`x-cloud-trace-context`、`traceparent`
+    #[serde(default = "default_request_id_headers")]
+    pub headers: Vec<String>,
+    /// 是否为缺失的请求头生成取值并转发给上游
+    #[serde(default = "default_request_id_propagate")]
+    pub propagate: bool,
+}
+
+fn default_request_id_headers() -> Vec<String> {
+    vec!["x-request-id".to_string()]
+}
+
+fn default_request_id_propagate() -> bool {
+    true
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self {
+            headers: default_request_id_headers(),
+            propagate: true,
+        }
+    }
+}
+
+/// 单个卸载原因的响应配置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ShedResponse {
+    /// 响应状态码（429 或 5xx）
+    pub status: u16,
+    /// Retry-After 响应头（秒），None 表示不发送
+    #[serde(default)]
+    pub retry_after_seconds: Option<u64>,
+}
+
+/// 卸载原因到响应的映射：配额/速率类默认 429 + Retry-After，故障类默认 503
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShedResponsesConfig {
+    /// 客户端超过速率限制
+    #[serde(default = "default_shed_rate_limited")]
+    pub rate_limited: ShedResponse,
+    /// 所有密钥都已达到每分钟限额
+    #[serde(default = "default_shed_quota_exhausted")]
+    pub quota_exhausted: ShedResponse,
+    /// 所有密钥的熔断器均已打开
+    #[serde(default = "default_shed_circuit_open")]
+    pub circuit_open: ShedResponse,
+    /// 没有可用的密钥（全部停用）
+    #[serde(default = "default_shed_no_key_available")]
+    pub no_key_available: ShedResponse,
+    /// 没有密钥允许服务请求的模型（`allowed_models`）
+    #[serde(default = "default_shed_model_unsupported")]
+    pub model_unsupported: ShedResponse,
+    /// 全进程在途请求数达到上限
+    #[serde(default = "default_shed_overloaded")]
+    pub overloaded: ShedResponse,
+    /// 正在停机排空在途请求
+    #[serde(default = "default_shed_draining")]
+    pub draining: ShedResponse,
+}
+
+fn default_shed_rate_limited() -> ShedResponse {
+    ShedResponse { status: 429, retry_after_seconds: Some(60) }
+}
+
+fn default_shed_quota_exhausted() -> ShedResponse {
+    ShedResponse { status: 429, retry_after_seconds: Some(60) }
+}
+
+fn default_shed_circuit_open() -> ShedResponse {
+    ShedResponse { status: 503, retry_after_seconds: Some(60) }
+}
+
+fn default_shed_no_key_available() -> ShedResponse {
+    ShedResponse { status: 503, retry_after_seconds: None }
+}
+
+fn default_shed_model_unsupported() -> ShedResponse {
+    ShedResponse { status: 503, retry_after_seconds: None }
+}
+
+fn default_shed_overloaded() -> ShedResponse {
+    ShedResponse { status: 503, retry_after_seconds: Some(1) }
+}
+
+fn default_shed_draining() -> ShedResponse {
+    ShedResponse { status: 503, retry_after_seconds: Some(1) }
+}
+
+impl Default for ShedResponsesConfig {
+    fn default() -> Self {
+        Self {
+            rate_limited: default_shed_rate_limited(),
+            quota_exhausted: default_shed_quota_exhausted(),
+            circuit_open: default_shed_circuit_open(),
+            no_key_available: default_shed_no_key_available(),
+            model_unsupported: default_shed_model_unsupported(),
+            overloaded: default_shed_overloaded(),
+            draining: default_shed_draining(),
+        }
+    }
+}
+
+fn default_gemini_base_url() -> String {
+    "https://generativelanguage.googleapis.com".to_string()
+}
+
+fn default_gemini_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_stream_timeout_seconds() -> u64 {
+    600
+}
+
+fn default_stream_idle_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_min_healthy_keys() -> usize {
+    1
+}
+
+/// 响应缓存配置：按请求内容哈希缓存确定性请求的上游响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    /// 是否启用（未启用时忽略客户端的缓存请求头）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 缓存有效期（秒）
+    #[serde(default = "default_response_cache_ttl_seconds")]
+    pub ttl_seconds: u64,
+    /// 最大缓存条目数
+    #[serde(default = "default_response_cache_max_entries")]
+    pub max_entries: usize,
+    /// 单条响应最大字节数，超出的响应不缓存
+    #[serde(default = "default_response_cache_max_entry_bytes")]
+    pub max_entry_bytes: usize,
+    /// 默认启用缓存的路径前缀；其他路径需客户端通过 `X-Gemini-Cache` 请求头显式启用
+    #[serde(default)]
+    pub path_prefixes: Vec<String>,
+}
+
+fn default_response_cache_ttl_seconds() -> u64 {
+    300
+}
+
+fn default_response_cache_max_entries() -> usize {
+    1000
+}
+
+fn default_response_cache_max_entry_bytes() -> usize {
+    1024 * 1024
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_seconds: default_response_cache_ttl_seconds(),
+            max_entries: default_response_cache_max_entries(),
+            max_entry_bytes: default_response_cache_max_entry_bytes(),
+            path_prefixes: Vec::new(),
+        }
+    }
+}
+
+/// 重复请求去重配置：窗口内携带相同 `Idempotency-Key`（或相同请求体）的请求直接返回首个请求的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 去重窗口（秒），从首个请求开始计算
+    #[serde(default = "default_dedup_window_seconds")]
+    pub window_seconds: u64,
+    /// 未携带 `Idempotency-Key` 时是否按请求体哈希识别重复请求
+    #[serde(default = "default_dedup_hash_body")]
+    pub hash_body: bool,
+    /// 最大记录条目数
+    #[serde(default = "default_response_cache_max_entries")]
+    pub max_entries: usize,
+    /// 单条响应最大字节数，超出的响应不记录
+    #[serde(default = "default_response_cache_max_entry_bytes")]
+    pub max_entry_bytes: usize,
+}
+
+fn default_dedup_window_seconds() -> u64 {
+    10
+}
+
+fn default_dedup_hash_body() -> bool {
+    true
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_seconds: default_dedup_window_seconds(),
+            hash_body: true,
+            max_entries: default_response_cache_max_entries(),
+            max_entry_bytes: default_response_cache_max_entry_bytes(),
+        }
+    }
+}
+
+/// 上游连接预热池配置：为上游地址保持若干空闲的 TCP 连接，在上游关闭空闲连接之前替换
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmPoolConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 每个上游地址保持的空闲连接数
+    #[serde(default = "default_warm_pool_connections_per_peer", alias = "connections_per_key")]
+    pub connections_per_peer: usize,
+    /// 上游关闭空闲连接的时间（秒）
+    #[serde(default = "default_warm_pool_idle_timeout_seconds")]
+    pub idle_timeout_seconds: u64,
+    /// 提前替换连接的时间（秒），连接空闲超过 `idle_timeout_seconds - refresh_before_seconds` 后重建
+    #[serde(default = "default_warm_pool_refresh_before_seconds")]
+    pub refresh_before_seconds: u64,
+    /// 检查与补足连接的间隔（秒）
+    #[serde(default = "default_warm_pool_refresh_interval_seconds")]
+    pub refresh_interval_seconds: u64,
+}
+
+fn default_warm_pool_connections_per_peer() -> usize {
+    2
+}
+
+fn default_warm_pool_idle_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_warm_pool_refresh_before_seconds() -> u64 {
+    10
+}
+
+fn default_warm_pool_refresh_interval_seconds() -> u64 {
+    5
+}
+
+impl Default for WarmPoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            connections_per_peer: default_warm_pool_connections_per_peer(),
+            idle_timeout_seconds: default_warm_pool_idle_timeout_seconds(),
+            refresh_before_seconds: default_warm_pool_refresh_before_seconds(),
+            refresh_interval_seconds: default_warm_pool_refresh_interval_seconds(),
+        }
+    }
+}
+
+/// 流量分类规则：规则中配置的所有匹配条件都满足时，请求被标记为对应的 traffic_class
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficClassRule {
+    /// 分类名称，例如 "batch"、"interactive"
+    pub class: String,
+    /// 请求路径前缀
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    /// 请求头名称（未配置 header_value 时只要求请求头存在）
+    #[serde(default)]
+    pub header: Option<String>,
+    /// 请求头取值
+    #[serde(default)]
+    pub header_value: Option<String>,
+    /// 请求体包含的内容
+    #[serde(default)]
+    pub body_contains: Option<String>,
+}
+
+/// 模型覆盖配置：允许客户端通过 `X-Gemini-Model-Override` 请求头改写目标模型
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelOverrideConfig {
+    /// 是否启用（未启用时忽略该请求头）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 允许覆盖到的模型列表
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+}
+
+/// 请求方法与路径允许列表配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteAllowlistConfig {
+    /// 是否启用（未启用时转发所有请求）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 允许的方法与路径组合，命中任一规则即允许
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+}
+
+/// 允许规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteRule {
+    /// 允许的 HTTP 方法（不区分大小写），为空表示任意方法
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// 路径模式：`*` 匹配单个路径段内的任意字符，`**` 匹配任意字符
+    pub path: String,
+}
+
+/// 模型价格（每 1k 令牌）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelCost {
+    /// 输入（提示）令牌价格
+    pub input_per_1k_tokens: f64,
+    /// 输出（生成）令牌价格
+    pub output_per_1k_tokens: f64,
+}
+
+/// 目录密钥来源配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeySourceConfig {
+    /// 密钥文件所在目录
+    pub directory: String,
+    /// 刷新间隔（秒）
+    #[serde(default = "default_key_source_refresh_interval")]
+    pub refresh_interval_seconds: u64,
+    /// 文件未指定权重时使用的默认权重
+    #[serde(default = "default_key_source_weight")]
+    pub default_weight: u32,
+    /// 文件未指定限额时使用的默认每分钟最大请求数
+    #[serde(default = "default_key_source_max_requests")]
+    pub default_max_requests_per_minute: u32,
+    /// 启动时密钥来源不可用的处理方式
+    #[serde(default)]
+    pub startup: StartupDependencyConfig,
+}
+
+/// 外部依赖在启动时不可用时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StartupMode {
+    /// 阻塞启动并按间隔重试，重试耗尽后退出
+    Block,
+    /// 记录降级的功能后继续启动，由后台任务在依赖恢复后补齐
+    #[default]
+    Degraded,
+}
+
+/// 启动时外部依赖（例如外部密钥来源）的检查配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StartupDependencyConfig {
+    pub mode: StartupMode,
+    /// 最大尝试次数（包括首次）
+    pub max_attempts: u32,
+    /// 重试间隔（毫秒）
+    pub retry_interval_ms: u64,
+}
+
+impl Default for StartupDependencyConfig {
+    fn default() -> Self {
+        Self {
+            mode: StartupMode::Degraded,
+            max_attempts: 5,
+            retry_interval_ms: 2000,
+        }
+    }
+}
+
+fn default_key_source_refresh_interval() -> u64 {
+    10
+}
+
+fn default_key_source_weight() -> u32 {
+    100
+}
+
+fn default_key_source_max_requests() -> u32 {
+    60
+}
+
+/// 上游状态码改写规则（例如将配额耗尽的 403 改写为 429）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusRewriteRule {
+    /// 需要改写的上游状态码
+    pub upstream_status: u16,
+    /// 返回给客户端的状态码
+    pub status: u16,
+    /// 可选的 Retry-After 秒数
+    #[serde(default)]
+    pub retry_after_seconds: Option<u64>,
+    /// 可选的替换响应体
+    #[serde(default)]
+    pub body: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiKeyConfig {
     pub id: String,
     pub key: String,
+    #[serde(default = "default_api_key_weight")]
     pub weight: u32,
+    #[serde(default = "default_api_key_max_requests_per_minute")]
     pub max_requests_per_minute: u32,
+    /// 所属租户（例如 GCP 项目），可通过 `/api/keys/drain?tenant=` 批量排空
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// 标签，可通过 `/api/keys/drain?tag=` 批量排空
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 所属区域，启用 `gemini.region_routing` 后优先选择延迟最低的区域
+    #[serde(default)]
+    pub region: Option<String>,
+    /// 权重下限，覆盖 `gemini.weight_bounds.min`
+    #[serde(default)]
+    pub weight_min: Option<u32>,
+    /// 权重上限，覆盖 `gemini.weight_bounds.max`
+    #[serde(default)]
+    pub weight_max: Option<u32>,
+    /// 优先服务的模型，请求这些模型时有效权重按 `gemini.preferred_model_boost` 放大，其他模型仍按基础权重参与选择
+    #[serde(default)]
+    pub preferred_models: Vec<String>,
+    /// 允许服务的模型（硬限制）：设置后该密钥只用于这些模型的请求，为空时服务所有模型
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    /// 便于识别的名称，作为 Prometheus `name` 标签并出现在统计接口中（未设置时使用密钥 ID）
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 说明（仅出现在统计接口中）
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
+impl ApiKeyConfig {
+    /// 只指定 ID、密钥、权重与每分钟限额，其余字段为空
+    pub fn new(id: impl Into<String>, key: impl Into<String>, weight: u32, max_requests_per_minute: u32) -> Self {
+        Self {
+            id: id.into(),
+            key: key.into(),
+            weight,
+            max_requests_per_minute,
+            tenant: None,
+            tags: Vec::new(),
+            region: None,
+            weight_min: None,
+            weight_max: None,
+            preferred_models: Vec::new(),
+            allowed_models: Vec::new(),
+            name: None,
+            description: None,
+        }
+    }
+
+    /// 该密钥生效的权重上下限
+    pub fn weight_bounds(&self, defaults: &WeightBounds) -> WeightBounds {
+        WeightBounds::resolve(self.weight_min, self.weight_max, defaults)
+    }
+}
+
+fn default_api_key_weight() -> u32 {
+    100
+}
+
+fn default_api_key_max_requests_per_minute() -> u32 {
+    60
+}
+
+/// 认证配置，省略时默认不启用；启用时必须显式配置 `jwt_secret` 与 `admin_password`
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AuthConfig {
     pub enabled: bool,
     pub jwt_secret: String,
@@ -58,21 +1556,173 @@ pub struct AuthConfig {
     pub token_expiry_hours: u64,
     pub refresh_token_enabled: bool,
     pub session_timeout_minutes: u64,
+    /// 会话绝对最长存活时间（分钟）：无论是否活跃，会话在创建后超过该时间即失效，0 表示不限制
+    pub session_max_lifetime_minutes: u64,
     pub max_login_attempts: u32,
     pub lockout_duration_minutes: u64,
+    /// 令牌校验器内部错误时的处理方式（默认拒绝）
+    pub failure_mode: AuthFailureMode,
+    /// 令牌校验结果缓存有效期（秒），有效期内同一令牌跳过签名校验，0 表示禁用
+    pub verification_cache_ttl_seconds: u64,
+    /// 令牌校验结果缓存的最大条目数
+    pub verification_cache_max_entries: usize,
+    /// 按路由前缀声明的访问要求（例如 `/api/config/*: admin`），最长前缀优先，未匹配的路由不额外校验
+    pub route_policies: HashMap<String, RouteAccess>,
+    /// 客户端代理 API 密钥（与上游 Gemini 密钥无关），可代替 JWT 出示
+    pub api_keys: Vec<ClientApiKeyConfig>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            jwt_secret: String::new(),
+            rate_limit_per_minute: 60,
+            admin_password: String::new(),
+            token_expiry_hours: 8,
+            refresh_token_enabled: true,
+            session_timeout_minutes: 30,
+            session_max_lifetime_minutes: 0,
+            max_login_attempts: 5,
+            lockout_duration_minutes: 15,
+            failure_mode: AuthFailureMode::default(),
+            verification_cache_ttl_seconds: 30,
+            verification_cache_max_entries: 10_000,
+            route_policies: HashMap::new(),
+            api_keys: Vec::new(),
+        }
+    }
 }
 
+impl AuthConfig {
+    /// 路径对应的访问要求（最长前缀优先），前缀末尾的 `/*` 可省略
+    pub fn route_access(&self, path: &str) -> Option<RouteAccess> {
+        self.route_policies
+            .iter()
+            .map(|(prefix, access)| (prefix.trim_end_matches('*').trim_end_matches('/'), *access))
+            .filter(|(prefix, _)| {
+                path.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, access)| access)
+    }
+}
+
+/// 客户端代理 API 密钥，配置中只保存 Argon2 哈希
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientApiKeyConfig {
+    pub id: String,
+    /// Argon2 PHC 格式的密钥哈希，例如 `$argon2id$v=19$m=19456,t=2,p=1$...`
+    pub secret_hash: String,
+    /// 权限：`proxy` 允许访问代理接口，`read`/`admin` 与 JWT 角色含义相同
+    #[serde(default = "default_client_key_permissions")]
+    pub permissions: Vec<String>,
+}
+
+fn default_client_key_permissions() -> Vec<String> {
+    vec!["proxy".to_string()]
+}
+
+/// 路由访问要求
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteAccess {
+    /// 无需令牌
+    Public,
+    /// 需要 read 或 admin 角色的令牌
+    Read,
+    /// 需要 admin 角色的令牌
+    Admin,
+}
+
+impl RouteAccess {
+    /// 角色是否满足访问要求
+    pub fn allows(&self, role: &str) -> bool {
+        match self {
+            Self::Public => true,
+            Self::Read => role == "read" || role == "admin",
+            Self::Admin => role == "admin",
+        }
+    }
+}
+
+/// 认证失败模式：校验器内部出错（如密钥加载失败）时放行还是拒绝请求
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthFailureMode {
+    /// 放行请求
+    Open,
+    /// 拒绝请求
+    #[default]
+    Closed,
+}
+
+/// 监控配置，默认在 9090 端口启用 Prometheus 指标与管理 API，不启用 TLS
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MetricsConfig {
     pub enabled: bool,
     pub prometheus_port: u16,
     pub tls: Option<TlsConfig>,  // API 服务器的 TLS 配置
+    /// 要求 API 服务器使用 TLS：未启用 `tls` 时拒绝启动 API 服务器
+    #[serde(default)]
+    pub require_tls: bool,
+    /// `/api/stats/prometheus` 业务指标导出
+    #[serde(default)]
+    pub business: BusinessMetricsConfig,
+}
+
+impl MetricsConfig {
+    /// API 服务器是否启用了 TLS
+    pub fn api_tls_enabled(&self) -> bool {
+        self.tls.as_ref().is_some_and(|tls| tls.enabled)
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            prometheus_port: 9090,
+            tls: None,
+            require_tls: false,
+            business: BusinessMetricsConfig::default(),
+        }
+    }
+}
+
+/// 业务指标导出配置：在代理指标之外合并密钥公平性、剩余配额、熔断状态与恢复统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusinessMetricsConfig {
+    /// 是否提供 `/api/stats/prometheus`（`/metrics` 不受影响）
+    #[serde(default = "default_business_metrics_enabled")]
+    pub enabled: bool,
+    /// 是否输出按密钥区分的指标（密钥较多时可关闭以控制标签基数）
+    #[serde(default = "default_business_metrics_per_key")]
+    pub per_key: bool,
+}
+
+fn default_business_metrics_enabled() -> bool {
+    true
+}
+
+fn default_business_metrics_per_key() -> bool {
+    true
+}
+
+impl Default for BusinessMetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_business_metrics_enabled(),
+            per_key: default_business_metrics_per_key(),
+        }
+    }
 }
 
 impl ProxyConfig {
     pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let content = fs::read_to_string(path)?;
-        let config: ProxyConfig = serde_yaml::from_str(&content)?;
+        let config = Self::from_source(&fs::read_to_string(path)?)?;
         
         // 配置验证
         config.validate()?;
@@ -83,25 +1733,53 @@ impl ProxyConfig {
     /// 使用新错误系统从文件加载配置
     pub fn from_file_enhanced(path: &str) -> crate::error::Result<Self> {
         use crate::config::validation::ConfigValidator;
+
+        let config = Self::parse_file(path)?;
+
+        // 使用新的验证器
+        ConfigValidator::validate_proxy_config(&config)?;
         
+        Ok(config)
+    }
+
+    /// 读取并解析配置文件（替换环境变量占位符），不做验证
+    pub fn parse_file(path: &str) -> crate::error::Result<Self> {
         let content = fs::read_to_string(path)
             .map_err(|e| crate::error::GeminiProxyError::config_with_context(
                 format!("无法读取配置文件: {}", e),
                 "config",
                 "load_file"
             ).with_metadata("file_path", path))?;
+        Self::from_source(&content).map_err(|e| e.with_metadata("file_path", path))
+    }
 
-        let config: ProxyConfig = serde_yaml::from_str(&content)
-            .map_err(|e| crate::error::GeminiProxyError::config_with_context(
-                format!("配置文件格式错误: {}", e),
-                "config", 
-                "parse_yaml"
-            ).with_metadata("file_path", path))?;
+    /// 解析配置内容：先解析 YAML，再替换字符串值中的 `${ENV_VAR}` / `${ENV_VAR:-default}` 占位符
+    pub fn from_source(content: &str) -> crate::error::Result<Self> {
+        let yaml_error = |e: serde_yaml::Error| crate::error::GeminiProxyError::config_with_context(
+            format!("配置文件格式错误: {}", e),
+            "config",
+            "parse_yaml"
+        );
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content).map_err(yaml_error)?;
+        expand_env_placeholders(&mut value)?;
+        serde_yaml::from_value(value).map_err(yaml_error)
+    }
 
-        // 使用新的验证器
-        ConfigValidator::validate_proxy_config(&config)?;
-        
-        Ok(config)
+    /// 把当前配置写回原始配置内容
+    ///
+    /// 只覆盖相对 `source` 变化的字段，未变化字段中的 `${ENV_VAR}` 占位符保持原样，
+    /// 环境变量中的密钥不会写入配置文件
+    pub fn to_source_yaml(&self, source: &str) -> crate::error::Result<String> {
+        let yaml_error = |e: serde_yaml::Error| crate::error::GeminiProxyError::config_with_context(
+            format!("配置序列化失败: {}", e),
+            "config",
+            "write_yaml"
+        );
+        let mut raw: serde_yaml::Value = serde_yaml::from_str(source).map_err(yaml_error)?;
+        let baseline = serde_yaml::to_value(Self::from_source(source)?).map_err(yaml_error)?;
+        let updated = serde_yaml::to_value(self).map_err(yaml_error)?;
+        merge_changed_values(&mut raw, &baseline, &updated);
+        serde_yaml::to_string(&raw).map_err(yaml_error)
     }
     
     /// 配置验证
@@ -157,7 +1835,7 @@ impl ProxyConfig {
         }
         
         // Gemini配置验证
-        if self.gemini.api_keys.is_empty() {
+        if self.gemini.api_keys.is_empty() && self.gemini.key_source.is_none() {
             return Err("必须配置至少一个Gemini API密钥".into());
         }
         
@@ -291,3 +1969,20 @@ impl ProxyConfig {
         password.len() >= 12 && has_lower && has_upper && has_digit && has_special
     }
 }
+
+#[cfg(test)]
+impl ProxyConfig {
+    /// 测试用最小配置：只包含给定 ID 的上游密钥（密钥为 `test-key-{id}`），其余部分取默认值；
+    /// 另设测试用 JWT 密钥，以便管理 API 测试签发令牌
+    ///
+    /// 测试只需修改关心的字段：`let mut config = ProxyConfig::for_test(&["key1"]); config.auth.max_login_attempts = 2;`
+    pub fn for_test(key_ids: &[&str]) -> Self {
+        let mut config: Self = serde_yaml::from_str("gemini:\n  api_keys: []\n").unwrap();
+        config.gemini.api_keys = key_ids
+            .iter()
+            .map(|id| ApiKeyConfig::new(*id, format!("test-key-{}", id), 100, 60))
+            .collect();
+        config.auth.jwt_secret = "test-jwt-secret-key-that-is-long-enough".to_string();
+        config
+    }
+}
@@ -4,7 +4,7 @@
 //! 使用新的统一错误系统进行配置验证
 
 use crate::error::{GeminiProxyError, ValidationError, ErrorSeverity};
-use super::{ProxyConfig, AuthConfig};
+use super::{AcmeChallengeType, AcmeDnsConfig, DnsProviderKind, ProxyConfig, AuthConfig, HashAttribute, KeySchedulingMode, WeightChangePolicy};
 
 /// 配置验证器
 pub struct ConfigValidator;
@@ -12,6 +12,20 @@ pub struct ConfigValidator;
 impl ConfigValidator {
     /// 验证完整的代理配置
     pub fn validate_proxy_config(config: &ProxyConfig) -> crate::error::Result<()> {
+        let validation_errors = Self::collect_errors(config);
+
+        if !validation_errors.is_empty() {
+            return Err(GeminiProxyError::validation(
+                format!("配置验证失败，发现 {} 个错误", validation_errors.len()),
+                validation_errors,
+            ).with_severity(ErrorSeverity::Critical));
+        }
+
+        Ok(())
+    }
+
+    /// 收集配置中的全部验证错误（不中断、不返回错误）
+    pub fn collect_errors(config: &ProxyConfig) -> Vec<ValidationError> {
         let mut validation_errors = Vec::new();
 
         // 验证服务器配置
@@ -26,14 +40,102 @@ impl ConfigValidator {
         // 验证监控配置
         Self::validate_metrics_config(config, &mut validation_errors);
 
-        if !validation_errors.is_empty() {
-            return Err(GeminiProxyError::validation(
-                format!("配置验证失败，发现 {} 个错误", validation_errors.len()),
-                validation_errors,
-            ).with_severity(ErrorSeverity::Critical));
+        // 验证审计日志配置
+        Self::validate_audit_config(config, &mut validation_errors);
+
+        // 验证安全配置
+        Self::validate_security_config(config, &mut validation_errors);
+
+        validation_errors
+    }
+
+    /// 验证审计日志配置
+    fn validate_audit_config(config: &ProxyConfig, errors: &mut Vec<ValidationError>) {
+        let dead_letter = &config.audit.dead_letter;
+        if !dead_letter.enabled {
+            return;
         }
 
-        Ok(())
+        if dead_letter.path.is_empty() {
+            errors.push(ValidationError {
+                field: "audit.dead_letter.path".to_string(),
+                message: "死信文件路径不能为空".to_string(),
+                value: None,
+            });
+        }
+        if dead_letter.retry_interval_seconds == 0 {
+            errors.push(ValidationError {
+                field: "audit.dead_letter.retry_interval_seconds".to_string(),
+                message: "死信重试间隔不能为0".to_string(),
+                value: Some(dead_letter.retry_interval_seconds.to_string()),
+            });
+        }
+        if dead_letter.max_entries == 0 {
+            errors.push(ValidationError {
+                field: "audit.dead_letter.max_entries".to_string(),
+                message: "死信文件最大条目数不能为0".to_string(),
+                value: Some(dead_letter.max_entries.to_string()),
+            });
+        }
+    }
+
+    /// 验证安全配置：IP 访问规则与受信任代理中的每个条目都必须是有效的 CIDR 或地址
+    fn validate_security_config(config: &ProxyConfig, errors: &mut Vec<ValidationError>) {
+        let security = &config.security;
+        for (field, entries) in [
+            ("ip_rules.allow", &security.ip_rules.allow),
+            ("ip_rules.deny", &security.ip_rules.deny),
+            ("trusted_proxies", &security.trusted_proxies),
+        ] {
+            for (i, entry) in entries.iter().enumerate() {
+                if let Err(e) = crate::security::ip_rules::IpCidr::parse(entry) {
+                    errors.push(ValidationError {
+                        field: format!("security.{}[{}]", field, i),
+                        message: e,
+                        value: Some(entry.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    /// 验证 ACME DNS-01 验证的服务商配置：服务商凭据齐全，传播等待时间与检查间隔大于 0
+    fn validate_acme_dns_config(dns: Option<&AcmeDnsConfig>, errors: &mut Vec<ValidationError>) {
+        let Some(dns) = dns else {
+            errors.push(ValidationError {
+                field: "server.tls.acme.dns".to_string(),
+                message: "使用 DNS-01 验证时必须配置 DNS 服务商".to_string(),
+                value: None,
+            });
+            return;
+        };
+        match dns.provider {
+            DnsProviderKind::Cloudflare => {
+                let cloudflare = dns.cloudflare.as_ref();
+                for (field, value) in [
+                    ("api_token", cloudflare.map(|c| c.api_token.as_str())),
+                    ("zone_id", cloudflare.map(|c| c.zone_id.as_str())),
+                ] {
+                    if value.filter(|v| !v.trim().is_empty()).is_none() {
+                        errors.push(ValidationError {
+                            field: format!("server.tls.acme.dns.cloudflare.{}", field),
+                            message: "使用 Cloudflare 时必须指定".to_string(),
+                            value: None,
+                        });
+                    }
+                }
+            }
+        }
+        if dns.propagation_timeout_seconds == 0 || dns.propagation_poll_interval_seconds == 0 {
+            errors.push(ValidationError {
+                field: "server.tls.acme.dns.propagation_timeout_seconds".to_string(),
+                message: "等待时间与检查间隔必须大于 0".to_string(),
+                value: Some(format!(
+                    "{}/{}",
+                    dns.propagation_timeout_seconds, dns.propagation_poll_interval_seconds
+                )),
+            });
+        }
     }
 
     /// 验证服务器配置
@@ -65,6 +167,38 @@ impl ConfigValidator {
             });
         }
 
+        // 请求头上限验证
+        if config.server.max_header_bytes == 0 {
+            errors.push(ValidationError {
+                field: "server.max_header_bytes".to_string(),
+                message: "请求头字节数上限不能为0".to_string(),
+                value: Some(config.server.max_header_bytes.to_string()),
+            });
+        }
+        if config.server.max_header_count == 0 {
+            errors.push(ValidationError {
+                field: "server.max_header_count".to_string(),
+                message: "请求头数量上限不能为0".to_string(),
+                value: Some(config.server.max_header_count.to_string()),
+            });
+        }
+
+        // 请求体与响应缓冲上限验证
+        if config.server.max_request_body_bytes == 0 {
+            errors.push(ValidationError {
+                field: "server.max_request_body_bytes".to_string(),
+                message: "请求体字节数上限不能为0".to_string(),
+                value: Some(config.server.max_request_body_bytes.to_string()),
+            });
+        }
+        if config.server.max_response_buffer_bytes == 0 {
+            errors.push(ValidationError {
+                field: "server.max_response_buffer_bytes".to_string(),
+                message: "响应体缓冲上限不能为0".to_string(),
+                value: Some(config.server.max_response_buffer_bytes.to_string()),
+            });
+        }
+
         // TLS 配置验证
         if config.server.tls.enabled {
             if config.server.tls.cert_path.is_empty() {
@@ -124,17 +258,35 @@ impl ConfigValidator {
                                 message: "无效的域名".to_string(),
                                 value: Some(domain.clone()),
                             });
+                        } else if domain.starts_with("*.") && acme.challenge == AcmeChallengeType::Http01 {
+                            errors.push(ValidationError {
+                                field: format!("server.tls.acme.domains[{}]", i),
+                                message: "通配符域名只能通过 DNS-01 验证".to_string(),
+                                value: Some(domain.clone()),
+                            });
                         }
                     }
+
+                    if acme.challenge == AcmeChallengeType::Dns01 {
+                        Self::validate_acme_dns_config(acme.dns.as_ref(), errors);
+                    }
                 }
             }
+        } else if config.server.tls.acme.as_ref().is_some_and(|acme| acme.enabled) {
+            // ACME 证书只在启用 TLS 时使用
+            errors.push(ValidationError {
+                field: "server.tls.acme.enabled".to_string(),
+                message: "启用ACME时必须同时启用TLS".to_string(),
+                value: None,
+            });
         }
     }
 
     /// 验证 Gemini 配置
     fn validate_gemini_config(config: &ProxyConfig, errors: &mut Vec<ValidationError>) {
         // API 密钥数量验证
-        if config.gemini.api_keys.is_empty() {
+        // 配置了外部密钥来源时允许静态密钥列表为空
+        if config.gemini.api_keys.is_empty() && config.gemini.key_source.is_none() {
             errors.push(ValidationError {
                 field: "gemini.api_keys".to_string(),
                 message: "必须配置至少一个Gemini API密钥".to_string(),
@@ -142,6 +294,30 @@ impl ConfigValidator {
             });
         }
 
+        if let Some(key_source) = &config.gemini.key_source {
+            if key_source.directory.is_empty() {
+                errors.push(ValidationError {
+                    field: "gemini.key_source.directory".to_string(),
+                    message: "密钥来源目录不能为空".to_string(),
+                    value: None,
+                });
+            }
+            if key_source.refresh_interval_seconds == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.key_source.refresh_interval_seconds".to_string(),
+                    message: "密钥来源刷新间隔不能为0".to_string(),
+                    value: Some(key_source.refresh_interval_seconds.to_string()),
+                });
+            }
+            if key_source.startup.max_attempts == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.key_source.startup.max_attempts".to_string(),
+                    message: "启动依赖检查的最大尝试次数不能为0".to_string(),
+                    value: Some(key_source.startup.max_attempts.to_string()),
+                });
+            }
+        }
+
         // 基础 URL 验证
         if config.gemini.base_url.is_empty() {
             errors.push(ValidationError {
@@ -159,6 +335,53 @@ impl ConfigValidator {
                 value: Some(config.gemini.timeout_seconds.to_string()),
             });
         }
+        if config.gemini.request_timeout_seconds == Some(0) {
+            errors.push(ValidationError {
+                field: "gemini.request_timeout_seconds".to_string(),
+                message: "非流式请求超时时间不能为0".to_string(),
+                value: Some("0".to_string()),
+            });
+        }
+        if config.gemini.stream_idle_timeout_seconds == 0
+            || config.gemini.stream_idle_timeout_seconds > config.gemini.stream_timeout_seconds
+        {
+            errors.push(ValidationError {
+                field: "gemini.stream_idle_timeout_seconds".to_string(),
+                message: "流式请求的数据块间隔超时必须大于0且不超过 stream_timeout_seconds".to_string(),
+                value: Some(config.gemini.stream_idle_timeout_seconds.to_string()),
+            });
+        }
+
+        // 上下文预检验证
+        let preflight = &config.gemini.context_preflight;
+        if preflight.enabled && (preflight.chars_per_token.is_nan() || preflight.chars_per_token <= 0.0) {
+            errors.push(ValidationError {
+                field: "gemini.context_preflight.chars_per_token".to_string(),
+                message: "每个 token 对应的字符数必须大于0".to_string(),
+                value: Some(preflight.chars_per_token.to_string()),
+            });
+        }
+
+        // 权重渐变验证
+        let weight_change = &config.gemini.weight_change;
+        if weight_change.policy == WeightChangePolicy::Ramp && weight_change.ramp_window_ms == 0 {
+            errors.push(ValidationError {
+                field: "gemini.weight_change.ramp_window_ms".to_string(),
+                message: "权重渐变窗口必须大于0".to_string(),
+                value: Some(weight_change.ramp_window_ms.to_string()),
+            });
+        }
+
+        // 账号配额验证：配额过小时部分密钥分配到的限额为 0
+        if let Some(quota) = config.gemini.account_quota_per_minute {
+            if (quota as usize) < config.gemini.api_keys.len().max(1) {
+                errors.push(ValidationError {
+                    field: "gemini.account_quota_per_minute".to_string(),
+                    message: "账号每分钟配额不能小于密钥数量".to_string(),
+                    value: Some(quota.to_string()),
+                });
+            }
+        }
 
         // API 密钥配置验证
         for (i, api_key) in config.gemini.api_keys.iter().enumerate() {
@@ -204,6 +427,36 @@ impl ConfigValidator {
                     value: Some(api_key.max_requests_per_minute.to_string()),
                 });
             }
+
+            let bounds = api_key.weight_bounds(&config.gemini.weight_bounds);
+            if let (Some(min), Some(max)) = (bounds.min, bounds.max) {
+                if min > max {
+                    errors.push(ValidationError {
+                        field: format!("{}.weight_min", prefix),
+                        message: format!("权重下限 {} 不能大于上限 {}", min, max),
+                        value: Some(min.to_string()),
+                    });
+                }
+            }
+            if bounds.clamp(api_key.weight) != api_key.weight {
+                errors.push(ValidationError {
+                    field: format!("{}.weight", prefix),
+                    message: "API密钥权重超出 weight_min/weight_max 范围".to_string(),
+                    value: Some(api_key.weight.to_string()),
+                });
+            }
+        }
+
+        // 全局权重上下限验证
+        let weight_bounds = &config.gemini.weight_bounds;
+        if let (Some(min), Some(max)) = (weight_bounds.min, weight_bounds.max) {
+            if min > max {
+                errors.push(ValidationError {
+                    field: "gemini.weight_bounds.min".to_string(),
+                    message: format!("权重下限 {} 不能大于上限 {}", min, max),
+                    value: Some(min.to_string()),
+                });
+            }
         }
 
         // 检查重复的 API 密钥 ID
@@ -217,10 +470,486 @@ impl ConfigValidator {
                 });
             }
         }
+
+        // 模型价格验证
+        for (model, cost) in &config.gemini.model_costs {
+            if cost.input_per_1k_tokens < 0.0 || cost.output_per_1k_tokens < 0.0 {
+                errors.push(ValidationError {
+                    field: format!("gemini.model_costs.{}", model),
+                    message: "模型价格不能为负数".to_string(),
+                    value: Some(format!("{}/{}", cost.input_per_1k_tokens, cost.output_per_1k_tokens)),
+                });
+            }
+        }
+
+        // 状态码改写规则验证
+        for (i, rule) in config.gemini.status_rewrites.iter().enumerate() {
+            for (name, status) in [("upstream_status", rule.upstream_status), ("status", rule.status)] {
+                if !(100..=599).contains(&status) {
+                    errors.push(ValidationError {
+                        field: format!("gemini.status_rewrites[{}].{}", i, name),
+                        message: "无效的HTTP状态码".to_string(),
+                        value: Some(status.to_string()),
+                    });
+                }
+            }
+        }
+
+        // 错误码规范化规则验证
+        let error_codes = &config.gemini.error_codes;
+        if error_codes.enabled {
+            for (i, rule) in error_codes.rules.iter().enumerate() {
+                if rule.code.trim().is_empty() || !rule.has_condition() {
+                    errors.push(ValidationError {
+                        field: format!("gemini.error_codes.rules[{}]", i),
+                        message: "错误码规则必须设置 code 和至少一个匹配条件".to_string(),
+                        value: Some(rule.code.clone()),
+                    });
+                }
+            }
+            if error_codes.fallback_code.trim().is_empty() {
+                errors.push(ValidationError {
+                    field: "gemini.error_codes.fallback_code".to_string(),
+                    message: "默认错误码不能为空".to_string(),
+                    value: None,
+                });
+            }
+        }
+
+        // 软限额阈值验证
+        let ratio = config.gemini.key_soft_limit_ratio;
+        if !(0.0..1.0).contains(&ratio) {
+            errors.push(ValidationError {
+                field: "gemini.key_soft_limit_ratio".to_string(),
+                message: "软限额阈值必须在 0（禁用）到 1 之间".to_string(),
+                value: Some(ratio.to_string()),
+            });
+        }
+
+        // 优先模型放大倍数验证
+        let boost = config.gemini.preferred_model_boost;
+        if !boost.is_finite() || boost < 1.0 {
+            errors.push(ValidationError {
+                field: "gemini.preferred_model_boost".to_string(),
+                message: "优先模型放大倍数必须不小于 1".to_string(),
+                value: Some(boost.to_string()),
+            });
+        }
+
+        // 模型覆盖验证
+        let model_override = &config.gemini.model_override;
+        if model_override.enabled && model_override.allowed_models.is_empty() {
+            errors.push(ValidationError {
+                field: "gemini.model_override.allowed_models".to_string(),
+                message: "启用模型覆盖时必须配置允许的模型列表".to_string(),
+                value: None,
+            });
+        }
+        for (i, model) in model_override.allowed_models.iter().enumerate() {
+            if model.is_empty() || model.contains(['/', ':', '?', '#']) {
+                errors.push(ValidationError {
+                    field: format!("gemini.model_override.allowed_models[{}]", i),
+                    message: "无效的模型名称".to_string(),
+                    value: Some(model.clone()),
+                });
+            }
+        }
+
+        // 哈希路由验证
+        let hash_routing = &config.gemini.hash_routing;
+        if hash_routing.enabled && hash_routing.field.split('.').all(|s| s.is_empty()) {
+            errors.push(ValidationError {
+                field: "gemini.hash_routing.field".to_string(),
+                message: "启用哈希路由时必须配置参与哈希的请求字段".to_string(),
+                value: Some(hash_routing.field.clone()),
+            });
+        }
+
+        // 区域延迟优先验证
+        let region_routing = &config.gemini.region_routing;
+        if region_routing.enabled {
+            if !(1.0..=f64::MAX).contains(&region_routing.latency_tolerance_ratio) {
+                errors.push(ValidationError {
+                    field: "gemini.region_routing.latency_tolerance_ratio".to_string(),
+                    message: "区域延迟容忍倍数必须不小于 1".to_string(),
+                    value: Some(region_routing.latency_tolerance_ratio.to_string()),
+                });
+            }
+            if !(f64::MIN_POSITIVE..=1.0).contains(&region_routing.latency_smoothing) {
+                errors.push(ValidationError {
+                    field: "gemini.region_routing.latency_smoothing".to_string(),
+                    message: "区域延迟平滑系数必须在 (0, 1] 范围内".to_string(),
+                    value: Some(region_routing.latency_smoothing.to_string()),
+                });
+            }
+        }
+
+        // 自适应每分钟限额验证
+        let adaptive = &config.gemini.adaptive_rate_limit;
+        if adaptive.enabled {
+            if !(f64::MIN_POSITIVE..1.0).contains(&adaptive.decrease_factor) {
+                errors.push(ValidationError {
+                    field: "gemini.adaptive_rate_limit.decrease_factor".to_string(),
+                    message: "下调因子必须在 (0, 1) 范围内".to_string(),
+                    value: Some(adaptive.decrease_factor.to_string()),
+                });
+            }
+            if adaptive.threshold == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.adaptive_rate_limit.threshold".to_string(),
+                    message: "429 次数阈值不能为0".to_string(),
+                    value: Some(adaptive.threshold.to_string()),
+                });
+            }
+            if adaptive.increase_step == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.adaptive_rate_limit.increase_step".to_string(),
+                    message: "上调步长不能为0".to_string(),
+                    value: Some(adaptive.increase_step.to_string()),
+                });
+            }
+            if adaptive.clean_period_seconds == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.adaptive_rate_limit.clean_period_seconds".to_string(),
+                    message: "平稳期长度不能为0".to_string(),
+                    value: Some(adaptive.clean_period_seconds.to_string()),
+                });
+            }
+        }
+
+        // 换密钥重试验证
+        let failover = &config.gemini.failover;
+        if failover.backoff_ms > failover.max_backoff_ms {
+            errors.push(ValidationError {
+                field: "gemini.failover.backoff_ms".to_string(),
+                message: "重试等待时间不能超过等待时间上限".to_string(),
+                value: Some(failover.backoff_ms.to_string()),
+            });
+        }
+        for (i, status) in failover.retry_statuses.iter().enumerate() {
+            if !(400..600).contains(status) {
+                errors.push(ValidationError {
+                    field: format!("gemini.failover.retry_statuses[{}]", i),
+                    message: "只能对 4xx/5xx 状态码重试".to_string(),
+                    value: Some(status.to_string()),
+                });
+            }
+        }
+
+        // 429 冷却验证
+        let key_cooldown = &config.gemini.key_cooldown;
+        if key_cooldown.enabled && key_cooldown.default_seconds > key_cooldown.max_seconds {
+            errors.push(ValidationError {
+                field: "gemini.key_cooldown.default_seconds".to_string(),
+                message: "默认冷却时长不能超过冷却时长上限".to_string(),
+                value: Some(key_cooldown.default_seconds.to_string()),
+            });
+        }
+
+        // 失败惩罚验证
+        let failure_penalty = &config.gemini.failure_penalty;
+        if failure_penalty.enabled {
+            for (name, ratio) in [
+                ("penalty_per_failure", failure_penalty.penalty_per_failure),
+                ("max_penalty", failure_penalty.max_penalty),
+            ] {
+                if !(0.0..=1.0).contains(&ratio) {
+                    errors.push(ValidationError {
+                        field: format!("gemini.failure_penalty.{}", name),
+                        message: "惩罚比例必须在 0 到 1 之间".to_string(),
+                        value: Some(ratio.to_string()),
+                    });
+                }
+            }
+            if failure_penalty.decay_seconds == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.failure_penalty.decay_seconds".to_string(),
+                    message: "惩罚衰减时长不能为0".to_string(),
+                    value: Some(failure_penalty.decay_seconds.to_string()),
+                });
+            }
+        }
+
+        let model_fallback = &config.gemini.model_fallback_chain;
+        if !model_fallback.chains.is_empty() && model_fallback.failure_threshold == 0 {
+            errors.push(ValidationError {
+                field: "gemini.model_fallback_chain.failure_threshold".to_string(),
+                message: "配置降级链时连续失败阈值不能为0".to_string(),
+                value: Some(model_fallback.failure_threshold.to_string()),
+            });
+        }
+        for (model, chain) in &model_fallback.chains {
+            if chain.iter().any(|m| m == model) {
+                errors.push(ValidationError {
+                    field: format!("gemini.model_fallback_chain.chains.{}", model),
+                    message: "降级链不能包含模型自身".to_string(),
+                    value: Some(chain.join(",")),
+                });
+            }
+        }
+
+        // 流式合并转发验证
+        let streaming_buffer = &config.gemini.streaming_buffer;
+        if streaming_buffer.buffer_bytes > 0 && streaming_buffer.max_hold_ms == 0 {
+            errors.push(ValidationError {
+                field: "gemini.streaming_buffer.max_hold_ms".to_string(),
+                message: "启用流式合并转发时最长暂存时间不能为0".to_string(),
+                value: Some(streaming_buffer.max_hold_ms.to_string()),
+            });
+        }
+
+        // 密钥调度验证
+        let scheduling = &config.gemini.scheduling;
+        if scheduling.mode == KeySchedulingMode::ConsistentHashing {
+            if scheduling.ring_size == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.scheduling.ring_size".to_string(),
+                    message: "一致性哈希环大小不能为0".to_string(),
+                    value: Some(scheduling.ring_size.to_string()),
+                });
+            }
+            if scheduling.hash_attribute == HashAttribute::Header && scheduling.hash_header.trim().is_empty() {
+                errors.push(ValidationError {
+                    field: "gemini.scheduling.hash_header".to_string(),
+                    message: "按请求头哈希时请求头名称不能为空".to_string(),
+                    value: None,
+                });
+            }
+        }
+
+        // 熔断器验证
+        let circuit_breaker = &config.gemini.circuit_breaker;
+        if circuit_breaker.failure_threshold == 0 {
+            errors.push(ValidationError {
+                field: "gemini.circuit_breaker.failure_threshold".to_string(),
+                message: "熔断器失败阈值不能为0".to_string(),
+                value: Some(circuit_breaker.failure_threshold.to_string()),
+            });
+        }
+        if circuit_breaker.half_open_max_calls == 0 {
+            errors.push(ValidationError {
+                field: "gemini.circuit_breaker.half_open_max_calls".to_string(),
+                message: "半开状态至少需要放行一个探测请求".to_string(),
+                value: Some(circuit_breaker.half_open_max_calls.to_string()),
+            });
+        }
+
+        // 路径允许列表验证
+        let route_allowlist = &config.gemini.route_allowlist;
+        if route_allowlist.enabled && route_allowlist.rules.is_empty() {
+            errors.push(ValidationError {
+                field: "gemini.route_allowlist.rules".to_string(),
+                message: "启用路径允许列表时必须配置至少一条规则".to_string(),
+                value: None,
+            });
+        }
+        for (i, rule) in route_allowlist.rules.iter().enumerate() {
+            if !rule.path.starts_with('/') {
+                errors.push(ValidationError {
+                    field: format!("gemini.route_allowlist.rules[{}].path", i),
+                    message: "路径模式必须以 / 开头".to_string(),
+                    value: Some(rule.path.clone()),
+                });
+            }
+            for method in &rule.methods {
+                if method.parse::<http::Method>().is_err() {
+                    errors.push(ValidationError {
+                        field: format!("gemini.route_allowlist.rules[{}].methods", i),
+                        message: "无效的 HTTP 方法".to_string(),
+                        value: Some(method.clone()),
+                    });
+                }
+            }
+        }
+
+        // 必需请求头验证
+        for (i, name) in config.gemini.required_headers.iter().enumerate() {
+            if name.parse::<http::HeaderName>().is_err() {
+                errors.push(ValidationError {
+                    field: format!("gemini.required_headers[{}]", i),
+                    message: "无效的请求头名称".to_string(),
+                    value: Some(name.clone()),
+                });
+            }
+        }
+
+        // 响应字段剥离路径验证
+        for (i, path) in config.gemini.strip_response_fields.iter().enumerate() {
+            if path.split('.').any(|segment| segment.trim().is_empty()) {
+                errors.push(ValidationError {
+                    field: format!("gemini.strip_response_fields[{}]", i),
+                    message: "字段路径不能为空或包含空的路径段".to_string(),
+                    value: Some(path.clone()),
+                });
+            }
+        }
+
+        // 流量分类规则验证
+        for (i, rule) in config.gemini.traffic_classes.iter().enumerate() {
+            if rule.class.is_empty() {
+                errors.push(ValidationError {
+                    field: format!("gemini.traffic_classes[{}].class", i),
+                    message: "流量分类名称不能为空".to_string(),
+                    value: None,
+                });
+            }
+            if rule.path_prefix.is_none() && rule.header.is_none() && rule.body_contains.is_none() {
+                errors.push(ValidationError {
+                    field: format!("gemini.traffic_classes[{}]", i),
+                    message: "流量分类规则至少需要一个匹配条件".to_string(),
+                    value: Some(rule.class.clone()),
+                });
+            }
+            if rule.header_value.is_some() && rule.header.is_none() {
+                errors.push(ValidationError {
+                    field: format!("gemini.traffic_classes[{}].header_value", i),
+                    message: "配置 header_value 时必须同时配置 header".to_string(),
+                    value: rule.header_value.clone(),
+                });
+            }
+        }
+
+        // 就绪阈值验证（使用外部密钥来源时密钥数在运行时才确定）
+        if config.gemini.key_source.is_none()
+            && !config.gemini.api_keys.is_empty()
+            && config.gemini.min_healthy_keys > config.gemini.api_keys.len()
+        {
+            errors.push(ValidationError {
+                field: "gemini.min_healthy_keys".to_string(),
+                message: format!(
+                    "最少健康密钥数不能超过配置的密钥数量 ({})",
+                    config.gemini.api_keys.len()
+                ),
+                value: Some(config.gemini.min_healthy_keys.to_string()),
+            });
+        }
+
+        // 上游连通性探测验证
+        let probe = &config.health.upstream_probe;
+        if probe.enabled && (probe.interval_seconds == 0 || probe.timeout_seconds == 0) {
+            errors.push(ValidationError {
+                field: "health.upstream_probe".to_string(),
+                message: "启用上游探测时 interval_seconds 和 timeout_seconds 必须大于 0".to_string(),
+                value: None,
+            });
+        }
+
+        // 卸载响应状态码验证
+        let shed = &config.gemini.shed_responses;
+        for (name, response) in [
+            ("rate_limited", &shed.rate_limited),
+            ("quota_exhausted", &shed.quota_exhausted),
+            ("circuit_open", &shed.circuit_open),
+            ("no_key_available", &shed.no_key_available),
+            ("model_unsupported", &shed.model_unsupported),
+            ("overloaded", &shed.overloaded),
+            ("draining", &shed.draining),
+        ] {
+            if response.status != 429 && !(500..=599).contains(&response.status) {
+                errors.push(ValidationError {
+                    field: format!("gemini.shed_responses.{}.status", name),
+                    message: "卸载响应状态码必须为 429 或 5xx".to_string(),
+                    value: Some(response.status.to_string()),
+                });
+            }
+        }
+
+        // 请求 ID 请求头验证
+        if config.gemini.request_id.headers.is_empty() {
+            errors.push(ValidationError {
+                field: "gemini.request_id.headers".to_string(),
+                message: "至少需要配置一个请求 ID 请求头".to_string(),
+                value: None,
+            });
+        }
+        for name in &config.gemini.request_id.headers {
+            if http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                errors.push(ValidationError {
+                    field: "gemini.request_id.headers".to_string(),
+                    message: "无效的请求头名称".to_string(),
+                    value: Some(name.clone()),
+                });
+            }
+        }
+
+        // 响应缓存验证
+        let cache = &config.gemini.response_cache;
+        if cache.enabled {
+            if cache.ttl_seconds == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.response_cache.ttl_seconds".to_string(),
+                    message: "启用响应缓存时有效期必须大于 0".to_string(),
+                    value: Some("0".to_string()),
+                });
+            }
+            if cache.max_entries == 0 || cache.max_entry_bytes == 0 {
+                errors.push(ValidationError {
+                    field: "gemini.response_cache".to_string(),
+                    message: "启用响应缓存时 max_entries 和 max_entry_bytes 必须大于 0".to_string(),
+                    value: None,
+                });
+            }
+        }
+
+        // 去重窗口验证
+        let dedup = &config.gemini.dedup;
+        if dedup.enabled && (dedup.window_seconds == 0 || dedup.max_entries == 0) {
+            errors.push(ValidationError {
+                field: "gemini.dedup".to_string(),
+                message: "启用去重时 window_seconds 和 max_entries 必须大于 0".to_string(),
+                value: None,
+            });
+        }
+
+        // 连接预热池验证
+        let warm_pool = &config.gemini.warm_pool;
+        if warm_pool.enabled
+            && (warm_pool.connections_per_peer == 0
+                || warm_pool.refresh_interval_seconds == 0
+                || warm_pool.refresh_before_seconds >= warm_pool.idle_timeout_seconds)
+        {
+            errors.push(ValidationError {
+                field: "gemini.warm_pool".to_string(),
+                message: "启用连接预热时 connections_per_peer 和 refresh_interval_seconds 必须大于 0，且 refresh_before_seconds 必须小于 idle_timeout_seconds".to_string(),
+                value: None,
+            });
+        }
     }
 
     /// 验证认证配置
     fn validate_auth_config(config: &AuthConfig, errors: &mut Vec<ValidationError>) {
+        for prefix in config.route_policies.keys() {
+            if !prefix.starts_with('/') {
+                errors.push(ValidationError {
+                    field: "auth.route_policies".to_string(),
+                    message: "路由前缀必须以 / 开头".to_string(),
+                    value: Some(prefix.clone()),
+                });
+            }
+        }
+
+        // 代理 API 密钥验证：只接受 Argon2 哈希，不接受明文
+        let mut client_key_ids = std::collections::HashSet::new();
+        for (i, key) in config.api_keys.iter().enumerate() {
+            if key.id.is_empty()
+                || key.id.contains(crate::auth::client_keys::CLIENT_KEY_SEPARATOR)
+                || !client_key_ids.insert(key.id.as_str())
+            {
+                errors.push(ValidationError {
+                    field: format!("auth.api_keys[{}].id", i),
+                    message: "代理 API 密钥 ID 不能为空、不能包含 `:` 且不能重复".to_string(),
+                    value: Some(key.id.clone()),
+                });
+            }
+            if let Err(e) = crate::auth::client_keys::validate_secret_hash(&key.secret_hash) {
+                errors.push(ValidationError {
+                    field: format!("auth.api_keys[{}].secret_hash", i),
+                    message: format!("代理 API 密钥必须配置 Argon2 哈希: {}", e),
+                    value: None,
+                });
+            }
+        }
+
         if config.enabled {
             // JWT 密钥验证
             if config.jwt_secret.is_empty() {
@@ -279,6 +1008,15 @@ impl ConfigValidator {
                 });
             }
 
+            // 令牌校验缓存验证
+            if config.verification_cache_ttl_seconds > 0 && config.verification_cache_max_entries == 0 {
+                errors.push(ValidationError {
+                    field: "auth.verification_cache_max_entries".to_string(),
+                    message: "启用令牌校验缓存时最大条目数不能为0".to_string(),
+                    value: Some(config.verification_cache_max_entries.to_string()),
+                });
+            }
+
             // Token 过期时间验证
             if config.token_expiry_hours == 0 {
                 errors.push(ValidationError {
@@ -316,6 +1054,24 @@ impl ConfigValidator {
                     value: Some(config.metrics.prometheus_port.to_string()),
                 });
             }
+
+            // API 服务器 TLS 验证
+            if config.metrics.require_tls && !config.metrics.api_tls_enabled() {
+                errors.push(ValidationError {
+                    field: "metrics.require_tls".to_string(),
+                    message: "已要求 API 服务器使用 TLS，但未启用 metrics.tls，API 服务器拒绝以明文 HTTP 启动".to_string(),
+                    value: None,
+                });
+            }
+            if let Some(tls) = config.metrics.tls.as_ref().filter(|tls| tls.enabled) {
+                if tls.cert_path.is_empty() || tls.key_path.is_empty() {
+                    errors.push(ValidationError {
+                        field: "metrics.tls".to_string(),
+                        message: "启用API服务器TLS时必须指定证书和私钥路径".to_string(),
+                        value: None,
+                    });
+                }
+            }
         }
     }
 
@@ -353,7 +1109,7 @@ impl ConfigValidator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{ProxyConfig, ServerConfig, GeminiConfig, AuthConfig, MetricsConfig, TlsConfig, ApiKeyConfig};
+    use crate::config::{ProxyConfig, ServerConfig, GeminiConfig, AuthConfig, MetricsConfig, TlsConfig, ApiKeyConfig, AuthFailureMode};
 
     fn create_valid_config() -> ProxyConfig {
         ProxyConfig {
@@ -362,6 +1118,12 @@ mod tests {
                 port: 8080,
                 workers: 4,
                 max_connections: 1000,
+                max_header_bytes: 64 * 1024,
+                max_header_count: 100,
+                max_inflight_requests: 0,
+                max_request_body_bytes: 10 * 1024 * 1024,
+                max_response_buffer_bytes: 10 * 1024 * 1024,
+                shutdown_grace_period_seconds: 30,
                 tls: TlsConfig {
                     enabled: false,
                     cert_path: "".to_string(),
@@ -375,9 +1137,57 @@ mod tests {
                     key: "valid-test-key-12345678901234567890".to_string(),
                     weight: 100,
                     max_requests_per_minute: 60,
+                    tenant: None,
+                    tags: Vec::new(),
+                    region: None,
+                    weight_min: None,
+                    weight_max: None,
+                    preferred_models: Vec::new(),
+                    allowed_models: Vec::new(),
+                    name: None,
+                    description: None,
                 }],
                 base_url: "https://generativelanguage.googleapis.com".to_string(),
                 timeout_seconds: 30,
+                request_timeout_seconds: None,
+                stream_timeout_seconds: 600,
+                stream_idle_timeout_seconds: 60,
+                key_stickiness_window_ms: 0,
+                key_soft_limit_ratio: 0.0,
+                preferred_model_boost: 3.0,
+                status_rewrites: vec![],
+                key_source: None,
+                model_costs: Default::default(),
+                model_override: Default::default(),
+                traffic_classes: vec![],
+                response_cache: Default::default(),
+                dedup: Default::default(),
+                warm_pool: Default::default(),
+                min_healthy_keys: 1,
+                shed_responses: Default::default(),
+                disabled_keys: vec![],
+                request_id: Default::default(),
+                account_quota_per_minute: None,
+                context_preflight: Default::default(),
+                weight_change: Default::default(),
+                route_allowlist: Default::default(),
+                required_headers: Vec::new(),
+                strip_response_fields: Vec::new(),
+                hash_routing: Default::default(),
+                region_routing: Default::default(),
+                soft_failure: Default::default(),
+                error_codes: Default::default(),
+                weight_bounds: Default::default(),
+                request_record: Default::default(),
+                response_headers: Default::default(),
+                adaptive_rate_limit: Default::default(),
+                failover: Default::default(),
+                key_cooldown: Default::default(),
+                failure_penalty: Default::default(),
+                model_fallback_chain: Default::default(),
+                circuit_breaker: Default::default(),
+                streaming_buffer: Default::default(),
+                scheduling: Default::default(),
             },
             auth: AuthConfig {
                 enabled: true,
@@ -387,14 +1197,26 @@ mod tests {
                 token_expiry_hours: 24,
                 refresh_token_enabled: true,
                 session_timeout_minutes: 30,
+                session_max_lifetime_minutes: 0,
                 max_login_attempts: 5,
                 lockout_duration_minutes: 15,
+                failure_mode: AuthFailureMode::Closed,
+                verification_cache_ttl_seconds: 30,
+                verification_cache_max_entries: 10_000,
+                route_policies: Default::default(),
+                api_keys: Default::default(),
             },
             metrics: MetricsConfig {
                 enabled: true,
                 prometheus_port: 9090,
                 tls: None,
+                require_tls: false,
+                business: Default::default(),
             },
+            health: Default::default(),
+            audit: Default::default(),
+            security: Default::default(),
+            persistence: Default::default(),
         }
     }
 
@@ -443,6 +1265,15 @@ mod tests {
             key: "another-valid-key-12345678901234567890".to_string(),
             weight: 50,
             max_requests_per_minute: 30,
+            tenant: None,
+            tags: Vec::new(),
+            region: None,
+            weight_min: None,
+            weight_max: None,
+            preferred_models: Vec::new(),
+            allowed_models: Vec::new(),
+            name: None,
+            description: None,
         });
 
         let result = ConfigValidator::validate_proxy_config(&config);
@@ -454,4 +1285,158 @@ mod tests {
             panic!("期望验证错误");
         }
     }
+
+    #[test]
+    fn test_minimal_config_uses_defaults() {
+        let config: ProxyConfig = serde_yaml::from_str(
+            r#"
+gemini:
+  api_keys:
+    - id: "primary"
+      key: "valid-test-key-12345678901234567890"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.workers, 4);
+        assert_eq!(config.server.max_connections, 1000);
+        assert!(!config.server.tls.enabled);
+        assert!(config.server.tls.acme.is_none());
+
+        assert_eq!(config.gemini.base_url, "https://generativelanguage.googleapis.com");
+        assert_eq!(config.gemini.timeout_seconds, 30);
+        assert_eq!(config.gemini.api_keys[0].weight, 100);
+        assert_eq!(config.gemini.api_keys[0].max_requests_per_minute, 60);
+
+        assert!(!config.auth.enabled);
+        assert_eq!(config.auth.failure_mode, AuthFailureMode::Closed);
+        assert!(config.metrics.enabled);
+        assert_eq!(config.metrics.prometheus_port, 9090);
+        assert!(config.metrics.tls.is_none());
+
+        assert!(ConfigValidator::validate_proxy_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_partial_sections_fill_missing_fields() {
+        let config: ProxyConfig = serde_yaml::from_str(
+            r#"
+server:
+  port: 8443
+  tls:
+    enabled: true
+gemini:
+  api_keys:
+    - id: "primary"
+      key: "valid-test-key-12345678901234567890"
+      weight: 50
+metrics:
+  prometheus_port: 9191
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.port, 8443);
+        assert_eq!(config.server.workers, 4);
+        assert_eq!(config.server.tls.cert_path, "certs/cert.pem");
+        assert_eq!(config.server.tls.key_path, "certs/key.pem");
+        assert_eq!(config.gemini.api_keys[0].weight, 50);
+        assert!(config.metrics.enabled);
+        assert_eq!(config.metrics.prometheus_port, 9191);
+        assert!(ConfigValidator::validate_proxy_config(&config).is_ok());
+
+        // 缺少必需的 gemini 部分时仍然报错
+        assert!(serde_yaml::from_str::<ProxyConfig>("server:\n  port: 8080\n").is_err());
+    }
+
+    #[test]
+    fn test_incoherent_tls_sections_rejected() {
+        let mut config = create_valid_config();
+        config.server.tls.acme = Some(crate::config::AcmeConfig {
+            enabled: true,
+            domains: vec!["api.example.com".to_string()],
+            email: "admin@example.com".to_string(),
+            directory_url: "https://acme-v02.api.letsencrypt.org/directory".to_string(),
+            max_concurrent_validations: 2,
+            challenge: Default::default(),
+            dns: None,
+        });
+        config.metrics.tls = Some(TlsConfig {
+            enabled: true,
+            cert_path: String::new(),
+            ..TlsConfig::default()
+        });
+
+        let fields = ConfigValidator::collect_errors(&config);
+        assert!(fields.iter().any(|e| e.field == "server.tls.acme.enabled"));
+        assert!(fields.iter().any(|e| e.field == "metrics.tls"));
+    }
+
+    #[test]
+    fn test_require_tls_refuses_plain_http_api_server() {
+        let mut config = create_valid_config();
+        config.metrics.require_tls = true;
+        let fields = ConfigValidator::collect_errors(&config);
+        assert!(fields.iter().any(|e| e.field == "metrics.require_tls"));
+
+        config.metrics.tls = Some(TlsConfig {
+            enabled: true,
+            cert_path: "certs/api-cert.pem".to_string(),
+            key_path: "certs/api-key.pem".to_string(),
+            ..TlsConfig::default()
+        });
+        let fields = ConfigValidator::collect_errors(&config);
+        assert!(!fields.iter().any(|e| e.field.starts_with("metrics")));
+    }
+
+    #[test]
+    fn test_wildcard_domain_requires_dns_challenge() {
+        let acme_errors = |config: &ProxyConfig| -> Vec<String> {
+            ConfigValidator::collect_errors(config)
+                .into_iter()
+                .map(|e| e.field)
+                .filter(|field| field.starts_with("server.tls"))
+                .collect()
+        };
+        let mut config = create_valid_config();
+        config.server.tls.enabled = true;
+        config.server.tls.cert_path = "certs/cert.pem".to_string();
+        config.server.tls.key_path = "certs/key.pem".to_string();
+        let mut acme: crate::config::AcmeConfig = serde_yaml::from_str(
+            r#"
+enabled: true
+domains: ["*.example.com", "example.com"]
+email: "admin@example.com"
+directory_url: "https://acme-v02.api.letsencrypt.org/directory"
+"#,
+        )
+        .unwrap();
+        assert_eq!(acme.challenge, AcmeChallengeType::Http01);
+        config.server.tls.acme = Some(acme.clone());
+        assert_eq!(acme_errors(&config), vec!["server.tls.acme.domains[0]"]);
+
+        // DNS-01 缺少 Cloudflare 凭据
+        acme.challenge = AcmeChallengeType::Dns01;
+        acme.dns = Some(
+            serde_yaml::from_str("provider: cloudflare\ncloudflare:\n  api_token: \"cf-token\"\n  zone_id: \"\"\n").unwrap(),
+        );
+        config.server.tls.acme = Some(acme.clone());
+        assert_eq!(acme_errors(&config), vec!["server.tls.acme.dns.cloudflare.zone_id"]);
+
+        acme.dns.as_mut().unwrap().cloudflare.as_mut().unwrap().zone_id = "zone-1".to_string();
+        config.server.tls.acme = Some(acme);
+        assert!(acme_errors(&config).is_empty());
+    }
+
+    #[test]
+    fn test_malformed_ip_rules_rejected() {
+        let mut config = create_valid_config();
+        config.security.ip_rules.allow = vec!["10.0.0.0/8".to_string(), "2001:db8::/200".to_string()];
+        config.security.ip_rules.deny = vec!["192.168.1.300".to_string()];
+
+        let fields: Vec<String> = ConfigValidator::collect_errors(&config).into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["security.ip_rules.allow[1]", "security.ip_rules.deny[0]"]);
+    }
 }
\ No newline at end of file
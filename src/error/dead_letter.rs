@@ -0,0 +1,245 @@
+// src/error/dead_letter.rs
+//! 写入失败的死信记录
+//!
+//! 审计日志或错误日志写入目标文件失败时，条目写入本地死信文件（JSON Lines）而不是直接丢弃。
+//! 后台重试任务按固定间隔重新投递死信条目，投递成功的条目从死信文件中移除，
+//! 失败的条目保留并累加尝试次数。死信文件条目数有上限，超出后丢弃最早的条目
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// 死信条目来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterSource {
+    /// 审计日志
+    Audit,
+    /// 错误日志
+    ErrorLog,
+}
+
+/// 写入失败的条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetterEntry {
+    pub id: String,
+    pub source: DeadLetterSource,
+    /// 原写入目标文件
+    pub target: PathBuf,
+    /// 待写入的内容（一行）
+    pub payload: String,
+    /// 最近一次写入失败的原因
+    pub last_error: String,
+    pub first_failed_at: chrono::DateTime<chrono::Utc>,
+    /// 已尝试写入的次数（包括第一次写入）
+    pub attempts: u32,
+}
+
+impl DeadLetterEntry {
+    pub fn new(source: DeadLetterSource, target: impl Into<PathBuf>, payload: impl Into<String>, error: impl Into<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            source,
+            target: target.into(),
+            payload: payload.into(),
+            last_error: error.into(),
+            first_failed_at: chrono::Utc::now(),
+            attempts: 1,
+        }
+    }
+}
+
+/// 一次重试的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryOutcome {
+    /// 投递成功并移除的条目数
+    pub delivered: usize,
+    /// 仍留在死信文件中的条目数
+    pub remaining: usize,
+}
+
+/// 本地死信文件
+#[derive(Debug)]
+pub struct DeadLetterStore {
+    path: PathBuf,
+    max_entries: usize,
+    /// 串行化对死信文件的读写
+    lock: Mutex<()>,
+}
+
+impl DeadLetterStore {
+    pub fn new(path: impl Into<PathBuf>, max_entries: usize) -> Self {
+        Self {
+            path: path.into(),
+            max_entries: max_entries.max(1),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 记录写入失败的条目
+    pub async fn record(&self, entry: DeadLetterEntry) -> Result<(), String> {
+        let _guard = self.lock.lock().await;
+        let mut entries = self.load_entries().await?;
+        entries.push(entry);
+        if entries.len() > self.max_entries {
+            let dropped = entries.len() - self.max_entries;
+            entries.drain(..dropped);
+            tracing::warn!("死信文件 {} 已满，丢弃最早的 {} 条记录", self.path.display(), dropped);
+        }
+        self.save_entries(&entries).await
+    }
+
+    /// 当前的死信条目
+    pub async fn entries(&self) -> Result<Vec<DeadLetterEntry>, String> {
+        let _guard = self.lock.lock().await;
+        self.load_entries().await
+    }
+
+    /// 重新投递所有死信条目
+    pub async fn retry(&self) -> Result<RetryOutcome, String> {
+        let _guard = self.lock.lock().await;
+        let entries = self.load_entries().await?;
+        if entries.is_empty() {
+            return Ok(RetryOutcome::default());
+        }
+
+        let mut outcome = RetryOutcome::default();
+        let mut remaining = Vec::new();
+        for mut entry in entries {
+            match append_line(&entry.target, &entry.payload).await {
+                Ok(()) => outcome.delivered += 1,
+                Err(e) => {
+                    entry.attempts += 1;
+                    entry.last_error = e;
+                    remaining.push(entry);
+                }
+            }
+        }
+        outcome.remaining = remaining.len();
+
+        self.save_entries(&remaining).await?;
+        Ok(outcome)
+    }
+
+    /// 按固定间隔持续重试
+    pub async fn run_retrier(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.retry().await {
+                Ok(outcome) if outcome.delivered > 0 => tracing::info!(
+                    "已重新投递 {} 条死信记录，剩余 {} 条",
+                    outcome.delivered,
+                    outcome.remaining
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("重试死信记录失败: {}", e),
+            }
+        }
+    }
+
+    async fn load_entries(&self) -> Result<Vec<DeadLetterEntry>, String> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("读取死信文件失败: {}", e)),
+        };
+
+        let mut entries = Vec::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("跳过无法解析的死信记录: {}", e),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 原子地重写死信文件（没有条目时删除文件）
+    async fn save_entries(&self, entries: &[DeadLetterEntry]) -> Result<(), String> {
+        if entries.is_empty() {
+            return match tokio::fs::remove_file(&self.path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("删除死信文件失败: {}", e)),
+                _ => Ok(()),
+            };
+        }
+
+        let mut content = String::new();
+        for entry in entries {
+            let line = serde_json::to_string(entry).map_err(|e| format!("序列化死信记录失败: {}", e))?;
+            content.push_str(&line);
+            content.push('\n');
+        }
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("创建死信目录失败: {}", e))?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        tokio::fs::write(&temp_path, content)
+            .await
+            .map_err(|e| format!("写入死信文件失败: {}", e))?;
+        tokio::fs::rename(&temp_path, &self.path)
+            .await
+            .map_err(|e| format!("写入死信文件失败: {}", e))
+    }
+}
+
+/// 将一行内容追加到目标文件
+async fn append_line(path: &Path, payload: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(|e| format!("打开文件失败: {}", e))?;
+
+    let mut line = payload.to_string();
+    if !line.ends_with('\n') {
+        line.push('\n');
+    }
+    file.write_all(line.as_bytes())
+        .await
+        .map_err(|e| format!("写入文件失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gemini-proxy-dead-letter-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_store_is_bounded() {
+        let dir = temp_dir();
+        let store = DeadLetterStore::new(dir.join("dead_letter.jsonl"), 2);
+        for i in 0..3 {
+            store
+                .record(DeadLetterEntry::new(DeadLetterSource::ErrorLog, dir.join("errors.log"), format!("line{}", i), "failed"))
+                .await
+                .unwrap();
+        }
+
+        let payloads: Vec<String> = store.entries().await.unwrap().into_iter().map(|e| e.payload).collect();
+        assert_eq!(payloads, vec!["line1".to_string(), "line2".to_string()]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -4,6 +4,7 @@
 //! 提供统一的错误日志记录功能，支持多种日志格式和输出目标
 
 use super::{GeminiProxyError, ErrorContext, ErrorSeverity};
+use super::dead_letter::{DeadLetterEntry, DeadLetterSource, DeadLetterStore};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -184,6 +185,8 @@ pub struct ErrorLogger {
     config: ErrorLoggingConfig,
     aggregations: Arc<RwLock<HashMap<String, ErrorAggregation>>>,
     stats: Arc<RwLock<ErrorLoggingStats>>,
+    /// 写入文件失败时保存条目的死信文件
+    dead_letter: Option<Arc<DeadLetterStore>>,
 }
 
 /// 错误日志统计
@@ -195,6 +198,9 @@ pub struct ErrorLoggingStats {
     pub aggregated_errors: u64,
     pub suppressed_errors: u64,
     pub logging_errors: u64,
+    /// 写入失败并转入死信文件的条目数
+    #[serde(default)]
+    pub dead_lettered: u64,
 }
 
 impl Default for ErrorLoggingConfig {
@@ -229,9 +235,16 @@ impl ErrorLogger {
             config,
             aggregations: Arc::new(RwLock::new(HashMap::new())),
             stats: Arc::new(RwLock::new(ErrorLoggingStats::default())),
+            dead_letter: None,
         }
     }
 
+    /// 写入文件失败时将条目保存到死信文件，由后台任务重新投递
+    pub fn with_dead_letter(mut self, dead_letter: Arc<DeadLetterStore>) -> Self {
+        self.dead_letter = Some(dead_letter);
+        self
+    }
+
     /// 记录错误
     pub async fn log_error(&self, error: &GeminiProxyError) -> Result<(), String> {
         if !self.config.enabled {
@@ -263,6 +276,7 @@ impl ErrorLogger {
             if let Err(e) = self.write_to_output(&log_entry, output).await {
                 self.increment_stat("logging_errors").await;
                 eprintln!("日志输出失败: {}", e);
+                self.dead_letter_entry(&log_entry, output, e).await;
             }
         }
 
@@ -562,6 +576,26 @@ impl ErrorLogger {
         Ok(())
     }
 
+    /// 将写入文件失败的条目保存到死信文件（仅文件输出，其他输出目标尚未实现，重试也无法成功）
+    async fn dead_letter_entry(&self, log_entry: &ErrorLogEntry, output: &LogOutput, error: String) {
+        let (dead_letter, path) = match (&self.dead_letter, output) {
+            (Some(dead_letter), LogOutput::File { path }) => (dead_letter, path),
+            _ => return,
+        };
+        let payload = match self.format_log_entry(log_entry).await {
+            Ok(payload) => payload,
+            Err(e) => {
+                eprintln!("格式化死信记录失败: {}", e);
+                return;
+            }
+        };
+
+        match dead_letter.record(DeadLetterEntry::new(DeadLetterSource::ErrorLog, path, payload, error)).await {
+            Ok(()) => self.increment_stat("dead_lettered").await,
+            Err(e) => eprintln!("写入死信文件失败: {}", e),
+        }
+    }
+
     async fn update_stats(&self, error: &GeminiProxyError) {
         let mut stats = self.stats.write().await;
         stats.total_errors_logged += 1;
@@ -579,6 +613,7 @@ impl ErrorLogger {
             "aggregated_errors" => stats.aggregated_errors += 1,
             "suppressed_errors" => stats.suppressed_errors += 1,
             "logging_errors" => stats.logging_errors += 1,
+            "dead_lettered" => stats.dead_lettered += 1,
             _ => {}
         }
     }
@@ -685,4 +720,34 @@ mod tests {
         let stats = logger.get_statistics().await;
         assert_eq!(stats.total_errors_logged, 1);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_failed_file_output_goes_to_dead_letter() {
+        let dir = std::env::temp_dir().join(format!("gemini-proxy-error-log-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // 目标路径的父目录不存在，写入失败
+        let log_path = dir.join("missing").join("errors.log");
+
+        let dead_letter = Arc::new(DeadLetterStore::new(dir.join("dead_letter.jsonl"), 100));
+        let logger = ErrorLogger::new(ErrorLoggingConfig {
+            outputs: vec![LogOutput::File { path: log_path.clone() }],
+            ..ErrorLoggingConfig::default()
+        })
+        .with_dead_letter(dead_letter.clone());
+
+        let error = GeminiProxyError::storage("磁盘已满").with_severity(super::ErrorSeverity::Error);
+        logger.log_error(&error).await.unwrap();
+
+        let stats = logger.get_statistics().await;
+        assert_eq!((stats.logging_errors, stats.dead_lettered), (1, 1));
+        let entries = dead_letter.entries().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source, DeadLetterSource::ErrorLog);
+
+        // 重试时创建缺失的目录并投递成功
+        let outcome = dead_letter.retry().await.unwrap();
+        assert_eq!(outcome.delivered, 1);
+        assert!(std::fs::read_to_string(&log_path).unwrap().contains("磁盘已满"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
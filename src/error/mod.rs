@@ -8,6 +8,7 @@ use std::collections::HashMap;
 use thiserror::Error;
 
 pub mod context;
+pub mod dead_letter;
 pub mod logging;
 pub mod migration;
 pub mod recovery;
@@ -252,6 +253,15 @@ impl GeminiProxyError {
         }
     }
 
+    /// 创建速率限制错误
+    pub fn rate_limit<S: Into<String>>(message: S) -> Self {
+        Self::RateLimit {
+            message: message.into(),
+            source: None,
+            context: ErrorContext::new("rate_limit", "unknown"),
+        }
+    }
+
     /// 创建验证错误
     pub fn validation<S: Into<String>>(message: S, fields: Vec<ValidationError>) -> Self {
         Self::Validation {
@@ -473,7 +483,7 @@ impl GeminiProxyError {
     }
 
     /// 获取错误类型名称
-    fn error_type_name(&self) -> &'static str {
+    pub fn error_type_name(&self) -> &'static str {
         match self {
             Self::Config { .. } => "Config",
             Self::LoadBalancer { .. } => "LoadBalancer",
@@ -575,6 +585,13 @@ impl From<crate::persistence::PersistenceError> for GeminiProxyError {
                     context: ErrorContext::new("storage", "serialization"),
                 }
             }
+            crate::persistence::PersistenceError::MessagePackError(msg) => {
+                GeminiProxyError::Storage {
+                    message: format!("MessagePack 编解码错误: {}", msg),
+                    source: None,
+                    context: ErrorContext::new("storage", "serialization"),
+                }
+            }
             crate::persistence::PersistenceError::DataNotFound(resource) => {
                 GeminiProxyError::NotFound {
                     resource_type: "data".to_string(),
@@ -589,6 +606,13 @@ impl From<crate::persistence::PersistenceError> for GeminiProxyError {
                     context: ErrorContext::new("storage", "validation"),
                 }
             }
+            crate::persistence::PersistenceError::DirectoryUnavailable(msg) => {
+                GeminiProxyError::Storage {
+                    message: format!("存储目录不可用: {}", msg),
+                    source: None,
+                    context: ErrorContext::new("storage", "create_directory"),
+                }
+            }
             crate::persistence::PersistenceError::PermissionError(msg) => {
                 GeminiProxyError::Permission {
                     operation: "storage_access".to_string(),
@@ -597,6 +621,20 @@ impl From<crate::persistence::PersistenceError> for GeminiProxyError {
                         .with_metadata("details", msg),
                 }
             }
+            crate::persistence::PersistenceError::DatabaseError(msg) => {
+                GeminiProxyError::Storage {
+                    message: format!("数据库错误: {}", msg),
+                    source: None,
+                    context: ErrorContext::new("storage", "database"),
+                }
+            }
+            crate::persistence::PersistenceError::EncryptionError(msg) => {
+                GeminiProxyError::Storage {
+                    message: format!("加密错误: {}", msg),
+                    source: None,
+                    context: ErrorContext::new("storage", "encryption"),
+                }
+            }
         }
     }
 }
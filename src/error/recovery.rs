@@ -7,10 +7,28 @@ use super::{GeminiProxyError, ErrorSeverity};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
 use tokio::time::sleep;
 
+/// 自愈操作 `ReloadConfig` 同一时间窗口内最多重新加载一次，避免反复失败的操作触发重载循环
+const CONFIG_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 自愈操作使用的配置重新加载入口（由配置管理实现）
+#[async_trait::async_trait]
+pub trait ConfigReloader: Send + Sync {
+    /// 重新加载并验证配置，失败时运行中的配置保持不变
+    async fn reload_config(&self) -> Result<(), String>;
+}
+
+/// 自愈操作 `ClearCache` 清理的缓存（例如响应缓存）
+pub trait CacheClearer: Send + Sync {
+    /// 缓存名称，用于日志与清理结果
+    fn cache_name(&self) -> &'static str;
+    /// 清空缓存，返回清理的条目数
+    fn clear_cache(&self) -> usize;
+}
+
 /// 恢复策略类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RecoveryStrategy {
@@ -112,7 +130,7 @@ pub enum HealingAction {
 }
 
 /// 熔断器状态
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CircuitBreakerState {
     /// 关闭状态（正常工作）
     Closed,
@@ -161,6 +179,14 @@ pub struct ErrorRecoveryManager {
     stats: Arc<RwLock<RecoveryStats>>,
     /// 配置
     config: RecoveryConfig,
+    /// 未单独注册熔断策略的组件（例如各 API 密钥）使用的熔断参数
+    default_circuit: CircuitParams,
+    /// 自愈操作 `ReloadConfig` 调用的配置重新加载入口
+    config_reloader: Option<Arc<dyn ConfigReloader>>,
+    /// 上一次自愈重新加载的时刻（锁同时保证重新加载串行执行）
+    last_config_reload: Arc<Mutex<Option<Instant>>>,
+    /// 自愈操作 `ClearCache` 清理的缓存
+    cache_clearers: Vec<Arc<dyn CacheClearer>>,
 }
 
 /// 熔断器信息
@@ -169,10 +195,54 @@ struct CircuitBreakerInfo {
     state: CircuitBreakerState,
     failure_count: u32,
     last_failure_time: chrono::DateTime<chrono::Utc>,
+    /// 允许进入半开探测的墙上时间（仅用于展示）
     next_attempt_time: chrono::DateTime<chrono::Utc>,
+    /// 熔断器打开的时刻（单调时钟），恢复判断不受系统时钟回拨影响
+    opened_at: Instant,
     half_open_calls: u32,
 }
 
+impl CircuitBreakerInfo {
+    /// 打开状态是否已超过恢复时间
+    fn recovery_elapsed(&self, recovery_timeout: Duration) -> bool {
+        self.opened_at.elapsed() >= recovery_timeout
+    }
+
+    fn open(&mut self, now: chrono::DateTime<chrono::Utc>, recovery_timeout: Duration) {
+        self.state = CircuitBreakerState::Open;
+        self.opened_at = Instant::now();
+        self.next_attempt_time = now
+            + chrono::Duration::from_std(recovery_timeout).unwrap_or_else(|_| chrono::Duration::seconds(60));
+    }
+}
+
+/// 熔断器参数（组件未注册熔断策略时使用默认值）
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CircuitParams {
+    failure_threshold: u32,
+    recovery_timeout: Duration,
+    half_open_max_calls: u32,
+}
+
+impl Default for CircuitParams {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            recovery_timeout: Duration::from_secs(60),
+            half_open_max_calls: 3,
+        }
+    }
+}
+
+/// 熔断器状态快照（对外只读）
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSnapshot {
+    pub state: CircuitBreakerState,
+    pub failure_count: u32,
+    /// 打开状态下允许进入半开探测的时间
+    pub next_attempt_time: chrono::DateTime<chrono::Utc>,
+}
+
 /// 恢复配置
 #[derive(Debug, Clone)]
 pub struct RecoveryConfig {
@@ -186,6 +256,8 @@ pub struct RecoveryConfig {
     pub history_retention_hours: u64,
     /// 启用自动清理
     pub auto_cleanup_enabled: bool,
+    /// 单次恢复中各次重试退避时间之和的上限：下一次退避会超出上限时不再重试，None 表示不限制
+    pub max_total_retry_delay: Option<Duration>,
 }
 
 impl Default for RecoveryConfig {
@@ -207,6 +279,7 @@ impl Default for RecoveryConfig {
             },
             history_retention_hours: 24,
             auto_cleanup_enabled: true,
+            max_total_retry_delay: None,
         }
     }
 }
@@ -220,9 +293,35 @@ impl ErrorRecoveryManager {
             circuit_breakers: Arc::new(RwLock::new(HashMap::new())),
             stats: Arc::new(RwLock::new(RecoveryStats::default())),
             config,
+            default_circuit: CircuitParams::default(),
+            config_reloader: None,
+            last_config_reload: Arc::new(Mutex::new(None)),
+            cache_clearers: Vec::new(),
         }
     }
 
+    /// 添加自愈操作 `ClearCache` 清理的缓存
+    pub fn with_cache_clearer(mut self, cache_clearer: Arc<dyn CacheClearer>) -> Self {
+        self.cache_clearers.push(cache_clearer);
+        self
+    }
+
+    /// 设置自愈操作 `ReloadConfig` 使用的配置重新加载入口
+    pub fn with_config_reloader(mut self, config_reloader: Arc<dyn ConfigReloader>) -> Self {
+        self.config_reloader = Some(config_reloader);
+        self
+    }
+
+    /// 设置未单独注册熔断策略的组件使用的熔断参数
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, recovery_timeout: Duration, half_open_max_calls: u32) -> Self {
+        self.default_circuit = CircuitParams {
+            failure_threshold,
+            recovery_timeout,
+            half_open_max_calls,
+        };
+        self
+    }
+
     /// 注册恢复策略
     pub async fn register_strategy(&self, key: String, strategy: RecoveryStrategy) {
         let mut strategies = self.recovery_strategies.write().await;
@@ -262,27 +361,130 @@ impl ErrorRecoveryManager {
         }
     }
 
-    /// 检查熔断器状态
+    /// 检查熔断器状态并占用调用名额
+    ///
+    /// 打开状态到达恢复时间后转为半开；半开状态下最多放行 `half_open_max_calls` 个探测请求，
+    /// 探测结果通过 `report_operation_result` 上报后关闭或重新打开熔断器
     pub async fn check_circuit_breaker(&self, component: &str) -> bool {
-        let circuit_breakers = self.circuit_breakers.read().await;
-        if let Some(cb_info) = circuit_breakers.get(component) {
-            match cb_info.state {
-                CircuitBreakerState::Open => {
-                    // 检查是否可以尝试半开
-                    chrono::Utc::now() >= cb_info.next_attempt_time
-                }
-                CircuitBreakerState::HalfOpen => {
-                    cb_info.half_open_calls < 3 // 限制半开状态下的调用次数
+        let params = self.circuit_params(component).await;
+        let mut circuit_breakers = self.circuit_breakers.write().await;
+        let cb_info = match circuit_breakers.get_mut(component) {
+            Some(cb_info) => cb_info,
+            None => return true, // 如果没有熔断器信息，允许通过
+        };
+
+        if cb_info.state == CircuitBreakerState::Open && cb_info.recovery_elapsed(params.recovery_timeout) {
+            cb_info.state = CircuitBreakerState::HalfOpen;
+            cb_info.half_open_calls = 0;
+        }
+
+        match cb_info.state {
+            CircuitBreakerState::Open => false,
+            CircuitBreakerState::HalfOpen => {
+                if cb_info.half_open_calls < params.half_open_max_calls {
+                    cb_info.half_open_calls += 1;
+                    true
+                } else {
+                    false
                 }
+            }
+            CircuitBreakerState::Closed => true,
+        }
+    }
+
+    /// 归还占用的半开探测名额，不计成功或失败
+    ///
+    /// 探测请求因客户端原因结束、没有得到上游结果时调用，熔断器保持半开，名额留给后续请求
+    pub async fn release_probe(&self, component: &str) {
+        let mut circuit_breakers = self.circuit_breakers.write().await;
+        if let Some(cb_info) = circuit_breakers.get_mut(component) {
+            if cb_info.state == CircuitBreakerState::HalfOpen {
+                cb_info.half_open_calls = cb_info.half_open_calls.saturating_sub(1);
+            }
+        }
+    }
+
+    /// 只读检查熔断器是否允许调用，不占用半开探测名额
+    async fn circuit_allows(&self, component: &str) -> bool {
+        let params = self.circuit_params(component).await;
+        let circuit_breakers = self.circuit_breakers.read().await;
+        match circuit_breakers.get(component) {
+            Some(cb_info) => match cb_info.state {
+                CircuitBreakerState::Open => cb_info.recovery_elapsed(params.recovery_timeout),
+                CircuitBreakerState::HalfOpen => cb_info.half_open_calls < params.half_open_max_calls,
                 CircuitBreakerState::Closed => true,
+            },
+            None => true,
+        }
+    }
+
+    /// 获取组件的熔断器参数：优先使用为该组件注册的熔断策略，其次是默认策略，最后是配置的默认熔断参数
+    async fn circuit_params(&self, component: &str) -> CircuitParams {
+        let strategies = self.recovery_strategies.read().await;
+        let strategy = strategies.get(component).unwrap_or(&self.config.default_strategy);
+        match strategy {
+            RecoveryStrategy::CircuitBreaker { failure_threshold, recovery_timeout, half_open_max_calls } => CircuitParams {
+                failure_threshold: *failure_threshold,
+                recovery_timeout: *recovery_timeout,
+                half_open_max_calls: *half_open_max_calls,
+            },
+            _ => self.default_circuit,
+        }
+    }
+
+    /// 熔断器当前阻止调用的组件（只读检查，不占用半开探测名额）
+    pub async fn blocked_components(&self, components: &[String]) -> Vec<String> {
+        let mut blocked = Vec::new();
+        for component in components {
+            if !self.circuit_allows(component).await {
+                blocked.push(component.clone());
             }
-        } else {
-            true // 如果没有熔断器信息，允许通过
         }
+        blocked
+    }
+
+    /// 检查是否所有组件的熔断器都阻止请求（组件列表为空时返回 false）
+    pub async fn all_circuits_open(&self, components: &[String]) -> bool {
+        if components.is_empty() {
+            return false;
+        }
+        for component in components {
+            if self.circuit_allows(component).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 所有报告过结果的组件的熔断器状态快照
+    pub async fn circuit_snapshots(&self) -> HashMap<String, CircuitSnapshot> {
+        let circuit_breakers = self.circuit_breakers.read().await;
+        circuit_breakers
+            .iter()
+            .map(|(component, cb_info)| {
+                let snapshot = CircuitSnapshot {
+                    state: cb_info.state.clone(),
+                    failure_count: cb_info.failure_count,
+                    next_attempt_time: cb_info.next_attempt_time,
+                };
+                (component.clone(), snapshot)
+            })
+            .collect()
+    }
+
+    /// 获取组件的熔断器状态快照（从未报告过结果的组件返回 None）
+    pub async fn circuit_snapshot(&self, component: &str) -> Option<CircuitSnapshot> {
+        let circuit_breakers = self.circuit_breakers.read().await;
+        circuit_breakers.get(component).map(|cb_info| CircuitSnapshot {
+            state: cb_info.state.clone(),
+            failure_count: cb_info.failure_count,
+            next_attempt_time: cb_info.next_attempt_time,
+        })
     }
 
     /// 报告操作结果
     pub async fn report_operation_result(&self, component: &str, success: bool) {
+        let params = self.circuit_params(component).await;
         let mut circuit_breakers = self.circuit_breakers.write().await;
         let now = chrono::Utc::now();
         
@@ -291,6 +493,7 @@ impl ErrorRecoveryManager {
             failure_count: 0,
             last_failure_time: now,
             next_attempt_time: now,
+            opened_at: Instant::now(),
             half_open_calls: 0,
         });
 
@@ -316,16 +519,14 @@ impl ErrorRecoveryManager {
             
             match cb_info.state {
                 CircuitBreakerState::Closed => {
-                    if cb_info.failure_count >= 5 { // 默认失败阈值
-                        cb_info.state = CircuitBreakerState::Open;
-                        cb_info.next_attempt_time = now + chrono::Duration::seconds(60); // 60秒后尝试半开
+                    if cb_info.failure_count >= params.failure_threshold {
+                        cb_info.open(now, params.recovery_timeout);
                         self.increment_stat("circuit_breaker_trips").await;
                     }
                 }
                 CircuitBreakerState::HalfOpen => {
                     // 半开状态下失败，重新开启熔断器
-                    cb_info.state = CircuitBreakerState::Open;
-                    cb_info.next_attempt_time = now + chrono::Duration::seconds(60);
+                    cb_info.open(now, params.recovery_timeout);
                     cb_info.half_open_calls = 0;
                 }
                 _ => {}
@@ -426,6 +627,7 @@ impl ErrorRecoveryManager {
         }
 
         let mut last_error = None;
+        let mut total_delay = Duration::ZERO;
         
         for attempt_num in 1..=max_attempts {
             attempt.attempt_number = attempt_num;
@@ -448,6 +650,15 @@ impl ErrorRecoveryManager {
                     if attempt_num < max_attempts {
                         // 计算延迟时间
                         let delay = self.calculate_backoff_delay(&backoff, attempt_num - 1);
+                        if self
+                            .config
+                            .max_total_retry_delay
+                            .is_some_and(|budget| total_delay + delay > budget)
+                        {
+                            tracing::debug!(attempts = attempt_num, ?total_delay, "重试退避预算已用尽，停止重试");
+                            break;
+                        }
+                        total_delay += delay;
                         attempt.next_retry_at = Some(chrono::Utc::now() + chrono::Duration::from_std(delay).unwrap());
                         
                         self.update_attempt(&attempt_id, attempt.clone()).await;
@@ -470,9 +681,10 @@ impl ErrorRecoveryManager {
         Err(last_error.unwrap())
     }
 
+    /// 按错误所属组件的熔断器执行操作：熔断器阻止时直接失败，否则执行并上报结果
     async fn execute_circuit_breaker_strategy<F, Fut, T>(
         &self,
-        _error: &GeminiProxyError,
+        error: &GeminiProxyError,
         operation: F,
         _failure_threshold: u32,
         _recovery_timeout: Duration,
@@ -483,9 +695,17 @@ impl ErrorRecoveryManager {
         Fut: std::future::Future<Output = Result<T, GeminiProxyError>> + Send,
         T: Send,
     {
-        // 简化实现，直接执行操作
-        // 实际实现应该检查熔断器状态
-        operation().await
+        // 熔断参数由 circuit_params 按组件解析
+        let component = error.get_context().component.clone();
+        if !self.check_circuit_breaker(&component).await {
+            return Err(GeminiProxyError::load_balancer(format!("{} 的熔断器处于打开状态", component))
+                .with_retryable(true)
+                .with_recovery_hint("等待熔断器恢复后重试"));
+        }
+
+        let result = operation().await;
+        self.report_operation_result(&component, result.is_ok()).await;
+        result
     }
 
     async fn execute_fallback_strategy<T>(
@@ -537,12 +757,15 @@ impl ErrorRecoveryManager {
     async fn execute_healing_action(&self, action: HealingAction) {
         match action {
             HealingAction::ClearCache => {
-                // 实现缓存清理逻辑
                 tracing::info!("执行自愈操作：清理缓存");
+                for cache in &self.cache_clearers {
+                    let cleared = cache.clear_cache();
+                    tracing::info!(cache = cache.cache_name(), cleared, "已清理缓存");
+                }
             }
             HealingAction::ReloadConfig => {
-                // 实现配置重加载逻辑
                 tracing::info!("执行自愈操作：重新加载配置");
+                self.reload_config_for_healing().await;
             }
             HealingAction::ResetConnections => {
                 // 实现连接重置逻辑
@@ -561,6 +784,25 @@ impl ErrorRecoveryManager {
         }
     }
 
+    /// 通过配置重新加载入口重新加载并验证配置（例如读取轮换后的密钥）
+    ///
+    /// 距上次自愈重新加载不足最短间隔时跳过，失败的重新加载同样计入间隔，避免持续失败的操作反复触发重载
+    async fn reload_config_for_healing(&self) {
+        let Some(reloader) = &self.config_reloader else {
+            tracing::warn!("未配置配置重新加载入口，跳过重新加载配置");
+            return;
+        };
+        let mut last_reload = self.last_config_reload.lock().await;
+        if last_reload.is_some_and(|at| at.elapsed() < CONFIG_RELOAD_INTERVAL) {
+            tracing::debug!("距上次自愈重新加载不足 {:?}，跳过重新加载配置", CONFIG_RELOAD_INTERVAL);
+            return;
+        }
+        *last_reload = Some(Instant::now());
+        if let Err(e) = reloader.reload_config().await {
+            tracing::warn!("自愈重新加载配置失败，继续使用运行中的配置: {}", e);
+        }
+    }
+
     fn should_retry(&self, error: &GeminiProxyError, conditions: &[RetryCondition]) -> bool {
         if conditions.is_empty() {
             return true; // 如果没有条件，默认重试
@@ -657,6 +899,7 @@ pub fn create_production_recovery_manager() -> ErrorRecoveryManager {
         },
         history_retention_hours: 72, // 3天
         auto_cleanup_enabled: true,
+        max_total_retry_delay: None,
     };
     
     ErrorRecoveryManager::new(config)
@@ -693,6 +936,34 @@ mod tests {
         assert_eq!(call_count.load(Ordering::SeqCst), 3);
     }
 
+    #[tokio::test]
+    async fn test_retry_delays_stay_within_total_budget() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let manager = ErrorRecoveryManager::new(RecoveryConfig {
+            default_strategy: RecoveryStrategy::Retry {
+                max_attempts: 50,
+                backoff: BackoffStrategy::Fixed(Duration::from_millis(20)),
+                retry_conditions: vec![RetryCondition::ErrorType("Network".to_string())],
+            },
+            max_total_retry_delay: Some(Duration::from_millis(100)),
+            ..RecoveryConfig::default()
+        });
+        let call_count = Arc::new(AtomicU32::new(0));
+        let call_count_clone = call_count.clone();
+        let operation = move || {
+            call_count_clone.fetch_add(1, Ordering::SeqCst);
+            async { Err::<String, _>(GeminiProxyError::network("网络错误")) }
+        };
+
+        let started = Instant::now();
+        let result = manager.attempt_recovery(&GeminiProxyError::network("初始错误"), operation).await;
+        assert!(result.is_err());
+        // 5 次 20ms 的退避正好用完 100ms 预算，之后不再重试（不限制时需要 49 次退避、约 980ms）
+        assert_eq!(call_count.load(Ordering::SeqCst), 6);
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
     #[tokio::test]
     async fn test_circuit_breaker() {
         let manager = create_default_recovery_manager();
@@ -707,6 +978,94 @@ mod tests {
         assert!(!can_proceed);
     }
 
+    async fn half_open_manager(half_open_max_calls: u32) -> ErrorRecoveryManager {
+        let manager = create_default_recovery_manager();
+        manager.register_strategy("probe".to_string(), RecoveryStrategy::CircuitBreaker {
+            failure_threshold: 2,
+            recovery_timeout: Duration::from_millis(0),
+            half_open_max_calls,
+        }).await;
+        for _ in 0..2 {
+            manager.report_operation_result("probe", false).await;
+        }
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_half_open_probe_limit_respected() {
+        let manager = half_open_manager(2).await;
+
+        assert!(manager.check_circuit_breaker("probe").await);
+        assert_eq!(manager.circuit_snapshot("probe").await.unwrap().state, CircuitBreakerState::HalfOpen);
+        assert!(manager.check_circuit_breaker("probe").await);
+        assert!(!manager.check_circuit_breaker("probe").await);
+        assert!(!manager.check_circuit_breaker("probe").await);
+        // 只读检查不占用名额
+        assert!(manager.all_circuits_open(&["probe".to_string()]).await);
+    }
+
+    #[tokio::test]
+    async fn test_half_open_probe_outcome_transitions() {
+        let manager = half_open_manager(1).await;
+        assert!(manager.check_circuit_breaker("probe").await);
+        manager.report_operation_result("probe", true).await;
+        assert_eq!(manager.circuit_snapshot("probe").await.unwrap().state, CircuitBreakerState::Closed);
+        assert!(manager.check_circuit_breaker("probe").await);
+        assert!(manager.check_circuit_breaker("probe").await);
+
+        let manager = half_open_manager(1).await;
+        assert!(manager.check_circuit_breaker("probe").await);
+        manager.report_operation_result("probe", false).await;
+        assert_eq!(manager.circuit_snapshot("probe").await.unwrap().state, CircuitBreakerState::Open);
+        // 恢复时间为 0，重新进入半开后名额重置
+        assert!(manager.check_circuit_breaker("probe").await);
+        assert!(!manager.check_circuit_breaker("probe").await);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_recovery_ignores_backward_clock_step() {
+        let manager = half_open_manager(1).await;
+        // 模拟系统时钟回拨一小时：墙上时间的恢复时刻位于"未来"
+        if let Some(cb_info) = manager.circuit_breakers.write().await.get_mut("probe") {
+            cb_info.next_attempt_time = chrono::Utc::now() + chrono::Duration::hours(1);
+        }
+        // 恢复时间按单调时钟计算，熔断器照常进入半开
+        assert!(manager.check_circuit_breaker("probe").await);
+        assert_eq!(manager.circuit_snapshot("probe").await.unwrap().state, CircuitBreakerState::HalfOpen);
+    }
+
+    #[tokio::test]
+    async fn test_configured_circuit_params_apply_to_keys() {
+        let manager = create_default_recovery_manager().with_circuit_breaker(2, Duration::from_secs(60), 1);
+
+        manager.report_operation_result("key1", false).await;
+        assert!(manager.blocked_components(&["key1".to_string()]).await.is_empty());
+        manager.report_operation_result("key1", false).await;
+        assert_eq!(manager.blocked_components(&["key1".to_string(), "key2".to_string()]).await, vec!["key1".to_string()]);
+
+        let snapshots = manager.circuit_snapshots().await;
+        assert_eq!(snapshots["key1"].state, CircuitBreakerState::Open);
+        assert_eq!(snapshots["key1"].failure_count, 2);
+        assert!(!snapshots.contains_key("key2"));
+    }
+
+    #[tokio::test]
+    async fn test_all_circuits_open() {
+        let manager = create_default_recovery_manager();
+        let components = vec!["key1".to_string(), "key2".to_string()];
+
+        assert!(!manager.all_circuits_open(&[]).await);
+        for _ in 0..5 {
+            manager.report_operation_result("key1", false).await;
+        }
+        assert!(!manager.all_circuits_open(&components).await);
+
+        for _ in 0..5 {
+            manager.report_operation_result("key2", false).await;
+        }
+        assert!(manager.all_circuits_open(&components).await);
+    }
+
     #[tokio::test]
     async fn test_backoff_calculation() {
         let manager = create_default_recovery_manager();
@@ -725,4 +1084,98 @@ mod tests {
         assert_eq!(delay2, Duration::from_millis(200));
         assert_eq!(delay3, Duration::from_millis(400));
     }
-}
\ No newline at end of file
+
+    /// 记录重新加载次数的配置入口，重新加载后"轮换"的密钥生效
+    #[derive(Default)]
+    struct CountingReloader {
+        reloads: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl ConfigReloader for CountingReloader {
+        async fn reload_config(&self) -> Result<(), String> {
+            self.reloads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_healing_reloads_then_retries() {
+        use std::sync::atomic::Ordering;
+
+        let reloader = Arc::new(CountingReloader::default());
+        let manager = create_default_recovery_manager().with_config_reloader(reloader.clone());
+        manager.register_strategy("Authentication".to_string(), RecoveryStrategy::SelfHealing {
+            healing_actions: vec![HealingAction::ReloadConfig],
+            validation_check: None,
+        }).await;
+
+        // 操作只有在配置重新加载（读取轮换后的密钥）之后才能成功
+        let observed = reloader.clone();
+        let operation = move || {
+            let reloads = observed.reloads.load(Ordering::SeqCst);
+            async move {
+                if reloads > 0 {
+                    Ok("成功".to_string())
+                } else {
+                    Err(GeminiProxyError::authentication("密钥已失效"))
+                }
+            }
+        };
+
+        let error = GeminiProxyError::authentication("密钥已失效");
+        assert_eq!(manager.attempt_recovery(&error, operation.clone()).await.unwrap(), "成功");
+        assert_eq!(reloader.reloads.load(Ordering::SeqCst), 1);
+
+        // 最短间隔内再次自愈不会重复重新加载
+        assert!(manager.attempt_recovery(&error, operation).await.is_ok());
+        assert_eq!(reloader.reloads.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_clear_cache_healing_flushes_entries_then_retry_repopulates() {
+        use crate::config::ResponseCacheConfig;
+        use crate::proxy::response_cache::{CachedResponse, ResponseCache};
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let cache = Arc::new(ResponseCache::new(&ResponseCacheConfig {
+            enabled: true,
+            ..ResponseCacheConfig::default()
+        }));
+        let manager = create_default_recovery_manager().with_cache_clearer(cache.clone());
+        manager.register_strategy("Storage".to_string(), RecoveryStrategy::SelfHealing {
+            healing_actions: vec![HealingAction::ClearCache],
+            validation_check: None,
+        }).await;
+
+        let cached = |body: &'static str| CachedResponse {
+            content_type: Some("application/json".to_string()),
+            content_encoding: None,
+            body: bytes::Bytes::from_static(body.as_bytes()),
+        };
+        cache.insert("stale".to_string(), cached("{\"stale\":true}"));
+        cache.insert("other".to_string(), cached("{}"));
+
+        // 模拟代理请求：未命中缓存时请求上游并写入缓存
+        let upstream_calls = Arc::new(AtomicU32::new(0));
+        let (request_cache, calls) = (cache.clone(), upstream_calls.clone());
+        let operation = move || {
+            let (cache, calls) = (request_cache.clone(), calls.clone());
+            async move {
+                if let Some(hit) = cache.get("stale") {
+                    return Ok(String::from_utf8(hit.body.to_vec()).unwrap());
+                }
+                calls.fetch_add(1, Ordering::SeqCst);
+                cache.insert("stale".to_string(), cached("{\"fresh\":true}"));
+                Ok("{\"fresh\":true}".to_string())
+            }
+        };
+
+        let error = GeminiProxyError::storage("缓存数据已过期");
+        assert_eq!(manager.attempt_recovery(&error, operation).await.unwrap(), "{\"fresh\":true}");
+        assert_eq!(upstream_calls.load(Ordering::SeqCst), 1);
+        // 其他条目被清理，重试的请求重新填充了缓存
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("stale").unwrap().body, bytes::Bytes::from_static(b"{\"fresh\":true}"));
+    }
+}
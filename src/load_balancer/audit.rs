@@ -4,7 +4,11 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
-// use crate::load_balancer::ApiKey;  // 移除：未使用的导入
+use crate::load_balancer::{KeyWeightChange, UnifiedKeyManager};
+use crate::persistence::config_history::{
+    diff_config_fields, ChangeSource as ConfigChangeSource, ConfigChangeType, ConfigHistoryStore,
+};
+use crate::persistence::PersistenceError;
 
 /// 权重变更记录
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -386,37 +390,86 @@ impl WeightAuditSystem {
     }
 
     /// 回滚到指定快照
+    ///
+    /// 按快照中的权重原子地更新运行中的密钥（快照中的密钥必须全部存在），审计记录使用更新前的实际权重；
+    /// 提供配置历史时同时持久化本次变更，持久化失败则恢复回滚前的权重并返回错误。返回实际生效的权重
     pub async fn rollback_to_snapshot(
         &self,
         snapshot_id: &str,
+        key_manager: &UnifiedKeyManager,
+        config_history: Option<&ConfigHistoryStore>,
         operator: &str,
         reason: &str,
     ) -> Result<HashMap<String, u32>, String> {
         let snapshot = self.get_snapshot(snapshot_id).await
             .ok_or_else(|| format!("Snapshot {} not found", snapshot_id))?;
 
-        // 记录回滚操作
-        for (key_id, &new_weight) in &snapshot.weights {
-            // 这里需要获取当前权重，实际实现时需要从KeyManager获取
-            let old_weight = 100; // 占位符，实际应从系统获取
-            
-            let mut metadata = HashMap::new();
-            metadata.insert("snapshot_id".to_string(), snapshot_id.to_string());
-            metadata.insert("rollback_reason".to_string(), reason.to_string());
+        let mut updates: Vec<(String, u32)> = snapshot.weights.iter()
+            .map(|(key_id, &weight)| (key_id.clone(), weight))
+            .collect();
+        updates.sort();
+        let changes = key_manager.apply_key_weights(&updates).await?;
+
+        let mut metadata = HashMap::new();
+        metadata.insert("snapshot_id".to_string(), snapshot_id.to_string());
+        metadata.insert("rollback_reason".to_string(), reason.to_string());
+
+        if let Some(config_history) = config_history {
+            if let Err(e) = Self::persist_rollback(config_history, &snapshot, &changes, operator, &metadata).await {
+                let restore: Vec<(String, u32)> = changes.iter()
+                    .map(|change| (change.key_id.clone(), change.previous_weight))
+                    .collect();
+                if let Err(restore_error) = key_manager.apply_key_weights(&restore).await {
+                    tracing::error!("回滚持久化失败后恢复原权重失败: {}", restore_error);
+                }
+                return Err(format!("无法保存回滚记录，已恢复回滚前的权重: {}", e));
+            }
+        }
 
+        for change in &changes {
             self.record_weight_change(
                 operator,
                 OperationType::Rollback,
-                key_id,
-                old_weight,
-                new_weight,
+                &change.key_id,
+                change.previous_weight,
+                change.applied_weight,
                 &format!("回滚到快照: {}", snapshot.description),
                 ChangeSource::WebUI,
-                Some(metadata),
+                Some(metadata.clone()),
             ).await?;
         }
 
-        Ok(snapshot.weights.clone())
+        Ok(changes.into_iter().map(|change| (change.key_id, change.applied_weight)).collect())
+    }
+
+    /// 将回滚前后的权重作为一次配置恢复写入配置历史
+    async fn persist_rollback(
+        config_history: &ConfigHistoryStore,
+        snapshot: &WeightSnapshot,
+        changes: &[KeyWeightChange],
+        operator: &str,
+        metadata: &HashMap<String, String>,
+    ) -> Result<String, PersistenceError> {
+        let weights_json = |weight_of: fn(&KeyWeightChange) -> u32| {
+            let weights: HashMap<&str, u32> = changes.iter()
+                .map(|change| (change.key_id.as_str(), weight_of(change)))
+                .collect();
+            serde_json::json!({ "weights": weights }).to_string()
+        };
+        let previous = weights_json(|change| change.previous_weight);
+        let applied = weights_json(|change| change.applied_weight);
+        let changed_fields = diff_config_fields(&previous, &applied)?;
+
+        config_history.record_change(
+            operator,
+            ConfigChangeType::Restore,
+            &format!("回滚权重到快照 {}: {}", snapshot.snapshot_id, snapshot.description),
+            Some(&previous),
+            &applied,
+            changed_fields,
+            ConfigChangeSource::WebUI,
+            Some(metadata.clone()),
+        ).await
     }
 
     /// 生成记录ID
@@ -534,6 +587,21 @@ pub struct WeightTrendPoint {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::load_balancer::key_manager::ApiKey;
+    use crate::persistence::config_history::{ConfigHistoryConfig, ConfigHistoryQuery};
+    use crate::persistence::PersistenceConfig;
+
+    fn create_key_manager(weights: &[(&str, u32)]) -> UnifiedKeyManager {
+        UnifiedKeyManager::new(weights.iter().map(|&(id, weight)| ApiKey {
+            weight,
+            max_requests_per_minute: 1000,
+            ..ApiKey::for_test(id)
+        }).collect())
+    }
+
+    async fn weights_of(key_manager: &UnifiedKeyManager) -> HashMap<String, u32> {
+        key_manager.get_all_keys().await.into_iter().map(|k| (k.id, k.weight)).collect()
+    }
 
     #[tokio::test]
     async fn test_record_weight_change() {
@@ -621,4 +689,77 @@ mod tests {
         assert!(stats.changes_by_type.contains_key("Manual"));
         assert!(stats.changes_by_operator.contains_key("admin"));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_rollback_applies_snapshot_and_records_actual_weights() {
+        let audit_system = WeightAuditSystem::new(AuditConfig::default());
+        let key_manager = create_key_manager(&[("key1", 300), ("key2", 50)]);
+        let snapshot_weights = HashMap::from([("key1".to_string(), 100), ("key2".to_string(), 200)]);
+        let snapshot_id = audit_system.create_snapshot(&snapshot_weights, "基线", "admin").await.unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history = ConfigHistoryStore::new(
+            PersistenceConfig { data_dir: temp_dir.path().to_path_buf(), ..Default::default() },
+            ConfigHistoryConfig::default(),
+        );
+        let applied = audit_system
+            .rollback_to_snapshot(&snapshot_id, &key_manager, Some(&history), "admin", "恢复基线")
+            .await
+            .unwrap();
+        assert_eq!(applied, snapshot_weights);
+        assert_eq!(weights_of(&key_manager).await, snapshot_weights);
+
+        let records = audit_system.query_audit_records(&AuditQuery {
+            start_time: None,
+            end_time: None,
+            operator: None,
+            operation_type: Some(OperationType::Rollback),
+            target_key_id: Some("key1".to_string()),
+            source: None,
+            limit: None,
+            offset: None,
+        }).await;
+        assert_eq!((records[0].old_weight, records[0].new_weight), (300, 100));
+
+        let changes = history.query_changes(&ConfigHistoryQuery {
+            operator: None,
+            change_type: Some(ConfigChangeType::Restore),
+            source: None,
+            start_time: None,
+            end_time: None,
+            changed_field: None,
+            limit: None,
+            offset: None,
+        }).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].changed_fields.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_restores_weights_when_persistence_fails() {
+        let audit_system = WeightAuditSystem::new(AuditConfig::default());
+        let key_manager = create_key_manager(&[("key1", 300), ("key2", 50)]);
+        let original = weights_of(&key_manager).await;
+
+        // 快照中的密钥已不存在时不做任何修改
+        let stale = HashMap::from([("key1".to_string(), 100), ("removed".to_string(), 100)]);
+        let stale_id = audit_system.create_snapshot(&stale, "旧快照", "admin").await.unwrap();
+        assert!(audit_system.rollback_to_snapshot(&stale_id, &key_manager, None, "admin", "").await.is_err());
+        assert_eq!(weights_of(&key_manager).await, original);
+
+        // 写入配置历史失败时恢复回滚前的权重
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history = ConfigHistoryStore::new(
+            PersistenceConfig { data_dir: temp_dir.path().to_path_buf(), max_file_size: 1, ..Default::default() },
+            ConfigHistoryConfig::default(),
+        );
+        let weights = HashMap::from([("key1".to_string(), 100), ("key2".to_string(), 200)]);
+        let snapshot_id = audit_system.create_snapshot(&weights, "基线", "admin").await.unwrap();
+        let result = audit_system
+            .rollback_to_snapshot(&snapshot_id, &key_manager, Some(&history), "admin", "恢复基线")
+            .await;
+        assert!(result.is_err());
+        assert_eq!(weights_of(&key_manager).await, original);
+        assert!(audit_system.get_weight_change_trend("key1", 1).await.is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+// src/load_balancer/hash_ring.rs
+//! 一致性哈希环
+//!
+//! 每个密钥按权重在环上放置若干虚拟节点（环的总节点数约为 `ring_size`），请求属性的哈希落在环上后
+//! 顺时针找到第一个可用密钥的节点。不可用（禁用、冷却、熔断）的密钥只是在查找时被跳过，
+//! 因此只有原本落在该密钥上的请求改变归属，其余请求仍然路由到原来的密钥。
+//! 密钥增删或权重变化时按新的总权重重建环，已有虚拟节点的位置保持不变
+
+/// 默认环大小（虚拟节点总数）
+pub const DEFAULT_RING_SIZE: usize = 1024;
+
+/// 一致性哈希环，节点记录密钥在成员列表中的位置
+#[derive(Debug, Default)]
+pub struct HashRing {
+    /// 成员（密钥 ID 与权重），与构建时的顺序一致
+    members: Vec<(String, u32)>,
+    /// 按哈希值排序的虚拟节点
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    /// 按成员权重分配虚拟节点（每个成员至少一个）；总权重为 0 时平均分配
+    pub fn build(members: Vec<(String, u32)>, ring_size: usize) -> Self {
+        let total_weight: u64 = members.iter().map(|(_, weight)| u64::from(*weight)).sum();
+        let mut points = Vec::new();
+        for (index, (key_id, weight)) in members.iter().enumerate() {
+            let replicas = if total_weight == 0 {
+                ring_size / members.len()
+            } else {
+                (ring_size as u64 * u64::from(*weight) / total_weight) as usize
+            };
+            points.extend((0..replicas.max(1)).map(|replica| (point_hash(key_id, replica), index)));
+        }
+        points.sort_unstable();
+        Self { members, points }
+    }
+
+    /// 环是否按给定成员构建（成员或权重变化时需要重建）
+    pub fn is_built_for<'a>(&self, members: impl IntoIterator<Item = (&'a str, u32)>) -> bool {
+        let mut members = members.into_iter();
+        self.members
+            .iter()
+            .all(|(key_id, weight)| members.next() == Some((key_id.as_str(), *weight)))
+            && members.next().is_none()
+    }
+
+    /// 虚拟节点总数
+    pub fn node_count(&self) -> usize {
+        self.points.len()
+    }
+
+    /// 从哈希位置顺时针查找第一个满足条件的成员，返回其位置
+    pub fn lookup(&self, hash: u64, eligible: impl Fn(usize) -> bool) -> Option<usize> {
+        let start = self.points.partition_point(|(point, _)| *point < hash);
+        self.points[start..]
+            .iter()
+            .chain(&self.points[..start])
+            .map(|(_, index)| *index)
+            .find(|index| eligible(*index))
+    }
+}
+
+/// 虚拟节点的位置（跨进程、跨实例一致）
+fn point_hash(key_id: &str, replica: usize) -> u64 {
+    let digest = openssl::sha::sha256(format!("{}#{}", key_id, replica).as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_nodes_follow_weight() {
+        let ring = HashRing::build(vec![("a".to_string(), 300), ("b".to_string(), 100)], 400);
+        assert_eq!(ring.node_count(), 400);
+        let a_points = ring.points.iter().filter(|(_, index)| *index == 0).count();
+        assert_eq!(a_points, 300);
+
+        // 请求哈希大致按权重分布
+        let a_hits = (0..4000u64)
+            .filter(|i| ring.lookup(i.wrapping_mul(0x9e3779b97f4a7c15), |_| true) == Some(0))
+            .count();
+        assert!((2600..3400).contains(&a_hits), "a_hits = {}", a_hits);
+        assert_eq!(ring.lookup(42, |_| false), None);
+    }
+}
@@ -1,7 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use crate::config::{ApiKeyConfig, WeightBounds};
+use crate::utils::clock::window_expired;
 use crate::load_balancer::weighted_round_robin::{WeightedRoundRobin, WeightStats};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +21,83 @@ pub struct ApiKey {
     pub is_active: bool,
     #[serde(skip)]
     pub failure_count: u32,
+    /// 所属租户（例如 GCP 项目），用于按租户批量排空
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// 标签，用于按标签批量排空
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 所属区域，启用区域延迟优先时按区域测得的延迟选择
+    #[serde(default)]
+    pub region: Option<String>,
+    /// 权重下限（未设置时使用全局默认值）
+    #[serde(default)]
+    pub weight_min: Option<u32>,
+    /// 权重上限（未设置时使用全局默认值）
+    #[serde(default)]
+    pub weight_max: Option<u32>,
+    /// 优先服务的模型（软偏好，不限制其他模型）
+    #[serde(default)]
+    pub preferred_models: Vec<String>,
+    /// 允许服务的模型（为空时服务所有模型）
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+}
+
+/// 由配置创建运行时密钥：计数清零、处于活跃状态
+impl From<&ApiKeyConfig> for ApiKey {
+    fn from(config: &ApiKeyConfig) -> Self {
+        Self {
+            id: config.id.clone(),
+            key: config.key.clone(),
+            weight: config.weight,
+            max_requests_per_minute: config.max_requests_per_minute,
+            current_requests: 0,
+            last_reset: Utc::now(),
+            is_active: true,
+            failure_count: 0,
+            tenant: config.tenant.clone(),
+            tags: config.tags.clone(),
+            region: config.region.clone(),
+            weight_min: config.weight_min,
+            weight_max: config.weight_max,
+            preferred_models: config.preferred_models.clone(),
+            allowed_models: config.allowed_models.clone(),
+        }
+    }
+}
+
+impl ApiKey {
+    /// 该密钥生效的权重上下限
+    pub fn weight_bounds(&self, defaults: &WeightBounds) -> WeightBounds {
+        WeightBounds::resolve(self.weight_min, self.weight_max, defaults)
+    }
+}
+
+#[cfg(test)]
+impl ApiKey {
+    /// 测试用密钥：权重 100、每分钟 60 次请求、处于活跃状态，其余字段为空
+    ///
+    /// 测试只需覆盖关心的字段：`ApiKey { weight: 200, ..ApiKey::for_test("key1") }`
+    pub fn for_test(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            key: format!("test-key-{}", id),
+            weight: 100,
+            max_requests_per_minute: 60,
+            current_requests: 0,
+            last_reset: Utc::now(),
+            is_active: true,
+            failure_count: 0,
+            tenant: None,
+            tags: Vec::new(),
+            region: None,
+            weight_min: None,
+            weight_max: None,
+            preferred_models: Vec::new(),
+            allowed_models: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -66,7 +146,8 @@ impl KeyManager {
         
         for key in keys.iter_mut() {
             // 重置速率限制计数器
-            if (now - key.last_reset).num_seconds() >= 60 {
+            // 时钟回拨时重新开始窗口，而不是等待时钟追上之前的重置时间
+            if window_expired(key.last_reset, now, Duration::from_secs(60)) {
                 key.current_requests = 0;
                 key.last_reset = now;
             }
@@ -195,14 +276,8 @@ mod tests {
 
     fn create_test_api_key(id: &str, weight: u32) -> ApiKey {
         ApiKey {
-            id: id.to_string(),
-            key: format!("test-key-{}", id),
             weight,
-            max_requests_per_minute: 60,
-            current_requests: 0,
-            last_reset: Utc::now(),
-            is_active: true,
-            failure_count: 0,
+            ..ApiKey::for_test(id)
         }
     }
 
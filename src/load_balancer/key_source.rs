@@ -0,0 +1,416 @@
+// src/load_balancer/key_source.rs
+//! 外部密钥来源
+//!
+//! 支持从目录（例如 Kubernetes Secret 挂载）加载 Gemini API 密钥，
+//! 并定期刷新到 `UnifiedKeyManager`，密钥轮换无需重启服务。
+//!
+//! 变化检测使用定时轮询而不是文件系统事件：Kubernetes 轮换 Secret 时只原子替换隐藏的 `..data` 链接，
+//! 文件事件落在被忽略的隐藏条目上；NFS 等网络文件系统与部分容器运行时也不投递 inotify 事件。
+//! 每次轮询只读取一个小目录，开销可以忽略，且写入中途的文件在下一次轮询时自然重试
+
+use crate::config::{ApiKeyConfig, GeminiConfig, KeySourceConfig, LiveConfig};
+use crate::error::{GeminiProxyError, Result};
+use crate::load_balancer::key_manager::ApiKey;
+use crate::load_balancer::UnifiedKeyManager;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 密钥来源接口
+#[async_trait]
+pub trait KeySource: Send + Sync {
+    /// 来源名称（用于日志）
+    fn name(&self) -> String;
+
+    /// 加载当前全部密钥
+    ///
+    /// 任意文件处于不完整状态时返回错误，调用方应保留现有密钥集合
+    async fn load_keys(&self) -> Result<Vec<ApiKeyConfig>>;
+}
+
+/// 密钥文件内容（JSON 格式），缺省字段使用目录级默认值
+#[derive(Debug, Deserialize)]
+struct KeyFileEntry {
+    id: Option<String>,
+    key: String,
+    weight: Option<u32>,
+    max_requests_per_minute: Option<u32>,
+}
+
+/// 单个文件可以包含一个密钥对象或密钥数组
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum KeyFileContent {
+    Single(KeyFileEntry),
+    Multiple(Vec<KeyFileEntry>),
+}
+
+/// 目录密钥来源
+///
+/// 目录下每个文件为一个密钥：纯文本文件的内容即密钥、文件名即密钥ID；
+/// JSON 文件可以包含一个或多个密钥对象。隐藏文件（包括 Kubernetes 的 `..data`
+/// 链接目录）和临时文件会被忽略
+pub struct DirectoryKeySource {
+    directory: PathBuf,
+    default_weight: u32,
+    default_max_requests_per_minute: u32,
+}
+
+impl DirectoryKeySource {
+    /// 创建目录密钥来源
+    pub fn new<P: Into<PathBuf>>(directory: P, default_weight: u32, default_max_requests_per_minute: u32) -> Self {
+        Self {
+            directory: directory.into(),
+            default_weight,
+            default_max_requests_per_minute,
+        }
+    }
+
+    /// 从配置创建
+    pub fn from_config(config: &KeySourceConfig) -> Self {
+        Self::new(
+            &config.directory,
+            config.default_weight,
+            config.default_max_requests_per_minute,
+        )
+    }
+
+    /// 判断文件是否应被忽略（隐藏文件、临时文件）
+    fn is_ignored(file_name: &str) -> bool {
+        file_name.starts_with('.')
+            || file_name.ends_with(".tmp")
+            || file_name.ends_with(".swp")
+            || file_name.ends_with('~')
+    }
+
+    /// 解析单个密钥文件
+    fn parse_key_file(&self, path: &Path, file_name: &str, content: &str) -> Result<Vec<ApiKeyConfig>> {
+        let trimmed = content.trim();
+        let file_id = file_name
+            .strip_suffix(".json")
+            .unwrap_or(file_name)
+            .to_string();
+
+        // 空文件通常意味着写入尚未完成
+        if trimmed.is_empty() {
+            return Err(Self::partial_file_error(path, "文件为空"));
+        }
+
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            let parsed: KeyFileContent = serde_json::from_str(trimmed)
+                .map_err(|e| Self::partial_file_error(path, &format!("JSON 解析失败: {}", e)))?;
+
+            let entries = match parsed {
+                KeyFileContent::Single(entry) => vec![entry],
+                KeyFileContent::Multiple(entries) => entries,
+            };
+            let multiple = entries.len() > 1;
+
+            return entries
+                .into_iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    if entry.key.trim().is_empty() {
+                        return Err(Self::partial_file_error(path, "密钥为空"));
+                    }
+                    let id = entry.id.unwrap_or_else(|| {
+                        if multiple { format!("{}-{}", file_id, i) } else { file_id.clone() }
+                    });
+                    Ok(ApiKeyConfig::new(
+                        id,
+                        entry.key.trim(),
+                        entry.weight.unwrap_or(self.default_weight),
+                        entry
+                            .max_requests_per_minute
+                            .unwrap_or(self.default_max_requests_per_minute),
+                    ))
+                })
+                .collect();
+        }
+
+        Ok(vec![ApiKeyConfig::new(
+            file_id,
+            trimmed,
+            self.default_weight,
+            self.default_max_requests_per_minute,
+        )])
+    }
+
+    fn partial_file_error(path: &Path, reason: &str) -> GeminiProxyError {
+        GeminiProxyError::config_with_context(
+            format!("密钥文件不完整: {}", reason),
+            "key_source",
+            "load_keys",
+        )
+        .with_retryable(true)
+        .with_metadata("file_path", path.display().to_string())
+    }
+}
+
+#[async_trait]
+impl KeySource for DirectoryKeySource {
+    fn name(&self) -> String {
+        format!("directory:{}", self.directory.display())
+    }
+
+    async fn load_keys(&self) -> Result<Vec<ApiKeyConfig>> {
+        let mut entries = tokio::fs::read_dir(&self.directory).await.map_err(|e| {
+            GeminiProxyError::config_with_context(
+                format!("无法读取密钥目录: {}", e),
+                "key_source",
+                "read_dir",
+            )
+            .with_metadata("directory", self.directory.display().to_string())
+        })?;
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if Self::is_ignored(&file_name) {
+                continue;
+            }
+            // 跟随符号链接（Kubernetes Secret 挂载的文件都是链接）
+            let path = entry.path();
+            if tokio::fs::metadata(&path).await.map(|m| m.is_file()).unwrap_or(false) {
+                files.push((file_name, path));
+            }
+        }
+        files.sort();
+
+        let mut keys = Vec::new();
+        for (file_name, path) in files {
+            let content = tokio::fs::read_to_string(&path).await?;
+            keys.extend(self.parse_key_file(&path, &file_name, &content)?);
+        }
+
+        // 检查密钥ID唯一性
+        let mut seen_ids = std::collections::HashSet::new();
+        for key in &keys {
+            if !seen_ids.insert(key.id.clone()) {
+                return Err(GeminiProxyError::config_with_context(
+                    format!("密钥来源中存在重复的密钥ID: {}", key.id),
+                    "key_source",
+                    "load_keys",
+                ));
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+/// 密钥来源刷新器：将来源中的密钥与静态配置的密钥合并后应用到密钥管理器
+pub struct KeySourceWatcher {
+    source: Arc<dyn KeySource>,
+    key_manager: Arc<UnifiedKeyManager>,
+    static_keys: Vec<ApiKeyConfig>,
+    /// 运行配置；设置后每次刷新读取其中的静态密钥，配置重新加载后的静态密钥在下次刷新时合并
+    live_config: Option<Arc<LiveConfig<GeminiConfig>>>,
+}
+
+impl KeySourceWatcher {
+    pub fn new(
+        source: Arc<dyn KeySource>,
+        key_manager: Arc<UnifiedKeyManager>,
+        static_keys: Vec<ApiKeyConfig>,
+    ) -> Self {
+        Self {
+            source,
+            key_manager,
+            static_keys,
+            live_config: None,
+        }
+    }
+
+    /// 从运行配置读取静态密钥，取代构造时传入的密钥
+    pub fn with_live_config(mut self, live_config: Arc<LiveConfig<GeminiConfig>>) -> Self {
+        self.live_config = Some(live_config);
+        self
+    }
+
+    /// 执行一次刷新，返回密钥集合是否发生变化
+    pub async fn refresh(&self) -> Result<bool> {
+        let loaded = self.source.load_keys().await?;
+
+        let mut merged: Vec<ApiKey> = match &self.live_config {
+            Some(live_config) => live_config.load().api_keys.iter().map(ApiKey::from).collect(),
+            None => self.static_keys.iter().map(ApiKey::from).collect(),
+        };
+        for key in &loaded {
+            if merged.iter().any(|k| k.id == key.id) {
+                tracing::warn!("密钥来源 {} 中的密钥 {} 与静态配置冲突，已忽略", self.source.name(), key.id);
+                continue;
+            }
+            merged.push(ApiKey::from(key));
+        }
+
+        Ok(self.key_manager.replace_keys(merged).await)
+    }
+
+    /// 按固定间隔轮询刷新（不使用文件系统事件的原因见模块文档）
+    pub async fn run(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.refresh().await {
+                Ok(true) => tracing::info!("已从 {} 刷新密钥集合", self.source.name()),
+                Ok(false) => {}
+                // 文件处于写入中间状态时保留现有密钥，等待下次刷新
+                Err(e) => tracing::warn!("从 {} 刷新密钥失败，保留现有密钥: {}", self.source.name(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// 模拟 Secret 轮换：先写临时文件再原子重命名
+    fn write_atomically(dir: &Path, name: &str, content: &str) {
+        let tmp_path = dir.join(format!(".{}.tmp", name));
+        std::fs::write(&tmp_path, content).unwrap();
+        std::fs::rename(&tmp_path, dir.join(name)).unwrap();
+    }
+
+    fn create_watcher(dir: &Path) -> (KeySourceWatcher, Arc<UnifiedKeyManager>) {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![]));
+        let source = Arc::new(DirectoryKeySource::new(dir, 100, 60));
+        (KeySourceWatcher::new(source, key_manager.clone(), vec![]), key_manager)
+    }
+
+    #[tokio::test]
+    async fn test_load_plain_and_json_key_files() {
+        let dir = tempdir().unwrap();
+        write_atomically(dir.path(), "primary", "AIza-primary-secret\n");
+        write_atomically(
+            dir.path(),
+            "pool.json",
+            r#"[{"id":"pool-a","key":"AIza-a","weight":50},{"key":"AIza-b"}]"#,
+        );
+
+        let source = DirectoryKeySource::new(dir.path(), 100, 60);
+        let keys = source.load_keys().await.unwrap();
+
+        assert_eq!(keys.len(), 3);
+        let pool_a = keys.iter().find(|k| k.id == "pool-a").unwrap();
+        assert_eq!(pool_a.weight, 50);
+        assert_eq!(pool_a.max_requests_per_minute, 60);
+        assert!(keys.iter().any(|k| k.id == "pool-1" && k.key == "AIza-b"));
+        let primary = keys.iter().find(|k| k.id == "primary").unwrap();
+        assert_eq!(primary.key, "AIza-primary-secret");
+        assert_eq!(primary.weight, 100);
+    }
+
+    #[tokio::test]
+    async fn test_rotated_secret_updates_live_keys() {
+        let dir = tempdir().unwrap();
+        write_atomically(dir.path(), "primary", "old-secret");
+        write_atomically(dir.path(), "backup", "backup-secret");
+
+        let (watcher, key_manager) = create_watcher(dir.path());
+        assert!(watcher.refresh().await.unwrap());
+        key_manager.mark_key_failed("backup").await;
+
+        // 轮换 primary 并移除 backup、新增 extra
+        write_atomically(dir.path(), "primary", "new-secret");
+        write_atomically(dir.path(), "extra", "extra-secret");
+        std::fs::remove_file(dir.path().join("backup")).unwrap();
+
+        assert!(watcher.refresh().await.unwrap());
+        let keys = key_manager.get_all_keys().await;
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.iter().find(|k| k.id == "primary").unwrap().key, "new-secret");
+        assert!(keys.iter().any(|k| k.id == "extra"));
+        assert!(!keys.iter().any(|k| k.id == "backup"));
+
+        // 无变化时不重复应用
+        assert!(!watcher.refresh().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_keys_keep_runtime_state() {
+        let dir = tempdir().unwrap();
+        write_atomically(dir.path(), "primary", "primary-secret");
+
+        let (watcher, key_manager) = create_watcher(dir.path());
+        watcher.refresh().await.unwrap();
+        key_manager.mark_key_failed("primary").await;
+
+        write_atomically(dir.path(), "extra", "extra-secret");
+        watcher.refresh().await.unwrap();
+
+        let keys = key_manager.get_all_keys().await;
+        assert_eq!(keys.iter().find(|k| k.id == "primary").unwrap().failure_count, 1);
+        assert_eq!(keys.iter().find(|k| k.id == "extra").unwrap().failure_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_partial_write_keeps_existing_keys() {
+        let dir = tempdir().unwrap();
+        write_atomically(dir.path(), "primary", "primary-secret");
+
+        let (watcher, key_manager) = create_watcher(dir.path());
+        watcher.refresh().await.unwrap();
+
+        // 非原子写入过程中文件被截断
+        std::fs::write(dir.path().join("pool.json"), r#"[{"id":"pool-a","key":"AI"#).unwrap();
+        assert!(watcher.refresh().await.is_err());
+
+        let keys = key_manager.get_all_keys().await;
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].id, "primary");
+
+        // 写入完成后下一次刷新生效
+        std::fs::write(dir.path().join("pool.json"), r#"[{"id":"pool-a","key":"AIza-a"}]"#).unwrap();
+        assert!(watcher.refresh().await.unwrap());
+        assert_eq!(key_manager.get_all_keys().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_static_keys_take_precedence() {
+        let dir = tempdir().unwrap();
+        write_atomically(dir.path(), "primary", "from-directory");
+        write_atomically(dir.path(), "extra", "extra-secret");
+
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![]));
+        let source = Arc::new(DirectoryKeySource::new(dir.path(), 100, 60));
+        let static_keys = vec![ApiKeyConfig::new("primary", "from-config", 200, 120)];
+        let watcher = KeySourceWatcher::new(source, key_manager.clone(), static_keys);
+        watcher.refresh().await.unwrap();
+
+        let keys = key_manager.get_all_keys().await;
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.iter().find(|k| k.id == "primary").unwrap().key, "from-config");
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_static_keys_merge_on_next_refresh() {
+        let dir = tempdir().unwrap();
+        write_atomically(dir.path(), "extra", "extra-secret");
+
+        let mut gemini: GeminiConfig = serde_yaml::from_str("api_keys: []").unwrap();
+        gemini.api_keys = vec![ApiKeyConfig::new("primary", "from-config", 100, 60)];
+        let live_config = Arc::new(LiveConfig::new(gemini.clone()));
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![]));
+        let source = Arc::new(DirectoryKeySource::new(dir.path(), 100, 60));
+        let watcher = KeySourceWatcher::new(source, key_manager.clone(), vec![]).with_live_config(live_config.clone());
+        watcher.refresh().await.unwrap();
+
+        // 配置重新加载后轮换静态密钥并新增一个
+        gemini.api_keys = vec![
+            ApiKeyConfig::new("primary", "rotated-config", 100, 60),
+            ApiKeyConfig::new("secondary", "secondary-config", 100, 60),
+        ];
+        live_config.store(gemini);
+        assert!(watcher.refresh().await.unwrap());
+
+        let keys = key_manager.get_all_keys().await;
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys.iter().find(|k| k.id == "primary").unwrap().key, "rotated-config");
+        assert!(keys.iter().any(|k| k.id == "secondary"));
+    }
+}
@@ -1,5 +1,8 @@
 // 核心负载均衡模块
 pub mod unified_key_manager;  // 统一密钥管理器（主要使用）
+pub mod key_source;           // 外部密钥来源（目录/Secret 挂载）
+pub mod selection_index;      // 大规模密钥池的加权选择索引
+pub mod hash_ring;            // 一致性哈希环
 
 // 向后兼容和备用实现（保留但不导出以避免警告）
 pub mod key_manager;          // 旧版密钥管理器（已被 unified_key_manager 替代）
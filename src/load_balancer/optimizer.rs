@@ -4,6 +4,7 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
+use crate::config::WeightBounds;
 use crate::load_balancer::key_manager::ApiKey;
 
 /// 权重优化器
@@ -43,6 +44,10 @@ pub struct OptimizerConfig {
     pub max_adjustment_percent: f64,
     /// 优化敏感度（0.0-1.0）
     pub sensitivity: f64,
+    /// 指数加权移动平均系数（0.0-1.0，越大越偏重近期数据），None 表示使用简单平均
+    pub ewma_alpha: Option<f64>,
+    /// 全局默认的权重上下限，应用优化建议时钳制到该范围内（密钥自身的上下限优先）
+    pub weight_bounds: WeightBounds,
 }
 
 /// 优化建议
@@ -111,10 +116,53 @@ impl Default for OptimizerConfig {
             throughput_weight: 0.2,
             max_adjustment_percent: 50.0,
             sensitivity: 0.7,
+            ewma_alpha: None,
+            weight_bounds: WeightBounds::default(),
         }
     }
 }
 
+impl OptimizerConfig {
+    /// 验证参数范围：各权重因子位于 0.0-1.0 且之和为 1.0，敏感度位于 0.0-1.0，
+    /// 最大调整幅度位于 (0, 100]，EWMA 系数位于 (0, 1]
+    pub fn validate(&self) -> Result<(), String> {
+        if self.history_days == 0 {
+            return Err("history_days 必须大于 0".to_string());
+        }
+        if self.min_samples == 0 {
+            return Err("min_samples 必须大于 0".to_string());
+        }
+
+        let weights = [
+            ("response_time_weight", self.response_time_weight),
+            ("success_rate_weight", self.success_rate_weight),
+            ("throughput_weight", self.throughput_weight),
+        ];
+        for (name, weight) in weights {
+            if !(0.0..=1.0).contains(&weight) {
+                return Err(format!("{} 必须在 0.0-1.0 之间，当前为 {}", name, weight));
+            }
+        }
+        let sum: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        if (sum - 1.0).abs() > 1e-6 {
+            return Err(format!("权重因子之和必须为 1.0，当前为 {}", sum));
+        }
+
+        if !(0.0..=100.0).contains(&self.max_adjustment_percent) || self.max_adjustment_percent <= 0.0 {
+            return Err(format!("max_adjustment_percent 必须在 (0, 100] 之间，当前为 {}", self.max_adjustment_percent));
+        }
+        if !(0.0..=1.0).contains(&self.sensitivity) {
+            return Err(format!("sensitivity 必须在 0.0-1.0 之间，当前为 {}", self.sensitivity));
+        }
+        if let Some(alpha) = self.ewma_alpha {
+            if !(0.0..=1.0).contains(&alpha) || alpha <= 0.0 {
+                return Err(format!("ewma_alpha 必须在 (0, 1] 之间，当前为 {}", alpha));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl WeightOptimizer {
     pub fn new(config: OptimizerConfig) -> Self {
         Self {
@@ -128,6 +176,13 @@ impl WeightOptimizer {
         &self.config
     }
 
+    /// 运行时更新配置（验证失败时保持原配置），已记录的历史性能数据保留
+    pub fn update_config(&mut self, config: OptimizerConfig) -> Result<(), String> {
+        config.validate()?;
+        self.config = config;
+        Ok(())
+    }
+
     /// 记录性能指标
     pub async fn record_performance(&self, key_id: &str, metric: PerformanceMetric) {
         let mut history = self.performance_history.write().await;
@@ -154,9 +209,9 @@ impl WeightOptimizer {
             return None;
         }
         
-        let avg_response_time = key_history.iter().map(|m| m.response_time_ms).sum::<f64>() / key_history.len() as f64;
-        let avg_success_rate = key_history.iter().map(|m| m.success_rate).sum::<f64>() / key_history.len() as f64;
-        let avg_throughput = key_history.iter().map(|m| m.throughput_rps).sum::<f64>() / key_history.len() as f64;
+        let avg_response_time = self.average(key_history, |m| m.response_time_ms);
+        let avg_success_rate = self.average(key_history, |m| m.success_rate);
+        let avg_throughput = self.average(key_history, |m| m.throughput_rps);
         
         // 归一化评分 (越低的响应时间越好，越高的成功率和吞吐量越好)
         let response_time_score = 1.0 / (1.0 + avg_response_time / 1000.0); // 归一化到0-1
@@ -170,6 +225,23 @@ impl WeightOptimizer {
         Some(total_score)
     }
 
+    /// 按配置对历史指标求平均：配置了 alpha 时使用指数加权移动平均（历史按时间顺序），否则为简单平均
+    fn average(&self, history: &[PerformanceMetric], value: impl Fn(&PerformanceMetric) -> f64) -> f64 {
+        if history.is_empty() {
+            return 0.0;
+        }
+
+        match self.config.ewma_alpha {
+            Some(alpha) => {
+                let alpha = alpha.clamp(0.0, 1.0);
+                let mut values = history.iter().map(&value);
+                let first = values.next().unwrap_or_default();
+                values.fold(first, |ewma, v| alpha * v + (1.0 - alpha) * ewma)
+            }
+            None => history.iter().map(value).sum::<f64>() / history.len() as f64,
+        }
+    }
+
     /// 生成优化建议
     pub async fn generate_recommendations(
         &self,
@@ -527,7 +599,7 @@ impl WeightOptimizer {
         }
     }
 
-    /// 应用优化建议
+    /// 应用优化建议，建议权重钳制到各密钥的权重上下限内
     pub async fn apply_optimization(
         &self,
         recommendations: &[OptimizationRecommendation],
@@ -535,7 +607,9 @@ impl WeightOptimizer {
     ) -> Result<(), String> {
         for recommendation in recommendations {
             if let Some(api_key) = api_keys.iter_mut().find(|k| k.id == recommendation.key_id) {
-                api_key.weight = recommendation.recommended_weight;
+                api_key.weight = api_key
+                    .weight_bounds(&self.config.weight_bounds)
+                    .clamp(recommendation.recommended_weight);
             } else {
                 return Err(format!("API key '{}' not found", recommendation.key_id));
             }
@@ -567,6 +641,62 @@ mod tests {
         assert!(score.is_none()); // 应该没有评分，因为样本数不足
     }
 
+    fn latency_metric(response_time_ms: f64) -> PerformanceMetric {
+        PerformanceMetric {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            response_time_ms,
+            success_rate: 1.0,
+            error_rate: 0.0,
+            throughput_rps: 50.0,
+            concurrent_requests: 1,
+        }
+    }
+
+    #[test]
+    fn test_ewma_reacts_faster_to_latency_step() {
+        // 90 个 100ms 样本后延迟突增到 1000ms，持续 10 个样本
+        let history: Vec<PerformanceMetric> = (0..100)
+            .map(|i| latency_metric(if i < 90 { 100.0 } else { 1000.0 }))
+            .collect();
+
+        let simple = WeightOptimizer::new(OptimizerConfig::default());
+        let ewma = WeightOptimizer::new(OptimizerConfig {
+            ewma_alpha: Some(0.3),
+            ..OptimizerConfig::default()
+        });
+
+        let simple_avg = simple.average(&history, |m| m.response_time_ms);
+        let ewma_avg = ewma.average(&history, |m| m.response_time_ms);
+
+        assert!((simple_avg - 190.0).abs() < 1e-9);
+        // EWMA 已接近新的延迟水平
+        assert!(ewma_avg > 900.0);
+        assert!(ewma_avg > simple_avg);
+    }
+
+    #[tokio::test]
+    async fn test_ewma_score_penalizes_recent_slowdown() {
+        let config = OptimizerConfig {
+            min_samples: 10,
+            ..OptimizerConfig::default()
+        };
+        let simple = WeightOptimizer::new(config.clone());
+        let ewma = WeightOptimizer::new(OptimizerConfig {
+            ewma_alpha: Some(0.5),
+            ..config
+        });
+
+        for i in 0..20 {
+            let metric = latency_metric(if i < 15 { 100.0 } else { 2000.0 });
+            simple.record_performance("key", metric.clone()).await;
+            ewma.record_performance("key", metric).await;
+        }
+
+        let simple_score = simple.calculate_performance_score("key").await.unwrap();
+        let ewma_score = ewma.calculate_performance_score("key").await.unwrap();
+        assert!(ewma_score < simple_score);
+    }
+
     #[tokio::test]
     async fn test_optimization_recommendations() {
         let optimizer = WeightOptimizer::new(OptimizerConfig::default());
@@ -584,4 +714,48 @@ mod tests {
         assert!(result.confidence_score >= 0.0);
         assert!(result.confidence_score <= 1.0);
     }
-}
\ No newline at end of file
+
+    fn bounded_key(id: &str, weight_min: Option<u32>, weight_max: Option<u32>) -> ApiKey {
+        ApiKey {
+            weight_min,
+            weight_max,
+            ..ApiKey::for_test(id)
+        }
+    }
+
+    fn recommendation(key_id: &str, recommended_weight: u32) -> OptimizationRecommendation {
+        OptimizationRecommendation {
+            key_id: key_id.to_string(),
+            current_weight: 100,
+            recommended_weight,
+            confidence: 0.9,
+            reason: "test".to_string(),
+            expected_improvement: 0.0,
+            risk_level: RiskLevel::Low,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_optimization_respects_weight_bounds() {
+        let optimizer = WeightOptimizer::new(OptimizerConfig {
+            weight_bounds: WeightBounds { min: Some(20), max: Some(400) },
+            ..OptimizerConfig::default()
+        });
+        let mut api_keys = vec![
+            bounded_key("key1", None, None),
+            bounded_key("key2", Some(80), None),
+            bounded_key("key3", None, Some(150)),
+        ];
+
+        optimizer
+            .apply_optimization(
+                &[recommendation("key1", 1000), recommendation("key2", 10), recommendation("key3", 300)],
+                &mut api_keys,
+            )
+            .await
+            .unwrap();
+
+        let weights: Vec<u32> = api_keys.iter().map(|k| k.weight).collect();
+        assert_eq!(weights, vec![400, 80, 150]);
+    }
+}
@@ -0,0 +1,205 @@
+// src/load_balancer/selection_index.rs
+//! 大规模密钥池的加权选择索引
+//!
+//! 以树状数组（Fenwick tree）维护各密钥参与选择的权重前缀和：单个权重变化 O(log n) 更新，
+//! 按累计权重定位密钥 O(log n)。选择目标按黄金分割低差异序列在总权重上推进，长期分布与
+//! 平滑加权轮询一致（各密钥被选中的比例等于权重占比），相邻请求也会分散到不同密钥
+
+/// 黄金分割比的小数部分，用作低差异序列的步长
+const GOLDEN_RATIO_FRACTION: f64 = 0.618_033_988_749_895;
+
+/// 加权选择索引，位置与密钥列表一一对应，权重为 0 的位置不会被选中
+#[derive(Debug, Clone, Default)]
+pub struct SelectionIndex {
+    /// 树状数组节点（下标从 1 开始）
+    tree: Vec<u64>,
+    /// 各位置的当前权重
+    weights: Vec<u64>,
+    /// 总权重
+    total: u64,
+    /// 低差异序列的当前位置（0..1）
+    cursor: f64,
+}
+
+impl SelectionIndex {
+    /// 按权重列表建立索引（O(n)）
+    pub fn new(weights: &[u64]) -> Self {
+        let mut index = Self::default();
+        index.rebuild(weights);
+        index
+    }
+
+    /// 重新建立索引（密钥增删时使用），保留选择序列的位置
+    pub fn rebuild(&mut self, weights: &[u64]) {
+        let len = weights.len();
+        let mut tree = vec![0u64; len + 1];
+        for i in 1..=len {
+            tree[i] += weights[i - 1];
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= len {
+                tree[parent] += tree[i];
+            }
+        }
+        self.tree = tree;
+        self.weights = weights.to_vec();
+        self.total = weights.iter().sum();
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// 更新单个位置的权重（O(log n)），返回权重是否发生变化
+    pub fn set(&mut self, position: usize, weight: u64) -> bool {
+        let old = self.weights[position];
+        if old == weight {
+            return false;
+        }
+        self.weights[position] = weight;
+        self.total = self.total - old + weight;
+        let mut node = position + 1;
+        while node < self.tree.len() {
+            self.tree[node] = self.tree[node] - old + weight;
+            node += node & node.wrapping_neg();
+        }
+        true
+    }
+
+    /// 定位累计权重区间包含 `target` 的位置（O(log n)），超出总权重时返回 None
+    pub fn find(&self, mut target: u64) -> Option<usize> {
+        if target >= self.total {
+            return None;
+        }
+        let mut position = 0;
+        let mut step = self.search_step();
+        while step > 0 {
+            let next = position + step;
+            if next < self.tree.len() && self.tree[next] <= target {
+                position = next;
+                target -= self.tree[next];
+            }
+            step >>= 1;
+        }
+        Some(position)
+    }
+
+    /// `find` 的迭代次数（⌊log2 n⌋ + 1）
+    pub fn search_depth(&self) -> u32 {
+        let step = self.search_step();
+        if step == 0 {
+            0
+        } else {
+            step.trailing_zeros() + 1
+        }
+    }
+
+    /// 按低差异序列选择下一个位置，总权重为 0 时返回 None
+    pub fn next(&mut self) -> Option<usize> {
+        if self.total == 0 {
+            return None;
+        }
+        self.cursor = (self.cursor + GOLDEN_RATIO_FRACTION).fract();
+        let target = ((self.cursor * self.total as f64) as u64).min(self.total - 1);
+        self.find(target)
+    }
+
+    /// 不超过长度的最大 2 的幂
+    fn search_step(&self) -> usize {
+        match self.len() {
+            0 => 0,
+            len => 1 << (usize::BITS - 1 - len.leading_zeros()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 线性扫描累计权重（未建索引时的做法）
+    fn linear_find(weights: &[u64], target: u64) -> Option<usize> {
+        let mut cumulative = 0;
+        for (i, &weight) in weights.iter().enumerate() {
+            cumulative += weight;
+            if target < cumulative {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    /// 平滑加权轮询（管理器中小规模密钥池使用的算法）
+    fn smooth_wrr_counts(weights: &[u64], selections: usize) -> Vec<u64> {
+        let total: i64 = weights.iter().sum::<u64>() as i64;
+        let mut current = vec![0i64; weights.len()];
+        let mut counts = vec![0u64; weights.len()];
+        for _ in 0..selections {
+            for (c, &w) in current.iter_mut().zip(weights) {
+                *c += w as i64;
+            }
+            let selected = (0..weights.len()).max_by_key(|&i| current[i]).unwrap();
+            current[selected] -= total;
+            counts[selected] += 1;
+        }
+        counts
+    }
+
+    fn pool_weights(n: usize) -> Vec<u64> {
+        (0..n).map(|i| (i % 5) as u64 + 1).collect()
+    }
+
+    #[test]
+    fn test_find_matches_linear_scan_after_updates() {
+        let mut weights: Vec<u64> = (0..1000).map(|i| (i * 7 % 11) as u64).collect();
+        let mut index = SelectionIndex::new(&weights);
+        assert_eq!(index.total(), weights.iter().sum::<u64>());
+        for target in 0..=index.total() {
+            assert_eq!(index.find(target), linear_find(&weights, target), "target {}", target);
+        }
+
+        // 增量更新后与重新扫描的结果一致，权重为 0 的位置不会被选中
+        for i in (0..1000).step_by(3) {
+            weights[i] = if i % 2 == 0 { 0 } else { weights[i] + 4 };
+            index.set(i, weights[i]);
+        }
+        assert!(!index.set(1, weights[1]));
+        assert_eq!(index.total(), weights.iter().sum::<u64>());
+        for target in 0..=index.total() {
+            assert_eq!(index.find(target), linear_find(&weights, target), "target {}", target);
+        }
+    }
+
+    #[test]
+    fn test_indexed_selection_matches_smooth_wrr_distribution() {
+        let weights = pool_weights(1000);
+        let rounds = 20;
+        let selections = weights.iter().sum::<u64>() as usize * rounds;
+
+        let expected = smooth_wrr_counts(&weights, selections);
+        let mut index = SelectionIndex::new(&weights);
+        let mut counts = vec![0u64; weights.len()];
+        for _ in 0..selections {
+            counts[index.next().unwrap()] += 1;
+        }
+
+        for (i, (&count, &naive)) in counts.iter().zip(&expected).enumerate() {
+            assert_eq!(naive, weights[i] * rounds as u64);
+            assert!(count.abs_diff(naive) <= 5, "key {}: indexed {} naive {}", i, count, naive);
+        }
+        assert!(SelectionIndex::new(&[0, 0]).next().is_none());
+    }
+
+    #[test]
+    fn test_selection_is_logarithmic_for_1000_keys() {
+        let weights = pool_weights(1000);
+        let index = SelectionIndex::new(&weights);
+        // 每次定位最多 ⌊log2 1000⌋ + 1 = 10 步
+        assert_eq!(index.search_depth(), 10);
+        assert_eq!(SelectionIndex::new(&pool_weights(2000)).search_depth(), 11);
+        assert_eq!(SelectionIndex::new(&pool_weights(1)).search_depth(), 1);
+    }
+}
@@ -7,7 +7,7 @@ use crate::load_balancer::{
     key_manager::ApiKey, 
     audit::{WeightAuditSystem, OperationType, ChangeSource}
 };
-use crate::config::ApiKeyConfig;
+use crate::config::{ApiKeyConfig, WeightBounds};
 // use crate::persistence::weight_presets::WeightPresetStore;
 
 /// 权重预设模板
@@ -100,6 +100,79 @@ pub struct ToolkitConfig {
     pub max_presets: usize,
     /// 风险阈值
     pub risk_thresholds: RiskThresholds,
+    /// 基于性能自动调整权重时的评分权重与调整范围
+    pub performance_weighting: PerformanceWeighting,
+    /// 全局默认的权重上下限，所有工具操作的结果都钳制到该范围内（密钥自身的上下限优先）
+    pub weight_bounds: WeightBounds,
+}
+
+/// 将目标权重钳制到上下限内；发生钳制时在审计元数据中记录 `weight_clamped` 与原始的 `requested_weight`
+fn clamp_weight(
+    bounds: WeightBounds,
+    requested: u32,
+    metadata: Option<HashMap<String, String>>,
+) -> (u32, Option<HashMap<String, String>>) {
+    let applied = bounds.clamp(requested);
+    if applied == requested {
+        return (applied, metadata);
+    }
+    let mut metadata = metadata.unwrap_or_default();
+    metadata.insert("weight_clamped".to_string(), "true".to_string());
+    metadata.insert("requested_weight".to_string(), requested.to_string());
+    (applied, Some(metadata))
+}
+
+/// 性能综合评分配置
+///
+/// 综合评分 = 响应时间因子 × `response_time_weight` + 成功率 × `success_rate_weight`，
+/// 新权重 = 当前权重 × 综合评分 × `max_adjustment`，并限制在 [`min_weight`, 当前权重 × `max_increase_ratio`] 内
+#[derive(Debug, Clone)]
+pub struct PerformanceWeighting {
+    /// 响应时间在综合评分中的占比
+    pub response_time_weight: f64,
+    /// 成功率在综合评分中的占比（与响应时间占比之和必须为 1.0）
+    pub success_rate_weight: f64,
+    /// 综合评分为满分时的调整倍数
+    pub max_adjustment: f64,
+    /// 单次调整后权重相对当前权重的最大倍数
+    pub max_increase_ratio: f64,
+    /// 调整后的最小权重
+    pub min_weight: u32,
+}
+
+impl Default for PerformanceWeighting {
+    fn default() -> Self {
+        Self {
+            response_time_weight: 0.6,
+            success_rate_weight: 0.4,
+            max_adjustment: 1.2, // 最多增加20%
+            max_increase_ratio: 2.0,
+            min_weight: 10,
+        }
+    }
+}
+
+impl PerformanceWeighting {
+    /// 验证评分占比之和为 1.0 且调整范围有效
+    pub fn validate(&self) -> Result<(), String> {
+        if self.response_time_weight < 0.0 || self.success_rate_weight < 0.0 {
+            return Err("评分占比不能为负数".to_string());
+        }
+        let sum = self.response_time_weight + self.success_rate_weight;
+        if (sum - 1.0).abs() > 1e-6 {
+            return Err(format!("响应时间与成功率的评分占比之和必须为 1.0，当前为 {}", sum));
+        }
+        if self.max_adjustment <= 0.0 {
+            return Err("调整倍数必须大于 0".to_string());
+        }
+        if self.max_increase_ratio < 1.0 {
+            return Err("最大增长倍数不能小于 1.0".to_string());
+        }
+        if self.min_weight == 0 {
+            return Err("最小权重必须大于 0".to_string());
+        }
+        Ok(())
+    }
 }
 
 /// 风险阈值配置
@@ -124,10 +197,19 @@ impl Default for ToolkitConfig {
                 variance_threshold: 0.5,
                 max_single_key_ratio: 0.7,
             },
+            performance_weighting: PerformanceWeighting::default(),
+            weight_bounds: WeightBounds::default(),
         }
     }
 }
 
+impl ToolkitConfig {
+    /// 验证工具集配置
+    pub fn validate(&self) -> Result<(), String> {
+        self.performance_weighting.validate()
+    }
+}
+
 impl WeightManagementToolkit {
     pub fn new(
         audit_system: Arc<RwLock<WeightAuditSystem>>,
@@ -140,20 +222,6 @@ impl WeightManagementToolkit {
         }
     }
 
-    /// 将 ApiKeyConfig 转换为 ApiKey
-    fn config_to_api_key(config: &ApiKeyConfig) -> ApiKey {
-        ApiKey {
-            id: config.id.clone(),
-            key: config.key.clone(),
-            weight: config.weight,
-            max_requests_per_minute: config.max_requests_per_minute,
-            current_requests: 0,
-            last_reset: chrono::Utc::now(),
-            is_active: true,
-            failure_count: 0,
-        }
-    }
-
     /// 将 ApiKey 的权重更新回 ApiKeyConfig
     fn update_config_weight(config: &mut ApiKeyConfig, api_key: &ApiKey) {
         config.weight = api_key.weight;
@@ -221,14 +289,16 @@ impl WeightManagementToolkit {
         let audit_system = self.audit_system.read().await;
         
         for config in api_key_configs.iter_mut() {
-            if let Some(&new_weight) = preset.weights.get(&config.id) {
+            if let Some(&requested) = preset.weights.get(&config.id) {
                 let old_weight = config.weight;
-                config.weight = new_weight;
                 
                 // 记录变更
                 let mut metadata = HashMap::new();
                 metadata.insert("preset_id".to_string(), preset_id.to_string());
                 metadata.insert("preset_name".to_string(), preset.name.clone());
+                let (new_weight, metadata) =
+                    clamp_weight(config.weight_bounds(&self.config.weight_bounds), requested, Some(metadata));
+                config.weight = new_weight;
                 
                 audit_system.record_weight_change(
                     operator,
@@ -238,7 +308,7 @@ impl WeightManagementToolkit {
                     new_weight,
                     &format!("应用预设: {}", preset.name),
                     ChangeSource::WebUI,
-                    Some(metadata),
+                    metadata,
                 ).await?;
             }
         }
@@ -260,14 +330,16 @@ impl WeightManagementToolkit {
         let audit_system = self.audit_system.read().await;
         
         for api_key in api_keys.iter_mut() {
-            if let Some(&new_weight) = preset.weights.get(&api_key.id) {
+            if let Some(&requested) = preset.weights.get(&api_key.id) {
                 let old_weight = api_key.weight;
-                api_key.weight = new_weight;
                 
                 // 记录变更
                 let mut metadata = HashMap::new();
                 metadata.insert("preset_id".to_string(), preset_id.to_string());
                 metadata.insert("preset_name".to_string(), preset.name.clone());
+                let (new_weight, metadata) =
+                    clamp_weight(api_key.weight_bounds(&self.config.weight_bounds), requested, Some(metadata));
+                api_key.weight = new_weight;
                 
                 audit_system.record_weight_change(
                     operator,
@@ -277,7 +349,7 @@ impl WeightManagementToolkit {
                     new_weight,
                     &format!("应用预设: {}", preset.name),
                     ChangeSource::WebUI,
-                    Some(metadata),
+                    metadata,
                 ).await?;
             }
         }
@@ -300,16 +372,16 @@ impl WeightManagementToolkit {
 
     /// 分析当前权重配置（使用 ApiKeyConfig）
     pub async fn analyze_weights_config(&self, api_key_configs: &[ApiKeyConfig]) -> WeightAnalysis {
-        let api_keys: Vec<ApiKey> = api_key_configs.iter().map(Self::config_to_api_key).collect();
+        let api_keys: Vec<ApiKey> = api_key_configs.iter().map(ApiKey::from).collect();
         self.analyze_weights(&api_keys).await
     }
 
     /// 分析当前权重配置
     pub async fn analyze_weights(&self, api_keys: &[ApiKey]) -> WeightAnalysis {
         let weights: Vec<u32> = api_keys.iter().map(|k| k.weight).collect();
-        let total_weight: u32 = weights.iter().sum();
+        let stats = WeightStats::from_weights(&weights);
         
-        if weights.is_empty() || total_weight == 0 {
+        if weights.is_empty() || stats.total == 0 {
             return WeightAnalysis {
                 load_balance_score: 0.0,
                 variance_coefficient: 0.0,
@@ -329,19 +401,19 @@ impl WeightManagementToolkit {
         }
 
         // 计算负载均衡评分
-        let load_balance_score = self.calculate_balance_score(&weights);
+        let load_balance_score = self.calculate_balance_score(&stats);
         
         // 计算方差系数
-        let variance_coefficient = self.calculate_variance_coefficient(&weights);
+        let variance_coefficient = self.calculate_variance_coefficient(&stats);
         
         // 计算效率评分
         let efficiency_score = self.calculate_efficiency_score(api_keys);
         
         // 生成调整建议
-        let recommended_adjustments = self.generate_weight_recommendations(api_keys);
+        let recommended_adjustments = self.generate_weight_recommendations(api_keys, &stats);
         
         // 风险评估
-        let risk_assessment = self.assess_risks(api_keys, &weights, total_weight);
+        let risk_assessment = self.assess_risks(api_keys, &stats);
 
         WeightAnalysis {
             load_balance_score,
@@ -369,7 +441,8 @@ impl WeightManagementToolkit {
         
         for config in api_key_configs.iter_mut() {
             let old_weight = config.weight;
-            let new_weight = ((config.weight as f64 / current_total as f64) * target_total as f64) as u32;
+            let requested = ((config.weight as f64 / current_total as f64) * target_total as f64) as u32;
+            let (new_weight, metadata) = clamp_weight(config.weight_bounds(&self.config.weight_bounds), requested, None);
             config.weight = new_weight;
             
             // 记录变更
@@ -381,7 +454,7 @@ impl WeightManagementToolkit {
                 new_weight,
                 &format!("权重标准化 (目标总重: {})", target_total),
                 ChangeSource::WebUI,
-                None,
+                metadata,
             ).await?;
         }
 
@@ -405,7 +478,8 @@ impl WeightManagementToolkit {
         
         for api_key in api_keys.iter_mut() {
             let old_weight = api_key.weight;
-            let new_weight = ((api_key.weight as f64 / current_total as f64) * target_total as f64) as u32;
+            let requested = ((api_key.weight as f64 / current_total as f64) * target_total as f64) as u32;
+            let (new_weight, metadata) = clamp_weight(api_key.weight_bounds(&self.config.weight_bounds), requested, None);
             api_key.weight = new_weight;
             
             // 记录变更
@@ -417,7 +491,7 @@ impl WeightManagementToolkit {
                 new_weight,
                 &format!("权重标准化 (目标总重: {})", target_total),
                 ChangeSource::WebUI,
-                None,
+                metadata,
             ).await?;
         }
 
@@ -442,11 +516,12 @@ impl WeightManagementToolkit {
 
         for (index, config) in api_key_configs.iter_mut().enumerate() {
             let old_weight = config.weight;
-            let new_weight = if index < remainder as usize {
+            let requested = if index < remainder as usize {
                 weight_per_key + 1
             } else {
                 weight_per_key
             };
+            let (new_weight, metadata) = clamp_weight(config.weight_bounds(&self.config.weight_bounds), requested, None);
             config.weight = new_weight;
             
             // 记录变更
@@ -458,7 +533,7 @@ impl WeightManagementToolkit {
                 new_weight,
                 "权重均分分配",
                 ChangeSource::WebUI,
-                None,
+                metadata,
             ).await?;
         }
 
@@ -483,11 +558,12 @@ impl WeightManagementToolkit {
 
         for (index, api_key) in api_keys.iter_mut().enumerate() {
             let old_weight = api_key.weight;
-            let new_weight = if index < remainder as usize {
+            let requested = if index < remainder as usize {
                 weight_per_key + 1
             } else {
                 weight_per_key
             };
+            let (new_weight, metadata) = clamp_weight(api_key.weight_bounds(&self.config.weight_bounds), requested, None);
             api_key.weight = new_weight;
             
             // 记录变更
@@ -499,7 +575,7 @@ impl WeightManagementToolkit {
                 new_weight,
                 "权重均分分配",
                 ChangeSource::WebUI,
-                None,
+                metadata,
             ).await?;
         }
 
@@ -518,7 +594,9 @@ impl WeightManagementToolkit {
         for config in api_key_configs.iter_mut() {
             if let Some(perf) = performance_data.get(&config.id) {
                 let old_weight = config.weight;
-                let new_weight = self.calculate_performance_based_weight(perf, old_weight);
+                let requested = self.calculate_performance_based_weight(perf, old_weight);
+                let (new_weight, metadata) =
+                    clamp_weight(config.weight_bounds(&self.config.weight_bounds), requested, None);
                 
                 if new_weight != old_weight {
                     config.weight = new_weight;
@@ -533,7 +611,7 @@ impl WeightManagementToolkit {
                         &format!("基于性能自动调整 (响应时间: {}ms, 成功率: {:.1}%)", 
                                 perf.avg_response_time, perf.success_rate * 100.0),
                         ChangeSource::Monitor,
-                        None,
+                        metadata,
                     ).await?;
                 }
             }
@@ -554,7 +632,9 @@ impl WeightManagementToolkit {
         for api_key in api_keys.iter_mut() {
             if let Some(perf) = performance_data.get(&api_key.id) {
                 let old_weight = api_key.weight;
-                let new_weight = self.calculate_performance_based_weight(perf, old_weight);
+                let requested = self.calculate_performance_based_weight(perf, old_weight);
+                let (new_weight, metadata) =
+                    clamp_weight(api_key.weight_bounds(&self.config.weight_bounds), requested, None);
                 
                 if new_weight != old_weight {
                     api_key.weight = new_weight;
@@ -569,7 +649,7 @@ impl WeightManagementToolkit {
                         &format!("基于性能自动调整 (响应时间: {}ms, 成功率: {:.1}%)", 
                                 perf.avg_response_time, perf.success_rate * 100.0),
                         ChangeSource::Monitor,
-                        None,
+                        metadata,
                     ).await?;
                 }
             }
@@ -580,7 +660,7 @@ impl WeightManagementToolkit {
 
     /// 权重健康检查（使用 ApiKeyConfig）
     pub async fn health_check_config(&self, api_key_configs: &[ApiKeyConfig]) -> HealthCheckResult {
-        let api_keys: Vec<ApiKey> = api_key_configs.iter().map(Self::config_to_api_key).collect();
+        let api_keys: Vec<ApiKey> = api_key_configs.iter().map(ApiKey::from).collect();
         self.health_check(&api_keys).await
     }
 
@@ -655,34 +735,20 @@ impl WeightManagementToolkit {
             .as_nanos())
     }
 
-    fn calculate_balance_score(&self, weights: &[u32]) -> f64 {
-        if weights.is_empty() {
+    fn calculate_balance_score(&self, stats: &WeightStats) -> f64 {
+        if stats.count == 0 {
             return 0.0;
         }
 
-        let mean = weights.iter().sum::<u32>() as f64 / weights.len() as f64;
-        let variance = weights.iter()
-            .map(|&w| (w as f64 - mean).powi(2))
-            .sum::<f64>() / weights.len() as f64;
-        
-        let cv = variance.sqrt() / mean;
+        let cv = stats.std_dev / stats.mean;
         (100.0 * (1.0 - cv.min(1.0))).max(0.0)
     }
 
-    fn calculate_variance_coefficient(&self, weights: &[u32]) -> f64 {
-        if weights.is_empty() {
-            return 0.0;
-        }
-
-        let mean = weights.iter().sum::<u32>() as f64 / weights.len() as f64;
-        let variance = weights.iter()
-            .map(|&w| (w as f64 - mean).powi(2))
-            .sum::<f64>() / weights.len() as f64;
-        
-        if mean == 0.0 {
+    fn calculate_variance_coefficient(&self, stats: &WeightStats) -> f64 {
+        if stats.count == 0 || stats.mean == 0.0 {
             0.0
         } else {
-            variance.sqrt() / mean
+            stats.std_dev / stats.mean
         }
     }
 
@@ -692,9 +758,9 @@ impl WeightManagementToolkit {
         enabled_ratio * 100.0
     }
 
-    fn generate_weight_recommendations(&self, api_keys: &[ApiKey]) -> Vec<WeightRecommendation> {
+    fn generate_weight_recommendations(&self, api_keys: &[ApiKey], stats: &WeightStats) -> Vec<WeightRecommendation> {
         let mut recommendations = Vec::new();
-        let total_weight: u32 = api_keys.iter().map(|k| k.weight).sum();
+        let total_weight = stats.total;
         
         if total_weight == 0 {
             return recommendations;
@@ -731,14 +797,14 @@ impl WeightManagementToolkit {
         recommendations
     }
 
-    fn assess_risks(&self, api_keys: &[ApiKey], weights: &[u32], total_weight: u32) -> RiskAssessment {
+    fn assess_risks(&self, api_keys: &[ApiKey], stats: &WeightStats) -> RiskAssessment {
         let mut risk_factors = Vec::new();
         let mut mitigation_suggestions = Vec::new();
 
         // 检查权重分布风险
-        if !weights.is_empty() {
-            let max_weight = *weights.iter().max().unwrap();
-            if max_weight as f64 / total_weight as f64 > self.config.risk_thresholds.max_single_key_ratio {
+        if stats.count > 0 {
+            let max_weight = stats.max;
+            if max_weight as f64 / stats.total as f64 > self.config.risk_thresholds.max_single_key_ratio {
                 risk_factors.push(RiskFactor {
                     factor_type: "SinglePointFailure".to_string(),
                     description: "单个密钥权重占比过高，存在单点故障风险".to_string(),
@@ -783,15 +849,49 @@ impl WeightManagementToolkit {
     }
 
     fn calculate_performance_based_weight(&self, perf: &PerformanceMetrics, current_weight: u32) -> u32 {
+        let weighting = &self.config.performance_weighting;
+        
         // 基于性能指标计算新权重
         let response_time_factor = 1.0 / (perf.avg_response_time / 1000.0 + 1.0); // 响应时间越短越好
         let success_rate_factor = perf.success_rate; // 成功率越高越好
-        let performance_score = response_time_factor * 0.6 + success_rate_factor * 0.4;
+        let performance_score = response_time_factor * weighting.response_time_weight
+            + success_rate_factor * weighting.success_rate_weight;
         
-        let adjustment_factor = performance_score * 1.2; // 最多增加20%
+        let adjustment_factor = performance_score * weighting.max_adjustment;
         let new_weight = (current_weight as f64 * adjustment_factor) as u32;
+        let max_weight = (current_weight as f64 * weighting.max_increase_ratio) as u32;
         
-        new_weight.max(10).min(current_weight * 2) // 限制调整范围
+        new_weight.max(weighting.min_weight).min(max_weight.max(weighting.min_weight)) // 限制调整范围
+    }
+}
+
+/// 权重分析的中间结果，计算一次后供各项评分与风险评估复用，避免对密钥列表重复遍历
+#[derive(Debug, Clone, Copy, Default)]
+struct WeightStats {
+    count: usize,
+    total: u32,
+    max: u32,
+    mean: f64,
+    std_dev: f64,
+}
+
+impl WeightStats {
+    fn from_weights(weights: &[u32]) -> Self {
+        if weights.is_empty() {
+            return Self::default();
+        }
+        let (total, max) = weights.iter().fold((0u32, 0u32), |(total, max), &w| (total + w, max.max(w)));
+        let mean = total as f64 / weights.len() as f64;
+        let variance = weights.iter()
+            .map(|&w| (w as f64 - mean).powi(2))
+            .sum::<f64>() / weights.len() as f64;
+        Self {
+            count: weights.len(),
+            total,
+            max,
+            mean,
+            std_dev: variance.sqrt(),
+        }
     }
 }
 
@@ -826,6 +926,7 @@ pub enum HealthStatus {
 mod tests {
     use super::*;
     use crate::load_balancer::AuditConfig;
+    use crate::load_balancer::audit::AuditQuery;
 
     #[tokio::test]
     async fn test_create_preset() {
@@ -862,34 +963,19 @@ mod tests {
         
         let api_keys = vec![
             ApiKey { 
-                id: "key1".to_string(), 
                 key: "test-key-1".to_string(),
-                weight: 100, 
-                max_requests_per_minute: 60,
-                current_requests: 0,
-                last_reset: chrono::Utc::now(),
-                is_active: true,
-                failure_count: 0,
+                ..ApiKey::for_test("key1")
             },
             ApiKey { 
-                id: "key2".to_string(), 
                 key: "test-key-2".to_string(),
-                weight: 200, 
-                max_requests_per_minute: 60,
-                current_requests: 0,
-                last_reset: chrono::Utc::now(),
-                is_active: true,
-                failure_count: 0,
+                weight: 200,
+                ..ApiKey::for_test("key2")
             },
             ApiKey { 
-                id: "key3".to_string(), 
                 key: "test-key-3".to_string(),
-                weight: 50, 
-                max_requests_per_minute: 60,
-                current_requests: 0,
-                last_reset: chrono::Utc::now(),
+                weight: 50,
                 is_active: false,
-                failure_count: 0,
+                ..ApiKey::for_test("key3")
             },
         ];
         
@@ -909,24 +995,14 @@ mod tests {
         
         let mut api_keys = vec![
             ApiKey { 
-                id: "key1".to_string(), 
                 key: "test-key-1".to_string(),
-                weight: 150, 
-                max_requests_per_minute: 60,
-                current_requests: 0,
-                last_reset: chrono::Utc::now(),
-                is_active: true,
-                failure_count: 0,
+                weight: 150,
+                ..ApiKey::for_test("key1")
             },
             ApiKey { 
-                id: "key2".to_string(), 
                 key: "test-key-2".to_string(),
-                weight: 300, 
-                max_requests_per_minute: 60,
-                current_requests: 0,
-                last_reset: chrono::Utc::now(),
-                is_active: true,
-                failure_count: 0,
+                weight: 300,
+                ..ApiKey::for_test("key2")
             },
         ];
         
@@ -938,4 +1014,158 @@ mod tests {
         // 检查比例是否保持
         assert_eq!(api_keys[0].weight * 2, api_keys[1].weight);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_normalize_weights_respects_bounds() {
+        let audit_system = Arc::new(RwLock::new(
+            WeightAuditSystem::new(AuditConfig::default())
+        ));
+        let toolkit = WeightManagementToolkit::new(audit_system.clone(), ToolkitConfig {
+            weight_bounds: WeightBounds { min: Some(100), max: None },
+            ..ToolkitConfig::default()
+        });
+
+        let mut api_key_configs = vec![
+            ApiKeyConfig {
+                id: "key1".to_string(),
+                key: "test-key-1".to_string(),
+                weight: 900,
+                max_requests_per_minute: 60,
+                tenant: None,
+                tags: Vec::new(),
+                region: None,
+                weight_min: None,
+                weight_max: Some(500),
+                preferred_models: Vec::new(),
+                allowed_models: Vec::new(),
+                name: None,
+                description: None,
+            },
+            ApiKeyConfig {
+                id: "key2".to_string(),
+                key: "test-key-2".to_string(),
+                weight: 100,
+                max_requests_per_minute: 60,
+                tenant: None,
+                tags: Vec::new(),
+                region: None,
+                weight_min: None,
+                weight_max: None,
+                preferred_models: Vec::new(),
+                allowed_models: Vec::new(),
+                name: None,
+                description: None,
+            },
+        ];
+
+        toolkit.normalize_weights_config(&mut api_key_configs, 1000, "admin").await.unwrap();
+
+        // 标准化结果 900/100 被钳制为 key1 的上限 500 与全局下限 100
+        assert_eq!(api_key_configs[0].weight, 500);
+        assert_eq!(api_key_configs[1].weight, 100);
+
+        let records = audit_system.read().await.query_audit_records(&AuditQuery {
+            start_time: None,
+            end_time: None,
+            operator: None,
+            operation_type: None,
+            target_key_id: Some("key1".to_string()),
+            source: None,
+            limit: None,
+            offset: None,
+        }).await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].new_weight, 500);
+        assert_eq!(records[0].metadata["weight_clamped"], "true");
+        assert_eq!(records[0].metadata["requested_weight"], "900");
+    }
+
+    fn toolkit_with(weighting: PerformanceWeighting) -> WeightManagementToolkit {
+        let audit_system = Arc::new(RwLock::new(
+            WeightAuditSystem::new(AuditConfig::default())
+        ));
+        WeightManagementToolkit::new(audit_system, ToolkitConfig {
+            performance_weighting: weighting,
+            ..ToolkitConfig::default()
+        })
+    }
+
+    fn perf(avg_response_time: f64, success_rate: f64) -> PerformanceMetrics {
+        PerformanceMetrics {
+            avg_response_time,
+            success_rate,
+            error_rate: 1.0 - success_rate,
+            throughput: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_performance_factors_change_weight_direction() {
+        let latency_first = toolkit_with(PerformanceWeighting {
+            response_time_weight: 0.9,
+            success_rate_weight: 0.1,
+            ..PerformanceWeighting::default()
+        });
+        let default = toolkit_with(PerformanceWeighting::default());
+        let reliability_first = toolkit_with(PerformanceWeighting {
+            response_time_weight: 0.2,
+            success_rate_weight: 0.8,
+            ..PerformanceWeighting::default()
+        });
+
+        // 慢但可靠的密钥：越重视成功率，权重越高
+        let slow_reliable = perf(2000.0, 1.0);
+        let latency = latency_first.calculate_performance_based_weight(&slow_reliable, 100);
+        let balanced = default.calculate_performance_based_weight(&slow_reliable, 100);
+        let reliability = reliability_first.calculate_performance_based_weight(&slow_reliable, 100);
+        assert!(latency < balanced && balanced < reliability);
+        assert_eq!(balanced, 72);
+
+        // 快但不稳定的密钥：越重视成功率，权重越低
+        let fast_flaky = perf(0.0, 0.5);
+        let latency = latency_first.calculate_performance_based_weight(&fast_flaky, 100);
+        let reliability = reliability_first.calculate_performance_based_weight(&fast_flaky, 100);
+        assert!(latency > reliability);
+    }
+
+    #[test]
+    fn test_performance_adjustment_bounds() {
+        let perfect = perf(0.0, 1.0);
+        assert_eq!(toolkit_with(PerformanceWeighting::default()).calculate_performance_based_weight(&perfect, 100), 120);
+
+        let generous = toolkit_with(PerformanceWeighting {
+            max_adjustment: 3.0,
+            max_increase_ratio: 1.5,
+            ..PerformanceWeighting::default()
+        });
+        assert_eq!(generous.calculate_performance_based_weight(&perfect, 100), 150);
+
+        let floor = toolkit_with(PerformanceWeighting {
+            min_weight: 40,
+            ..PerformanceWeighting::default()
+        });
+        assert_eq!(floor.calculate_performance_based_weight(&perf(10_000.0, 0.0), 100), 40);
+    }
+
+    #[test]
+    fn test_performance_weighting_validation() {
+        assert!(ToolkitConfig::default().validate().is_ok());
+
+        let invalid = [
+            PerformanceWeighting { response_time_weight: 0.6, success_rate_weight: 0.6, ..PerformanceWeighting::default() },
+            PerformanceWeighting { response_time_weight: -0.2, success_rate_weight: 1.2, ..PerformanceWeighting::default() },
+            PerformanceWeighting { max_adjustment: 0.0, ..PerformanceWeighting::default() },
+            PerformanceWeighting { max_increase_ratio: 0.5, ..PerformanceWeighting::default() },
+            PerformanceWeighting { min_weight: 0, ..PerformanceWeighting::default() },
+        ];
+        for weighting in invalid {
+            assert!(weighting.validate().is_err(), "{:?}", weighting);
+        }
+
+        let config = ToolkitConfig {
+            performance_weighting: PerformanceWeighting { response_time_weight: 0.5, ..PerformanceWeighting::default() },
+            ..ToolkitConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
@@ -3,9 +3,23 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use crate::config::{
+    AdaptiveRateLimitConfig, FailurePenaltyConfig, KeyCooldownConfig, KeySchedulingMode, RegionRoutingConfig,
+    SchedulingConfig, WeightBounds,
+};
+use crate::load_balancer::hash_ring::HashRing;
 use crate::load_balancer::key_manager::ApiKey;
+use crate::load_balancer::selection_index::SelectionIndex;
+
+/// 每分钟速率限制窗口
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// 密钥数量达到该值时使用选择索引（O(log n) 定位），更小的密钥池使用平滑加权轮询
+pub const INDEXED_SELECTION_MIN_KEYS: usize = 64;
 
 /// 统一的 API 密钥结构，包含所有必要的状态信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +29,20 @@ pub struct UnifiedApiKey {
     pub key: String,
     pub weight: u32,
     pub max_requests_per_minute: u32,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub weight_min: Option<u32>,
+    #[serde(default)]
+    pub weight_max: Option<u32>,
+    #[serde(default)]
+    pub preferred_models: Vec<String>,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
     
     // 运行时状态（不序列化）
     #[serde(skip)]
@@ -29,9 +57,82 @@ pub struct UnifiedApiKey {
 #[derive(Debug, Clone)]
 pub struct KeyRuntimeState {
     pub current_requests: u32,
+    /// 最近一次重置速率限制计数的墙上时间（仅用于展示）
     pub last_reset: DateTime<Utc>,
+    /// 当前速率限制窗口的开始时刻（单调时钟，不受系统时钟回拨影响）
+    pub window_started: Instant,
     pub is_active: bool,
     pub failure_count: u32,
+    /// 最近一次失败时上游返回的状态码（成功后清除）
+    pub last_error_status: Option<u16>,
+    /// 运维手动停用（维护下线），与失败后的自动停用（is_active）相互独立，不会自动恢复
+    pub disabled: bool,
+    /// 按租户/标签批量排空（维护期间不再接收新请求），与手动停用相互独立
+    pub drained: bool,
+    /// 影子预热状态（通过 API 新增且指定了预热期的密钥），预热期内只接收镜像流量
+    pub shadow: Option<ShadowWarmup>,
+    /// 连续收到的 429 次数（成功后清零）
+    pub consecutive_429s: u32,
+    /// 根据 429 自适应下调后的每分钟限额（None 表示使用配置的限额）
+    pub adaptive_limit: Option<u32>,
+    /// 最近一次 429 或自适应限额调整的时刻，用于判断平稳期
+    pub adaptive_changed_at: Instant,
+    /// 收到 429 后的冷却截止时刻（单调时钟，不受系统时钟回拨影响），截止前不参与选择
+    pub cooldown_until: Option<Instant>,
+    /// 最近失败带来的选择惩罚（随时间衰减到零）
+    pub failure_penalty: Option<FailurePenaltyState>,
+}
+
+/// 失败惩罚：失败时有效权重按比例降低，比例在窗口内线性衰减到零
+#[derive(Debug, Clone, Copy)]
+pub struct FailurePenaltyState {
+    /// 最近一次失败时的惩罚比例
+    pub ratio: f64,
+    pub applied_at: Instant,
+    pub decay_window: Duration,
+}
+
+impl FailurePenaltyState {
+    /// 指定时刻的惩罚比例
+    pub fn ratio_at(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.applied_at).as_secs_f64();
+        let window = self.decay_window.as_secs_f64();
+        if window <= 0.0 || elapsed >= window {
+            return 0.0;
+        }
+        self.ratio * (1.0 - elapsed / window)
+    }
+}
+
+/// 影子预热：预热期满之前密钥不参与真实选择，镜像请求失败时重新开始预热
+#[derive(Debug, Clone)]
+pub struct ShadowWarmup {
+    pub until: Instant,
+    pub warmup: Duration,
+    /// 已完成的镜像请求数
+    pub mirrored_requests: u64,
+    /// 失败的镜像请求数
+    pub mirror_failures: u64,
+}
+
+impl ShadowWarmup {
+    pub fn new(warmup: Duration) -> Self {
+        Self {
+            until: Instant::now() + warmup,
+            warmup,
+            mirrored_requests: 0,
+            mirror_failures: 0,
+        }
+    }
+
+    /// 记录一次镜像请求结果，失败时从现在起重新开始预热
+    pub fn record(&mut self, success: bool) {
+        self.mirrored_requests += 1;
+        if !success {
+            self.mirror_failures += 1;
+            self.until = Instant::now() + self.warmup;
+        }
+    }
 }
 
 /// 密钥调度状态（用于加权轮询算法）
@@ -41,6 +142,32 @@ pub struct KeySchedulingState {
     pub current_weight: i32,
     /// 有效权重值（配置的权重）
     pub effective_weight: i32,
+    /// 进行中的权重渐变（权重调整按渐变策略应用时）
+    pub ramp: Option<WeightRamp>,
+}
+
+/// 权重渐变：在窗口内将有效权重从 `from` 线性过渡到 `to`
+#[derive(Debug, Clone)]
+pub struct WeightRamp {
+    pub from: i32,
+    pub to: i32,
+    pub started: Instant,
+    pub window: Duration,
+}
+
+impl WeightRamp {
+    /// 指定时刻的有效权重
+    pub fn weight_at(&self, now: Instant) -> i32 {
+        if self.is_finished(now) {
+            return self.to;
+        }
+        let progress = now.saturating_duration_since(self.started).as_secs_f64() / self.window.as_secs_f64();
+        self.from + ((self.to - self.from) as f64 * progress).round() as i32
+    }
+
+    pub fn is_finished(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.started) >= self.window
+    }
 }
 
 impl Default for KeyRuntimeState {
@@ -48,8 +175,18 @@ impl Default for KeyRuntimeState {
         Self {
             current_requests: 0,
             last_reset: Utc::now(),
+            window_started: Instant::now(),
             is_active: true,
             failure_count: 0,
+            last_error_status: None,
+            disabled: false,
+            drained: false,
+            shadow: None,
+            consecutive_429s: 0,
+            adaptive_limit: None,
+            adaptive_changed_at: Instant::now(),
+            cooldown_until: None,
+            failure_penalty: None,
         }
     }
 }
@@ -59,6 +196,7 @@ impl Default for KeySchedulingState {
         Self {
             current_weight: 0,
             effective_weight: 0,
+            ramp: None,
         }
     }
 }
@@ -72,10 +210,18 @@ impl UnifiedApiKey {
             key,
             weight,
             max_requests_per_minute,
+            tenant: None,
+            tags: Vec::new(),
+            region: None,
+            weight_min: None,
+            weight_max: None,
+            preferred_models: Vec::new(),
+            allowed_models: Vec::new(),
             runtime_state: KeyRuntimeState::default(),
             scheduling_state: KeySchedulingState {
                 current_weight: 0,
                 effective_weight: weight as i32,
+                ramp: None,
             },
         }
     }
@@ -87,15 +233,25 @@ impl UnifiedApiKey {
             key: api_key.key,
             weight: api_key.weight,
             max_requests_per_minute: api_key.max_requests_per_minute,
+            tenant: api_key.tenant,
+            tags: api_key.tags,
+            region: api_key.region,
+            weight_min: api_key.weight_min,
+            weight_max: api_key.weight_max,
+            preferred_models: api_key.preferred_models.clone(),
+            allowed_models: api_key.allowed_models.clone(),
             runtime_state: KeyRuntimeState {
                 current_requests: api_key.current_requests,
                 last_reset: api_key.last_reset,
+                window_started: Instant::now(),
                 is_active: api_key.is_active,
                 failure_count: api_key.failure_count,
+                ..KeyRuntimeState::default()
             },
             scheduling_state: KeySchedulingState {
                 current_weight: 0,
                 effective_weight: api_key.weight as i32,
+                ramp: None,
             },
         }
     }
@@ -111,27 +267,169 @@ impl UnifiedApiKey {
             last_reset: self.runtime_state.last_reset,
             is_active: self.runtime_state.is_active,
             failure_count: self.runtime_state.failure_count,
+            tenant: self.tenant.clone(),
+            tags: self.tags.clone(),
+            region: self.region.clone(),
+            weight_min: self.weight_min,
+            weight_max: self.weight_max,
+            preferred_models: self.preferred_models.clone(),
+            allowed_models: self.allowed_models.clone(),
+        }
+    }
+    
+    /// 该密钥生效的权重上下限
+    pub fn weight_bounds(&self, defaults: &WeightBounds) -> WeightBounds {
+        WeightBounds::resolve(self.weight_min, self.weight_max, defaults)
+    }
+    
+    /// 是否优先服务该模型
+    pub fn prefers_model(&self, model: &str) -> bool {
+        self.preferred_models.iter().any(|m| m.eq_ignore_ascii_case(model))
+    }
+    
+    /// 是否允许服务该模型：未设置 `allowed_models` 或请求未指定模型时允许
+    pub fn allows_model(&self, model: Option<&str>) -> bool {
+        match model {
+            Some(model) if !self.allowed_models.is_empty() => {
+                self.allowed_models.iter().any(|m| m.eq_ignore_ascii_case(model))
+            }
+            _ => true,
+        }
+    }
+    
+    /// 参与选择时的权重：请求的模型在优先列表中时按倍数放大，否则为基础权重
+    pub fn selection_weight(&self, weight: i32, model: Option<&str>, boost: f64) -> i32 {
+        if model.is_some_and(|m| self.prefers_model(m)) {
+            (weight as f64 * boost).round() as i32
+        } else {
+            weight
         }
     }
     
+    /// 扣除失败惩罚后的权重；正权重至少保留 1，惩罚期内仍能分到少量流量
+    pub fn penalized_weight(&self, weight: i32, now: Instant) -> i32 {
+        let ratio = match &self.runtime_state.failure_penalty {
+            Some(penalty) => penalty.ratio_at(now),
+            None => return weight,
+        };
+        if weight <= 0 || ratio <= 0.0 {
+            return weight;
+        }
+        ((weight as f64 * (1.0 - ratio)).round() as i32).max(1)
+    }
+    
+    /// 记录一次失败的惩罚：叠加到尚未衰减完的惩罚上（不超过上限），并从现在起重新衰减
+    pub fn record_failure_penalty(&mut self, params: &FailurePenalty, now: Instant) {
+        let remaining = self.runtime_state.failure_penalty.map_or(0.0, |penalty| penalty.ratio_at(now));
+        self.runtime_state.failure_penalty = Some(FailurePenaltyState {
+            ratio: (remaining + params.per_failure).min(params.max_ratio),
+            applied_at: now,
+            decay_window: params.decay_window,
+        });
+    }
+    
     /// 检查密钥是否可用
     pub fn is_available(&self) -> bool {
-        self.runtime_state.is_active 
+        !self.runtime_state.disabled
+            && !self.runtime_state.drained
+            && !self.in_shadow()
+            && !self.in_cooldown(Instant::now())
+            && self.runtime_state.is_active 
             && self.runtime_state.failure_count < 3
-            && self.runtime_state.current_requests < self.max_requests_per_minute
+            && self.runtime_state.current_requests < self.effective_max_requests_per_minute()
+    }
+    
+    /// 当前生效的每分钟限额：自适应下调后的值，不超过配置的限额
+    pub fn effective_max_requests_per_minute(&self) -> u32 {
+        self.runtime_state
+            .adaptive_limit
+            .map_or(self.max_requests_per_minute, |limit| limit.min(self.max_requests_per_minute))
+    }
+    
+    /// 记录一次上游 429：连续次数达到阈值后按乘法因子下调生效限额，返回是否下调
+    pub fn record_rate_limited(&mut self, params: &AdaptiveRateLimit, now: Instant) -> bool {
+        self.runtime_state.adaptive_changed_at = now;
+        self.runtime_state.consecutive_429s += 1;
+        if self.runtime_state.consecutive_429s < params.threshold.max(1) {
+            return false;
+        }
+        
+        self.runtime_state.consecutive_429s = 0;
+        let current = self.effective_max_requests_per_minute();
+        let lowered = ((current as f64 * params.decrease_factor).floor() as u32)
+            .max(params.min_requests_per_minute)
+            .min(current);
+        self.runtime_state.adaptive_limit = Some(lowered);
+        true
+    }
+    
+    /// 记录一次成功请求：距上次 429 或上次调整每满一个平稳期，生效限额按步长上调，恢复到配置的限额后取消下调
+    pub fn record_clean_request(&mut self, params: &AdaptiveRateLimit, now: Instant) {
+        self.runtime_state.consecutive_429s = 0;
+        let limit = match self.runtime_state.adaptive_limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        let elapsed = now.saturating_duration_since(self.runtime_state.adaptive_changed_at);
+        let periods = (elapsed.as_millis() / params.clean_period.as_millis().max(1)).min(u32::MAX as u128) as u32;
+        if periods == 0 {
+            return;
+        }
+        
+        let raised = limit.saturating_add(params.increase_step.saturating_mul(periods));
+        self.runtime_state.adaptive_limit = (raised < self.max_requests_per_minute).then_some(raised);
+        self.runtime_state.adaptive_changed_at = now;
+    }
+    
+    /// 是否处于 429 冷却期
+    pub fn in_cooldown(&self, now: Instant) -> bool {
+        self.runtime_state.cooldown_until.is_some_and(|until| now < until)
+    }
+    
+    /// 冷却期剩余时长（不在冷却期时为 None）
+    pub fn cooldown_remaining(&self, now: Instant) -> Option<Duration> {
+        self.runtime_state
+            .cooldown_until
+            .map(|until| until.saturating_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+    
+    /// 是否仍处于影子预热期
+    pub fn in_shadow(&self) -> bool {
+        self.runtime_state.shadow.as_ref().is_some_and(|shadow| Instant::now() < shadow.until)
+    }
+    
+    /// 检查密钥是否接近每分钟限额（利用率达到软阈值），ratio 不在 (0, 1) 内时视为禁用
+    pub fn is_near_limit(&self, ratio: f64) -> bool {
+        ratio > 0.0
+            && ratio < 1.0
+            && self.runtime_state.current_requests as f64 >= self.effective_max_requests_per_minute() as f64 * ratio
+    }
+    
+    /// 在选择索引中的权重：不可用或接近软限额时为 0，最近失败的密钥扣除失败惩罚
+    pub fn indexed_weight(&self, soft_limit_ratio: f64) -> u64 {
+        if self.is_available() && !self.is_near_limit(soft_limit_ratio) {
+            self.penalized_weight(self.scheduling_state.effective_weight, Instant::now()).max(0) as u64
+        } else {
+            0
+        }
     }
     
-    /// 检查是否需要重置速率限制计数器
+    /// 检查是否需要重置速率限制计数器（按单调时钟计算窗口）
     pub fn should_reset_rate_limit(&self) -> bool {
-        let now = Utc::now();
-        let elapsed = now.signed_duration_since(self.runtime_state.last_reset);
-        elapsed.num_seconds() >= 60
+        self.rate_limit_window_elapsed(Instant::now()) >= RATE_LIMIT_WINDOW
+    }
+    
+    /// 当前速率限制窗口已经过的时长
+    pub fn rate_limit_window_elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.runtime_state.window_started)
     }
     
     /// 重置速率限制计数器
     pub fn reset_rate_limit(&mut self) {
         self.runtime_state.current_requests = 0;
         self.runtime_state.last_reset = Utc::now();
+        self.runtime_state.window_started = Instant::now();
     }
     
     /// 增加请求计数
@@ -153,15 +451,244 @@ impl UnifiedApiKey {
     /// 标记密钥成功
     pub fn mark_success(&mut self) {
         self.runtime_state.failure_count = 0;
+        self.runtime_state.last_error_status = None;
         self.runtime_state.is_active = true;
-        // 恢复有效权重
-        self.scheduling_state.effective_weight = self.weight as i32;
+        // 恢复有效权重（权重渐变中时恢复到渐变的当前值）
+        self.scheduling_state.effective_weight = match &self.scheduling_state.ramp {
+            Some(ramp) => ramp.weight_at(Instant::now()),
+            None => self.weight as i32,
+        };
     }
     
     /// 更新权重
     pub fn update_weight(&mut self, new_weight: u32) {
         self.weight = new_weight;
         self.scheduling_state.effective_weight = new_weight as i32;
+        self.scheduling_state.ramp = None;
+    }
+    
+    /// 在窗口内从当前有效权重逐步过渡到新权重，窗口为零时立即生效
+    pub fn ramp_weight(&mut self, new_weight: u32, window: Duration) {
+        if window.is_zero() {
+            self.update_weight(new_weight);
+            return;
+        }
+        self.weight = new_weight;
+        self.scheduling_state.ramp = Some(WeightRamp {
+            from: self.scheduling_state.effective_weight,
+            to: new_weight as i32,
+            started: Instant::now(),
+            window,
+        });
+    }
+    
+    /// 推进权重渐变，返回有效权重是否发生变化
+    pub fn advance_ramp(&mut self, now: Instant) -> bool {
+        let weight = match &self.scheduling_state.ramp {
+            Some(ramp) if ramp.is_finished(now) => {
+                let weight = ramp.to;
+                self.scheduling_state.ramp = None;
+                weight
+            }
+            Some(ramp) => ramp.weight_at(now),
+            None => return false,
+        };
+        let changed = self.scheduling_state.effective_weight != weight;
+        self.scheduling_state.effective_weight = weight;
+        changed
+    }
+}
+
+/// 按租户或标签选择一组密钥
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySelector {
+    Tenant(String),
+    Tag(String),
+}
+
+impl KeySelector {
+    pub fn matches(&self, key: &UnifiedApiKey) -> bool {
+        match self {
+            Self::Tenant(tenant) => key.tenant.as_deref() == Some(tenant.as_str()),
+            Self::Tag(tag) => key.tags.iter().any(|t| t == tag),
+        }
+    }
+}
+
+/// 加权 rendezvous 哈希得分：`-weight / ln(u)`，u 为由路由哈希与密钥 ID 确定的 (0, 1) 均匀值
+fn rendezvous_score(routing_hash: u64, key_id: &str, weight: u32) -> f64 {
+    // FNV-1a 哈希密钥 ID，再与路由哈希混合（splitmix64）
+    let key_hash = key_id.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    let mut x = routing_hash ^ key_hash;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^= x >> 31;
+    let u = ((x >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    -(weight.max(1) as f64) / u.ln()
+}
+
+/// 按权重比例拆分账号配额（最大余数法），各份之和等于配额；总权重为 0 时平均分配
+pub fn split_quota_by_weight(quota: u32, weights: &[u32]) -> Vec<u32> {
+    if weights.is_empty() {
+        return Vec::new();
+    }
+    let total_weight: u64 = weights.iter().map(|&w| w as u64).sum();
+    let weights: Vec<u64> = if total_weight == 0 {
+        vec![1; weights.len()]
+    } else {
+        weights.iter().map(|&w| w as u64).collect()
+    };
+    let total_weight: u64 = weights.iter().sum();
+
+    let mut shares: Vec<u32> = weights.iter()
+        .map(|&w| (quota as u64 * w / total_weight) as u32)
+        .collect();
+
+    // 剩余配额按余数从大到小依次分配
+    let assigned: u32 = shares.iter().sum();
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(quota as u64 * weights[i] % total_weight));
+    for &i in order.iter().take((quota - assigned) as usize) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+/// 区域延迟优先参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegionPreference {
+    /// 延迟不超过最优区域该倍数的区域视为同等优先
+    pub latency_tolerance_ratio: f64,
+    /// 区域延迟指数移动平均的平滑系数
+    pub latency_smoothing: f64,
+}
+
+impl RegionPreference {
+    /// 按配置创建区域延迟优先参数，未启用时返回 None
+    pub fn from_config(config: &RegionRoutingConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            latency_tolerance_ratio: config.latency_tolerance_ratio,
+            latency_smoothing: config.latency_smoothing,
+        })
+    }
+}
+
+/// 根据上游 429 自适应调整每分钟限额（AIMD）的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveRateLimit {
+    /// 连续收到多少次 429 后下调一次
+    pub threshold: u32,
+    /// 下调时生效限额乘以的因子
+    pub decrease_factor: f64,
+    /// 每个平稳期上调的请求数
+    pub increase_step: u32,
+    /// 下调的下限
+    pub min_requests_per_minute: u32,
+    /// 没有 429 的平稳期长度
+    pub clean_period: Duration,
+}
+
+impl AdaptiveRateLimit {
+    /// 按配置创建自适应限额参数，未启用时返回 None
+    pub fn from_config(config: &AdaptiveRateLimitConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            threshold: config.threshold,
+            decrease_factor: config.decrease_factor,
+            increase_step: config.increase_step,
+            min_requests_per_minute: config.min_requests_per_minute,
+            clean_period: Duration::from_secs(config.clean_period_seconds),
+        })
+    }
+}
+
+/// 密钥调度方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedulingMode {
+    /// 平滑加权轮询（可配合粘性窗口）
+    #[default]
+    WeightedRoundRobin,
+    /// 按请求属性的哈希在一致性哈希环上选择密钥，`ring_size` 为环上虚拟节点总数
+    ConsistentHashing { ring_size: usize },
+}
+
+impl From<&SchedulingConfig> for SchedulingMode {
+    fn from(config: &SchedulingConfig) -> Self {
+        match config.mode {
+            KeySchedulingMode::WeightedRoundRobin => Self::WeightedRoundRobin,
+            KeySchedulingMode::ConsistentHashing => Self::ConsistentHashing { ring_size: config.ring_size },
+        }
+    }
+}
+
+/// 上游 429 后的密钥冷却参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitCooldown {
+    /// 响应未携带 Retry-After 时的冷却时长
+    pub default_duration: Duration,
+    /// 冷却时长上限（Retry-After 超过时截断）
+    pub max_duration: Duration,
+}
+
+impl RateLimitCooldown {
+    /// 按配置创建 429 冷却参数，未启用时返回 None
+    pub fn from_config(config: &KeyCooldownConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            default_duration: Duration::from_secs(config.default_seconds),
+            max_duration: Duration::from_secs(config.max_seconds),
+        })
+    }
+}
+
+/// 失败惩罚参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FailurePenalty {
+    /// 每次失败增加的惩罚比例（占有效权重）
+    pub per_failure: f64,
+    /// 惩罚比例上限
+    pub max_ratio: f64,
+    /// 惩罚从最近一次失败起衰减到零的时长
+    pub decay_window: Duration,
+}
+
+impl FailurePenalty {
+    /// 按配置创建失败惩罚参数，未启用时返回 None
+    pub fn from_config(config: &FailurePenaltyConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            per_failure: config.penalty_per_failure,
+            max_ratio: config.max_penalty,
+            decay_window: Duration::from_secs(config.decay_seconds),
+        })
+    }
+}
+
+/// 从候选密钥中保留延迟最优（在容忍倍数内）的区域的密钥
+///
+/// 尚未测得延迟的区域按 0 处理，保证新区域能先获得流量完成测量；候选集只包含可用密钥，
+/// 因此最优区域的密钥全部不可用时自动转移到其他区域
+fn preferred_region_candidates(
+    keys: &[UnifiedApiKey],
+    candidates: Vec<usize>,
+    region_latency: &HashMap<Option<String>, f64>,
+    latency_tolerance_ratio: f64,
+) -> Vec<usize> {
+    let latency_of = |i: usize| region_latency.get(&keys[i].region).copied().unwrap_or(0.0);
+    let best = match candidates.iter().map(|&i| latency_of(i)).min_by(|a, b| a.total_cmp(b)) {
+        Some(best) => best,
+        None => return candidates,
+    };
+    let threshold = best * latency_tolerance_ratio;
+    candidates.into_iter().filter(|&i| latency_of(i) <= threshold).collect()
+}
+
+/// 按账号配额重新分配各密钥的每分钟限额
+fn apply_account_quota(keys: &mut [UnifiedApiKey], quota: Option<u32>) {
+    let quota = match quota {
+        Some(quota) => quota,
+        None => return,
+    };
+    let weights: Vec<u32> = keys.iter().map(|k| k.weight).collect();
+    for (key, limit) in keys.iter_mut().zip(split_quota_by_weight(quota, &weights)) {
+        key.max_requests_per_minute = limit;
     }
 }
 
@@ -175,6 +702,32 @@ pub struct LoadBalancingStats {
     pub failed_keys: usize,
 }
 
+/// 一个密钥的权重变更
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyWeightChange {
+    pub key_id: String,
+    /// 更新前的权重
+    pub previous_weight: u32,
+    /// 钳制到上下限后实际生效的权重
+    pub applied_weight: u32,
+}
+
+/// 某个模型可用的密钥数
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ModelKeyAvailability {
+    /// 允许服务该模型的密钥数（包括未限制模型的密钥）
+    pub total_keys: usize,
+    /// 其中当前可参与选择的密钥数
+    pub available_keys: usize,
+}
+
+/// 粘性绑定记录：客户端/会话 -> 密钥
+#[derive(Debug, Clone)]
+struct StickyBinding {
+    key_id: String,
+    last_used: Instant,
+}
+
 /// 统一的负载均衡器状态管理器
 /// 
 /// 这个结构替代了原来的 KeyManager + WeightedRoundRobin 双重状态管理
@@ -185,6 +738,36 @@ pub struct UnifiedKeyManager {
     keys: Arc<RwLock<Vec<UnifiedApiKey>>>,
     /// 总权重缓存，避免重复计算
     total_weight: Arc<RwLock<i32>>,
+    /// 密钥粘性窗口（为零表示禁用）
+    stickiness_window: Arc<RwLock<Duration>>,
+    /// 客户端/会话到密钥的粘性绑定
+    sticky_bindings: Arc<RwLock<HashMap<String, StickyBinding>>>,
+    /// 软限额阈值（每分钟限额的利用率），超过后优先选择其他密钥，0 表示禁用
+    soft_limit_ratio: Arc<RwLock<f64>>,
+    /// 账号级每分钟配额，设置后各密钥的每分钟限额按权重比例分配
+    account_quota: Arc<RwLock<Option<u32>>>,
+    /// 权重调整的渐变窗口（为零表示立即生效）
+    weight_ramp_window: Arc<RwLock<Duration>>,
+    /// 区域延迟优先参数（None 表示不区分区域）
+    region_preference: Arc<RwLock<Option<RegionPreference>>>,
+    /// 各区域测得的平滑延迟（毫秒），未配置区域的密钥归入 None
+    region_latency: Arc<RwLock<HashMap<Option<String>, f64>>>,
+    /// 全局默认的权重上下限（密钥自身的 weight_min/weight_max 优先）
+    weight_bounds: Arc<RwLock<WeightBounds>>,
+    /// 请求密钥优先模型时的权重放大倍数（1 表示不放大）
+    preferred_model_boost: Arc<RwLock<f64>>,
+    /// 根据 429 自适应调整每分钟限额的参数（None 表示使用配置的固定限额）
+    adaptive_rate_limit: Arc<RwLock<Option<AdaptiveRateLimit>>>,
+    /// 收到 429 后的密钥冷却参数（None 表示不冷却）
+    rate_limit_cooldown: Arc<RwLock<Option<RateLimitCooldown>>>,
+    /// 最近失败密钥的选择惩罚参数（None 表示不惩罚）
+    failure_penalty: Arc<RwLock<Option<FailurePenalty>>>,
+    /// 大规模密钥池的选择索引，位置与 `keys` 一一对应，每次选择前只更新权重变化的位置
+    selection_index: Arc<RwLock<SelectionIndex>>,
+    /// 密钥调度方式
+    scheduling_mode: Arc<RwLock<SchedulingMode>>,
+    /// 一致性哈希环，密钥集合或权重变化后在下次选择时重建
+    hash_ring: Arc<RwLock<HashRing>>,
 }
 
 impl UnifiedKeyManager {
@@ -201,156 +784,780 @@ impl UnifiedKeyManager {
         Self {
             keys: Arc::new(RwLock::new(unified_keys)),
             total_weight: Arc::new(RwLock::new(total_weight)),
+            stickiness_window: Arc::new(RwLock::new(Duration::ZERO)),
+            sticky_bindings: Arc::new(RwLock::new(HashMap::new())),
+            soft_limit_ratio: Arc::new(RwLock::new(0.0)),
+            account_quota: Arc::new(RwLock::new(None)),
+            weight_ramp_window: Arc::new(RwLock::new(Duration::ZERO)),
+            region_preference: Arc::new(RwLock::new(None)),
+            region_latency: Arc::new(RwLock::new(HashMap::new())),
+            weight_bounds: Arc::new(RwLock::new(WeightBounds::default())),
+            preferred_model_boost: Arc::new(RwLock::new(1.0)),
+            adaptive_rate_limit: Arc::new(RwLock::new(None)),
+            rate_limit_cooldown: Arc::new(RwLock::new(None)),
+            failure_penalty: Arc::new(RwLock::new(None)),
+            selection_index: Arc::new(RwLock::new(SelectionIndex::default())),
+            scheduling_mode: Arc::new(RwLock::new(SchedulingMode::default())),
+            hash_ring: Arc::new(RwLock::new(HashRing::default())),
         }
     }
     
-    /// 获取下一个可用的 API 密钥（使用平滑加权轮询算法）
-    pub async fn get_next_key(&self) -> Option<ApiKey> {
-        let mut keys = self.keys.write().await;
-        
-        // 更新所有密钥的可用状态
-        self.update_keys_availability(&mut keys).await;
-        
-        // 使用平滑加权轮询算法选择密钥
-        let selected_key = self.select_key_with_smooth_wrr(&mut keys).await;
-        
-        if let Some(selected) = selected_key {
-            // 增加请求计数
-            if let Some(key) = keys.iter_mut().find(|k| k.id == selected.id) {
-                key.increment_requests();
+    /// 构造时指定密钥粘性窗口（毫秒）
+    pub fn with_stickiness_window_ms(self, window_ms: u64) -> Self {
+        Self {
+            stickiness_window: Arc::new(RwLock::new(Duration::from_millis(window_ms))),
+            ..self
+        }
+    }
+    
+    /// 构造时指定软限额阈值（例如 0.9 表示利用率达到 90% 后优先轮换到其他密钥）
+    pub fn with_soft_limit_ratio(self, ratio: f64) -> Self {
+        Self {
+            soft_limit_ratio: Arc::new(RwLock::new(ratio)),
+            ..self
+        }
+    }
+    
+    /// 构造时停用指定密钥（例如配置中持久化的 `disabled_keys`）
+    pub fn with_disabled_keys(self, key_ids: &[String]) -> Self {
+        if let Ok(mut keys) = self.keys.try_write() {
+            for key in keys.iter_mut().filter(|k| key_ids.contains(&k.id)) {
+                key.runtime_state.disabled = true;
             }
-            Some(selected)
-        } else {
-            None
         }
+        self
     }
     
-    /// 更新密钥的可用状态（内部方法，已持有写锁）
-    async fn update_keys_availability(&self, keys: &mut Vec<UnifiedApiKey>) {
-        let now = Utc::now();
-        let mut total_weight_changed = false;
-        
+    /// 按配置的 `disabled_keys` 重新设置各密钥的手动停用状态，清理指向停用密钥的粘性绑定
+    pub async fn set_disabled_keys(&self, key_ids: &[String]) {
+        let mut keys = self.keys.write().await;
         for key in keys.iter_mut() {
-            // 重置速率限制计数器
-            if key.should_reset_rate_limit() {
-                key.reset_rate_limit();
-            }
-            
-            // 检查密钥是否应该重新激活
-            if !key.runtime_state.is_active && key.runtime_state.failure_count < 3 {
-                // 简单的恢复策略：5分钟后重试失败的密钥
-                let elapsed = now.signed_duration_since(key.runtime_state.last_reset);
-                if elapsed.num_minutes() >= 5 {
-                    key.mark_success();
-                    total_weight_changed = true;
+            key.runtime_state.disabled = key_ids.contains(&key.id);
+        }
+        drop(keys);
+        self.sticky_bindings.write().await.retain(|_, binding| !key_ids.contains(&binding.key_id));
+    }
+    
+    /// 构造时指定权重调整的渐变窗口（毫秒），0 表示权重调整立即生效
+    pub fn with_weight_ramp_window_ms(self, window_ms: u64) -> Self {
+        Self {
+            weight_ramp_window: Arc::new(RwLock::new(Duration::from_millis(window_ms))),
+            ..self
+        }
+    }
+    
+    /// 构造时指定全局默认的权重上下限，并将现有密钥的权重钳制到范围内
+    pub fn with_weight_bounds(self, bounds: WeightBounds) -> Self {
+        if let Ok(mut keys) = self.keys.try_write() {
+            for key in keys.iter_mut() {
+                let clamped = key.weight_bounds(&bounds).clamp(key.weight);
+                if clamped != key.weight {
+                    key.update_weight(clamped);
                 }
             }
+            if let Ok(mut total_weight) = self.total_weight.try_write() {
+                *total_weight = keys.iter().map(|k| k.scheduling_state.effective_weight).sum();
+            }
         }
-        
-        // 如果总权重发生变化，更新缓存
-        if total_weight_changed {
-            let new_total_weight = keys.iter()
-                .map(|k| k.scheduling_state.effective_weight)
-                .sum();
-            *self.total_weight.write().await = new_total_weight;
+        Self {
+            weight_bounds: Arc::new(RwLock::new(bounds)),
+            ..self
         }
     }
     
-    /// 使用平滑加权轮询算法选择密钥（内部方法，已持有写锁）
-    async fn select_key_with_smooth_wrr(&self, keys: &mut Vec<UnifiedApiKey>) -> Option<ApiKey> {
-        // 过滤出可用的密钥
-        let available_keys: Vec<usize> = keys.iter()
-            .enumerate()
-            .filter(|(_, key)| key.is_available())
-            .map(|(i, _)| i)
-            .collect();
-        
-        if available_keys.is_empty() {
-            return None;
+    /// 设置全局默认的权重上下限，并将现有密钥的权重钳制到范围内
+    pub async fn set_weight_bounds(&self, bounds: WeightBounds) {
+        let mut keys = self.keys.write().await;
+        *self.weight_bounds.write().await = bounds;
+        for key in keys.iter_mut() {
+            let clamped = key.weight_bounds(&bounds).clamp(key.weight);
+            if clamped != key.weight {
+                key.update_weight(clamped);
+            }
         }
-        
-        // 计算总有效权重
-        let total_effective_weight: i32 = available_keys.iter()
-            .map(|&i| keys[i].scheduling_state.effective_weight)
-            .sum();
-        
-        if total_effective_weight <= 0 {
-            return None;
+        *self.total_weight.write().await = keys.iter().map(|k| k.scheduling_state.effective_weight).sum();
+    }
+    
+    /// 构造时指定优先模型的权重放大倍数（1 表示不放大）
+    pub fn with_preferred_model_boost(self, boost: f64) -> Self {
+        Self {
+            preferred_model_boost: Arc::new(RwLock::new(boost.max(1.0))),
+            ..self
         }
-        
-        // 平滑加权轮询算法
-        // 1. 为每个可用密钥增加其有效权重到当前权重
-        for &i in &available_keys {
-            keys[i].scheduling_state.current_weight += keys[i].scheduling_state.effective_weight;
+    }
+    
+    /// 设置优先模型的权重放大倍数（1 表示不放大）
+    pub async fn set_preferred_model_boost(&self, boost: f64) {
+        *self.preferred_model_boost.write().await = boost.max(1.0);
+    }
+    
+    /// 构造时指定自适应每分钟限额参数（None 表示使用配置的固定限额）
+    pub fn with_adaptive_rate_limit(self, params: Option<AdaptiveRateLimit>) -> Self {
+        Self {
+            adaptive_rate_limit: Arc::new(RwLock::new(params)),
+            ..self
         }
-        
-        // 2. 选择当前权重最大的密钥
-        let selected_index = available_keys.iter()
-            .max_by_key(|&&i| keys[i].scheduling_state.current_weight)
-            .copied()?;
-        
-        // 3. 将选中密钥的当前权重减去总有效权重
-        keys[selected_index].scheduling_state.current_weight -= total_effective_weight;
-        
-        Some(keys[selected_index].to_api_key())
     }
     
-    /// 标记密钥为失败状态
-    pub async fn mark_key_failed(&self, key_id: &str) {
+    /// 设置自适应每分钟限额参数，关闭时各密钥恢复配置的固定限额
+    pub async fn set_adaptive_rate_limit(&self, params: Option<AdaptiveRateLimit>) {
         let mut keys = self.keys.write().await;
-        if let Some(key) = keys.iter_mut().find(|k| k.id == key_id) {
-            let old_effective_weight = key.scheduling_state.effective_weight;
-            key.mark_failed();
-            
-            // 如果有效权重发生变化，更新总权重缓存
-            if old_effective_weight != key.scheduling_state.effective_weight {
-                let new_total_weight = keys.iter()
-                    .map(|k| k.scheduling_state.effective_weight)
-                    .sum();
-                *self.total_weight.write().await = new_total_weight;
+        *self.adaptive_rate_limit.write().await = params;
+        if params.is_none() {
+            for key in keys.iter_mut() {
+                key.runtime_state.adaptive_limit = None;
+                key.runtime_state.consecutive_429s = 0;
             }
         }
     }
     
-    /// 标记密钥为成功状态
-    pub async fn mark_key_success(&self, key_id: &str) {
+    /// 构造时指定 429 冷却参数（None 表示不冷却）
+    pub fn with_rate_limit_cooldown(self, params: Option<RateLimitCooldown>) -> Self {
+        Self {
+            rate_limit_cooldown: Arc::new(RwLock::new(params)),
+            ..self
+        }
+    }
+    
+    /// 设置 429 冷却参数，关闭时结束所有密钥的冷却期
+    pub async fn set_rate_limit_cooldown(&self, params: Option<RateLimitCooldown>) {
         let mut keys = self.keys.write().await;
-        if let Some(key) = keys.iter_mut().find(|k| k.id == key_id) {
-            let old_effective_weight = key.scheduling_state.effective_weight;
-            key.mark_success();
-            
-            // 如果有效权重发生变化，更新总权重缓存
-            if old_effective_weight != key.scheduling_state.effective_weight {
-                let new_total_weight = keys.iter()
-                    .map(|k| k.scheduling_state.effective_weight)
-                    .sum();
-                *self.total_weight.write().await = new_total_weight;
+        *self.rate_limit_cooldown.write().await = params;
+        if params.is_none() {
+            for key in keys.iter_mut() {
+                key.runtime_state.cooldown_until = None;
             }
         }
     }
     
-    /// 更新密钥权重（原子操作）
-    pub async fn update_key_weight(&self, key_id: &str, new_weight: u32) -> Result<(), String> {
-        let mut keys = self.keys.write().await;
-        
-        if let Some(key) = keys.iter_mut().find(|k| k.id == key_id) {
-            key.update_weight(new_weight);
-            
-            // 更新总权重缓存
-            let new_total_weight = keys.iter()
-                .map(|k| k.scheduling_state.effective_weight)
-                .sum();
+    /// 构造时指定失败惩罚参数（None 表示不惩罚）
+    pub fn with_failure_penalty(self, params: Option<FailurePenalty>) -> Self {
+        Self {
+            failure_penalty: Arc::new(RwLock::new(params)),
+            ..self
+        }
+    }
+    
+    /// 设置失败惩罚参数（None 表示不惩罚），已记录的惩罚继续按原窗口衰减
+    pub async fn set_failure_penalty(&self, params: Option<FailurePenalty>) {
+        *self.failure_penalty.write().await = params;
+    }
+    
+    /// 构造时指定密钥调度方式
+    pub fn with_scheduling_mode(self, mode: SchedulingMode) -> Self {
+        Self {
+            scheduling_mode: Arc::new(RwLock::new(mode)),
+            ..self
+        }
+    }
+    
+    /// 设置密钥调度方式，一致性哈希环在下次选择时按新的环大小重建
+    pub async fn set_scheduling_mode(&self, mode: SchedulingMode) {
+        let mut current = self.scheduling_mode.write().await;
+        if *current != mode {
+            *current = mode;
+            *self.hash_ring.write().await = HashRing::default();
+        }
+    }
+    
+    /// 构造时指定区域延迟优先参数（None 表示不区分区域）
+    pub fn with_region_preference(self, preference: Option<RegionPreference>) -> Self {
+        Self {
+            region_preference: Arc::new(RwLock::new(preference)),
+            ..self
+        }
+    }
+    
+    /// 设置区域延迟优先参数，关闭时清除已测得的区域延迟
+    pub async fn set_region_preference(&self, preference: Option<RegionPreference>) {
+        *self.region_preference.write().await = preference;
+        if preference.is_none() {
+            self.region_latency.write().await.clear();
+        }
+    }
+    
+    /// 记录一次成功请求的延迟，更新密钥所属区域的平滑延迟（未启用区域延迟优先时忽略）
+    pub async fn record_key_latency(&self, key_id: &str, latency: Duration) {
+        let preference = match *self.region_preference.read().await {
+            Some(preference) => preference,
+            None => return,
+        };
+        let region = match self.keys.read().await.iter().find(|k| k.id == key_id) {
+            Some(key) => key.region.clone(),
+            None => return,
+        };
+        
+        let sample = latency.as_secs_f64() * 1000.0;
+        let mut region_latency = self.region_latency.write().await;
+        region_latency
+            .entry(region)
+            .and_modify(|avg| *avg += (sample - *avg) * preference.latency_smoothing)
+            .or_insert(sample);
+    }
+    
+    /// 设置权重调整的渐变窗口（毫秒），0 表示立即生效
+    pub async fn set_weight_ramp_window_ms(&self, window_ms: u64) {
+        *self.weight_ramp_window.write().await = Duration::from_millis(window_ms);
+    }
+    
+    /// 构造时指定账号级每分钟配额（None 表示使用各密钥自身的限额）
+    pub fn with_account_quota(self, quota: Option<u32>) -> Self {
+        if let Ok(mut keys) = self.keys.try_write() {
+            apply_account_quota(&mut keys, quota);
+        }
+        Self {
+            account_quota: Arc::new(RwLock::new(quota)),
+            ..self
+        }
+    }
+    
+    /// 设置账号级每分钟配额并重新分配各密钥的限额（设为 None 时保留当前限额）
+    pub async fn set_account_quota(&self, quota: Option<u32>) {
+        let mut keys = self.keys.write().await;
+        *self.account_quota.write().await = quota;
+        apply_account_quota(&mut keys, quota);
+    }
+    
+    /// 设置软限额阈值，0 表示禁用
+    pub async fn set_soft_limit_ratio(&self, ratio: f64) {
+        *self.soft_limit_ratio.write().await = ratio;
+    }
+    
+    /// 设置密钥粘性窗口（毫秒），0 表示禁用粘性
+    pub async fn set_stickiness_window_ms(&self, window_ms: u64) {
+        *self.stickiness_window.write().await = Duration::from_millis(window_ms);
+        if window_ms == 0 {
+            self.sticky_bindings.write().await.clear();
+        }
+    }
+    
+    /// 按客户端/会话获取密钥
+    /// 
+    /// 在粘性窗口内，同一客户端会持续获得上一次分配的密钥（只要该密钥仍可用）；
+    /// 窗口过期或密钥不可用时重新走加权轮询选择，并刷新绑定
+    pub async fn get_next_key_for_client(&self, client_id: &str, model: Option<&str>) -> Option<ApiKey> {
+        self.get_next_key_for_client_excluding(client_id, model, &[]).await
+    }
+    
+    /// 同 [`Self::get_next_key_for_client`]，跳过指定的密钥（例如熔断器打开的密钥），粘性绑定到被跳过的密钥时重新选择
+    pub async fn get_next_key_for_client_excluding(
+        &self,
+        client_id: &str,
+        model: Option<&str>,
+        exclude: &[String],
+    ) -> Option<ApiKey> {
+        let window = *self.stickiness_window.read().await;
+        if window.is_zero() {
+            return self.get_next_key_excluding(model, exclude).await;
+        }
+        
+        let mut keys = self.keys.write().await;
+        self.update_keys_availability(&mut keys).await;
+        
+        let mut bindings = self.sticky_bindings.write().await;
+        let now = Instant::now();
+        
+        // 窗口内复用上一次的密钥
+        if let Some(binding) = bindings.get_mut(client_id) {
+            if now.duration_since(binding.last_used) < window {
+                let soft_limit_ratio = *self.soft_limit_ratio.read().await;
+                if let Some(key) = keys.iter_mut().find(|k| {
+                    k.id == binding.key_id
+                        && k.is_available()
+                        && k.allows_model(model)
+                        && !k.is_near_limit(soft_limit_ratio)
+                        && !exclude.contains(&k.id)
+                }) {
+                    key.increment_requests();
+                    binding.last_used = now;
+                    return Some(key.to_api_key());
+                }
+            }
+        }
+        
+        // 清理过期绑定，避免无限增长
+        bindings.retain(|_, b| now.duration_since(b.last_used) < window);
+        
+        let selected = self.select_key_with_smooth_wrr(&mut keys, model, exclude).await?;
+        if let Some(key) = keys.iter_mut().find(|k| k.id == selected.id) {
+            key.increment_requests();
+        }
+        bindings.insert(client_id.to_string(), StickyBinding {
+            key_id: selected.id.clone(),
+            last_used: now,
+        });
+        Some(selected)
+    }
+    
+    /// 按请求的路由哈希确定性地选择密钥
+    /// 
+    /// 在可用密钥（优先未接近限额的密钥）中使用加权 rendezvous 哈希：相同哈希总是得到同一个密钥，
+    /// 不同哈希按权重比例分散；密钥集合变化时只有原本落在变化密钥上的哈希改变归属
+    pub async fn get_next_key_for_hash(&self, routing_hash: u64, model: Option<&str>) -> Option<ApiKey> {
+        self.get_next_key_for_hash_excluding(routing_hash, model, &[]).await
+    }
+    
+    /// 同 [`Self::get_next_key_for_hash`]，跳过指定的密钥，原本落在被跳过密钥上的哈希改由其余密钥承接
+    pub async fn get_next_key_for_hash_excluding(
+        &self,
+        routing_hash: u64,
+        model: Option<&str>,
+        exclude: &[String],
+    ) -> Option<ApiKey> {
+        let mut keys = self.keys.write().await;
+        self.update_keys_availability(&mut keys).await;
+        
+        let soft_limit_ratio = *self.soft_limit_ratio.read().await;
+        let available: Vec<usize> = keys.iter()
+            .enumerate()
+            .filter(|(_, key)| key.is_available() && key.allows_model(model) && !exclude.contains(&key.id))
+            .map(|(i, _)| i)
+            .collect();
+        let below_soft_limit: Vec<usize> = available.iter()
+            .copied()
+            .filter(|&i| !keys[i].is_near_limit(soft_limit_ratio))
+            .collect();
+        let candidates = if below_soft_limit.is_empty() { available } else { below_soft_limit };
+        
+        let boost = *self.preferred_model_boost.read().await;
+        let now = Instant::now();
+        let weight_of = |i: usize| {
+            let weight = keys[i].selection_weight(keys[i].weight as i32, model, boost);
+            keys[i].penalized_weight(weight, now).max(0) as u32
+        };
+        let selected = candidates.into_iter().max_by(|&a, &b| {
+            let score_a = rendezvous_score(routing_hash, &keys[a].id, weight_of(a));
+            let score_b = rendezvous_score(routing_hash, &keys[b].id, weight_of(b));
+            score_a.total_cmp(&score_b)
+        })?;
+        
+        keys[selected].increment_requests();
+        Some(keys[selected].to_api_key())
+    }
+    
+    /// 一致性哈希模式下按请求属性（客户端 IP、会话头或 JWT sub）的哈希选择密钥
+    /// 
+    /// 各密钥按权重在环上占有虚拟节点，从哈希位置顺时针选择第一个可用且未被跳过的密钥；
+    /// 密钥禁用、冷却或熔断时只有原本落在它上面的属性改由环上的下一个密钥承接。
+    /// 未启用一致性哈希时使用加权轮询
+    pub async fn get_next_key_for_affinity_excluding(
+        &self,
+        affinity_hash: u64,
+        model: Option<&str>,
+        exclude: &[String],
+    ) -> Option<ApiKey> {
+        let SchedulingMode::ConsistentHashing { ring_size } = *self.scheduling_mode.read().await else {
+            return self.get_next_key_excluding(model, exclude).await;
+        };
+        
+        let mut keys = self.keys.write().await;
+        self.update_keys_availability(&mut keys).await;
+        
+        let mut ring = self.hash_ring.write().await;
+        if !ring.is_built_for(keys.iter().map(|k| (k.id.as_str(), k.weight))) {
+            *ring = HashRing::build(keys.iter().map(|k| (k.id.clone(), k.weight)).collect(), ring_size);
+            tracing::debug!(keys = keys.len(), nodes = ring.node_count(), "重建一致性哈希环");
+        }
+        let selected = ring.lookup(affinity_hash, |i| {
+            keys[i].is_available() && keys[i].allows_model(model) && !exclude.contains(&keys[i].id)
+        })?;
+        
+        keys[selected].increment_requests();
+        Some(keys[selected].to_api_key())
+    }
+    
+    /// 获取下一个可用的 API 密钥（使用平滑加权轮询算法）
+    pub async fn get_next_key(&self) -> Option<ApiKey> {
+        self.get_next_key_for_model(None).await
+    }
+    
+    /// 为请求的模型获取下一个可用的 API 密钥，优先该模型的密钥按放大后的权重参与轮询
+    pub async fn get_next_key_for_model(&self, model: Option<&str>) -> Option<ApiKey> {
+        self.get_next_key_excluding(model, &[]).await
+    }
+    
+    /// 获取下一个可用的 API 密钥，跳过指定的密钥（用于请求内换密钥重试）
+    pub async fn get_next_key_excluding(&self, model: Option<&str>, exclude: &[String]) -> Option<ApiKey> {
+        let mut keys = self.keys.write().await;
+        
+        // 更新所有密钥的可用状态
+        self.update_keys_availability(&mut keys).await;
+        
+        // 使用平滑加权轮询算法选择密钥
+        let selected_key = self.select_key_with_smooth_wrr(&mut keys, model, exclude).await;
+        
+        if let Some(selected) = selected_key {
+            // 增加请求计数
+            if let Some(key) = keys.iter_mut().find(|k| k.id == selected.id) {
+                key.increment_requests();
+            }
+            Some(selected)
+        } else {
+            None
+        }
+    }
+    
+    /// 更新密钥的可用状态（内部方法，已持有写锁）
+    async fn update_keys_availability(&self, keys: &mut Vec<UnifiedApiKey>) {
+        let now_instant = Instant::now();
+        let mut total_weight_changed = false;
+        
+        for key in keys.iter_mut() {
+            // 推进进行中的权重渐变
+            if key.advance_ramp(now_instant) {
+                total_weight_changed = true;
+            }
+            
+            // 冷却期结束后自动恢复参与选择
+            if key.runtime_state.cooldown_until.is_some() && !key.in_cooldown(now_instant) {
+                key.runtime_state.cooldown_until = None;
+                tracing::info!(key_id = %key.id, "密钥冷却期结束，恢复参与选择");
+            }
+            
+            // 重置速率限制计数器
+            if key.should_reset_rate_limit() {
+                key.reset_rate_limit();
+            }
+            
+            // 检查密钥是否应该重新激活
+            if !key.runtime_state.is_active && key.runtime_state.failure_count < 3 {
+                // 简单的恢复策略：5分钟后重试失败的密钥
+                if key.rate_limit_window_elapsed(now_instant) >= Duration::from_secs(300) {
+                    key.mark_success();
+                    total_weight_changed = true;
+                }
+            }
+        }
+        
+        // 如果总权重发生变化，更新缓存
+        if total_weight_changed {
+            let new_total_weight = keys.iter()
+                .map(|k| k.scheduling_state.effective_weight)
+                .sum();
+            *self.total_weight.write().await = new_total_weight;
+        }
+        
+        // 大规模密钥池：同步选择索引，密钥增删时重建，否则只更新权重变化的位置
+        if keys.len() >= INDEXED_SELECTION_MIN_KEYS {
+            let soft_limit_ratio = *self.soft_limit_ratio.read().await;
+            let mut index = self.selection_index.write().await;
+            if index.len() != keys.len() {
+                let weights: Vec<u64> = keys.iter().map(|k| k.indexed_weight(soft_limit_ratio)).collect();
+                index.rebuild(&weights);
+            } else {
+                for (position, key) in keys.iter().enumerate() {
+                    index.set(position, key.indexed_weight(soft_limit_ratio));
+                }
+            }
+        }
+    }
+    
+    /// 使用选择索引定位密钥（内部方法，已持有写锁）
+    ///
+    /// 仅用于大规模密钥池且没有逐请求的筛选条件（排除列表、区域优先、模型加权、模型限制）时；
+    /// 索引中没有可选密钥（例如全部接近软限额）时返回 None，由平滑加权轮询处理
+    async fn select_key_indexed(&self, keys: &[UnifiedApiKey], model: Option<&str>, exclude: &[String]) -> Option<usize> {
+        if keys.len() < INDEXED_SELECTION_MIN_KEYS
+            || !exclude.is_empty()
+            || (model.is_some() && keys.iter().any(|k| !k.allowed_models.is_empty()))
+            || self.region_preference.read().await.is_some()
+            || (model.is_some() && *self.preferred_model_boost.read().await > 1.0)
+        {
+            return None;
+        }
+        self.selection_index.write().await.next()
+    }
+    
+    /// 使用平滑加权轮询算法选择密钥（内部方法，已持有写锁）
+    async fn select_key_with_smooth_wrr(
+        &self,
+        keys: &mut Vec<UnifiedApiKey>,
+        model: Option<&str>,
+        exclude: &[String],
+    ) -> Option<ApiKey> {
+        if let Some(index) = self.select_key_indexed(keys, model, exclude).await {
+            return Some(keys[index].to_api_key());
+        }
+        
+        // 过滤出可用且允许服务请求模型的密钥
+        let mut available_keys: Vec<usize> = keys.iter()
+            .enumerate()
+            .filter(|(_, key)| key.is_available() && key.allows_model(model) && !exclude.contains(&key.id))
+            .map(|(i, _)| i)
+            .collect();
+        
+        if available_keys.is_empty() {
+            return None;
+        }
+        
+        // 接近限额的密钥降低优先级：只要还有其他可用密钥就不选择它们
+        let soft_limit_ratio = *self.soft_limit_ratio.read().await;
+        let below_soft_limit: Vec<usize> = available_keys.iter()
+            .copied()
+            .filter(|&i| !keys[i].is_near_limit(soft_limit_ratio))
+            .collect();
+        if !below_soft_limit.is_empty() {
+            available_keys = below_soft_limit;
+        }
+        
+        // 启用区域延迟优先时只在延迟最优的区域内按权重轮询
+        if let Some(preference) = *self.region_preference.read().await {
+            let region_latency = self.region_latency.read().await;
+            available_keys = preferred_region_candidates(
+                keys,
+                available_keys,
+                &region_latency,
+                preference.latency_tolerance_ratio,
+            );
+        }
+        
+        // 按请求模型计算各密钥参与本次选择的有效权重，最近失败的密钥扣除失败惩罚
+        let boost = *self.preferred_model_boost.read().await;
+        let now = Instant::now();
+        let weights: Vec<i32> = available_keys.iter()
+            .map(|&i| {
+                let weight = keys[i].selection_weight(keys[i].scheduling_state.effective_weight, model, boost);
+                keys[i].penalized_weight(weight, now)
+            })
+            .collect();
+        let total_effective_weight: i32 = weights.iter().sum();
+        
+        if total_effective_weight <= 0 {
+            return None;
+        }
+        
+        // 平滑加权轮询算法
+        // 1. 为每个可用密钥增加其有效权重到当前权重
+        for (&i, &weight) in available_keys.iter().zip(&weights) {
+            keys[i].scheduling_state.current_weight += weight;
+        }
+        
+        // 2. 选择当前权重最大的密钥
+        let selected_index = available_keys.iter()
+            .max_by_key(|&&i| keys[i].scheduling_state.current_weight)
+            .copied()?;
+        
+        // 3. 将选中密钥的当前权重减去总有效权重
+        keys[selected_index].scheduling_state.current_weight -= total_effective_weight;
+        
+        Some(keys[selected_index].to_api_key())
+    }
+    
+    /// 标记密钥为失败状态
+    pub async fn mark_key_failed(&self, key_id: &str) {
+        self.mark_key_failed_inner(key_id, None).await;
+    }
+    
+    /// 标记密钥为失败状态，并记录上游返回的状态码（用于健康报告）
+    pub async fn mark_key_failed_with_status(&self, key_id: &str, status: u16) {
+        self.mark_key_failed_inner(key_id, Some(status)).await;
+    }
+    
+    async fn mark_key_failed_inner(&self, key_id: &str, status: Option<u16>) {
+        let adaptive = *self.adaptive_rate_limit.read().await;
+        let penalty = *self.failure_penalty.read().await;
+        let mut keys = self.keys.write().await;
+        if let Some(key) = keys.iter_mut().find(|k| k.id == key_id) {
+            if let Some(params) = penalty {
+                key.record_failure_penalty(&params, Instant::now());
+            }
+            if status.is_some() {
+                key.runtime_state.last_error_status = status;
+            }
+            if let (Some(params), Some(429)) = (adaptive, status) {
+                if key.record_rate_limited(&params, Instant::now()) {
+                    tracing::info!(
+                        key_id = %key.id,
+                        effective_limit = key.effective_max_requests_per_minute(),
+                        "连续收到 429，下调密钥的每分钟限额"
+                    );
+                }
+            }
+            let old_effective_weight = key.scheduling_state.effective_weight;
+            key.mark_failed();
+            
+            // 如果有效权重发生变化，更新总权重缓存
+            if old_effective_weight != key.scheduling_state.effective_weight {
+                let new_total_weight = keys.iter()
+                    .map(|k| k.scheduling_state.effective_weight)
+                    .sum();
+                *self.total_weight.write().await = new_total_weight;
+            }
+        }
+    }
+    
+    /// 上游返回 429 时让密钥进入冷却期，冷却期内不参与选择，结束后自动恢复
+    ///
+    /// 冷却时长优先使用 Retry-After（不超过上限），已有更晚的冷却截止时间时保留；未启用冷却时忽略。
+    /// 返回本次设置的冷却时长
+    pub async fn start_cooldown(&self, key_id: &str, retry_after: Option<Duration>) -> Option<Duration> {
+        let params = (*self.rate_limit_cooldown.read().await)?;
+        let duration = retry_after.unwrap_or(params.default_duration).min(params.max_duration);
+        if duration.is_zero() {
+            return None;
+        }
+        let until = Instant::now() + duration;
+        
+        let mut keys = self.keys.write().await;
+        let key = keys.iter_mut().find(|k| k.id == key_id)?;
+        if key.runtime_state.cooldown_until.is_some_and(|current| current >= until) {
+            return None;
+        }
+        key.runtime_state.cooldown_until = Some(until);
+        tracing::warn!(key_id = %key_id, cooldown_secs = duration.as_secs_f64(), "密钥收到 429，进入冷却期");
+        Some(duration)
+    }
+    
+    /// 各密钥剩余的冷却时长（只包含处于冷却期的密钥）
+    pub async fn cooldowns(&self) -> HashMap<String, Duration> {
+        let now = Instant::now();
+        self.keys
+            .read()
+            .await
+            .iter()
+            .filter_map(|k| k.cooldown_remaining(now).map(|remaining| (k.id.clone(), remaining)))
+            .collect()
+    }
+    
+    /// 标记密钥为成功状态
+    pub async fn mark_key_success(&self, key_id: &str) {
+        let adaptive = *self.adaptive_rate_limit.read().await;
+        let mut keys = self.keys.write().await;
+        if let Some(key) = keys.iter_mut().find(|k| k.id == key_id) {
+            if let Some(params) = adaptive {
+                key.record_clean_request(&params, Instant::now());
+            }
+            let old_effective_weight = key.scheduling_state.effective_weight;
+            key.mark_success();
+            
+            // 如果有效权重发生变化，更新总权重缓存
+            if old_effective_weight != key.scheduling_state.effective_weight {
+                let new_total_weight = keys.iter()
+                    .map(|k| k.scheduling_state.effective_weight)
+                    .sum();
+                *self.total_weight.write().await = new_total_weight;
+            }
+        }
+    }
+    
+    /// 手动停用或启用密钥，返回密钥之前是否处于启用状态
+    /// 
+    /// 停用的密钥不参与任何选择（包括粘性绑定），也不计入健康与就绪统计；
+    /// 启用只清除手动停用标记，不影响失败计数与自动停用状态
+    pub async fn set_key_enabled(&self, key_id: &str, enabled: bool) -> Result<bool, String> {
+        let mut keys = self.keys.write().await;
+        let key = keys.iter_mut()
+            .find(|k| k.id == key_id)
+            .ok_or_else(|| format!("Key '{}' not found", key_id))?;
+        let was_enabled = !key.runtime_state.disabled;
+        key.runtime_state.disabled = !enabled;
+        drop(keys);
+        
+        if !enabled {
+            self.sticky_bindings.write().await.retain(|_, binding| binding.key_id != key_id);
+        }
+        Ok(was_enabled)
+    }
+    
+    /// 运行时新增密钥
+    /// 
+    /// 指定影子预热期时，密钥在预热期内不参与真实选择，只接收镜像流量（见 [`Self::shadow_keys`]）；
+    /// 镜像请求失败会重新开始预热，预热期满后自动参与选择
+    pub async fn add_key(&self, api_key: ApiKey, shadow_warmup: Option<Duration>) -> Result<(), String> {
+        let mut keys = self.keys.write().await;
+        if keys.iter().any(|k| k.id == api_key.id) {
+            return Err(format!("Key '{}' already exists", api_key.id));
+        }
+        
+        let mut key = UnifiedApiKey::from_api_key(api_key);
+        let weight = key.weight_bounds(&*self.weight_bounds.read().await).clamp(key.weight);
+        key.update_weight(weight);
+        key.runtime_state.shadow = shadow_warmup.filter(|w| !w.is_zero()).map(ShadowWarmup::new);
+        keys.push(key);
+        
+        let new_total_weight = keys.iter()
+            .map(|k| k.scheduling_state.effective_weight)
+            .sum();
+        *self.total_weight.write().await = new_total_weight;
+        apply_account_quota(&mut keys, *self.account_quota.read().await);
+        Ok(())
+    }
+    
+    /// 处于影子预热期、应接收镜像流量的密钥（停用或排空的密钥除外）
+    pub async fn shadow_keys(&self) -> Vec<ApiKey> {
+        self.keys.read().await
+            .iter()
+            .filter(|k| k.in_shadow() && !k.runtime_state.disabled && !k.runtime_state.drained)
+            .map(|k| k.to_api_key())
+            .collect()
+    }
+    
+    /// 是否存在处于影子预热期的密钥
+    pub async fn has_shadow_keys(&self) -> bool {
+        self.keys.read().await.iter().any(|k| k.in_shadow())
+    }
+    
+    /// 记录影子密钥的镜像请求结果
+    pub async fn record_shadow_result(&self, key_id: &str, success: bool) {
+        let mut keys = self.keys.write().await;
+        if let Some(shadow) = keys.iter_mut()
+            .find(|k| k.id == key_id)
+            .and_then(|k| k.runtime_state.shadow.as_mut())
+        {
+            shadow.record(success);
+        }
+    }
+    
+    /// 批量排空或恢复匹配选择器的密钥（原子操作），返回状态发生变化的密钥 ID
+    pub async fn set_keys_drained(&self, selector: &KeySelector, drained: bool) -> Vec<String> {
+        let mut keys = self.keys.write().await;
+        let changed: Vec<String> = keys.iter_mut()
+            .filter(|k| selector.matches(k) && k.runtime_state.drained != drained)
+            .map(|k| {
+                k.runtime_state.drained = drained;
+                k.id.clone()
+            })
+            .collect();
+        drop(keys);
+        
+        if drained && !changed.is_empty() {
+            self.sticky_bindings.write().await.retain(|_, binding| !changed.contains(&binding.key_id));
+        }
+        changed
+    }
+    
+    /// 更新密钥权重（原子操作），权重先钳制到该密钥的上下限内，返回实际生效的权重
+    pub async fn update_key_weight(&self, key_id: &str, new_weight: u32) -> Result<u32, String> {
+        let mut keys = self.keys.write().await;
+        
+        let ramp_window = *self.weight_ramp_window.read().await;
+        let bounds = *self.weight_bounds.read().await;
+        if let Some(key) = keys.iter_mut().find(|k| k.id == key_id) {
+            let applied = key.weight_bounds(&bounds).clamp(new_weight);
+            key.ramp_weight(applied, ramp_window);
+            
+            // 更新总权重缓存
+            let new_total_weight = keys.iter()
+                .map(|k| k.scheduling_state.effective_weight)
+                .sum();
             *self.total_weight.write().await = new_total_weight;
+            apply_account_quota(&mut keys, *self.account_quota.read().await);
             
-            Ok(())
+            Ok(applied)
         } else {
             Err(format!("密钥 {} 不存在", key_id))
         }
     }
     
-    /// 批量更新密钥权重（原子操作）
+    /// 批量更新密钥权重（原子操作），返回各密钥钳制后实际生效的权重
     #[allow(dead_code)]
-    pub async fn batch_update_weights(&self, updates: &[(String, u32)]) -> Result<(), String> {
+    pub async fn batch_update_weights(&self, updates: &[(String, u32)]) -> Result<Vec<(String, u32)>, String> {
+        let changes = self.apply_key_weights(updates).await?;
+        Ok(changes.into_iter().map(|change| (change.key_id, change.applied_weight)).collect())
+    }
+    
+    /// 批量更新密钥权重（原子操作），同时返回更新前的权重；任一密钥不存在时不做任何修改
+    pub async fn apply_key_weights(&self, updates: &[(String, u32)]) -> Result<Vec<KeyWeightChange>, String> {
         let mut keys = self.keys.write().await;
         
         // 验证所有密钥是否存在
@@ -361,9 +1568,19 @@ impl UnifiedKeyManager {
         }
         
         // 批量更新权重
+        let ramp_window = *self.weight_ramp_window.read().await;
+        let bounds = *self.weight_bounds.read().await;
+        let mut applied = Vec::with_capacity(updates.len());
         for (key_id, new_weight) in updates {
             if let Some(key) = keys.iter_mut().find(|k| &k.id == key_id) {
-                key.update_weight(*new_weight);
+                let previous_weight = key.weight;
+                let weight = key.weight_bounds(&bounds).clamp(*new_weight);
+                key.ramp_weight(weight, ramp_window);
+                applied.push(KeyWeightChange {
+                    key_id: key_id.clone(),
+                    previous_weight,
+                    applied_weight: weight,
+                });
             }
         }
         
@@ -372,8 +1589,81 @@ impl UnifiedKeyManager {
             .map(|k| k.scheduling_state.effective_weight)
             .sum();
         *self.total_weight.write().await = new_total_weight;
+        apply_account_quota(&mut keys, *self.account_quota.read().await);
         
-        Ok(())
+        Ok(applied)
+    }
+    
+    /// 原子地替换密钥集合
+    /// 
+    /// 未变化的密钥保留运行时状态（请求计数、失败次数等）；密钥内容或配置变化的密钥
+    /// 更新配置但保留运行时状态；新增密钥从初始状态开始；被移除的密钥直接下线。
+    /// 返回密钥集合是否发生变化
+    pub async fn replace_keys(&self, mut new_keys: Vec<ApiKey>) -> bool {
+        let mut keys = self.keys.write().await;
+        let account_quota = *self.account_quota.read().await;
+        let bounds = *self.weight_bounds.read().await;
+        for new_key in new_keys.iter_mut() {
+            new_key.weight = new_key.weight_bounds(&bounds).clamp(new_key.weight);
+        }
+        
+        // 按账号配额分配限额时，密钥自身的限额不生效，不参与变化判断
+        let unchanged = keys.len() == new_keys.len()
+            && new_keys.iter().all(|new_key| {
+                keys.iter().any(|k| {
+                    k.id == new_key.id
+                        && k.key == new_key.key
+                        && k.weight == new_key.weight
+                        && k.tenant == new_key.tenant
+                        && k.tags == new_key.tags
+                        && k.region == new_key.region
+                        && k.weight_min == new_key.weight_min
+                        && k.weight_max == new_key.weight_max
+                        && k.preferred_models == new_key.preferred_models
+                        && k.allowed_models == new_key.allowed_models
+                        && (account_quota.is_some() || k.max_requests_per_minute == new_key.max_requests_per_minute)
+                })
+            });
+        if unchanged {
+            return false;
+        }
+        
+        let ramp_window = *self.weight_ramp_window.read().await;
+        let mut merged = Vec::with_capacity(new_keys.len());
+        for new_key in new_keys {
+            match keys.iter().position(|k| k.id == new_key.id) {
+                Some(index) => {
+                    let mut existing = keys.swap_remove(index);
+                    existing.key = new_key.key;
+                    existing.max_requests_per_minute = new_key.max_requests_per_minute;
+                    existing.tenant = new_key.tenant;
+                    existing.tags = new_key.tags;
+                    existing.region = new_key.region;
+                    existing.weight_min = new_key.weight_min;
+                    existing.weight_max = new_key.weight_max;
+                    existing.preferred_models = new_key.preferred_models;
+                    existing.allowed_models = new_key.allowed_models;
+                    if existing.weight != new_key.weight {
+                        existing.ramp_weight(new_key.weight, ramp_window);
+                    }
+                    merged.push(existing);
+                }
+                None => merged.push(UnifiedApiKey::from_api_key(new_key)),
+            }
+        }
+        *keys = merged;
+        apply_account_quota(&mut keys, account_quota);
+        
+        let new_total_weight = keys.iter()
+            .map(|k| k.scheduling_state.effective_weight)
+            .sum();
+        *self.total_weight.write().await = new_total_weight;
+        
+        // 清理指向已移除密钥的粘性绑定
+        self.sticky_bindings.write().await
+            .retain(|_, binding| keys.iter().any(|k| k.id == binding.key_id));
+        
+        true
     }
     
     /// 获取所有密钥的状态（兼容性方法）
@@ -382,24 +1672,59 @@ impl UnifiedKeyManager {
         keys.iter().map(|k| k.to_api_key()).collect()
     }
     
+    /// 获取所有密钥的完整状态快照（包含运行时状态）
+    pub async fn get_key_states(&self) -> Vec<UnifiedApiKey> {
+        self.keys.read().await.clone()
+    }
+    
     /// 获取负载均衡统计信息
     pub async fn get_stats(&self) -> LoadBalancingStats {
         let keys = self.keys.read().await;
         
         LoadBalancingStats {
             total_keys: keys.len(),
-            active_keys: keys.iter().filter(|k| k.runtime_state.is_active).count(),
+            active_keys: keys.iter().filter(|k| k.runtime_state.is_active && !k.runtime_state.disabled && !k.runtime_state.drained && !k.in_shadow()).count(),
             total_weight: keys.iter().map(|k| k.weight).sum(),
             total_requests: keys.iter().map(|k| k.runtime_state.current_requests).sum(),
             failed_keys: keys.iter().filter(|k| k.runtime_state.failure_count >= 3).count(),
         }
     }
     
+    /// 是否有密钥（不论当前状态）允许服务该模型
+    pub async fn serves_model(&self, model: &str) -> bool {
+        self.keys.read().await.iter().any(|k| k.allows_model(Some(model)))
+    }
+    
+    /// 按模型统计可用密钥：`allowed_models` 中出现的每个模型，以及 `*`（只由未限制模型的密钥服务的其他模型）
+    pub async fn model_availability(&self) -> HashMap<String, ModelKeyAvailability> {
+        let keys = self.keys.read().await;
+        let mut models: Vec<String> = keys.iter()
+            .flat_map(|k| k.allowed_models.iter().map(|m| m.to_ascii_lowercase()))
+            .collect();
+        models.sort();
+        models.dedup();
+        
+        let availability_of = |matching: Vec<&UnifiedApiKey>| ModelKeyAvailability {
+            total_keys: matching.len(),
+            available_keys: matching.iter().filter(|k| k.is_available()).count(),
+        };
+        let mut availability: HashMap<String, ModelKeyAvailability> = models
+            .into_iter()
+            .map(|model| {
+                let matching = keys.iter().filter(|k| k.allows_model(Some(model.as_str()))).collect();
+                (model, availability_of(matching))
+            })
+            .collect();
+        let unrestricted = keys.iter().filter(|k| k.allowed_models.is_empty()).collect();
+        availability.insert("*".to_string(), availability_of(unrestricted));
+        availability
+    }
+    
     /// 获取活跃密钥数量
     #[allow(dead_code)]
     pub async fn get_active_keys_count(&self) -> usize {
         let keys = self.keys.read().await;
-        keys.iter().filter(|k| k.runtime_state.is_active).count()
+        keys.iter().filter(|k| k.runtime_state.is_active && !k.runtime_state.disabled && !k.runtime_state.drained).count()
     }
     
     /// 检查是否有可用密钥
@@ -411,4 +1736,720 @@ impl UnifiedKeyManager {
 }
 
 // 移除 WeightStats 类型别名以避免与 weighted_round_robin 模块冲突
-// 使用 LoadBalancingStats 作为统一的统计类型
\ No newline at end of file
+// 使用 LoadBalancingStats 作为统一的统计类型
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_api_key(id: &str, weight: u32) -> ApiKey {
+        ApiKey {
+            weight,
+            max_requests_per_minute: 1000,
+            ..ApiKey::for_test(id)
+        }
+    }
+
+    fn create_test_manager() -> UnifiedKeyManager {
+        UnifiedKeyManager::new(vec![
+            create_test_api_key("key1", 100),
+            create_test_api_key("key2", 100),
+            create_test_api_key("key3", 100),
+        ])
+    }
+
+    fn create_limited_api_key(id: &str, weight: u32, max_requests_per_minute: u32) -> ApiKey {
+        ApiKey {
+            max_requests_per_minute,
+            ..create_test_api_key(id, weight)
+        }
+    }
+
+    async fn requests_of(manager: &UnifiedKeyManager, key_id: &str) -> u32 {
+        manager
+            .get_all_keys()
+            .await
+            .into_iter()
+            .find(|k| k.id == key_id)
+            .map(|k| k.current_requests)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_large_pool_indexed_selection_follows_weights() {
+        let keys: Vec<ApiKey> = (0..200)
+            .map(|i| create_test_api_key(&format!("key{}", i), (i % 4 + 1) * 10))
+            .collect();
+        let manager = UnifiedKeyManager::new(keys).with_disabled_keys(&["key0".to_string(), "key1".to_string()]);
+
+        // 去掉停用密钥后共 497 个权重单位（10 为一个单位），每轮每个权重单位被选中一次
+        let rounds = 4;
+        for _ in 0..497 * rounds {
+            manager.get_next_key().await.unwrap();
+        }
+
+        assert_eq!(requests_of(&manager, "key0").await, 0);
+        assert_eq!(requests_of(&manager, "key1").await, 0);
+        for i in 2..200u32 {
+            let expected = (i % 4 + 1) * rounds;
+            let actual = requests_of(&manager, &format!("key{}", i)).await;
+            assert!(actual.abs_diff(expected) <= 3, "key{}: {} vs {}", i, actual, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_soft_limit_shifts_traffic_before_hard_cap() {
+        let manager = UnifiedKeyManager::new(vec![
+            create_limited_api_key("hot", 900, 10),
+            create_limited_api_key("spare", 100, 1000),
+        ])
+        .with_soft_limit_ratio(0.9);
+
+        for _ in 0..50 {
+            manager.get_next_key().await.unwrap();
+        }
+
+        // 高权重密钥在达到 90% 利用率后不再被选中，从未触及硬上限
+        assert_eq!(requests_of(&manager, "hot").await, 9);
+        assert_eq!(requests_of(&manager, "spare").await, 41);
+    }
+
+    #[tokio::test]
+    async fn test_without_soft_limit_key_reaches_hard_cap() {
+        let manager = UnifiedKeyManager::new(vec![
+            create_limited_api_key("hot", 900, 10),
+            create_limited_api_key("spare", 100, 1000),
+        ]);
+
+        for _ in 0..50 {
+            manager.get_next_key().await.unwrap();
+        }
+
+        assert_eq!(requests_of(&manager, "hot").await, 10);
+    }
+
+    #[tokio::test]
+    async fn test_near_limit_key_still_used_when_others_unavailable() {
+        let manager = UnifiedKeyManager::new(vec![
+            create_limited_api_key("hot", 100, 10),
+            create_limited_api_key("broken", 100, 1000),
+        ])
+        .with_soft_limit_ratio(0.9);
+        for _ in 0..3 {
+            manager.mark_key_failed("broken").await;
+        }
+
+        for _ in 0..10 {
+            assert_eq!(manager.get_next_key().await.unwrap().id, "hot");
+        }
+        // 达到硬上限后不再可用
+        assert!(manager.get_next_key().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stickiness_reuses_key_within_window() {
+        let manager = create_test_manager();
+        manager.set_stickiness_window_ms(60_000).await;
+
+        let first = manager.get_next_key_for_client("client-a", None).await.unwrap();
+        for _ in 0..10 {
+            let key = manager.get_next_key_for_client("client-a", None).await.unwrap();
+            assert_eq!(key.id, first.id);
+        }
+
+        // 其他客户端不受影响，仍按轮询分配
+        let other = manager.get_next_key_for_client("client-b", None).await.unwrap();
+        assert_ne!(other.id, first.id);
+    }
+
+    #[tokio::test]
+    async fn test_stickiness_rebalances_after_window() {
+        let manager = create_test_manager();
+        manager.set_stickiness_window_ms(20).await;
+
+        let first = manager.get_next_key_for_client("client-a", None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+
+        let second = manager.get_next_key_for_client("client-a", None).await.unwrap();
+        assert_ne!(second.id, first.id);
+    }
+
+    #[tokio::test]
+    async fn test_stickiness_disabled_by_default() {
+        let manager = create_test_manager();
+
+        let first = manager.get_next_key_for_client("client-a", None).await.unwrap();
+        let second = manager.get_next_key_for_client("client-a", None).await.unwrap();
+        assert_ne!(first.id, second.id);
+    }
+
+    #[tokio::test]
+    async fn test_stickiness_reselects_unavailable_key() {
+        let manager = create_test_manager();
+        manager.set_stickiness_window_ms(60_000).await;
+
+        let first = manager.get_next_key_for_client("client-a", None).await.unwrap();
+        for _ in 0..3 {
+            manager.mark_key_failed(&first.id).await;
+        }
+
+        let second = manager.get_next_key_for_client("client-a", None).await.unwrap();
+        assert_ne!(second.id, first.id);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_key_excluded_until_enabled() {
+        let manager = create_test_manager();
+        assert_eq!(manager.set_key_enabled("key2", false).await, Ok(true));
+        assert!(manager.set_key_enabled("missing", false).await.is_err());
+
+        for _ in 0..30 {
+            let key = manager.get_next_key().await.unwrap();
+            assert_ne!(key.id, "key2");
+        }
+        assert_eq!(manager.get_active_keys_count().await, 2);
+
+        // 成功请求不会清除手动停用标记
+        manager.mark_key_success("key2").await;
+        assert_eq!(manager.get_stats().await.active_keys, 2);
+
+        assert_eq!(manager.set_key_enabled("key2", true).await, Ok(false));
+        let mut selected = Vec::new();
+        for _ in 0..3 {
+            selected.push(manager.get_next_key().await.unwrap().id);
+        }
+        assert!(selected.contains(&"key2".to_string()));
+        assert_eq!(manager.get_active_keys_count().await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_failover_selection_skips_tried_keys() {
+        let manager = create_test_manager();
+        let tried = vec!["key1".to_string(), "key2".to_string()];
+        for _ in 0..5 {
+            assert_eq!(manager.get_next_key_excluding(None, &tried).await.unwrap().id, "key3");
+        }
+
+        let all = vec!["key1".to_string(), "key2".to_string(), "key3".to_string()];
+        assert!(manager.get_next_key_excluding(None, &all).await.is_none());
+    }
+
+    #[test]
+    fn test_with_disabled_keys_applies_persisted_state() {
+        let manager = create_test_manager().with_disabled_keys(&["key1".to_string()]);
+        let states = manager.keys.try_read().unwrap();
+        assert!(states.iter().find(|k| k.id == "key1").unwrap().runtime_state.disabled);
+        assert!(!states.iter().find(|k| k.id == "key2").unwrap().runtime_state.disabled);
+    }
+
+    async fn limits(manager: &UnifiedKeyManager) -> HashMap<String, u32> {
+        manager.get_all_keys().await.into_iter().map(|k| (k.id, k.max_requests_per_minute)).collect()
+    }
+
+    #[test]
+    fn test_split_quota_sums_to_account_quota() {
+        assert_eq!(split_quota_by_weight(1000, &[100, 300]), vec![250, 750]);
+        assert_eq!(split_quota_by_weight(100, &[1, 1, 1]).iter().sum::<u32>(), 100);
+        assert_eq!(split_quota_by_weight(10, &[0, 0]), vec![5, 5]);
+        for weights in [vec![7, 13, 29], vec![1, 1000], vec![33, 33, 34, 1]] {
+            assert_eq!(split_quota_by_weight(997, &weights).iter().sum::<u32>(), 997);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_quota_follows_weight_changes() {
+        let manager = UnifiedKeyManager::new(vec![
+            create_test_api_key("key1", 100),
+            create_test_api_key("key2", 300),
+        ])
+        .with_account_quota(Some(600));
+
+        let derived = limits(&manager).await;
+        assert_eq!((derived["key1"], derived["key2"]), (150, 450));
+
+        manager.update_key_weight("key1", 300).await.unwrap();
+        let derived = limits(&manager).await;
+        assert_eq!((derived["key1"], derived["key2"]), (300, 300));
+
+        manager.batch_update_weights(&[("key1".to_string(), 100), ("key2".to_string(), 100)]).await.unwrap();
+        manager.replace_keys(vec![
+            create_test_api_key("key1", 100),
+            create_test_api_key("key2", 100),
+            create_test_api_key("key3", 100),
+        ]).await;
+        let derived = limits(&manager).await;
+        assert_eq!(derived.values().sum::<u32>(), 600);
+        assert!(derived.values().all(|&limit| limit == 200));
+
+        // 未设置账号配额时使用密钥自身的限额
+        let manager = create_test_manager();
+        assert!(limits(&manager).await.values().all(|&limit| limit == 1000));
+    }
+
+    #[tokio::test]
+    async fn test_manual_weight_changes_respect_bounds() {
+        let capped = ApiKey {
+            weight_max: Some(500),
+            ..create_test_api_key("key1", 900)
+        };
+        let manager = UnifiedKeyManager::new(vec![capped, create_test_api_key("key2", 100)])
+            .with_weight_bounds(WeightBounds { min: Some(50), max: Some(1000) });
+
+        // 构造时已有权重被钳制，密钥自身的上限优先于全局上限
+        assert_eq!(manager.get_all_keys().await[0].weight, 500);
+        assert_eq!(manager.get_stats().await.total_weight, 600);
+
+        assert_eq!(manager.update_key_weight("key1", 800).await.unwrap(), 500);
+        assert_eq!(manager.update_key_weight("key2", 10).await.unwrap(), 50);
+        assert_eq!(manager.update_key_weight("key2", 5000).await.unwrap(), 1000);
+
+        let applied = manager
+            .batch_update_weights(&[("key1".to_string(), 1), ("key2".to_string(), 200)])
+            .await
+            .unwrap();
+        assert_eq!(applied, vec![("key1".to_string(), 50), ("key2".to_string(), 200)]);
+    }
+
+    #[tokio::test]
+    async fn test_shadow_key_receives_no_selections_until_warmup_completes() {
+        let manager = create_pair_manager();
+        manager
+            .add_key(create_test_api_key("shadow", 100), Some(Duration::from_millis(100)))
+            .await
+            .unwrap();
+        assert!(manager.add_key(create_test_api_key("shadow", 100), None).await.is_err());
+
+        // 预热期内只作为镜像目标，不参与真实选择
+        assert!(manager.has_shadow_keys().await);
+        assert_eq!(manager.shadow_keys().await.iter().map(|k| k.id.as_str()).collect::<Vec<_>>(), vec!["shadow"]);
+        for _ in 0..20 {
+            assert_ne!(manager.get_next_key().await.unwrap().id, "shadow");
+        }
+        assert_eq!(manager.get_stats().await.active_keys, 2);
+
+        // 镜像请求失败会重新开始预热
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        manager.record_shadow_result("shadow", false).await;
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(manager.has_shadow_keys().await);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(manager.shadow_keys().await.is_empty());
+        let mut selected = std::collections::HashSet::new();
+        for _ in 0..30 {
+            selected.insert(manager.get_next_key().await.unwrap().id);
+        }
+        assert!(selected.contains("shadow"));
+    }
+
+    /// 连续选择 `rounds` 次，返回 key1 被选中的次数
+    async fn key1_selections(manager: &UnifiedKeyManager, rounds: usize) -> usize {
+        let mut count = 0;
+        for _ in 0..rounds {
+            if manager.get_next_key().await.unwrap().id == "key1" {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn create_pair_manager() -> UnifiedKeyManager {
+        UnifiedKeyManager::new(vec![
+            create_test_api_key("key1", 100),
+            create_test_api_key("key2", 100),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_immediate_weight_change_applies_at_once() {
+        let manager = create_pair_manager();
+        assert_eq!(key1_selections(&manager, 100).await, 50);
+
+        manager.update_key_weight("key1", 900).await.unwrap();
+
+        // 下一轮选择立即按 900:100 分配
+        let count = key1_selections(&manager, 100).await;
+        assert!((88..=92).contains(&count), "key1 selected {} times", count);
+    }
+
+    #[tokio::test]
+    async fn test_ramped_weight_change_applies_gradually() {
+        let manager = create_pair_manager().with_weight_ramp_window_ms(400);
+        assert_eq!(key1_selections(&manager, 100).await, 50);
+
+        manager.update_key_weight("key1", 900).await.unwrap();
+
+        // 变更后立即选择时，有效权重仍接近旧值
+        let count = key1_selections(&manager, 100).await;
+        assert!(count < 65, "key1 selected {} times", count);
+        let stats = manager.get_all_keys().await;
+        assert_eq!(stats.iter().find(|k| k.id == "key1").unwrap().weight, 900);
+
+        // 成功请求不会跳过渐变
+        manager.mark_key_success("key1").await;
+        assert!(key1_selections(&manager, 100).await < 65);
+
+        // 窗口结束后按新权重分配
+        tokio::time::sleep(Duration::from_millis(450)).await;
+        let count = key1_selections(&manager, 100).await;
+        assert!((88..=92).contains(&count), "key1 selected {} times", count);
+        assert!(manager.keys.read().await.iter().all(|k| k.scheduling_state.ramp.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_failure_penalty_decays_selection_share() {
+        let params = FailurePenalty {
+            per_failure: 0.5,
+            max_ratio: 0.9,
+            decay_window: Duration::from_secs(60),
+        };
+        let mut key = UnifiedApiKey::from_api_key(create_test_api_key("key1", 100));
+        let start = Instant::now();
+        assert_eq!(key.penalized_weight(100, start), 100);
+
+        // 刚失败的密钥按一半权重参与选择，再次失败时惩罚叠加（不超过上限）
+        key.record_failure_penalty(&params, start);
+        assert_eq!(key.penalized_weight(100, start), 50);
+        key.record_failure_penalty(&params, start);
+        assert_eq!(key.penalized_weight(100, start), 10);
+
+        // 惩罚随时间线性衰减，衰减完后恢复原有权重
+        assert_eq!(key.penalized_weight(100, start + Duration::from_secs(30)), 55);
+        assert_eq!(key.penalized_weight(100, start + Duration::from_secs(60)), 100);
+        assert_eq!(key.penalized_weight(1, start), 1);
+
+        // 标记失败时记录惩罚，平滑加权轮询按扣除后的权重分配
+        let manager = create_pair_manager().with_failure_penalty(Some(params));
+        manager.mark_key_failed("key1").await;
+        assert!(manager.keys.read().await.iter().any(|k| k.id == "key1" && k.runtime_state.failure_penalty.is_some()));
+        assert!(key1_selections(&manager, 100).await < 50);
+    }
+
+    #[tokio::test]
+    async fn test_hash_routing_is_deterministic_and_spreads() {
+        let manager = create_test_manager();
+        
+        // 相同哈希总是路由到同一个密钥
+        let first = manager.get_next_key_for_hash(42, None).await.unwrap();
+        for _ in 0..10 {
+            assert_eq!(manager.get_next_key_for_hash(42, None).await.unwrap().id, first.id);
+        }
+        
+        // 不同哈希分散到所有密钥
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for i in 0..300u64 {
+            let key = manager.get_next_key_for_hash(i.wrapping_mul(0x9e3779b97f4a7c15), None).await.unwrap();
+            *counts.entry(key.id).or_default() += 1;
+        }
+        assert_eq!(counts.len(), 3);
+        assert!(counts.values().all(|&count| count > 50), "{:?}", counts);
+        
+        // 选中的密钥不可用时改为其他密钥，且不影响其他哈希的归属
+        for _ in 0..3 {
+            manager.mark_key_failed(&first.id).await;
+        }
+        assert_ne!(manager.get_next_key_for_hash(42, None).await.unwrap().id, first.id);
+    }
+
+    #[tokio::test]
+    async fn test_consistent_hashing_remaps_only_removed_key_share() {
+        let key = |id: &str, weight: u32| create_limited_api_key(id, weight, 100_000);
+        let manager = UnifiedKeyManager::new(vec![
+            key("key1", 100),
+            key("key2", 100),
+            key("key3", 200),
+            key("key4", 100),
+        ])
+        .with_scheduling_mode(SchedulingMode::ConsistentHashing { ring_size: 1024 });
+        let hashes: Vec<u64> = (0..500u64).map(|i| i.wrapping_mul(0x9e3779b97f4a7c15)).collect();
+        let assign = || async {
+            let mut assigned = Vec::new();
+            for &hash in &hashes {
+                assigned.push(manager.get_next_key_for_affinity_excluding(hash, None, &[]).await.unwrap().id);
+            }
+            assigned
+        };
+        
+        let before = assign().await;
+        assert_eq!(before, assign().await);
+        let key3_share = before.iter().filter(|id| *id == "key3").count();
+        assert!((150..250).contains(&key3_share), "key3 share = {}", key3_share);
+        
+        // 禁用 key2：只有原本落在 key2 上的属性改变归属
+        manager.set_key_enabled("key2", false).await.unwrap();
+        let after = assign().await;
+        for (old, new) in before.iter().zip(&after) {
+            if old == "key2" {
+                assert_ne!(new, "key2");
+            } else {
+                assert_eq!(old, new);
+            }
+        }
+        
+        // 从密钥集合中移除 key2 后按新的总权重重建环：其余密钥的虚拟节点只增不减，
+        // 除 key2 的份额外只有新增节点接管的少量属性改变归属
+        manager.replace_keys(vec![
+            key("key1", 100),
+            key("key3", 200),
+            key("key4", 100),
+        ]).await;
+        let rebuilt = assign().await;
+        let moved = before.iter().zip(&rebuilt).filter(|(old, new)| old != new).count();
+        let key2_share = before.iter().filter(|id| *id == "key2").count();
+        assert!(moved <= key2_share + hashes.len() / 5, "moved = {}, key2 share = {}", moved, key2_share);
+    }
+
+    fn create_region_manager() -> UnifiedKeyManager {
+        let regional = |id: &str, region: &str| ApiKey {
+            region: Some(region.to_string()),
+            ..create_test_api_key(id, 100)
+        };
+        UnifiedKeyManager::new(vec![
+            regional("us-1", "us"),
+            regional("us-2", "us"),
+            regional("eu-1", "eu"),
+        ])
+        .with_region_preference(Some(RegionPreference {
+            latency_tolerance_ratio: 1.2,
+            latency_smoothing: 0.5,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_region_preference_follows_latency() {
+        let manager = create_region_manager();
+        manager.record_key_latency("us-1", Duration::from_millis(50)).await;
+        manager.record_key_latency("eu-1", Duration::from_millis(200)).await;
+        
+        // 流量集中在延迟最低的区域，并在区域内按权重分配
+        let mut selected: HashMap<String, usize> = HashMap::new();
+        for _ in 0..20 {
+            *selected.entry(manager.get_next_key().await.unwrap().id).or_default() += 1;
+        }
+        assert_eq!(selected.get("us-1"), Some(&10));
+        assert_eq!(selected.get("us-2"), Some(&10));
+        
+        // us 区域延迟升高后流量转移到 eu
+        for _ in 0..5 {
+            manager.record_key_latency("us-2", Duration::from_millis(800)).await;
+        }
+        assert!(manager.region_latency.read().await[&Some("us".to_string())] > 240.0);
+        for _ in 0..10 {
+            assert_eq!(manager.get_next_key().await.unwrap().id, "eu-1");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_region_preference_fails_over_when_region_unavailable() {
+        let manager = create_region_manager();
+        manager.record_key_latency("us-1", Duration::from_millis(50)).await;
+        manager.record_key_latency("eu-1", Duration::from_millis(200)).await;
+        
+        for key_id in ["us-1", "us-2"] {
+            for _ in 0..3 {
+                manager.mark_key_failed(key_id).await;
+            }
+        }
+        for _ in 0..5 {
+            assert_eq!(manager.get_next_key().await.unwrap().id, "eu-1");
+        }
+    }
+
+    fn create_model_preference_manager(flash_limit: u32) -> UnifiedKeyManager {
+        UnifiedKeyManager::new(vec![
+            ApiKey {
+                preferred_models: vec!["gemini-1.5-flash".to_string()],
+                ..create_limited_api_key("flash", 100, flash_limit)
+            },
+            create_limited_api_key("general", 100, 1000),
+        ])
+        .with_preferred_model_boost(3.0)
+    }
+
+    #[tokio::test]
+    async fn test_preferred_model_favors_matching_key() {
+        let manager = create_model_preference_manager(1000);
+        for _ in 0..40 {
+            manager.get_next_key_for_model(Some("gemini-1.5-flash")).await.unwrap();
+        }
+        // 优先密钥的权重放大 3 倍，按 3:1 分配
+        assert_eq!(requests_of(&manager, "flash").await, 30);
+        assert_eq!(requests_of(&manager, "general").await, 10);
+
+        // 其他模型仍按基础权重参与选择
+        let manager = create_model_preference_manager(1000);
+        for _ in 0..40 {
+            manager.get_next_key_for_model(Some("gemini-1.5-pro")).await.unwrap();
+        }
+        assert_eq!(requests_of(&manager, "flash").await, 20);
+        assert_eq!(requests_of(&manager, "general").await, 20);
+    }
+
+    #[tokio::test]
+    async fn test_preferred_model_still_uses_others_under_load() {
+        let manager = create_model_preference_manager(10);
+        for _ in 0..40 {
+            manager.get_next_key_for_model(Some("gemini-1.5-flash")).await.unwrap();
+        }
+        // 优先密钥达到每分钟限额后其余请求由其他密钥承担
+        assert_eq!(requests_of(&manager, "flash").await, 10);
+        assert_eq!(requests_of(&manager, "general").await, 30);
+    }
+
+    #[tokio::test]
+    async fn test_allowed_models_partition_key_pools() {
+        let restricted = |id: &str, model: &str| ApiKey {
+            allowed_models: vec![model.to_string()],
+            ..create_limited_api_key(id, 100, 1000)
+        };
+        let manager = UnifiedKeyManager::new(vec![
+            restricted("flash", "gemini-1.5-flash"),
+            restricted("pro", "gemini-1.5-pro"),
+            create_limited_api_key("general", 100, 1000),
+        ]);
+        for _ in 0..20 {
+            let key = manager.get_next_key_for_model(Some("gemini-1.5-flash")).await.unwrap();
+            assert_ne!(key.id, "pro");
+        }
+        // 未被任何密钥限定的模型只由不限制模型的密钥服务
+        for _ in 0..10 {
+            let key = manager.get_next_key_for_model(Some("gemini-2.0-flash")).await.unwrap();
+            assert_eq!(key.id, "general");
+        }
+        assert_eq!(requests_of(&manager, "pro").await, 0);
+
+        let availability = manager.model_availability().await;
+        assert_eq!(availability["gemini-1.5-pro"].total_keys, 2);
+        assert_eq!(availability["gemini-1.5-flash"].available_keys, 2);
+        assert_eq!(availability["*"].total_keys, 1);
+    }
+
+    fn adaptive_params() -> AdaptiveRateLimit {
+        AdaptiveRateLimit {
+            threshold: 2,
+            decrease_factor: 0.5,
+            increase_step: 10,
+            min_requests_per_minute: 5,
+            clean_period: Duration::from_secs(60),
+        }
+    }
+
+    async fn effective_limit(manager: &UnifiedKeyManager, key_id: &str) -> u32 {
+        manager
+            .get_key_states()
+            .await
+            .into_iter()
+            .find(|k| k.id == key_id)
+            .map(|k| k.effective_max_requests_per_minute())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_repeated_429s_reduce_effective_limit() {
+        let manager = UnifiedKeyManager::new(vec![create_limited_api_key("key1", 100, 100)])
+            .with_adaptive_rate_limit(Some(adaptive_params()));
+
+        // 单次 429 不下调，连续两次下调一半
+        manager.mark_key_failed_with_status("key1", 429).await;
+        assert_eq!(effective_limit(&manager, "key1").await, 100);
+        manager.mark_key_failed_with_status("key1", 429).await;
+        assert_eq!(effective_limit(&manager, "key1").await, 50);
+
+        // 其他错误不影响限额，下调不低于下限
+        manager.mark_key_failed_with_status("key1", 500).await;
+        assert_eq!(effective_limit(&manager, "key1").await, 50);
+        for _ in 0..20 {
+            manager.mark_key_failed_with_status("key1", 429).await;
+        }
+        assert_eq!(effective_limit(&manager, "key1").await, 5);
+
+        // 未启用时保持配置的限额
+        let fixed = UnifiedKeyManager::new(vec![create_limited_api_key("key1", 100, 100)]);
+        for _ in 0..4 {
+            fixed.mark_key_failed_with_status("key1", 429).await;
+        }
+        assert_eq!(effective_limit(&fixed, "key1").await, 100);
+    }
+
+    #[test]
+    fn test_clean_period_restores_effective_limit() {
+        let params = adaptive_params();
+        let mut key = UnifiedApiKey::from_api_key(create_limited_api_key("key1", 100, 30));
+        let start = Instant::now();
+        key.record_rate_limited(&params, start);
+        key.record_rate_limited(&params, start);
+        assert_eq!(key.effective_max_requests_per_minute(), 15);
+        key.runtime_state.current_requests = 15;
+        assert!(!key.is_available());
+
+        // 平稳期未满不上调
+        key.record_clean_request(&params, start + Duration::from_secs(30));
+        assert_eq!(key.effective_max_requests_per_minute(), 15);
+
+        // 每个平稳期加性上调，恢复到配置限额后取消下调
+        key.record_clean_request(&params, start + Duration::from_secs(60));
+        assert_eq!(key.effective_max_requests_per_minute(), 25);
+        assert!(key.is_available());
+        key.record_clean_request(&params, start + Duration::from_secs(120));
+        assert_eq!(key.effective_max_requests_per_minute(), 30);
+        assert_eq!(key.runtime_state.adaptive_limit, None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_window_survives_backward_clock_step() {
+        let manager = UnifiedKeyManager::new(vec![create_limited_api_key("key1", 100, 10)]);
+        {
+            let mut keys = manager.keys.write().await;
+            let key = &mut keys[0];
+            key.runtime_state.current_requests = 10;
+            // 系统时钟回拨一小时：上次重置的墙上时间位于"未来"
+            key.runtime_state.last_reset = Utc::now() + chrono::Duration::hours(1);
+            key.runtime_state.window_started = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        }
+
+        // 单调时钟上窗口已经结束，计数照常重置，密钥不会被锁定到时钟追上为止
+        assert_eq!(manager.get_next_key().await.unwrap().id, "key1");
+        assert_eq!(requests_of(&manager, "key1").await, 1);
+        assert!(manager.keys.read().await[0].runtime_state.last_reset <= Utc::now());
+
+        // 时钟回拨但窗口未结束时不提前重置
+        {
+            let mut keys = manager.keys.write().await;
+            keys[0].runtime_state.current_requests = 10;
+            keys[0].runtime_state.last_reset = Utc::now() + chrono::Duration::hours(1);
+        }
+        assert!(manager.get_next_key().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_key_cools_down_then_returns() {
+        let manager = create_pair_manager().with_rate_limit_cooldown(Some(RateLimitCooldown {
+            default_duration: Duration::from_millis(80),
+            max_duration: Duration::from_millis(150),
+        }));
+
+        // Retry-After 超过上限时截断；更短的冷却不会覆盖已有的冷却
+        assert_eq!(
+            manager.start_cooldown("key1", Some(Duration::from_secs(3600))).await,
+            Some(Duration::from_millis(150))
+        );
+        assert_eq!(manager.start_cooldown("key1", None).await, None);
+        let cooldowns = manager.cooldowns().await;
+        assert_eq!(cooldowns.len(), 1);
+        assert!(cooldowns["key1"] <= Duration::from_millis(150));
+
+        // 冷却期内不参与选择
+        assert_eq!(key1_selections(&manager, 10).await, 0);
+
+        // 冷却期结束后自动恢复
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(key1_selections(&manager, 10).await > 0);
+        assert!(manager.cooldowns().await.is_empty());
+        assert!(manager.keys.read().await[0].runtime_state.cooldown_until.is_none());
+
+        // 未启用冷却时忽略
+        let disabled = create_pair_manager();
+        assert_eq!(disabled.start_cooldown("key1", None).await, None);
+        assert!(disabled.keys.read().await[0].runtime_state.cooldown_until.is_none());
+    }
+}
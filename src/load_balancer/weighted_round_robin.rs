@@ -282,18 +282,11 @@ pub struct KeyWeightInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
 
     fn create_test_api_key(id: &str, weight: u32) -> ApiKey {
         ApiKey {
-            id: id.to_string(),
-            key: format!("test-key-{}", id),
             weight,
-            max_requests_per_minute: 60,
-            current_requests: 0,
-            last_reset: Utc::now(),
-            is_active: true,
-            failure_count: 0,
+            ..ApiKey::for_test(id)
         }
     }
 
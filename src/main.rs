@@ -1,19 +1,39 @@
 // src/main.rs
 use crate::auth::AuthHandler;
-use crate::config::ProxyConfig;
-use crate::load_balancer::{UnifiedKeyManager, key_manager::ApiKey};
+use crate::auth::client_keys::{generate_client_key, ClientKeyStore, FailureLimit};
+use crate::config::{AcmeChallengeType, LiveConfig, ProxyConfig};
+use crate::load_balancer::{AdaptiveRateLimit, FailurePenalty, RateLimitCooldown, RegionPreference, UnifiedKeyManager, key_manager::ApiKey};
+use crate::load_balancer::audit::{AuditConfig as WeightAuditConfig, WeightAuditSystem};
+use crate::load_balancer::key_source::{DirectoryKeySource, KeySourceWatcher};
+use crate::load_balancer::optimizer::{OptimizerConfig, WeightOptimizer};
 use crate::metrics::MetricsCollector;
 use crate::proxy::acme_service::{AcmeChallengeService, AcmeChallengeState};
 use crate::proxy::GeminiProxyService;
-use crate::utils::health_check::HealthChecker;
+use crate::proxy::body_limits::BodyLimits;
+use crate::proxy::dedup::DedupWindow;
+use crate::proxy::header_limits::HeaderLimits;
+use crate::proxy::inflight_limit::InflightLimiter;
+use crate::proxy::response_cache::ResponseCache;
+use crate::proxy::warm_pool::{TcpWarmConnector, UpstreamConnectionStats, WarmPool, WarmPoolService};
+use crate::utils::health_check::{BuildInfo, HealthChecker};
+use crate::utils::shutdown::{DrainingShutdown, ShutdownState};
+use crate::utils::startup::await_dependency;
+use crate::utils::upstream_probe::UpstreamProber;
 use crate::api::config::ConfigState;
 use crate::api::weight_management::WeightManagementState;
 use crate::utils::tls::{acme_renewal_loop, generate_self_signed_cert_if_not_exists};
 use crate::utils::performance::PerformanceOptimizer;
+use crate::error::dead_letter::DeadLetterStore;
+use crate::error::recovery::{create_production_recovery_manager, CacheClearer, ErrorRecoveryManager};
 use crate::utils::error::ErrorHandler;
-use chrono::Utc;
+use crate::persistence::StorageManager;
+use crate::persistence::config_history::{ConfigHistoryConfig, ConfigHistoryStore};
+use crate::security::{AuditConfig, AuditLogManager, SharedAuditLog};
+use crate::security::ip_rules::IpRules;
+use pingora::services::background::background_service;
 use pingora::proxy::http_proxy_service;
-use pingora::server::Server;
+use pingora::server::configuration::ServerConf;
+use pingora::server::{RunArgs, Server};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use tokio::runtime::Builder;
@@ -33,6 +53,37 @@ mod utils;
 fn main() {
     tracing_subscriber::fmt::init();
 
+    // `--validate-config <path>`：只验证配置文件并输出报告，不启动服务（供 CI 使用）
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(position) = args.iter().position(|arg| arg == "--validate-config") {
+        let Some(path) = args.get(position + 1) else {
+            eprintln!("用法: gemini-proxy --validate-config <path>");
+            std::process::exit(2);
+        };
+        std::process::exit(validate_config_file(path, &mut std::io::stdout()));
+    }
+
+    // `--hash-client-key <key_id>`：生成代理 API 密钥，输出交给客户端的密钥与写入 `auth.api_keys` 的哈希
+    if let Some(position) = args.iter().position(|arg| arg == "--hash-client-key") {
+        let Some(key_id) = args.get(position + 1) else {
+            eprintln!("用法: gemini-proxy --hash-client-key <key_id>");
+            std::process::exit(2);
+        };
+        match generate_client_key(key_id) {
+            Ok((client_key, secret_hash)) => {
+                println!("客户端密钥（只显示一次，通过 X-Proxy-Key 出示）: {}", client_key);
+                println!("auth.api_keys 配置:");
+                println!("  - id: \"{}\"", key_id);
+                println!("    secret_hash: \"{}\"", secret_hash);
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("生成代理 API 密钥失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     // 使用增强的配置加载，包含安全验证
     let config = match load_and_validate_config("config/proxy.yaml") {
         Ok(config) => config,
@@ -48,38 +99,162 @@ fn main() {
             .gemini
             .api_keys
             .iter()
-            .map(|k| ApiKey {
-                id: k.id.clone(),
-                key: k.key.clone(),
-                weight: k.weight,
-                max_requests_per_minute: k.max_requests_per_minute,
-                current_requests: 0,
-                last_reset: Utc::now(),
-                is_active: true,
-                failure_count: 0,
-            })
+            .map(ApiKey::from)
             .collect(),
-    ));
+    )
+    .with_stickiness_window_ms(config.gemini.key_stickiness_window_ms)
+    .with_soft_limit_ratio(config.gemini.key_soft_limit_ratio)
+    .with_disabled_keys(&config.gemini.disabled_keys)
+    .with_account_quota(config.gemini.account_quota_per_minute)
+    .with_weight_ramp_window_ms(config.gemini.weight_change.effective_ramp_window_ms())
+    .with_region_preference(RegionPreference::from_config(&config.gemini.region_routing))
+    .with_weight_bounds(config.gemini.weight_bounds)
+    .with_preferred_model_boost(config.gemini.preferred_model_boost)
+    .with_scheduling_mode((&config.gemini.scheduling).into())
+    .with_adaptive_rate_limit(AdaptiveRateLimit::from_config(&config.gemini.adaptive_rate_limit))
+    .with_rate_limit_cooldown(RateLimitCooldown::from_config(&config.gemini.key_cooldown))
+    .with_failure_penalty(FailurePenalty::from_config(&config.gemini.failure_penalty)));
+
+    // 代理运行配置，通过 POST /api/config/reload 热重载时整体替换；外部密钥来源从中读取静态密钥
+    let gemini_config = Arc::new(LiveConfig::new(config.gemini.clone()));
+
+    // 外部密钥来源（例如 Kubernetes Secret 挂载目录），定期刷新密钥集合
+    if let Some(key_source_config) = config.gemini.key_source.clone() {
+        let source = Arc::new(DirectoryKeySource::from_config(&key_source_config));
+        let watcher = KeySourceWatcher::new(source, key_manager.clone(), config.gemini.api_keys.clone())
+            .with_live_config(gemini_config.clone());
+        let interval = std::time::Duration::from_secs(key_source_config.refresh_interval_seconds);
+
+        // 启动时检查密钥来源：阻塞模式重试耗尽后退出，降级模式仅使用静态密钥继续启动并由后台刷新补齐
+        let startup_runtime = Builder::new_current_thread().enable_all().build().unwrap();
+        let startup = startup_runtime.block_on(await_dependency(
+            "key_source",
+            "外部密钥来源中的密钥暂不可用，仅使用静态配置的密钥",
+            &key_source_config.startup,
+            || watcher.refresh(),
+        ));
+        if let Err(e) = startup {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+
+        std::thread::spawn(move || {
+            let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(watcher.run(interval));
+        });
+    }
+
+    // 代理请求路径共享的审计日志
+    let mut audit_manager = AuditLogManager::new(AuditConfig {
+        file_output_enabled: true,
+        log_file_path: "logs/audit.log".to_string(),
+        event_id_prefix: config.audit.event_id_prefix.clone(),
+        reuse_request_id: config.audit.reuse_request_id,
+        ..AuditConfig::default()
+    });
+
+    // 写入失败的审计条目转入死信文件，后台定期重新投递
+    let dead_letter_config = &config.audit.dead_letter;
+    if dead_letter_config.enabled {
+        let dead_letter = Arc::new(DeadLetterStore::new(&dead_letter_config.path, dead_letter_config.max_entries));
+        audit_manager = audit_manager.with_dead_letter(dead_letter.clone());
+        let interval = std::time::Duration::from_secs(dead_letter_config.retry_interval_seconds.max(1));
+
+        std::thread::spawn(move || {
+            let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(dead_letter.run_retrier(interval));
+        });
+    }
+    let audit_log: SharedAuditLog = Arc::new(tokio::sync::Mutex::new(audit_manager));
+
+    // 持久化存储：数据目录不可用时记录审计日志，并按 on_unavailable_dir 退出或退化为仅内存存储
+    let storage = Arc::new(StorageManager::new(config.persistence.clone()).with_audit_log(audit_log.clone()));
+    let storage_runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    if let Err(e) = storage_runtime.block_on(storage.initialize()) {
+        tracing::error!("持久化存储初始化失败: {}", e);
+        std::process::exit(1);
+    }
 
-    let auth_handler = Arc::new(AuthHandler::new(
-        config.auth.jwt_secret.clone(),
-        config.auth.rate_limit_per_minute,
-    ));
+    let auth_handler = Arc::new(
+        AuthHandler::new(
+            config.auth.jwt_secret.clone(),
+            config.auth.rate_limit_per_minute,
+        )
+        .with_failure_mode(config.auth.failure_mode)
+        .with_verification_cache(
+            std::time::Duration::from_secs(config.auth.verification_cache_ttl_seconds),
+            config.auth.verification_cache_max_entries,
+        )
+        .with_audit_log(audit_log.clone())
+        .with_client_keys(Arc::new(
+            ClientKeyStore::from_config(&config.auth.api_keys)
+                .with_failure_limit(FailureLimit::from_auth_config(&config.auth)),
+        )),
+    );
     let metrics = Arc::new(MetricsCollector::new());
-    let gemini_config = Arc::new(config.gemini.clone());
+    metrics.set_key_labels(&config.gemini.api_keys);
     
-    // 初始化性能监控和错误处理
-    let performance_optimizer = Arc::new(PerformanceOptimizer::new(config.server.max_connections as u64));
+    // 初始化性能监控和错误处理，全进程在途请求上限与性能统计共享计数
+    let inflight_limiter = Arc::new(InflightLimiter::new(config.server.max_inflight_requests));
+    // 上游连接复用计数与预热池由代理和性能统计共享
+    let upstream_connections = Arc::new(UpstreamConnectionStats::new());
+    let warm_pool = config.gemini.warm_pool.enabled.then(|| {
+        Arc::new(WarmPool::new(
+            &config.gemini.warm_pool,
+            TcpWarmConnector::new(std::time::Duration::from_secs(config.gemini.timeout_seconds)),
+        ))
+    });
+    let performance_optimizer = PerformanceOptimizer::new(config.server.max_connections as u64)
+        .with_inflight_limiter(inflight_limiter.clone())
+        .with_upstream_connection_stats(upstream_connections.clone());
+    let performance_optimizer = Arc::new(match &warm_pool {
+        Some(warm_pool) => performance_optimizer.with_warm_pool(warm_pool.clone()),
+        None => performance_optimizer,
+    });
     let error_handler = Arc::new(ErrorHandler::new(1000));
+    // 停机状态由代理、健康检查与信号处理共享
+    let shutdown_state = Arc::new(ShutdownState::new());
+    // 配置管理由管理 API 与自愈操作（重新加载配置）共享
+    let config_state = ConfigState::new(config.clone(), "config/proxy.yaml".to_string())
+        .with_metrics(metrics.clone())
+        .with_key_manager(key_manager.clone())
+        .with_live_gemini_config(gemini_config.clone());
+    // 响应缓存按启动时的配置创建，由代理、自愈清理与缓存管理接口共享
+    let response_cache = config
+        .gemini
+        .response_cache
+        .enabled
+        .then(|| Arc::new(ResponseCache::new(&config.gemini.response_cache)));
+    let caches: Vec<Arc<dyn CacheClearer>> = response_cache
+        .iter()
+        .map(|cache| cache.clone() as Arc<dyn CacheClearer>)
+        .collect();
+    // 按密钥维护熔断器状态，代理与管理 API 共享
+    let circuit_breaker = &config.gemini.circuit_breaker;
+    let recovery_manager = caches.iter().fold(
+        create_production_recovery_manager()
+            .with_circuit_breaker(
+                circuit_breaker.failure_threshold,
+                std::time::Duration::from_secs(circuit_breaker.recovery_timeout_seconds),
+                circuit_breaker.half_open_max_calls,
+            )
+            .with_config_reloader(Arc::new(config_state.clone())),
+        |manager, cache| manager.with_cache_clearer(cache.clone()),
+    );
+    let recovery_manager = Arc::new(recovery_manager);
 
     if config.metrics.enabled {
         let metrics_clone = metrics.clone();
         let metrics_port = config.metrics.prometheus_port;
         let total_keys = config.gemini.api_keys.len();
-        let config_state = ConfigState::new(config.clone(), "config/proxy.yaml".to_string());
         let performance_optimizer_clone = performance_optimizer.clone();
         let error_handler_clone = error_handler.clone();
         let key_manager_clone = key_manager.clone();
+        let recovery_manager_clone = recovery_manager.clone();
+        let audit_log_clone = audit_log.clone();
+        let storage_clone = storage.clone();
+        let auth_handler_clone = auth_handler.clone();
+        let shutdown_state_clone = shutdown_state.clone();
         
         std::thread::spawn(move || {
             let runtime = Builder::new_current_thread().enable_all().build().unwrap();
@@ -91,13 +266,22 @@ fn main() {
                     config_state,
                     performance_optimizer_clone,
                     error_handler_clone,
-                    key_manager_clone
+                    key_manager_clone,
+                    recovery_manager_clone,
+                    audit_log_clone,
+                    storage_clone,
+                    auth_handler_clone,
+                    caches,
+                    shutdown_state_clone
                 ).await;
             });
         });
     }
 
-    let mut server = Server::new(None).unwrap();
+    // 在途请求由 DrainingShutdown 排空，Pingora 收到停机通知后无需再额外等待
+    let mut server_conf = ServerConf::new().unwrap();
+    server_conf.grace_period_seconds = Some(0);
+    let mut server = Server::new_with_opt_and_conf(None, server_conf);
     server.bootstrap();
 
     if config.server.tls.enabled {
@@ -106,13 +290,16 @@ fn main() {
             if acme_config.enabled {
                 let challenge_state: AcmeChallengeState = Arc::new(RwLock::new(HashMap::new()));
 
-                let acme_challenge_service = AcmeChallengeService {
-                    challenge_state: challenge_state.clone(),
-                };
-                let mut acme_http_service =
-                    http_proxy_service(&server.configuration, acme_challenge_service);
-                acme_http_service.add_tcp("0.0.0.0:80");
-                server.add_service(acme_http_service);
+                // HTTP-01 需要在 80 端口响应验证请求，DNS-01 不监听 80 端口
+                if acme_config.challenge == AcmeChallengeType::Http01 {
+                    let acme_challenge_service = AcmeChallengeService {
+                        challenge_state: challenge_state.clone(),
+                    };
+                    let mut acme_http_service =
+                        http_proxy_service(&server.configuration, acme_challenge_service);
+                    acme_http_service.add_tcp("0.0.0.0:80");
+                    server.add_service(acme_http_service);
+                }
 
                 let acme_conf_clone = acme_config.clone();
                 let cert_path_clone = tls_config.cert_path.clone();
@@ -137,12 +324,43 @@ fn main() {
         }
     }
 
+    // 客户端 IP 访问规则（配置验证已检查 CIDR 格式）
+    let ip_rules = match IpRules::from_config(&config.security.ip_rules) {
+        Ok(ip_rules) => ip_rules,
+        Err(e) => {
+            tracing::error!("IP 访问规则无效: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     let service = GeminiProxyService::new(
         key_manager, 
         auth_handler, 
         metrics.clone(), 
-        gemini_config
-    );
+        gemini_config.clone()
+    )
+    .with_audit_log(audit_log.clone())
+    .with_recovery_manager(recovery_manager)
+    .with_header_limits(HeaderLimits::from_server_config(&config.server))
+    .with_body_limits(BodyLimits::from_server_config(&config.server))
+    .with_inflight_limiter(inflight_limiter.clone())
+    .with_ip_rules(ip_rules)
+    .with_shutdown_state(shutdown_state.clone())
+    .with_upstream_connection_stats(upstream_connections);
+    let service = match response_cache {
+        Some(response_cache) => service.with_response_cache(response_cache),
+        None => service,
+    };
+    // 去重窗口按启动时的配置创建
+    let service = if config.gemini.dedup.enabled {
+        service.with_dedup_window(Arc::new(DedupWindow::new(&config.gemini.dedup)))
+    } else {
+        service
+    };
+    let service = match warm_pool.clone() {
+        Some(warm_pool) => service.with_warm_pool(warm_pool),
+        None => service,
+    };
     let mut proxy_service = http_proxy_service(&server.configuration, service);
     let addr = format!("{}:{}", config.server.host, config.server.port);
 
@@ -154,8 +372,23 @@ fn main() {
         proxy_service.add_tcp(&addr);
     }
     server.add_service(proxy_service);
+    // 预热池在服务器运行时中维持上游连接，随服务器停机退出
+    if let Some(warm_pool) = warm_pool {
+        server.add_service(background_service("upstream warm pool", WarmPoolService::new(warm_pool, gemini_config)));
+    }
 
-    server.run_forever();
+    // SIGTERM/SIGINT：排空在途请求、落盘审计日志后退出
+    let shutdown = DrainingShutdown::new(
+        shutdown_state,
+        inflight_limiter,
+        std::time::Duration::from_secs(config.server.shutdown_grace_period_seconds),
+    )
+    .with_audit_log(audit_log);
+    server.run(RunArgs {
+        shutdown_signal: Box::new(shutdown),
+    });
+    tracing::info!("服务已停止");
+    std::process::exit(0);
 }
 
 async fn start_api_server(
@@ -166,16 +399,75 @@ async fn start_api_server(
     performance_optimizer: Arc<PerformanceOptimizer>,
     error_handler: Arc<ErrorHandler>,
     key_manager: Arc<UnifiedKeyManager>,
+    recovery_manager: Arc<ErrorRecoveryManager>,
+    audit_log: SharedAuditLog,
+    storage: Arc<StorageManager>,
+    auth_handler: Arc<AuthHandler>,
+    caches: Vec<Arc<dyn CacheClearer>>,
+    shutdown_state: Arc<ShutdownState>,
 ) {
     use warp::Filter;
     
+    // 获取 API 服务器的配置
+    let api_config = config_state.get_config().await;
+    // 要求 TLS 时拒绝以明文 HTTP 提供 API（启动时的配置验证已拒绝这种配置，这里防止绕过验证启动）
+    if api_config.metrics.require_tls && !api_config.metrics.api_tls_enabled() {
+        tracing::error!("metrics.require_tls 已启用但 API 服务器未启用 TLS，拒绝启动 API 服务器");
+        return;
+    }
+
     // Setup health checker
-    let health_checker = HealthChecker::new(total_keys, total_keys, true);
+    let mut health_checker = HealthChecker::new(total_keys, total_keys, true)
+        .with_min_healthy_keys(api_config.gemini.min_healthy_keys)
+        .with_shutdown_state(shutdown_state);
+    if api_config.health.verbose {
+        health_checker = health_checker.with_build_info(BuildInfo::from_config(&api_config));
+    }
+    // 上游连通性探测在后台定期执行，就绪检查读取最近一次结果
+    if api_config.health.upstream_probe.enabled {
+        let prober = Arc::new(UpstreamProber::new(
+            &api_config.health.upstream_probe,
+            &api_config.gemini.base_url,
+            key_manager.clone(),
+        ));
+        tokio::spawn(prober.clone().run());
+        health_checker = health_checker.with_upstream_probe(prober);
+    }
     let health_checker = Arc::new(health_checker);
     
     // Metrics route
+    let metrics_clone = metrics.clone();
     let metrics_route = warp::path("metrics")
-        .map(move || metrics.get_metrics());
+        .map(move || warp::reply::with_header(metrics_clone.get_metrics(), "content-type", "text/plain; version=0.0.4"));
+    
+    // 就绪检查路由：健康密钥数低于阈值时返回 503
+    let key_health_state = crate::api::key_health::KeyHealthState::new(key_manager.clone())
+        .with_recovery_manager(recovery_manager.clone());
+    let readiness_checker = health_checker.clone();
+    let readiness_key_health = key_health_state.clone();
+    let health_ready_route = warp::path!("health" / "ready")
+        .and(warp::get())
+        .and_then(move || {
+            let checker = readiness_checker.clone();
+            let key_health = readiness_key_health.clone();
+            async move {
+                let readiness = checker.check_readiness(key_health.ready_key_count().await);
+                let status = if readiness.ready {
+                    warp::http::StatusCode::OK
+                } else {
+                    warp::http::StatusCode::SERVICE_UNAVAILABLE
+                };
+                Result::<_, warp::Rejection>::Ok(warp::reply::with_status(
+                    warp::reply::json(&readiness),
+                    status,
+                ))
+            }
+        });
+    
+    // 存活检查路由：只反映进程是否在运行，不检查密钥与上游
+    let health_live_route = warp::path!("health" / "live")
+        .and(warp::get())
+        .map(|| warp::reply::json(&serde_json::json!({ "status": "alive" })));
     
     // Health check route
     let health_checker_clone = health_checker.clone();
@@ -185,11 +477,16 @@ async fn start_api_server(
             let checker = health_checker_clone.clone();
             async move {
                 let health_status = checker.check_health().await;
+                // 停机排空时返回 503，负载均衡据此摘除实例
+                let status = if checker.is_draining() {
+                    warp::http::StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    warp::http::StatusCode::OK
+                };
                 let json = serde_json::to_string(&health_status).unwrap();
-                Result::<_, warp::Rejection>::Ok(warp::reply::with_header(
-                    json,
-                    "content-type",
-                    "application/json",
+                Result::<_, warp::Rejection>::Ok(warp::reply::with_status(
+                    warp::reply::with_header(json, "content-type", "application/json"),
+                    status,
                 ))
             }
         });
@@ -211,15 +508,19 @@ async fn start_api_server(
             }
         });
 
-    // 错误统计路由  
+    // 错误统计路由（附带各密钥的熔断器状态）
     let error_handler_clone = error_handler.clone();
+    let errors_recovery_manager = recovery_manager.clone();
     let errors_route = warp::path("errors")
         .and(warp::get())
         .and_then(move || {
             let handler = error_handler_clone.clone();
+            let recovery_manager = errors_recovery_manager.clone();
             async move {
                 let stats = handler.get_error_statistics().await;
-                let json = serde_json::to_string(&stats).unwrap();
+                let mut body = serde_json::to_value(&stats).unwrap();
+                body["circuit_breakers"] = serde_json::to_value(recovery_manager.circuit_snapshots().await).unwrap();
+                let json = body.to_string();
                 Result::<_, warp::Rejection>::Ok(warp::reply::with_header(
                     json,
                     "content-type",
@@ -228,35 +529,106 @@ async fn start_api_server(
             }
         });
     
-    // 获取 API 服务器的配置
-    let api_config = config_state.get_config().await;
-    
     // 配置API路由
     let config_routes = crate::api::config::config_routes(config_state.clone());
     
-    // 权重管理路由
-    let weight_state = WeightManagementState::new(config_state);
+    // 权重管理路由（权重快照与回滚的审计记录同时出现在统一审计检索中，回滚写入配置历史）
+    let weight_audit = Arc::new(tokio::sync::RwLock::new(WeightAuditSystem::new(WeightAuditConfig::default())));
+    let config_history = Arc::new(ConfigHistoryStore::new(storage.effective_config(), ConfigHistoryConfig::default()));
+    if let Err(e) = config_history.initialize().await {
+        tracing::warn!("配置历史存储初始化失败: {}", e);
+    }
+    let weight_state = WeightManagementState::new(config_state.clone())
+        .with_weight_audit(weight_audit.clone())
+        .with_config_history(config_history);
     weight_state.set_key_manager(key_manager.clone()).await;
     let weight_routes = crate::api::weight_management::weight_management_routes(weight_state);
     
+    // 密钥健康报告路由
+    let key_health_routes = crate::api::key_health::key_health_routes(key_health_state.clone());
+    
     // 负载均衡统计路由
-    let stats_state = crate::api::load_balancing_stats::StatsState::new(Some(key_manager));
+    let stats_state = crate::api::load_balancing_stats::StatsState::new(Some(key_manager.clone()))
+        .with_metrics(metrics.clone())
+        .with_recovery_manager(recovery_manager.clone())
+        .with_business_metrics(api_config.metrics.business.clone());
+    let key_metrics_routes = crate::api::key_metrics::key_metrics_routes(stats_state.clone());
     let stats_routes = crate::api::load_balancing_stats::load_balancing_stats_routes(stats_state);
     
     // 认证路由 (暂时保持原有结构，计划重构到 /api/v1/auth/*)
-    let auth_state = crate::api::auth::AuthState::new(Arc::new(api_config.clone()));
+    let auth_state = crate::api::auth::AuthState::new(Arc::new(api_config.clone()))
+        .with_audit_log(audit_log.clone())
+        .with_metrics(metrics.clone());
     let auth_routes = crate::api::auth::auth_routes(auth_state.clone());
     
+    // 诊断支持包路由（仅管理员）
+    let support_bundle_state = crate::api::support_bundle::SupportBundleState::new(
+        config_state.clone(),
+        key_health_state.clone(),
+        error_handler.clone(),
+    )
+    .with_recovery_manager(recovery_manager)
+    .with_storage(storage);
+    let support_bundle_routes =
+        crate::api::support_bundle::support_bundle_routes(support_bundle_state, auth_state.clone());
+    
+    // 密钥启用/停用路由（仅管理员）
+    let key_control_state = crate::api::key_control::KeyControlState::new(key_manager, config_state.clone())
+        .with_audit_log(audit_log.clone());
+    let key_control_routes =
+        crate::api::key_control::key_control_routes(key_control_state, auth_state.clone());
+    
+    // 权重优化器配置路由（更新仅管理员）
+    let optimizer = Arc::new(tokio::sync::RwLock::new(WeightOptimizer::new(OptimizerConfig::default())));
+    let optimizer_config_state = crate::api::optimizer_config::OptimizerConfigState::new(optimizer)
+        .with_audit_log(audit_log.clone());
+    let optimizer_config_routes =
+        crate::api::optimizer_config::optimizer_config_routes(optimizer_config_state, auth_state.clone());
+    
+    // 缓存管理路由（仅管理员）
+    let cache_state = caches
+        .into_iter()
+        .fold(crate::api::cache::CacheState::new(), |state, cache| state.with_cache(cache));
+    let cache_routes = crate::api::cache::cache_routes(cache_state, auth_state.clone());
+    
+    // 客户端令牌吊销路由（仅管理员）
+    let client_token_state = crate::api::client_tokens::ClientTokenState::new(auth_handler)
+        .with_audit_log(audit_log.clone());
+    let client_token_routes = crate::api::client_tokens::client_token_routes(client_token_state, auth_state.clone());
+    
+    // 统一审计检索路由（仅管理员）
+    let audit_search_state = crate::api::audit_search::AuditSearchState::new()
+        .with_audit_log(audit_log.clone())
+        .with_weight_audit(weight_audit);
+    let audit_search_routes = crate::api::audit_search::audit_search_routes(audit_search_state, auth_state.clone());
+    
     // API路由 (暂时移除认证保护以解决404问题)
     let business_api_routes = config_routes
         .or(weight_routes)
+        .or(key_health_routes)
+        .or(key_metrics_routes)
+        .or(key_control_routes)
+        .or(optimizer_config_routes)
+        .or(cache_routes)
+        .or(client_token_routes)
+        .or(audit_search_routes)
+        .or(support_bundle_routes)
         .or(stats_routes);
     
-    let api_routes = warp::path("api")
-        .and(business_api_routes);
+    // 敏感端点的只读访问审计
+    let read_audit_state = crate::api::read_audit::ReadAuditState::new(api_config.audit.read_access.clone(), audit_log)
+        .with_auth_state(auth_state.clone());
+    let api_routes = crate::api::read_audit::with_read_audit(
+        warp::path("api")
+            .and(crate::api::auth::route_policy(auth_state.clone()))
+            .and(business_api_routes),
+        read_audit_state,
+    );
     
     // 组合所有路由 - 暂时移除认证保护
     let routes = metrics_route
+        .or(health_live_route)
+        .or(health_ready_route)
         .or(health_route)
         .or(performance_route)
         .or(errors_route)
@@ -276,9 +648,9 @@ async fn start_api_server(
             ).expect("Failed to generate API server certificate");
             
             tracing::info!("API server running on https://127.0.0.1:{} (HTTPS)", port);
-            tracing::info!("Business APIs: /api/config/*, /api/weights/*, /api/stats/* (暂时无认证)");
+            tracing::info!("Business APIs: /api/config/*, /api/weights/*, /api/keys/*, /api/optimizer/config, /api/tokens/*, /api/stats/* (密钥启用/停用、优化器配置更新、令牌吊销与支持包需管理员令牌)");
             tracing::info!("Auth APIs: /auth/* (JWT获取和刷新)");
-            tracing::info!("Monitor APIs: /metrics, /health, /performance, /errors (无需认证)");
+            tracing::info!("Monitor APIs: /metrics, /health, /health/live, /health/ready, /performance, /errors (无需认证)");
             
             warp::serve(routes)
                 .tls()
@@ -288,23 +660,73 @@ async fn start_api_server(
                 .await;
         } else {
             tracing::info!("API server running on http://127.0.0.1:{} (HTTP)", port);
-            tracing::info!("Business APIs: /api/config/*, /api/weights/*, /api/stats/* (暂时无认证)");
+            tracing::info!("Business APIs: /api/config/*, /api/weights/*, /api/keys/*, /api/optimizer/config, /api/tokens/*, /api/stats/* (密钥启用/停用、优化器配置更新、令牌吊销与支持包需管理员令牌)");
             tracing::info!("Auth APIs: /auth/* (JWT获取和刷新)");
-            tracing::info!("Monitor APIs: /metrics, /health, /performance, /errors (无需认证)");
+            tracing::info!("Monitor APIs: /metrics, /health, /health/live, /health/ready, /performance, /errors (无需认证)");
             warp::serve(routes).run(([127, 0, 0, 1], port)).await;
         }
     } else {
         tracing::info!("API server running on http://127.0.0.1:{} (HTTP)", port);
-        tracing::info!("Business APIs: /api/config/*, /api/weights/*, /api/stats/* (暂时无认证)");
+        tracing::info!("Business APIs: /api/config/*, /api/weights/*, /api/keys/*, /api/optimizer/config, /api/tokens/*, /api/stats/* (密钥启用/停用、优化器配置更新、令牌吊销与支持包需管理员令牌)");
         tracing::info!("Auth APIs: /auth/* (JWT获取和刷新)");
-        tracing::info!("Monitor APIs: /metrics, /health, /performance, /errors (无需认证)");
+        tracing::info!("Monitor APIs: /metrics, /health, /health/live, /health/ready, /performance, /errors (无需认证)");
         warp::serve(routes).run(([127, 0, 0, 1], port)).await;
     }
 }
 
+/// 运行完整的配置验证与安全检查并输出报告，返回进程退出码：
+/// 无法加载、存在验证错误或严重安全问题时为 1，否则为 0
+fn validate_config_file(config_path: &str, out: &mut impl std::io::Write) -> i32 {
+    use crate::security::{SecurityConfigValidator, ThreatLevel};
+    use crate::config::validation::ConfigValidator;
+
+    let config = match ProxyConfig::parse_file(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            let _ = writeln!(out, "❌ 配置文件加载失败: {}", e);
+            return 1;
+        }
+    };
+
+    let validation_errors = ConfigValidator::collect_errors(&config);
+    let security_report = SecurityConfigValidator::audit_security(&config);
+    let summary = &security_report.summary;
+
+    let _ = writeln!(out, "配置文件: {}", config_path);
+    let _ = writeln!(out, "配置验证: 发现 {} 个错误", validation_errors.len());
+    for error in &validation_errors {
+        let _ = writeln!(out, "  - {}: {}", error.field, error.message);
+    }
+    let _ = writeln!(out, "安全评分: {}/100", security_report.security_score);
+    let _ = writeln!(out, "发现 {} 个安全问题 (严重:{}, 高:{}, 中:{}, 低:{})",
+        summary.total_issues,
+        summary.critical_issues,
+        summary.high_issues,
+        summary.medium_issues,
+        summary.low_issues
+    );
+    for issue in &security_report.issues {
+        let _ = writeln!(out, "  - [{}] {} ({}): {}",
+            issue.threat_level.label(),
+            issue.description,
+            issue.affected_field,
+            issue.remediation
+        );
+    }
+
+    let critical = security_report.issues.iter().any(|i| i.threat_level == ThreatLevel::Critical);
+    if validation_errors.is_empty() && !critical {
+        let _ = writeln!(out, "✅ 配置有效");
+        0
+    } else {
+        let _ = writeln!(out, "❌ 配置无效");
+        1
+    }
+}
+
 /// 加载并验证配置的安全性
 fn load_and_validate_config(config_path: &str) -> Result<ProxyConfig, String> {
-    use crate::security::{SecurityConfigValidator, AuditLogManager, AuditConfig};
+    use crate::security::SecurityConfigValidator;
     use crate::config::validation::ConfigValidator;
     
     // 1. 基础配置加载
@@ -338,12 +760,7 @@ fn load_and_validate_config(config_path: &str) -> Result<ProxyConfig, String> {
                 for issue in &security_report.issues {
                     if matches!(issue.threat_level, crate::security::ThreatLevel::High | crate::security::ThreatLevel::Medium) {
                         tracing::warn!("- [{}] {}: {}", 
-                            match issue.threat_level {
-                                crate::security::ThreatLevel::Critical => "严重",
-                                crate::security::ThreatLevel::High => "高",
-                                crate::security::ThreatLevel::Medium => "中",
-                                crate::security::ThreatLevel::Low => "低",
-                            },
+                            issue.threat_level.label(),
                             issue.description, 
                             issue.remediation
                         );
@@ -388,3 +805,55 @@ fn load_and_validate_config(config_path: &str) -> Result<ProxyConfig, String> {
     tracing::info!("✅ 配置加载和安全验证完成");
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// 写入临时配置文件并运行验证，返回退出码与报告
+    fn validate_yaml(yaml: &str) -> (i32, String) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("proxy.yaml");
+        std::fs::write(&path, yaml).unwrap();
+        let mut out = Vec::new();
+        let code = validate_config_file(path.to_str().unwrap(), &mut out);
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_validate_config_accepts_good_config() {
+        let (code, report) = validate_yaml(
+            r#"
+gemini:
+  api_keys:
+    - id: "primary"
+      key: "valid-test-key-12345678901234567890"
+"#,
+        );
+        assert_eq!(code, 0, "{}", report);
+        assert!(report.contains("配置验证: 发现 0 个错误"));
+        // 非严重的安全问题只报告，不影响退出码
+        assert!(report.contains("auth.enabled"));
+    }
+
+    #[test]
+    fn test_validate_config_rejects_bad_config() {
+        let (code, report) = validate_yaml(
+            r#"
+server:
+  port: 0
+gemini:
+  api_keys:
+    - id: "primary"
+      key: "your-example-api-key"
+"#,
+        );
+        assert_eq!(code, 1);
+        assert!(report.contains("server.port"), "{}", report);
+        assert!(report.contains("[严重]"), "{}", report);
+
+        let mut out = Vec::new();
+        assert_eq!(validate_config_file("/nonexistent/proxy.yaml", &mut out), 1);
+    }
+}
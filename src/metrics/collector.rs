@@ -1,12 +1,48 @@
 // src/metrics/collector.rs
-use prometheus::{CounterVec, Encoder, HistogramVec, Opts, Registry, TextEncoder};
-use std::sync::{Arc, Mutex};
+use prometheus::{
+    CounterVec, IntCounter, IntCounterVec, Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntGauge, Opts,
+    IntGaugeVec, Registry, TextEncoder,
+};
+use crate::config::ApiKeyConfig;
+use crate::metrics::key_activity::{KeyActivitySnapshot, KeyActivityTracker};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 
+/// 请求耗时分桶：覆盖 LLM 调用常见的数十毫秒到一分钟
+const LATENCY_BUCKETS: [f64; 14] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 3.0, 5.0, 10.0, 15.0, 20.0, 30.0, 45.0, 60.0];
+
 pub struct MetricsCollector {
     registry: Registry,
     request_count: CounterVec,
     response_time: HistogramVec,
+    time_to_first_byte: HistogramVec,
+    stream_duration: Histogram,
+    request_duration: HistogramVec,
+    upstream_duration: HistogramVec,
+    active_sessions: IntGauge,
+    session_age: Histogram,
+    estimated_cost: CounterVec,
+    request_tokens: IntCounterVec,
+    prompt_tokens: IntCounterVec,
+    completion_tokens: IntCounterVec,
+    malformed_upstream: IntCounter,
+    upstream_soft_failures: IntCounterVec,
+    gemini_errors: IntCounterVec,
+    in_flight: IntGauge,
+    client_cancelled: IntCounter,
+    requests_by_class: CounterVec,
+    config_reloads: IntCounterVec,
+    config_reload_timestamp: Gauge,
+    failover_retries: IntCounterVec,
+    failover_recovered: IntCounter,
+    key_requests: IntCounterVec,
+    key_errors: IntCounterVec,
+    key_latency: HistogramVec,
+    key_info: IntGaugeVec,
+    /// 密钥 ID 到名称与说明的映射，随配置加载更新
+    key_labels: RwLock<HashMap<String, KeyLabels>>,
+    key_activity: KeyActivityTracker,
     data: Arc<Mutex<()>>, // Dummy data for thread safety marker
 }
 
@@ -24,13 +60,207 @@ impl MetricsCollector {
             .subsystem("proxy");
         let response_time = HistogramVec::new(response_time_opts.into(), &["status_code"]).unwrap();
 
+        let time_to_first_byte_opts = HistogramOpts::new(
+            "time_to_first_byte_seconds",
+            "Time from request start to the first response body byte",
+        )
+        .namespace("gemini_proxy")
+        .subsystem("proxy");
+        let time_to_first_byte = HistogramVec::new(time_to_first_byte_opts, &["streaming"]).unwrap();
+
+        // 流式响应可能持续数分钟
+        let stream_duration_opts = HistogramOpts::new("stream_duration_seconds", "Total duration of streaming (SSE) responses")
+            .namespace("gemini_proxy")
+            .subsystem("proxy")
+            .buckets(vec![0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0]);
+        let stream_duration = Histogram::with_opts(stream_duration_opts).unwrap();
+
+        let request_duration_opts = HistogramOpts::new(
+            "request_duration_seconds",
+            "End-to-end request duration observed by the client, until the response is fully sent",
+        )
+        .namespace("gemini_proxy")
+        .buckets(LATENCY_BUCKETS.to_vec());
+        let request_duration = HistogramVec::new(request_duration_opts, &["streaming"]).unwrap();
+
+        let upstream_duration_opts = HistogramOpts::new(
+            "upstream_duration_seconds",
+            "Duration of each Gemini upstream call, from connecting until the response body ends",
+        )
+        .namespace("gemini_proxy")
+        .buckets(LATENCY_BUCKETS.to_vec());
+        let upstream_duration = HistogramVec::new(upstream_duration_opts, &["streaming"]).unwrap();
+
+        let active_sessions_opts = Opts::new("active", "Number of active sessions")
+            .namespace("gemini_proxy")
+            .subsystem("session");
+        let active_sessions = IntGauge::with_opts(active_sessions_opts).unwrap();
+
+        // 会话年龄分桶：1分钟 ~ 1天
+        let session_age_opts = HistogramOpts::new("age_seconds", "Session age observed on refresh and expiry")
+            .namespace("gemini_proxy")
+            .subsystem("session")
+            .buckets(vec![60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 14400.0, 43200.0, 86400.0]);
+        let session_age = Histogram::with_opts(session_age_opts).unwrap();
+
+        let estimated_cost_opts = Opts::new("estimated_cost_total", "Estimated upstream cost based on the model cost table")
+            .namespace("gemini");
+        let estimated_cost = CounterVec::new(estimated_cost_opts, &["key_id"]).unwrap();
+
+        let request_tokens_opts = Opts::new("request_tokens_total", "Tokens used per caller, key and model")
+            .namespace("gemini");
+        let request_tokens = IntCounterVec::new(request_tokens_opts, &["caller", "key_id", "model", "kind"]).unwrap();
+
+        // 按密钥与模型的令牌用量（不含调用方标签，用于成本分摊）
+        let prompt_tokens_opts = Opts::new("prompt_tokens_total", "Prompt tokens reported by upstream usageMetadata per key and model")
+            .namespace("gemini_proxy");
+        let prompt_tokens = IntCounterVec::new(prompt_tokens_opts, &["key_id", "model"]).unwrap();
+
+        let completion_tokens_opts = Opts::new(
+            "completion_tokens_total",
+            "Completion (candidates) tokens reported by upstream usageMetadata per key and model",
+        )
+        .namespace("gemini_proxy");
+        let completion_tokens = IntCounterVec::new(completion_tokens_opts, &["key_id", "model"]).unwrap();
+
+        let malformed_upstream_opts = Opts::new("malformed_upstream_total", "Upstream responses whose body could not be parsed")
+            .namespace("gemini");
+        let malformed_upstream = IntCounter::with_opts(malformed_upstream_opts).unwrap();
+
+        let upstream_soft_failures_opts = Opts::new(
+            "upstream_soft_failure_total",
+            "Upstream 200 responses treated as failures (empty, error-shaped or blocked body)",
+        )
+        .namespace("gemini");
+        let upstream_soft_failures = IntCounterVec::new(upstream_soft_failures_opts, &["reason"]).unwrap();
+
+        let gemini_errors_opts = Opts::new("errors_total", "Upstream Gemini errors by normalized error code")
+            .namespace("gemini");
+        let gemini_errors = IntCounterVec::new(gemini_errors_opts, &["code"]).unwrap();
+
+        let in_flight_opts = Opts::new("in_flight_requests", "Number of requests currently being proxied")
+            .namespace("gemini_proxy")
+            .subsystem("proxy");
+        let in_flight = IntGauge::with_opts(in_flight_opts).unwrap();
+
+        let client_cancelled_opts = Opts::new("client_cancelled_total", "Requests aborted because the client disconnected")
+            .namespace("gemini");
+        let client_cancelled = IntCounter::with_opts(client_cancelled_opts).unwrap();
+
+        let requests_by_class_opts = Opts::new("requests_by_class_total", "Total number of requests per traffic class")
+            .namespace("gemini_proxy")
+            .subsystem("proxy");
+        let requests_by_class = CounterVec::new(requests_by_class_opts, &["traffic_class"]).unwrap();
+
+        let config_reloads_opts = Opts::new("config_reload_total", "Configuration reloads by result")
+            .namespace("gemini_proxy");
+        let config_reloads = IntCounterVec::new(config_reloads_opts, &["result"]).unwrap();
+
+        let config_reload_timestamp_opts = Opts::new(
+            "config_reload_timestamp",
+            "Unix timestamp of the last successful configuration reload",
+        )
+        .namespace("gemini_proxy");
+        let config_reload_timestamp = Gauge::with_opts(config_reload_timestamp_opts).unwrap();
+
+        let failover_retries_opts = Opts::new(
+            "failover_retries_total",
+            "In-request retries on a different API key, by the upstream status that triggered them",
+        )
+        .namespace("gemini_proxy")
+        .subsystem("proxy");
+        let failover_retries = IntCounterVec::new(failover_retries_opts, &["status_code"]).unwrap();
+
+        let failover_recovered_opts = Opts::new(
+            "failover_recovered_total",
+            "Requests that succeeded after at least one in-request key failover",
+        )
+        .namespace("gemini_proxy")
+        .subsystem("proxy");
+        let failover_recovered = IntCounter::with_opts(failover_recovered_opts).unwrap();
+
+        // 按密钥统计的上游调用：标签只使用密钥 ID，不能使用密钥本身
+        let key_requests_opts = Opts::new("key_requests_total", "Upstream calls made with each API key")
+            .namespace("gemini_proxy");
+        let key_requests = IntCounterVec::new(key_requests_opts, &["key_id"]).unwrap();
+
+        let key_errors_opts = Opts::new(
+            "key_errors_total",
+            "Failed upstream calls per API key, by upstream status or transport error",
+        )
+        .namespace("gemini_proxy");
+        let key_errors = IntCounterVec::new(key_errors_opts, &["key_id", "status"]).unwrap();
+
+        let key_latency_opts = HistogramOpts::new(
+            "key_latency_seconds",
+            "Upstream latency per API key, from connecting to receiving the response header",
+        )
+        .namespace("gemini_proxy")
+        .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]);
+        let key_latency = HistogramVec::new(key_latency_opts, &["key_id"]).unwrap();
+
+        // 密钥名称：值恒为 1，面板按 key_id 关联后显示名称（密钥数量有限，基数可控）
+        let key_info_opts = Opts::new("key_info", "Human-friendly name of each configured API key")
+            .namespace("gemini_proxy");
+        let key_info = IntGaugeVec::new(key_info_opts, &["key_id", "name"]).unwrap();
+
         registry.register(Box::new(request_count.clone())).unwrap();
         registry.register(Box::new(response_time.clone())).unwrap();
+        registry.register(Box::new(time_to_first_byte.clone())).unwrap();
+        registry.register(Box::new(stream_duration.clone())).unwrap();
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        registry.register(Box::new(upstream_duration.clone())).unwrap();
+        registry.register(Box::new(active_sessions.clone())).unwrap();
+        registry.register(Box::new(session_age.clone())).unwrap();
+        registry.register(Box::new(estimated_cost.clone())).unwrap();
+        registry.register(Box::new(request_tokens.clone())).unwrap();
+        registry.register(Box::new(prompt_tokens.clone())).unwrap();
+        registry.register(Box::new(completion_tokens.clone())).unwrap();
+        registry.register(Box::new(malformed_upstream.clone())).unwrap();
+        registry.register(Box::new(upstream_soft_failures.clone())).unwrap();
+        registry.register(Box::new(gemini_errors.clone())).unwrap();
+        registry.register(Box::new(in_flight.clone())).unwrap();
+        registry.register(Box::new(client_cancelled.clone())).unwrap();
+        registry.register(Box::new(requests_by_class.clone())).unwrap();
+        registry.register(Box::new(config_reloads.clone())).unwrap();
+        registry.register(Box::new(config_reload_timestamp.clone())).unwrap();
+        registry.register(Box::new(failover_retries.clone())).unwrap();
+        registry.register(Box::new(failover_recovered.clone())).unwrap();
+        registry.register(Box::new(key_requests.clone())).unwrap();
+        registry.register(Box::new(key_errors.clone())).unwrap();
+        registry.register(Box::new(key_latency.clone())).unwrap();
+        registry.register(Box::new(key_info.clone())).unwrap();
 
         Self {
             registry,
             request_count,
             response_time,
+            time_to_first_byte,
+            stream_duration,
+            request_duration,
+            upstream_duration,
+            active_sessions,
+            session_age,
+            estimated_cost,
+            request_tokens,
+            prompt_tokens,
+            completion_tokens,
+            malformed_upstream,
+            upstream_soft_failures,
+            gemini_errors,
+            in_flight,
+            client_cancelled,
+            requests_by_class,
+            config_reloads,
+            config_reload_timestamp,
+            failover_retries,
+            failover_recovered,
+            key_requests,
+            key_errors,
+            key_latency,
+            key_info,
+            key_labels: RwLock::new(HashMap::new()),
+            key_activity: KeyActivityTracker::default(),
             data: Arc::new(Mutex::new(())),
         }
     }
@@ -47,6 +277,242 @@ impl MetricsCollector {
             .observe(duration.as_secs_f64());
     }
 
+    /// 记录首字节时间（同步方法，在响应体过滤器中调用）
+    pub fn record_time_to_first_byte(&self, streaming: bool, duration: Duration) {
+        let _lock = self.data.lock().unwrap();
+        self.time_to_first_byte
+            .with_label_values(&[if streaming { "true" } else { "false" }])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// 记录客户端观察到的请求总耗时
+    pub fn record_request_duration(&self, streaming: bool, duration: Duration) {
+        let _lock = self.data.lock().unwrap();
+        self.request_duration
+            .with_label_values(&[if streaming { "true" } else { "false" }])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// 记录一次上游调用的耗时（失败与重试的调用各记一次）
+    pub fn record_upstream_duration(&self, streaming: bool, duration: Duration) {
+        let _lock = self.data.lock().unwrap();
+        self.upstream_duration
+            .with_label_values(&[if streaming { "true" } else { "false" }])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// 记录流式响应的总时长
+    pub fn record_stream_duration(&self, duration: Duration) {
+        let _lock = self.data.lock().unwrap();
+        self.stream_duration.observe(duration.as_secs_f64());
+    }
+
+    /// 记录一次换密钥重试
+    pub fn record_failover_retry(&self, status: u16) {
+        let _lock = self.data.lock().unwrap();
+        self.failover_retries.with_label_values(&[&status.to_string()]).inc();
+    }
+
+    /// 记录一次经换密钥重试后成功的请求
+    pub fn record_failover_recovered(&self) {
+        let _lock = self.data.lock().unwrap();
+        self.failover_recovered.inc();
+    }
+
+    /// 记录密钥的一次上游响应（用于密钥下钻）
+    pub fn record_key_response(&self, key_id: &str, status: u16, duration: Duration) {
+        self.key_activity.record_response(key_id, status, duration);
+    }
+
+    /// 记录密钥的一次上游调用：status 为上游状态码或传输错误类型，failed 时同时计入错误数
+    pub fn record_key_upstream_call(&self, key_id: &str, status: &str, failed: bool, latency: Duration) {
+        let _lock = self.data.lock().unwrap();
+        self.key_requests.with_label_values(&[key_id]).inc();
+        if failed {
+            self.key_errors.with_label_values(&[key_id, status]).inc();
+        }
+        self.key_latency
+            .with_label_values(&[key_id])
+            .observe(latency.as_secs_f64());
+    }
+
+    /// 获取密钥的上游调用次数与指定状态的错误次数
+    #[cfg(test)]
+    pub fn get_key_upstream_calls(&self, key_id: &str, status: &str) -> (u64, u64) {
+        let _lock = self.data.lock().unwrap();
+        (
+            self.key_requests.with_label_values(&[key_id]).get(),
+            self.key_errors.with_label_values(&[key_id, status]).get(),
+        )
+    }
+
+    /// 按配置更新密钥名称与说明：已移除的密钥不再导出，未设置名称时使用密钥 ID
+    pub fn set_key_labels(&self, keys: &[ApiKeyConfig]) {
+        let _lock = self.data.lock().unwrap();
+        self.key_info.reset();
+        let mut key_labels = self.key_labels.write().unwrap();
+        key_labels.clear();
+        for key in keys {
+            let labels = KeyLabels {
+                name: key.name.as_deref().map(sanitize_label_value).filter(|name| !name.is_empty()),
+                description: key.description.clone(),
+            };
+            let name = labels.name.as_deref().unwrap_or(&key.id);
+            self.key_info.with_label_values(&[key.id.as_str(), name]).set(1);
+            key_labels.insert(key.id.clone(), labels);
+        }
+    }
+
+    /// 获取密钥的名称与说明
+    pub fn key_labels(&self, key_id: &str) -> KeyLabels {
+        self.key_labels.read().unwrap().get(key_id).cloned().unwrap_or_default()
+    }
+
+    /// 获取密钥的活动快照（请求/错误计数、延迟分位数、令牌用量与最近错误）
+    pub fn key_activity(&self, key_id: &str) -> KeyActivitySnapshot {
+        self.key_activity.snapshot(key_id)
+    }
+
+    /// 按流量分类记录请求
+    pub async fn record_traffic_class(&self, traffic_class: &str) {
+        let _lock = self.data.lock().unwrap();
+        self.requests_by_class.with_label_values(&[traffic_class]).inc();
+    }
+
+    /// 记录一次配置重新加载及其结果，成功时更新最近重新加载时间
+    pub fn record_config_reload(&self, success: bool) {
+        let _lock = self.data.lock().unwrap();
+        let result = if success { "success" } else { "failure" };
+        self.config_reloads.with_label_values(&[result]).inc();
+        if success {
+            self.config_reload_timestamp.set(chrono::Utc::now().timestamp() as f64);
+        }
+    }
+
+    /// 获取指定结果（success/failure）的配置重新加载次数
+    pub fn get_config_reload_count(&self, result: &str) -> u64 {
+        let _lock = self.data.lock().unwrap();
+        self.config_reloads.with_label_values(&[result]).get()
+    }
+
+    /// 设置活跃会话数（用于启动时从存储恢复）
+    pub async fn set_active_sessions(&self, count: i64) {
+        let _lock = self.data.lock().unwrap();
+        self.active_sessions.set(count);
+    }
+
+    /// 记录新建会话
+    pub async fn record_session_created(&self) {
+        let _lock = self.data.lock().unwrap();
+        self.active_sessions.inc();
+    }
+
+    /// 记录会话刷新时的年龄
+    pub async fn record_session_refreshed(&self, age: Duration) {
+        let _lock = self.data.lock().unwrap();
+        self.session_age.observe(age.as_secs_f64());
+    }
+
+    /// 记录会话结束（过期/失效/删除）及其最终年龄
+    pub async fn record_session_ended(&self, age: Duration) {
+        let _lock = self.data.lock().unwrap();
+        self.active_sessions.dec();
+        self.session_age.observe(age.as_secs_f64());
+    }
+
+    /// 累加密钥的估算成本
+    pub async fn record_estimated_cost(&self, key_id: &str, cost: f64) {
+        let _lock = self.data.lock().unwrap();
+        if cost > 0.0 {
+            self.estimated_cost.with_label_values(&[key_id]).inc_by(cost);
+        }
+    }
+
+    /// 按调用方、密钥与模型累加令牌用量（kind 为 prompt/completion）
+    pub fn record_request_tokens(&self, caller: &str, key_id: &str, model: &str, prompt_tokens: u64, completion_tokens: u64) {
+        let _lock = self.data.lock().unwrap();
+        self.request_tokens
+            .with_label_values(&[caller, key_id, model, "prompt"])
+            .inc_by(prompt_tokens);
+        self.request_tokens
+            .with_label_values(&[caller, key_id, model, "completion"])
+            .inc_by(completion_tokens);
+    }
+
+    /// 按密钥与模型累加上游报告的令牌用量，并计入密钥的累计与滚动用量
+    pub fn record_key_tokens(&self, key_id: &str, model: &str, prompt_tokens: u64, completion_tokens: u64) {
+        let _lock = self.data.lock().unwrap();
+        self.prompt_tokens.with_label_values(&[key_id, model]).inc_by(prompt_tokens);
+        self.completion_tokens.with_label_values(&[key_id, model]).inc_by(completion_tokens);
+        self.key_activity.record_tokens(key_id, prompt_tokens, completion_tokens);
+    }
+
+    /// 记录无法解析的上游响应体
+    pub async fn increment_malformed_upstream(&self) {
+        let _lock = self.data.lock().unwrap();
+        self.malformed_upstream.inc();
+    }
+
+    /// 记录上游软失败（200 但响应体不可用）
+    pub async fn record_upstream_soft_failure(&self, reason: &str) {
+        let _lock = self.data.lock().unwrap();
+        self.upstream_soft_failures.with_label_values(&[reason]).inc();
+    }
+
+    /// 获取指定原因的上游软失败次数
+    #[cfg(test)]
+    pub fn get_upstream_soft_failures(&self, reason: &str) -> u64 {
+        self.upstream_soft_failures.with_label_values(&[reason]).get()
+    }
+
+    /// 记录一次按规范化错误码分类的上游错误
+    pub async fn record_gemini_error(&self, code: &str) {
+        let _lock = self.data.lock().unwrap();
+        self.gemini_errors.with_label_values(&[code]).inc();
+    }
+
+    /// 获取指定规范化错误码的上游错误次数
+    #[cfg(test)]
+    pub fn get_gemini_errors(&self, code: &str) -> u64 {
+        self.gemini_errors.with_label_values(&[code]).get()
+    }
+
+    /// 记录请求开始代理（同步方法，便于在 Drop 中配对调用）
+    pub fn request_started(&self) {
+        let _lock = self.data.lock().unwrap();
+        self.in_flight.inc();
+    }
+
+    /// 记录请求结束代理
+    pub fn request_finished(&self) {
+        let _lock = self.data.lock().unwrap();
+        self.in_flight.dec();
+    }
+
+    /// 记录因客户端断开而中止的请求
+    pub fn increment_client_cancelled(&self) {
+        let _lock = self.data.lock().unwrap();
+        self.client_cancelled.inc();
+    }
+
+    /// 获取正在代理的请求数
+    pub fn get_in_flight(&self) -> i64 {
+        let _lock = self.data.lock().unwrap();
+        self.in_flight.get()
+    }
+
+    /// 获取因客户端断开而中止的请求数
+    pub fn get_client_cancelled(&self) -> u64 {
+        let _lock = self.data.lock().unwrap();
+        self.client_cancelled.get()
+    }
+
+    /// 获取密钥累计估算成本
+    pub fn get_estimated_cost(&self, key_id: &str) -> f64 {
+        let _lock = self.data.lock().unwrap();
+        self.estimated_cost.with_label_values(&[key_id]).get()
+    }
+
     pub fn get_metrics(&self) -> String {
         let _lock = self.data.lock().unwrap();
         let mut buffer = vec![];
@@ -57,8 +523,157 @@ impl MetricsCollector {
     }
 }
 
+/// 密钥的名称与说明
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyLabels {
+    /// 清理后的名称（与 Prometheus 标签值一致）
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// 标签值的最大字符数
+const MAX_LABEL_VALUE_CHARS: usize = 64;
+
+/// 清理标签值：只保留字母、数字与 `-_.:/ `，其余字符（引号、反斜杠、控制字符等）替换为 `_`，
+/// 去掉首尾空白并截断到 64 个字符
+fn sanitize_label_value(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_LABEL_VALUE_CHARS)
+        .collect()
+}
+
 impl Default for MetricsCollector {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_estimated_cost_accrues_per_key() {
+        let metrics = MetricsCollector::new();
+
+        // 混合模型流量：同一密钥先后服务 pro 与 flash 请求
+        metrics.record_estimated_cost("key1", 0.0075).await;
+        metrics.record_estimated_cost("key1", 0.00045).await;
+        metrics.record_estimated_cost("key2", 0.00045).await;
+
+        assert!((metrics.get_estimated_cost("key1") - 0.00795).abs() < 1e-12);
+        assert!((metrics.get_estimated_cost("key2") - 0.00045).abs() < 1e-12);
+
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_estimated_cost_total{key_id=\"key1\"}"));
+    }
+
+    #[test]
+    fn test_key_upstream_calls_labeled_by_key_id() {
+        let metrics = MetricsCollector::new();
+        metrics.record_key_upstream_call("key1", "200", false, Duration::from_millis(120));
+        metrics.record_key_upstream_call("key1", "429", true, Duration::from_millis(40));
+        metrics.record_key_upstream_call("key1", "ConnectTimedout", true, Duration::from_secs(3));
+        metrics.record_key_upstream_call("key2", "200", false, Duration::from_millis(80));
+
+        assert_eq!(metrics.get_key_upstream_calls("key1", "429"), (3, 1));
+        assert_eq!(metrics.get_key_upstream_calls("key2", "429"), (1, 0));
+
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_key_requests_total{key_id=\"key1\"} 3"));
+        assert!(output.contains("gemini_proxy_key_errors_total{key_id=\"key1\",status=\"429\"} 1"));
+        assert!(output.contains("gemini_proxy_key_latency_seconds_count{key_id=\"key2\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_soft_failures_and_gemini_errors_counted_by_label() {
+        let metrics = MetricsCollector::new();
+        metrics.record_upstream_soft_failure("empty_body").await;
+        metrics.record_upstream_soft_failure("empty_body").await;
+        metrics.record_gemini_error("RESOURCE_EXHAUSTED").await;
+
+        assert_eq!(metrics.get_upstream_soft_failures("empty_body"), 2);
+        assert_eq!(metrics.get_upstream_soft_failures("error_body"), 0);
+        assert_eq!(metrics.get_gemini_errors("RESOURCE_EXHAUSTED"), 1);
+        assert_eq!(metrics.get_gemini_errors("INVALID_ARGUMENT"), 0);
+    }
+
+    #[test]
+    fn test_latency_histograms_exposed() {
+        let metrics = MetricsCollector::new();
+        metrics.record_request_duration(false, Duration::from_millis(800));
+        metrics.record_request_duration(true, Duration::from_secs(42));
+        metrics.record_upstream_duration(false, Duration::from_millis(700));
+
+        let output = metrics.get_metrics();
+        assert!(output.contains("# TYPE gemini_proxy_request_duration_seconds histogram"));
+        assert!(output.contains("gemini_proxy_request_duration_seconds_bucket{streaming=\"false\",le=\"1\"} 1"));
+        assert!(output.contains("gemini_proxy_request_duration_seconds_bucket{streaming=\"true\",le=\"30\"} 0"));
+        assert!(output.contains("gemini_proxy_request_duration_seconds_bucket{streaming=\"true\",le=\"45\"} 1"));
+        assert!(output.contains("gemini_proxy_request_duration_seconds_bucket{streaming=\"true\",le=\"+Inf\"} 1"));
+        assert!(output.contains("gemini_proxy_upstream_duration_seconds_count{streaming=\"false\"} 1"));
+        assert!(output.contains("gemini_proxy_upstream_duration_seconds_sum{streaming=\"false\"} 0.7"));
+    }
+
+    #[test]
+    fn test_key_tokens_counted_per_key_and_model() {
+        let metrics = MetricsCollector::new();
+        metrics.record_key_tokens("key1", "gemini-1.5-pro", 1000, 500);
+        metrics.record_key_tokens("key1", "gemini-1.5-pro", 200, 100);
+        metrics.record_key_tokens("key1", "gemini-1.5-flash", 30, 20);
+
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_prompt_tokens_total{key_id=\"key1\",model=\"gemini-1.5-pro\"} 1200"));
+        assert!(output.contains("gemini_proxy_completion_tokens_total{key_id=\"key1\",model=\"gemini-1.5-flash\"} 20"));
+        assert_eq!(metrics.key_activity("key1").tokens_last_minute, 1850);
+    }
+
+    #[test]
+    fn test_key_name_label_is_sanitized() {
+        let key = |id: &str, name: Option<&str>| ApiKeyConfig {
+            id: id.to_string(),
+            key: format!("secret-{}", id),
+            weight: 100,
+            max_requests_per_minute: 60,
+            tenant: None,
+            tags: Vec::new(),
+            region: None,
+            weight_min: None,
+            weight_max: None,
+            preferred_models: Vec::new(),
+            allowed_models: Vec::new(),
+            name: name.map(str::to_string),
+            description: Some("团队 A 的生产密钥".to_string()),
+        };
+        let metrics = MetricsCollector::new();
+        metrics.set_key_labels(&[
+            key("key1", Some(" Prod \"eu\"\nwest{1} ")),
+            key("key2", None),
+            key("key3", Some(&"x".repeat(100))),
+        ]);
+
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_key_info{key_id=\"key1\",name=\"Prod _eu__west_1_\"} 1"));
+        assert!(output.contains("gemini_proxy_key_info{key_id=\"key2\",name=\"key2\"} 1"));
+        assert!(output.contains(&format!("name=\"{}\"", "x".repeat(64))));
+        assert!(!output.contains("secret-key1"));
+        assert_eq!(metrics.key_labels("key1").name.as_deref(), Some("Prod _eu__west_1_"));
+        assert_eq!(metrics.key_labels("key1").description.as_deref(), Some("团队 A 的生产密钥"));
+
+        // 重新加载后已移除的密钥不再导出
+        metrics.set_key_labels(&[key("key2", Some("backup"))]);
+        let output = metrics.get_metrics();
+        assert!(!output.contains("key_id=\"key1\",name="));
+        assert!(output.contains("gemini_proxy_key_info{key_id=\"key2\",name=\"backup\"} 1"));
+        assert_eq!(metrics.key_labels("key1"), KeyLabels::default());
+    }
+}
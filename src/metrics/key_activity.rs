@@ -0,0 +1,199 @@
+// src/metrics/key_activity.rs
+//! 按密钥的活动记录
+//!
+//! Prometheus 指标按状态码或调用方聚合，无法直接还原单个密钥的完整情况。这里按密钥记录请求/错误计数、
+//! 最近的响应时间样本、令牌用量（累计与最近一分钟）与最近的错误，供密钥下钻接口计算延迟分位数等数据
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 每个密钥保留的响应时间样本数
+const MAX_LATENCY_SAMPLES: usize = 1000;
+/// 每个密钥保留的最近错误数
+const MAX_RECENT_ERRORS: usize = 10;
+/// 滚动令牌用量的统计窗口（与密钥每分钟限额对齐）
+const TOKEN_WINDOW: Duration = Duration::from_secs(60);
+
+/// 最近一次上游错误
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentKeyError {
+    pub timestamp: DateTime<Utc>,
+    pub status: u16,
+}
+
+/// 响应时间分位数（毫秒）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyPercentiles {
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// 单个密钥的活动快照
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KeyActivitySnapshot {
+    pub total_requests: u64,
+    pub error_requests: u64,
+    pub latency: LatencyPercentiles,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 最近一分钟内的令牌用量（提示与输出之和）
+    pub tokens_last_minute: u64,
+    pub recent_errors: Vec<RecentKeyError>,
+}
+
+#[derive(Debug, Default)]
+struct KeyActivity {
+    total_requests: u64,
+    error_requests: u64,
+    latencies_ms: VecDeque<f64>,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    /// 窗口内各次请求的令牌用量（记录时刻, 令牌数）
+    recent_tokens: VecDeque<(Instant, u64)>,
+    recent_errors: VecDeque<RecentKeyError>,
+}
+
+impl KeyActivity {
+    /// 丢弃统计窗口之外的令牌用量
+    fn prune_tokens(&mut self, now: Instant) {
+        while self
+            .recent_tokens
+            .front()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) >= TOKEN_WINDOW)
+        {
+            self.recent_tokens.pop_front();
+        }
+    }
+}
+
+/// 按密钥的活动记录
+#[derive(Debug, Default)]
+pub struct KeyActivityTracker {
+    keys: Mutex<HashMap<String, KeyActivity>>,
+}
+
+impl KeyActivityTracker {
+    /// 记录一次上游响应，状态码 >= 400 计为错误
+    pub fn record_response(&self, key_id: &str, status: u16, duration: Duration) {
+        let mut keys = self.keys.lock().unwrap();
+        let activity = keys.entry(key_id.to_string()).or_default();
+        activity.total_requests += 1;
+        if activity.latencies_ms.len() >= MAX_LATENCY_SAMPLES {
+            activity.latencies_ms.pop_front();
+        }
+        activity.latencies_ms.push_back(duration.as_secs_f64() * 1000.0);
+
+        if status >= 400 {
+            activity.error_requests += 1;
+            if activity.recent_errors.len() >= MAX_RECENT_ERRORS {
+                activity.recent_errors.pop_front();
+            }
+            activity.recent_errors.push_back(RecentKeyError {
+                timestamp: Utc::now(),
+                status,
+            });
+        }
+    }
+
+    /// 累加令牌用量
+    pub fn record_tokens(&self, key_id: &str, prompt_tokens: u64, completion_tokens: u64) {
+        let mut keys = self.keys.lock().unwrap();
+        let activity = keys.entry(key_id.to_string()).or_default();
+        activity.prompt_tokens += prompt_tokens;
+        activity.completion_tokens += completion_tokens;
+
+        let now = Instant::now();
+        activity.prune_tokens(now);
+        activity.recent_tokens.push_back((now, prompt_tokens + completion_tokens));
+    }
+
+    /// 获取密钥的活动快照，尚无活动时返回全零快照
+    pub fn snapshot(&self, key_id: &str) -> KeyActivitySnapshot {
+        let mut keys = self.keys.lock().unwrap();
+        let activity = match keys.get_mut(key_id) {
+            Some(activity) => activity,
+            None => return KeyActivitySnapshot::default(),
+        };
+        activity.prune_tokens(Instant::now());
+
+        let mut sorted: Vec<f64> = activity.latencies_ms.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        KeyActivitySnapshot {
+            total_requests: activity.total_requests,
+            error_requests: activity.error_requests,
+            latency: LatencyPercentiles {
+                samples: sorted.len(),
+                p50_ms: percentile(&sorted, 0.50),
+                p90_ms: percentile(&sorted, 0.90),
+                p99_ms: percentile(&sorted, 0.99),
+            },
+            prompt_tokens: activity.prompt_tokens,
+            completion_tokens: activity.completion_tokens,
+            tokens_last_minute: activity.recent_tokens.iter().map(|(_, tokens)| tokens).sum(),
+            // 最新的错误排在最前
+            recent_errors: activity.recent_errors.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+/// 最近秩法分位数，输入需已排序
+fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_tracks_errors_latency_and_tokens() {
+        let tracker = KeyActivityTracker::default();
+        for ms in 1..=100 {
+            tracker.record_response("key1", 200, Duration::from_millis(ms));
+        }
+        tracker.record_response("key1", 429, Duration::from_millis(5));
+        tracker.record_response("key1", 503, Duration::from_millis(5));
+        tracker.record_tokens("key1", 10, 20);
+        tracker.record_tokens("key1", 5, 0);
+
+        let snapshot = tracker.snapshot("key1");
+        assert_eq!(snapshot.total_requests, 102);
+        assert_eq!(snapshot.error_requests, 2);
+        assert_eq!(snapshot.latency.samples, 102);
+        // 两个 5ms 的错误样本也计入分布
+        assert_eq!(snapshot.latency.p50_ms, 49.0);
+        assert_eq!(snapshot.latency.p99_ms, 99.0);
+        assert_eq!((snapshot.prompt_tokens, snapshot.completion_tokens), (15, 20));
+        assert_eq!(snapshot.tokens_last_minute, 35);
+        let statuses: Vec<u16> = snapshot.recent_errors.iter().map(|e| e.status).collect();
+        assert_eq!(statuses, vec![503, 429]);
+
+        assert_eq!(tracker.snapshot("unused").total_requests, 0);
+    }
+
+    #[test]
+    fn test_rolling_tokens_expire_after_window() {
+        let tracker = KeyActivityTracker::default();
+        tracker.record_tokens("key1", 100, 50);
+        // 把这次用量挪到窗口之外
+        if let Some(activity) = tracker.keys.lock().unwrap().get_mut("key1") {
+            let (at, _) = activity.recent_tokens.front_mut().unwrap();
+            *at = at.checked_sub(TOKEN_WINDOW).unwrap();
+        }
+        tracker.record_tokens("key1", 7, 3);
+
+        let snapshot = tracker.snapshot("key1");
+        assert_eq!(snapshot.tokens_last_minute, 10);
+        // 累计用量不受窗口影响
+        assert_eq!((snapshot.prompt_tokens, snapshot.completion_tokens), (107, 53));
+    }
+}
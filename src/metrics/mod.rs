@@ -1,2 +1,3 @@
 pub mod collector;
+pub mod key_activity;
 pub use collector::*;
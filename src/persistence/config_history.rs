@@ -2,8 +2,11 @@
 //! 配置变更历史记录
 //! 
 //! 记录和管理配置文件的历史变更，支持版本控制、回滚和变更审计
+//!
+//! 每条变更单独保存为一个文件；超过 `archive_after_days` 的变更按月合并到归档文件
+//! （`config_change_archives/<YYYY-MM>`）后删除单独的文件，归档中的记录仍可查询
 
-use super::{DataStore, FileSystemStore, PersistenceConfig, PersistenceError};
+use super::{open_store, BoxedStore, DataStore, PersistenceConfig, PersistenceError};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -89,6 +92,24 @@ pub struct ConfigSnapshot {
     pub related_change_id: Option<String>,
 }
 
+/// 按月归档的配置变更记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigChangeArchive {
+    /// 归档月份（`YYYY-MM`）
+    pub month: String,
+    /// 该月的变更记录，按时间戳升序
+    pub records: Vec<ConfigChangeRecord>,
+}
+
+/// 一次归档的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompactionReport {
+    /// 合并进归档的变更记录数
+    pub archived_records: usize,
+    /// 写入的归档月份
+    pub archived_months: Vec<String>,
+}
+
 /// 配置历史查询条件
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigHistoryQuery {
@@ -125,9 +146,11 @@ pub struct TimeSeriesPoint {
 /// 配置历史管理器
 pub struct ConfigHistoryStore {
     /// 变更记录存储
-    changes_store: FileSystemStore<ConfigChangeRecord>,
+    changes_store: BoxedStore<ConfigChangeRecord>,
+    /// 按月归档的变更记录存储
+    archives_store: BoxedStore<ConfigChangeArchive>,
     /// 快照存储
-    snapshots_store: FileSystemStore<ConfigSnapshot>,
+    snapshots_store: BoxedStore<ConfigSnapshot>,
     /// 内存索引（用于快速查询）
     change_index: Arc<RwLock<HashMap<String, Vec<String>>>>, // field_name -> change_ids
     /// 版本计数器
@@ -147,6 +170,8 @@ pub struct ConfigHistoryConfig {
     pub auto_snapshot_interval: u64,
     /// 启用压缩
     pub enable_compression: bool,
+    /// 超过该天数的变更合并到按月归档文件（0 表示不归档）
+    pub archive_after_days: u32,
 }
 
 impl Default for ConfigHistoryConfig {
@@ -156,6 +181,7 @@ impl Default for ConfigHistoryConfig {
             max_records: 10000,
             auto_snapshot_interval: 3600, // 1小时
             enable_compression: false,
+            archive_after_days: 30,
         }
     }
 }
@@ -163,11 +189,13 @@ impl Default for ConfigHistoryConfig {
 impl ConfigHistoryStore {
     /// 创建新的配置历史存储
     pub fn new(persistence_config: PersistenceConfig, history_config: ConfigHistoryConfig) -> Self {
-        let changes_store = FileSystemStore::new(persistence_config.clone(), "config_changes".to_string());
-        let snapshots_store = FileSystemStore::new(persistence_config, "config_snapshots".to_string());
+        let changes_store = open_store(&persistence_config, "config_changes");
+        let archives_store = open_store(&persistence_config, "config_change_archives");
+        let snapshots_store = open_store(&persistence_config, "config_snapshots");
         
         Self {
             changes_store,
+            archives_store,
             snapshots_store,
             change_index: Arc::new(RwLock::new(HashMap::new())),
             version_counter: Arc::new(RwLock::new(0)),
@@ -230,13 +258,41 @@ impl ConfigHistoryStore {
         // 更新索引
         self.update_index(&changed_fields, &record_id).await;
         
-        // 清理旧记录
+        // 清理旧记录并归档
         self.cleanup_old_records().await.ok();
+        self.compact().await.ok();
         
         tracing::info!("已记录配置变更: {} (版本: {})", description, version);
         Ok(record_id)
     }
     
+    /// 记录配置更新，`changed_fields` 由前后配置的 JSON 差异计算得出
+    pub async fn record_config_update(
+        &self,
+        operator: &str,
+        description: &str,
+        previous_config: Option<&str>,
+        new_config: &str,
+        source: ChangeSource,
+        metadata: Option<HashMap<String, String>>,
+    ) -> Result<String, PersistenceError> {
+        let (change_type, changed_fields) = match previous_config {
+            Some(previous) => (ConfigChangeType::Update, diff_config_fields(previous, new_config)?),
+            None => (ConfigChangeType::Create, diff_config_fields("{}", new_config)?),
+        };
+        
+        self.record_change(
+            operator,
+            change_type,
+            description,
+            previous_config,
+            new_config,
+            changed_fields,
+            source,
+            metadata,
+        ).await
+    }
+    
     /// 创建配置快照
     pub async fn create_snapshot(
         &self,
@@ -269,21 +325,12 @@ impl ConfigHistoryStore {
     
     /// 查询配置变更历史
     pub async fn query_changes(&self, query: &ConfigHistoryQuery) -> Result<Vec<ConfigChangeRecord>, PersistenceError> {
-        let all_change_ids = self.changes_store.list_keys().await?;
-        let mut matching_records = Vec::new();
-        
-        for change_id in all_change_ids {
-            match self.changes_store.load(&change_id).await {
-                Ok(record) => {
-                    if self.matches_change_query(&record, query) {
-                        matching_records.push(record);
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!("加载变更记录 {} 失败: {}", change_id, e);
-                }
-            }
-        }
+        let mut matching_records: Vec<ConfigChangeRecord> = self
+            .load_all_changes()
+            .await?
+            .into_iter()
+            .filter(|record| self.matches_change_query(record, query))
+            .collect();
         
         // 按时间戳倒序排序
         matching_records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
@@ -372,13 +419,17 @@ impl ConfigHistoryStore {
         metadata.insert("target_version".to_string(), target_version.to_string());
         metadata.insert("rollback_reason".to_string(), reason.to_string());
         
+        // 无法解析的历史配置按全部字段变更记录
+        let changed_fields = diff_config_fields(&current_config, &target_config)
+            .unwrap_or_else(|_| vec!["*".to_string()]);
+        
         let change_id = self.record_change(
             operator,
             ConfigChangeType::Restore,
             &format!("回滚到版本 {}: {}", target_version, reason),
             Some(&current_config),
             &target_config,
-            vec!["*".to_string()], // 表示全部字段
+            changed_fields,
             ChangeSource::WebUI,
             Some(metadata),
         ).await?;
@@ -468,6 +519,62 @@ impl ConfigHistoryStore {
         })
     }
     
+    /// 将超过 `archive_after_days` 的变更按月合并到归档文件，并删除已归档的单独文件
+    ///
+    /// 先写入归档再删除单独文件，中途失败时记录最多重复存在，不会丢失
+    pub async fn compact(&self) -> Result<CompactionReport, PersistenceError> {
+        let mut report = CompactionReport::default();
+        if self.config.archive_after_days == 0 {
+            return Ok(report);
+        }
+        let cutoff_time = (chrono::Utc::now().timestamp() as u64)
+            .saturating_sub(self.config.archive_after_days as u64 * 24 * 3600);
+
+        let mut by_month: HashMap<String, Vec<ConfigChangeRecord>> = HashMap::new();
+        for change_id in self.changes_store.list_keys().await? {
+            if let Ok(record) = self.changes_store.load(&change_id).await {
+                if record.timestamp < cutoff_time {
+                    by_month.entry(archive_month(record.timestamp)).or_default().push(record);
+                }
+            }
+        }
+
+        let mut months: Vec<String> = by_month.keys().cloned().collect();
+        months.sort();
+        for month in months {
+            let records = by_month.remove(&month).unwrap_or_default();
+            let mut archive = match self.archives_store.load(&month).await {
+                Ok(archive) => archive,
+                Err(PersistenceError::DataNotFound(_)) => ConfigChangeArchive {
+                    month: month.clone(),
+                    records: Vec::new(),
+                },
+                Err(e) => return Err(e),
+            };
+            let ids: Vec<String> = records.iter().map(|r| r.id.clone()).collect();
+            archive.records.retain(|existing| !ids.contains(&existing.id));
+            archive.records.extend(records);
+            archive.records.sort_by_key(|r| r.timestamp);
+            self.archives_store.save(&month, &archive).await?;
+
+            // 记录已保存在归档中，删除单独文件时不再生成备份
+            for id in &ids {
+                self.changes_store.delete_without_backup(id).await?;
+            }
+            report.archived_records += ids.len();
+            report.archived_months.push(month);
+        }
+
+        if report.archived_records > 0 {
+            tracing::info!(
+                "已将 {} 条配置变更归档到 {} 个月度文件",
+                report.archived_records,
+                report.archived_months.len()
+            );
+        }
+        Ok(report)
+    }
+    
     /// 导出配置历史
     pub async fn export_history(&self, query: &ConfigHistoryQuery) -> Result<String, PersistenceError> {
         let changes = self.query_changes(query).await?;
@@ -518,18 +625,37 @@ impl ConfigHistoryStore {
         true
     }
     
+    /// 加载全部变更记录（单独文件与月度归档）
+    async fn load_all_changes(&self) -> Result<Vec<ConfigChangeRecord>, PersistenceError> {
+        let mut records = Vec::new();
+        for change_id in self.changes_store.list_keys().await? {
+            match self.changes_store.load(&change_id).await {
+                Ok(record) => records.push(record),
+                Err(e) => {
+                    tracing::warn!("加载变更记录 {} 失败: {}", change_id, e);
+                }
+            }
+        }
+        for month in self.archives_store.list_keys().await? {
+            match self.archives_store.load(&month).await {
+                Ok(archive) => records.extend(archive.records),
+                Err(e) => {
+                    tracing::warn!("加载变更归档 {} 失败: {}", month, e);
+                }
+            }
+        }
+        Ok(records)
+    }
+    
     /// 重建索引
     async fn rebuild_index(&self) -> Result<(), PersistenceError> {
-        let change_ids = self.changes_store.list_keys().await?;
         let mut index = HashMap::new();
         
-        for change_id in change_ids {
-            if let Ok(record) = self.changes_store.load(&change_id).await {
-                for field in &record.changed_fields {
-                    index.entry(field.clone())
-                        .or_insert_with(Vec::new)
-                        .push(change_id.clone());
-                }
+        for record in self.load_all_changes().await? {
+            for field in &record.changed_fields {
+                index.entry(field.clone())
+                    .or_insert_with(Vec::new)
+                    .push(record.id.clone());
             }
         }
         
@@ -549,16 +675,8 @@ impl ConfigHistoryStore {
     
     /// 获取最新版本号
     async fn get_latest_version(&self) -> Result<u32, PersistenceError> {
-        let change_ids = self.changes_store.list_keys().await?;
-        let mut max_version = 0u32;
-        
-        for change_id in change_ids {
-            if let Ok(record) = self.changes_store.load(&change_id).await {
-                max_version = max_version.max(record.version);
-            }
-        }
-        
-        Ok(max_version)
+        let records = self.load_all_changes().await?;
+        Ok(records.iter().map(|r| r.version).max().unwrap_or(0))
     }
     
     /// 清理旧记录
@@ -576,6 +694,23 @@ impl ConfigHistoryStore {
             }
         }
         
+        // 归档中的过期记录同样清理，整月过期时删除归档文件
+        for month in self.archives_store.list_keys().await? {
+            if let Ok(mut archive) = self.archives_store.load(&month).await {
+                let before = archive.records.len();
+                archive.records.retain(|r| r.timestamp >= cutoff_time);
+                if archive.records.len() == before {
+                    continue;
+                }
+                deletion_count += before - archive.records.len();
+                if archive.records.is_empty() {
+                    self.archives_store.delete(&month).await.ok();
+                } else {
+                    self.archives_store.save(&month, &archive).await.ok();
+                }
+            }
+        }
+        
         if deletion_count > 0 {
             tracing::info!("已清理 {} 条过期的配置变更记录", deletion_count);
             self.rebuild_index().await?;
@@ -600,6 +735,60 @@ impl Default for ConfigHistoryQuery {
     }
 }
 
+/// 变更记录所属的归档月份（UTC，`YYYY-MM`）
+fn archive_month(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|dt| dt.format("%Y-%m").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 比较两份 JSON 配置，返回发生变化的字段路径（如 `gemini.api_keys[0].weight`），按字典序排列
+///
+/// 新增或删除的字段记录其自身路径，不再展开子字段；根节点本身不是对象且发生变化时返回 `*`
+pub fn diff_config_fields(previous: &str, new: &str) -> Result<Vec<String>, PersistenceError> {
+    let previous: serde_json::Value = serde_json::from_str(previous)?;
+    let new: serde_json::Value = serde_json::from_str(new)?;
+    
+    let mut changed = Vec::new();
+    collect_changed_paths("", &previous, &new, &mut changed);
+    changed.sort();
+    Ok(changed)
+}
+
+fn collect_changed_paths(path: &str, previous: &serde_json::Value, new: &serde_json::Value, changed: &mut Vec<String>) {
+    use serde_json::Value;
+    
+    match (previous, new) {
+        (Value::Object(previous), Value::Object(new)) => {
+            for (key, previous_value) in previous {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match new.get(key) {
+                    Some(new_value) => collect_changed_paths(&child, previous_value, new_value, changed),
+                    None => changed.push(child),
+                }
+            }
+            for key in new.keys().filter(|key| !previous.contains_key(*key)) {
+                changed.push(if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) });
+            }
+        }
+        (Value::Array(previous), Value::Array(new)) => {
+            for index in 0..previous.len().max(new.len()) {
+                let child = format!("{}[{}]", path, index);
+                match (previous.get(index), new.get(index)) {
+                    (Some(previous_value), Some(new_value)) => {
+                        collect_changed_paths(&child, previous_value, new_value, changed)
+                    }
+                    _ => changed.push(child),
+                }
+            }
+        }
+        _ if previous != new => {
+            changed.push(if path.is_empty() { "*".to_string() } else { path.to_string() });
+        }
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -657,4 +846,126 @@ mod tests {
         assert_eq!(stats.total_changes, 1);
         assert!(stats.changes_by_operator.contains_key("admin"));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_diff_config_fields_exact_paths() {
+        let previous = r#"{
+            "server": {"host": "0.0.0.0", "port": 8080},
+            "gemini": {"api_keys": [{"id": "key1", "weight": 100}, {"id": "key2", "weight": 50}], "timeout_seconds": 30},
+            "cache": {"enabled": true}
+        }"#;
+        let new = r#"{
+            "server": {"host": "0.0.0.0", "port": 8443},
+            "gemini": {"api_keys": [{"id": "key1", "weight": 200}], "timeout_seconds": 30},
+            "metrics": {"enabled": true}
+        }"#;
+        
+        assert_eq!(
+            diff_config_fields(previous, new).unwrap(),
+            vec![
+                "cache".to_string(),
+                "gemini.api_keys[0].weight".to_string(),
+                "gemini.api_keys[1]".to_string(),
+                "metrics".to_string(),
+                "server.port".to_string(),
+            ]
+        );
+        assert!(diff_config_fields(previous, previous).unwrap().is_empty());
+        assert!(diff_config_fields("not json", new).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_record_config_update_records_computed_fields() {
+        let temp_dir = tempdir().unwrap();
+        let persistence_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let history_store = ConfigHistoryStore::new(persistence_config, ConfigHistoryConfig::default());
+        history_store.initialize().await.unwrap();
+        
+        history_store.record_config_update(
+            "admin",
+            "调整超时",
+            Some(r#"{"gemini": {"timeout_seconds": 30, "base_url": "https://a"}}"#),
+            r#"{"gemini": {"timeout_seconds": 60, "base_url": "https://a"}}"#,
+            ChangeSource::API,
+            None,
+        ).await.unwrap();
+        
+        let changes = history_store.query_changes(&ConfigHistoryQuery::default()).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ConfigChangeType::Update);
+        assert_eq!(changes[0].changed_fields, vec!["gemini.timeout_seconds".to_string()]);
+        
+        let stats = history_store.get_statistics(None).await.unwrap();
+        assert_eq!(stats.most_changed_fields, vec![("gemini.timeout_seconds".to_string(), 1)]);
+    }
+    
+    fn change_at(id: &str, version: u32, timestamp: u64) -> ConfigChangeRecord {
+        ConfigChangeRecord {
+            id: id.to_string(),
+            version,
+            timestamp,
+            operator: "admin".to_string(),
+            change_type: ConfigChangeType::Update,
+            description: format!("变更 {}", id),
+            previous_config: None,
+            new_config: format!(r#"{{"version": {}}}"#, version),
+            changed_fields: vec!["gemini.timeout_seconds".to_string()],
+            source: ChangeSource::API,
+            metadata: HashMap::new(),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_compaction_archives_old_changes_by_month() {
+        let temp_dir = tempdir().unwrap();
+        let persistence_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let history_config = ConfigHistoryConfig {
+            retention_days: 365,
+            archive_after_days: 30,
+            ..Default::default()
+        };
+        let history_store = ConfigHistoryStore::new(persistence_config, history_config);
+        
+        let day = 24 * 3600;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let old = [("old-1", 1, now - 100 * day), ("old-2", 2, now - 99 * day), ("old-3", 3, now - 40 * day)];
+        for (id, version, timestamp) in old {
+            history_store.changes_store.save(id, &change_at(id, version, timestamp)).await.unwrap();
+        }
+        history_store.changes_store.save("recent", &change_at("recent", 4, now - day)).await.unwrap();
+        
+        let report = history_store.compact().await.unwrap();
+        assert_eq!(report.archived_records, 3);
+        let expected_months: std::collections::BTreeSet<_> = old.iter().map(|(_, _, ts)| archive_month(*ts)).collect();
+        assert_eq!(report.archived_months, expected_months.iter().cloned().collect::<Vec<_>>());
+        
+        // 旧变更的单独文件已删除，近期变更保持单独文件
+        assert_eq!(history_store.changes_store.list_keys().await.unwrap(), vec!["recent".to_string()]);
+        assert_eq!(history_store.archives_store.list_keys().await.unwrap().len(), expected_months.len());
+        
+        // 归档中的记录仍可查询，版本号从归档中恢复
+        history_store.initialize().await.unwrap();
+        let all = history_store.query_changes(&ConfigHistoryQuery::default()).await.unwrap();
+        let ids: Vec<_> = all.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["recent", "old-3", "old-2", "old-1"]);
+        let ranged = history_store
+            .query_changes(&ConfigHistoryQuery {
+                end_time: Some(now - 50 * day),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(ranged.len(), 2);
+        assert_eq!(*history_store.version_counter.read().await, 4);
+        
+        // 再次归档不重复写入
+        assert_eq!(history_store.compact().await.unwrap().archived_records, 0);
+        assert_eq!(history_store.query_changes(&ConfigHistoryQuery::default()).await.unwrap().len(), 4);
+    }
+}
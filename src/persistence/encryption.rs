@@ -0,0 +1,144 @@
+// src/persistence/encryption.rs
+//! 持久化数据的静态加密
+//!
+//! 启用后 `FileSystemStore` 写入的文件内容与 `SqliteStore` 保存的数据以 AES-256-GCM 加密，文件格式为 `魔数 | 12 字节随机 nonce | 密文 | 16 字节认证标签`，
+//! 压缩在加密之前进行，文件名不变。密钥由环境变量中的主密钥经 PBKDF2-HMAC-SHA256 派生。
+//! 读取时按魔数识别加密文件，未加密的旧文件照常读取，重新保存时才加密，便于逐步迁移
+
+use super::PersistenceError;
+use openssl::hash::MessageDigest;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+
+/// 加密文件开头的魔数
+const ENCRYPTED_MAGIC: &[u8] = b"GPENC1\0";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+/// 派生密钥使用的固定盐与迭代次数（修改会导致已加密的文件无法读取）
+const KEY_DERIVATION_SALT: &[u8] = b"gemini-proxy/persistence/v1";
+const KEY_DERIVATION_ITERATIONS: usize = 100_000;
+
+/// 默认读取主密钥的环境变量
+pub const DEFAULT_MASTER_SECRET_ENV: &str = "GEMINI_PROXY_MASTER_KEY";
+
+/// 静态加密配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// 是否加密新写入的数据
+    #[serde(default)]
+    pub enabled: bool,
+    /// 主密钥所在的环境变量
+    #[serde(default = "default_master_secret_env")]
+    pub master_secret_env: String,
+}
+
+fn default_master_secret_env() -> String {
+    DEFAULT_MASTER_SECRET_ENV.to_string()
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            master_secret_env: default_master_secret_env(),
+        }
+    }
+}
+
+/// 由主密钥派生的 AES-256-GCM 密钥
+pub struct PayloadCipher {
+    key: [u8; KEY_LEN],
+}
+
+impl std::fmt::Debug for PayloadCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PayloadCipher(..)")
+    }
+}
+
+impl PayloadCipher {
+    /// 按配置读取主密钥；未启用时返回 None，启用但环境变量未设置或为空时返回错误
+    pub fn from_config(config: &EncryptionConfig) -> Result<Option<Self>, PersistenceError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let secret = std::env::var(&config.master_secret_env)
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| {
+                PersistenceError::EncryptionError(format!("启用了静态加密，但环境变量 {} 未设置", config.master_secret_env))
+            })?;
+        Self::from_secret(secret.as_bytes()).map(Some)
+    }
+
+    pub fn from_secret(secret: &[u8]) -> Result<Self, PersistenceError> {
+        let mut key = [0u8; KEY_LEN];
+        openssl::pkcs5::pbkdf2_hmac(
+            secret,
+            KEY_DERIVATION_SALT,
+            KEY_DERIVATION_ITERATIONS,
+            MessageDigest::sha256(),
+            &mut key,
+        )
+        .map_err(encryption_error)?;
+        Ok(Self { key })
+    }
+
+    /// 加密并在密文前写入魔数与随机 nonce
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, PersistenceError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        openssl::rand::rand_bytes(&mut nonce).map_err(encryption_error)?;
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &self.key, Some(&nonce), ENCRYPTED_MAGIC, plaintext, &mut tag)
+            .map_err(encryption_error)?;
+
+        let mut sealed = Vec::with_capacity(ENCRYPTED_MAGIC.len() + NONCE_LEN + ciphertext.len() + TAG_LEN);
+        sealed.extend_from_slice(ENCRYPTED_MAGIC);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed.extend_from_slice(&tag);
+        Ok(sealed)
+    }
+
+    /// 解密 `encrypt` 的输出；主密钥不匹配或内容被篡改时返回错误
+    pub fn decrypt(&self, sealed: &[u8]) -> Result<Vec<u8>, PersistenceError> {
+        let body = sealed
+            .strip_prefix(ENCRYPTED_MAGIC)
+            .filter(|body| body.len() >= NONCE_LEN + TAG_LEN)
+            .ok_or_else(|| PersistenceError::EncryptionError("加密数据不完整".to_string()))?;
+        let (nonce, rest) = body.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        decrypt_aead(Cipher::aes_256_gcm(), &self.key, Some(nonce), ENCRYPTED_MAGIC, ciphertext, tag)
+            .map_err(|_| PersistenceError::EncryptionError("解密失败：主密钥不匹配或数据已损坏".to_string()))
+    }
+}
+
+/// 内容是否为加密数据
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENCRYPTED_MAGIC)
+}
+
+fn encryption_error(error: openssl::error::ErrorStack) -> PersistenceError {
+    PersistenceError::EncryptionError(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_wrong_secret_rejected() {
+        let cipher = PayloadCipher::from_secret(b"master-secret").unwrap();
+        let sealed = cipher.encrypt(br#"{"refresh_token":"abc"}"#).unwrap();
+
+        assert!(is_encrypted(&sealed));
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), br#"{"refresh_token":"abc"}"#);
+        // 每次加密使用新的 nonce
+        assert_ne!(cipher.encrypt(br#"{"refresh_token":"abc"}"#).unwrap(), sealed);
+
+        let other = PayloadCipher::from_secret(b"another-secret").unwrap();
+        assert!(matches!(other.decrypt(&sealed), Err(PersistenceError::EncryptionError(_))));
+        assert!(!is_encrypted(br#"{"refresh_token":"abc"}"#));
+    }
+}
@@ -3,11 +3,16 @@
 //! 
 //! 提供统一的数据持久化接口，支持权重预设、配置历史、会话状态等数据的存储和检索
 
+use crate::security::{AuditResult, SharedAuditLog};
+use encryption::{is_encrypted, EncryptionConfig, PayloadCipher};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+pub mod encryption;
 pub mod storage;
 pub mod weight_presets;
 pub mod config_history;
@@ -22,6 +27,9 @@ pub enum PersistenceError {
     #[error("序列化错误: {0}")]
     SerializationError(#[from] serde_json::Error),
     
+    #[error("MessagePack 编解码错误: {0}")]
+    MessagePackError(String),
+    
     #[error("数据不存在: {0}")]
     DataNotFound(String),
     
@@ -30,14 +38,24 @@ pub enum PersistenceError {
     
     #[error("权限错误: {0}")]
     PermissionError(String),
+    
+    #[error("存储目录不可用: {0}")]
+    DirectoryUnavailable(String),
+    
+    #[error("数据库错误: {0}")]
+    DatabaseError(String),
+    
+    #[error("加密错误: {0}")]
+    EncryptionError(String),
 }
 
-/// 持久化配置
+/// 持久化配置（`proxy.yaml` 的 `persistence` 部分，省略的字段使用默认值）
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PersistenceConfig {
     /// 数据存储根目录
     pub data_dir: PathBuf,
-    /// 是否启用数据压缩
+    /// 是否以 gzip 压缩写入数据文件（`{key}.json.gz`），读取时两种文件都能识别
     pub enable_compression: bool,
     /// 备份保留天数
     pub backup_retention_days: u32,
@@ -45,6 +63,106 @@ pub struct PersistenceConfig {
     pub auto_backup_interval: u64,
     /// 最大文件大小（字节）
     pub max_file_size: u64,
+    /// 写入时使用的序列化格式（读取时自动识别）
+    #[serde(default)]
+    pub format: PersistenceFormat,
+    /// 存储后端
+    #[serde(default)]
+    pub backend: PersistenceBackend,
+    /// 数据目录无法创建（例如没有权限）时的处理方式
+    #[serde(default)]
+    pub on_unavailable_dir: UnavailableDirPolicy,
+    /// 文件系统存储的静态加密（SQLite 与内存后端不加密）
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+}
+
+/// 数据目录无法创建时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum UnavailableDirPolicy {
+    /// 初始化失败并返回错误
+    #[default]
+    FailFast,
+    /// 记录警告后改用内存存储继续运行，进程退出后数据丢失
+    MemoryOnly,
+}
+
+/// 持久化存储后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PersistenceBackend {
+    /// 每个键一个文件，写入临时文件后重命名
+    #[default]
+    FileSystem,
+    /// 数据目录下的单个 SQLite 数据库文件，写入在事务中完成（始终以未压缩的 JSON 保存）
+    Sqlite,
+    /// 仅保存在内存中，不访问数据目录
+    Memory,
+}
+
+/// 持久化序列化格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PersistenceFormat {
+    /// 格式化的 JSON（可读性好）
+    #[default]
+    Json,
+    /// MessagePack（体积小、编解码快）
+    MessagePack,
+}
+
+impl PersistenceFormat {
+    /// 所有支持的格式
+    const ALL: [PersistenceFormat; 2] = [PersistenceFormat::Json, PersistenceFormat::MessagePack];
+
+    /// 文件扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+        }
+    }
+
+    /// 根据文件内容识别格式：JSON 文档以 `{` 或 `[` 开头，其余按 MessagePack 处理
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') | Some(b'[') => Self::Json,
+            _ => Self::MessagePack,
+        }
+    }
+
+    /// 序列化数据
+    pub fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, PersistenceError> {
+        match self {
+            Self::Json => Ok(serde_json::to_vec_pretty(data)?),
+            Self::MessagePack => rmp_serde::to_vec_named(data)
+                .map_err(|e| PersistenceError::MessagePackError(e.to_string())),
+        }
+    }
+
+    /// 反序列化数据
+    pub fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, PersistenceError> {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(bytes)?),
+            Self::MessagePack => rmp_serde::from_slice(bytes)
+                .map_err(|e| PersistenceError::MessagePackError(e.to_string())),
+        }
+    }
+}
+
+/// gzip 压缩文件的扩展名
+const GZIP_EXTENSION: &str = "gz";
+
+/// gzip 压缩
+fn gzip_compress(bytes: &[u8]) -> Result<Vec<u8>, PersistenceError> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+/// gzip 解压
+fn gzip_decompress(bytes: &[u8]) -> Result<Vec<u8>, PersistenceError> {
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
 }
 
 impl Default for PersistenceConfig {
@@ -55,6 +173,10 @@ impl Default for PersistenceConfig {
             backup_retention_days: 30,
             auto_backup_interval: 3600, // 1小时
             max_file_size: 10 * 1024 * 1024, // 10MB
+            format: PersistenceFormat::default(),
+            backend: PersistenceBackend::default(),
+            on_unavailable_dir: UnavailableDirPolicy::default(),
+            encryption: EncryptionConfig::default(),
         }
     }
 }
@@ -76,12 +198,52 @@ pub trait DataStore<T> {
     
     /// 检查数据是否存在
     async fn exists(&self, key: &str) -> Result<bool, PersistenceError>;
+    
+    /// 删除数据但不创建备份（数据已另行保存时使用，例如合并进归档）
+    async fn delete_without_backup(&self, key: &str) -> Result<(), PersistenceError> {
+        self.delete(key).await
+    }
+}
+
+/// 按配置的后端创建的存储实例
+pub type BoxedStore<T> = Box<dyn DataStore<T> + Send + Sync>;
+
+/// 按 `PersistenceConfig::backend` 创建存储实例
+pub fn open_store<T>(config: &PersistenceConfig, namespace: &str) -> BoxedStore<T>
+where
+    T: Clone + Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static,
+{
+    match config.backend {
+        PersistenceBackend::FileSystem => {
+            Box::new(FileSystemStore::new(config.clone(), namespace.to_string()))
+        }
+        PersistenceBackend::Sqlite => {
+            Box::new(storage::SqliteStore::new(config.clone(), namespace.to_string()))
+        }
+        PersistenceBackend::Memory => Box::new(storage::MemoryStorage::new()),
+    }
+}
+
+/// 创建目录；权限不足时返回 `PermissionError`，其他原因返回 `DirectoryUnavailable`
+pub(crate) async fn create_directory(dir: &Path) -> Result<(), PersistenceError> {
+    fs::create_dir_all(dir).await.map_err(|e| directory_error(dir, e))
+}
+
+fn directory_error(dir: &Path, error: std::io::Error) -> PersistenceError {
+    let message = format!("无法创建目录 {}: {}", dir.display(), error);
+    if error.kind() == std::io::ErrorKind::PermissionDenied {
+        PersistenceError::PermissionError(message)
+    } else {
+        PersistenceError::DirectoryUnavailable(message)
+    }
 }
 
 /// 文件系统存储实现
 pub struct FileSystemStore<T> {
     config: PersistenceConfig,
     namespace: String,
+    /// 启用静态加密时派生的密钥；主密钥不可用时保存错误信息，读写加密数据时返回
+    cipher: Result<Option<PayloadCipher>, String>,
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -90,37 +252,90 @@ where
     T: Serialize + for<'de> Deserialize<'de>,
 {
     pub fn new(config: PersistenceConfig, namespace: String) -> Self {
+        let cipher = PayloadCipher::from_config(&config.encryption).map_err(|e| e.to_string());
         Self {
             config,
             namespace,
+            cipher,
             _phantom: std::marker::PhantomData,
         }
     }
     
-    /// 获取文件路径
+    /// 启用静态加密时使用的密钥
+    fn cipher(&self) -> Result<Option<&PayloadCipher>, PersistenceError> {
+        self.cipher
+            .as_ref()
+            .map(Option::as_ref)
+            .map_err(|e| PersistenceError::EncryptionError(e.clone()))
+    }
+    
+    /// 获取文件路径（使用配置的写入格式与压缩设置）
     fn get_file_path(&self, key: &str) -> PathBuf {
-        self.config.data_dir
-            .join(&self.namespace)
-            .join(format!("{}.json", key))
+        self.get_file_path_for(key, self.config.format, self.config.enable_compression)
+    }
+
+    /// 获取指定格式的文件路径，压缩文件追加 `.gz`
+    fn get_file_path_for(&self, key: &str, format: PersistenceFormat, compressed: bool) -> PathBuf {
+        let file_name = if compressed {
+            format!("{}.{}.{}", key, format.extension(), GZIP_EXTENSION)
+        } else {
+            format!("{}.{}", key, format.extension())
+        };
+        self.config.data_dir.join(&self.namespace).join(file_name)
+    }
+
+    /// 所有可能的文件变体（格式与是否压缩），配置的写入方式排在最前
+    fn file_variants(&self) -> impl Iterator<Item = (PersistenceFormat, bool)> {
+        let preferred = (self.config.format, self.config.enable_compression);
+        std::iter::once(preferred).chain(
+            PersistenceFormat::ALL
+                .into_iter()
+                .flat_map(|format| [(format, false), (format, true)])
+                .filter(move |variant| *variant != preferred),
+        )
+    }
+
+    /// 查找已存在的数据文件，优先使用配置的格式，兼容其他格式或压缩设置写入的旧文件
+    fn find_existing_file(&self, key: &str) -> Option<PathBuf> {
+        self.file_variants()
+            .map(|(format, compressed)| self.get_file_path_for(key, format, compressed))
+            .find(|path| path.exists())
+    }
+
+    /// 从数据文件名解析键，不是数据文件时返回 None
+    fn key_from_file_name(file_name: &str) -> Option<&str> {
+        let name = file_name
+            .strip_suffix(GZIP_EXTENSION)
+            .and_then(|name| name.strip_suffix('.'))
+            .unwrap_or(file_name);
+        PersistenceFormat::ALL.iter().find_map(|format| {
+            name.strip_suffix(format.extension())?.strip_suffix('.')
+        })
     }
     
     /// 确保目录存在
     async fn ensure_directory(&self) -> Result<(), PersistenceError> {
         let dir = self.config.data_dir.join(&self.namespace);
         if !dir.exists() {
-            fs::create_dir_all(&dir).await?;
+            create_directory(&dir).await?;
         }
         Ok(())
     }
     
     /// 创建备份
     async fn create_backup(&self, key: &str) -> Result<(), PersistenceError> {
-        let original_path = self.get_file_path(key);
-        if original_path.exists() {
+        if let Some(original_path) = self.find_existing_file(key) {
+            // 保留完整扩展名（如 `.json.gz`），压缩文件原样复制
+            let suffix = original_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(key))
+                .unwrap_or(".json")
+                .to_string();
             let backup_path = self.config.data_dir
                 .join(&self.namespace)
                 .join("backups")
-                .join(format!("{}_{}.json", key, chrono::Utc::now().timestamp()));
+                .join(format!("{}_{}{}", key, chrono::Utc::now().timestamp(), suffix));
             
             if let Some(parent) = backup_path.parent() {
                 fs::create_dir_all(parent).await?;
@@ -170,48 +385,68 @@ where
         self.create_backup(key).await.ok(); // 忽略备份错误
         
         let file_path = self.get_file_path(key);
-        let json_data = serde_json::to_string_pretty(data)?;
+        let mut encoded = self.config.format.encode(data)?;
+        if self.config.enable_compression {
+            encoded = gzip_compress(&encoded)?;
+        }
+        if let Some(cipher) = self.cipher()? {
+            encoded = cipher.encrypt(&encoded)?;
+        }
         
         // 检查文件大小
-        if json_data.len() as u64 > self.config.max_file_size {
+        if encoded.len() as u64 > self.config.max_file_size {
             return Err(PersistenceError::InvalidFormat(
-                format!("文件大小超过限制: {} bytes", json_data.len())
+                format!("文件大小超过限制: {} bytes", encoded.len())
             ));
         }
         
         // 写入临时文件，然后重命名（原子操作）
         let temp_path = file_path.with_extension("tmp");
         let mut file = fs::File::create(&temp_path).await?;
-        file.write_all(json_data.as_bytes()).await?;
+        file.write_all(&encoded).await?;
         file.sync_all().await?;
         
         fs::rename(&temp_path, &file_path).await?;
         
+        // 删除以其他格式或压缩设置保存的旧文件，避免同一个键存在多份数据
+        for (format, compressed) in self.file_variants().skip(1) {
+            let stale_path = self.get_file_path_for(key, format, compressed);
+            if stale_path.exists() {
+                fs::remove_file(&stale_path).await?;
+            }
+        }
+        
         Ok(())
     }
     
     async fn load(&self, key: &str) -> Result<T, PersistenceError> {
-        let file_path = self.get_file_path(key);
-        
-        if !file_path.exists() {
-            return Err(PersistenceError::DataNotFound(key.to_string()));
-        }
+        let file_path = self
+            .find_existing_file(key)
+            .ok_or_else(|| PersistenceError::DataNotFound(key.to_string()))?;
         
         let mut file = fs::File::open(&file_path).await?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).await?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        // 未加密的旧文件直接读取
+        if is_encrypted(&contents) {
+            let cipher = self.cipher()?.ok_or_else(|| {
+                PersistenceError::EncryptionError(format!("数据 {} 已加密，需要启用静态加密并提供主密钥", key))
+            })?;
+            contents = cipher.decrypt(&contents)?;
+        }
+        if file_path.extension().is_some_and(|ext| ext == GZIP_EXTENSION) {
+            contents = gzip_decompress(&contents)?;
+        }
         
-        let data = serde_json::from_str(&contents)?;
-        Ok(data)
+        // 按内容识别格式，兼容切换格式前写入的文件
+        PersistenceFormat::detect(&contents).decode(&contents)
     }
     
     async fn delete(&self, key: &str) -> Result<(), PersistenceError> {
-        let file_path = self.get_file_path(key);
-        
-        if file_path.exists() {
+        if self.find_existing_file(key).is_some() {
             // 创建备份
             self.create_backup(key).await.ok();
-            fs::remove_file(&file_path).await?;
+            self.delete_without_backup(key).await?;
         }
         
         Ok(())
@@ -229,11 +464,16 @@ where
         
         while let Some(entry) = entries.next_entry().await? {
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
-                if let Some(stem) = path.file_stem() {
-                    if let Some(key) = stem.to_str() {
-                        keys.push(key.to_string());
-                    }
+            if !path.is_file() {
+                continue;
+            }
+            let key = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(Self::key_from_file_name);
+            if let Some(key) = key {
+                if !keys.iter().any(|k| k == key) {
+                    keys.push(key.to_string());
                 }
             }
         }
@@ -242,33 +482,100 @@ where
     }
     
     async fn exists(&self, key: &str) -> Result<bool, PersistenceError> {
-        let file_path = self.get_file_path(key);
-        Ok(file_path.exists())
+        Ok(self.find_existing_file(key).is_some())
+    }
+    
+    async fn delete_without_backup(&self, key: &str) -> Result<(), PersistenceError> {
+        for (format, compressed) in self.file_variants() {
+            let file_path = self.get_file_path_for(key, format, compressed);
+            if file_path.exists() {
+                fs::remove_file(&file_path).await?;
+            }
+        }
+        Ok(())
     }
 }
 
 /// 数据存储管理器
 pub struct StorageManager {
     config: PersistenceConfig,
+    /// 数据目录不可用且策略为 `MemoryOnly` 时置位，之后创建的存储只保存在内存中
+    memory_only: AtomicBool,
+    audit_log: Option<SharedAuditLog>,
 }
 
 impl StorageManager {
     pub fn new(config: PersistenceConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            memory_only: AtomicBool::new(false),
+            audit_log: None,
+        }
+    }
+    
+    /// 数据目录不可用时写入审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
     }
     
-    /// 创建存储实例
-    pub fn create_store<T>(&self, namespace: &str) -> FileSystemStore<T>
-    where
-        T: Serialize + for<'de> Deserialize<'de>,
-    {
-        FileSystemStore::new(self.config.clone(), namespace.to_string())
+    /// 是否已退化为仅内存存储
+    pub fn is_memory_only(&self) -> bool {
+        self.memory_only.load(Ordering::Relaxed)
+    }
+    
+    /// 实际生效的配置：退化为仅内存存储后使用内存后端
+    pub fn effective_config(&self) -> PersistenceConfig {
+        let mut config = self.config.clone();
+        if self.is_memory_only() {
+            config.backend = PersistenceBackend::Memory;
+        }
+        config
     }
     
     /// 初始化存储目录
+    ///
+    /// 目录无法创建时记录审计日志，并按 `on_unavailable_dir` 返回错误或退化为仅内存存储
     pub async fn initialize(&self) -> Result<(), PersistenceError> {
+        if self.config.backend == PersistenceBackend::Memory {
+            return Ok(());
+        }
+        // 启用静态加密但主密钥不可用时直接失败，避免之后每次写入才报错
+        PayloadCipher::from_config(&self.config.encryption)?;
+        let error = match self.create_directories().await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        
+        let fallback = self.config.on_unavailable_dir == UnavailableDirPolicy::MemoryOnly;
+        if let Some(audit_log) = &self.audit_log {
+            let details = if fallback {
+                format!("{}，改用内存存储", error)
+            } else {
+                error.to_string()
+            };
+            if let Err(e) = audit_log
+                .lock()
+                .await
+                .log_system_operation("初始化数据目录", "persistence", AuditResult::Failure, Some(details))
+                .await
+            {
+                tracing::warn!("记录数据目录审计日志失败: {}", e);
+            }
+        }
+        
+        if fallback {
+            tracing::warn!("{}，持久化数据只保存在内存中，进程退出后丢失", error);
+            self.memory_only.store(true, Ordering::Relaxed);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+    
+    async fn create_directories(&self) -> Result<(), PersistenceError> {
         if !self.config.data_dir.exists() {
-            fs::create_dir_all(&self.config.data_dir).await?;
+            create_directory(&self.config.data_dir).await?;
         }
         
         // 创建基本子目录
@@ -276,7 +583,7 @@ impl StorageManager {
         for subdir in &subdirs {
             let dir_path = self.config.data_dir.join(subdir);
             if !dir_path.exists() {
-                fs::create_dir_all(&dir_path).await?;
+                create_directory(&dir_path).await?;
             }
         }
         
@@ -373,6 +680,154 @@ mod tests {
         assert!(!store.exists("test_key").await.unwrap());
     }
     
+    #[tokio::test]
+    async fn test_message_pack_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            format: PersistenceFormat::MessagePack,
+            ..Default::default()
+        };
+        
+        let store: FileSystemStore<TestData> = FileSystemStore::new(config, "test".to_string());
+        let test_data = TestData {
+            value: "二进制数据".to_string(),
+            number: 7,
+        };
+        
+        store.save("msgpack_key", &test_data).await.unwrap();
+        
+        let file_path = temp_dir.path().join("test").join("msgpack_key.msgpack");
+        assert!(file_path.exists());
+        let raw = std::fs::read(&file_path).unwrap();
+        assert_eq!(PersistenceFormat::detect(&raw), PersistenceFormat::MessagePack);
+        
+        assert_eq!(store.load("msgpack_key").await.unwrap(), test_data);
+        assert_eq!(store.list_keys().await.unwrap(), vec!["msgpack_key".to_string()]);
+    }
+    
+    #[tokio::test]
+    async fn test_mixed_format_directory_compatibility() {
+        let temp_dir = tempdir().unwrap();
+        let json_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let msgpack_config = PersistenceConfig {
+            format: PersistenceFormat::MessagePack,
+            ..json_config.clone()
+        };
+        
+        let json_store: FileSystemStore<TestData> = FileSystemStore::new(json_config, "test".to_string());
+        let msgpack_store: FileSystemStore<TestData> = FileSystemStore::new(msgpack_config, "test".to_string());
+        
+        let old = TestData { value: "旧数据".to_string(), number: 1 };
+        let new = TestData { value: "新数据".to_string(), number: 2 };
+        json_store.save("old_key", &old).await.unwrap();
+        msgpack_store.save("new_key", &new).await.unwrap();
+        
+        // 两种格式的存储都能读取目录中的全部数据
+        for store in [&json_store, &msgpack_store] {
+            assert_eq!(store.load("old_key").await.unwrap(), old);
+            assert_eq!(store.load("new_key").await.unwrap(), new);
+            let mut keys = store.list_keys().await.unwrap();
+            keys.sort();
+            assert_eq!(keys, vec!["new_key".to_string(), "old_key".to_string()]);
+        }
+        
+        // 以新格式重写旧数据后不会残留旧格式文件
+        msgpack_store.save("old_key", &old).await.unwrap();
+        assert!(!temp_dir.path().join("test").join("old_key.json").exists());
+        assert!(temp_dir.path().join("test").join("old_key.msgpack").exists());
+        
+        msgpack_store.delete("new_key").await.unwrap();
+        assert!(!json_store.exists("new_key").await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_compression_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let plain_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let compressed_config = PersistenceConfig {
+            enable_compression: true,
+            ..plain_config.clone()
+        };
+        
+        let plain: FileSystemStore<TestData> = FileSystemStore::new(plain_config, "test".to_string());
+        let compressed: FileSystemStore<TestData> = FileSystemStore::new(compressed_config, "test".to_string());
+        let test_data = TestData { value: "压缩数据".repeat(100), number: 9 };
+        let dir = temp_dir.path().join("test");
+        
+        plain.save("plain_key", &test_data).await.unwrap();
+        compressed.save("gz_key", &test_data).await.unwrap();
+        assert!(dir.join("plain_key.json").exists());
+        assert!(dir.join("gz_key.json.gz").exists());
+        assert!(std::fs::metadata(dir.join("gz_key.json.gz")).unwrap().len()
+            < std::fs::metadata(dir.join("plain_key.json")).unwrap().len());
+        
+        // 两种设置都能读取压缩与未压缩的文件，键名去掉两层扩展名
+        for store in [&plain, &compressed] {
+            assert_eq!(store.load("plain_key").await.unwrap(), test_data);
+            assert_eq!(store.load("gz_key").await.unwrap(), test_data);
+            let mut keys = store.list_keys().await.unwrap();
+            keys.sort();
+            assert_eq!(keys, vec!["gz_key".to_string(), "plain_key".to_string()]);
+        }
+        
+        // 覆盖写入时备份压缩文件，并删除未压缩的旧文件
+        compressed.save("plain_key", &test_data).await.unwrap();
+        compressed.save("gz_key", &test_data).await.unwrap();
+        assert!(!dir.join("plain_key.json").exists());
+        let backups: Vec<String> = std::fs::read_dir(dir.join("backups"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(backups.iter().any(|name| name.starts_with("gz_key_") && name.ends_with(".json.gz")));
+        assert!(backups.iter().any(|name| name.starts_with("plain_key_") && name.ends_with(".json")));
+        
+        compressed.delete("gz_key").await.unwrap();
+        assert!(!plain.exists("gz_key").await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_encrypted_store_reads_legacy_plaintext_files() {
+        let secret_env = "GEMINI_PROXY_TEST_STORE_MASTER_KEY";
+        std::env::set_var(secret_env, "file-store-master-secret");
+        let temp_dir = tempdir().unwrap();
+        let plain_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let encrypted_config = PersistenceConfig {
+            encryption: EncryptionConfig { enabled: true, master_secret_env: secret_env.to_string() },
+            ..plain_config.clone()
+        };
+        let plain: FileSystemStore<TestData> = FileSystemStore::new(plain_config, "test".to_string());
+        let encrypted: FileSystemStore<TestData> = FileSystemStore::new(encrypted_config.clone(), "test".to_string());
+        let legacy = TestData { value: "旧的明文数据".to_string(), number: 1 };
+        
+        // 启用加密前写入的明文文件照常读取，重新保存后加密，键名不变
+        plain.save("legacy_key", &legacy).await.unwrap();
+        assert_eq!(encrypted.load("legacy_key").await.unwrap(), legacy);
+        encrypted.save("legacy_key", &legacy).await.unwrap();
+        let raw = std::fs::read(temp_dir.path().join("test").join("legacy_key.json")).unwrap();
+        assert!(is_encrypted(&raw));
+        assert_eq!(encrypted.load("legacy_key").await.unwrap(), legacy);
+        assert_eq!(encrypted.list_keys().await.unwrap(), vec!["legacy_key".to_string()]);
+        
+        // 未启用加密的存储无法读取加密数据；主密钥缺失时写入失败
+        assert!(matches!(plain.load("legacy_key").await, Err(PersistenceError::EncryptionError(_))));
+        let missing_secret = PersistenceConfig {
+            encryption: EncryptionConfig { enabled: true, master_secret_env: "GEMINI_PROXY_TEST_UNSET_MASTER_KEY".to_string() },
+            ..encrypted_config
+        };
+        let unusable: FileSystemStore<TestData> = FileSystemStore::new(missing_secret, "test".to_string());
+        assert!(matches!(unusable.save("other", &legacy).await, Err(PersistenceError::EncryptionError(_))));
+    }
+    
     #[tokio::test]
     async fn test_storage_manager() {
         let temp_dir = tempdir().unwrap();
@@ -393,4 +848,53 @@ mod tests {
         let stats = manager.get_storage_stats().await.unwrap();
         assert_eq!(stats.file_count, 0);
     }
+    
+    #[tokio::test]
+    async fn test_uncreatable_data_dir_follows_policy() {
+        use crate::security::{AuditConfig, AuditEventType, AuditLogManager};
+        
+        // 父路径是普通文件时目录无法创建（以 root 运行时权限检查不生效，因此不依赖文件权限）
+        let temp_dir = tempdir().unwrap();
+        let blocker = temp_dir.path().join("blocker");
+        std::fs::write(&blocker, b"").unwrap();
+        let config = |policy| PersistenceConfig {
+            data_dir: blocker.join("data"),
+            on_unavailable_dir: policy,
+            ..Default::default()
+        };
+        let audit_log: SharedAuditLog = std::sync::Arc::new(tokio::sync::Mutex::new(AuditLogManager::new(AuditConfig {
+            file_output_enabled: false,
+            ..AuditConfig::default()
+        })));
+        
+        let manager = StorageManager::new(config(UnavailableDirPolicy::FailFast)).with_audit_log(audit_log.clone());
+        let err = manager.initialize().await.unwrap_err();
+        assert!(matches!(err, PersistenceError::DirectoryUnavailable(_)), "{:?}", err);
+        assert!(matches!(
+            crate::error::GeminiProxyError::from(err),
+            crate::error::GeminiProxyError::Storage { .. }
+        ));
+        assert!(!manager.is_memory_only());
+        
+        let manager = StorageManager::new(config(UnavailableDirPolicy::MemoryOnly)).with_audit_log(audit_log.clone());
+        manager.initialize().await.unwrap();
+        assert!(manager.is_memory_only());
+        let store = manager.create_store::<TestData>("sessions");
+        let data = TestData { value: "内存".to_string(), number: 1 };
+        store.save("k", &data).await.unwrap();
+        assert_eq!(store.load("k").await.unwrap(), data);
+        assert!(!blocker.join("data").exists());
+        
+        let audit_log = audit_log.lock().await;
+        let events = audit_log.get_logs_by_type(AuditEventType::SystemOperation, 10);
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.result == AuditResult::Failure));
+        
+        // 权限不足映射为权限错误
+        let err = directory_error(Path::new("/data"), std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(matches!(
+            crate::error::GeminiProxyError::from(err),
+            crate::error::GeminiProxyError::Permission { .. }
+        ));
+    }
 }
\ No newline at end of file
@@ -3,7 +3,8 @@
 //! 
 //! 提供用户会话的持久化存储，支持会话恢复、跨服务器实例共享会话状态
 
-use super::{DataStore, FileSystemStore, PersistenceConfig, PersistenceError};
+use super::{open_store, BoxedStore, DataStore, PersistenceConfig, PersistenceError};
+use crate::metrics::MetricsCollector;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -102,13 +103,15 @@ pub struct SessionQuery {
 /// 会话存储管理器
 pub struct SessionStore {
     /// 会话数据存储
-    session_store: FileSystemStore<PersistentSession>,
+    session_store: BoxedStore<PersistentSession>,
     /// 登录活动存储
-    activity_store: FileSystemStore<Vec<LoginActivity>>,
+    activity_store: BoxedStore<Vec<LoginActivity>>,
     /// 内存缓存
     cache: Arc<RwLock<HashMap<String, PersistentSession>>>,
     /// 配置
     config: SessionStoreConfig,
+    /// 会话指标收集器（可选）
+    metrics: Option<Arc<MetricsCollector>>,
 }
 
 /// 会话存储配置
@@ -124,6 +127,8 @@ pub struct SessionStoreConfig {
     pub max_sessions: usize,
     /// 活动日志保留天数
     pub activity_retention_days: i64,
+    /// 会话绝对最长存活时间：无论是否活跃或刷新，会话在 `created_at + absolute_max_lifetime` 时过期
+    pub absolute_max_lifetime: Option<Duration>,
 }
 
 impl Default for SessionStoreConfig {
@@ -134,6 +139,7 @@ impl Default for SessionStoreConfig {
             cleanup_interval: 300, // 5分钟
             max_sessions: 10000,
             activity_retention_days: 30,
+            absolute_max_lifetime: None,
         }
     }
 }
@@ -141,17 +147,24 @@ impl Default for SessionStoreConfig {
 impl SessionStore {
     /// 创建新的会话存储
     pub fn new(persistence_config: PersistenceConfig, store_config: SessionStoreConfig) -> Self {
-        let session_store = FileSystemStore::new(persistence_config.clone(), "sessions".to_string());
-        let activity_store = FileSystemStore::new(persistence_config, "session_activities".to_string());
+        let session_store = open_store(&persistence_config, "sessions");
+        let activity_store = open_store(&persistence_config, "session_activities");
         
         Self {
             session_store,
             activity_store,
             cache: Arc::new(RwLock::new(HashMap::new())),
             config: store_config,
+            metrics: None,
         }
     }
     
+    /// 关联指标收集器，用于上报活跃会话数和会话年龄分布
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    
     /// 初始化会话存储
     pub async fn initialize(&self) -> Result<(), PersistenceError> {
         if self.config.enable_cache {
@@ -161,6 +174,12 @@ impl SessionStore {
         // 清理过期会话
         self.cleanup_expired_sessions().await.ok();
         
+        // 同步活跃会话数指标
+        if let Some(metrics) = &self.metrics {
+            let active_count = self.count_active_sessions().await.unwrap_or(0);
+            metrics.set_active_sessions(active_count as i64).await;
+        }
+        
         tracing::info!("会话存储初始化完成");
         Ok(())
     }
@@ -182,7 +201,7 @@ impl SessionStore {
             user_id: user_id.to_string(),
             created_at: now,
             last_activity: now,
-            expires_at: now + timeout,
+            expires_at: self.cap_expiry(now, now + timeout),
             is_active: true,
             refresh_token: Some(uuid::Uuid::new_v4().to_string()),
             client_info,
@@ -201,6 +220,10 @@ impl SessionStore {
         // 记录登录活动
         self.record_login_activity(&session, true).await.ok();
         
+        if let Some(metrics) = &self.metrics {
+            metrics.record_session_created().await;
+        }
+        
         tracing::info!("已创建会话: {} (用户: {})", session_id, user_id);
         Ok(session)
     }
@@ -209,9 +232,13 @@ impl SessionStore {
     pub async fn get_session(&self, session_id: &str) -> Result<Option<PersistentSession>, PersistenceError> {
         // 首先从缓存获取
         if self.config.enable_cache {
-            let cache = self.cache.read().await;
-            if let Some(session) = cache.get(session_id) {
-                return Ok(Some(session.clone()));
+            let cached = self.cache.read().await.get(session_id).cloned();
+            if let Some(session) = cached {
+                if !self.is_expired(&session, Utc::now()) {
+                    return Ok(Some(session));
+                }
+                self.cache.write().await.remove(session_id);
+                return Ok(None);
             }
         }
         
@@ -219,7 +246,7 @@ impl SessionStore {
         match self.session_store.load(session_id).await {
             Ok(session) => {
                 // 检查是否过期
-                if session.expires_at < Utc::now() {
+                if self.is_expired(&session, Utc::now()) {
                     return Ok(None);
                 }
                 
@@ -287,6 +314,8 @@ impl SessionStore {
     
     /// 删除会话
     pub async fn delete_session(&self, session_id: &str) -> Result<(), PersistenceError> {
+        let existing = self.session_store.load(session_id).await.ok();
+        
         // 从存储删除
         self.session_store.delete(session_id).await?;
         
@@ -295,6 +324,12 @@ impl SessionStore {
             self.cache.write().await.remove(session_id);
         }
         
+        if let Some(session) = existing {
+            if session.is_active && session.expires_at > Utc::now() {
+                self.record_session_ended(&session).await;
+            }
+        }
+        
         tracing::info!("已删除会话: {}", session_id);
         Ok(())
     }
@@ -304,6 +339,7 @@ impl SessionStore {
         let mut session = self.get_session(session_id).await?
             .ok_or_else(|| PersistenceError::DataNotFound(session_id.to_string()))?;
         
+        let was_active = session.is_active;
         session.is_active = false;
         session.expires_at = Utc::now(); // 立即过期
         
@@ -315,6 +351,10 @@ impl SessionStore {
             self.cache.write().await.remove(session_id);
         }
         
+        if was_active {
+            self.record_session_ended(&session).await;
+        }
+        
         tracing::info!("已使会话失效: {}", session_id);
         Ok(())
     }
@@ -329,8 +369,10 @@ impl SessionStore {
             .ok_or_else(|| PersistenceError::DataNotFound(session_id.to_string()))?;
         
         let extension = extend_duration.unwrap_or_else(|| Duration::minutes(self.config.default_session_timeout));
-        session.expires_at = Utc::now() + extension;
-        session.last_activity = Utc::now();
+        let now = Utc::now();
+        // 刷新不能超过绝对最长存活时间
+        session.expires_at = self.cap_expiry(session.created_at, now + extension);
+        session.last_activity = now;
         
         // 保存更新
         self.session_store.save(session_id, &session).await?;
@@ -340,6 +382,11 @@ impl SessionStore {
             self.cache.write().await.insert(session_id.to_string(), session.clone());
         }
         
+        if let Some(metrics) = &self.metrics {
+            let age = (session.last_activity - session.created_at).to_std().unwrap_or_default();
+            metrics.record_session_refreshed(age).await;
+        }
+        
         tracing::debug!("已刷新会话: {}", session_id);
         Ok(session)
     }
@@ -407,9 +454,14 @@ impl SessionStore {
         
         for session_id in session_ids {
             if let Ok(session) = self.session_store.load(&session_id).await {
-                if session.expires_at < now || !session.is_active {
+                if self.is_expired(&session, now) || !session.is_active {
                     self.session_store.delete(&session_id).await.ok();
                     
+                    // 已失效的会话在失效时已计入指标，这里只处理自然过期的会话
+                    if session.is_active {
+                        self.record_session_ended(&session).await;
+                    }
+                    
                     // 从缓存删除
                     if self.config.enable_cache {
                         self.cache.write().await.remove(&session_id);
@@ -495,6 +547,50 @@ impl SessionStore {
     
     // 私有辅助方法
     
+    /// 会话是否已过期（包括超过绝对最长存活时间）
+    fn is_expired(&self, session: &PersistentSession, now: DateTime<Utc>) -> bool {
+        if session.expires_at < now {
+            return true;
+        }
+        match self.config.absolute_max_lifetime {
+            Some(max_lifetime) => session.created_at + max_lifetime <= now,
+            None => false,
+        }
+    }
+    
+    /// 将过期时间限制在绝对最长存活时间之内
+    fn cap_expiry(&self, created_at: DateTime<Utc>, expires_at: DateTime<Utc>) -> DateTime<Utc> {
+        match self.config.absolute_max_lifetime {
+            Some(max_lifetime) => expires_at.min(created_at + max_lifetime),
+            None => expires_at,
+        }
+    }
+    
+    /// 统计当前活跃会话数
+    async fn count_active_sessions(&self) -> Result<usize, PersistenceError> {
+        let session_ids = self.session_store.list_keys().await?;
+        let now = Utc::now();
+        let mut count = 0;
+        
+        for session_id in session_ids {
+            if let Ok(session) = self.session_store.load(&session_id).await {
+                if session.is_active && session.expires_at > now {
+                    count += 1;
+                }
+            }
+        }
+        
+        Ok(count)
+    }
+    
+    /// 上报会话结束指标
+    async fn record_session_ended(&self, session: &PersistentSession) {
+        if let Some(metrics) = &self.metrics {
+            let age = (Utc::now() - session.created_at).to_std().unwrap_or_default();
+            metrics.record_session_ended(age).await;
+        }
+    }
+    
     /// 加载活跃会话到缓存
     async fn load_active_sessions_to_cache(&self) -> Result<(), PersistenceError> {
         let session_ids = self.session_store.list_keys().await?;
@@ -668,4 +764,143 @@ mod tests {
         let invalidated = session_store.get_session(&session.session_id).await.unwrap();
         assert!(invalidated.is_none());
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_session_metrics() {
+        let temp_dir = tempdir().unwrap();
+        let persistence_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        
+        let metrics = Arc::new(MetricsCollector::new());
+        let session_store = SessionStore::new(persistence_config, SessionStoreConfig::default())
+            .with_metrics(metrics.clone());
+        session_store.initialize().await.unwrap();
+        
+        let client_info = ClientInfo {
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: None,
+            device_type: None,
+            location: None,
+        };
+        
+        let short_lived = session_store.create_session(
+            "user1",
+            client_info.clone(),
+            vec![],
+            Some(Duration::milliseconds(10)),
+        ).await.unwrap();
+        let long_lived = session_store.create_session("user2", client_info, vec![], None).await.unwrap();
+        
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_session_active 2"));
+        assert!(output.contains("gemini_proxy_session_age_seconds_count 0"));
+        
+        // 刷新会话记录年龄
+        session_store.refresh_session(&long_lived.session_id, None).await.unwrap();
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_session_active 2"));
+        assert!(output.contains("gemini_proxy_session_age_seconds_count 1"));
+        
+        // 自然过期的会话被清理后计入指标
+        tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+        let cleaned = session_store.cleanup_expired_sessions().await.unwrap();
+        assert_eq!(cleaned, 1);
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_session_active 1"));
+        assert!(output.contains("gemini_proxy_session_age_seconds_count 2"));
+        
+        // 主动失效同样更新指标，且后续清理不会重复计数
+        session_store.invalidate_session(&long_lived.session_id).await.unwrap();
+        session_store.cleanup_expired_sessions().await.unwrap();
+        let output = metrics.get_metrics();
+        assert!(output.contains("gemini_proxy_session_active 0"));
+        assert!(output.contains("gemini_proxy_session_age_seconds_count 3"));
+        
+        assert!(session_store.get_session(&short_lived.session_id).await.unwrap().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_absolute_max_lifetime_expires_refreshed_session() {
+        let temp_dir = tempdir().unwrap();
+        let persistence_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let store_config = SessionStoreConfig {
+            absolute_max_lifetime: Some(Duration::milliseconds(200)),
+            ..Default::default()
+        };
+        
+        let session_store = SessionStore::new(persistence_config, store_config);
+        session_store.initialize().await.unwrap();
+        
+        let client_info = ClientInfo {
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: None,
+            device_type: None,
+            location: None,
+        };
+        let session = session_store.create_session("user1", client_info, vec![], None).await.unwrap();
+        assert_eq!(session.expires_at, session.created_at + Duration::milliseconds(200));
+        
+        // 持续刷新与活动不会把过期时间延长到绝对上限之后
+        for _ in 0..3 {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            let refreshed = session_store.refresh_session(&session.session_id, None).await.unwrap();
+            assert!(refreshed.expires_at <= session.created_at + Duration::milliseconds(200));
+            session_store.update_session_activity(&session.session_id).await.unwrap();
+        }
+        
+        tokio::time::sleep(std::time::Duration::from_millis(80)).await;
+        assert!(session_store.get_session(&session.session_id).await.unwrap().is_none());
+        assert!(matches!(
+            session_store.refresh_session(&session.session_id, None).await,
+            Err(PersistenceError::DataNotFound(_))
+        ));
+        assert_eq!(session_store.cleanup_expired_sessions().await.unwrap(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_encrypted_session_file_contains_no_plaintext_refresh_token() {
+        let secret_env = "GEMINI_PROXY_TEST_SESSION_MASTER_KEY";
+        std::env::set_var(secret_env, "session-store-master-secret");
+        let temp_dir = tempdir().unwrap();
+        let persistence_config = PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            encryption: crate::persistence::encryption::EncryptionConfig {
+                enabled: true,
+                master_secret_env: secret_env.to_string(),
+            },
+            ..Default::default()
+        };
+        
+        let session_store = SessionStore::new(persistence_config, SessionStoreConfig::default());
+        session_store.initialize().await.unwrap();
+        let client_info = ClientInfo {
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: None,
+            device_type: None,
+            location: None,
+        };
+        let session = session_store.create_session("user1", client_info, vec![], None).await.unwrap();
+        let refresh_token = session.refresh_token.clone().unwrap();
+        
+        let session_files: Vec<Vec<u8>> = std::fs::read_dir(temp_dir.path().join("sessions"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_file())
+            .map(|path| std::fs::read(path).unwrap())
+            .collect();
+        assert!(!session_files.is_empty());
+        for contents in &session_files {
+            assert!(crate::persistence::encryption::is_encrypted(contents));
+            assert!(!contents.windows(refresh_token.len()).any(|window| window == refresh_token.as_bytes()));
+        }
+        
+        // 读取时透明解密
+        let loaded = session_store.get_session(&session.session_id).await.unwrap().unwrap();
+        assert_eq!(loaded.refresh_token, Some(refresh_token));
+    }
+}
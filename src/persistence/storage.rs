@@ -3,11 +3,16 @@
 //! 
 //! 提供统一的存储抽象层，支持多种存储后端
 
-use super::{PersistenceConfig, PersistenceError};
+use super::encryption::{is_encrypted, PayloadCipher};
+use super::{gzip_compress, gzip_decompress, DataStore, PersistenceConfig, PersistenceError, PersistenceFormat};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OnceCell, RwLock};
 
 /// 存储后端类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -343,6 +348,240 @@ where
     }
 }
 
+/// SQLite 数据库文件名（位于数据目录下，所有命名空间共用）
+pub const SQLITE_FILE_NAME: &str = "store.sqlite3";
+
+/// gzip 数据开头的魔数
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// SQLite 存储实现
+///
+/// 所有命名空间共用数据目录下的同一个数据库文件，数据保存在 `(namespace, key, json)` 表中。
+/// 未启用压缩与加密时保存的 JSON 与 `FileSystemStore` 的 JSON 文件内容完全一致，可通过 `import_from` 迁移已有数据；
+/// 启用时与文件存储相同，先 gzip 压缩再加密，以 BLOB 保存。
+/// 写入在事务中完成，取代临时文件重命名。连接在首次使用时打开，数据库操作在阻塞线程池中执行
+pub struct SqliteStore<T> {
+    config: PersistenceConfig,
+    namespace: String,
+    connection: OnceCell<Arc<Mutex<Connection>>>,
+    /// 启用静态加密时派生的密钥；主密钥不可用时保存错误信息，读写加密数据时返回
+    cipher: Result<Option<PayloadCipher>, String>,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T> SqliteStore<T>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Send + Sync,
+{
+    pub fn new(config: PersistenceConfig, namespace: String) -> Self {
+        let cipher = PayloadCipher::from_config(&config.encryption).map_err(|e| e.to_string());
+        Self {
+            config,
+            namespace,
+            connection: OnceCell::new(),
+            cipher,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+    
+    /// 启用静态加密时使用的密钥
+    fn cipher(&self) -> Result<Option<&PayloadCipher>, PersistenceError> {
+        self.cipher
+            .as_ref()
+            .map(Option::as_ref)
+            .map_err(|e| PersistenceError::EncryptionError(e.clone()))
+    }
+    
+    /// 数据库文件路径
+    pub fn database_path(&self) -> PathBuf {
+        self.config.data_dir.join(SQLITE_FILE_NAME)
+    }
+    
+    /// 获取连接，首次调用时创建数据库文件和表
+    async fn connection(&self) -> Result<Arc<Mutex<Connection>>, PersistenceError> {
+        self.connection
+            .get_or_try_init(|| async {
+                super::create_directory(&self.config.data_dir).await?;
+                let path = self.database_path();
+                let connection = tokio::task::spawn_blocking(move || open_database(&path))
+                    .await
+                    .map_err(|e| PersistenceError::DatabaseError(e.to_string()))?
+                    .map_err(database_error)?;
+                Ok::<_, PersistenceError>(Arc::new(Mutex::new(connection)))
+            })
+            .await
+            .cloned()
+    }
+    
+    /// 在阻塞线程池中使用连接执行数据库操作
+    async fn with_connection<R, F>(&self, operation: F) -> Result<R, PersistenceError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<R> + Send + 'static,
+    {
+        let connection = self.connection().await?;
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+            operation(&mut connection)
+        })
+        .await
+        .map_err(|e| PersistenceError::DatabaseError(e.to_string()))?
+        .map_err(database_error)
+    }
+    
+    /// 从其他存储（例如 `FileSystemStore`）导入全部数据，返回导入的条数
+    pub async fn import_from<S>(&self, source: &S) -> Result<usize, PersistenceError>
+    where
+        S: DataStore<T> + Sync + ?Sized,
+    {
+        let keys = source.list_keys().await?;
+        for key in &keys {
+            let data = source.load(key).await?;
+            self.save(key, &data).await?;
+        }
+        Ok(keys.len())
+    }
+}
+
+/// 打开数据库：启用 WAL 以便读写并发，并设置忙等待时间
+fn open_database(path: &Path) -> rusqlite::Result<Connection> {
+    let connection = Connection::open(path)?;
+    connection.busy_timeout(Duration::from_secs(5))?;
+    connection.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS data_store (
+            namespace TEXT NOT NULL,
+            key TEXT NOT NULL,
+            json TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (namespace, key)
+        )",
+    )?;
+    Ok(connection)
+}
+
+fn database_error(error: rusqlite::Error) -> PersistenceError {
+    PersistenceError::DatabaseError(error.to_string())
+}
+
+#[async_trait::async_trait]
+impl<T> DataStore<T> for SqliteStore<T>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Send + Sync,
+{
+    async fn save(&self, key: &str, data: &T) -> Result<(), PersistenceError> {
+        // 与 FileSystemStore 的 JSON 文件使用相同的编码
+        let mut encoded = PersistenceFormat::Json.encode(data)?;
+        let plain = !self.config.enable_compression && !self.config.encryption.enabled;
+        if self.config.enable_compression {
+            encoded = gzip_compress(&encoded)?;
+        }
+        if let Some(cipher) = self.cipher()? {
+            encoded = cipher.encrypt(&encoded)?;
+        }
+        if encoded.len() as u64 > self.config.max_file_size {
+            return Err(PersistenceError::InvalidFormat(
+                format!("文件大小超过限制: {} bytes", encoded.len())
+            ));
+        }
+        // 未压缩未加密的 JSON 以文本保存，其余以 BLOB 保存
+        let payload = if plain {
+            let json = String::from_utf8(encoded)
+                .map_err(|e| PersistenceError::InvalidFormat(e.to_string()))?;
+            rusqlite::types::Value::Text(json)
+        } else {
+            rusqlite::types::Value::Blob(encoded)
+        };
+        
+        let namespace = self.namespace.clone();
+        let key = key.to_string();
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute(
+                "INSERT INTO data_store (namespace, key, json, updated_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (namespace, key) DO UPDATE SET json = excluded.json, updated_at = excluded.updated_at",
+                params![namespace, key, payload, chrono::Utc::now().timestamp()],
+            )?;
+            transaction.commit()
+        })
+        .await
+    }
+    
+    async fn load(&self, key: &str) -> Result<T, PersistenceError> {
+        let namespace = self.namespace.clone();
+        let owned_key = key.to_string();
+        let mut contents = self
+            .with_connection(move |connection| {
+                connection
+                    .query_row(
+                        "SELECT json FROM data_store WHERE namespace = ?1 AND key = ?2",
+                        params![namespace, owned_key],
+                        |row| match row.get_ref(0)? {
+                            ValueRef::Text(bytes) | ValueRef::Blob(bytes) => Ok(bytes.to_vec()),
+                            other => Err(rusqlite::Error::InvalidColumnType(0, "json".to_string(), other.data_type())),
+                        },
+                    )
+                    .optional()
+            })
+            .await?
+            .ok_or_else(|| PersistenceError::DataNotFound(key.to_string()))?;
+        
+        // 与文件存储相同：未加密的旧数据直接读取，按内容识别是否压缩
+        if is_encrypted(&contents) {
+            let cipher = self.cipher()?.ok_or_else(|| {
+                PersistenceError::EncryptionError(format!("数据 {} 已加密，需要启用静态加密并提供主密钥", key))
+            })?;
+            contents = cipher.decrypt(&contents)?;
+        }
+        if contents.starts_with(GZIP_MAGIC) {
+            contents = gzip_decompress(&contents)?;
+        }
+        PersistenceFormat::Json.decode(&contents)
+    }
+    
+    async fn delete(&self, key: &str) -> Result<(), PersistenceError> {
+        let namespace = self.namespace.clone();
+        let key = key.to_string();
+        self.with_connection(move |connection| {
+            connection.execute(
+                "DELETE FROM data_store WHERE namespace = ?1 AND key = ?2",
+                params![namespace, key],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+    
+    async fn list_keys(&self) -> Result<Vec<String>, PersistenceError> {
+        let namespace = self.namespace.clone();
+        self.with_connection(move |connection| {
+            let mut statement =
+                connection.prepare("SELECT key FROM data_store WHERE namespace = ?1 ORDER BY key")?;
+            let keys = statement
+                .query_map(params![namespace], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(keys)
+        })
+        .await
+    }
+    
+    async fn exists(&self, key: &str) -> Result<bool, PersistenceError> {
+        let namespace = self.namespace.clone();
+        let key = key.to_string();
+        self.with_connection(move |connection| {
+            connection
+                .query_row(
+                    "SELECT 1 FROM data_store WHERE namespace = ?1 AND key = ?2",
+                    params![namespace, key],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map(|row| row.is_some())
+        })
+        .await
+    }
+}
+
 /// 存储工厂
 pub struct StorageFactory;
 
@@ -509,4 +748,116 @@ mod tests {
         let loaded = proxy.load("proxy_key").await.unwrap();
         assert_eq!(test_data, loaded);
     }
-}
\ No newline at end of file
+    
+    fn sqlite_config(dir: &tempfile::TempDir) -> PersistenceConfig {
+        PersistenceConfig {
+            data_dir: dir.path().to_path_buf(),
+            backend: crate::persistence::PersistenceBackend::Sqlite,
+            ..Default::default()
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_sqlite_store_trait_and_migration() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = sqlite_config(&temp_dir);
+        let store: SqliteStore<TestData> = SqliteStore::new(config.clone(), "test".to_string());
+        let data = TestData { value: "sqlite".to_string(), number: 7 };
+        
+        assert!(matches!(store.load("missing").await, Err(PersistenceError::DataNotFound(_))));
+        store.save("a", &data).await.unwrap();
+        assert!(store.exists("a").await.unwrap());
+        assert_eq!(store.load("a").await.unwrap(), data);
+        
+        // 命名空间相互隔离
+        let other: SqliteStore<TestData> = SqliteStore::new(config.clone(), "other".to_string());
+        assert!(other.list_keys().await.unwrap().is_empty());
+        
+        store.delete("a").await.unwrap();
+        assert!(!store.exists("a").await.unwrap());
+        
+        // 从文件系统存储迁移，保存的 JSON 与文件内容一致
+        let files = crate::persistence::FileSystemStore::new(
+            PersistenceConfig { data_dir: temp_dir.path().join("files"), ..Default::default() },
+            "test".to_string(),
+        );
+        files.save("b", &data).await.unwrap();
+        files.save("c", &data).await.unwrap();
+        assert_eq!(store.import_from(&files).await.unwrap(), 2);
+        assert_eq!(store.list_keys().await.unwrap(), vec!["b".to_string(), "c".to_string()]);
+        
+        let file_json = std::fs::read_to_string(temp_dir.path().join("files/test/b.json")).unwrap();
+        let connection = Connection::open(store.database_path()).unwrap();
+        let stored_json: String = connection
+            .query_row("SELECT json FROM data_store WHERE namespace = 'test' AND key = 'b'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored_json, file_json);
+    }
+    
+    #[tokio::test]
+    async fn test_sqlite_store_encrypts_and_compresses_payload() {
+        let secret_env = "GEMINI_PROXY_TEST_SQLITE_MASTER_KEY";
+        std::env::set_var(secret_env, "sqlite-store-master-secret");
+        let temp_dir = tempfile::tempdir().unwrap();
+        let plain: SqliteStore<TestData> = SqliteStore::new(sqlite_config(&temp_dir), "test".to_string());
+        let encrypted: SqliteStore<TestData> = SqliteStore::new(
+            PersistenceConfig {
+                enable_compression: true,
+                encryption: crate::persistence::encryption::EncryptionConfig {
+                    enabled: true,
+                    master_secret_env: secret_env.to_string(),
+                },
+                ..sqlite_config(&temp_dir)
+            },
+            "test".to_string(),
+        );
+        let data = TestData { value: "refresh-token-secret".to_string(), number: 9 };
+
+        // 启用加密前写入的明文数据照常读取
+        plain.save("legacy", &data).await.unwrap();
+        assert_eq!(encrypted.load("legacy").await.unwrap(), data);
+
+        encrypted.save("sealed", &data).await.unwrap();
+        assert_eq!(encrypted.load("sealed").await.unwrap(), data);
+        let connection = Connection::open(encrypted.database_path()).unwrap();
+        let stored: Vec<u8> = connection
+            .query_row("SELECT json FROM data_store WHERE namespace = 'test' AND key = 'sealed'", [], |row| row.get(0))
+            .unwrap();
+        assert!(crate::persistence::encryption::is_encrypted(&stored));
+        assert!(!String::from_utf8_lossy(&stored).contains("refresh-token-secret"));
+
+        // 未启用加密的存储无法读取加密数据
+        assert!(matches!(plain.load("sealed").await, Err(PersistenceError::EncryptionError(_))));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sqlite_store_concurrent_saves() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store: Arc<crate::persistence::BoxedStore<TestData>> =
+            Arc::new(crate::persistence::open_store(&sqlite_config(&temp_dir), "concurrent"));
+        
+        let handles: Vec<_> = (0..16)
+            .map(|task| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    for i in 0..25 {
+                        let data = TestData { value: format!("task{}", task), number: i };
+                        store.save(&format!("task{}-{}", task, i), &data).await.unwrap();
+                        // 所有任务同时覆盖同一个键
+                        store.save("shared", &data).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        
+        assert_eq!(store.list_keys().await.unwrap().len(), 16 * 25 + 1);
+        let loaded = store.load("task3-24").await.unwrap();
+        assert_eq!(loaded, TestData { value: "task3".to_string(), number: 24 });
+        let shared = store.load("shared").await.unwrap();
+        assert_eq!(shared.number, 24);
+        assert!(shared.value.starts_with("task"));
+    }
+}
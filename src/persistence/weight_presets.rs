@@ -3,7 +3,7 @@
 //! 
 //! 提供权重预设的持久化功能，包括预设的创建、更新、删除和查询
 
-use super::{DataStore, FileSystemStore, PersistenceConfig, PersistenceError};
+use super::{open_store, BoxedStore, DataStore, PersistenceConfig, PersistenceError};
 use crate::load_balancer::tools::WeightPreset;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -13,7 +13,7 @@ use tokio::sync::RwLock;
 /// 权重预设存储管理器
 pub struct WeightPresetStore {
     /// 文件存储
-    file_store: FileSystemStore<WeightPreset>,
+    file_store: BoxedStore<WeightPreset>,
     /// 内存缓存
     cache: Arc<RwLock<HashMap<String, WeightPreset>>>,
     /// 是否启用缓存
@@ -43,7 +43,7 @@ pub struct PresetStatistics {
 impl WeightPresetStore {
     /// 创建新的权重预设存储
     pub fn new(config: PersistenceConfig, enable_cache: bool) -> Self {
-        let file_store = FileSystemStore::new(config, "weight_presets".to_string());
+        let file_store = open_store(&config, "weight_presets");
         
         Self {
             file_store,
@@ -0,0 +1,200 @@
+// src/proxy/attempt_log.rs
+//! 单个请求的上游尝试记录
+//!
+//! 记录一个客户端请求依次尝试的密钥、上游状态码（或错误）与耗时，便于事后排查。
+//! 代理生成的失败响应体中始终包含尝试记录；客户端携带 `X-Gemini-Debug-Attempts`
+//! 请求头时，响应还会附带 `X-Upstream-Attempts` 响应头。记录条数有上限，超出后丢弃最早的记录
+
+use crate::error::GeminiProxyError;
+use crate::proxy::circuit_guard::error_body;
+use http::HeaderMap;
+use serde::Serialize;
+use std::fmt::Display;
+use std::time::Instant;
+
+/// 客户端选择返回尝试记录响应头的请求头
+pub const ATTEMPT_LOG_DEBUG_HEADER: &str = "x-gemini-debug-attempts";
+
+/// 尝试记录响应头
+pub const ATTEMPT_LOG_RESPONSE_HEADER: &str = "x-upstream-attempts";
+
+/// 每个请求最多保留的尝试记录条数
+pub const MAX_RECORDED_ATTEMPTS: usize = 8;
+
+/// 单次上游尝试
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamAttempt {
+    /// 第几次尝试（从 1 开始，丢弃旧记录后仍保持原序号）
+    pub attempt: u32,
+    pub key_id: String,
+    /// 上游响应状态码（未收到响应时为 None）
+    pub status: Option<u16>,
+    /// 未收到响应时的错误
+    pub error: Option<String>,
+    /// 相对请求第一次尝试的开始时间（毫秒）
+    pub started_after_ms: u64,
+    /// 尝试耗时（毫秒），尚未结束时为 None
+    pub duration_ms: Option<u64>,
+}
+
+/// 请求的上游尝试记录
+#[derive(Debug, Default)]
+pub struct AttemptLog {
+    first_started: Option<Instant>,
+    current_started: Option<Instant>,
+    attempts: Vec<UpstreamAttempt>,
+    total: u32,
+}
+
+impl AttemptLog {
+    /// 开始一次新的尝试
+    pub fn begin(&mut self, key_id: &str) {
+        let now = Instant::now();
+        let first_started = *self.first_started.get_or_insert(now);
+        self.total += 1;
+        if self.attempts.len() >= MAX_RECORDED_ATTEMPTS {
+            self.attempts.remove(0);
+        }
+        self.attempts.push(UpstreamAttempt {
+            attempt: self.total,
+            key_id: key_id.to_string(),
+            status: None,
+            error: None,
+            started_after_ms: now.duration_since(first_started).as_millis() as u64,
+            duration_ms: None,
+        });
+        self.current_started = Some(now);
+    }
+
+    /// 以上游状态码结束当前尝试
+    pub fn finish_status(&mut self, status: u16) {
+        if let Some(attempt) = self.finish_current() {
+            attempt.status = Some(status);
+        }
+    }
+
+    /// 以错误结束当前尝试（当前没有进行中的尝试时忽略）
+    pub fn finish_error(&mut self, error: impl Display) {
+        if let Some(attempt) = self.finish_current() {
+            attempt.error = Some(error.to_string());
+        }
+    }
+
+    fn finish_current(&mut self) -> Option<&mut UpstreamAttempt> {
+        let started = self.current_started.take()?;
+        let attempt = self.attempts.last_mut()?;
+        attempt.duration_ms = Some(started.elapsed().as_millis() as u64);
+        Some(attempt)
+    }
+
+    /// 尝试总次数（包括已丢弃的记录）
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn attempts(&self) -> &[UpstreamAttempt] {
+        &self.attempts
+    }
+
+    /// 紧凑的响应头格式，例如 `key1=503;12ms, key2=error;30ms`
+    pub fn header_value(&self) -> String {
+        self.attempts
+            .iter()
+            .map(|attempt| {
+                let outcome = match (attempt.status, &attempt.error) {
+                    (Some(status), _) => status.to_string(),
+                    (None, Some(_)) => "error".to_string(),
+                    (None, None) => "pending".to_string(),
+                };
+                match attempt.duration_ms {
+                    Some(duration) => format!("{}={};{}ms", attempt.key_id, outcome, duration),
+                    None => format!("{}={}", attempt.key_id, outcome),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "total": self.total,
+            "dropped": self.total as usize - self.attempts.len(),
+            "entries": self.attempts,
+        })
+    }
+}
+
+/// 客户端是否请求返回尝试记录响应头
+pub fn wants_attempt_log(headers: &HeaderMap) -> bool {
+    headers
+        .get(ATTEMPT_LOG_DEBUG_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "on"))
+}
+
+/// 包含尝试记录的结构化失败响应体
+pub fn failure_body(status: u16, error: &GeminiProxyError, log: &AttemptLog) -> serde_json::Value {
+    let mut body = error_body(status, error);
+    body["error"]["attempts"] = log.to_json();
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_key_failover_failure_body_lists_both_attempts() {
+        let mut log = AttemptLog::default();
+        log.begin("key1");
+        log.finish_status(503);
+        log.begin("key2");
+        log.finish_error("ConnectTimedout");
+
+        let error = GeminiProxyError::network("上游请求失败").with_request_id("req-1");
+        let body = failure_body(502, &error, &log);
+
+        assert_eq!(body["error"]["code"], 502);
+        assert_eq!(body["error"]["request_id"], "req-1");
+        let attempts = &body["error"]["attempts"];
+        assert_eq!(attempts["total"], 2);
+        assert_eq!(attempts["dropped"], 0);
+        let entries = attempts["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0]["attempt"].as_u64(), entries[0]["key_id"].as_str()), (Some(1), Some("key1")));
+        assert_eq!(entries[0]["status"], 503);
+        assert!(entries[0]["duration_ms"].is_u64());
+        assert_eq!((entries[1]["attempt"].as_u64(), entries[1]["key_id"].as_str()), (Some(2), Some("key2")));
+        assert!(entries[1]["status"].is_null());
+        assert_eq!(entries[1]["error"], "ConnectTimedout");
+
+        let header = log.header_value();
+        assert!(header.starts_with("key1=503;"));
+        assert!(header.contains(", key2=error;"));
+    }
+
+    #[test]
+    fn test_log_is_bounded() {
+        let mut log = AttemptLog::default();
+        for i in 0..MAX_RECORDED_ATTEMPTS + 3 {
+            log.begin(&format!("key{}", i));
+            log.finish_status(500);
+        }
+        // 没有进行中的尝试时忽略结束
+        log.finish_error("ignored");
+
+        assert_eq!(log.total() as usize, MAX_RECORDED_ATTEMPTS + 3);
+        assert_eq!(log.attempts().len(), MAX_RECORDED_ATTEMPTS);
+        assert_eq!(log.attempts()[0].attempt, 4);
+        assert!(log.attempts().iter().all(|a| a.error.is_none()));
+        assert_eq!(log.to_json()["dropped"], 3);
+    }
+
+    #[test]
+    fn test_debug_header_opt_in() {
+        let mut headers = HeaderMap::new();
+        assert!(!wants_attempt_log(&headers));
+        headers.insert(ATTEMPT_LOG_DEBUG_HEADER, "on".parse().unwrap());
+        assert!(wants_attempt_log(&headers));
+    }
+}
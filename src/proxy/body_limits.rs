@@ -0,0 +1,82 @@
+// src/proxy/body_limits.rs
+//! 请求体与响应缓冲上限
+//!
+//! 声明了 content-length 的请求在读取请求体之前按长度直接返回 413；分块传输的请求边转发边计数，
+//! 累计超过上限时中止转发并返回 413，不会为此缓冲整个请求体。上游响应体只在上限内缓冲用于用量解析
+
+use crate::config::ServerConfig;
+use bytes::Bytes;
+use pingora::http::RequestHeader;
+
+/// 请求体过大的拒绝原因（写入审计日志元数据）
+pub const REQUEST_BODY_TOO_LARGE_REASON: &str = "request_body_too_large";
+
+/// 请求体与响应缓冲上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    pub max_request_bytes: usize,
+    pub max_response_buffer_bytes: usize,
+}
+
+impl BodyLimits {
+    pub fn from_server_config(config: &ServerConfig) -> Self {
+        Self {
+            max_request_bytes: config.max_request_body_bytes,
+            max_response_buffer_bytes: config.max_response_buffer_bytes,
+        }
+    }
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self::from_server_config(&ServerConfig::default())
+    }
+}
+
+/// 请求声明的 content-length 超过上限时返回声明的长度
+pub fn declared_length_exceeded(limits: &BodyLimits, header: &RequestHeader) -> Option<u64> {
+    header
+        .headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|&len| len > limits.max_request_bytes as u64)
+}
+
+/// 累计已转发的请求体字节数，返回是否超过上限
+pub fn count_request_chunk(limits: &BodyLimits, received: &mut usize, chunk: &Option<Bytes>) -> bool {
+    if let Some(chunk) = chunk {
+        *received = received.saturating_add(chunk.len());
+    }
+    *received > limits.max_request_bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(content_length: Option<usize>) -> RequestHeader {
+        let mut header = RequestHeader::build("POST", b"/v1beta/models/gemini-1.5-pro:generateContent", None).unwrap();
+        if let Some(len) = content_length {
+            header.insert_header("content-length", len.to_string()).unwrap();
+        }
+        header
+    }
+
+    #[test]
+    fn test_over_limit_body_rejected() {
+        let limits = BodyLimits { max_request_bytes: 1024, max_response_buffer_bytes: 1024 };
+
+        // 声明了长度：读取请求体前即可拒绝，恰好达到上限时允许
+        assert_eq!(declared_length_exceeded(&limits, &post(Some(4096))), Some(4096));
+        assert_eq!(declared_length_exceeded(&limits, &post(Some(1024))), None);
+        assert_eq!(declared_length_exceeded(&limits, &post(None)), None);
+
+        // 分块传输：累计超过上限的数据块触发拒绝
+        let mut received = 0;
+        let chunk = Some(Bytes::from(vec![b'a'; 600]));
+        assert!(!count_request_chunk(&limits, &mut received, &chunk));
+        assert!(count_request_chunk(&limits, &mut received, &chunk));
+        assert_eq!(received, 1200);
+    }
+}
@@ -0,0 +1,84 @@
+// src/proxy/cancellation.rs
+//! 客户端断开时的协作式取消
+//!
+//! Pingora 在检测到下游连接关闭时会中止请求并丢弃上游连接（不再复用），
+//! 从而停止继续消耗上游令牌与配额。这里负责在此过程中维护在途请求计数，
+//! 并把由客户端断开导致的中止记录为 `gemini_client_cancelled_total`
+
+use crate::metrics::MetricsCollector;
+use pingora_error::{Error, ErrorSource, ErrorType};
+use std::sync::Arc;
+
+/// 判断错误是否由客户端（下游）断开引起
+pub fn is_client_disconnect(error: &Error) -> bool {
+    error.esource() == &ErrorSource::Downstream
+        && matches!(
+            error.etype(),
+            ErrorType::ConnectionClosed | ErrorType::ReadError | ErrorType::WriteError
+        )
+}
+
+/// 在途请求守卫
+///
+/// 创建时增加在途计数，结束或被丢弃时减少。若请求未经 [`InFlightGuard::complete`]
+/// 就被丢弃（例如客户端断开导致整个请求被中止），视为客户端取消
+pub struct InFlightGuard {
+    metrics: Arc<MetricsCollector>,
+    completed: bool,
+}
+
+impl InFlightGuard {
+    pub fn new(metrics: Arc<MetricsCollector>) -> Self {
+        metrics.request_started();
+        Self {
+            metrics,
+            completed: false,
+        }
+    }
+
+    /// 请求正常结束（包括上游返回错误）
+    pub fn complete(mut self) {
+        self.completed = true;
+    }
+
+    /// 根据请求结束时的错误结束守卫
+    pub fn finish(self, error: Option<&Error>) {
+        if error.is_some_and(is_client_disconnect) {
+            tracing::info!("客户端已断开，上游请求已中止");
+            // 直接丢弃，按客户端取消计数
+            drop(self);
+        } else {
+            self.complete();
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.metrics.request_finished();
+        if !self.completed {
+            self.metrics.increment_client_cancelled();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finish_classifies_errors() {
+        let metrics = Arc::new(MetricsCollector::new());
+
+        InFlightGuard::new(metrics.clone()).finish(None);
+        let upstream_error = Error::new_up(ErrorType::ConnectionClosed);
+        InFlightGuard::new(metrics.clone()).finish(Some(&upstream_error));
+        assert_eq!(metrics.get_client_cancelled(), 0);
+
+        let downstream_error = Error::new_down(ErrorType::ConnectionClosed);
+        assert!(is_client_disconnect(&downstream_error));
+        InFlightGuard::new(metrics.clone()).finish(Some(&downstream_error));
+        assert_eq!(metrics.get_in_flight(), 0);
+        assert_eq!(metrics.get_client_cancelled(), 1);
+    }
+}
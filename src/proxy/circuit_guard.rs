@@ -0,0 +1,291 @@
+// src/proxy/circuit_guard.rs
+//! 密钥选择的熔断检查与快速失败
+//!
+//! 选择密钥时跳过熔断器打开的密钥；熔断器到达恢复时间后进入半开，选中即占用一个探测名额，
+//! 探测结果由代理在上游响应后上报。所有密钥的熔断器都处于打开状态时，不再尝试选择密钥并请求上游
+//! （只会超时或失败），而是立即返回带结构化错误体的卸载响应（默认 503，见 [`crate::proxy::shed`]）
+
+use crate::error::recovery::ErrorRecoveryManager;
+use crate::error::GeminiProxyError;
+use crate::load_balancer::key_manager::ApiKey;
+use crate::load_balancer::UnifiedKeyManager;
+use crate::proxy::shed::{Shed, ShedReason};
+
+/// 密钥选择方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyRouting {
+    /// 加权轮询（粘性窗口内按客户端复用密钥）
+    Client,
+    /// 按请求内容的哈希（加权 rendezvous 哈希）
+    ContentHash(u64),
+    /// 按客户端属性的哈希（一致性哈希环）
+    Affinity(u64),
+}
+
+/// 为客户端选择密钥；无法选择时返回卸载原因：
+/// 没有密钥允许服务请求的模型、所有熔断器打开、所有活跃密钥达到每分钟限额，或没有可用密钥
+pub async fn select_key(
+    key_manager: &UnifiedKeyManager,
+    recovery_manager: Option<&ErrorRecoveryManager>,
+    client_id: &str,
+) -> std::result::Result<ApiKey, Shed> {
+    select_key_with_hash(key_manager, recovery_manager, client_id, KeyRouting::Client, None).await
+}
+
+/// 同 [`select_key`]，按指定的方式选择密钥（哈希方式下相同哈希确定性地得到同一个密钥）；
+/// 提供模型时只在允许服务该模型的密钥中选择，优先该模型的密钥权重放大
+pub async fn select_key_with_hash(
+    key_manager: &UnifiedKeyManager,
+    recovery_manager: Option<&ErrorRecoveryManager>,
+    client_id: &str,
+    routing: KeyRouting,
+    model: Option<&str>,
+) -> std::result::Result<ApiKey, Shed> {
+    if let Some(model) = model {
+        if !key_manager.serves_model(model).await {
+            let error = GeminiProxyError::load_balancer(format!("没有 API 密钥允许服务模型 {}", model))
+                .with_metadata("model", model)
+                .with_recovery_hint("检查密钥的 allowed_models 配置或改用其他模型");
+            return Err(Shed::new(ShedReason::ModelUnsupported, error));
+        }
+    }
+
+    let mut blocked = Vec::new();
+    if let Some(recovery_manager) = recovery_manager {
+        let key_ids = all_key_ids(key_manager).await;
+        if recovery_manager.all_circuits_open(&key_ids).await {
+            return Err(circuit_open_shed(format!("所有 {} 个 API 密钥的熔断器均处于打开状态", key_ids.len())));
+        }
+        blocked = recovery_manager.blocked_components(&key_ids).await;
+    }
+
+    loop {
+        let selected = match routing {
+            KeyRouting::ContentHash(routing_hash) => {
+                key_manager.get_next_key_for_hash_excluding(routing_hash, model, &blocked).await
+            }
+            KeyRouting::Affinity(affinity_hash) => {
+                key_manager.get_next_key_for_affinity_excluding(affinity_hash, model, &blocked).await
+            }
+            KeyRouting::Client => key_manager.get_next_key_for_client_excluding(client_id, model, &blocked).await,
+        };
+        let Some(key) = selected else { break };
+        match recovery_manager {
+            // 半开探测名额可能已被并发请求占用，跳过该密钥重新选择
+            Some(recovery_manager) if !recovery_manager.check_circuit_breaker(&key.id).await => blocked.push(key.id),
+            _ => return Ok(key),
+        }
+    }
+
+    // 区分配额耗尽（仍有活跃密钥，只是达到限额）、其余密钥熔断与密钥全部不可用
+    let quota_exhausted = key_manager
+        .get_key_states()
+        .await
+        .iter()
+        .filter(|key| !blocked.contains(&key.id) && key.allows_model(model))
+        .any(|key| {
+            !key.runtime_state.disabled
+                && !key.runtime_state.drained
+                && key.runtime_state.is_active
+                && key.runtime_state.failure_count < 3
+        });
+    if quota_exhausted {
+        let error = GeminiProxyError::rate_limit("所有 API 密钥均已达到每分钟请求上限")
+            .with_retryable(true)
+            .with_recovery_hint("等待配额重置后重试");
+        Err(Shed::new(ShedReason::QuotaExhausted, error))
+    } else if !blocked.is_empty() {
+        Err(circuit_open_shed(format!("{} 个 API 密钥的熔断器处于打开状态，其余密钥均不可用", blocked.len())))
+    } else {
+        let error = GeminiProxyError::load_balancer("没有可用的 API 密钥").with_retryable(true);
+        Err(Shed::new(ShedReason::NoKeyAvailable, error))
+    }
+}
+
+/// 选择换密钥重试使用的密钥：跳过已尝试过的密钥与熔断器打开的密钥
+pub async fn select_failover_key(
+    key_manager: &UnifiedKeyManager,
+    recovery_manager: Option<&ErrorRecoveryManager>,
+    model: Option<&str>,
+    tried_keys: &[String],
+) -> Option<ApiKey> {
+    let mut blocked = tried_keys.to_vec();
+    if let Some(recovery_manager) = recovery_manager {
+        blocked.extend(recovery_manager.blocked_components(&all_key_ids(key_manager).await).await);
+    }
+    loop {
+        let key = key_manager.get_next_key_excluding(model, &blocked).await?;
+        match recovery_manager {
+            Some(recovery_manager) if !recovery_manager.check_circuit_breaker(&key.id).await => blocked.push(key.id),
+            _ => return Some(key),
+        }
+    }
+}
+
+/// 上游状态码是否说明密钥本身有问题（认证被拒、限流或上游错误）；400、404、422 等由客户端请求引起，
+/// 不计入密钥失败，否则单个发送错误请求的客户端就能打开所有密钥的熔断器
+pub fn is_key_failure_status(status: u16) -> bool {
+    matches!(status, 401 | 403 | 429 | 500..=599)
+}
+
+async fn all_key_ids(key_manager: &UnifiedKeyManager) -> Vec<String> {
+    key_manager.get_all_keys().await.into_iter().map(|key| key.id).collect()
+}
+
+fn circuit_open_shed(message: String) -> Shed {
+    let error = GeminiProxyError::load_balancer(message)
+        .with_retryable(true)
+        .with_recovery_hint("等待熔断器恢复后重试");
+    Shed::new(ShedReason::CircuitOpen, error)
+}
+
+/// 结构化错误响应体
+pub fn error_body(status: u16, error: &GeminiProxyError) -> serde_json::Value {
+    let context = error.get_context();
+    serde_json::json!({
+        "error": {
+            "code": status,
+            "type": error.error_type_name(),
+            "message": error.to_string(),
+            "error_id": context.error_id,
+            "request_id": context.request_id,
+            "retryable": context.retryable,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShedResponsesConfig;
+    use crate::error::recovery::create_default_recovery_manager;
+    use crate::proxy::shed::shed_response;
+
+    #[tokio::test]
+    async fn test_all_breakers_open_fails_fast_with_structured_503() {
+        let key_manager = UnifiedKeyManager::new(vec![ApiKey::for_test("key1"), ApiKey::for_test("key2")]);
+        let recovery_manager = create_default_recovery_manager();
+        for key_id in ["key1", "key2"] {
+            for _ in 0..5 {
+                recovery_manager.report_operation_result(key_id, false).await;
+            }
+        }
+
+        let shed = select_key(&key_manager, Some(&recovery_manager), "client").await.unwrap_err();
+        assert_eq!(shed.reason, ShedReason::CircuitOpen);
+        assert!(matches!(shed.error, GeminiProxyError::LoadBalancer { .. }));
+
+        // 未选择任何密钥，也就不会发起上游请求
+        assert!(key_manager.get_all_keys().await.iter().all(|k| k.current_requests == 0));
+
+        // 代理通过 write_shed_response 发送的卸载响应
+        let (header, body) = shed_response(&ShedResponsesConfig::default(), &shed).unwrap();
+        assert_eq!(header.status.as_u16(), 503);
+        assert_eq!(header.headers.get("content-type").unwrap(), "application/json");
+        assert_eq!(header.headers.get("retry-after").unwrap(), "60");
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], 503);
+        assert_eq!(body["error"]["type"], "LoadBalancer");
+        assert_eq!(body["error"]["retryable"], true);
+        assert_eq!(body["error"]["reason"], "circuit_open");
+    }
+
+    #[tokio::test]
+    async fn test_selects_key_while_any_breaker_closed() {
+        let key_manager = UnifiedKeyManager::new(vec![ApiKey::for_test("key1"), ApiKey::for_test("key2")]);
+        let recovery_manager = create_default_recovery_manager();
+        for _ in 0..5 {
+            recovery_manager.report_operation_result("key1", false).await;
+        }
+
+        // 熔断器打开的密钥不参与选择
+        for _ in 0..10 {
+            let key = select_key(&key_manager, Some(&recovery_manager), "client").await.unwrap();
+            assert_eq!(key.id, "key2");
+        }
+        let key = select_failover_key(&key_manager, Some(&recovery_manager), None, &[]).await.unwrap();
+        assert_eq!(key.id, "key2");
+        assert!(select_failover_key(&key_manager, Some(&recovery_manager), None, &["key2".to_string()])
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_open_key_recovers_through_half_open_probe() {
+        let key_manager = UnifiedKeyManager::new(vec![ApiKey::for_test("key1"), ApiKey::for_test("key2")]);
+        let recovery_manager =
+            create_default_recovery_manager().with_circuit_breaker(2, std::time::Duration::from_millis(50), 1);
+        for _ in 0..2 {
+            recovery_manager.report_operation_result("key1", false).await;
+        }
+        let selected_key1 = |count: &mut usize, key: ApiKey| {
+            if key.id == "key1" {
+                *count += 1;
+            }
+        };
+
+        let mut key1_count = 0;
+        for _ in 0..10 {
+            selected_key1(&mut key1_count, select_key(&key_manager, Some(&recovery_manager), "client").await.unwrap());
+        }
+        assert_eq!(key1_count, 0);
+
+        // 恢复时间过后只放行一个探测请求
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        for _ in 0..10 {
+            selected_key1(&mut key1_count, select_key(&key_manager, Some(&recovery_manager), "client").await.unwrap());
+        }
+        assert_eq!(key1_count, 1);
+
+        // 探测成功后熔断器关闭，密钥恢复按权重参与选择
+        recovery_manager.report_operation_result("key1", true).await;
+        key1_count = 0;
+        for _ in 0..10 {
+            selected_key1(&mut key1_count, select_key(&key_manager, Some(&recovery_manager), "client").await.unwrap());
+        }
+        assert_eq!(key1_count, 5);
+    }
+
+    #[tokio::test]
+    async fn test_distinguishes_quota_exhaustion_from_no_keys() {
+        let mut exhausted = ApiKey::for_test("key1");
+        exhausted.current_requests = exhausted.max_requests_per_minute;
+        let key_manager = UnifiedKeyManager::new(vec![exhausted]);
+        let shed = select_key(&key_manager, None, "client").await.unwrap_err();
+        assert_eq!(shed.reason, ShedReason::QuotaExhausted);
+
+        let key_manager = UnifiedKeyManager::new(vec![ApiKey::for_test("key1")]);
+        for _ in 0..3 {
+            key_manager.mark_key_failed_with_status("key1", 500).await;
+        }
+        let shed = select_key(&key_manager, None, "client").await.unwrap_err();
+        assert_eq!(shed.reason, ShedReason::NoKeyAvailable);
+    }
+
+    #[test]
+    fn test_only_key_related_statuses_are_key_failures() {
+        for status in [401, 403, 429, 500, 502, 503] {
+            assert!(is_key_failure_status(status), "{}", status);
+        }
+        for status in [200, 304, 400, 404, 413, 422] {
+            assert!(!is_key_failure_status(status), "{}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_model_sheds_with_distinct_reason() {
+        let mut flash = ApiKey::for_test("key1");
+        flash.allowed_models = vec!["gemini-1.5-flash".to_string()];
+        let key_manager = UnifiedKeyManager::new(vec![flash]);
+
+        let shed = select_key_with_hash(&key_manager, None, "client", KeyRouting::Client, Some("gemini-1.5-pro"))
+            .await
+            .unwrap_err();
+        assert_eq!(shed.reason, ShedReason::ModelUnsupported);
+
+        let key = select_key_with_hash(&key_manager, None, "client", KeyRouting::Client, Some("gemini-1.5-flash"))
+            .await
+            .unwrap();
+        assert_eq!(key.id, "key1");
+    }
+}
@@ -0,0 +1,300 @@
+// src/proxy/dedup.rs
+//! 重复请求去重窗口
+//!
+//! 有缺陷的客户端可能在短时间内重复发送同一个生成请求，导致重复计费。启用后按客户端凭据加上
+//! `Idempotency-Key` 请求头（未携带时按请求路径与请求体哈希）识别重复请求：窗口内的重复请求直接返回
+//! 首个请求的成功响应，首个请求仍在进行时等待其完成；首个请求失败时重复请求照常转发。
+//! 重放的响应通过 `X-Dedup: REPLAYED` 响应头标明
+
+use crate::auth::client_keys::PROXY_KEY_HEADER;
+use crate::config::DedupConfig;
+use crate::proxy::response_cache::CachedResponse;
+use bytes::Bytes;
+use http::HeaderMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// 客户端标识同一操作的请求头
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// 标明响应为重放结果的响应头
+pub const DEDUP_STATUS_HEADER: &str = "x-dedup";
+
+/// 重放响应的 `X-Dedup` 取值
+pub const DEDUP_REPLAYED: &str = "REPLAYED";
+
+/// 按请求体识别重复请求时读取的请求体上限（需在转发前完整读取请求体）；
+/// 不能超过 Pingora 的重试缓冲，否则读取后的请求体无法再转发给上游
+pub const MAX_DEDUP_BODY_BYTES: usize = 64 * 1024;
+
+/// 请求携带的幂等键
+pub fn idempotency_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+/// 计算去重键：不同客户端凭据的相同请求互不去重；既没有幂等键也没有请求体时返回 None
+pub fn dedup_key(path_and_query: &str, headers: &HeaderMap, body: Option<&[u8]>) -> Option<String> {
+    let mut hasher = openssl::sha::Sha256::new();
+    for name in [PROXY_KEY_HEADER, "authorization"] {
+        if let Some(value) = headers.get(name) {
+            hasher.update(value.as_bytes());
+        }
+        hasher.update(&[0]);
+    }
+    hasher.update(path_and_query.as_bytes());
+    hasher.update(&[0]);
+    match (idempotency_key(headers), body) {
+        (Some(key), _) => {
+            hasher.update(b"key:");
+            hasher.update(key.as_bytes());
+        }
+        (None, Some(body)) => {
+            hasher.update(b"body:");
+            hasher.update(body);
+        }
+        (None, None) => return None,
+    }
+    Some(hasher.finish().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+enum Slot {
+    /// 首个请求进行中，完成时通过通道发布响应
+    InFlight(watch::Receiver<Option<CachedResponse>>),
+    /// 首个请求的成功响应
+    Done(CachedResponse),
+}
+
+struct Entry {
+    slot: Slot,
+    started_at: Instant,
+}
+
+/// 查找结果
+pub enum DedupLookup {
+    /// 窗口内已有首个请求的成功响应
+    Replay(CachedResponse),
+    /// 首个请求仍在进行，等待其完成
+    Wait(watch::Receiver<Option<CachedResponse>>),
+    /// 窗口内的首个请求：照常转发，完成后记录响应
+    Forward(DedupTicket),
+}
+
+/// 按首个请求开始时间计算窗口的去重记录
+pub struct DedupWindow {
+    window: Duration,
+    max_entries: usize,
+    max_entry_bytes: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl DedupWindow {
+    pub fn new(config: &DedupConfig) -> Self {
+        Self::with_window(
+            Duration::from_secs(config.window_seconds),
+            config.max_entries,
+            config.max_entry_bytes,
+        )
+    }
+
+    fn with_window(window: Duration, max_entries: usize, max_entry_bytes: usize) -> Self {
+        Self {
+            window,
+            max_entries,
+            max_entry_bytes,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 查找窗口内的相同请求；没有时登记为首个请求
+    pub fn lookup(self: &Arc<Self>, key: String) -> DedupLookup {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(&key).filter(|entry| entry.started_at.elapsed() < self.window) {
+            return match &entry.slot {
+                Slot::Done(response) => DedupLookup::Replay(response.clone()),
+                Slot::InFlight(first) => DedupLookup::Wait(first.clone()),
+            };
+        }
+
+        if !entries.contains_key(&key) && entries.len() >= self.max_entries {
+            // 先清理窗口外的条目，仍然已满时淘汰最早的条目
+            let window = self.window;
+            entries.retain(|_, entry| entry.started_at.elapsed() < window);
+            if entries.len() >= self.max_entries {
+                if let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.started_at)
+                    .map(|(key, _)| key.clone())
+                {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        let (sender, receiver) = watch::channel(None);
+        entries.insert(
+            key.clone(),
+            Entry {
+                slot: Slot::InFlight(receiver),
+                started_at: Instant::now(),
+            },
+        );
+        DedupLookup::Forward(DedupTicket {
+            window: self.clone(),
+            key,
+            sender,
+            pending: None,
+            completed: false,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+/// 等待首个请求完成；首个请求失败时返回 None（由上游超时保证等待有界）
+pub async fn wait_for_first(mut first: watch::Receiver<Option<CachedResponse>>) -> Option<CachedResponse> {
+    first.wait_for(Option::is_some).await.ok().and_then(|response| response.clone())
+}
+
+/// 首个请求持有的登记；未完成即释放时删除登记，等待中的重复请求照常转发
+pub struct DedupTicket {
+    window: Arc<DedupWindow>,
+    key: String,
+    sender: watch::Sender<Option<CachedResponse>>,
+    pending: Option<CachedResponse>,
+    completed: bool,
+}
+
+impl DedupTicket {
+    /// 记录上游成功响应的响应头信息（响应体在请求结束时补齐），非成功响应传入 None
+    pub fn set_pending(&mut self, pending: Option<CachedResponse>) {
+        self.pending = pending;
+    }
+
+    /// 首个请求成功完成：记录响应并唤醒等待中的重复请求；响应体超过单条上限时不记录
+    pub fn complete(mut self, body: Bytes) {
+        let mut response = match self.pending.take() {
+            Some(response) if body.len() <= self.window.max_entry_bytes => response,
+            _ => return,
+        };
+        response.body = body;
+        {
+            let mut entries = self.window.entries.lock().unwrap();
+            match entries.get_mut(&self.key) {
+                Some(entry) if self.owns(entry) => entry.slot = Slot::Done(response.clone()),
+                _ => return,
+            }
+        }
+        self.sender.send_replace(Some(response));
+        self.completed = true;
+    }
+
+    /// 登记是否仍属于本请求（窗口过期后相同请求会重新登记）
+    fn owns(&self, entry: &Entry) -> bool {
+        matches!(&entry.slot, Slot::InFlight(first) if first.same_channel(&self.sender.subscribe()))
+    }
+}
+
+impl Drop for DedupTicket {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        let mut entries = self.window.entries.lock().unwrap();
+        if entries.get(&self.key).is_some_and(|entry| self.owns(entry)) {
+            entries.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "/v1beta/models/gemini-1.5-flash:generateContent";
+
+    fn headers(idempotency_key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(PROXY_KEY_HEADER, "client-secret".parse().unwrap());
+        headers.insert(IDEMPOTENCY_KEY_HEADER, idempotency_key.parse().unwrap());
+        headers
+    }
+
+    fn upstream_response(text: &str) -> (CachedResponse, Bytes) {
+        let pending = CachedResponse {
+            content_type: Some("application/json".to_string()),
+            content_encoding: None,
+            body: Bytes::new(),
+        };
+        (pending, Bytes::from(format!(r#"{{"candidates":[{{"text":"{}"}}]}}"#, text)))
+    }
+
+    fn forward(lookup: DedupLookup) -> DedupTicket {
+        match lookup {
+            DedupLookup::Forward(ticket) => ticket,
+            _ => panic!("expected the request to be forwarded"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_within_window_returns_first_response() {
+        let window = Arc::new(DedupWindow::with_window(Duration::from_secs(10), 100, 1024));
+        let key = dedup_key(PATH, &headers("req-1"), None).unwrap();
+
+        let mut first = forward(window.lookup(key.clone()));
+        // 首个请求进行中到达的重复请求等待其完成
+        let waiting = match window.lookup(key.clone()) {
+            DedupLookup::Wait(receiver) => tokio::spawn(wait_for_first(receiver)),
+            _ => panic!("expected the duplicate to wait for the first request"),
+        };
+        let (pending, body) = upstream_response("first");
+        first.set_pending(Some(pending));
+        first.complete(body.clone());
+        assert_eq!(waiting.await.unwrap().unwrap().body, body);
+
+        // 首个请求完成后的重复请求直接重放
+        match window.lookup(key) {
+            DedupLookup::Replay(response) => assert_eq!(response.body, body),
+            _ => panic!("expected the duplicate to be replayed"),
+        }
+
+        // 不同的幂等键或未携带幂等键与请求体时不去重
+        assert!(matches!(
+            window.lookup(dedup_key(PATH, &headers("req-2"), None).unwrap()),
+            DedupLookup::Forward(_)
+        ));
+        assert!(dedup_key(PATH, &HeaderMap::new(), None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_outside_window_or_after_failure_forwards_anew() {
+        let window = Arc::new(DedupWindow::with_window(Duration::from_millis(50), 100, 1024));
+        let body = br#"{"contents":[{"parts":[{"text":"hi"}]}]}"#;
+        let key = dedup_key(PATH, &HeaderMap::new(), Some(body)).unwrap();
+
+        let mut first = forward(window.lookup(key.clone()));
+        let (pending, response) = upstream_response("first");
+        first.set_pending(Some(pending));
+        first.complete(response);
+        std::thread::sleep(Duration::from_millis(80));
+        let mut second = forward(window.lookup(key.clone()));
+
+        // 首个请求失败（未成功完成）：等待中的重复请求与之后的请求都照常转发
+        let waiting = match window.lookup(key.clone()) {
+            DedupLookup::Wait(receiver) => tokio::spawn(wait_for_first(receiver)),
+            _ => panic!("expected the duplicate to wait for the first request"),
+        };
+        second.set_pending(None);
+        drop(second);
+        assert!(waiting.await.unwrap().is_none());
+        assert!(matches!(window.lookup(key), DedupLookup::Forward(_)));
+        assert_eq!(window.len(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+// src/proxy/error_codes.rs
+//! Gemini 错误码规范化
+//!
+//! Gemini 的错误响应形式多样：HTTP 错误状态加 `{"error":{"code","status","message"}}`、200 响应中的提示词拦截
+//! （`promptFeedback.blockReason`）或候选结果因安全策略终止（`finishReason: SAFETY`）。按配置的规则把它们映射为
+//! 稳定的规范化错误码（例如 `quota_exceeded`、`safety_blocked`），计入 `gemini_errors_total{code}` 指标；
+//! 非流式 JSON 错误响应在 `error` 对象中注入 `normalized_code` 字段，客户端无需解析上游的错误文本
+
+use crate::config::{ErrorCodeRule, ErrorCodesConfig};
+use crate::proxy::usage::{parse_upstream_body, UpstreamBody};
+use bytes::Bytes;
+use pingora::http::ResponseHeader;
+use serde_json::Value;
+
+/// 从响应体中提取的错误特征
+#[derive(Debug, Default)]
+struct ErrorSignals {
+    status: u16,
+    gemini_status: Option<String>,
+    message: Option<String>,
+    block_reasons: Vec<String>,
+    finish_reasons: Vec<String>,
+    has_error_body: bool,
+}
+
+impl ErrorSignals {
+    fn from_body(status: u16, body: &UpstreamBody) -> Self {
+        let values: &[Value] = match body {
+            UpstreamBody::Json(Value::Array(items)) => items,
+            UpstreamBody::Json(value) => std::slice::from_ref(value),
+            UpstreamBody::EventStream(events) => events,
+            UpstreamBody::Empty | UpstreamBody::Malformed(_) => &[],
+        };
+        let mut signals = Self { status, ..Self::default() };
+        for value in values {
+            if let Some(error) = value.get("error").filter(|e| e.is_object()) {
+                signals.has_error_body = true;
+                // 200 响应中的错误结构以 `error.code` 为准
+                if let Some(code) = error.get("code").and_then(Value::as_u64) {
+                    signals.status = code as u16;
+                }
+                signals.gemini_status = error.get("status").and_then(Value::as_str).map(str::to_string);
+                signals.message = error.get("message").and_then(Value::as_str).map(str::to_string);
+            }
+            if let Some(reason) = value.pointer("/promptFeedback/blockReason").and_then(Value::as_str) {
+                signals.block_reasons.push(reason.to_string());
+            }
+            let finish_reasons = value
+                .get("candidates")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|candidate| candidate.get("finishReason").and_then(Value::as_str));
+            signals.finish_reasons.extend(finish_reasons.map(str::to_string));
+        }
+        signals
+    }
+
+    fn matches(&self, rule: &ErrorCodeRule) -> bool {
+        let any_reason = |expected: &str, reasons: &[String]| {
+            reasons.iter().any(|r| expected == "*" || r.eq_ignore_ascii_case(expected))
+        };
+        rule.has_condition()
+            && condition(rule.status, |status| status == self.status)
+            && condition(rule.gemini_status.as_deref(), |expected| {
+                self.gemini_status.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(expected))
+            })
+            && condition(rule.message_contains.as_deref(), |expected| {
+                self.message
+                    .as_deref()
+                    .is_some_and(|m| m.to_lowercase().contains(&expected.to_lowercase()))
+            })
+            && condition(rule.block_reason.as_deref(), |expected| any_reason(expected, &self.block_reasons))
+            && condition(rule.finish_reason.as_deref(), |expected| any_reason(expected, &self.finish_reasons))
+    }
+}
+
+/// 未设置的条件视为满足
+fn condition<T>(expected: Option<T>, check: impl FnOnce(T) -> bool) -> bool {
+    match expected {
+        Some(expected) => check(expected),
+        None => true,
+    }
+}
+
+/// 规范化上游响应的错误码；不是错误（也未被拦截）的响应返回 None
+pub fn normalize_error(config: &ErrorCodesConfig, status: u16, body: &UpstreamBody) -> Option<String> {
+    if !config.enabled {
+        return None;
+    }
+    let signals = ErrorSignals::from_body(status, body);
+    if let Some(rule) = config.rules.iter().find(|rule| signals.matches(rule)) {
+        return Some(rule.code.clone());
+    }
+    (status >= 400 || signals.has_error_body).then(|| config.fallback_code.clone())
+}
+
+/// 响应是否需要注入规范化错误码：启用、错误状态、未压缩的 JSON 响应
+pub fn should_annotate(config: &ErrorCodesConfig, response_header: &ResponseHeader) -> bool {
+    let header_value = |name: &str| {
+        response_header
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_ascii_lowercase)
+    };
+    let is_json = header_value("content-type").is_some_and(|v| v.starts_with("application/json"));
+    let encoded = header_value("content-encoding").is_some_and(|v| v != "identity");
+    config.enabled && response_header.status.as_u16() >= 400 && is_json && !encoded
+}
+
+/// 在错误响应体的 `error` 对象中注入 `normalized_code`；响应体不含错误结构时返回 None（调用方原样转发）
+pub fn annotate_error_body(config: &ErrorCodesConfig, status: u16, body: &[u8]) -> Option<Bytes> {
+    let code = normalize_error(config, status, &parse_upstream_body(body))?;
+    let mut value: Value = serde_json::from_slice(body).ok()?;
+    let errors: Vec<&mut Value> = match &mut value {
+        Value::Array(items) => items.iter_mut().filter_map(|item| item.get_mut("error")).collect(),
+        value => value.get_mut("error").into_iter().collect(),
+    };
+    let mut annotated = false;
+    for error in errors {
+        if let Some(error) = error.as_object_mut() {
+            error.insert("normalized_code".to_string(), Value::String(code.clone()));
+            annotated = true;
+        }
+    }
+    if !annotated {
+        return None;
+    }
+    serde_json::to_vec(&value).ok().map(Bytes::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_error_normalized() {
+        let config = ErrorCodesConfig::default();
+        let quota = br#"{"error":{"code":429,"message":"Resource has been exhausted (e.g. check quota).","status":"RESOURCE_EXHAUSTED"}}"#;
+        assert_eq!(normalize_error(&config, 429, &parse_upstream_body(quota)).as_deref(), Some("quota_exceeded"));
+
+        let annotated: Value = serde_json::from_slice(&annotate_error_body(&config, 429, quota).unwrap()).unwrap();
+        assert_eq!(annotated["error"]["normalized_code"], "quota_exceeded");
+        assert_eq!(annotated["error"]["status"], "RESOURCE_EXHAUSTED");
+
+        // 无法识别的错误使用默认错误码，普通成功响应不产生错误码
+        let unknown = br#"{"error":{"code":418,"message":"teapot","status":"UNKNOWN"}}"#;
+        assert_eq!(normalize_error(&config, 418, &parse_upstream_body(unknown)).as_deref(), Some("upstream_error"));
+        let ok = br#"{"candidates":[{"content":{"parts":[{"text":"hi"}]},"finishReason":"STOP"}]}"#;
+        assert_eq!(normalize_error(&config, 200, &parse_upstream_body(ok)), None);
+    }
+
+    #[test]
+    fn test_safety_block_normalized() {
+        let config = ErrorCodesConfig::default();
+        let blocked = br#"{"promptFeedback":{"blockReason":"SAFETY","safetyRatings":[]}}"#;
+        assert_eq!(normalize_error(&config, 200, &parse_upstream_body(blocked)).as_deref(), Some("safety_blocked"));
+
+        let stream = b"data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"a\"}]}}]}\n\n\
+                       data: {\"candidates\":[{\"finishReason\":\"SAFETY\"}]}\n\n";
+        assert_eq!(normalize_error(&config, 200, &parse_upstream_body(stream)).as_deref(), Some("safety_blocked"));
+
+        // 配置的规则替换内置规则
+        let custom = ErrorCodesConfig {
+            rules: vec![ErrorCodeRule {
+                code: "content_policy".to_string(),
+                block_reason: Some("*".to_string()),
+                ..ErrorCodeRule::default()
+            }],
+            ..ErrorCodesConfig::default()
+        };
+        assert_eq!(normalize_error(&custom, 200, &parse_upstream_body(blocked)).as_deref(), Some("content_policy"));
+    }
+}
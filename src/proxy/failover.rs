@@ -0,0 +1,122 @@
+// src/proxy/failover.rs
+//! 请求内换密钥重试
+//!
+//! 上游返回可重试状态码（默认 429/500/502/503/504）且响应头尚未发给客户端时，按失败记录当前密钥，
+//! 选择另一个未尝试过的密钥并由 Pingora 重放请求。请求体由 Pingora 的重试缓冲保存（上限 64KB），
+//! 超出缓冲的请求无法重放，直接返回上游响应。返回 429 的密钥同时按 `Retry-After` 进入冷却期
+
+use crate::config::FailoverConfig;
+use chrono::{DateTime, Utc};
+use http::HeaderMap;
+use std::time::Duration;
+
+/// 当前状态码是否应换密钥重试（`retries_done` 为本请求已重试次数）
+pub fn should_failover(config: &FailoverConfig, status: u16, retries_done: u32) -> bool {
+    retries_done < config.max_retries && config.retry_statuses.contains(&status)
+}
+
+/// 第 `retry` 次重试（从 1 开始）前的等待时间：指数增长，不超过上限
+pub fn failover_backoff(config: &FailoverConfig, retry: u32) -> Duration {
+    let factor = 1u64.checked_shl(retry.saturating_sub(1)).unwrap_or(u64::MAX);
+    Duration::from_millis(config.backoff_ms.saturating_mul(factor).min(config.max_backoff_ms))
+}
+
+/// 下一次重试的等待时间加上本请求已等待的时间是否仍在退避预算内（预算为 0 表示不限制）
+pub fn within_backoff_budget(config: &FailoverConfig, spent: Duration, next: Duration) -> bool {
+    config.max_total_backoff_ms == 0 || spent + next <= Duration::from_millis(config.max_total_backoff_ms)
+}
+
+/// 解析上游响应的 `Retry-After`（秒数或 HTTP 日期），缺失或无法解析时返回 None
+pub fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get("retry-after")?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((date - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retryable_statuses_up_to_max_retries() {
+        let config = FailoverConfig::default();
+        for status in [429, 500, 502, 503, 504] {
+            assert!(should_failover(&config, status, 0), "{}", status);
+        }
+        assert!(!should_failover(&config, 400, 0));
+        assert!(!should_failover(&config, 200, 0));
+        assert!(should_failover(&config, 503, 1));
+        assert!(!should_failover(&config, 503, 2));
+
+        let disabled = FailoverConfig {
+            max_retries: 0,
+            ..FailoverConfig::default()
+        };
+        assert!(!should_failover(&disabled, 429, 0));
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let config = FailoverConfig {
+            backoff_ms: 100,
+            max_backoff_ms: 350,
+            ..FailoverConfig::default()
+        };
+        assert_eq!(failover_backoff(&config, 1), Duration::from_millis(100));
+        assert_eq!(failover_backoff(&config, 2), Duration::from_millis(200));
+        assert_eq!(failover_backoff(&config, 3), Duration::from_millis(350));
+        assert_eq!(failover_backoff(&config, 80), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_total_backoff_stays_within_budget() {
+        let config = FailoverConfig {
+            max_retries: 50,
+            backoff_ms: 100,
+            max_backoff_ms: 350,
+            max_total_backoff_ms: 1000,
+            ..FailoverConfig::default()
+        };
+        let mut spent = Duration::ZERO;
+        let mut retries = 0;
+        while should_failover(&config, 503, retries) {
+            let next = failover_backoff(&config, retries + 1);
+            if !within_backoff_budget(&config, spent, next) {
+                break;
+            }
+            spent += next;
+            retries += 1;
+        }
+        // 100 + 200 + 350 + 350 = 1000ms，第 5 次重试的 350ms 会超出预算
+        assert_eq!(retries, 4);
+        assert_eq!(spent, Duration::from_millis(1000));
+
+        let unlimited = FailoverConfig {
+            max_total_backoff_ms: 0,
+            ..config
+        };
+        assert!(within_backoff_budget(&unlimited, Duration::from_secs(60), Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_retry_after_seconds_and_date() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT").unwrap().with_timezone(&Utc);
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers, now), None);
+
+        headers.insert("retry-after", "30".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(30)));
+
+        headers.insert("retry-after", "Wed, 21 Oct 2015 07:29:30 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(90)));
+        // 已过去的日期视为无需等待
+        headers.insert("retry-after", "Wed, 21 Oct 2015 07:00:00 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), Some(Duration::ZERO));
+
+        headers.insert("retry-after", "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), None);
+    }
+}
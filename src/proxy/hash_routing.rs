@@ -0,0 +1,146 @@
+// src/proxy/hash_routing.rs
+//! 按请求内容哈希选择密钥
+//!
+//! 对 embedding 等请求，把相同输入路由到同一个密钥可以提高上游缓存命中率。启用后，代理读取请求体中
+//! 配置的字段（例如 `content.parts.0.text`），对取值做哈希后在可用密钥中按权重确定性地选择密钥
+//! （加权 rendezvous 哈希，密钥集合变化时只有少量输入改变归属）。字段缺失、请求体无法解析或
+//! 请求路径不匹配时回退到常规的加权轮询。
+//!
+//! 一致性哈希调度（`gemini.scheduling.mode: consistent_hashing`）则按客户端属性（客户端 IP、
+//! 会话请求头或 JWT `sub`）的哈希在哈希环上选择密钥，让同一逻辑客户端持续使用同一个密钥
+
+use crate::config::{HashAttribute, HashRoutingConfig, KeySchedulingMode, SchedulingConfig};
+use base64::Engine;
+use http::HeaderMap;
+use std::net::IpAddr;
+
+/// 参与哈希路由的请求体上限（需在转发前完整读取请求体）
+pub const MAX_HASH_ROUTING_BODY_BYTES: usize = 64 * 1024;
+
+/// 请求路径是否启用哈希路由
+pub fn applies_to(config: &HashRoutingConfig, path: &str) -> bool {
+    config.enabled && (config.path_suffixes.is_empty() || config.path_suffixes.iter().any(|s| path.ends_with(s.as_str())))
+}
+
+/// 按点分隔路径读取 JSON 字段（数组使用下标），字符串返回原值，其他类型返回其 JSON 文本
+pub fn extract_field(body: &[u8], field: &str) -> Option<String> {
+    let request: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let mut value = &request;
+    for segment in field.split('.').filter(|s| !s.is_empty()) {
+        value = match value {
+            serde_json::Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => value.get(segment)?,
+        };
+    }
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// 字段取值的稳定哈希（跨进程、跨实例一致）
+pub fn routing_hash(value: &str) -> u64 {
+    let digest = openssl::sha::sha256(value.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+/// 计算请求的路由哈希，不适用或字段缺失时返回 None（回退到加权轮询）
+pub fn routing_hash_for_request(config: &HashRoutingConfig, path: &str, body: &[u8]) -> Option<u64> {
+    if !applies_to(config, path) {
+        return None;
+    }
+    extract_field(body, &config.field).map(|value| routing_hash(&value))
+}
+
+/// 一致性哈希调度使用的客户端属性哈希，未启用或属性缺失时返回 None（回退到加权轮询）
+pub fn affinity_hash(config: &SchedulingConfig, headers: &HeaderMap, client_ip: Option<IpAddr>) -> Option<u64> {
+    if config.mode != KeySchedulingMode::ConsistentHashing {
+        return None;
+    }
+    let value = match config.hash_attribute {
+        HashAttribute::ClientIp => client_ip?.to_canonical().to_string(),
+        HashAttribute::Header => headers
+            .get(config.hash_header.as_str())?
+            .to_str()
+            .ok()
+            .filter(|value| !value.is_empty())?
+            .to_string(),
+        HashAttribute::JwtSub => jwt_subject(headers.get("authorization")?.to_str().ok()?)?,
+    };
+    Some(routing_hash(&value))
+}
+
+/// 读取 Bearer JWT 的 `sub` 声明（不校验签名，令牌已由认证阶段校验）
+fn jwt_subject(authorization: &str) -> Option<String> {
+    let payload = authorization.strip_prefix("Bearer ")?.split('.').nth(1)?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    claims.get("sub")?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HashRoutingConfig {
+        HashRoutingConfig {
+            enabled: true,
+            ..HashRoutingConfig::default()
+        }
+    }
+
+    fn embed_body(text: &str) -> Vec<u8> {
+        serde_json::json!({ "content": { "parts": [{ "text": text }] } }).to_string().into_bytes()
+    }
+
+    #[test]
+    fn test_identical_inputs_hash_identically() {
+        let path = "/v1beta/models/text-embedding-004:embedContent";
+        let first = routing_hash_for_request(&config(), path, &embed_body("hello world"));
+        let second = routing_hash_for_request(&config(), path, &embed_body("hello world"));
+        let other = routing_hash_for_request(&config(), path, &embed_body("goodbye"));
+
+        assert!(first.is_some());
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_falls_back_when_not_applicable() {
+        let path = "/v1beta/models/text-embedding-004:embedContent";
+        // 字段缺失或请求体无法解析
+        assert!(routing_hash_for_request(&config(), path, br#"{"content":{"parts":[]}}"#).is_none());
+        assert!(routing_hash_for_request(&config(), path, b"not json").is_none());
+        // 路径不匹配或未启用
+        assert!(routing_hash_for_request(&config(), "/v1beta/models/gemini-1.5-flash:generateContent", &embed_body("x")).is_none());
+        assert!(routing_hash_for_request(&HashRoutingConfig::default(), path, &embed_body("x")).is_none());
+    }
+
+    #[test]
+    fn test_affinity_hash_uses_configured_attribute() {
+        let mut config = SchedulingConfig {
+            mode: KeySchedulingMode::ConsistentHashing,
+            ..SchedulingConfig::default()
+        };
+        let claims = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(br#"{"sub":"user-7","role":"admin"}"#);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-session-id", "session-a".parse().unwrap());
+        headers.insert("authorization", format!("Bearer eyJhbGciOiJIUzI1NiJ9.{}.c2ln", claims).parse().unwrap());
+        let ip: IpAddr = "10.0.0.7".parse().unwrap();
+
+        assert_eq!(affinity_hash(&config, &headers, Some(ip)), Some(routing_hash("10.0.0.7")));
+        assert_eq!(
+            affinity_hash(&config, &headers, Some("::ffff:10.0.0.7".parse().unwrap())),
+            Some(routing_hash("10.0.0.7"))
+        );
+        config.hash_attribute = HashAttribute::Header;
+        assert_eq!(affinity_hash(&config, &headers, Some(ip)), Some(routing_hash("session-a")));
+        config.hash_attribute = HashAttribute::JwtSub;
+        assert_eq!(affinity_hash(&config, &headers, Some(ip)), Some(routing_hash("user-7")));
+
+        // 属性缺失或未启用一致性哈希时回退到加权轮询
+        assert_eq!(affinity_hash(&config, &HeaderMap::new(), Some(ip)), None);
+        assert_eq!(affinity_hash(&SchedulingConfig::default(), &headers, Some(ip)), None);
+    }
+}
@@ -0,0 +1,100 @@
+// src/proxy/header_limits.rs
+//! 入站请求头上限
+//!
+//! 请求头总字节数（名称与值长度之和）或数量超过 `server` 配置的上限时直接返回 431，
+//! 在鉴权与密钥选择之前拒绝，防止利用超大或大量请求头消耗资源
+
+use crate::config::ServerConfig;
+use pingora::http::RequestHeader;
+
+/// 请求头上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderLimits {
+    pub max_bytes: usize,
+    pub max_count: usize,
+}
+
+impl HeaderLimits {
+    pub fn from_server_config(config: &ServerConfig) -> Self {
+        Self {
+            max_bytes: config.max_header_bytes,
+            max_count: config.max_header_count,
+        }
+    }
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        Self::from_server_config(&ServerConfig::default())
+    }
+}
+
+/// 超出上限的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderLimitExceeded {
+    /// 请求头总字节数超限
+    TooLarge,
+    /// 请求头数量超限
+    TooMany,
+}
+
+impl HeaderLimitExceeded {
+    /// 拒绝原因（写入审计日志元数据）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TooLarge => "header_too_large",
+            Self::TooMany => "too_many_headers",
+        }
+    }
+}
+
+/// 检查请求头是否超出上限
+pub fn check_header_limits(limits: &HeaderLimits, header: &RequestHeader) -> Option<HeaderLimitExceeded> {
+    if header.headers.len() > limits.max_count {
+        return Some(HeaderLimitExceeded::TooMany);
+    }
+    let total_bytes: usize = header
+        .headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    (total_bytes > limits.max_bytes).then_some(HeaderLimitExceeded::TooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(String, String)]) -> RequestHeader {
+        let mut header = RequestHeader::build("POST", b"/v1beta/models/gemini-1.5-pro:generateContent", None).unwrap();
+        for (name, value) in headers {
+            header.append_header(name.clone(), value.as_str()).unwrap();
+        }
+        header
+    }
+
+    #[test]
+    fn test_normal_request_passes() {
+        let header = request(&[
+            ("host".to_string(), "proxy.example.com".to_string()),
+            ("content-type".to_string(), "application/json".to_string()),
+            ("authorization".to_string(), "Bearer token".to_string()),
+        ]);
+        assert_eq!(check_header_limits(&HeaderLimits::default(), &header), None);
+    }
+
+    #[test]
+    fn test_over_limit_headers_rejected() {
+        let limits = HeaderLimits { max_bytes: 1024, max_count: 10 };
+
+        let many: Vec<_> = (0..11).map(|i| (format!("x-custom-{}", i), "v".to_string())).collect();
+        assert_eq!(check_header_limits(&limits, &request(&many)), Some(HeaderLimitExceeded::TooMany));
+
+        let large = vec![("x-padding".to_string(), "a".repeat(1024))];
+        assert_eq!(check_header_limits(&limits, &request(&large)), Some(HeaderLimitExceeded::TooLarge));
+
+        // 恰好达到上限时允许
+        let exact = vec![("x-padding".to_string(), "a".repeat(1024 - "x-padding".len()))];
+        assert_eq!(check_header_limits(&limits, &request(&exact)), None);
+    }
+}
@@ -0,0 +1,114 @@
+// src/proxy/inflight_limit.rs
+//! 全进程在途请求上限
+//!
+//! 与各密钥的每分钟限额相互独立：在途请求数达到 `server.max_inflight_requests` 后，新请求在鉴权与
+//! 密钥选择之前直接返回 503（`overloaded`），即使仍有密钥空闲，用于限制缓冲请求/响应占用的内存。
+//! 许可随请求上下文一起释放；上限为 0 时只计数不限制
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// 非阻塞的计数信号量：获取失败时立即返回，不排队等待
+#[derive(Debug, Default)]
+pub struct InflightLimiter {
+    max: usize,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl InflightLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// 尝试占用一个在途名额，已达上限时返回 None
+    pub fn try_acquire(&self) -> Option<InflightPermit> {
+        self.in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                (self.max == 0 || current < self.max).then_some(current + 1)
+            })
+            .ok()?;
+        Some(InflightPermit {
+            in_flight: self.in_flight.clone(),
+        })
+    }
+
+    /// 当前在途请求数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// 在途请求上限（None 表示不限制）
+    pub fn max(&self) -> Option<usize> {
+        (self.max > 0).then_some(self.max)
+    }
+}
+
+/// 在途名额，丢弃时归还
+#[derive(Debug)]
+pub struct InflightPermit {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for InflightPermit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShedResponsesConfig;
+    use crate::error::GeminiProxyError;
+    use crate::load_balancer::key_manager::ApiKey;
+    use crate::load_balancer::UnifiedKeyManager;
+    use crate::proxy::shed::{shed_response, Shed, ShedReason};
+
+    fn api_key(id: &str) -> ApiKey {
+        ApiKey {
+            key: format!("key-{}", id),
+            max_requests_per_minute: 1000,
+            ..ApiKey::for_test(id)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_saturated_ceiling_rejects_while_keys_have_capacity() {
+        let limiter = InflightLimiter::new(2);
+        let key_manager = UnifiedKeyManager::new(vec![api_key("a"), api_key("b")]);
+
+        let first = limiter.try_acquire().unwrap();
+        let _second = limiter.try_acquire().unwrap();
+        assert_eq!(limiter.in_flight(), 2);
+
+        // 两个密钥都远未达到限额，全局上限仍然拒绝新请求
+        assert!(limiter.try_acquire().is_none());
+        assert!(key_manager.get_next_key().await.is_some());
+        assert_eq!(limiter.in_flight(), 2);
+
+        let shed = Shed::new(ShedReason::Overloaded, GeminiProxyError::load_balancer("saturated"));
+        let (header, body) = shed_response(&ShedResponsesConfig::default(), &shed).unwrap();
+        assert_eq!(header.status.as_u16(), 503);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["reason"], "overloaded");
+
+        // 请求结束归还名额后可以继续接收
+        drop(first);
+        assert_eq!(limiter.in_flight(), 1);
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn test_zero_ceiling_counts_without_limiting() {
+        let limiter = InflightLimiter::new(0);
+        let permits: Vec<_> = (0..100).map(|_| limiter.try_acquire().unwrap()).collect();
+
+        assert_eq!(limiter.in_flight(), 100);
+        assert_eq!(limiter.max(), None);
+        drop(permits);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+}
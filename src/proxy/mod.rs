@@ -1,3 +1,34 @@
 pub mod acme_service;
+pub mod attempt_log;
+pub mod body_limits;
+pub mod cancellation;
+pub mod circuit_guard;
+pub mod dedup;
+pub mod error_codes;
+pub mod failover;
+pub mod hash_routing;
+pub mod header_limits;
+pub mod inflight_limit;
+pub mod model_override;
+pub mod model_fallback;
+pub mod openai_compat;
+pub mod preflight;
+pub mod request_id;
+pub mod request_record;
+pub mod required_headers;
+pub mod route_allowlist;
+pub mod response_cache;
+pub mod response_headers;
+pub mod response_rewrite;
+pub mod response_transform;
 pub mod service;
+pub mod shadow;
+pub mod shed;
+pub mod soft_failure;
+pub mod spans;
+pub mod streaming;
+pub mod timeouts;
+pub mod traffic_class;
+pub mod usage;
+pub mod warm_pool;
 pub use service::*;
@@ -0,0 +1,136 @@
+// src/proxy/model_fallback.rs
+//! 模型降级链
+//!
+//! 按模型统计上游连续过载响应（默认 503），达到阈值后该模型进入降级状态：恢复窗口内请求该模型时
+//! 改写到降级链中第一个未降级的模型，并通过 `X-Gemini-Served-Model` 响应头标明实际服务的模型。
+//! 恢复窗口过后重新尝试原模型，成功响应清零计数，再次过载则立即重新降级
+
+use crate::config::ModelFallbackConfig;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 标明实际服务模型的响应头
+pub const SERVED_MODEL_HEADER: &str = "x-gemini-served-model";
+
+#[derive(Debug, Default)]
+struct ModelHealth {
+    consecutive_failures: u32,
+    degraded_until: Option<Instant>,
+}
+
+impl ModelHealth {
+    fn is_degraded(&self, now: Instant) -> bool {
+        self.degraded_until.is_some_and(|until| now < until)
+    }
+}
+
+/// 各模型的过载状态（只记录降级链中出现的模型）
+#[derive(Debug, Default)]
+pub struct ModelFallbackTracker {
+    models: Mutex<HashMap<String, ModelHealth>>,
+}
+
+impl ModelFallbackTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录模型的上游响应状态：过载状态累计连续失败，2xx 清零
+    pub fn record(&self, config: &ModelFallbackConfig, model: &str, status: u16, now: Instant) {
+        if !is_tracked(config, model) {
+            return;
+        }
+        let mut models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+        if config.overload_statuses.contains(&status) {
+            let health = models.entry(model.to_string()).or_default();
+            health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+            if health.consecutive_failures >= config.failure_threshold {
+                if !health.is_degraded(now) {
+                    tracing::warn!(model, failures = health.consecutive_failures, "模型持续过载，后续请求按降级链改用其他模型");
+                }
+                health.degraded_until = Some(now + Duration::from_secs(config.recovery_seconds));
+            }
+        } else if (200..300).contains(&status) {
+            if models.remove(model).is_some_and(|health| health.degraded_until.is_some()) {
+                tracing::info!(model, "模型已恢复，不再降级");
+            }
+        }
+    }
+
+    /// 请求模型的降级目标：原模型未降级时返回 None；链中所有模型都已降级时仍使用原模型
+    pub fn resolve(&self, config: &ModelFallbackConfig, model: &str, now: Instant) -> Option<String> {
+        let chain = config.chains.get(model)?;
+        let models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+        let degraded = |m: &str| models.get(m).is_some_and(|health| health.is_degraded(now));
+        if !degraded(model) {
+            return None;
+        }
+        chain.iter().find(|m| !degraded(m)).cloned()
+    }
+}
+
+/// 模型是否出现在降级链中（作为原模型或降级目标）
+fn is_tracked(config: &ModelFallbackConfig, model: &str) -> bool {
+    config.chains.contains_key(model) || config.chains.values().any(|chain| chain.iter().any(|m| m == model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ModelFallbackConfig {
+        ModelFallbackConfig {
+            chains: HashMap::from([(
+                "gemini-2.5-pro".to_string(),
+                vec!["gemini-2.5-flash".to_string(), "gemini-2.0-flash".to_string()],
+            )]),
+            ..ModelFallbackConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_failing_primary_falls_through_to_next() {
+        let config = config();
+        let tracker = ModelFallbackTracker::new();
+        let now = Instant::now();
+
+        tracker.record(&config, "gemini-2.5-pro", 503, now);
+        tracker.record(&config, "gemini-2.5-pro", 503, now);
+        assert_eq!(tracker.resolve(&config, "gemini-2.5-pro", now), None);
+        tracker.record(&config, "gemini-2.5-pro", 503, now);
+        assert_eq!(tracker.resolve(&config, "gemini-2.5-pro", now).as_deref(), Some("gemini-2.5-flash"));
+
+        // 第一个降级目标也持续过载时继续沿链降级
+        for _ in 0..3 {
+            tracker.record(&config, "gemini-2.5-flash", 503, now);
+        }
+        assert_eq!(tracker.resolve(&config, "gemini-2.5-pro", now).as_deref(), Some("gemini-2.0-flash"));
+
+        // 恢复窗口过后重新尝试原模型，再次过载立即重新降级
+        let later = now + Duration::from_secs(config.recovery_seconds + 1);
+        assert_eq!(tracker.resolve(&config, "gemini-2.5-pro", later), None);
+        tracker.record(&config, "gemini-2.5-pro", 503, later);
+        assert_eq!(tracker.resolve(&config, "gemini-2.5-pro", later).as_deref(), Some("gemini-2.5-flash"));
+    }
+
+    #[test]
+    fn test_healthy_primary_used_directly() {
+        let config = config();
+        let tracker = ModelFallbackTracker::new();
+        let now = Instant::now();
+
+        // 成功响应清零连续失败计数，其他错误状态不计入过载
+        for status in [503, 503, 200, 503, 503, 500, 429] {
+            tracker.record(&config, "gemini-2.5-pro", status, now);
+        }
+        assert_eq!(tracker.resolve(&config, "gemini-2.5-pro", now), None);
+
+        // 未配置降级链的模型不降级，也不记录状态
+        for _ in 0..5 {
+            tracker.record(&config, "gemini-1.5-flash", 503, now);
+        }
+        assert_eq!(tracker.resolve(&config, "gemini-1.5-flash", now), None);
+        assert!(tracker.models.lock().unwrap().get("gemini-1.5-flash").is_none());
+    }
+}
@@ -0,0 +1,127 @@
+// src/proxy/model_override.rs
+//! 按请求覆盖目标模型
+//!
+//! 客户端可通过 `X-Gemini-Model-Override` 请求头把请求改写到另一个模型，
+//! 便于在不修改客户端的情况下进行 A/B 测试。该功能需在配置中启用，且目标模型必须在允许列表中
+
+use crate::config::ModelOverrideConfig;
+
+/// 模型覆盖请求头
+pub const MODEL_OVERRIDE_HEADER: &str = "x-gemini-model-override";
+
+/// 模型覆盖结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelOverride {
+    /// 未请求覆盖或功能未启用，保持原请求
+    Unchanged,
+    /// 覆盖后的模型与请求路径
+    Rewritten { model: String, path: String },
+    /// 目标模型不在允许列表中
+    Disallowed(String),
+    /// 请求路径中没有可替换的模型
+    NoModelInPath,
+}
+
+/// 将请求路径（可含查询串）中的模型替换为指定模型
+pub fn rewrite_model_path(path_and_query: &str, model: &str) -> Option<String> {
+    let marker = "/models/";
+    let start = path_and_query.find(marker)? + marker.len();
+    let rest = &path_and_query[start..];
+    let end = rest.find([':', '/', '?']).unwrap_or(rest.len());
+    if end == 0 {
+        return None;
+    }
+    Some(format!("{}{}{}", &path_and_query[..start], model, &rest[end..]))
+}
+
+/// 根据配置解析覆盖请求头
+pub fn resolve_model_override(
+    config: &ModelOverrideConfig,
+    requested: Option<&str>,
+    path_and_query: &str,
+) -> ModelOverride {
+    let requested = match requested.map(str::trim).filter(|m| !m.is_empty()) {
+        Some(model) if config.enabled => model,
+        _ => return ModelOverride::Unchanged,
+    };
+
+    if !config.allowed_models.iter().any(|m| m == requested) {
+        return ModelOverride::Disallowed(requested.to_string());
+    }
+
+    match rewrite_model_path(path_and_query, requested) {
+        Some(path) => ModelOverride::Rewritten {
+            model: requested.to_string(),
+            path,
+        },
+        None => ModelOverride::NoModelInPath,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled_config() -> ModelOverrideConfig {
+        ModelOverrideConfig {
+            enabled: true,
+            allowed_models: vec!["gemini-2.0-flash".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_override_rewrites_upstream_path() {
+        let result = resolve_model_override(
+            &enabled_config(),
+            Some("gemini-2.0-flash"),
+            "/v1beta/models/gemini-1.5-flash:streamGenerateContent?alt=sse",
+        );
+
+        assert_eq!(
+            result,
+            ModelOverride::Rewritten {
+                model: "gemini-2.0-flash".to_string(),
+                path: "/v1beta/models/gemini-2.0-flash:streamGenerateContent?alt=sse".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_disallowed_model_rejected() {
+        let result = resolve_model_override(
+            &enabled_config(),
+            Some("gemini-ultra-internal"),
+            "/v1beta/models/gemini-1.5-flash:generateContent",
+        );
+
+        assert_eq!(result, ModelOverride::Disallowed("gemini-ultra-internal".to_string()));
+    }
+
+    #[test]
+    fn test_override_ignored_when_disabled() {
+        let config = ModelOverrideConfig {
+            enabled: false,
+            ..enabled_config()
+        };
+        let result = resolve_model_override(
+            &config,
+            Some("gemini-2.0-flash"),
+            "/v1beta/models/gemini-1.5-flash:generateContent",
+        );
+
+        assert_eq!(result, ModelOverride::Unchanged);
+        assert_eq!(
+            resolve_model_override(&enabled_config(), None, "/v1beta/models/gemini-1.5-flash:generateContent"),
+            ModelOverride::Unchanged
+        );
+    }
+
+    #[test]
+    fn test_path_without_model() {
+        assert_eq!(rewrite_model_path("/v1beta/models", "gemini-2.0-flash"), None);
+        assert_eq!(
+            resolve_model_override(&enabled_config(), Some("gemini-2.0-flash"), "/v1beta/files"),
+            ModelOverride::NoModelInPath
+        );
+    }
+}
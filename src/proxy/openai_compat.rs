@@ -0,0 +1,421 @@
+// src/proxy/openai_compat.rs
+//! OpenAI 兼容的 chat completions 接口
+//!
+//! `POST /v1/chat/completions` 的请求体转换为 Gemini `generateContent`（`stream: true` 时为
+//! `streamGenerateContent?alt=sse`）请求后走正常的认证与密钥选择流程转发；上游成功响应再转换为 OpenAI 的
+//! `chat.completion` / `chat.completion.chunk` 格式。上游错误响应原样返回。
+//! 转换后的请求体在重试时需要重放，因此请求体大小受 Pingora 重试缓冲（64KB）限制
+
+use crate::error::{GeminiProxyError, ValidationError};
+use crate::proxy::circuit_guard::error_body;
+use bytes::Bytes;
+use pingora::http::ResponseHeader;
+use pingora_error::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub const CHAT_COMPLETIONS_PATH: &str = "/v1/chat/completions";
+/// 请求体上限，与 Pingora 重试缓冲一致
+pub const MAX_OPENAI_REQUEST_BYTES: usize = 64 * 1024;
+/// 非流式响应的缓冲上限，超出后原样转发
+pub const MAX_OPENAI_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    temperature: Option<f64>,
+    max_tokens: Option<u32>,
+    #[serde(default)]
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: Value,
+}
+
+/// 转换后的 Gemini 请求
+#[derive(Debug, Clone)]
+pub struct TranslatedRequest {
+    pub model: String,
+    pub stream: bool,
+    /// 上游请求路径（含查询参数）
+    pub path_and_query: String,
+    pub body: Bytes,
+}
+
+/// 请求是否为 OpenAI chat completions 请求
+pub fn is_chat_completions(method: &str, path: &str) -> bool {
+    method.eq_ignore_ascii_case("POST") && path.trim_end_matches('/') == CHAT_COMPLETIONS_PATH
+}
+
+fn invalid(field: &str, message: impl Into<String>) -> GeminiProxyError {
+    let message = message.into();
+    GeminiProxyError::validation(
+        message.clone(),
+        vec![ValidationError {
+            field: field.to_string(),
+            message,
+            value: None,
+        }],
+    )
+}
+
+/// 消息内容：字符串，或 OpenAI 的内容片段数组（只取 `text` 片段）
+fn message_text(content: &Value) -> Option<String> {
+    match content {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(parts) => Some(
+            parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join(""),
+        ),
+        _ => None,
+    }
+}
+
+/// 将 OpenAI chat completions 请求体转换为 Gemini 请求
+pub fn translate_request(body: &[u8]) -> std::result::Result<TranslatedRequest, GeminiProxyError> {
+    let request: ChatCompletionRequest =
+        serde_json::from_slice(body).map_err(|e| invalid("body", format!("无效的 chat completions 请求体: {}", e)))?;
+
+    let model = request.model.strip_prefix("models/").unwrap_or(&request.model).to_string();
+    let valid_model = !model.is_empty()
+        && model
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid_model {
+        return Err(invalid("model", format!("无效的模型名称: {}", request.model)));
+    }
+
+    let mut system_parts = Vec::new();
+    let mut contents = Vec::new();
+    for (index, message) in request.messages.iter().enumerate() {
+        let text = message_text(&message.content)
+            .ok_or_else(|| invalid(&format!("messages[{}].content", index), "消息内容必须是字符串或内容片段数组"))?;
+        let role = match message.role.as_str() {
+            "system" | "developer" => {
+                system_parts.push(json!({ "text": text }));
+                continue;
+            }
+            "user" => "user",
+            "assistant" => "model",
+            other => {
+                return Err(invalid(&format!("messages[{}].role", index), format!("不支持的消息角色: {}", other)));
+            }
+        };
+        contents.push(json!({ "role": role, "parts": [{ "text": text }] }));
+    }
+    if contents.is_empty() {
+        return Err(invalid("messages", "至少需要一条 user 或 assistant 消息"));
+    }
+
+    let mut gemini = json!({ "contents": contents });
+    if !system_parts.is_empty() {
+        gemini["systemInstruction"] = json!({ "parts": system_parts });
+    }
+    let mut generation_config = serde_json::Map::new();
+    if let Some(temperature) = request.temperature {
+        generation_config.insert("temperature".to_string(), json!(temperature));
+    }
+    if let Some(max_tokens) = request.max_tokens {
+        generation_config.insert("maxOutputTokens".to_string(), json!(max_tokens));
+    }
+    if !generation_config.is_empty() {
+        gemini["generationConfig"] = Value::Object(generation_config);
+    }
+
+    let path_and_query = if request.stream {
+        format!("/v1beta/models/{}:streamGenerateContent?alt=sse", model)
+    } else {
+        format!("/v1beta/models/{}:generateContent", model)
+    };
+    Ok(TranslatedRequest {
+        model,
+        stream: request.stream,
+        path_and_query,
+        body: Bytes::from(gemini.to_string()),
+    })
+}
+
+/// 请求体无法转换时返回的 400 响应
+pub fn invalid_request_response(error: GeminiProxyError, request_id: &str) -> Result<(ResponseHeader, Bytes)> {
+    let error = error.with_request_id(request_id);
+    let body = Bytes::from(error_body(400, &error).to_string());
+    let mut header = ResponseHeader::build(400, Some(2))?;
+    header.insert_header("content-type", "application/json")?;
+    header.insert_header("content-length", body.len().to_string())?;
+    Ok((header, body))
+}
+
+/// Gemini finishReason 映射为 OpenAI finish_reason
+fn finish_reason(reason: Option<&str>) -> Value {
+    match reason {
+        None | Some("FINISH_REASON_UNSPECIFIED") => Value::Null,
+        Some("MAX_TOKENS") => json!("length"),
+        Some("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII") => json!("content_filter"),
+        Some(_) => json!("stop"),
+    }
+}
+
+fn candidate_text(candidate: &Value) -> String {
+    candidate["content"]["parts"]
+        .as_array()
+        .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect::<Vec<_>>().join(""))
+        .unwrap_or_default()
+}
+
+fn usage(response: &Value) -> Option<Value> {
+    let metadata = response.get("usageMetadata")?;
+    let prompt = metadata["promptTokenCount"].as_u64().unwrap_or(0);
+    let completion = metadata["candidatesTokenCount"].as_u64().unwrap_or(0);
+    let total = metadata["totalTokenCount"].as_u64().unwrap_or(prompt + completion);
+    Some(json!({ "prompt_tokens": prompt, "completion_tokens": completion, "total_tokens": total }))
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// 将上游 Gemini 成功响应转换为 OpenAI 格式
+#[derive(Debug)]
+pub struct ChatCompletionTranslator {
+    id: String,
+    model: String,
+    created: i64,
+    stream: bool,
+    buffer: Vec<u8>,
+    /// 流式响应是否已输出过 `role` 增量
+    role_sent: bool,
+    /// 非流式响应超出缓冲上限后改为原样转发
+    passthrough: bool,
+}
+
+impl ChatCompletionTranslator {
+    pub fn new(request: &TranslatedRequest, request_id: &str) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", request_id),
+            model: request.model.clone(),
+            created: chrono::Utc::now().timestamp(),
+            stream: request.stream,
+            buffer: Vec::new(),
+            role_sent: false,
+            passthrough: false,
+        }
+    }
+
+    /// 转换完整的 generateContent 响应
+    pub fn translate_response(&self, response: &[u8]) -> Option<Bytes> {
+        let response: Value = serde_json::from_slice(response).ok()?;
+        let choices: Vec<Value> = response["candidates"]
+            .as_array()
+            .map(|candidates| {
+                candidates
+                    .iter()
+                    .enumerate()
+                    .map(|(i, candidate)| {
+                        json!({
+                            "index": candidate["index"].as_u64().unwrap_or(i as u64),
+                            "message": { "role": "assistant", "content": candidate_text(candidate) },
+                            "finish_reason": finish_reason(candidate["finishReason"].as_str()),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut completion = json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": choices,
+        });
+        if let Some(usage) = usage(&response) {
+            completion["usage"] = usage;
+        }
+        Some(Bytes::from(completion.to_string()))
+    }
+
+    /// 转换一个 SSE 事件的 data 内容为 `chat.completion.chunk` 事件
+    fn translate_event(&mut self, data: &str) -> Option<String> {
+        let response: Value = serde_json::from_str(data).ok()?;
+        let candidates = response["candidates"].as_array().cloned().unwrap_or_default();
+        let mut choices = Vec::with_capacity(candidates.len());
+        let mut finished = false;
+        for (i, candidate) in candidates.iter().enumerate() {
+            let mut delta = json!({ "content": candidate_text(candidate) });
+            if !self.role_sent {
+                delta["role"] = json!("assistant");
+            }
+            let reason = finish_reason(candidate["finishReason"].as_str());
+            finished |= !reason.is_null();
+            choices.push(json!({
+                "index": candidate["index"].as_u64().unwrap_or(i as u64),
+                "delta": delta,
+                "finish_reason": reason,
+            }));
+        }
+        self.role_sent |= !choices.is_empty();
+
+        let mut chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": choices,
+        });
+        if finished {
+            if let Some(usage) = usage(&response) {
+                chunk["usage"] = usage;
+            }
+        }
+        Some(format!("data: {}\n\n", chunk))
+    }
+
+    /// 转换缓冲区中所有完整的 SSE 事件
+    fn drain_events(&mut self, flush: bool) -> String {
+        let mut out = String::new();
+        loop {
+            let boundary = [&b"\r\n\r\n"[..], &b"\n\n"[..]]
+                .iter()
+                .filter_map(|sep| find_bytes(&self.buffer, sep).map(|i| (i, sep.len())))
+                .min_by_key(|(i, _)| *i);
+            let (end, consumed) = match boundary {
+                Some((i, len)) => (i, i + len),
+                None if flush && !self.buffer.iter().all(u8::is_ascii_whitespace) => (self.buffer.len(), self.buffer.len()),
+                None => break,
+            };
+            let event = String::from_utf8_lossy(&self.buffer[..end]).into_owned();
+            self.buffer.drain(..consumed);
+
+            let data = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join("\n");
+            if let Some(chunk) = self.translate_event(&data) {
+                out.push_str(&chunk);
+            }
+        }
+        out
+    }
+
+    /// 处理一块上游响应体：非流式响应缓冲后在结束时整体转换，流式响应按事件转换并在结束时追加 `[DONE]`
+    pub fn push(&mut self, body: &mut Option<Bytes>, end_of_stream: bool) {
+        if self.passthrough {
+            return;
+        }
+        if let Some(chunk) = body.take() {
+            self.buffer.extend_from_slice(&chunk);
+        }
+
+        if self.stream {
+            let mut out = self.drain_events(end_of_stream);
+            if end_of_stream {
+                out.push_str("data: [DONE]\n\n");
+            }
+            if !out.is_empty() {
+                *body = Some(Bytes::from(out));
+            }
+            return;
+        }
+
+        if self.buffer.len() > MAX_OPENAI_RESPONSE_BYTES {
+            tracing::warn!("OpenAI 兼容响应超过缓冲上限，原样转发上游响应");
+            *body = Some(Bytes::from(std::mem::take(&mut self.buffer)));
+            self.passthrough = true;
+        } else if end_of_stream {
+            let buffered = std::mem::take(&mut self.buffer);
+            *body = Some(self.translate_response(&buffered).unwrap_or_else(|| Bytes::from(buffered)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_chat_request() {
+        let body = br#"{
+            "model": "gemini-1.5-flash",
+            "messages": [
+                {"role": "system", "content": "You are terse."},
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": [{"type": "text", "text": "Hello"}]},
+                {"role": "user", "content": "Bye"}
+            ],
+            "temperature": 0.2,
+            "max_tokens": 64
+        }"#;
+        let translated = translate_request(body).unwrap();
+        assert_eq!(translated.model, "gemini-1.5-flash");
+        assert_eq!(translated.path_and_query, "/v1beta/models/gemini-1.5-flash:generateContent");
+
+        let gemini: Value = serde_json::from_slice(&translated.body).unwrap();
+        assert_eq!(gemini["systemInstruction"]["parts"][0]["text"], "You are terse.");
+        assert_eq!(gemini["contents"].as_array().unwrap().len(), 3);
+        assert_eq!(gemini["contents"][1]["role"], "model");
+        assert_eq!(gemini["contents"][1]["parts"][0]["text"], "Hello");
+        assert_eq!(gemini["generationConfig"]["temperature"], 0.2);
+        assert_eq!(gemini["generationConfig"]["maxOutputTokens"], 64);
+
+        let streaming = translate_request(br#"{"model":"gemini-pro","messages":[{"role":"user","content":"x"}],"stream":true}"#)
+            .unwrap();
+        assert_eq!(streaming.path_and_query, "/v1beta/models/gemini-pro:streamGenerateContent?alt=sse");
+
+        assert!(translate_request(br#"{"model":"../admin","messages":[{"role":"user","content":"x"}]}"#).is_err());
+        assert!(translate_request(br#"{"model":"gemini-pro","messages":[{"role":"tool","content":"x"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_translate_responses() {
+        let request = translate_request(br#"{"model":"gemini-pro","messages":[{"role":"user","content":"x"}]}"#).unwrap();
+        let mut translator = ChatCompletionTranslator::new(&request, "req-1");
+        let upstream = r#"{"candidates":[{"content":{"parts":[{"text":"Hel"},{"text":"lo"}],"role":"model"},"finishReason":"STOP","index":0}],
+            "usageMetadata":{"promptTokenCount":5,"candidatesTokenCount":2,"totalTokenCount":7}}"#;
+        let mut body = Some(Bytes::from(&upstream[..20]));
+        translator.push(&mut body, false);
+        assert!(body.is_none());
+        let mut body = Some(Bytes::from(&upstream[20..]));
+        translator.push(&mut body, true);
+
+        let completion: Value = serde_json::from_slice(&body.unwrap()).unwrap();
+        assert_eq!(completion["id"], "chatcmpl-req-1");
+        assert_eq!(completion["object"], "chat.completion");
+        assert_eq!(completion["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(completion["choices"][0]["finish_reason"], "stop");
+        assert_eq!(completion["usage"]["total_tokens"], 7);
+
+        // 流式：事件跨数据块拆分，结束时追加 [DONE]
+        let request =
+            translate_request(br#"{"model":"gemini-pro","messages":[{"role":"user","content":"x"}],"stream":true}"#).unwrap();
+        let mut translator = ChatCompletionTranslator::new(&request, "req-2");
+        let mut body = Some(Bytes::from(
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hel\"}]}}]}\r\n\r\ndata: {\"candidates\":[{\"content\"",
+        ));
+        translator.push(&mut body, false);
+        let first = String::from_utf8(body.unwrap().to_vec()).unwrap();
+        assert_eq!(first.matches("data: ").count(), 1);
+        let chunk: Value = serde_json::from_str(first.trim().trim_start_matches("data: ")).unwrap();
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+        assert_eq!(chunk["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(chunk["choices"][0]["delta"]["content"], "Hel");
+
+        let mut body = Some(Bytes::from(
+            ":{\"parts\":[{\"text\":\"lo\"}]},\"finishReason\":\"MAX_TOKENS\"}],\"usageMetadata\":{\"promptTokenCount\":1,\"candidatesTokenCount\":2}}\r\n\r\n",
+        ));
+        translator.push(&mut body, true);
+        let rest = String::from_utf8(body.unwrap().to_vec()).unwrap();
+        assert!(rest.ends_with("data: [DONE]\n\n"));
+        let last: Value = serde_json::from_str(rest.split("\n\n").next().unwrap().trim_start_matches("data: ")).unwrap();
+        assert!(last["choices"][0]["delta"].get("role").is_none());
+        assert_eq!(last["choices"][0]["finish_reason"], "length");
+        assert_eq!(last["usage"]["total_tokens"], 3);
+    }
+}
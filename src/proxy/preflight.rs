@@ -0,0 +1,280 @@
+// src/proxy/preflight.rs
+//! 上下文窗口预检
+//!
+//! 超过模型上下文上限的请求在上游必然失败，白白消耗一次调用。启用预检后，代理在转发请求体前
+//! 缓冲完整的 JSON 请求体，用 [`TokenEstimator`] 估算提示词 token 数，超过配置的模型上限时
+//! 中止转发并返回 400（`reason: context_length_exceeded`）。上游只收到不完整的请求，不会处理。
+//! 非 JSON 请求体、未配置上限的模型以及超过缓冲上限的请求体跳过预检
+
+use crate::config::ContextPreflightConfig;
+use crate::error::GeminiProxyError;
+use crate::proxy::circuit_guard::error_body;
+use bytes::Bytes;
+use http::HeaderMap;
+use pingora::http::ResponseHeader;
+use pingora_error::Result;
+use std::collections::HashMap;
+
+/// 拒绝原因
+pub const CONTEXT_EXCEEDED_REASON: &str = "context_length_exceeded";
+
+/// 提示词 token 数估算器
+pub trait TokenEstimator: Send + Sync {
+    /// 估算 Gemini 请求体中提示词的 token 数
+    fn estimate_prompt_tokens(&self, request: &serde_json::Value) -> u64;
+}
+
+/// 默认估算器：统计所有 `text` 字段的字符数，按固定的字符/token 比例换算
+#[derive(Debug, Clone)]
+pub struct CharRatioEstimator {
+    chars_per_token: f64,
+}
+
+impl CharRatioEstimator {
+    pub fn new(chars_per_token: f64) -> Self {
+        Self { chars_per_token }
+    }
+}
+
+impl TokenEstimator for CharRatioEstimator {
+    fn estimate_prompt_tokens(&self, request: &serde_json::Value) -> u64 {
+        let chars = count_text_chars(request);
+        (chars as f64 / self.chars_per_token).ceil() as u64
+    }
+}
+
+/// 递归统计 `text` 字段（contents、systemInstruction 等各处的文本片段）的字符数
+fn count_text_chars(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(name, value)| match value {
+                serde_json::Value::String(text) if name == "text" => text.chars().count(),
+                _ => count_text_chars(value),
+            })
+            .sum(),
+        serde_json::Value::Array(items) => items.iter().map(count_text_chars).sum(),
+        _ => 0,
+    }
+}
+
+/// 查找模型上下文上限：优先精确匹配，其次最长前缀匹配
+pub fn find_context_limit(limits: &HashMap<String, u64>, model: &str) -> Option<u64> {
+    if let Some(limit) = limits.get(model) {
+        return Some(*limit);
+    }
+    limits
+        .iter()
+        .filter(|(name, _)| model.starts_with(name.as_str()))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, limit)| *limit)
+}
+
+/// 请求体是否可能为 JSON（未声明 content-type 时也尝试解析）
+pub fn is_json_request(headers: &HeaderMap) -> bool {
+    match headers.get("content-type").and_then(|v| v.to_str().ok()) {
+        Some(content_type) => content_type.to_ascii_lowercase().contains("json"),
+        None => true,
+    }
+}
+
+/// 超过上下文上限的请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextExceeded {
+    pub model: String,
+    pub estimated_tokens: u64,
+    pub limit: u64,
+}
+
+/// 检查请求体，无法解析为 JSON 时跳过（返回 None）
+pub fn check_context(estimator: &dyn TokenEstimator, model: &str, limit: u64, body: &[u8]) -> Option<ContextExceeded> {
+    let request: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let estimated_tokens = estimator.estimate_prompt_tokens(&request);
+    (estimated_tokens > limit).then(|| ContextExceeded {
+        model: model.to_string(),
+        estimated_tokens,
+        limit,
+    })
+}
+
+/// 预检缓冲过程中的下一步
+#[derive(Debug, PartialEq, Eq)]
+pub enum PreflightStep {
+    /// 继续缓冲，本次不向上游转发
+    Buffering,
+    /// 预检结束（通过或跳过），缓冲的请求体已放回待转发的数据中
+    Forward,
+    /// 超过上下文上限，应中止转发
+    Rejected(ContextExceeded),
+}
+
+/// 单个请求的预检状态：缓冲请求体直到读取完毕
+#[derive(Debug)]
+pub struct PendingPreflight {
+    model: String,
+    limit: u64,
+    max_body_bytes: usize,
+    buffer: Vec<u8>,
+}
+
+impl PendingPreflight {
+    /// 按配置为请求创建预检，未启用、模型未配置上限或请求体不是 JSON 时返回 None
+    pub fn for_request(config: &ContextPreflightConfig, model: Option<&str>, headers: &HeaderMap) -> Option<Self> {
+        if !config.enabled || !is_json_request(headers) {
+            return None;
+        }
+        let model = model?;
+        let limit = find_context_limit(&config.context_limits, model)?;
+        Some(Self {
+            model: model.to_string(),
+            limit,
+            max_body_bytes: config.max_body_bytes,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// 处理一块请求体：缓冲期间清空待转发的数据，结束时放回完整的请求体
+    pub fn push(&mut self, body: &mut Option<Bytes>, end_of_stream: bool, estimator: &dyn TokenEstimator) -> PreflightStep {
+        if let Some(chunk) = body.take() {
+            self.buffer.extend_from_slice(&chunk);
+        }
+
+        if self.buffer.len() > self.max_body_bytes {
+            *body = Some(Bytes::from(std::mem::take(&mut self.buffer)));
+            return PreflightStep::Forward;
+        }
+        if !end_of_stream {
+            return PreflightStep::Buffering;
+        }
+
+        match check_context(estimator, &self.model, self.limit, &self.buffer) {
+            Some(exceeded) => PreflightStep::Rejected(exceeded),
+            None => {
+                *body = Some(Bytes::from(std::mem::take(&mut self.buffer)));
+                PreflightStep::Forward
+            }
+        }
+    }
+}
+
+/// 构建 400 拒绝响应
+pub fn context_exceeded_response(exceeded: &ContextExceeded, request_id: &str) -> Result<(ResponseHeader, Bytes)> {
+    let error = GeminiProxyError::validation(
+        format!(
+            "请求估算约 {} 个 token，超过模型 {} 的上下文上限 {}",
+            exceeded.estimated_tokens, exceeded.model, exceeded.limit
+        ),
+        vec![],
+    )
+    .with_request_id(request_id);
+
+    let mut body = error_body(400, &error);
+    body["error"]["reason"] = serde_json::Value::from(CONTEXT_EXCEEDED_REASON);
+    body["error"]["estimated_tokens"] = serde_json::Value::from(exceeded.estimated_tokens);
+    body["error"]["context_limit"] = serde_json::Value::from(exceeded.limit);
+    let body = Bytes::from(body.to_string());
+
+    let mut header = ResponseHeader::build(400, Some(3))?;
+    header.insert_header("content-type", "application/json")?;
+    header.insert_header("content-length", body.len().to_string())?;
+    Ok((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ContextPreflightConfig {
+        ContextPreflightConfig {
+            enabled: true,
+            context_limits: HashMap::from([("gemini-1.5-flash".to_string(), 10)]),
+            ..ContextPreflightConfig::default()
+        }
+    }
+
+    fn request(text: &str) -> Bytes {
+        Bytes::from(
+            serde_json::json!({
+                "systemInstruction": { "parts": [{ "text": "sys" }] },
+                "contents": [{ "role": "user", "parts": [{ "text": text }] }]
+            })
+            .to_string(),
+        )
+    }
+
+    fn json_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        headers
+    }
+
+    /// 分两块推送请求体，返回最终结果与转发的数据
+    fn run(preflight: &mut PendingPreflight, body: Bytes) -> (PreflightStep, Option<Bytes>) {
+        let estimator = CharRatioEstimator::new(4.0);
+        let (first, second) = body.split_at(body.len() / 2);
+        let mut chunk = Some(Bytes::copy_from_slice(first));
+        assert_eq!(preflight.push(&mut chunk, false, &estimator), PreflightStep::Buffering);
+        assert!(chunk.is_none());
+
+        let mut chunk = Some(Bytes::copy_from_slice(second));
+        let step = preflight.push(&mut chunk, true, &estimator);
+        (step, chunk)
+    }
+
+    #[test]
+    fn test_over_limit_request_rejected() {
+        let mut preflight = PendingPreflight::for_request(&config(), Some("gemini-1.5-flash-002"), &json_headers()).unwrap();
+
+        // "sys" + 40 个字符 = 43 个字符，约 11 个 token
+        let (step, forwarded) = run(&mut preflight, request(&"a".repeat(40)));
+        let exceeded = match step {
+            PreflightStep::Rejected(exceeded) => exceeded,
+            other => panic!("unexpected step: {:?}", other),
+        };
+        assert_eq!((exceeded.estimated_tokens, exceeded.limit), (11, 10));
+        assert!(forwarded.is_none());
+
+        let (header, body) = context_exceeded_response(&exceeded, "req-1").unwrap();
+        assert_eq!(header.status.as_u16(), 400);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["reason"], CONTEXT_EXCEEDED_REASON);
+        assert_eq!(body["error"]["request_id"], "req-1");
+        assert_eq!(body["error"]["context_limit"], 10);
+    }
+
+    #[test]
+    fn test_under_limit_request_forwarded_intact() {
+        let mut preflight = PendingPreflight::for_request(&config(), Some("gemini-1.5-flash"), &json_headers()).unwrap();
+
+        let body = request("short prompt");
+        let (step, forwarded) = run(&mut preflight, body.clone());
+        assert_eq!(step, PreflightStep::Forward);
+        assert_eq!(forwarded, Some(body));
+    }
+
+    #[test]
+    fn test_skips_non_json_and_unconfigured_models() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "multipart/form-data; boundary=x".parse().unwrap());
+        assert!(PendingPreflight::for_request(&config(), Some("gemini-1.5-flash"), &headers).is_none());
+        assert!(PendingPreflight::for_request(&config(), Some("gemini-1.5-pro"), &json_headers()).is_none());
+        assert!(PendingPreflight::for_request(&ContextPreflightConfig::default(), Some("gemini-1.5-flash"), &json_headers()).is_none());
+
+        // 声明为 JSON 但无法解析时同样放行
+        let estimator = CharRatioEstimator::new(4.0);
+        assert!(check_context(&estimator, "gemini-1.5-flash", 1, b"not json").is_none());
+    }
+
+    #[test]
+    fn test_custom_estimator() {
+        struct Fixed(u64);
+        impl TokenEstimator for Fixed {
+            fn estimate_prompt_tokens(&self, _request: &serde_json::Value) -> u64 {
+                self.0
+            }
+        }
+
+        assert!(check_context(&Fixed(10), "m", 10, b"{}").is_none());
+        assert_eq!(check_context(&Fixed(11), "m", 10, b"{}").unwrap().estimated_tokens, 11);
+    }
+}
@@ -0,0 +1,146 @@
+// src/proxy/request_id.rs
+//! 请求 ID 的读取与向上游传播
+//!
+//! 按配置的请求头顺序读取客户端提供的请求 ID（例如 `x-request-id`、`x-cloud-trace-context`、
+//! `traceparent`），第一个非空值作为代理内部使用的请求 ID（日志、审计、错误响应）。
+//! 客户端未提供的请求头由代理生成后转发给上游，使链路追踪能够跨越代理关联。
+//! 生成的取值共用同一个追踪 ID，并遵循各请求头的格式
+
+use crate::config::RequestIdConfig;
+use pingora::http::RequestHeader;
+use pingora_error::Result;
+use uuid::Uuid;
+
+/// W3C Trace Context 请求头
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Google Cloud Trace 请求头
+pub const CLOUD_TRACE_HEADER: &str = "x-cloud-trace-context";
+
+/// 为缺失的请求头生成的追踪标识
+struct GeneratedTrace {
+    id: Uuid,
+    span_id: u64,
+}
+
+impl GeneratedTrace {
+    fn new() -> Self {
+        let id = Uuid::new_v4();
+        // 使用 UUID 的低 64 位作为 span ID，保证非零
+        let span_id = (id.as_u128() as u64).max(1);
+        Self { id, span_id }
+    }
+
+    /// 按请求头格式生成取值
+    fn value_for(&self, header: &str) -> String {
+        if header.eq_ignore_ascii_case(TRACEPARENT_HEADER) {
+            format!("00-{}-{:016x}-01", self.id.simple(), self.span_id)
+        } else if header.eq_ignore_ascii_case(CLOUD_TRACE_HEADER) {
+            format!("{}/{};o=1", self.id.simple(), self.span_id)
+        } else {
+            self.id.to_string()
+        }
+    }
+}
+
+/// 读取请求头的非空取值
+fn header_value(header: &RequestHeader, name: &str) -> Option<String> {
+    header
+        .headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// 读取请求 ID；启用传播时为缺失的请求头写入生成的取值（随请求转发给上游）
+pub fn apply_request_id(config: &RequestIdConfig, header: &mut RequestHeader) -> Result<String> {
+    let provided = config.headers.iter().find_map(|name| header_value(header, name));
+
+    let generated = GeneratedTrace::new();
+    if config.propagate {
+        for name in &config.headers {
+            if header_value(header, name).is_none() {
+                header.insert_header(name.clone(), generated.value_for(name))?;
+            }
+        }
+    }
+
+    Ok(provided.unwrap_or_else(|| match config.headers.first() {
+        Some(name) if config.propagate => generated.value_for(name),
+        _ => generated.id.to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(headers: &[&str]) -> RequestIdConfig {
+        RequestIdConfig {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            propagate: true,
+        }
+    }
+
+    fn request(headers: &[(&str, &str)]) -> RequestHeader {
+        let mut header = RequestHeader::build("POST", b"/v1beta/models/gemini-1.5-flash:generateContent", None).unwrap();
+        for (name, value) in headers {
+            header.insert_header(name.to_string(), *value).unwrap();
+        }
+        header
+    }
+
+    fn forwarded(header: &RequestHeader, name: &str) -> String {
+        header_value(header, name).unwrap()
+    }
+
+    #[test]
+    fn test_propagates_configured_header_value() {
+        let config = config(&["x-cloud-trace-context", "x-request-id"]);
+        let mut header = request(&[("x-cloud-trace-context", "105445aa7843bc8bf206b12000100000/1;o=1")]);
+
+        let request_id = apply_request_id(&config, &mut header).unwrap();
+
+        assert_eq!(request_id, "105445aa7843bc8bf206b12000100000/1;o=1");
+        // 客户端提供的取值原样转发，缺失的请求头由代理补齐
+        assert_eq!(forwarded(&header, "x-cloud-trace-context"), request_id);
+        assert!(Uuid::parse_str(&forwarded(&header, "x-request-id")).is_ok());
+    }
+
+    #[test]
+    fn test_generates_formatted_ids_when_missing() {
+        let config = config(&["traceparent", "x-cloud-trace-context", "x-request-id"]);
+        let mut header = request(&[("x-request-id", "  ")]);
+
+        let request_id = apply_request_id(&config, &mut header).unwrap();
+
+        let traceparent = forwarded(&header, "traceparent");
+        assert_eq!(request_id, traceparent);
+        let parts: Vec<&str> = traceparent.split('-').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!((parts[0], parts[1].len(), parts[2].len(), parts[3]), ("00", 32, 16, "01"));
+
+        // 所有生成的请求头共用同一个追踪 ID
+        let cloud_trace = forwarded(&header, "x-cloud-trace-context");
+        assert!(cloud_trace.starts_with(&format!("{}/", parts[1])));
+        assert!(cloud_trace.ends_with(";o=1"));
+        let request_uuid = Uuid::parse_str(&forwarded(&header, "x-request-id")).unwrap();
+        assert_eq!(request_uuid.simple().to_string(), parts[1]);
+    }
+
+    #[test]
+    fn test_propagation_disabled_leaves_request_untouched() {
+        let config = RequestIdConfig {
+            propagate: false,
+            ..config(&["x-request-id"])
+        };
+        let mut header = request(&[]);
+
+        let request_id = apply_request_id(&config, &mut header).unwrap();
+
+        assert!(Uuid::parse_str(&request_id).is_ok());
+        assert!(header.headers.get("x-request-id").is_none());
+    }
+}
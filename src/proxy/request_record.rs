@@ -0,0 +1,149 @@
+// src/proxy/request_record.rs
+//! 请求汇总记录
+//!
+//! 每个请求在响应结束（用量已知）后输出一条记录，把调用方、选中的密钥、模型与令牌用量关联起来，
+//! 用于成本归属。流式响应的用量按块累加得到
+
+use crate::config::ModelCost;
+use crate::metrics::MetricsCollector;
+use crate::proxy::usage::{estimate_cost, TokenUsage, UpstreamBody};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// 访问日志中请求汇总记录的 tracing target
+pub const REQUEST_RECORD_TARGET: &str = "gemini_proxy::request_record";
+
+/// 单个请求的汇总记录
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequestRecord {
+    pub request_id: String,
+    /// 调用方标识（x-session-id 或客户端 IP）
+    pub caller: String,
+    pub api_key_id: Option<String>,
+    pub model: Option<String>,
+    pub status: Option<u16>,
+    pub streaming: bool,
+    /// 上游返回的令牌用量（响应中无用量信息时为 None）
+    pub usage: Option<TokenUsage>,
+    pub estimated_cost: f64,
+    pub duration_ms: i64,
+}
+
+impl RequestRecord {
+    pub fn new(request_id: String, caller: String, api_key_id: Option<String>, model: Option<String>) -> Self {
+        Self {
+            request_id,
+            caller,
+            api_key_id,
+            model,
+            status: None,
+            streaming: false,
+            usage: None,
+            estimated_cost: 0.0,
+            duration_ms: 0,
+        }
+    }
+
+    pub fn with_status(mut self, status: Option<u16>, duration_ms: i64) -> Self {
+        self.status = status;
+        self.duration_ms = duration_ms;
+        self
+    }
+
+    /// 根据上游响应体填充用量与估算成本
+    pub fn with_upstream_body(mut self, body: &UpstreamBody, model_costs: &HashMap<String, ModelCost>) -> Self {
+        self.streaming = body.is_streaming();
+        self.usage = body.summed_usage();
+        self.estimated_cost = match (&self.model, &self.usage) {
+            (Some(model), Some(usage)) => estimate_cost(model_costs, model, usage),
+            _ => 0.0,
+        };
+        self
+    }
+
+    /// 输出到访问日志，并按需累加令牌指标
+    pub fn emit(&self, metrics: Option<&MetricsCollector>) {
+        let usage = self.usage.unwrap_or_default();
+        tracing::info!(
+            target: REQUEST_RECORD_TARGET,
+            request_id = %self.request_id,
+            caller = %self.caller,
+            api_key_id = self.api_key_id.as_deref().unwrap_or("N/A"),
+            model = self.model.as_deref().unwrap_or("N/A"),
+            status = self.status,
+            streaming = self.streaming,
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
+            total_tokens = usage.total_tokens,
+            estimated_cost = self.estimated_cost,
+            duration_ms = self.duration_ms,
+            "请求汇总"
+        );
+
+        if let (Some(metrics), Some(usage), Some(key_id)) = (metrics, self.usage, &self.api_key_id) {
+            let model = self.model.as_deref().unwrap_or("unknown");
+            metrics.record_request_tokens(&self.caller, key_id, model, usage.prompt_tokens, usage.completion_tokens);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::usage::parse_upstream_body;
+
+    fn costs() -> HashMap<String, ModelCost> {
+        let mut costs = HashMap::new();
+        costs.insert(
+            "gemini-1.5-pro".to_string(),
+            ModelCost { input_per_1k_tokens: 0.001, output_per_1k_tokens: 0.002 },
+        );
+        costs
+    }
+
+    fn record() -> RequestRecord {
+        RequestRecord::new(
+            "req-1".to_string(),
+            "session:abc".to_string(),
+            Some("key1".to_string()),
+            Some("gemini-1.5-pro".to_string()),
+        )
+        .with_status(Some(200), 120)
+    }
+
+    #[test]
+    fn test_record_ties_caller_key_model_and_tokens() {
+        let body = br#"{"candidates":[],"usageMetadata":{"promptTokenCount":1000,"candidatesTokenCount":500,"totalTokenCount":1500}}"#;
+        let record = record().with_upstream_body(&parse_upstream_body(body), &costs());
+
+        assert_eq!(record.caller, "session:abc");
+        assert_eq!(record.api_key_id.as_deref(), Some("key1"));
+        assert_eq!(record.model.as_deref(), Some("gemini-1.5-pro"));
+        assert_eq!(record.status, Some(200));
+        assert!(!record.streaming);
+        assert_eq!(
+            record.usage,
+            Some(TokenUsage { prompt_tokens: 1000, completion_tokens: 500, total_tokens: 1500 })
+        );
+        assert!((record.estimated_cost - 0.002).abs() < 1e-12);
+
+        let metrics = MetricsCollector::new();
+        record.emit(Some(&metrics));
+        assert!(metrics.get_metrics().contains(
+            "gemini_request_tokens_total{caller=\"session:abc\",key_id=\"key1\",kind=\"completion\",model=\"gemini-1.5-pro\"} 500"
+        ));
+    }
+
+    #[test]
+    fn test_streaming_record_sums_chunk_usage() {
+        let sse = b"data: {\"usageMetadata\":{\"promptTokenCount\":10,\"candidatesTokenCount\":4}}\n\n\
+                    data: {\"usageMetadata\":{\"promptTokenCount\":10,\"candidatesTokenCount\":6}}\n\n";
+        let record = record().with_upstream_body(&parse_upstream_body(sse), &costs());
+
+        assert!(record.streaming);
+        assert_eq!(
+            record.usage,
+            Some(TokenUsage { prompt_tokens: 10, completion_tokens: 10, total_tokens: 20 })
+        );
+    }
+}
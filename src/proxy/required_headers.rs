@@ -0,0 +1,93 @@
+// src/proxy/required_headers.rs
+//! 必需请求头检查
+//!
+//! 运维可要求每个请求都携带特定请求头（例如 `X-Team` 或计费标识）。缺少任一请求头（或值为空）的请求
+//! 在选择密钥之前直接返回 400，响应体列出缺少的请求头
+
+use crate::error::{GeminiProxyError, ValidationError};
+use crate::proxy::circuit_guard::error_body;
+use bytes::Bytes;
+use http::HeaderMap;
+use pingora::http::ResponseHeader;
+use pingora_error::Result;
+
+/// 拒绝原因（写入审计日志元数据与响应体）
+pub const MISSING_HEADERS_REASON: &str = "missing_required_headers";
+
+/// 返回缺少（或值为空）的必需请求头，按配置顺序排列
+pub fn missing_required_headers(required: &[String], headers: &HeaderMap) -> Vec<String> {
+    required
+        .iter()
+        .filter(|name| {
+            let present = headers
+                .get(name.as_str())
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| !v.trim().is_empty());
+            !present
+        })
+        .cloned()
+        .collect()
+}
+
+/// 构造列出缺少请求头的 400 响应
+pub fn missing_headers_response(missing: &[String], request_id: &str) -> Result<(ResponseHeader, Bytes)> {
+    let validation_errors = missing
+        .iter()
+        .map(|name| ValidationError {
+            field: name.clone(),
+            message: "缺少必需的请求头".to_string(),
+            value: None,
+        })
+        .collect();
+    let error = GeminiProxyError::validation(
+        format!("缺少必需的请求头: {}", missing.join(", ")),
+        validation_errors,
+    )
+    .with_request_id(request_id);
+
+    let mut body = error_body(400, &error);
+    body["error"]["reason"] = serde_json::Value::from(MISSING_HEADERS_REASON);
+    body["error"]["missing_headers"] = serde_json::Value::from(missing.to_vec());
+    let body = Bytes::from(body.to_string());
+
+    let mut header = ResponseHeader::build(400, Some(3))?;
+    header.insert_header("content-type", "application/json")?;
+    header.insert_header("content-length", body.len().to_string())?;
+    Ok((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn required() -> Vec<String> {
+        vec!["X-Team".to_string(), "x-billing-id".to_string()]
+    }
+
+    #[test]
+    fn test_compliant_request_passes() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-team", "search".parse().unwrap());
+        headers.insert("x-billing-id", "cc-42".parse().unwrap());
+
+        assert!(missing_required_headers(&required(), &headers).is_empty());
+        // 未配置必需请求头时不做限制
+        assert!(missing_required_headers(&[], &HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_missing_header_rejected_with_list() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-billing-id", "  ".parse().unwrap());
+
+        let missing = missing_required_headers(&required(), &headers);
+        assert_eq!(missing, vec!["X-Team".to_string(), "x-billing-id".to_string()]);
+
+        let (header, body) = missing_headers_response(&missing, "req-1").unwrap();
+        assert_eq!(header.status.as_u16(), 400);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["reason"], MISSING_HEADERS_REASON);
+        assert_eq!(body["error"]["missing_headers"], serde_json::json!(["X-Team", "x-billing-id"]));
+        assert_eq!(body["error"]["request_id"], "req-1");
+    }
+}
@@ -0,0 +1,304 @@
+// src/proxy/response_cache.rs
+//! 确定性请求的响应缓存
+//!
+//! 对 temperature 为 0 的非流式请求，按请求路径（包含模型）与请求体的内容哈希缓存上游的成功响应，
+//! 相同请求在有效期内直接由代理返回，不再消耗上游配额。缓存需在配置中启用，
+//! 并由客户端通过 `X-Gemini-Cache` 请求头或按路径前缀配置显式选择。
+//! 流式响应与错误响应永不缓存，响应通过 `X-Cache` 请求头标明 HIT / MISS / BYPASS
+
+use crate::config::ResponseCacheConfig;
+use crate::error::recovery::CacheClearer;
+use bytes::Bytes;
+use http::HeaderMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 客户端选择启用缓存的请求头
+pub const CACHE_OPT_IN_HEADER: &str = "x-gemini-cache";
+
+/// 标明缓存结果的响应头
+pub const CACHE_STATUS_HEADER: &str = "x-cache";
+
+/// 参与缓存的请求体上限（需在转发前完整读取请求体）
+pub const MAX_CACHEABLE_REQUEST_BYTES: usize = 64 * 1024;
+
+/// 缓存结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// 命中缓存，未请求上游
+    Hit,
+    /// 未命中，上游成功响应后写入缓存
+    Miss,
+    /// 请求了缓存但请求不可缓存（流式、非确定性或请求体过大）
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hit => "HIT",
+            Self::Miss => "MISS",
+            Self::Bypass => "BYPASS",
+        }
+    }
+}
+
+/// 缓存的上游响应
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub body: Bytes,
+}
+
+/// 请求是否选择使用缓存：需启用缓存，且命中配置的路径前缀或携带启用请求头
+pub fn wants_cache(config: &ResponseCacheConfig, path: &str, headers: &HeaderMap) -> bool {
+    if !config.enabled {
+        return false;
+    }
+    let opted_in = headers
+        .get(CACHE_OPT_IN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "on"));
+    opted_in || config.path_prefixes.iter().any(|prefix| path.starts_with(prefix.as_str()))
+}
+
+/// 是否为流式请求
+pub fn is_streaming_request(path_and_query: &str) -> bool {
+    path_and_query.contains(":streamGenerateContent") || path_and_query.contains("alt=sse")
+}
+
+/// 请求是否确定性（显式设置 `generationConfig.temperature` 为 0）
+///
+/// 未设置 temperature 时使用上游默认值（非 0），不视为确定性请求
+pub fn is_deterministic(body: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| value.pointer("/generationConfig/temperature").and_then(|t| t.as_f64()))
+        .is_some_and(|temperature| temperature == 0.0)
+}
+
+/// 计算缓存键；不可缓存的请求返回 None
+pub fn cache_key(path_and_query: &str, body: &[u8]) -> Option<String> {
+    if is_streaming_request(path_and_query) || !is_deterministic(body) {
+        return None;
+    }
+    let mut hasher = openssl::sha::Sha256::new();
+    hasher.update(path_and_query.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(body);
+    Some(hasher.finish().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+struct CacheEntry {
+    response: CachedResponse,
+    inserted_at: Instant,
+}
+
+/// 带有效期与容量限制的内存响应缓存
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    max_entry_bytes: usize,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl ResponseCache {
+    pub fn new(config: &ResponseCacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_seconds),
+            max_entries: config.max_entries,
+            max_entry_bytes: config.max_entry_bytes,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 查找未过期的缓存响应
+    pub fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.inserted_at.elapsed() < self.ttl => Some(entry.response.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// 写入缓存，响应体超过单条上限时不缓存并返回 false
+    pub fn insert(&self, key: String, response: CachedResponse) -> bool {
+        if response.body.len() > self.max_entry_bytes || self.max_entries == 0 {
+            return false;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&key) && entries.len() >= self.max_entries {
+            // 先清理过期条目，仍然已满时淘汰最早写入的条目
+            let ttl = self.ttl;
+            entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+            if entries.len() >= self.max_entries {
+                if let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.inserted_at)
+                    .map(|(key, _)| key.clone())
+                {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                response,
+                inserted_at: Instant::now(),
+            },
+        );
+        true
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// 清空缓存，返回清理的条目数
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let cleared = entries.len();
+        entries.clear();
+        cleared
+    }
+}
+
+/// 自愈操作 `ClearCache` 与 `POST /api/cache/clear` 清空响应缓存
+impl CacheClearer for ResponseCache {
+    fn cache_name(&self) -> &'static str {
+        "response_cache"
+    }
+
+    fn clear_cache(&self) -> usize {
+        self.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "/v1beta/models/gemini-1.5-flash:generateContent";
+
+    fn enabled_config() -> ResponseCacheConfig {
+        ResponseCacheConfig {
+            enabled: true,
+            ..ResponseCacheConfig::default()
+        }
+    }
+
+    fn request_body(temperature: f64) -> Vec<u8> {
+        serde_json::json!({
+            "contents": [{"parts": [{"text": "2+2=?"}]}],
+            "generationConfig": {"temperature": temperature}
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn opt_in_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_OPT_IN_HEADER, "on".parse().unwrap());
+        headers
+    }
+
+    /// 模拟代理的请求流程：命中缓存时直接返回，否则请求上游并写入缓存
+    fn handle(cache: &ResponseCache, body: &[u8], upstream_calls: &mut u32) -> (CacheStatus, Bytes) {
+        let key = match cache_key(PATH, body) {
+            Some(key) => key,
+            None => {
+                *upstream_calls += 1;
+                return (CacheStatus::Bypass, Bytes::from_static(b"{\"candidates\":[]}"));
+            }
+        };
+        if let Some(cached) = cache.get(&key) {
+            return (CacheStatus::Hit, cached.body);
+        }
+
+        *upstream_calls += 1;
+        let body = Bytes::from_static(b"{\"candidates\":[]}");
+        cache.insert(
+            key,
+            CachedResponse {
+                content_type: Some("application/json".to_string()),
+                content_encoding: None,
+                body: body.clone(),
+            },
+        );
+        (CacheStatus::Miss, body)
+    }
+
+    #[test]
+    fn test_cache_hit_avoids_upstream_call() {
+        let config = enabled_config();
+        assert!(wants_cache(&config, PATH, &opt_in_headers()));
+
+        let cache = ResponseCache::new(&config);
+        let mut upstream_calls = 0;
+
+        let (status, first) = handle(&cache, &request_body(0.0), &mut upstream_calls);
+        assert_eq!(status, CacheStatus::Miss);
+        let (status, second) = handle(&cache, &request_body(0.0), &mut upstream_calls);
+        assert_eq!(status, CacheStatus::Hit);
+
+        assert_eq!(upstream_calls, 1);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_non_deterministic_and_streaming_requests_bypass_cache() {
+        let cache = ResponseCache::new(&enabled_config());
+        let mut upstream_calls = 0;
+
+        for _ in 0..2 {
+            let (status, _) = handle(&cache, &request_body(0.7), &mut upstream_calls);
+            assert_eq!(status, CacheStatus::Bypass);
+        }
+        assert_eq!(upstream_calls, 2);
+        assert_eq!(cache.len(), 0);
+
+        // 未设置 temperature 与流式请求同样不缓存
+        assert!(cache_key(PATH, br#"{"contents":[]}"#).is_none());
+        assert!(cache_key(
+            "/v1beta/models/gemini-1.5-flash:streamGenerateContent?alt=sse",
+            &request_body(0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_opt_in_and_limits() {
+        let mut config = enabled_config();
+        assert!(!wants_cache(&config, PATH, &HeaderMap::new()));
+        config.path_prefixes = vec!["/v1beta/models/gemini-1.5-flash".to_string()];
+        assert!(wants_cache(&config, PATH, &HeaderMap::new()));
+        config.enabled = false;
+        assert!(!wants_cache(&config, PATH, &opt_in_headers()));
+
+        let cache = ResponseCache::new(&ResponseCacheConfig {
+            max_entries: 1,
+            max_entry_bytes: 8,
+            ..enabled_config()
+        });
+        let response = |body: &'static [u8]| CachedResponse {
+            content_type: None,
+            content_encoding: None,
+            body: Bytes::from_static(body),
+        };
+        assert!(!cache.insert("large".to_string(), response(b"too large body")));
+        assert!(cache.insert("a".to_string(), response(b"a")));
+        assert!(cache.insert("b".to_string(), response(b"b")));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+    }
+}
@@ -0,0 +1,119 @@
+// src/proxy/response_headers.rs
+//! 上游响应头规范化
+//!
+//! 部分上游会返回重复的响应头或 trailer，某些客户端无法正确处理。按配置移除指定的响应头、
+//! 合并或去重重复的响应头，并在不转发 trailer 时移除声明 trailer 的 `trailer` 响应头
+
+use crate::config::{DuplicateHeaderPolicy, ResponseHeaderConfig};
+use pingora::http::ResponseHeader;
+use pingora_error::Result;
+
+/// 合并时不能拼接为一个值的响应头
+const NON_MERGEABLE_HEADERS: &[&str] = &["set-cookie"];
+
+/// 按配置规范化响应头
+pub fn normalize_response_headers(config: &ResponseHeaderConfig, response_header: &mut ResponseHeader) -> Result<()> {
+    for name in &config.drop {
+        response_header.remove_header(name.as_str());
+    }
+    if !config.forward_trailers {
+        response_header.remove_header("trailer");
+    }
+    if config.duplicates == DuplicateHeaderPolicy::Keep {
+        return Ok(());
+    }
+
+    let duplicated: Vec<String> = response_header
+        .headers
+        .keys()
+        .filter(|name| response_header.headers.get_all(*name).iter().count() > 1)
+        .map(|name| name.as_str().to_string())
+        .collect();
+
+    for name in duplicated {
+        let values: Vec<Vec<u8>> = response_header
+            .headers
+            .get_all(name.as_str())
+            .iter()
+            .map(|value| value.as_bytes().to_vec())
+            .collect();
+        let value = match config.duplicates {
+            DuplicateHeaderPolicy::Merge if NON_MERGEABLE_HEADERS.contains(&name.as_str()) => continue,
+            DuplicateHeaderPolicy::Merge => values.join(&b", "[..]),
+            DuplicateHeaderPolicy::First => values.first().cloned().unwrap_or_default(),
+            DuplicateHeaderPolicy::Last => values.last().cloned().unwrap_or_default(),
+            DuplicateHeaderPolicy::Keep => continue,
+        };
+        response_header.insert_header(name, value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstream_header() -> ResponseHeader {
+        let mut header = ResponseHeader::build(200, None).unwrap();
+        header.append_header("vary", "Origin").unwrap();
+        header.append_header("vary", "Accept-Encoding").unwrap();
+        header.append_header("set-cookie", "a=1").unwrap();
+        header.append_header("set-cookie", "b=2").unwrap();
+        header.append_header("x-goog-trace", "abc").unwrap();
+        header.append_header("trailer", "grpc-status").unwrap();
+        header
+    }
+
+    fn values(header: &ResponseHeader, name: &str) -> Vec<String> {
+        header
+            .headers
+            .get_all(name)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_duplicate_headers() {
+        let config = ResponseHeaderConfig {
+            duplicates: DuplicateHeaderPolicy::Merge,
+            ..ResponseHeaderConfig::default()
+        };
+        let mut header = upstream_header();
+        normalize_response_headers(&config, &mut header).unwrap();
+
+        assert_eq!(values(&header, "vary"), vec!["Origin, Accept-Encoding"]);
+        // set-cookie 不能合并
+        assert_eq!(values(&header, "set-cookie"), vec!["a=1", "b=2"]);
+
+        let config = ResponseHeaderConfig {
+            duplicates: DuplicateHeaderPolicy::Last,
+            ..ResponseHeaderConfig::default()
+        };
+        let mut header = upstream_header();
+        normalize_response_headers(&config, &mut header).unwrap();
+        assert_eq!(values(&header, "vary"), vec!["Accept-Encoding"]);
+        assert_eq!(values(&header, "set-cookie"), vec!["b=2"]);
+
+        // 默认原样转发
+        let mut header = upstream_header();
+        normalize_response_headers(&ResponseHeaderConfig::default(), &mut header).unwrap();
+        assert_eq!(values(&header, "vary").len(), 2);
+        assert_eq!(values(&header, "trailer"), vec!["grpc-status"]);
+    }
+
+    #[test]
+    fn test_drop_configured_headers_and_trailers() {
+        let config = ResponseHeaderConfig {
+            drop: vec!["X-Goog-Trace".to_string()],
+            forward_trailers: false,
+            ..ResponseHeaderConfig::default()
+        };
+        let mut header = upstream_header();
+        normalize_response_headers(&config, &mut header).unwrap();
+
+        assert!(header.headers.get("x-goog-trace").is_none());
+        assert!(header.headers.get("trailer").is_none());
+        assert_eq!(values(&header, "vary").len(), 2);
+    }
+}
@@ -0,0 +1,108 @@
+// src/proxy/response_rewrite.rs
+//! 上游响应状态码改写
+//!
+//! 根据配置的改写表将特定的上游状态码映射为对客户端更友好的响应，
+//! 原始状态码保留在 `x-original-status` 响应头中便于排查
+
+use crate::config::StatusRewriteRule;
+use bytes::Bytes;
+use pingora::http::ResponseHeader;
+use pingora_error::Result;
+
+/// 保留原始上游状态码的响应头
+pub const ORIGINAL_STATUS_HEADER: &str = "x-original-status";
+
+/// 查找与上游状态码匹配的改写规则
+pub fn find_rule(rules: &[StatusRewriteRule], upstream_status: u16) -> Option<&StatusRewriteRule> {
+    rules.iter().find(|rule| rule.upstream_status == upstream_status)
+}
+
+/// 对响应头应用改写规则
+///
+/// 返回需要替换的响应体（若规则配置了 body）；未匹配任何规则时响应保持不变
+pub fn apply_status_rewrite(
+    rules: &[StatusRewriteRule],
+    response_header: &mut ResponseHeader,
+) -> Result<Option<Bytes>> {
+    let upstream_status = response_header.status.as_u16();
+    let rule = match find_rule(rules, upstream_status) {
+        Some(rule) => rule,
+        None => return Ok(None),
+    };
+
+    response_header.set_status(rule.status)?;
+    response_header.insert_header(ORIGINAL_STATUS_HEADER, upstream_status.to_string())?;
+
+    if let Some(retry_after) = rule.retry_after_seconds {
+        response_header.insert_header("retry-after", retry_after.to_string())?;
+    }
+
+    match &rule.body {
+        Some(body) => {
+            let body = Bytes::from(body.clone());
+            response_header.remove_header("transfer-encoding");
+            response_header.insert_header("content-length", body.len().to_string())?;
+            Ok(Some(body))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quota_rule() -> StatusRewriteRule {
+        StatusRewriteRule {
+            upstream_status: 403,
+            status: 429,
+            retry_after_seconds: Some(30),
+            body: Some(r#"{"error":"quota exceeded"}"#.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_403_to_429() {
+        let rules = vec![quota_rule()];
+        let mut header = ResponseHeader::build(403, None).unwrap();
+
+        let body = apply_status_rewrite(&rules, &mut header).unwrap();
+
+        assert_eq!(header.status.as_u16(), 429);
+        assert_eq!(header.headers.get(ORIGINAL_STATUS_HEADER).unwrap(), "403");
+        assert_eq!(header.headers.get("retry-after").unwrap(), "30");
+        let body = body.unwrap();
+        assert_eq!(body, Bytes::from(r#"{"error":"quota exceeded"}"#));
+        assert_eq!(
+            header.headers.get("content-length").unwrap(),
+            body.len().to_string().as_str()
+        );
+    }
+
+    #[test]
+    fn test_unmapped_status_passes_through() {
+        let rules = vec![quota_rule()];
+        let mut header = ResponseHeader::build(500, None).unwrap();
+
+        let body = apply_status_rewrite(&rules, &mut header).unwrap();
+
+        assert!(body.is_none());
+        assert_eq!(header.status.as_u16(), 500);
+        assert!(header.headers.get(ORIGINAL_STATUS_HEADER).is_none());
+        assert!(header.headers.get("retry-after").is_none());
+    }
+
+    #[test]
+    fn test_rewrite_without_body_keeps_upstream_body() {
+        let rules = vec![StatusRewriteRule {
+            body: None,
+            ..quota_rule()
+        }];
+        let mut header = ResponseHeader::build(403, None).unwrap();
+
+        let body = apply_status_rewrite(&rules, &mut header).unwrap();
+
+        assert!(body.is_none());
+        assert_eq!(header.status.as_u16(), 429);
+    }
+}
@@ -0,0 +1,136 @@
+// src/proxy/response_transform.rs
+//! 响应字段剥离
+//!
+//! 部分 Gemini 响应字段（内部调试、安全元数据等）不应返回给最终用户。按配置的 JSON 路径从非流式 JSON
+//! 响应中移除这些字段，其余内容保持不变。路径以 `.` 分隔，`*` 匹配数组的每个元素或对象的每个值，
+//! 例如 `candidates.*.safetyRatings`。流式响应、压缩响应与非 JSON 响应原样转发
+
+use bytes::Bytes;
+use pingora::http::ResponseHeader;
+use serde_json::Value;
+
+/// 响应体缓冲上限，超出后停止剥离并原样转发
+pub const MAX_TRANSFORM_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+/// 响应是否需要剥离字段：配置了路径、非流式、未压缩的 JSON 响应
+pub fn should_strip(paths: &[String], streaming_request: bool, response_header: &ResponseHeader) -> bool {
+    let header_value = |name: &str| {
+        response_header
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_ascii_lowercase)
+    };
+    let is_json = header_value("content-type").is_some_and(|v| v.starts_with("application/json"));
+    let encoded = header_value("content-encoding").is_some_and(|v| v != "identity");
+    !paths.is_empty() && !streaming_request && is_json && !encoded
+}
+
+/// 从 JSON 响应体中移除配置的字段，响应体不是 JSON 时返回 None（调用方原样转发）
+pub fn strip_response_fields(paths: &[String], body: &[u8]) -> Option<Bytes> {
+    let mut value: Value = serde_json::from_slice(body).ok()?;
+    for path in paths {
+        let segments: Vec<&str> = path.split('.').filter(|s| !s.is_empty()).collect();
+        strip_path(&mut value, &segments);
+    }
+    serde_json::to_vec(&value).ok().map(Bytes::from)
+}
+
+fn strip_path(value: &mut Value, segments: &[&str]) {
+    let (segment, rest) = match segments {
+        [] => return,
+        [segment, rest @ ..] => (*segment, rest),
+    };
+
+    if rest.is_empty() {
+        match value {
+            Value::Object(map) if segment == "*" => map.clear(),
+            Value::Object(map) => {
+                map.remove(segment);
+            }
+            Value::Array(items) if segment == "*" => items.clear(),
+            Value::Array(items) => {
+                if let Some(index) = segment.parse::<usize>().ok().filter(|&i| i < items.len()) {
+                    items.remove(index);
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match value {
+        Value::Object(map) if segment == "*" => map.values_mut().for_each(|child| strip_path(child, rest)),
+        Value::Object(map) => {
+            if let Some(child) = map.get_mut(segment) {
+                strip_path(child, rest);
+            }
+        }
+        Value::Array(items) if segment == "*" => items.iter_mut().for_each(|child| strip_path(child, rest)),
+        Value::Array(items) => {
+            if let Some(child) = segment.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                strip_path(child, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths() -> Vec<String> {
+        vec![
+            "candidates.*.safetyRatings".to_string(),
+            "promptFeedback".to_string(),
+            "usageMetadata.promptTokensDetails".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_configured_fields_removed_and_others_preserved() {
+        let body = serde_json::json!({
+            "candidates": [
+                {"content": {"parts": [{"text": "hi"}]}, "safetyRatings": [{"category": "HARM"}], "finishReason": "STOP"},
+                {"content": {"parts": [{"text": "yo"}]}, "safetyRatings": []}
+            ],
+            "promptFeedback": {"safetyRatings": []},
+            "usageMetadata": {"promptTokenCount": 3, "promptTokensDetails": [{"modality": "TEXT"}]},
+            "modelVersion": "gemini-1.5-pro-002"
+        });
+
+        let stripped = strip_response_fields(&paths(), body.to_string().as_bytes()).unwrap();
+        let stripped: Value = serde_json::from_slice(&stripped).unwrap();
+
+        assert_eq!(
+            stripped,
+            serde_json::json!({
+                "candidates": [
+                    {"content": {"parts": [{"text": "hi"}]}, "finishReason": "STOP"},
+                    {"content": {"parts": [{"text": "yo"}]}}
+                ],
+                "usageMetadata": {"promptTokenCount": 3},
+                "modelVersion": "gemini-1.5-pro-002"
+            })
+        );
+    }
+
+    #[test]
+    fn test_streaming_and_non_json_skipped() {
+        let mut header = ResponseHeader::build(200, None).unwrap();
+        header.insert_header("content-type", "application/json; charset=UTF-8").unwrap();
+        assert!(should_strip(&paths(), false, &header));
+        assert!(!should_strip(&paths(), true, &header));
+        assert!(!should_strip(&[], false, &header));
+
+        header.insert_header("content-encoding", "gzip").unwrap();
+        assert!(!should_strip(&paths(), false, &header));
+
+        let mut sse = ResponseHeader::build(200, None).unwrap();
+        sse.insert_header("content-type", "text/event-stream").unwrap();
+        assert!(!should_strip(&paths(), false, &sse));
+
+        assert!(strip_response_fields(&paths(), b"not json").is_none());
+    }
+}
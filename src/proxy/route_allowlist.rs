@@ -0,0 +1,82 @@
+// src/proxy/route_allowlist.rs
+//! 请求方法与路径允许列表
+//!
+//! 启用后，代理只转发命中允许规则的请求（例如只允许 `POST .../models/*:generateContent`
+//! 和 `GET /v1beta/models`），其他方法与路径组合直接返回 403，不选择密钥也不请求上游。
+//! 路径模式中的 `*` 匹配不含 `/` 的任意字符，`**` 匹配任意字符（含 `/`）
+
+use crate::config::{RouteAllowlistConfig, RouteRule};
+
+/// 拒绝原因（写入审计日志元数据）
+pub const ROUTE_NOT_ALLOWED_REASON: &str = "route_not_allowed";
+
+/// 请求是否被允许：未启用时全部允许
+pub fn is_route_allowed(config: &RouteAllowlistConfig, method: &str, path: &str) -> bool {
+    !config.enabled || config.rules.iter().any(|rule| rule_matches(rule, method, path))
+}
+
+fn rule_matches(rule: &RouteRule, method: &str, path: &str) -> bool {
+    let method_allowed = rule.methods.is_empty() || rule.methods.iter().any(|m| m.eq_ignore_ascii_case(method));
+    method_allowed && path_matches(rule.path.as_bytes(), path.as_bytes())
+}
+
+/// 按路径模式匹配
+pub fn path_matches(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| path_matches(rest, &path[i..])),
+        [b'*', rest @ ..] => {
+            let segment_end = path.iter().position(|&c| c == b'/').unwrap_or(path.len());
+            (0..=segment_end).any(|i| path_matches(rest, &path[i..]))
+        }
+        [c, rest @ ..] => path.first() == Some(c) && path_matches(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RouteAllowlistConfig {
+        RouteAllowlistConfig {
+            enabled: true,
+            rules: vec![
+                RouteRule {
+                    methods: vec!["POST".to_string()],
+                    path: "/v1beta/models/*:generateContent".to_string(),
+                },
+                RouteRule {
+                    methods: vec!["GET".to_string()],
+                    path: "/v1beta/models".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_allowed_routes_pass() {
+        let config = config();
+        assert!(is_route_allowed(&config, "POST", "/v1beta/models/gemini-1.5-flash:generateContent"));
+        assert!(is_route_allowed(&config, "get", "/v1beta/models"));
+        // 未启用时不做限制
+        assert!(is_route_allowed(&RouteAllowlistConfig::default(), "DELETE", "/v1beta/files/abc"));
+    }
+
+    #[test]
+    fn test_disallowed_path_or_method_rejected() {
+        let config = config();
+        // 方法不匹配
+        assert!(!is_route_allowed(&config, "GET", "/v1beta/models/gemini-1.5-flash:generateContent"));
+        assert!(!is_route_allowed(&config, "POST", "/v1beta/models"));
+        // 路径不匹配（`*` 不跨越路径段）
+        assert!(!is_route_allowed(&config, "POST", "/v1beta/models/gemini-1.5-flash:streamGenerateContent"));
+        assert!(!is_route_allowed(&config, "POST", "/v1beta/models/a/b:generateContent"));
+        assert!(!is_route_allowed(&config, "DELETE", "/v1beta/files/abc"));
+    }
+
+    #[test]
+    fn test_double_star_matches_across_segments() {
+        assert!(path_matches(b"/v1beta/**", b"/v1beta/tunedModels/x/operations"));
+        assert!(!path_matches(b"/v1beta/*", b"/v1beta/tunedModels/x"));
+    }
+}
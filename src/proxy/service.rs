@@ -1,27 +1,184 @@
 // src/proxy/service.rs
 use crate::auth::AuthHandler;
-use crate::config::GeminiConfig;
+use crate::config::{GeminiConfig, LiveConfig};
+use crate::error::GeminiProxyError;
+use crate::error::recovery::ErrorRecoveryManager;
 use crate::load_balancer::UnifiedKeyManager;
 use crate::metrics::MetricsCollector;
+use crate::proxy::attempt_log::{failure_body, wants_attempt_log, AttemptLog, ATTEMPT_LOG_RESPONSE_HEADER};
+use crate::proxy::body_limits::{count_request_chunk, declared_length_exceeded, BodyLimits, REQUEST_BODY_TOO_LARGE_REASON};
+use crate::proxy::circuit_guard::{is_key_failure_status, select_failover_key, select_key_with_hash, KeyRouting};
+use crate::proxy::dedup::{
+    dedup_key, idempotency_key, wait_for_first, DedupLookup, DedupTicket, DedupWindow, DEDUP_REPLAYED, DEDUP_STATUS_HEADER,
+    MAX_DEDUP_BODY_BYTES,
+};
+use crate::proxy::failover::{failover_backoff, retry_after, should_failover, within_backoff_budget};
+use crate::proxy::hash_routing::{self, affinity_hash, routing_hash_for_request, MAX_HASH_ROUTING_BODY_BYTES};
+use crate::proxy::cancellation::{is_client_disconnect, InFlightGuard};
+use crate::proxy::model_fallback::{ModelFallbackTracker, SERVED_MODEL_HEADER};
+use crate::proxy::model_override::{resolve_model_override, rewrite_model_path, ModelOverride, MODEL_OVERRIDE_HEADER};
+use crate::proxy::openai_compat::{
+    invalid_request_response, is_chat_completions, translate_request, ChatCompletionTranslator,
+    MAX_OPENAI_REQUEST_BYTES,
+};
+use crate::proxy::preflight::{
+    context_exceeded_response, CharRatioEstimator, ContextExceeded, PendingPreflight, PreflightStep, TokenEstimator,
+};
+use crate::proxy::request_id::apply_request_id;
+use crate::proxy::request_record::RequestRecord;
+use crate::proxy::header_limits::{check_header_limits, HeaderLimits};
+use crate::proxy::inflight_limit::{InflightLimiter, InflightPermit};
+use crate::proxy::required_headers::{missing_headers_response, missing_required_headers, MISSING_HEADERS_REASON};
+use crate::proxy::response_headers::normalize_response_headers;
+use crate::proxy::route_allowlist::{is_route_allowed, ROUTE_NOT_ALLOWED_REASON};
+use crate::proxy::response_cache::{
+    cache_key, is_streaming_request, wants_cache, CacheStatus, CachedResponse, ResponseCache,
+    CACHE_STATUS_HEADER, MAX_CACHEABLE_REQUEST_BYTES,
+};
+use crate::proxy::error_codes::{annotate_error_body, normalize_error, should_annotate};
+use crate::proxy::response_rewrite::apply_status_rewrite;
+use crate::proxy::response_transform::{should_strip, strip_response_fields, MAX_TRANSFORM_BODY_BYTES};
+use crate::proxy::shadow::{mirror_to_shadow_keys, MirroredRequest, MAX_MIRROR_BODY_BYTES};
+use crate::proxy::shed::{shed_response, Shed, ShedReason};
+use crate::proxy::spans::{request_span, TimedSpan};
+use crate::proxy::soft_failure::{detect_soft_failure, rewrite_empty_response, SoftFailure};
+use crate::proxy::streaming::{
+    is_event_stream, is_upstream_interruption, prepare_streaming_response, stream_interrupted_event,
+    StreamCoalescer,
+};
+use crate::proxy::timeouts::{UpstreamDeadline, UpstreamTimeouts};
+use crate::proxy::traffic_class::{classify, needs_body, DEFAULT_TRAFFIC_CLASS, MAX_CLASSIFY_BODY_BYTES};
+use crate::security::ip_rules::{IpDecision, IpRules, IP_DENIED_REASON};
+use crate::security::{ApiCallRecord, AuditResult, SharedAuditLog};
+use crate::proxy::usage::{buffer_response_chunk, extract_model, inspect_upstream_body};
+use crate::proxy::warm_pool::{UpstreamConnectionStats, UpstreamWarmPool};
+use crate::utils::shutdown::ShutdownState;
 use async_trait::async_trait;
+use bytes::Bytes;
 use chrono::Utc;
+use pingora::connectors::http::Connector;
 use pingora::http::ResponseHeader;
 use pingora::protocols::l4::socket::SocketAddr;
-use pingora::proxy::{ProxyHttp, Session};
+use pingora::protocols::Digest;
+use pingora::proxy::{FailToProxy, ProxyHttp, Session};
 use pingora::upstreams::peer::HttpPeer;
-use pingora_error::{Error, Result};
+use pingora_error::{Error, ErrorSource, ErrorType, OrErr, Result};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
+use std::time::Instant;
+use tracing::Instrument;
 
 pub struct ProxyCtx {
+    /// 请求 ID，用于关联审计、错误日志和指标
+    pub request_id: String,
     pub api_key_id: Option<String>,
     pub request_start_time: Option<chrono::DateTime<Utc>>,
+    /// 状态码改写后用于替换上游响应体的内容
+    pub rewritten_body: Option<Bytes>,
+    /// 请求的目标模型
+    pub model: Option<String>,
+    /// 缓冲的上游响应体（用于用量解析）
+    pub response_body: Vec<u8>,
+    /// 响应体是否超过缓冲上限（超限时不做解析）
+    pub response_body_truncated: bool,
+    /// 在途请求守卫，客户端断开导致请求被中止时记录取消
+    pub in_flight: Option<InFlightGuard>,
+    /// 全进程在途请求名额，随上下文释放
+    pub inflight_permit: Option<InflightPermit>,
+    /// 请求的流量分类
+    pub traffic_class: Option<String>,
+    /// 缓冲的请求体（仅在存在请求体分类规则时使用）
+    pub request_body: Vec<u8>,
+    /// 本次上游尝试已转发的请求体字节数（用于请求体上限）
+    pub request_body_received: usize,
+    /// 响应缓存结果（未请求缓存时为 None）
+    pub cache_status: Option<CacheStatus>,
+    /// 缓存未命中时的缓存键，上游成功响应后写入缓存
+    pub cache_key: Option<String>,
+    /// 待写入缓存的响应头信息（响应体在请求结束时补齐）
+    pub cache_pending: Option<CachedResponse>,
+    /// 去重窗口内首个请求的登记，成功完成后记录响应供重复请求重放
+    pub dedup_ticket: Option<DedupTicket>,
+    /// 上游尝试记录
+    pub attempts: AttemptLog,
+    /// 客户端是否请求返回尝试记录响应头
+    pub debug_attempts: bool,
+    /// 进行中的上下文窗口预检（缓冲请求体）
+    pub preflight: Option<PendingPreflight>,
+    /// 预检拒绝的请求，在 `fail_to_proxy` 中返回 400
+    pub preflight_rejection: Option<ContextExceeded>,
+    /// 转发前完整读取的请求体（响应缓存与哈希路由共用）
+    pub buffered_request_body: Option<Bytes>,
+    /// 代理直接拒绝请求的原因（写入审计日志）
+    pub denied_reason: Option<&'static str>,
+    /// 上游 200 响应的软失败类型
+    pub soft_failure: Option<SoftFailure>,
+    /// 存在影子预热密钥时待镜像的请求，真实请求成功后发送
+    pub mirror_request: Option<MirroredRequest>,
+    /// 需要剥离字段的响应体缓冲，流结束时转换后一次性输出
+    pub strip_buffer: Option<Vec<u8>>,
+    /// 需要注入规范化错误码的上游错误状态码（与剥离字段共用响应体缓冲）
+    pub error_status: Option<u16>,
+    /// 是否为流式（SSE）请求，响应逐块透传
+    pub streaming: bool,
+    /// 是否已收到首个响应体数据块（用于记录首字节时间）
+    pub first_byte_seen: bool,
+    /// 流式响应的数据块合并器（未配置合并时为 None）
+    pub stream_coalescer: Option<StreamCoalescer>,
+    /// 请求根 span，密钥选择、上游请求与响应处理的 span 嵌套其下
+    pub request_span: tracing::Span,
+    /// 进行中的上游请求 span（收到响应头或连接失败时结束）
+    pub upstream_span: Option<TimedSpan>,
+    /// 本次上游尝试的总时长与数据块间隔跟踪（流式与非流式请求的超时不同）
+    pub upstream_deadline: Option<UpstreamDeadline>,
+    /// 本请求已换密钥重试的次数
+    pub failover_retries: u32,
+    /// 本请求中因可重试状态被换下的密钥
+    pub failover_tried_keys: Vec<String>,
+    /// 本请求换密钥重试已等待的总时间
+    pub failover_backoff_spent: std::time::Duration,
+    /// 响应过滤器已选好新密钥，等待 Pingora 重试
+    pub failover_pending: bool,
+    /// 本次上游尝试的结果（连接失败或响应状态码）已结算到熔断器，`fail_to_proxy` 不再重复上报
+    pub key_failure_reported: bool,
+    /// 上游返回了成功响应头，熔断器结果待请求结束时上报
+    pub key_success_pending: bool,
+    /// OpenAI 兼容请求转换后的 Gemini 请求体，替换客户端原始请求体转发
+    pub openai_request: Option<Bytes>,
+    /// OpenAI 兼容请求的响应转换（上游非成功响应时清除）
+    pub openai_response: Option<ChatCompletionTranslator>,
+    /// 按降级链改写前客户端请求的模型（未降级时为 None）
+    pub fallback_from: Option<String>,
 }
 
 pub struct GeminiProxyService {
     key_manager: Arc<UnifiedKeyManager>,
     auth_handler: Arc<AuthHandler>,
     metrics: Arc<MetricsCollector>,
-    gemini_config: Arc<GeminiConfig>,
+    /// 运行中的 Gemini 配置，热重载时整体替换
+    gemini_config: Arc<LiveConfig<GeminiConfig>>,
+    audit_log: Option<SharedAuditLog>,
+    recovery_manager: Option<Arc<ErrorRecoveryManager>>,
+    response_cache: Option<Arc<ResponseCache>>,
+    dedup_window: Option<Arc<DedupWindow>>,
+    token_estimator: Arc<dyn TokenEstimator>,
+    /// 影子密钥镜像请求使用的上游连接器
+    shadow_connector: Arc<Connector>,
+    /// 上游地址的预热连接，设置到上游 peer 上供 Pingora 新建连接时取用
+    warm_pool: Option<Arc<UpstreamWarmPool>>,
+    /// 转发请求的上游连接复用统计
+    upstream_connections: Arc<UpstreamConnectionStats>,
+    header_limits: HeaderLimits,
+    body_limits: BodyLimits,
+    /// 全进程在途请求上限
+    inflight_limiter: Arc<InflightLimiter>,
+    /// 各模型的过载状态，用于模型降级链
+    model_fallback: ModelFallbackTracker,
+    /// 客户端 IP 访问规则（启动时解析）
+    ip_rules: IpRules,
+    /// 停机状态，排空期间拒绝新请求
+    shutdown: Arc<ShutdownState>,
 }
 
 impl GeminiProxyService {
@@ -29,15 +186,732 @@ impl GeminiProxyService {
         key_manager: Arc<UnifiedKeyManager>,
         auth_handler: Arc<AuthHandler>,
         metrics: Arc<MetricsCollector>,
-        gemini_config: Arc<GeminiConfig>,
+        gemini_config: Arc<LiveConfig<GeminiConfig>>,
     ) -> Self {
+        // token 估算器按启动时的配置创建，热重载不改变
+        let initial_config = gemini_config.load();
         Self {
             key_manager,
             auth_handler,
             metrics,
+            audit_log: None,
+            recovery_manager: None,
+            response_cache: None,
+            dedup_window: None,
+            token_estimator: Arc::new(CharRatioEstimator::new(initial_config.context_preflight.chars_per_token)),
+            shadow_connector: Arc::new(Connector::new(None)),
+            warm_pool: None,
+            upstream_connections: Arc::new(UpstreamConnectionStats::new()),
+            header_limits: HeaderLimits::default(),
+            body_limits: BodyLimits::default(),
+            inflight_limiter: Arc::new(InflightLimiter::new(0)),
+            model_fallback: ModelFallbackTracker::new(),
+            ip_rules: IpRules::default(),
+            shutdown: Arc::new(ShutdownState::new()),
             gemini_config,
         }
     }
+
+    /// 当前生效的 Gemini 配置快照
+    fn gemini_config(&self) -> Arc<GeminiConfig> {
+        self.gemini_config.load()
+    }
+
+    /// 上游 Gemini API 的连接目标；启用连接预热时新建连接取用预热连接
+    fn upstream_http_peer(&self) -> HttpPeer {
+        let mut peer = HttpPeer::new(
+            self.gemini_config().base_url.clone(),
+            true, // HTTPS
+            self.upstream_host(),
+        );
+        if let Some(warm_pool) = &self.warm_pool {
+            peer.options.custom_l4 = Some(warm_pool.clone());
+        }
+        peer
+    }
+
+    fn upstream_host(&self) -> String {
+        self.gemini_config()
+            .base_url
+            .split(':')
+            .next()
+            .unwrap_or("")
+            .to_string()
+    }
+
+    /// 设置入站请求头上限
+    pub fn with_header_limits(mut self, header_limits: HeaderLimits) -> Self {
+        self.header_limits = header_limits;
+        self
+    }
+
+    /// 设置请求体与响应缓冲上限
+    pub fn with_body_limits(mut self, body_limits: BodyLimits) -> Self {
+        self.body_limits = body_limits;
+        self
+    }
+
+    /// 关联停机状态（与健康检查和信号处理共享）
+    pub fn with_shutdown_state(mut self, shutdown: Arc<ShutdownState>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// 设置全进程在途请求上限（与性能统计共享计数）
+    pub fn with_inflight_limiter(mut self, inflight_limiter: Arc<InflightLimiter>) -> Self {
+        self.inflight_limiter = inflight_limiter;
+        self
+    }
+
+    /// 关联审计日志，每个代理请求都会记录一条 API 调用审计
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 关联错误恢复管理器，按密钥维护熔断器状态
+    pub fn with_recovery_manager(mut self, recovery_manager: Arc<ErrorRecoveryManager>) -> Self {
+        self.recovery_manager = Some(recovery_manager);
+        self
+    }
+
+    /// 设置客户端 IP 访问规则
+    pub fn with_ip_rules(mut self, ip_rules: IpRules) -> Self {
+        self.ip_rules = ip_rules;
+        self
+    }
+
+    /// 启用响应缓存（按启动时的配置创建，与自愈清理及管理接口共享）
+    pub fn with_response_cache(mut self, response_cache: Arc<ResponseCache>) -> Self {
+        self.response_cache = Some(response_cache);
+        self
+    }
+
+    /// 启用重复请求去重窗口（按启动时的配置创建）
+    pub fn with_dedup_window(mut self, dedup_window: Arc<DedupWindow>) -> Self {
+        self.dedup_window = Some(dedup_window);
+        self
+    }
+
+    /// 启用上游连接预热，转发与镜像请求新建上游连接时优先取用预热连接
+    pub fn with_warm_pool(mut self, warm_pool: Arc<UpstreamWarmPool>) -> Self {
+        self.warm_pool = Some(warm_pool);
+        self
+    }
+
+    /// 与性能统计共享转发请求的上游连接复用计数
+    pub fn with_upstream_connection_stats(mut self, stats: Arc<UpstreamConnectionStats>) -> Self {
+        self.upstream_connections = stats;
+        self
+    }
+}
+
+impl GeminiProxyService {
+    /// 按 IP 访问规则拒绝客户端（403 并记录安全事件），返回 true 表示请求已被拒绝
+    async fn enforce_ip_rules(&self, session: &mut Session, ctx: &mut ProxyCtx) -> Result<bool> {
+        if self.ip_rules.is_empty() {
+            return Ok(false);
+        }
+        let client_ip = match session.client_addr() {
+            Some(SocketAddr::Inet(inet_addr)) => Some(inet_addr.ip()),
+            _ => None,
+        };
+        let details = match self.ip_rules.evaluate(client_ip) {
+            IpDecision::Allowed => return Ok(false),
+            IpDecision::Denied(rule) => format!("命中拒绝规则 {}", rule),
+            IpDecision::NotAllowlisted => "不在允许列表中".to_string(),
+        };
+
+        tracing::warn!(request_id = %ctx.request_id, client_ip = ?client_ip, "按 IP 访问规则拒绝请求: {}", details);
+        ctx.denied_reason = Some(IP_DENIED_REASON);
+        if let Some(audit_log) = &self.audit_log {
+            let source_ip = client_ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+            if let Err(e) = audit_log
+                .lock()
+                .await
+                .log_security_event(source_ip, "IP 访问规则拒绝", &details, "Warning")
+                .await
+            {
+                tracing::warn!("记录审计日志失败: {}", e);
+            }
+        }
+        session.respond_error(403).await?;
+        Ok(true)
+    }
+
+    /// 请求体超过上限时记录安全事件并返回 413
+    async fn reject_oversized_body(&self, session: &mut Session, ctx: &mut ProxyCtx, details: String) -> Result<()> {
+        tracing::warn!(request_id = %ctx.request_id, "拒绝请求体超出上限的请求: {}", details);
+        ctx.denied_reason = Some(REQUEST_BODY_TOO_LARGE_REASON);
+        if let Some(audit_log) = &self.audit_log {
+            let source_ip = match session.client_addr() {
+                Some(SocketAddr::Inet(inet_addr)) => inet_addr.ip(),
+                _ => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            };
+            if let Err(e) = audit_log
+                .lock()
+                .await
+                .log_security_event(source_ip, "请求体超出上限", &details, "Warning")
+                .await
+            {
+                tracing::warn!("记录审计日志失败: {}", e);
+            }
+        }
+        session.respond_error(413).await
+    }
+
+    /// 处理模型覆盖请求头，返回 true 表示请求已被拒绝
+    async fn apply_model_override(&self, session: &mut Session, ctx: &mut ProxyCtx) -> Result<bool> {
+        let requested = session
+            .req_header()
+            .headers
+            .get(MODEL_OVERRIDE_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+        let path_and_query = session
+            .req_header()
+            .uri
+            .path_and_query()
+            .map_or("/", |pq| pq.as_str())
+            .to_string();
+
+        // 覆盖请求头只在代理内部使用，不转发给上游
+        session.req_header_mut().remove_header(MODEL_OVERRIDE_HEADER);
+
+        match resolve_model_override(&self.gemini_config().model_override, requested.as_deref(), &path_and_query) {
+            ModelOverride::Unchanged => Ok(false),
+            ModelOverride::Rewritten { model, path } => {
+                let uri = path
+                    .parse::<http::Uri>()
+                    .or_err(ErrorType::InvalidHTTPHeader, "模型覆盖后的请求路径无效")?;
+                tracing::info!(
+                    from = ctx.model.as_deref().unwrap_or("N/A"),
+                    to = %model,
+                    "按请求头覆盖目标模型"
+                );
+                session.req_header_mut().set_uri(uri);
+                ctx.model = Some(model);
+                Ok(false)
+            }
+            ModelOverride::Disallowed(model) => {
+                tracing::warn!("拒绝不在允许列表中的模型覆盖: {}", model);
+                session.respond_error(403).await?;
+                Ok(true)
+            }
+            ModelOverride::NoModelInPath => {
+                session.respond_error(400).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// 请求的模型持续过载时按降级链改写目标模型
+    fn apply_model_fallback(&self, session: &mut Session, ctx: &mut ProxyCtx) -> Result<()> {
+        let model = match ctx.model.as_deref() {
+            Some(model) => model,
+            None => return Ok(()),
+        };
+        let fallback = match self
+            .model_fallback
+            .resolve(&self.gemini_config().model_fallback_chain, model, Instant::now())
+        {
+            Some(fallback) => fallback,
+            None => return Ok(()),
+        };
+        let path_and_query = session.req_header().uri.path_and_query().map_or("/", |pq| pq.as_str());
+        let uri = match rewrite_model_path(path_and_query, &fallback) {
+            Some(path) => path
+                .parse::<http::Uri>()
+                .or_err(ErrorType::InvalidHTTPHeader, "降级后的请求路径无效")?,
+            None => return Ok(()),
+        };
+        tracing::info!(
+            request_id = %ctx.request_id,
+            from = model,
+            to = %fallback,
+            "模型持续过载，按降级链改用其他模型"
+        );
+        session.req_header_mut().set_uri(uri);
+        ctx.fallback_from = ctx.model.replace(fallback);
+        Ok(())
+    }
+}
+
+impl GeminiProxyService {
+    /// 按配置的状态码与 Retry-After 返回卸载响应
+    async fn write_shed_response(&self, session: &mut Session, ctx: &ProxyCtx, shed: Shed) -> Result<()> {
+        let shed = Shed {
+            error: shed.error.with_request_id(ctx.request_id.clone()),
+            ..shed
+        };
+        let (header, body) = shed_response(&self.gemini_config().shed_responses, &shed)?;
+        session.write_response_header(Box::new(header), false).await?;
+        session.write_response_body(Some(body), true).await?;
+        Ok(())
+    }
+
+    /// 上游返回可重试状态时换用其他密钥：按失败记录当前密钥、选择未尝试过的密钥并改写请求头，
+    /// 返回 true 表示需要由 Pingora 重放请求
+    async fn try_failover(
+        &self,
+        session: &mut Session,
+        response_header: &ResponseHeader,
+        ctx: &mut ProxyCtx,
+    ) -> Result<bool> {
+        let status = response_header.status.as_u16();
+        let config = &self.gemini_config().failover;
+        let key_id = match &ctx.api_key_id {
+            Some(key_id) if should_failover(config, status, ctx.failover_retries) => key_id.clone(),
+            _ => return Ok(false),
+        };
+        // 请求体超出重试缓冲时无法重放
+        if session.as_ref().retry_buffer_truncated() {
+            return Ok(false);
+        }
+        // 整个请求的重试等待时间不超过预算
+        let backoff = failover_backoff(config, ctx.failover_retries + 1);
+        if !within_backoff_budget(config, ctx.failover_backoff_spent, backoff) {
+            tracing::debug!(request_id = %ctx.request_id, spent = ?ctx.failover_backoff_spent, "重试等待预算已用尽，不再换密钥重试");
+            return Ok(false);
+        }
+
+        let mut tried_keys = std::mem::take(&mut ctx.failover_tried_keys);
+        tried_keys.push(key_id.clone());
+        let next_key = select_failover_key(
+            &self.key_manager,
+            self.recovery_manager.as_deref(),
+            ctx.model.as_deref(),
+            &tried_keys,
+        )
+        .await;
+        ctx.failover_tried_keys = tried_keys;
+        let next_key = match next_key {
+            Some(next_key) => next_key,
+            None => return Ok(false),
+        };
+
+        ctx.attempts.finish_status(status);
+        self.mark_upstream_failure(&key_id, response_header).await;
+        if let Some(recovery_manager) = &self.recovery_manager {
+            recovery_manager.report_operation_result(&key_id, false).await;
+        }
+        self.metrics.record_failover_retry(status);
+
+        ctx.failover_retries += 1;
+        tracing::warn!(
+            request_id = %ctx.request_id,
+            failed_key = %key_id,
+            next_key = %next_key.id,
+            status,
+            retry = ctx.failover_retries,
+            "上游返回可重试状态，换用其他密钥重试"
+        );
+        tokio::time::sleep(backoff).await;
+        ctx.failover_backoff_spent += backoff;
+
+        session
+            .req_header_mut()
+            .insert_header("x-goog-api-key", &next_key.key)?;
+        self.metrics.increment_request_count(&next_key.id).await;
+        ctx.api_key_id = Some(next_key.id);
+        ctx.failover_pending = true;
+        Ok(true)
+    }
+
+    /// 结束当前上游调用的计时并记录上游耗时
+    fn finish_upstream_call(&self, ctx: &mut ProxyCtx) {
+        if let Some(deadline) = ctx.upstream_deadline.take() {
+            self.metrics
+                .record_upstream_duration(ctx.streaming, deadline.elapsed(Instant::now()));
+        }
+    }
+
+    /// 按选中密钥的 ID（不是密钥本身）记录一次上游调用的结果与耗时
+    fn record_key_upstream_call(&self, ctx: &ProxyCtx, status: &str, failed: bool, latency: std::time::Duration) {
+        if let Some(key_id) = &ctx.api_key_id {
+            self.metrics.record_key_upstream_call(key_id, status, failed, latency);
+        }
+    }
+
+    /// 连接上游失败时向熔断器上报密钥失败（半开状态下同时释放探测名额）
+    ///
+    /// `fail_to_connect` 不是异步方法，上报在后台任务中执行；返回上报任务
+    fn report_connect_failure(&self, ctx: &mut ProxyCtx) -> Option<tokio::task::JoinHandle<()>> {
+        let recovery_manager = self.recovery_manager.clone()?;
+        let key_id = ctx.api_key_id.clone()?;
+        ctx.key_failure_reported = true;
+        Some(tokio::spawn(async move {
+            recovery_manager.report_operation_result(&key_id, false).await;
+        }))
+    }
+
+    /// 请求以错误结束时结算选中密钥的熔断器：密钥导致的失败上报失败，客户端原因或尚未请求上游时
+    /// 只归还半开探测名额。连接失败已上报的不重复上报
+    async fn settle_key_after_error(&self, ctx: &mut ProxyCtx, key_at_fault: bool) {
+        if std::mem::take(&mut ctx.key_failure_reported) {
+            return;
+        }
+        if let (Some(recovery_manager), Some(key_id)) = (&self.recovery_manager, &ctx.api_key_id) {
+            if key_at_fault {
+                recovery_manager.report_operation_result(key_id, false).await;
+            } else {
+                recovery_manager.release_probe(key_id).await;
+            }
+        }
+    }
+
+    /// 上游返回成功响应头的请求结束时向熔断器上报一次结果：软失败计为失败；
+    /// 以错误结束的请求已在 `fail_to_proxy` 中结算，这里不再上报
+    async fn settle_key_after_success(&self, ctx: &mut ProxyCtx, completed: bool) {
+        if !std::mem::take(&mut ctx.key_success_pending) || !completed {
+            return;
+        }
+        if let (Some(recovery_manager), Some(key_id)) = (&self.recovery_manager, &ctx.api_key_id) {
+            recovery_manager.report_operation_result(key_id, ctx.soft_failure.is_none()).await;
+        }
+    }
+
+    /// 按上游状态码记录密钥失败，429 时按 Retry-After 让密钥进入冷却期
+    async fn mark_upstream_failure(&self, key_id: &str, response_header: &ResponseHeader) {
+        let status = response_header.status.as_u16();
+        self.key_manager.mark_key_failed_with_status(key_id, status).await;
+        if status == 429 {
+            let retry_after = retry_after(&response_header.headers, Utc::now());
+            self.key_manager.start_cooldown(key_id, retry_after).await;
+        }
+    }
+
+    /// 响应头处理：记录密钥状态与指标、写入缓存状态并应用面向客户端的改写
+    async fn filter_response(&self, response_header: &mut ResponseHeader, ctx: &mut ProxyCtx) -> Result<()> {
+        // 响应头尚未写给客户端，直接读取上游响应状态码
+        let status = response_header.status.as_u16();
+        ctx.attempts.finish_status(status);
+        if ctx.debug_attempts {
+            response_header.insert_header(ATTEMPT_LOG_RESPONSE_HEADER, ctx.attempts.header_value())?;
+        }
+        let response_time = ctx.request_start_time.map_or_else(
+            || std::time::Duration::from_secs(0),
+            |start| crate::utils::clock::elapsed_since(start, Utc::now()),
+        );
+
+        self.metrics.record_response(status, response_time).await;
+
+        if let Some(key_id) = &ctx.api_key_id {
+            self.metrics.record_key_response(key_id, status, response_time);
+            if (200..300).contains(&status) {
+                if ctx.failover_retries > 0 {
+                    self.metrics.record_failover_recovered();
+                }
+                self.key_manager.mark_key_success(key_id).await;
+                self.key_manager.record_key_latency(key_id, response_time).await;
+                // 成功响应可能仍是软失败，熔断器在 logging 中按最终结果结算
+                ctx.key_success_pending = true;
+            } else if is_key_failure_status(status) {
+                self.mark_upstream_failure(key_id, response_header).await;
+                if let Some(recovery_manager) = &self.recovery_manager {
+                    recovery_manager.report_operation_result(key_id, false).await;
+                }
+                ctx.key_failure_reported = true;
+            } else if status >= 300 {
+                // 客户端请求引起的错误不说明密钥状态，只归还半开探测名额
+                if let Some(recovery_manager) = &self.recovery_manager {
+                    recovery_manager.release_probe(key_id).await;
+                }
+                ctx.key_failure_reported = true;
+            }
+        }
+
+        // 只缓存与重放上游的成功响应
+        let pending = (status == 200).then(|| {
+            let header_value = |name: &str| {
+                response_header
+                    .headers
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            };
+            CachedResponse {
+                content_type: header_value("content-type"),
+                content_encoding: header_value("content-encoding"),
+                body: Bytes::new(),
+            }
+        });
+        if let Some(key) = ctx.cache_key.take() {
+            if pending.is_some() {
+                ctx.cache_pending = pending.clone();
+                ctx.cache_key = Some(key);
+            }
+        }
+        if let Some(ticket) = ctx.dedup_ticket.as_mut() {
+            ticket.set_pending(pending);
+        }
+        if let Some(cache_status) = ctx.cache_status {
+            response_header.insert_header(CACHE_STATUS_HEADER, cache_status.as_str())?;
+        }
+        // 配置了降级链的模型标明实际服务的模型
+        if let Some(model) = &ctx.model {
+            let chains = &self.gemini_config().model_fallback_chain.chains;
+            if ctx.fallback_from.is_some() || chains.contains_key(model) {
+                response_header.insert_header(SERVED_MODEL_HEADER, model.as_str())?;
+            }
+        }
+
+        // 密钥状态按原始上游状态码记录后，再应用面向客户端的改写
+        if let Some(body) = rewrite_empty_response(&self.gemini_config().soft_failure, &ctx.request_id, response_header)? {
+            ctx.rewritten_body = Some(body);
+            ctx.soft_failure = Some(SoftFailure::EmptyBody);
+        } else {
+            ctx.rewritten_body = apply_status_rewrite(&self.gemini_config().status_rewrites, response_header)?;
+        }
+        normalize_response_headers(&self.gemini_config().response_headers, response_header)?;
+
+        // 流式响应逐块透传（配置合并阈值时合并小数据块）
+        if ctx.streaming && ctx.rewritten_body.is_none() && is_event_stream(response_header) {
+            prepare_streaming_response(response_header)?;
+            ctx.stream_coalescer = StreamCoalescer::from_config(&self.gemini_config().streaming_buffer);
+        }
+
+        // OpenAI 兼容请求只转换上游成功响应，错误响应原样返回
+        if ctx.openai_response.is_some() {
+            if status == 200 && ctx.rewritten_body.is_none() {
+                response_header.remove_header("content-length");
+                response_header.insert_header("transfer-encoding", "chunked")?;
+            } else {
+                ctx.openai_response = None;
+            }
+        }
+
+        // 剥离字段或注入规范化错误码会改变响应体长度，改为分块传输
+        if ctx.rewritten_body.is_none()
+            && ctx.openai_response.is_none()
+            && should_annotate(&self.gemini_config().error_codes, response_header)
+        {
+            ctx.error_status = Some(response_header.status.as_u16());
+        }
+        if ctx.rewritten_body.is_none()
+            && ctx.openai_response.is_none()
+            && (ctx.error_status.is_some()
+                || should_strip(&self.gemini_config().strip_response_fields, ctx.streaming, response_header))
+        {
+            ctx.strip_buffer = Some(Vec::new());
+            response_header.remove_header("content-length");
+            response_header.insert_header("transfer-encoding", "chunked")?;
+        }
+        Ok(())
+    }
+
+    /// 将 OpenAI chat completions 请求改写为 Gemini 请求，返回 true 表示请求已被拒绝
+    async fn translate_openai_request(&self, session: &mut Session, ctx: &mut ProxyCtx) -> Result<bool> {
+        let body = match buffer_request_body(session, ctx, MAX_OPENAI_REQUEST_BYTES).await? {
+            Some(body) => body,
+            None => {
+                tracing::warn!(request_id = %ctx.request_id, "OpenAI 兼容请求体缺少长度或超过上限");
+                session.respond_error(413).await?;
+                return Ok(true);
+            }
+        };
+        let translated = match translate_request(&body) {
+            Ok(translated) => translated,
+            Err(error) => {
+                tracing::warn!(request_id = %ctx.request_id, "无法转换 OpenAI 兼容请求: {}", error);
+                let (header, body) = invalid_request_response(error, &ctx.request_id)?;
+                session.write_response_header(Box::new(header), false).await?;
+                session.write_response_body(Some(body), true).await?;
+                return Ok(true);
+            }
+        };
+
+        let uri = translated
+            .path_and_query
+            .parse::<http::Uri>()
+            .or_err(ErrorType::InvalidHTTPHeader, "转换后的请求路径无效")?;
+        let req = session.req_header_mut();
+        req.set_uri(uri);
+        req.insert_header("content-type", "application/json")?;
+        req.insert_header("content-length", translated.body.len().to_string())?;
+        // 上游响应需要解析后转换，不接受压缩编码
+        req.remove_header("accept-encoding");
+
+        ctx.model = Some(translated.model.clone());
+        ctx.streaming = translated.stream;
+        // 哈希路由与影子镜像读取的是转换后的请求体
+        ctx.buffered_request_body = Some(translated.body.clone());
+        ctx.openai_response = Some(ChatCompletionTranslator::new(&translated, &ctx.request_id));
+        ctx.openai_request = Some(translated.body);
+        Ok(false)
+    }
+
+    /// 查询响应缓存，返回 true 表示已直接返回缓存的响应
+    async fn lookup_response_cache(&self, session: &mut Session, ctx: &mut ProxyCtx) -> Result<bool> {
+        let cache = match &self.response_cache {
+            // 缓存保存的是上游原始响应，OpenAI 兼容请求不走缓存
+            Some(_) if ctx.openai_response.is_some() => return Ok(false),
+            Some(cache) => cache.clone(),
+            None => return Ok(false),
+        };
+        let req = session.req_header();
+        if !wants_cache(&self.gemini_config().response_cache, req.uri.path(), &req.headers) {
+            return Ok(false);
+        }
+
+        let path_and_query = req.uri.path_and_query().map_or("/", |pq| pq.as_str()).to_string();
+        // 只缓存声明了长度且不超过上限的非流式请求，避免无界缓冲请求体
+        let body = if is_streaming_request(&path_and_query) {
+            None
+        } else {
+            buffer_request_body(session, ctx, MAX_CACHEABLE_REQUEST_BYTES).await?
+        };
+        let body = match body {
+            Some(body) => body,
+            None => {
+                ctx.cache_status = Some(CacheStatus::Bypass);
+                return Ok(false);
+            }
+        };
+
+        let key = match cache_key(&path_and_query, &body) {
+            Some(key) => key,
+            None => {
+                ctx.cache_status = Some(CacheStatus::Bypass);
+                return Ok(false);
+            }
+        };
+
+        match cache.get(&key) {
+            Some(cached) => {
+                self.write_stored_response(session, cached, CACHE_STATUS_HEADER, CacheStatus::Hit.as_str())
+                    .await?;
+                ctx.cache_status = Some(CacheStatus::Hit);
+                Ok(true)
+            }
+            None => {
+                ctx.cache_status = Some(CacheStatus::Miss);
+                ctx.cache_key = Some(key);
+                Ok(false)
+            }
+        }
+    }
+
+    /// 直接返回保存的上游成功响应（缓存命中或重复请求重放），`status_header` 标明响应来源
+    async fn write_stored_response(
+        &self,
+        session: &mut Session,
+        mut stored: CachedResponse,
+        status_header: &'static str,
+        status_value: &'static str,
+    ) -> Result<()> {
+        // 保存的是上游原始响应体，返回前同样剥离配置的字段
+        let strip_paths = &self.gemini_config().strip_response_fields;
+        let is_json = stored.content_type.as_deref().is_some_and(|t| t.starts_with("application/json"));
+        if !strip_paths.is_empty() && is_json && stored.content_encoding.is_none() {
+            if let Some(stripped) = strip_response_fields(strip_paths, &stored.body) {
+                stored.body = stripped;
+            }
+        }
+        let mut header = ResponseHeader::build(200, Some(4))?;
+        if let Some(content_type) = &stored.content_type {
+            header.insert_header("content-type", content_type)?;
+        }
+        if let Some(content_encoding) = &stored.content_encoding {
+            header.insert_header("content-encoding", content_encoding)?;
+        }
+        header.insert_header("content-length", stored.body.len().to_string())?;
+        header.insert_header(status_header, status_value)?;
+        session.write_response_header(Box::new(header), false).await?;
+        session.write_response_body(Some(stored.body), true).await
+    }
+
+    /// 去重窗口：重复请求返回首个请求的响应，返回 true 表示已直接响应
+    async fn lookup_dedup(&self, session: &mut Session, ctx: &mut ProxyCtx) -> Result<bool> {
+        // OpenAI 兼容请求的响应需要转换，不参与去重
+        let window = match &self.dedup_window {
+            Some(_) if ctx.openai_response.is_some() => return Ok(false),
+            Some(window) => window.clone(),
+            None => return Ok(false),
+        };
+        let config = self.gemini_config();
+        let path_and_query = session.req_header().uri.path_and_query().map_or("/", |pq| pq.as_str()).to_string();
+        if !config.dedup.enabled || is_streaming_request(&path_and_query) {
+            return Ok(false);
+        }
+
+        let body = if idempotency_key(&session.req_header().headers).is_none() && config.dedup.hash_body {
+            buffer_request_body(session, ctx, MAX_DEDUP_BODY_BYTES).await?
+        } else {
+            None
+        };
+        let key = match dedup_key(&path_and_query, &session.req_header().headers, body.as_deref()) {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        let first = match window.lookup(key) {
+            DedupLookup::Forward(ticket) => {
+                ctx.dedup_ticket = Some(ticket);
+                return Ok(false);
+            }
+            DedupLookup::Replay(response) => response,
+            DedupLookup::Wait(first) => match wait_for_first(first).await {
+                Some(response) => response,
+                // 首个请求失败：照常转发
+                None => return Ok(false),
+            },
+        };
+        tracing::info!(request_id = %ctx.request_id, "去重窗口内的重复请求，返回首个请求的响应");
+        self.write_stored_response(session, first, DEDUP_STATUS_HEADER, DEDUP_REPLAYED).await?;
+        Ok(true)
+    }
+}
+
+/// Pingora 重试缓冲的容量，超出时缓冲被截断，提前读取的请求体不会再转发给上游
+const RETRY_BUFFER_BYTES: usize = 64 * 1024;
+
+/// 在转发前完整读取请求体，仅限声明了长度且不超过 `max_bytes`（以及重试缓冲容量）的请求；
+/// 读取结果保存在上下文中，供响应缓存与哈希路由共用
+async fn buffer_request_body(session: &mut Session, ctx: &mut ProxyCtx, max_bytes: usize) -> Result<Option<Bytes>> {
+    if let Some(body) = &ctx.buffered_request_body {
+        return Ok(Some(body.clone()));
+    }
+    let content_length = session
+        .req_header()
+        .headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    let content_length = match content_length {
+        Some(len) if len <= max_bytes.min(RETRY_BUFFER_BYTES) => len,
+        _ => return Ok(None),
+    };
+
+    // 启用重试缓冲后，Pingora 会把这里读取的请求体继续转发给上游
+    session.enable_retry_buffering();
+    let mut body = Vec::with_capacity(content_length);
+    while let Some(chunk) = session.read_request_body().await? {
+        body.extend_from_slice(&chunk);
+    }
+    let body = Bytes::from(body);
+    ctx.buffered_request_body = Some(body.clone());
+    Ok(Some(body))
+}
+
+/// 粘性绑定使用的客户端标识：优先使用 x-session-id 请求头，否则使用客户端 IP
+fn sticky_client_id(session: &Session) -> String {
+    if let Some(session_id) = session
+        .req_header()
+        .headers
+        .get("x-session-id")
+        .and_then(|h| h.to_str().ok())
+        .filter(|s| !s.is_empty())
+    {
+        return format!("session:{}", session_id);
+    }
+
+    session
+        .client_addr()
+        .map(|addr| match addr {
+            SocketAddr::Inet(inet_addr) => inet_addr.ip().to_string(),
+            SocketAddr::Unix(_) => "unix_socket".to_string(),
+        })
+        .unwrap_or_else(|| "unknown".to_string())
 }
 
 #[async_trait]
@@ -46,87 +920,707 @@ impl ProxyHttp for GeminiProxyService {
 
     fn new_ctx(&self) -> Self::CTX {
         ProxyCtx {
+            request_id: String::new(),
             api_key_id: None,
             request_start_time: None,
+            rewritten_body: None,
+            model: None,
+            response_body: Vec::new(),
+            response_body_truncated: false,
+            in_flight: None,
+            inflight_permit: None,
+            traffic_class: None,
+            request_body: Vec::new(),
+            request_body_received: 0,
+            cache_status: None,
+            cache_key: None,
+            cache_pending: None,
+            dedup_ticket: None,
+            attempts: AttemptLog::default(),
+            debug_attempts: false,
+            preflight: None,
+            preflight_rejection: None,
+            denied_reason: None,
+            buffered_request_body: None,
+            soft_failure: None,
+            mirror_request: None,
+            strip_buffer: None,
+            error_status: None,
+            streaming: false,
+            first_byte_seen: false,
+            stream_coalescer: None,
+            request_span: tracing::Span::none(),
+            upstream_span: None,
+            upstream_deadline: None,
+            failover_retries: 0,
+            failover_tried_keys: Vec::new(),
+            failover_backoff_spent: std::time::Duration::ZERO,
+            failover_pending: false,
+            key_failure_reported: false,
+            key_success_pending: false,
+            openai_request: None,
+            openai_response: None,
+            fallback_from: None,
         }
     }
 
     async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool> {
         ctx.request_start_time = Some(Utc::now());
+        ctx.request_id = apply_request_id(&self.gemini_config().request_id, session.req_header_mut())?;
+        ctx.request_span = request_span(&ctx.request_id);
+        // 停机排空期间不再接受新请求，并关闭连接让客户端改连其他实例
+        if self.shutdown.is_draining() {
+            session.set_keepalive(None);
+            let shed = Shed::new(
+                ShedReason::Draining,
+                GeminiProxyError::load_balancer("代理正在停机").with_retryable(true),
+            );
+            self.write_shed_response(session, ctx, shed).await?;
+            return Ok(true);
+        }
+        if self.enforce_ip_rules(session, ctx).await? {
+            return Ok(true);
+        }
+        if let Some(exceeded) = check_header_limits(&self.header_limits, session.req_header()) {
+            tracing::warn!(request_id = %ctx.request_id, reason = exceeded.as_str(), "拒绝请求头超出上限的请求");
+            ctx.denied_reason = Some(exceeded.as_str());
+            session.respond_error(431).await?;
+            return Ok(true);
+        }
+        // 按声明的长度提前拒绝，不读取请求体
+        if let Some(declared) = declared_length_exceeded(&self.body_limits, session.req_header()) {
+            let details = format!("content-length {} 超过上限 {}", declared, self.body_limits.max_request_bytes);
+            self.reject_oversized_body(session, ctx, details).await?;
+            return Ok(true);
+        }
+        ctx.inflight_permit = self.inflight_limiter.try_acquire();
+        if ctx.inflight_permit.is_none() {
+            tracing::warn!(request_id = %ctx.request_id, in_flight = self.inflight_limiter.in_flight(), "在途请求数已达上限，拒绝请求");
+            let shed = Shed::new(
+                ShedReason::Overloaded,
+                GeminiProxyError::load_balancer("代理在途请求数已达上限").with_retryable(true),
+            );
+            self.write_shed_response(session, ctx, shed).await?;
+            return Ok(true);
+        }
+        ctx.debug_attempts = wants_attempt_log(&session.req_header().headers);
+        ctx.model = extract_model(session.req_header().uri.path());
+        ctx.streaming = session
+            .req_header()
+            .uri
+            .path_and_query()
+            .is_some_and(|pq| is_streaming_request(pq.as_str()));
+        ctx.traffic_class = classify(
+            &self.gemini_config().traffic_classes,
+            session.req_header().uri.path(),
+            &session.req_header().headers,
+            None,
+        )
+        .map(str::to_string);
+
+        let req = session.req_header();
+        if !is_route_allowed(&self.gemini_config().route_allowlist, req.method.as_str(), req.uri.path()) {
+            tracing::warn!(
+                request_id = %ctx.request_id,
+                method = %req.method,
+                path = req.uri.path(),
+                "拒绝不在允许列表中的请求"
+            );
+            ctx.denied_reason = Some(ROUTE_NOT_ALLOWED_REASON);
+            session.respond_error(403).await?;
+            return Ok(true);
+        }
 
         if !self.auth_handler.validate_request(session).await? {
             session.respond_error(401).await?;
             return Ok(true);
         }
 
+        let missing = missing_required_headers(&self.gemini_config().required_headers, &session.req_header().headers);
+        if !missing.is_empty() {
+            tracing::warn!(request_id = %ctx.request_id, missing = ?missing, "拒绝缺少必需请求头的请求");
+            ctx.denied_reason = Some(MISSING_HEADERS_REASON);
+            let (header, body) = missing_headers_response(&missing, &ctx.request_id)?;
+            session.write_response_header(Box::new(header), false).await?;
+            session.write_response_body(Some(body), true).await?;
+            return Ok(true);
+        }
+
         if !self.auth_handler.check_rate_limit(session).await? {
-            session.respond_error(429).await?;
+            let shed = Shed::new(
+                ShedReason::RateLimited,
+                GeminiProxyError::rate_limit("客户端请求过于频繁").with_retryable(true),
+            );
+            self.write_shed_response(session, ctx, shed).await?;
             return Ok(true);
         }
 
-        if let Some(api_key) = self.key_manager.get_next_key().await {
-            session
-                .req_header_mut()
-                .insert_header("x-goog-api-key", &api_key.key)?;
-            ctx.api_key_id = Some(api_key.id.clone());
-            self.metrics.increment_request_count(&api_key.id).await;
-        } else {
-            session.respond_error(503).await?;
+        let req = session.req_header();
+        if is_chat_completions(req.method.as_str(), req.uri.path()) && self.translate_openai_request(session, ctx).await? {
+            return Ok(true);
+        }
+
+        if self.apply_model_override(session, ctx).await? {
+            return Ok(true);
+        }
+        self.apply_model_fallback(session, ctx)?;
+
+        // 命中缓存时不选择密钥，也不请求上游
+        if self.lookup_response_cache(session, ctx).await? {
+            return Ok(true);
+        }
+        if self.lookup_dedup(session, ctx).await? {
             return Ok(true);
         }
 
+        ctx.preflight = PendingPreflight::for_request(
+            &self.gemini_config().context_preflight,
+            ctx.model.as_deref(),
+            &session.req_header().headers,
+        );
+
+        // 哈希路由：相同输入固定到同一个密钥，字段缺失时回退到加权轮询
+        let hash_routing = &self.gemini_config().hash_routing;
+        let routing_hash = if hash_routing::applies_to(hash_routing, session.req_header().uri.path()) {
+            match buffer_request_body(session, ctx, MAX_HASH_ROUTING_BODY_BYTES).await? {
+                Some(body) => routing_hash_for_request(hash_routing, session.req_header().uri.path(), &body),
+                None => None,
+            }
+        } else {
+            None
+        };
+        // 一致性哈希调度：同一客户端属性固定到哈希环上的同一个密钥
+        let routing = match routing_hash {
+            Some(routing_hash) => KeyRouting::ContentHash(routing_hash),
+            None => {
+                let client_ip = match session.client_addr() {
+                    Some(SocketAddr::Inet(inet_addr)) => Some(inet_addr.ip()),
+                    _ => None,
+                };
+                affinity_hash(&self.gemini_config().scheduling, &session.req_header().headers, client_ip)
+                    .map_or(KeyRouting::Client, KeyRouting::Affinity)
+            }
+        };
+
+        // 换密钥重试需要重放请求体
+        if self.gemini_config().failover.max_retries > 0 {
+            session.enable_retry_buffering();
+        }
+
+        let client_id = sticky_client_id(session);
+        let select_span = TimedSpan::select_key(&ctx.request_span);
+        let selected = select_key_with_hash(
+            &self.key_manager,
+            self.recovery_manager.as_deref(),
+            &client_id,
+            routing,
+            ctx.model.as_deref(),
+        )
+        .instrument(select_span.span().clone())
+        .await;
+        if let Ok(api_key) = &selected {
+            select_span.record("key_id", api_key.id.as_str());
+        }
+        select_span.finish();
+        match selected {
+            Ok(api_key) => {
+                session
+                    .req_header_mut()
+                    .insert_header("x-goog-api-key", &api_key.key)?;
+                ctx.api_key_id = Some(api_key.id.clone());
+                ctx.in_flight = Some(InFlightGuard::new(self.metrics.clone()));
+                self.metrics.increment_request_count(&api_key.id).await;
+            }
+            Err(shed) => {
+                // 快速失败，不尝试上游
+                tracing::warn!(request_id = %ctx.request_id, reason = shed.reason.as_str(), "密钥选择失败: {}", shed.error);
+                self.write_shed_response(session, ctx, shed).await?;
+                return Ok(true);
+            }
+        }
+
+        // 存在影子预热密钥时缓冲请求体，真实请求成功后镜像给影子密钥
+        if self.key_manager.has_shadow_keys().await {
+            if let Some(body) = buffer_request_body(session, ctx, MAX_MIRROR_BODY_BYTES).await? {
+                let req = session.req_header();
+                ctx.mirror_request = Some(MirroredRequest {
+                    method: req.method.to_string(),
+                    path_and_query: req.uri.path_and_query().map_or("/", |pq| pq.as_str()).to_string(),
+                    host: self.upstream_host(),
+                    body,
+                });
+            }
+        }
+
         Ok(false)
     }
 
+    async fn request_body_filter(
+        &self,
+        session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        // 未声明长度的请求边转发边计数，超出上限时中止转发（响应已写出，fail_to_proxy 不再响应）
+        if count_request_chunk(&self.body_limits, &mut ctx.request_body_received, body) {
+            let details = format!("请求体超过上限 {}", self.body_limits.max_request_bytes);
+            self.reject_oversized_body(session, ctx, details).await?;
+            // 由客户端引起，不计入密钥的上游失败
+            return Err(Error::create(ErrorType::HTTPStatus(413), ErrorSource::Downstream, Some("请求体超出上限".into()), None));
+        }
+
+        // OpenAI 兼容请求：丢弃客户端原始请求体，改为转发转换后的 Gemini 请求体（重试时同样重放）
+        if let Some(translated) = &ctx.openai_request {
+            *body = end_of_stream.then(|| translated.clone());
+        }
+
+        let rules = &self.gemini_config().traffic_classes;
+        if needs_body(rules) {
+            if let Some(chunk) = body {
+                let remaining = MAX_CLASSIFY_BODY_BYTES.saturating_sub(ctx.request_body.len());
+                ctx.request_body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+            }
+
+            // 请求体读取完毕后按完整规则重新分类
+            if end_of_stream {
+                ctx.traffic_class = classify(
+                    rules,
+                    session.req_header().uri.path(),
+                    &session.req_header().headers,
+                    Some(&ctx.request_body),
+                )
+                .map(str::to_string);
+                ctx.request_body = Vec::new();
+            }
+        }
+
+        // 上下文窗口预检：缓冲完整请求体后再转发，超限时中止转发
+        if let Some(preflight) = ctx.preflight.as_mut() {
+            match preflight.push(body, end_of_stream, self.token_estimator.as_ref()) {
+                PreflightStep::Buffering => {}
+                PreflightStep::Forward => ctx.preflight = None,
+                PreflightStep::Rejected(exceeded) => {
+                    tracing::warn!(
+                        request_id = %ctx.request_id,
+                        model = %exceeded.model,
+                        estimated_tokens = exceeded.estimated_tokens,
+                        limit = exceeded.limit,
+                        "请求超过模型上下文上限，已拒绝"
+                    );
+                    ctx.preflight = None;
+                    ctx.preflight_rejection = Some(exceeded);
+                    return Error::e_explain(ErrorType::HTTPStatus(400), "请求超过模型上下文上限");
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn upstream_peer(
         &self,
         _session: &mut Session,
-        _ctx: &mut Self::CTX,
+        ctx: &mut Self::CTX,
     ) -> Result<Box<HttpPeer>> {
-        let peer = Box::new(HttpPeer::new(
-            self.gemini_config.base_url.clone(),
-            true, // HTTPS
-            self.gemini_config
-                .base_url
-                .split(':')
-                .next()
-                .unwrap_or("")
-                .to_string(),
-        ));
-        Ok(peer)
+        // 每次（重试）连接上游都记录为一次新的尝试，重放的请求体重新计数
+        ctx.request_body_received = 0;
+        ctx.key_failure_reported = false;
+        ctx.key_success_pending = false;
+        if let Some(key_id) = &ctx.api_key_id {
+            ctx.attempts.begin(key_id);
+            ctx.upstream_span = Some(TimedSpan::upstream_request(&ctx.request_span, key_id));
+        }
+
+        let timeouts = UpstreamTimeouts::for_request(&self.gemini_config(), ctx.streaming);
+        ctx.upstream_deadline = Some(UpstreamDeadline::start(timeouts, Instant::now()));
+        let mut peer = self.upstream_http_peer();
+        timeouts.apply(&mut peer);
+        Ok(Box::new(peer))
     }
 
-    async fn response_filter(
+    async fn connected_to_upstream(
         &self,
         _session: &mut Session,
-        _response_header: &mut ResponseHeader,
+        reused: bool,
+        _peer: &HttpPeer,
+        #[cfg(unix)] _fd: std::os::unix::io::RawFd,
+        #[cfg(windows)] _sock: std::os::windows::io::RawSocket,
+        _digest: Option<&Digest>,
+        _ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        self.upstream_connections.record(reused);
+        Ok(())
+    }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
+        response_header: &mut ResponseHeader,
         ctx: &mut Self::CTX,
     ) -> Result<()> {
-        let status = _session
-            .response_written()
-            .map(|r| r.status.as_u16())
-            .unwrap_or(0);
-        let response_time = ctx.request_start_time.map_or_else(
-            || std::time::Duration::from_secs(0),
-            |start| (Utc::now() - start).to_std().unwrap_or_default(),
-        );
+        let status = response_header.status.as_u16();
+        if let Some(upstream_span) = ctx.upstream_span.take() {
+            upstream_span.record("status", status);
+            let latency = upstream_span.finish();
+            self.record_key_upstream_call(ctx, &status.to_string(), status >= 400, latency);
+        }
+        if let Some(model) = &ctx.model {
+            self.model_fallback
+                .record(&self.gemini_config().model_fallback_chain, model, status, Instant::now());
+        }
+        if self.try_failover(session, response_header, ctx).await? {
+            self.finish_upstream_call(ctx);
+            return Error::e_explain(ErrorType::HTTPStatus(status), "上游返回可重试状态，换用其他密钥重试");
+        }
 
-        self.metrics.record_response(status, response_time).await;
+        let span = TimedSpan::response_filter(&ctx.request_span);
+        span.record("status", status);
+        let result = self
+            .filter_response(response_header, ctx)
+            .instrument(span.span().clone())
+            .await;
+        span.finish();
+        result
+    }
 
-        if let Some(key_id) = &ctx.api_key_id {
-            if (200..300).contains(&status) {
-                self.key_manager.mark_key_success(key_id).await;
-            } else if status >= 400 {
+    async fn response_trailer_filter(
+        &self,
+        _session: &mut Session,
+        upstream_trailers: &mut http::HeaderMap,
+        _ctx: &mut Self::CTX,
+    ) -> Result<Option<Bytes>> {
+        if !self.gemini_config().response_headers.forward_trailers {
+            upstream_trailers.clear();
+        }
+        Ok(None)
+    }
+
+    fn fail_to_connect(
+        &self,
+        _session: &mut Session,
+        _peer: &HttpPeer,
+        ctx: &mut Self::CTX,
+        e: Box<Error>,
+    ) -> Box<Error> {
+        ctx.attempts.finish_error(e.etype().as_str());
+        if let Some(upstream_span) = ctx.upstream_span.take() {
+            upstream_span.record("error", e.etype().as_str());
+            let latency = upstream_span.finish();
+            self.record_key_upstream_call(ctx, e.etype().as_str(), true, latency);
+        }
+        self.finish_upstream_call(ctx);
+        self.report_connect_failure(ctx);
+        e
+    }
+
+    fn error_while_proxy(
+        &self,
+        peer: &HttpPeer,
+        session: &mut Session,
+        e: Box<Error>,
+        ctx: &mut Self::CTX,
+        client_reused: bool,
+    ) -> Box<Error> {
+        let mut e = e.more_context(format!("Peer: {}", peer));
+        if std::mem::take(&mut ctx.failover_pending) {
+            // 响应过滤器已换好密钥，重放请求
+            e.set_retry(true);
+        } else {
+            e.retry
+                .decide_reuse(client_reused && !session.as_ref().retry_buffer_truncated());
+        }
+        e
+    }
+
+    async fn fail_to_proxy(&self, session: &mut Session, e: &Error, ctx: &mut Self::CTX) -> FailToProxy
+    where
+        Self::CTX: Send + Sync,
+    {
+        ctx.attempts.finish_error(e.etype().as_str());
+        // 客户端引起的错误（请求体超限、客户端断开等）不计为密钥的上游失败
+        let downstream_caused = matches!(e.esource(), ErrorSource::Downstream);
+        if let Some(upstream_span) = ctx.upstream_span.take() {
+            upstream_span.record("error", e.etype().as_str());
+            let latency = upstream_span.finish();
+            if !downstream_caused {
+                self.record_key_upstream_call(ctx, e.etype().as_str(), true, latency);
+            }
+        }
+        self.finish_upstream_call(ctx);
+
+        if let Some(exceeded) = ctx.preflight_rejection.take() {
+            self.settle_key_after_error(ctx, false).await;
+            let written = async {
+                let (header, body) = context_exceeded_response(&exceeded, &ctx.request_id)?;
+                session.write_response_header(Box::new(header), false).await?;
+                session.write_response_body(Some(body), true).await
+            };
+            if let Err(write_error) = written.await {
+                tracing::warn!(request_id = %ctx.request_id, "写入预检拒绝响应出错: {}", write_error);
+            }
+            return FailToProxy {
+                error_code: 400,
+                can_reuse_downstream: false,
+            };
+        }
+
+        let code = match e.etype() {
+            ErrorType::HTTPStatus(code) => *code,
+            _ => match e.esource() {
+                ErrorSource::Upstream => 502,
+                // 客户端连接已断开时无需响应
+                ErrorSource::Downstream => match e.etype() {
+                    ErrorType::WriteError | ErrorType::ReadError | ErrorType::ConnectionClosed => 0,
+                    _ => 400,
+                },
+                ErrorSource::Internal | ErrorSource::Unset => 500,
+            },
+        };
+
+        // 流式响应中途上游断开：响应头已发送，追加 SSE 错误事件而不是静默截断
+        if ctx.streaming && session.response_written().is_some() && is_upstream_interruption(e) {
+            let (error, event) = stream_interrupted_event(e, &ctx.request_id);
+            tracing::warn!(request_id = %ctx.request_id, "{}", error);
+            // 先转发合并器中暂存的数据块，再追加错误事件
+            let event = match ctx.stream_coalescer.as_mut().and_then(StreamCoalescer::take_pending) {
+                Some(pending) => Bytes::from([pending, event].concat()),
+                None => event,
+            };
+            if let Some(key_id) = &ctx.api_key_id {
                 self.key_manager.mark_key_failed(key_id).await;
             }
+            self.settle_key_after_error(ctx, true).await;
+            if let Err(write_error) = session.write_response_body(Some(event), true).await {
+                tracing::warn!(request_id = %ctx.request_id, "写入流式错误事件出错: {}", write_error);
+            }
+            return FailToProxy {
+                error_code: code,
+                can_reuse_downstream: false,
+            };
+        }
+
+        // 换密钥重试未能执行时新密钥还没有请求上游
+        let key_at_fault = !std::mem::take(&mut ctx.failover_pending) && !downstream_caused;
+        self.settle_key_after_error(ctx, key_at_fault).await;
+
+        // 响应头已发送（例如流式响应中途失败）时无法再返回错误响应
+        if code > 0 && session.response_written().is_none() {
+            let error = GeminiProxyError::network(format!("上游请求失败: {}", e.etype().as_str()))
+                .with_request_id(ctx.request_id.clone())
+                .with_retryable(code >= 500);
+            let body = Bytes::from(failure_body(code, &error, &ctx.attempts).to_string());
+            let written = async {
+                let mut header = ResponseHeader::build(code, Some(3))?;
+                header.insert_header("content-type", "application/json")?;
+                header.insert_header("content-length", body.len().to_string())?;
+                if ctx.debug_attempts {
+                    header.insert_header(ATTEMPT_LOG_RESPONSE_HEADER, ctx.attempts.header_value())?;
+                }
+                session.write_response_header(Box::new(header), false).await?;
+                session.write_response_body(Some(body), true).await
+            };
+            if let Err(write_error) = written.await {
+                tracing::warn!(request_id = %ctx.request_id, "写入失败响应出错: {}", write_error);
+            }
+        }
+
+        FailToProxy {
+            error_code: code,
+            can_reuse_downstream: false,
+        }
+    }
+
+    fn upstream_response_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        if let Some(deadline) = ctx.upstream_deadline.as_mut().filter(|_| body.is_some()) {
+            if let Err(timeout) = deadline.on_chunk(Instant::now()) {
+                tracing::warn!(
+                    request_id = %ctx.request_id,
+                    streaming = ctx.streaming,
+                    timeout = timeout.as_str(),
+                    "上游响应超时，中止请求"
+                );
+                return Error::e_explain(ErrorType::ReadTimedout, format!("上游响应超时: {}", timeout.as_str()));
+            }
+        }
+        if end_of_stream {
+            self.finish_upstream_call(ctx);
         }
         Ok(())
     }
 
-    async fn logging(&self, session: &mut Session, _e: Option<&Error>, ctx: &mut Self::CTX) {
+    fn response_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<Option<std::time::Duration>>
+    where
+        Self::CTX: Send + Sync,
+    {
+        if !ctx.first_byte_seen && body.as_ref().is_some_and(|b| !b.is_empty()) {
+            ctx.first_byte_seen = true;
+            if let Some(start) = ctx.request_start_time {
+                self.metrics
+                    .record_time_to_first_byte(ctx.streaming, crate::utils::clock::elapsed_since(start, Utc::now()));
+            }
+        }
+
+        if let Some(replacement) = &ctx.rewritten_body {
+            // 丢弃上游响应体，仅在流结束时输出替换内容
+            *body = if end_of_stream {
+                Some(replacement.clone())
+            } else {
+                None
+            };
+            return Ok(None);
+        }
+
+        // 缓冲响应体用于用量解析，超出上限后停止缓冲
+        if !ctx.response_body_truncated && buffer_response_chunk(&mut ctx.response_body, body, self.body_limits.max_response_buffer_bytes) {
+            ctx.response_body_truncated = true;
+            ctx.response_body = Vec::new();
+        }
+
+        if let Some(translator) = ctx.openai_response.as_mut() {
+            translator.push(body, end_of_stream);
+        }
+
+        // 需要剥离字段或注入错误码时暂存响应体，流结束时输出转换结果；超出缓冲上限则输出已暂存内容并改为原样转发
+        if let Some(buffer) = ctx.strip_buffer.as_mut() {
+            if let Some(chunk) = body.take() {
+                buffer.extend_from_slice(&chunk);
+            }
+            if buffer.len() > MAX_TRANSFORM_BODY_BYTES {
+                *body = ctx.strip_buffer.take().map(Bytes::from);
+            } else if end_of_stream {
+                let buffered = ctx.strip_buffer.take().unwrap_or_default();
+                let config = self.gemini_config();
+                let transformed = strip_response_fields(&config.strip_response_fields, &buffered)
+                    .unwrap_or_else(|| Bytes::from(buffered));
+                *body = Some(match ctx.error_status {
+                    Some(status) => {
+                        annotate_error_body(&config.error_codes, status, &transformed).unwrap_or(transformed)
+                    }
+                    None => transformed,
+                });
+            }
+        }
+
+        if let Some(coalescer) = ctx.stream_coalescer.as_mut() {
+            *body = coalescer.push(body.take(), end_of_stream, Instant::now());
+        }
+        Ok(None)
+    }
+
+    async fn logging(&self, session: &mut Session, e: Option<&Error>, ctx: &mut Self::CTX) {
+        // 客户端断开时 Pingora 已中止上游请求，这里结束在途计数并记录取消
+        let client_cancelled = e.is_some_and(is_client_disconnect);
+        if let Some(in_flight) = ctx.in_flight.take() {
+            in_flight.finish(e);
+        }
+        // 上游调用未正常结束（例如客户端中途断开）时在此结束计时
+        self.finish_upstream_call(ctx);
+
+        let status = session.response_written().map(|r| r.status.as_u16());
         let response_time = ctx
             .request_start_time
-            .map_or(0, |start| (Utc::now() - start).num_milliseconds());
+            .map_or(0, |start| crate::utils::clock::elapsed_since(start, Utc::now()).as_millis() as i64);
+        if let Some(start) = ctx.request_start_time {
+            self.metrics
+                .record_request_duration(ctx.streaming, crate::utils::clock::elapsed_since(start, Utc::now()));
+        }
+        if ctx.streaming && ctx.first_byte_seen && e.is_none() {
+            if let Some(start) = ctx.request_start_time {
+                self.metrics
+                    .record_stream_duration(crate::utils::clock::elapsed_since(start, Utc::now()));
+            }
+        }
+        let mut record = RequestRecord::new(
+            ctx.request_id.clone(),
+            sticky_client_id(session),
+            ctx.api_key_id.clone(),
+            ctx.model.clone(),
+        )
+        .with_status(status, response_time);
+
+        // 容错解析上游响应体，并根据用量估算成本
+        if ctx.rewritten_body.is_none() && !ctx.response_body_truncated && !client_cancelled {
+            let parsed = inspect_upstream_body(&self.metrics, &ctx.response_body).await;
+            // 仅检查上游返回的响应（缓存命中等代理直接返回的响应不经过上游）
+            let upstream_ok = ctx.attempts.total() > 0 && status == Some(200);
+            if e.is_none() && upstream_ok {
+                ctx.soft_failure = detect_soft_failure(&self.gemini_config().soft_failure, &parsed);
+            }
+            // 上游错误与拦截按规范化错误码计数
+            if let Some(status) = status.filter(|_| ctx.attempts.total() > 0) {
+                if let Some(code) = normalize_error(&self.gemini_config().error_codes, status, &parsed) {
+                    self.metrics.record_gemini_error(&code).await;
+                }
+            }
+            record = record.with_upstream_body(&parsed, &self.gemini_config().model_costs);
+            if let Some(key_id) = &ctx.api_key_id {
+                self.metrics.record_estimated_cost(key_id, record.estimated_cost).await;
+                // 上游响应不含 usageMetadata 时不计入令牌用量
+                if let Some(usage) = record.usage {
+                    let model = ctx.model.as_deref().unwrap_or("unknown");
+                    self.metrics
+                        .record_key_tokens(key_id, model, usage.prompt_tokens, usage.completion_tokens);
+                }
+            }
+        }
+
+        // 完整接收的成功响应写入缓存
+        if let (Some(cache), Some(key), Some(mut pending)) =
+            (&self.response_cache, ctx.cache_key.take(), ctx.cache_pending.take())
+        {
+            if e.is_none() && ctx.rewritten_body.is_none() && !ctx.response_body_truncated {
+                pending.body = Bytes::copy_from_slice(&ctx.response_body);
+                cache.insert(key, pending);
+            }
+        }
+
+        // 去重窗口内的首个请求成功完成后记录响应，未完成时释放登记让重复请求照常转发
+        if let Some(ticket) = ctx.dedup_ticket.take() {
+            let completed = e.is_none() && ctx.rewritten_body.is_none() && !ctx.response_body_truncated;
+            if completed && ctx.soft_failure.is_none() {
+                ticket.complete(Bytes::copy_from_slice(&ctx.response_body));
+            }
+        }
+
+        // 真实请求成功后镜像给影子预热密钥（后台执行，响应丢弃）
+        if let Some(request) = ctx.mirror_request.take() {
+            let succeeded = e.is_none() && ctx.soft_failure.is_none() && status.is_some_and(|s| (200..300).contains(&s));
+            if succeeded && ctx.attempts.total() > 0 {
+                let shadow_keys = self.key_manager.shadow_keys().await;
+                if !shadow_keys.is_empty() {
+                    mirror_to_shadow_keys(
+                        self.shadow_connector.clone(),
+                        self.key_manager.clone(),
+                        self.upstream_http_peer(),
+                        request,
+                        shadow_keys,
+                    );
+                }
+            }
+        }
+
+        // 软失败不计为成功：密钥按失败记录，后续请求与客户端重试换用其他密钥
+        if let Some(soft_failure) = ctx.soft_failure {
+            self.metrics.record_upstream_soft_failure(soft_failure.as_str()).await;
+            if let Some(key_id) = &ctx.api_key_id {
+                self.key_manager.mark_key_failed(key_id).await;
+            }
+            tracing::warn!(request_id = %ctx.request_id, reason = soft_failure.as_str(), "上游返回 200 但响应不可用，按软失败处理");
+        }
+        self.settle_key_after_success(ctx, e.is_none()).await;
+
         let client_ip = session
             .client_addr()
             .map(|addr| match addr {
@@ -135,13 +1629,342 @@ impl ProxyHttp for GeminiProxyService {
             })
             .unwrap_or_else(|| "unknown".to_string());
 
+        let traffic_class = ctx.traffic_class.as_deref().unwrap_or(DEFAULT_TRAFFIC_CLASS);
+        self.metrics.record_traffic_class(traffic_class).await;
+
+        if let Some(audit_log) = &self.audit_log {
+            let status_code = status.unwrap_or(0);
+            let mut metadata = HashMap::new();
+            metadata.insert("traffic_class".to_string(), traffic_class.to_string());
+            if let Some(reason) = ctx.denied_reason {
+                metadata.insert("denied_reason".to_string(), reason.to_string());
+            }
+            if let Some(soft_failure) = ctx.soft_failure {
+                metadata.insert("soft_failure".to_string(), soft_failure.as_str().to_string());
+            }
+            let record = ApiCallRecord {
+                source_ip: client_ip.parse().unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                user_id: ctx.api_key_id.clone(),
+                method: session.req_header().method.to_string(),
+                resource: session.req_header().uri.path().to_string(),
+                status_code,
+                duration_ms: response_time.max(0) as u64,
+                result: match status_code {
+                    429 => AuditResult::RateLimited,
+                    401 | 403 => AuditResult::Denied,
+                    200..=399 => AuditResult::Success,
+                    _ => AuditResult::Failure,
+                },
+                request_id: Some(ctx.request_id.clone()),
+                metadata,
+            };
+            if let Err(e) = audit_log.lock().await.log_api_call_record(record).await {
+                tracing::warn!("记录审计日志失败: {}", e);
+            }
+        }
+
         tracing::info!(
+            request_id = %ctx.request_id,
             method = %session.req_header().method,
             uri = %session.req_header().uri,
-            status = session.response_written().map(|r| r.status.as_u16()),
+            status,
             client_ip = %client_ip,
             api_key_id = ctx.api_key_id.as_deref().unwrap_or("N/A"),
+            traffic_class,
+            upstream_attempts = ctx.attempts.total(),
             processing_time_ms = response_time,
+            client_cancelled,
+        );
+
+        let request_record = &self.gemini_config().request_record;
+        if request_record.enabled {
+            record.emit(request_record.metrics.then_some(self.metrics.as_ref()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::recovery::{create_default_recovery_manager, CircuitBreakerState};
+    use crate::load_balancer::key_manager::ApiKey;
+    use std::time::Duration;
+
+    /// 密钥 key1 的熔断器已进入半开状态，且唯一的探测名额已被选中请求占用
+    async fn half_open_service() -> (GeminiProxyService, Arc<ErrorRecoveryManager>, ProxyCtx) {
+        let recovery_manager = Arc::new(create_default_recovery_manager().with_circuit_breaker(1, Duration::from_millis(0), 1));
+        recovery_manager.report_operation_result("key1", false).await;
+        assert!(recovery_manager.check_circuit_breaker("key1").await);
+        assert!(!recovery_manager.check_circuit_breaker("key1").await);
+
+        let gemini_config: GeminiConfig = serde_yaml::from_str("api_keys: []").unwrap();
+        let service = GeminiProxyService::new(
+            Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")])),
+            Arc::new(AuthHandler::new(String::new(), 60)),
+            Arc::new(MetricsCollector::new()),
+            Arc::new(LiveConfig::new(gemini_config)),
+        )
+        .with_recovery_manager(recovery_manager.clone());
+        let mut ctx = service.new_ctx();
+        ctx.api_key_id = Some("key1".to_string());
+        (service, recovery_manager, ctx)
+    }
+
+    /// 通过内存管道读取客户端发送的请求头，请求体留给代理读取
+    async fn h1_session(head: &str, body: &[u8]) -> (Session, tokio::io::DuplexStream) {
+        use tokio::io::AsyncWriteExt;
+
+        let (mut client, server) = tokio::io::duplex(head.len() + body.len() + 1024);
+        client.write_all(head.as_bytes()).await.unwrap();
+        client.write_all(body).await.unwrap();
+        let mut session = Session::new_h1(Box::new(server));
+        assert!(session.read_request().await.unwrap());
+        (session, client)
+    }
+
+    fn post_with_body(len: usize) -> String {
+        format!(
+            "POST /v1beta/models/gemini-1.5-pro:generateContent HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+            len
+        )
+    }
+
+    #[tokio::test]
+    async fn test_mirror_buffering_keeps_body_forwardable() {
+        let service = GeminiProxyService::new(
+            Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")])),
+            Arc::new(AuthHandler::new(String::new(), 60)),
+            Arc::new(MetricsCollector::new()),
+            Arc::new(LiveConfig::new(serde_yaml::from_str("api_keys: []").unwrap())),
         );
+
+        // 重试缓冲之内：读取的请求体仍可由 Pingora 从重试缓冲转发给上游
+        let body = vec![b'a'; MAX_MIRROR_BODY_BYTES];
+        let (mut session, _client) = h1_session(&post_with_body(body.len()), &body).await;
+        let mut ctx = service.new_ctx();
+        let buffered = buffer_request_body(&mut session, &mut ctx, MAX_MIRROR_BODY_BYTES).await.unwrap();
+        assert_eq!(buffered.as_deref(), Some(body.as_slice()));
+        assert_eq!(session.as_ref().get_retry_buffer().as_deref(), Some(body.as_slice()));
+
+        // 超过 64 KiB：不提前读取，请求体照常流式转发
+        let body = vec![b'a'; 64 * 1024 + 1];
+        let (mut session, _client) = h1_session(&post_with_body(body.len()), &body).await;
+        let mut ctx = service.new_ctx();
+        assert!(buffer_request_body(&mut session, &mut ctx, MAX_MIRROR_BODY_BYTES).await.unwrap().is_none());
+        assert!(!session.is_body_done());
+        let mut forwarded = Vec::new();
+        while let Some(chunk) = session.read_request_body().await.unwrap() {
+            forwarded.extend_from_slice(&chunk);
+        }
+        assert_eq!(forwarded, body);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_skips_body_above_retry_buffer() {
+        let config: GeminiConfig = serde_yaml::from_str("api_keys: []\ndedup:\n  enabled: true").unwrap();
+        let service = GeminiProxyService::new(
+            Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")])),
+            Arc::new(AuthHandler::new(String::new(), 60)),
+            Arc::new(MetricsCollector::new()),
+            Arc::new(LiveConfig::new(config.clone())),
+        )
+        .with_dedup_window(Arc::new(DedupWindow::new(&config.dedup)));
+
+        // 上限之内按请求体哈希去重，请求体保留在重试缓冲中转发
+        let body = vec![b'a'; MAX_DEDUP_BODY_BYTES];
+        let (mut session, _client) = h1_session(&post_with_body(body.len()), &body).await;
+        let mut ctx = service.new_ctx();
+        assert!(!service.lookup_dedup(&mut session, &mut ctx).await.unwrap());
+        assert!(ctx.dedup_ticket.is_some());
+        assert_eq!(session.as_ref().get_retry_buffer().as_deref(), Some(body.as_slice()));
+
+        // 刚超过 64 KiB：不参与去重，也不提前读取请求体
+        let body = vec![b'a'; 64 * 1024 + 1];
+        let (mut session, _client) = h1_session(&post_with_body(body.len()), &body).await;
+        let mut ctx = service.new_ctx();
+        assert!(!service.lookup_dedup(&mut session, &mut ctx).await.unwrap());
+        assert!(ctx.dedup_ticket.is_none());
+        assert!(!session.is_body_done());
+    }
+
+    #[tokio::test]
+    async fn test_connect_error_on_half_open_key_reopens_circuit_once() {
+        let (service, recovery_manager, mut ctx) = half_open_service().await;
+
+        service.report_connect_failure(&mut ctx).unwrap().await.unwrap();
+        let snapshot = recovery_manager.circuit_snapshot("key1").await.unwrap();
+        assert_eq!(snapshot.state, CircuitBreakerState::Open);
+        assert_eq!(snapshot.failure_count, 2);
+
+        // 随后的 fail_to_proxy 不重复计入这次连接失败
+        service.settle_key_after_error(&mut ctx, true).await;
+        assert_eq!(recovery_manager.circuit_snapshot("key1").await.unwrap().failure_count, 2);
+        // 探测名额随熔断器重新打开而释放，恢复时间为 0 时下一个请求可以再次探测
+        assert!(recovery_manager.check_circuit_breaker("key1").await);
+    }
+
+    #[tokio::test]
+    async fn test_client_caused_error_releases_half_open_slot() {
+        let (service, recovery_manager, mut ctx) = half_open_service().await;
+
+        service.settle_key_after_error(&mut ctx, false).await;
+        let snapshot = recovery_manager.circuit_snapshot("key1").await.unwrap();
+        assert_eq!(snapshot.state, CircuitBreakerState::HalfOpen);
+        assert_eq!(snapshot.failure_count, 1);
+        assert!(recovery_manager.check_circuit_breaker("key1").await);
+    }
+
+    #[tokio::test]
+    async fn test_client_error_status_is_not_a_breaker_failure() {
+        let (service, recovery_manager, mut ctx) = half_open_service().await;
+
+        for status in [400, 404, 422] {
+            let mut response_header = ResponseHeader::build(status, None).unwrap();
+            service.filter_response(&mut response_header, &mut ctx).await.unwrap();
+            // 熔断器保持半开，探测名额归还给下一个请求
+            let snapshot = recovery_manager.circuit_snapshot("key1").await.unwrap();
+            assert_eq!(snapshot.state, CircuitBreakerState::HalfOpen);
+            assert_eq!(snapshot.failure_count, 1);
+            assert!(recovery_manager.check_circuit_breaker("key1").await);
+            // 随后的 fail_to_proxy 不再结算这次尝试
+            service.settle_key_after_error(&mut ctx, true).await;
+            assert_eq!(recovery_manager.circuit_snapshot("key1").await.unwrap().failure_count, 1);
+        }
+
+        let mut response_header = ResponseHeader::build(503, None).unwrap();
+        service.filter_response(&mut response_header, &mut ctx).await.unwrap();
+        let snapshot = recovery_manager.circuit_snapshot("key1").await.unwrap();
+        assert_eq!(snapshot.state, CircuitBreakerState::Open);
+        assert_eq!(snapshot.failure_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_soft_failure_is_reported_to_breaker_once() {
+        let recovery_manager = Arc::new(create_default_recovery_manager().with_circuit_breaker(5, Duration::from_secs(60), 1));
+        for _ in 0..2 {
+            recovery_manager.report_operation_result("key1", false).await;
+        }
+        let service = GeminiProxyService::new(
+            Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")])),
+            Arc::new(AuthHandler::new(String::new(), 60)),
+            Arc::new(MetricsCollector::new()),
+            Arc::new(LiveConfig::new(serde_yaml::from_str("api_keys: []").unwrap())),
+        )
+        .with_recovery_manager(recovery_manager.clone());
+        let mut ctx = service.new_ctx();
+        ctx.api_key_id = Some("key1".to_string());
+
+        // 成功响应头不立即计为成功，软失败在请求结束时只计一次失败
+        let mut response_header = ResponseHeader::build(200, None).unwrap();
+        service.filter_response(&mut response_header, &mut ctx).await.unwrap();
+        assert_eq!(recovery_manager.circuit_snapshot("key1").await.unwrap().failure_count, 2);
+        ctx.soft_failure = Some(SoftFailure::ErrorBody);
+        service.settle_key_after_success(&mut ctx, true).await;
+        service.settle_key_after_success(&mut ctx, true).await;
+        assert_eq!(recovery_manager.circuit_snapshot("key1").await.unwrap().failure_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_releases_in_flight_and_key() {
+        let secret = "a-valid-secret-that-is-long-enough-1234";
+        let metrics = Arc::new(MetricsCollector::new());
+        let inflight_limiter = Arc::new(InflightLimiter::new(1));
+        // key1 的熔断器到达恢复时间，下一个请求占用半开探测名额
+        let recovery_manager = Arc::new(create_default_recovery_manager().with_circuit_breaker(1, Duration::from_millis(0), 1));
+        recovery_manager.report_operation_result("key1", false).await;
+        let service = GeminiProxyService::new(
+            Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")])),
+            Arc::new(AuthHandler::new(secret.to_string(), 60)),
+            metrics.clone(),
+            Arc::new(LiveConfig::new(serde_yaml::from_str("api_keys: []").unwrap())),
+        )
+        .with_recovery_manager(recovery_manager.clone())
+        .with_inflight_limiter(inflight_limiter.clone());
+
+        let claims = serde_json::json!({ "sub": "client", "exp": Utc::now().timestamp() + 3600 });
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_ref()),
+        )
+        .unwrap();
+        let head = format!(
+            "POST /v1beta/models/gemini-1.5-pro:generateContent HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\nContent-Length: 64\r\n\r\n",
+            token
+        );
+        let (mut session, client) = h1_session(&head, b"{\"contents\"").await;
+        let mut ctx = service.new_ctx();
+        assert!(!service.request_filter(&mut session, &mut ctx).await.unwrap());
+        assert_eq!(ctx.api_key_id.as_deref(), Some("key1"));
+        assert_eq!(metrics.get_in_flight(), 1);
+        assert_eq!(inflight_limiter.in_flight(), 1);
+        assert!(!recovery_manager.check_circuit_breaker("key1").await);
+
+        // 客户端在请求体发送完之前断开，Pingora 按下游错误中止请求
+        drop(client);
+        let error = loop {
+            match session.read_request_body().await {
+                Ok(Some(_)) => continue,
+                Ok(None) => panic!("请求体不完整时不应正常结束"),
+                Err(e) => break e.into_down(),
+            }
+        };
+        assert!(is_client_disconnect(&error));
+        let outcome = service.fail_to_proxy(&mut session, &error, &mut ctx).await;
+        assert_eq!(outcome.error_code, 0);
+        service.logging(&mut session, Some(&error), &mut ctx).await;
+        drop(ctx);
+
+        assert_eq!(metrics.get_in_flight(), 0);
+        assert_eq!(metrics.get_client_cancelled(), 1);
+        assert_eq!(inflight_limiter.in_flight(), 0);
+        // 断开不计为密钥失败，半开探测名额归还
+        let snapshot = recovery_manager.circuit_snapshot("key1").await.unwrap();
+        assert_eq!(snapshot.state, CircuitBreakerState::HalfOpen);
+        assert_eq!(snapshot.failure_count, 1);
+        assert!(recovery_manager.check_circuit_breaker("key1").await);
+    }
+
+    #[tokio::test]
+    async fn test_over_limit_chunked_post_is_not_a_key_failure() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let metrics = Arc::new(MetricsCollector::new());
+        let recovery_manager = Arc::new(create_default_recovery_manager().with_circuit_breaker(1, Duration::from_secs(60), 1));
+        let gemini_config: GeminiConfig = serde_yaml::from_str("api_keys: []").unwrap();
+        let service = GeminiProxyService::new(
+            Arc::new(UnifiedKeyManager::new(vec![ApiKey::for_test("key1")])),
+            Arc::new(AuthHandler::new(String::new(), 60)),
+            metrics.clone(),
+            Arc::new(LiveConfig::new(gemini_config)),
+        )
+        .with_recovery_manager(recovery_manager.clone())
+        .with_body_limits(BodyLimits { max_request_bytes: 16, max_response_buffer_bytes: 1024 });
+
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        client
+            .write_all(b"POST /v1beta/models/gemini-1.5-pro:generateContent HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n")
+            .await
+            .unwrap();
+        let mut session = Session::new_h1(Box::new(server));
+        assert!(session.read_request().await.unwrap());
+
+        // 已选好密钥并开始上游尝试，请求体转发途中超过上限
+        let mut ctx = service.new_ctx();
+        ctx.api_key_id = Some("key1".to_string());
+        ctx.attempts.begin("key1");
+        ctx.upstream_span = Some(TimedSpan::upstream_request(&ctx.request_span, "key1"));
+        let mut chunk = Some(Bytes::from(vec![b'a'; 32]));
+        let error = service.request_body_filter(&mut session, &mut chunk, false, &mut ctx).await.unwrap_err();
+        assert_eq!(error.etype(), &ErrorType::HTTPStatus(413));
+        let outcome = service.fail_to_proxy(&mut session, &error, &mut ctx).await;
+        assert_eq!(outcome.error_code, 413);
+
+        let mut response = vec![0; 1024];
+        let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut response)).await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&response[..read]).starts_with("HTTP/1.1 413"));
+        // 密钥没有上游调用与失败记录，熔断器不受影响
+        assert_eq!(metrics.get_key_upstream_calls("key1", "HTTPStatus"), (0, 0));
+        assert!(recovery_manager.circuit_snapshot("key1").await.is_none());
     }
 }
@@ -0,0 +1,106 @@
+// src/proxy/shadow.rs
+//! 影子密钥流量镜像
+//!
+//! 通过 API 新增并指定了预热期的密钥在预热期内不参与真实选择。真实请求成功后，代理在后台以相同的方法、
+//! 路径与请求体用每个影子密钥再请求一次上游，响应直接丢弃，只把成功与否记录到密钥的预热状态中；
+//! 镜像请求失败会重新开始预热，因此有问题的密钥不会服务客户端。镜像请求使用与转发相同的上游 peer，
+//! 启用连接预热时同样优先取用预热连接
+
+use crate::load_balancer::key_manager::ApiKey;
+use crate::load_balancer::UnifiedKeyManager;
+use bytes::Bytes;
+use pingora::connectors::http::Connector;
+use pingora::http::RequestHeader;
+use pingora::upstreams::peer::HttpPeer;
+use pingora_error::Result;
+use std::sync::Arc;
+
+/// 镜像请求体上限，超出（或未声明长度）的请求不镜像；不能超过 Pingora 的重试缓冲，
+/// 否则提前读取的请求体无法再转发给上游
+pub const MAX_MIRROR_BODY_BYTES: usize = 64 * 1024;
+
+/// 待镜像的请求
+#[derive(Debug, Clone)]
+pub struct MirroredRequest {
+    pub method: String,
+    pub path_and_query: String,
+    pub host: String,
+    pub body: Bytes,
+}
+
+impl MirroredRequest {
+    /// 构造发往影子密钥的请求头
+    pub fn request_header(&self, api_key: &str) -> Result<RequestHeader> {
+        let mut header = RequestHeader::build(self.method.as_str(), self.path_and_query.as_bytes(), Some(4))?;
+        header.insert_header("host", &self.host)?;
+        header.insert_header("x-goog-api-key", api_key)?;
+        header.insert_header("content-type", "application/json")?;
+        header.insert_header("content-length", self.body.len().to_string())?;
+        Ok(header)
+    }
+}
+
+/// 在后台把请求镜像给各影子密钥，并记录结果
+pub fn mirror_to_shadow_keys(
+    connector: Arc<Connector>,
+    key_manager: Arc<UnifiedKeyManager>,
+    peer: HttpPeer,
+    request: MirroredRequest,
+    shadow_keys: Vec<ApiKey>,
+) {
+    for key in shadow_keys {
+        let connector = connector.clone();
+        let key_manager = key_manager.clone();
+        let peer = peer.clone();
+        let request = request.clone();
+        tokio::spawn(async move {
+            let success = match send_mirrored_request(&connector, &peer, &request, &key.key).await {
+                Ok(status) => (200..300).contains(&status),
+                Err(e) => {
+                    tracing::warn!(key_id = %key.id, "影子密钥镜像请求失败: {}", e);
+                    false
+                }
+            };
+            tracing::debug!(key_id = %key.id, success, "影子密钥镜像请求完成");
+            key_manager.record_shadow_result(&key.id, success).await;
+        });
+    }
+}
+
+/// 发送镜像请求并丢弃响应体，返回上游状态码
+async fn send_mirrored_request(
+    connector: &Connector,
+    peer: &HttpPeer,
+    request: &MirroredRequest,
+    api_key: &str,
+) -> Result<u16> {
+    let (mut session, _reused) = connector.get_http_session(peer).await?;
+    session.write_request_header(Box::new(request.request_header(api_key)?)).await?;
+    session.write_request_body(request.body.clone(), true).await?;
+    session.finish_request_body().await?;
+    session.read_response_header().await?;
+    let status = session.response_header().map_or(0, |header| header.status.as_u16());
+    while session.read_response_body().await?.is_some() {}
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirrored_request_uses_shadow_key() {
+        let request = MirroredRequest {
+            method: "POST".to_string(),
+            path_and_query: "/v1beta/models/gemini-1.5-pro:generateContent".to_string(),
+            host: "generativelanguage.googleapis.com".to_string(),
+            body: Bytes::from_static(br#"{"contents":[]}"#),
+        };
+
+        let header = request.request_header("shadow-secret").unwrap();
+        assert_eq!(header.method, "POST");
+        assert_eq!(header.uri.path(), "/v1beta/models/gemini-1.5-pro:generateContent");
+        assert_eq!(header.headers.get("x-goog-api-key").unwrap(), "shadow-secret");
+        assert_eq!(header.headers.get("content-length").unwrap(), "15");
+    }
+}
@@ -0,0 +1,162 @@
+// src/proxy/shed.rs
+//! 卸载（拒绝请求）原因与响应
+//!
+//! 代理主动拒绝请求时，状态码决定了客户端的重试行为：配额/速率类原因默认返回
+//! 429 + Retry-After，故障类原因默认返回 503。每个原因的状态码与 Retry-After 均可配置，
+//! 响应体统一使用 [`error_body`] 的结构化格式并附带 `reason` 字段
+
+use crate::config::{ShedResponse, ShedResponsesConfig};
+use crate::error::GeminiProxyError;
+use crate::proxy::circuit_guard::error_body;
+use bytes::Bytes;
+use pingora::http::ResponseHeader;
+use pingora_error::Result;
+
+/// 卸载原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShedReason {
+    /// 客户端超过速率限制
+    RateLimited,
+    /// 所有密钥都已达到每分钟限额
+    QuotaExhausted,
+    /// 所有密钥的熔断器均已打开
+    CircuitOpen,
+    /// 没有可用的密钥
+    NoKeyAvailable,
+    /// 没有密钥允许服务请求的模型
+    ModelUnsupported,
+    /// 全进程在途请求数达到上限
+    Overloaded,
+    /// 正在停机，不再接受新请求
+    Draining,
+}
+
+impl ShedReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RateLimited => "rate_limited",
+            Self::QuotaExhausted => "quota_exhausted",
+            Self::CircuitOpen => "circuit_open",
+            Self::NoKeyAvailable => "no_key_available",
+            Self::ModelUnsupported => "model_unsupported",
+            Self::Overloaded => "overloaded",
+            Self::Draining => "draining",
+        }
+    }
+
+    /// 该原因对应的响应配置
+    pub fn response(&self, config: &ShedResponsesConfig) -> ShedResponse {
+        match self {
+            Self::RateLimited => config.rate_limited,
+            Self::QuotaExhausted => config.quota_exhausted,
+            Self::CircuitOpen => config.circuit_open,
+            Self::NoKeyAvailable => config.no_key_available,
+            Self::ModelUnsupported => config.model_unsupported,
+            Self::Overloaded => config.overloaded,
+            Self::Draining => config.draining,
+        }
+    }
+}
+
+/// 被卸载的请求：原因与对应的错误
+#[derive(Debug)]
+pub struct Shed {
+    pub reason: ShedReason,
+    pub error: GeminiProxyError,
+}
+
+impl Shed {
+    pub fn new(reason: ShedReason, error: GeminiProxyError) -> Self {
+        Self { reason, error }
+    }
+}
+
+/// 按配置构建卸载响应
+pub fn shed_response(config: &ShedResponsesConfig, shed: &Shed) -> Result<(ResponseHeader, Bytes)> {
+    let response = shed.reason.response(config);
+
+    let mut body = error_body(response.status, &shed.error);
+    body["error"]["reason"] = serde_json::Value::from(shed.reason.as_str());
+    let body = Bytes::from(body.to_string());
+
+    let mut header = ResponseHeader::build(response.status, Some(4))?;
+    header.insert_header("content-type", "application/json")?;
+    header.insert_header("content-length", body.len().to_string())?;
+    if let Some(retry_after) = response.retry_after_seconds {
+        header.insert_header("retry-after", retry_after.to_string())?;
+    }
+    Ok((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shed(reason: ShedReason) -> Shed {
+        let error = match reason {
+            ShedReason::RateLimited | ShedReason::QuotaExhausted => GeminiProxyError::rate_limit("limited"),
+            ShedReason::CircuitOpen
+            | ShedReason::NoKeyAvailable
+            | ShedReason::ModelUnsupported
+            | ShedReason::Overloaded
+            | ShedReason::Draining => {
+                GeminiProxyError::load_balancer("outage")
+            }
+        };
+        Shed::new(reason, error)
+    }
+
+    fn parse(body: &Bytes) -> serde_json::Value {
+        serde_json::from_slice(body).unwrap()
+    }
+
+    #[test]
+    fn test_default_status_per_reason() {
+        let config = ShedResponsesConfig::default();
+        let cases = [
+            (ShedReason::RateLimited, 429, Some("60")),
+            (ShedReason::QuotaExhausted, 429, Some("60")),
+            (ShedReason::CircuitOpen, 503, Some("60")),
+            (ShedReason::NoKeyAvailable, 503, None),
+            (ShedReason::ModelUnsupported, 503, None),
+            (ShedReason::Overloaded, 503, Some("1")),
+            (ShedReason::Draining, 503, Some("1")),
+        ];
+
+        for (reason, status, retry_after) in cases {
+            let (header, body) = shed_response(&config, &shed(reason)).unwrap();
+            assert_eq!(header.status.as_u16(), status, "{:?}", reason);
+            assert_eq!(
+                header.headers.get("retry-after").map(|v| v.to_str().unwrap()),
+                retry_after,
+                "{:?}",
+                reason
+            );
+            assert_eq!(header.headers.get("content-type").unwrap(), "application/json");
+
+            let body = parse(&body);
+            assert_eq!(body["error"]["code"], status);
+            assert_eq!(body["error"]["reason"], reason.as_str());
+            assert!(body["error"]["message"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_configured_status_per_reason() {
+        let config = ShedResponsesConfig {
+            quota_exhausted: ShedResponse { status: 503, retry_after_seconds: Some(5) },
+            no_key_available: ShedResponse { status: 429, retry_after_seconds: Some(30) },
+            ..ShedResponsesConfig::default()
+        };
+
+        let (header, body) = shed_response(&config, &shed(ShedReason::QuotaExhausted)).unwrap();
+        assert_eq!(header.status.as_u16(), 503);
+        assert_eq!(header.headers.get("retry-after").unwrap(), "5");
+        assert_eq!(parse(&body)["error"]["type"], "RateLimit");
+
+        let (header, body) = shed_response(&config, &shed(ShedReason::NoKeyAvailable)).unwrap();
+        assert_eq!(header.status.as_u16(), 429);
+        assert_eq!(header.headers.get("retry-after").unwrap(), "30");
+        assert_eq!(parse(&body)["error"]["reason"], "no_key_available");
+    }
+}
@@ -0,0 +1,154 @@
+// src/proxy/soft_failure.rs
+//! 上游软失败检测
+//!
+//! Gemini 偶尔返回 200，但响应体为空、是错误结构（顶层 `error`）或提示词被拦截
+//! （`promptFeedback.blockReason`）。这类响应不应计为成功：密钥按失败记录（后续请求与客户端重试会换用其他密钥），
+//! 并计入 `gemini_upstream_soft_failure_total` 指标。响应头在读取响应体之前已发送，因此只有
+//! `content-length: 0` 的空响应可以在转发前改写为可重试的 502；其余软失败仍原样返回给客户端
+
+use crate::config::SoftFailureConfig;
+use crate::error::GeminiProxyError;
+use crate::proxy::circuit_guard::error_body;
+use crate::proxy::usage::UpstreamBody;
+use bytes::Bytes;
+use pingora::http::ResponseHeader;
+use pingora_error::Result;
+
+/// 软失败类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftFailure {
+    /// 响应体为空
+    EmptyBody,
+    /// 响应体为错误结构
+    ErrorBody,
+    /// 提示词被安全策略拦截
+    Blocked,
+}
+
+impl SoftFailure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::EmptyBody => "empty_body",
+            Self::ErrorBody => "error_body",
+            Self::Blocked => "blocked",
+        }
+    }
+}
+
+/// 检查 200 响应体是否为软失败（未启用时总是返回 None）
+pub fn detect_soft_failure(config: &SoftFailureConfig, body: &UpstreamBody) -> Option<SoftFailure> {
+    if !config.enabled {
+        return None;
+    }
+    match body {
+        UpstreamBody::Empty => Some(SoftFailure::EmptyBody),
+        UpstreamBody::Json(serde_json::Value::Array(items)) => detect_in_values(config, items),
+        UpstreamBody::Json(value) => detect_in_values(config, std::slice::from_ref(value)),
+        UpstreamBody::EventStream(events) => detect_in_values(config, events),
+        // 无法解析的响应体已由用量解析单独计数
+        UpstreamBody::Malformed(_) => None,
+    }
+}
+
+fn detect_in_values(config: &SoftFailureConfig, values: &[serde_json::Value]) -> Option<SoftFailure> {
+    if values.is_empty() {
+        return Some(SoftFailure::EmptyBody);
+    }
+    if values.iter().any(|value| value.get("error").is_some_and(|e| e.is_object())) {
+        return Some(SoftFailure::ErrorBody);
+    }
+    let blocked = values
+        .iter()
+        .any(|value| value.pointer("/promptFeedback/blockReason").is_some_and(|r| !r.is_null()));
+    if config.detect_blocked && blocked {
+        return Some(SoftFailure::Blocked);
+    }
+    None
+}
+
+/// 不把软失败返回给客户端时，将声明为空响应体的 200 改写为 502
+///
+/// 返回替换的响应体；未启用、仍返回给客户端或响应体非空时响应保持不变
+pub fn rewrite_empty_response(
+    config: &SoftFailureConfig,
+    request_id: &str,
+    response_header: &mut ResponseHeader,
+) -> Result<Option<Bytes>> {
+    let empty = response_header
+        .headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim() == "0");
+    if !config.enabled || config.return_to_client || response_header.status.as_u16() != 200 || !empty {
+        return Ok(None);
+    }
+
+    let error = GeminiProxyError::network("上游返回了空响应体")
+        .with_request_id(request_id.to_string())
+        .with_retryable(true);
+    let body = Bytes::from(error_body(502, &error).to_string());
+    response_header.set_status(502)?;
+    response_header.remove_header("transfer-encoding");
+    response_header.insert_header("content-type", "application/json")?;
+    response_header.insert_header("content-length", body.len().to_string())?;
+    Ok(Some(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::usage::parse_upstream_body;
+
+    fn enabled_config() -> SoftFailureConfig {
+        SoftFailureConfig {
+            enabled: true,
+            ..SoftFailureConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_detect_soft_failure_distinguishes_normal_200() {
+        let config = enabled_config();
+        let normal = br#"{"candidates":[{"content":{"parts":[{"text":"hi"}]}}]}"#;
+        assert_eq!(detect_soft_failure(&config, &parse_upstream_body(normal)), None);
+        assert_eq!(detect_soft_failure(&config, &parse_upstream_body(b"")), Some(SoftFailure::EmptyBody));
+        assert_eq!(detect_soft_failure(&config, &parse_upstream_body(b"[]")), Some(SoftFailure::EmptyBody));
+        assert_eq!(
+            detect_soft_failure(&config, &parse_upstream_body(br#"{"error":{"code":500,"message":"internal"}}"#)),
+            Some(SoftFailure::ErrorBody)
+        );
+
+        let blocked = br#"{"promptFeedback":{"blockReason":"SAFETY"}}"#;
+        assert_eq!(detect_soft_failure(&config, &parse_upstream_body(blocked)), Some(SoftFailure::Blocked));
+        let lenient = SoftFailureConfig { detect_blocked: false, ..enabled_config() };
+        assert_eq!(detect_soft_failure(&lenient, &parse_upstream_body(blocked)), None);
+
+        assert_eq!(detect_soft_failure(&SoftFailureConfig::default(), &parse_upstream_body(b"")), None);
+    }
+
+    #[test]
+    fn test_empty_response_rewritten_only_when_not_returned_to_client() {
+        let empty_header = || {
+            let mut header = ResponseHeader::build(200, None).unwrap();
+            header.insert_header("content-length", "0").unwrap();
+            header
+        };
+
+        // 默认仍原样返回给客户端
+        let mut header = empty_header();
+        assert!(rewrite_empty_response(&enabled_config(), "req-1", &mut header).unwrap().is_none());
+        assert_eq!(header.status.as_u16(), 200);
+
+        let config = SoftFailureConfig { return_to_client: false, ..enabled_config() };
+        let mut header = empty_header();
+        let body = rewrite_empty_response(&config, "req-1", &mut header).unwrap().unwrap();
+        assert_eq!(header.status.as_u16(), 502);
+        assert_eq!(header.headers.get("content-length").unwrap(), body.len().to_string().as_str());
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["request_id"], "req-1");
+
+        let mut header = ResponseHeader::build(200, None).unwrap();
+        header.insert_header("content-length", "42").unwrap();
+        assert!(rewrite_empty_response(&config, "req-1", &mut header).unwrap().is_none());
+    }
+}
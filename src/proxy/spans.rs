@@ -0,0 +1,191 @@
+// src/proxy/spans.rs
+//! 请求级追踪 span
+//!
+//! 每个请求创建 `proxy_request` span，其下嵌套 `select_key`（密钥选择）、`upstream_request`
+//! （从选中密钥到收到上游响应头）与 `response_filter`（响应处理）三个 span，用于区分选择与上游耗时。
+//! span 结束时记录 `elapsed_us` 字段，由 tracing 订阅者输出；接入 OTLP 等导出层后同样会被导出
+
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::Span;
+
+pub const REQUEST_SPAN: &str = "proxy_request";
+pub const SELECT_KEY_SPAN: &str = "select_key";
+pub const UPSTREAM_REQUEST_SPAN: &str = "upstream_request";
+pub const RESPONSE_FILTER_SPAN: &str = "response_filter";
+
+/// 请求根 span
+pub fn request_span(request_id: &str) -> Span {
+    tracing::info_span!(REQUEST_SPAN, request_id = %request_id)
+}
+
+/// 结束时记录耗时的 span
+pub struct TimedSpan {
+    span: Span,
+    started: Instant,
+}
+
+impl TimedSpan {
+    /// 密钥选择 span，选择完成后记录 `key_id`
+    pub fn select_key(parent: &Span) -> Self {
+        Self::new(tracing::info_span!(parent: parent, SELECT_KEY_SPAN, key_id = Empty, elapsed_us = Empty))
+    }
+
+    /// 上游请求 span，收到响应头后记录 `status`，失败时记录 `error`
+    pub fn upstream_request(parent: &Span, key_id: &str) -> Self {
+        Self::new(tracing::info_span!(
+            parent: parent,
+            UPSTREAM_REQUEST_SPAN,
+            key_id = %key_id,
+            status = Empty,
+            error = Empty,
+            elapsed_us = Empty
+        ))
+    }
+
+    /// 响应处理 span
+    pub fn response_filter(parent: &Span) -> Self {
+        Self::new(tracing::info_span!(parent: parent, RESPONSE_FILTER_SPAN, status = Empty, elapsed_us = Empty))
+    }
+
+    fn new(span: Span) -> Self {
+        Self {
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// 记录字段
+    pub fn record<V: tracing::Value>(&self, field: &str, value: V) {
+        self.span.record(field, value);
+    }
+
+    /// 记录耗时并结束 span，返回 span 的存活时长
+    pub fn finish(self) -> Duration {
+        let elapsed = self.started.elapsed();
+        let elapsed_us = elapsed.as_micros().max(1) as u64;
+        self.span.record("elapsed_us", elapsed_us);
+        elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Instrument;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    /// 已结束的 span：名称、父 span 名称、实际存活时长与记录的耗时字段
+    #[derive(Debug, Clone)]
+    struct ClosedSpan {
+        name: &'static str,
+        parent: Option<&'static str>,
+        lifetime: Duration,
+        elapsed_us: Option<u64>,
+    }
+
+    struct SpanTiming {
+        opened: Instant,
+        elapsed_us: Option<u64>,
+    }
+
+    #[derive(Default)]
+    struct ElapsedVisitor(Option<u64>);
+
+    impl tracing::field::Visit for ElapsedVisitor {
+        fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+            if field.name() == "elapsed_us" {
+                self.0 = Some(value);
+            }
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingLayer {
+        closed: Arc<Mutex<Vec<ClosedSpan>>>,
+    }
+
+    impl<S: tracing::Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RecordingLayer {
+        fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            span.extensions_mut().insert(SpanTiming {
+                opened: Instant::now(),
+                elapsed_us: None,
+            });
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            let mut visitor = ElapsedVisitor::default();
+            values.record(&mut visitor);
+            if let Some(elapsed_us) = visitor.0 {
+                let span = ctx.span(id).unwrap();
+                if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                    timing.elapsed_us = Some(elapsed_us);
+                }
+            }
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let extensions = span.extensions();
+            let timing = extensions.get::<SpanTiming>().unwrap();
+            self.closed.lock().unwrap().push(ClosedSpan {
+                name: span.name(),
+                parent: span.parent().map(|p| p.name()),
+                lifetime: timing.opened.elapsed(),
+                elapsed_us: timing.elapsed_us,
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nested_spans_with_durations() {
+        let layer = RecordingLayer::default();
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        // 按代理服务中的顺序创建 span
+        let request = request_span("req-1");
+        let select = TimedSpan::select_key(&request);
+        async { tokio::time::sleep(Duration::from_millis(2)).await }
+            .instrument(select.span().clone())
+            .await;
+        select.record("key_id", "key1");
+        select.finish();
+
+        let upstream = TimedSpan::upstream_request(&request, "key1");
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        upstream.record("status", 200u64);
+        upstream.finish();
+
+        let response = TimedSpan::response_filter(&request);
+        async { tokio::time::sleep(Duration::from_millis(1)).await }
+            .instrument(response.span().clone())
+            .await;
+        response.finish();
+        drop(request);
+
+        let closed = layer.closed.lock().unwrap().clone();
+        let by_name: HashMap<_, _> = closed.iter().map(|s| (s.name, s)).collect();
+        assert_eq!(closed.len(), 4);
+        for name in [SELECT_KEY_SPAN, UPSTREAM_REQUEST_SPAN, RESPONSE_FILTER_SPAN] {
+            let span = by_name[name];
+            assert_eq!(span.parent, Some(REQUEST_SPAN), "{}", name);
+            assert!(span.lifetime > Duration::ZERO, "{}", name);
+            assert!(span.elapsed_us.is_some_and(|us| us >= 1000), "{:?}", span);
+        }
+        assert!(by_name[REQUEST_SPAN].lifetime >= by_name[UPSTREAM_REQUEST_SPAN].lifetime);
+    }
+}
@@ -0,0 +1,174 @@
+// src/proxy/streaming.rs
+//! 流式（SSE）响应透传
+//!
+//! `:streamGenerateContent` 或 `alt=sse` 请求的上游响应逐块转发给客户端，不做缓冲或改写。响应头中移除
+//! `content-length` 并声明禁止中间层缓冲；上游在流中途断开时，向客户端追加一条 `event: error` 事件，
+//! 内容为 `GeminiProxyError::Network` 的结构化错误，避免客户端把截断的流当作正常结束。
+//!
+//! 配置 `streaming_buffer` 后，小数据块先合并暂存，累积到阈值或暂存超时后再转发。Pingora 只在收到
+//! 上游数据块时调用响应体过滤器，暂存时间因此在下一个数据块到达时检查
+
+use crate::config::StreamingBufferConfig;
+use crate::error::GeminiProxyError;
+use crate::proxy::circuit_guard::error_body;
+use bytes::Bytes;
+use pingora::http::ResponseHeader;
+use pingora_error::{Error, ErrorSource, Result};
+use std::time::{Duration, Instant};
+
+/// 响应是否为 SSE 事件流
+pub fn is_event_stream(response_header: &ResponseHeader) -> bool {
+    response_header
+        .headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_ascii_lowercase().starts_with("text/event-stream"))
+}
+
+/// 为透传的流式响应调整响应头：逐块转发，禁止客户端与中间层缓存或缓冲
+pub fn prepare_streaming_response(response_header: &mut ResponseHeader) -> Result<()> {
+    response_header.remove_header("content-length");
+    response_header.insert_header("cache-control", "no-cache")?;
+    response_header.insert_header("x-accel-buffering", "no")?;
+    Ok(())
+}
+
+/// 流式响应的数据块合并器
+#[derive(Debug)]
+pub struct StreamCoalescer {
+    buffer: Vec<u8>,
+    /// 当前暂存的最早数据块到达的时间
+    held_since: Option<Instant>,
+    buffer_bytes: usize,
+    max_hold: Duration,
+}
+
+impl StreamCoalescer {
+    /// 按配置创建合并器，未启用合并时返回 None
+    pub fn from_config(config: &StreamingBufferConfig) -> Option<Self> {
+        (config.buffer_bytes > 0).then(|| Self {
+            buffer: Vec::with_capacity(config.buffer_bytes),
+            held_since: None,
+            buffer_bytes: config.buffer_bytes,
+            max_hold: Duration::from_millis(config.max_hold_ms),
+        })
+    }
+
+    /// 处理一个上游数据块，返回本次应转发给客户端的内容（None 表示继续暂存）
+    pub fn push(&mut self, chunk: Option<Bytes>, end_of_stream: bool, now: Instant) -> Option<Bytes> {
+        if let Some(chunk) = chunk.filter(|c| !c.is_empty()) {
+            self.buffer.extend_from_slice(&chunk);
+            self.held_since.get_or_insert(now);
+        }
+        let hold_expired = self.held_since.is_some_and(|since| now.saturating_duration_since(since) >= self.max_hold);
+        if end_of_stream || self.buffer.len() >= self.buffer_bytes || hold_expired {
+            self.take_pending()
+        } else {
+            None
+        }
+    }
+
+    /// 取出全部暂存内容（流中途中断时在错误事件之前转发）
+    pub fn take_pending(&mut self) -> Option<Bytes> {
+        self.held_since = None;
+        (!self.buffer.is_empty()).then(|| Bytes::from(std::mem::take(&mut self.buffer)))
+    }
+}
+
+/// 流式响应是否因上游原因中途中断（客户端断开不算）
+pub fn is_upstream_interruption(e: &Error) -> bool {
+    !matches!(e.esource(), ErrorSource::Downstream)
+}
+
+/// 流中途中断时追加给客户端的 SSE 错误事件
+pub fn stream_interrupted_event(e: &Error, request_id: &str) -> (GeminiProxyError, Bytes) {
+    let error = GeminiProxyError::network(format!("上游流式响应中途断开: {}", e.etype().as_str()))
+        .with_request_id(request_id)
+        .with_retryable(true);
+    let event = format!("event: error\ndata: {}\n\n", error_body(502, &error));
+    (error, Bytes::from(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pingora_error::ErrorType;
+
+    #[test]
+    fn test_streaming_response_headers() {
+        let mut header = ResponseHeader::build(200, None).unwrap();
+        header.insert_header("content-type", "text/event-stream; charset=UTF-8").unwrap();
+        header.insert_header("content-length", "1024").unwrap();
+        assert!(is_event_stream(&header));
+
+        prepare_streaming_response(&mut header).unwrap();
+        assert!(header.headers.get("content-length").is_none());
+        assert_eq!(header.headers.get("x-accel-buffering").unwrap(), "no");
+
+        let mut json = ResponseHeader::build(200, None).unwrap();
+        json.insert_header("content-type", "application/json").unwrap();
+        assert!(!is_event_stream(&json));
+    }
+
+    fn coalescer(buffer_bytes: usize, max_hold_ms: u64) -> StreamCoalescer {
+        StreamCoalescer::from_config(&StreamingBufferConfig { buffer_bytes, max_hold_ms }).unwrap()
+    }
+
+    #[test]
+    fn test_small_chunks_coalesced_until_threshold() {
+        let mut coalescer = coalescer(18, 1_000);
+        let now = Instant::now();
+
+        assert_eq!(coalescer.push(Some(Bytes::from("data: a\n\n")), false, now), None);
+        assert_eq!(coalescer.push(Some(Bytes::from("data: b")), false, now), None);
+        // 达到阈值时一次转发全部暂存内容
+        assert_eq!(
+            coalescer.push(Some(Bytes::from("\n\n")), false, now).as_deref(),
+            Some(&b"data: a\n\ndata: b\n\n"[..])
+        );
+
+        // 最后一块不足阈值也立即转发
+        assert_eq!(coalescer.push(Some(Bytes::from("data: c")), false, now), None);
+        assert_eq!(coalescer.push(None, true, now).as_deref(), Some(&b"data: c"[..]));
+        assert_eq!(coalescer.push(None, true, now), None);
+
+        assert!(StreamCoalescer::from_config(&StreamingBufferConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_held_chunks_flushed_at_time_bound() {
+        let mut coalescer = coalescer(1024, 50);
+        let start = Instant::now();
+
+        assert_eq!(coalescer.push(Some(Bytes::from("a")), false, start), None);
+        assert_eq!(coalescer.push(Some(Bytes::from("b")), false, start + Duration::from_millis(49)), None);
+        // 从最早暂存的数据块开始计时
+        assert_eq!(
+            coalescer.push(Some(Bytes::from("c")), false, start + Duration::from_millis(50)).as_deref(),
+            Some(&b"abc"[..])
+        );
+        // 转发后重新计时
+        assert_eq!(coalescer.push(Some(Bytes::from("d")), false, start + Duration::from_millis(80)), None);
+        assert_eq!(coalescer.take_pending().as_deref(), Some(&b"d"[..]));
+        assert_eq!(coalescer.take_pending(), None);
+    }
+
+    #[test]
+    fn test_mid_stream_disconnect_surfaces_network_error() {
+        let upstream = Error::new_up(ErrorType::ConnectionClosed);
+        assert!(is_upstream_interruption(&upstream));
+        let downstream = Error::new_down(ErrorType::ConnectionClosed);
+        assert!(!is_upstream_interruption(&downstream));
+
+        let (error, event) = stream_interrupted_event(&upstream, "req-1");
+        assert!(matches!(error, GeminiProxyError::Network { .. }));
+
+        let event = std::str::from_utf8(&event).unwrap();
+        assert!(event.starts_with("event: error\ndata: "));
+        assert!(event.ends_with("\n\n"));
+        let data: serde_json::Value =
+            serde_json::from_str(event.trim_start_matches("event: error\ndata: ").trim()).unwrap();
+        assert_eq!(data["error"]["request_id"], "req-1");
+        assert_eq!(data["error"]["retryable"], true);
+    }
+}
@@ -0,0 +1,141 @@
+// src/proxy/timeouts.rs
+//! 流式与非流式请求的上游超时
+//!
+//! 流式生成的持续时间远长于单次调用，两者使用不同的超时：非流式请求等待响应与读取响应体的总时长不超过
+//! `request_timeout_seconds`（未设置时为 `timeout_seconds`）；流式请求总时长不超过 `stream_timeout_seconds`，
+//! 相邻两个数据块的间隔不超过 `stream_idle_timeout_seconds`。等待期间的间隔由 Pingora 的上游读超时保证，
+//! 总时长与数据块间隔在每次收到数据块时检查
+
+use crate::config::GeminiConfig;
+use pingora::upstreams::peer::HttpPeer;
+use std::time::{Duration, Instant};
+
+/// 一次上游请求适用的超时
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamTimeouts {
+    /// 从连接上游开始的总时长上限
+    pub total: Duration,
+    /// 两次收到数据之间的最长间隔
+    pub idle: Duration,
+}
+
+impl UpstreamTimeouts {
+    pub fn for_request(config: &GeminiConfig, streaming: bool) -> Self {
+        if streaming {
+            Self {
+                total: Duration::from_secs(config.stream_timeout_seconds),
+                idle: Duration::from_secs(config.stream_idle_timeout_seconds),
+            }
+        } else {
+            let timeout = Duration::from_secs(config.request_timeout_seconds.unwrap_or(config.timeout_seconds));
+            Self { total: timeout, idle: timeout }
+        }
+    }
+
+    /// 设置上游读写超时：单次读取（等待响应头或下一个数据块）不超过数据块间隔
+    pub fn apply(&self, peer: &mut HttpPeer) {
+        peer.options.read_timeout = Some(self.idle);
+        peer.options.write_timeout = Some(self.idle);
+    }
+}
+
+/// 超时类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamTimeout {
+    /// 超过总时长上限
+    Total,
+    /// 数据块间隔过长
+    Idle,
+}
+
+impl UpstreamTimeout {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Total => "total",
+            Self::Idle => "idle",
+        }
+    }
+}
+
+/// 跟踪一次上游请求的总时长与数据块间隔
+#[derive(Debug, Clone)]
+pub struct UpstreamDeadline {
+    timeouts: UpstreamTimeouts,
+    started_at: Instant,
+    last_activity: Instant,
+}
+
+impl UpstreamDeadline {
+    pub fn start(timeouts: UpstreamTimeouts, now: Instant) -> Self {
+        Self {
+            timeouts,
+            started_at: now,
+            last_activity: now,
+        }
+    }
+
+    /// 收到一个响应数据块，超时时返回超时类型
+    pub fn on_chunk(&mut self, now: Instant) -> Result<(), UpstreamTimeout> {
+        if now.saturating_duration_since(self.last_activity) > self.timeouts.idle {
+            return Err(UpstreamTimeout::Idle);
+        }
+        if now.saturating_duration_since(self.started_at) > self.timeouts.total {
+            return Err(UpstreamTimeout::Total);
+        }
+        self.last_activity = now;
+        Ok(())
+    }
+
+    /// 从连接上游开始经过的时长
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.started_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> GeminiConfig {
+        let mut config: GeminiConfig = serde_yaml::from_str("api_keys: []").unwrap();
+        config.timeout_seconds = 30;
+        config.stream_timeout_seconds = 600;
+        config.stream_idle_timeout_seconds = 60;
+        config
+    }
+
+    /// 每隔 `gap` 秒收到一个数据块，返回第一个超时
+    fn run(timeouts: UpstreamTimeouts, chunks: usize, gap: u64) -> Result<(), UpstreamTimeout> {
+        let start = Instant::now();
+        let mut deadline = UpstreamDeadline::start(timeouts, start);
+        (1..=chunks as u64).try_for_each(|i| deadline.on_chunk(start + Duration::from_secs(i * gap)))
+    }
+
+    #[test]
+    fn test_long_stream_not_killed_by_request_timeout() {
+        let config = config();
+        let stream = UpstreamTimeouts::for_request(&config, true);
+        let single_shot = UpstreamTimeouts::for_request(&config, false);
+        assert_eq!(single_shot.total, Duration::from_secs(30));
+
+        // 持续 5 分钟、每 10 秒一个数据块的流式响应
+        assert_eq!(run(stream, 30, 10), Ok(()));
+        assert_eq!(run(single_shot, 30, 10), Err(UpstreamTimeout::Total));
+        // 超过流式总时长上限时中止
+        assert_eq!(run(stream, 61, 10), Err(UpstreamTimeout::Total));
+
+        let mut overridden = config;
+        overridden.request_timeout_seconds = Some(120);
+        assert_eq!(UpstreamTimeouts::for_request(&overridden, false).total, Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_idle_stream_killed() {
+        let stream = UpstreamTimeouts::for_request(&config(), true);
+        assert_eq!(run(stream, 3, 90), Err(UpstreamTimeout::Idle));
+
+        let mut peer = HttpPeer::new("127.0.0.1:443", true, "generativelanguage.googleapis.com".to_string());
+        stream.apply(&mut peer);
+        assert_eq!(peer.options.read_timeout, Some(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,146 @@
+// src/proxy/traffic_class.rs
+//! 请求流量分类
+//!
+//! 按配置的规则（路径前缀、请求头、请求体）为请求打上 `traffic_class` 标签，
+//! 该标签会附加到指标和审计日志中，便于下游分析
+
+use crate::config::TrafficClassRule;
+use http::HeaderMap;
+
+/// 未命中任何规则时使用的分类
+pub const DEFAULT_TRAFFIC_CLASS: &str = "default";
+
+/// 参与分类的请求体最大缓冲字节数
+pub const MAX_CLASSIFY_BODY_BYTES: usize = 64 * 1024;
+
+/// 是否存在依赖请求体的规则（需要在读取请求体后再分类）
+pub fn needs_body(rules: &[TrafficClassRule]) -> bool {
+    rules.iter().any(|rule| rule.body_contains.is_some())
+}
+
+/// 按顺序匹配规则，返回首个命中的分类
+///
+/// `body` 为 None 时，依赖请求体的规则视为未命中
+pub fn classify<'a>(
+    rules: &'a [TrafficClassRule],
+    path: &str,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|rule| rule_matches(rule, path, headers, body))
+        .map(|rule| rule.class.as_str())
+}
+
+fn rule_matches(rule: &TrafficClassRule, path: &str, headers: &HeaderMap, body: Option<&[u8]>) -> bool {
+    if let Some(prefix) = &rule.path_prefix {
+        if !path.starts_with(prefix.as_str()) {
+            return false;
+        }
+    }
+
+    if let Some(name) = &rule.header {
+        let value = match headers.get(name.as_str()).and_then(|v| v.to_str().ok()) {
+            Some(value) => value,
+            None => return false,
+        };
+        if let Some(expected) = &rule.header_value {
+            if !value.eq_ignore_ascii_case(expected) {
+                return false;
+            }
+        }
+    }
+
+    if let Some(needle) = &rule.body_contains {
+        let body = match body {
+            Some(body) => body,
+            None => return false,
+        };
+        let needle = needle.as_bytes();
+        if needle.is_empty() || !body.windows(needle.len()).any(|window| window == needle) {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(class: &str) -> TrafficClassRule {
+        TrafficClassRule {
+            class: class.to_string(),
+            path_prefix: None,
+            header: None,
+            header_value: None,
+            body_contains: None,
+        }
+    }
+
+    fn rules() -> Vec<TrafficClassRule> {
+        vec![
+            TrafficClassRule {
+                header: Some("x-traffic-class".to_string()),
+                header_value: Some("batch".to_string()),
+                ..rule("batch")
+            },
+            TrafficClassRule {
+                path_prefix: Some("/v1beta/models/gemini-1.5-flash".to_string()),
+                ..rule("interactive")
+            },
+            TrafficClassRule {
+                body_contains: Some("\"embedding\"".to_string()),
+                ..rule("embedding")
+            },
+        ]
+    }
+
+    #[test]
+    fn test_path_based_rule() {
+        let class = classify(
+            &rules(),
+            "/v1beta/models/gemini-1.5-flash:generateContent",
+            &HeaderMap::new(),
+            None,
+        );
+        assert_eq!(class, Some("interactive"));
+
+        let class = classify(&rules(), "/v1beta/models/gemini-1.5-pro:generateContent", &HeaderMap::new(), None);
+        assert_eq!(class, None);
+    }
+
+    #[test]
+    fn test_header_based_rule() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-traffic-class", "Batch".parse().unwrap());
+
+        // 请求头规则排在路径规则之前，优先命中
+        let class = classify(
+            &rules(),
+            "/v1beta/models/gemini-1.5-flash:generateContent",
+            &headers,
+            None,
+        );
+        assert_eq!(class, Some("batch"));
+
+        headers.insert("x-traffic-class", "realtime".parse().unwrap());
+        let class = classify(&rules(), "/v1beta/files", &headers, None);
+        assert_eq!(class, None);
+    }
+
+    #[test]
+    fn test_body_based_rule_requires_body() {
+        let rules = rules();
+        assert!(needs_body(&rules));
+
+        let body = br#"{"task":"embedding"}"#;
+        assert_eq!(classify(&rules, "/v1beta/files", &HeaderMap::new(), None), None);
+        assert_eq!(
+            classify(&rules, "/v1beta/files", &HeaderMap::new(), Some(body)),
+            Some("embedding")
+        );
+    }
+}
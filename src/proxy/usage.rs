@@ -0,0 +1,297 @@
+// src/proxy/usage.rs
+//! 上游用量解析与成本估算
+//!
+//! 从 Gemini 响应体中解析 `usageMetadata`，并按配置的模型价格表估算成本。
+//! 响应体解析是容错的：无法解析的上游响应体原样转发给客户端，只记录指标和警告
+
+use crate::config::ModelCost;
+use crate::metrics::MetricsCollector;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 单次请求的令牌用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+    #[serde(default)]
+    total_token_count: u64,
+}
+
+impl From<UsageMetadata> for TokenUsage {
+    fn from(metadata: UsageMetadata) -> Self {
+        let total_tokens = if metadata.total_token_count > 0 {
+            metadata.total_token_count
+        } else {
+            metadata.prompt_token_count + metadata.candidates_token_count
+        };
+        Self {
+            prompt_tokens: metadata.prompt_token_count,
+            completion_tokens: metadata.candidates_token_count,
+            total_tokens,
+        }
+    }
+}
+
+/// 从请求路径中提取模型名称，例如 `/v1beta/models/gemini-1.5-pro:generateContent`
+pub fn extract_model(path: &str) -> Option<String> {
+    let rest = path.split("/models/").nth(1)?;
+    let model = rest.split([':', '/', '?']).next()?;
+    if model.is_empty() {
+        None
+    } else {
+        Some(model.to_string())
+    }
+}
+
+/// 上游响应体解析结果
+#[derive(Debug, Clone)]
+pub enum UpstreamBody {
+    /// 空响应体
+    Empty,
+    /// 普通 JSON 响应
+    Json(serde_json::Value),
+    /// SSE 流式响应中的各个事件
+    EventStream(Vec<serde_json::Value>),
+    /// 无法解析的响应体（网关错误页、截断等），附带原因
+    Malformed(String),
+}
+
+impl UpstreamBody {
+    /// 提取用量信息（SSE 取最后一个包含用量的事件）
+    pub fn usage(&self) -> Option<TokenUsage> {
+        match self {
+            Self::Json(value) => usage_from_value(value),
+            Self::EventStream(events) => events.iter().filter_map(usage_from_value).last(),
+            Self::Empty | Self::Malformed(_) => None,
+        }
+    }
+
+    /// 汇总整个响应的用量：流式响应（SSE 事件或 JSON 数组）按块累加输出令牌，
+    /// 提示令牌各块重复携带，取最大值
+    pub fn summed_usage(&self) -> Option<TokenUsage> {
+        match self {
+            Self::Json(serde_json::Value::Array(items)) => sum_chunk_usage(items),
+            Self::EventStream(events) => sum_chunk_usage(events),
+            _ => self.usage(),
+        }
+    }
+
+    /// 是否为流式响应（SSE 事件流或流式 JSON 数组）
+    pub fn is_streaming(&self) -> bool {
+        matches!(self, Self::EventStream(_) | Self::Json(serde_json::Value::Array(_)))
+    }
+
+    pub fn is_malformed(&self) -> bool {
+        matches!(self, Self::Malformed(_))
+    }
+}
+
+/// 容错地解析上游响应体，永不失败
+pub fn parse_upstream_body(body: &[u8]) -> UpstreamBody {
+    let text = match std::str::from_utf8(body) {
+        Ok(text) => text.trim(),
+        Err(e) => return UpstreamBody::Malformed(format!("非 UTF-8 响应体: {}", e)),
+    };
+    if text.is_empty() {
+        return UpstreamBody::Empty;
+    }
+
+    if text.starts_with('{') || text.starts_with('[') {
+        return match serde_json::from_str(text) {
+            Ok(value) => UpstreamBody::Json(value),
+            Err(e) => UpstreamBody::Malformed(format!("JSON 解析失败: {}", e)),
+        };
+    }
+
+    let data_lines: Vec<&str> = text
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("data:"))
+        .map(str::trim)
+        .filter(|data| !data.is_empty() && *data != "[DONE]")
+        .collect();
+    if data_lines.is_empty() {
+        return UpstreamBody::Malformed("响应体既不是 JSON 也不是 SSE 事件流".to_string());
+    }
+
+    let mut events = Vec::with_capacity(data_lines.len());
+    for data in data_lines {
+        match serde_json::from_str(data) {
+            Ok(value) => events.push(value),
+            Err(e) => return UpstreamBody::Malformed(format!("SSE 事件解析失败: {}", e)),
+        }
+    }
+    UpstreamBody::EventStream(events)
+}
+
+/// 将上游响应块追加到缓冲区（只读取，不修改转发给客户端的内容）
+///
+/// 返回是否已超过缓冲上限 `max_bytes`（超出部分不再参与用量解析）
+pub fn buffer_response_chunk(buffer: &mut Vec<u8>, chunk: &Option<Bytes>, max_bytes: usize) -> bool {
+    if let Some(chunk) = chunk {
+        if buffer.len() + chunk.len() > max_bytes {
+            return true;
+        }
+        buffer.extend_from_slice(chunk);
+    }
+    false
+}
+
+/// 检查缓冲的上游响应体：无法解析时记录指标和警告，但不影响请求
+pub async fn inspect_upstream_body(metrics: &MetricsCollector, body: &[u8]) -> UpstreamBody {
+    let parsed = parse_upstream_body(body);
+    if let UpstreamBody::Malformed(reason) = &parsed {
+        metrics.increment_malformed_upstream().await;
+        tracing::warn!("上游返回了无法解析的响应体（已原样转发）: {}", reason);
+    }
+    parsed
+}
+
+fn usage_from_value(value: &serde_json::Value) -> Option<TokenUsage> {
+    // 流式 JSON 数组响应取最后一个元素
+    let value = match value {
+        serde_json::Value::Array(items) => items.iter().rev().find(|v| v.get("usageMetadata").is_some())?,
+        other => other,
+    };
+    let metadata = value.get("usageMetadata")?;
+    serde_json::from_value::<UsageMetadata>(metadata.clone())
+        .ok()
+        .map(TokenUsage::from)
+}
+
+fn sum_chunk_usage(chunks: &[serde_json::Value]) -> Option<TokenUsage> {
+    chunks
+        .iter()
+        .filter_map(usage_from_value)
+        .reduce(|acc, usage| {
+            let prompt_tokens = acc.prompt_tokens.max(usage.prompt_tokens);
+            let completion_tokens = acc.completion_tokens + usage.completion_tokens;
+            TokenUsage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            }
+        })
+}
+
+/// 查找模型价格：优先精确匹配，其次最长前缀匹配（如 `gemini-1.5-pro` 匹配 `gemini-1.5-pro-002`）
+pub fn find_model_cost<'a>(costs: &'a HashMap<String, ModelCost>, model: &str) -> Option<&'a ModelCost> {
+    if let Some(cost) = costs.get(model) {
+        return Some(cost);
+    }
+    costs
+        .iter()
+        .filter(|(name, _)| model.starts_with(name.as_str()))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, cost)| cost)
+}
+
+/// 估算单次请求成本，未配置价格的模型成本为 0
+pub fn estimate_cost(costs: &HashMap<String, ModelCost>, model: &str, usage: &TokenUsage) -> f64 {
+    match find_model_cost(costs, model) {
+        Some(cost) => {
+            usage.prompt_tokens as f64 / 1000.0 * cost.input_per_1k_tokens
+                + usage.completion_tokens as f64 / 1000.0 * cost.output_per_1k_tokens
+        }
+        None => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cost_table() -> HashMap<String, ModelCost> {
+        let mut costs = HashMap::new();
+        costs.insert(
+            "gemini-1.5-pro".to_string(),
+            ModelCost { input_per_1k_tokens: 0.00125, output_per_1k_tokens: 0.005 },
+        );
+        costs.insert(
+            "gemini-1.5-flash".to_string(),
+            ModelCost { input_per_1k_tokens: 0.000075, output_per_1k_tokens: 0.0003 },
+        );
+        costs
+    }
+
+    #[test]
+    fn test_extract_model() {
+        assert_eq!(
+            extract_model("/v1beta/models/gemini-1.5-pro:generateContent"),
+            Some("gemini-1.5-pro".to_string())
+        );
+        assert_eq!(
+            extract_model("/v1beta/models/gemini-1.5-flash:streamGenerateContent?alt=sse"),
+            Some("gemini-1.5-flash".to_string())
+        );
+        assert_eq!(extract_model("/v1beta/models"), None);
+    }
+
+    fn parse_usage(body: &[u8]) -> Option<TokenUsage> {
+        parse_upstream_body(body).usage()
+    }
+
+    #[test]
+    fn test_parse_usage_json_and_sse() {
+        let json = br#"{"candidates":[],"usageMetadata":{"promptTokenCount":10,"candidatesTokenCount":20,"totalTokenCount":30}}"#;
+        let usage = parse_usage(json).unwrap();
+        assert_eq!(usage, TokenUsage { prompt_tokens: 10, completion_tokens: 20, total_tokens: 30 });
+
+        let sse = b"data: {\"candidates\":[]}\n\ndata: {\"usageMetadata\":{\"promptTokenCount\":5,\"candidatesTokenCount\":7}}\n\n";
+        let usage = parse_usage(sse).unwrap();
+        assert_eq!(usage, TokenUsage { prompt_tokens: 5, completion_tokens: 7, total_tokens: 12 });
+
+        assert!(parse_usage(b"<html>bad gateway</html>").is_none());
+        assert!(parse_usage(br#"{"candidates":[]}"#).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_upstream_body_passthrough_and_metric() {
+        let metrics = MetricsCollector::new();
+        let chunk = Some(Bytes::from_static(b"<html><body>502 Bad Gateway</body></html>"));
+
+        // 缓冲不改变转发给客户端的响应块
+        let mut buffer = Vec::new();
+        assert!(!buffer_response_chunk(&mut buffer, &chunk, 1024));
+        assert!(buffer_response_chunk(&mut buffer, &chunk, 16));
+        assert_eq!(chunk.as_deref(), Some(&b"<html><body>502 Bad Gateway</body></html>"[..]));
+
+        let parsed = inspect_upstream_body(&metrics, &buffer).await;
+        assert!(parsed.is_malformed());
+        assert!(parsed.usage().is_none());
+
+        // 截断的 JSON 同样视为异常
+        let parsed = inspect_upstream_body(&metrics, br#"{"candidates":[{"content""#).await;
+        assert!(parsed.is_malformed());
+
+        // 正常响应不计数
+        inspect_upstream_body(&metrics, br#"{"candidates":[]}"#).await;
+        assert!(metrics.get_metrics().contains("gemini_malformed_upstream_total 2"));
+    }
+
+    #[test]
+    fn test_estimate_cost_for_mixed_models() {
+        let costs = cost_table();
+        let usage = TokenUsage { prompt_tokens: 2000, completion_tokens: 1000, total_tokens: 3000 };
+
+        let pro = estimate_cost(&costs, "gemini-1.5-pro-002", &usage);
+        let flash = estimate_cost(&costs, "gemini-1.5-flash", &usage);
+        let unknown = estimate_cost(&costs, "text-embedding-004", &usage);
+
+        assert!((pro - (2.0 * 0.00125 + 0.005)).abs() < 1e-12);
+        assert!((flash - (2.0 * 0.000075 + 0.0003)).abs() < 1e-12);
+        assert!(pro > flash);
+        assert_eq!(unknown, 0.0);
+    }
+}
@@ -0,0 +1,338 @@
+// src/proxy/warm_pool.rs
+//! 上游连接预热池
+//!
+//! 后台服务（运行在 Pingora 服务器的运行时中）为上游地址保持 `connections_per_peer` 个已建立的空闲 TCP 连接，
+//! 连接空闲接近上游的空闲超时前主动替换，上游地址变化（热重载 `base_url` 或 DNS 变化）后关闭旧地址的连接。
+//! 预热池实现 Pingora 的 L4 连接接口并设置到上游 peer 上：Pingora 转发客户端请求（以及影子密钥镜像请求）
+//! 需要新建连接时优先取用预热连接，省去 TCP 建连时间，TLS 握手在取用后照常进行。
+//! 转发请求的连接复用情况单独统计
+
+use crate::config::{GeminiConfig, LiveConfig, WarmPoolConfig};
+use async_trait::async_trait;
+use pingora::connectors::L4Connect;
+use pingora::protocols::l4::socket::SocketAddr;
+use pingora::protocols::l4::stream::Stream;
+use pingora::server::ShutdownWatch;
+use pingora::services::background::BackgroundService;
+use pingora_error::{Error, ErrorType, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr as InetSocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+/// 建立到上游地址的新连接
+#[async_trait]
+pub trait WarmConnector: Send + Sync {
+    type Connection: Send;
+
+    async fn connect(&self, addr: &InetSocketAddr) -> std::result::Result<Self::Connection, String>;
+}
+
+/// 建立到上游的 TCP 连接
+pub struct TcpWarmConnector {
+    connect_timeout: Duration,
+}
+
+impl TcpWarmConnector {
+    pub fn new(connect_timeout: Duration) -> Self {
+        Self { connect_timeout }
+    }
+}
+
+#[async_trait]
+impl WarmConnector for TcpWarmConnector {
+    type Connection = TcpStream;
+
+    async fn connect(&self, addr: &InetSocketAddr) -> std::result::Result<TcpStream, String> {
+        match tokio::time::timeout(self.connect_timeout, TcpStream::connect(addr)).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("连接超时（{:?}）", self.connect_timeout)),
+        }
+    }
+}
+
+/// 代理使用的上游预热池
+pub type UpstreamWarmPool = WarmPool<TcpWarmConnector>;
+
+/// 预热池状态，输出到 `/performance`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WarmPoolStats {
+    /// 维持预热连接的上游地址数
+    pub warmed_peers: u64,
+    /// 当前空闲的预热连接数
+    pub idle_connections: u64,
+    /// 每个上游地址的目标空闲连接数
+    pub connections_per_peer: u64,
+    /// 取用到预热连接的次数
+    pub hits: u64,
+    /// 没有可用预热连接、需要现场建连的次数
+    pub misses: u64,
+}
+
+/// 转发请求连接上游时复用已有连接与新建连接的次数
+#[derive(Debug, Default)]
+pub struct UpstreamConnectionStats {
+    reused: AtomicU64,
+    new: AtomicU64,
+}
+
+impl UpstreamConnectionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, reused: bool) {
+        let counter = if reused { &self.reused } else { &self.new };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// (复用次数, 新建次数)
+    pub fn counts(&self) -> (u64, u64) {
+        (self.reused.load(Ordering::Relaxed), self.new.load(Ordering::Relaxed))
+    }
+}
+
+struct IdleConnection<T> {
+    connection: T,
+    opened_at: Instant,
+}
+
+/// 按上游地址分组的空闲连接池
+pub struct WarmPool<C: WarmConnector> {
+    connector: C,
+    connections_per_peer: usize,
+    /// 连接空闲超过该时长即替换（上游空闲超时减去提前量）
+    max_idle: Duration,
+    refresh_interval: Duration,
+    idle: Mutex<HashMap<InetSocketAddr, Vec<IdleConnection<C::Connection>>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<C: WarmConnector> WarmPool<C> {
+    pub fn new(config: &WarmPoolConfig, connector: C) -> Self {
+        Self {
+            connector,
+            connections_per_peer: config.connections_per_peer,
+            max_idle: Duration::from_secs(config.idle_timeout_seconds.saturating_sub(config.refresh_before_seconds)),
+            refresh_interval: Duration::from_secs(config.refresh_interval_seconds),
+            idle: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 取出一个上游地址的预热连接；连接被取走后由下一次补足重建
+    pub fn take(&self, addr: &InetSocketAddr) -> Option<C::Connection> {
+        let connection = {
+            let mut idle = self.idle.lock().unwrap();
+            idle.get_mut(addr).and_then(|connections| {
+                connections.retain(|c| c.opened_at.elapsed() < self.max_idle);
+                connections.pop().map(|c| c.connection)
+            })
+        };
+        let counter = if connection.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        connection
+    }
+
+    /// 关闭不再使用的上游地址的连接与即将被上游关闭的连接，再把每个地址补足到目标连接数；返回新建的连接数
+    pub async fn maintain(&self, peers: &[InetSocketAddr]) -> usize {
+        let missing: Vec<(InetSocketAddr, usize)> = {
+            let mut idle = self.idle.lock().unwrap();
+            idle.retain(|addr, _| peers.contains(addr));
+            peers
+                .iter()
+                .map(|addr| {
+                    let connections = idle.entry(*addr).or_default();
+                    connections.retain(|c| c.opened_at.elapsed() < self.max_idle);
+                    (*addr, self.connections_per_peer.saturating_sub(connections.len()))
+                })
+                .filter(|(_, missing)| *missing > 0)
+                .collect()
+        };
+
+        let mut opened = 0;
+        for (addr, missing) in missing {
+            for _ in 0..missing {
+                match self.connector.connect(&addr).await {
+                    Ok(connection) => {
+                        let mut idle = self.idle.lock().unwrap();
+                        // 建连期间上游地址可能已变化
+                        if let Some(connections) = idle.get_mut(&addr) {
+                            connections.push(IdleConnection { connection, opened_at: Instant::now() });
+                            opened += 1;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!(peer = %addr, "预热上游连接失败: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+        opened
+    }
+
+    pub fn stats(&self) -> WarmPoolStats {
+        let idle = self.idle.lock().unwrap();
+        WarmPoolStats {
+            warmed_peers: idle.len() as u64,
+            idle_connections: idle.values().map(|connections| connections.len() as u64).sum(),
+            connections_per_peer: self.connections_per_peer as u64,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<C: WarmConnector> std::fmt::Debug for WarmPool<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WarmPool")
+            .field("connections_per_peer", &self.connections_per_peer)
+            .field("max_idle", &self.max_idle)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Pingora 新建上游连接时调用：优先取用预热连接，没有时现场建连
+#[async_trait]
+impl L4Connect for UpstreamWarmPool {
+    async fn connect(&self, addr: &SocketAddr) -> Result<Stream> {
+        let SocketAddr::Inet(addr) = addr else {
+            return Error::e_explain(ErrorType::ConnectError, "连接预热只支持 TCP 上游");
+        };
+        if let Some(connection) = self.take(addr) {
+            return Ok(connection.into());
+        }
+        match self.connector.connect(addr).await {
+            Ok(connection) => Ok(connection.into()),
+            Err(e) => Error::e_explain(ErrorType::ConnectError, format!("连接上游 {} 失败: {}", addr, e)),
+        }
+    }
+}
+
+/// 解析上游地址（与 `HttpPeer::new` 一样取第一个解析结果）
+pub async fn resolve_upstream(base_url: &str) -> Option<InetSocketAddr> {
+    tokio::net::lookup_host(base_url).await.ok()?.next()
+}
+
+/// 在 Pingora 服务器运行时中按配置的间隔为当前上游地址维持预热连接，服务器停机时退出
+pub struct WarmPoolService {
+    pool: Arc<UpstreamWarmPool>,
+    gemini_config: Arc<LiveConfig<GeminiConfig>>,
+}
+
+impl WarmPoolService {
+    pub fn new(pool: Arc<UpstreamWarmPool>, gemini_config: Arc<LiveConfig<GeminiConfig>>) -> Self {
+        Self { pool, gemini_config }
+    }
+}
+
+#[async_trait]
+impl BackgroundService for WarmPoolService {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let mut ticker = tokio::time::interval(self.pool.refresh_interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.changed() => return,
+            }
+            let base_url = self.gemini_config.load().base_url.clone();
+            let peers: Vec<InetSocketAddr> = match resolve_upstream(&base_url).await {
+                Some(addr) => vec![addr],
+                None => {
+                    tracing::warn!(base_url = %base_url, "解析上游地址失败，本轮不预热连接");
+                    Vec::new()
+                }
+            };
+            self.pool.maintain(&peers).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 依次编号的假连接
+    #[derive(Default)]
+    struct CountingConnector {
+        opened: AtomicU64,
+    }
+
+    #[async_trait]
+    impl WarmConnector for CountingConnector {
+        type Connection = u64;
+
+        async fn connect(&self, _addr: &InetSocketAddr) -> std::result::Result<u64, String> {
+            Ok(self.opened.fetch_add(1, Ordering::Relaxed))
+        }
+    }
+
+    fn pool(connections_per_peer: usize, idle_timeout_seconds: u64, refresh_before_seconds: u64) -> WarmPool<CountingConnector> {
+        let config = WarmPoolConfig {
+            enabled: true,
+            connections_per_peer,
+            idle_timeout_seconds,
+            refresh_before_seconds,
+            refresh_interval_seconds: 1,
+        };
+        WarmPool::new(&config, CountingConnector::default())
+    }
+
+    fn addr(last_octet: u8) -> InetSocketAddr {
+        InetSocketAddr::from(([10, 0, 0, last_octet], 443))
+    }
+
+    #[tokio::test]
+    async fn test_pool_maintains_configured_idle_connections_per_peer() {
+        let pool = pool(2, 60, 10);
+        let peers = vec![addr(1), addr(2)];
+
+        assert_eq!(pool.maintain(&peers).await, 4);
+        assert_eq!(pool.stats().idle_connections, 4);
+        // 已经满足目标时不再建连
+        assert_eq!(pool.maintain(&peers).await, 0);
+
+        // 下一次新建连接取用预热连接，补足时只重建被取走的连接
+        assert!(pool.take(&addr(1)).is_some());
+        assert_eq!(pool.stats().idle_connections, 3);
+        assert_eq!(pool.maintain(&peers).await, 1);
+
+        // 上游地址变化后关闭旧地址的连接；未预热的地址取用不到连接
+        pool.maintain(&peers[..1]).await;
+        assert!(pool.take(&addr(2)).is_none());
+        let stats = pool.stats();
+        assert_eq!((stats.warmed_peers, stats.idle_connections), (1, 2));
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_pingora_connect_uses_warm_tcp_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = listener.local_addr().unwrap();
+        let config = WarmPoolConfig {
+            enabled: true,
+            connections_per_peer: 1,
+            idle_timeout_seconds: 60,
+            refresh_before_seconds: 10,
+            refresh_interval_seconds: 1,
+        };
+        let pool = WarmPool::new(&config, TcpWarmConnector::new(Duration::from_secs(5)));
+
+        assert_eq!(pool.maintain(&[upstream]).await, 1);
+        listener.accept().await.unwrap();
+        // Pingora 新建连接时拿到预热时建立的连接
+        L4Connect::connect(&pool, &SocketAddr::Inet(upstream)).await.unwrap();
+        assert_eq!((pool.stats().hits, pool.stats().idle_connections), (1, 0));
+
+        // 池中没有连接时现场建连
+        L4Connect::connect(&pool, &SocketAddr::Inet(upstream)).await.unwrap();
+        listener.accept().await.unwrap();
+        assert_eq!(pool.stats().misses, 1);
+    }
+}
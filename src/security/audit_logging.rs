@@ -3,11 +3,13 @@
 //! 
 //! 提供API调用审计、配置变更追踪、安全事件监控等功能
 
+use crate::error::dead_letter::{DeadLetterEntry, DeadLetterSource, DeadLetterStore};
 use crate::error::GeminiProxyError;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::net::IpAddr;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 /// 审计事件类型
@@ -103,6 +105,25 @@ pub struct SecurityEvent {
     pub recommended_action: String,
 }
 
+/// 一次API调用的审计记录
+#[derive(Debug, Clone)]
+pub struct ApiCallRecord {
+    pub source_ip: IpAddr,
+    pub user_id: Option<String>,
+    pub method: String,
+    pub resource: String,
+    pub status_code: u16,
+    pub duration_ms: u64,
+    pub result: AuditResult,
+    /// 请求 ID（用于跨日志关联）
+    pub request_id: Option<String>,
+    /// 附加到审计条目的元数据
+    pub metadata: HashMap<String, String>,
+}
+
+/// 跨组件共享的审计日志管理器
+pub type SharedAuditLog = std::sync::Arc<tokio::sync::Mutex<AuditLogManager>>;
+
 /// 审计日志管理器
 pub struct AuditLogManager {
     /// 内存日志缓冲区
@@ -113,6 +134,8 @@ pub struct AuditLogManager {
     statistics: AuditStatistics,
     /// IP访问统计
     ip_statistics: HashMap<IpAddr, IpAccessStats>,
+    /// 写入日志文件失败时保存条目的死信文件
+    dead_letter: Option<Arc<DeadLetterStore>>,
 }
 
 /// 审计配置
@@ -132,6 +155,16 @@ pub struct AuditConfig {
     pub realtime_monitoring: bool,
     /// 安全事件阈值
     pub security_thresholds: SecurityThresholds,
+    /// 事件 ID 前缀
+    #[serde(default = "default_event_id_prefix")]
+    pub event_id_prefix: String,
+    /// 存在请求 ID 时直接作为事件 ID
+    #[serde(default)]
+    pub reuse_request_id: bool,
+}
+
+fn default_event_id_prefix() -> String {
+    "audit_".to_string()
 }
 
 /// 安全阈值配置
@@ -159,6 +192,9 @@ pub struct AuditStatistics {
     pub failed_requests: u64,
     pub blocked_requests: u64,
     pub unique_ips: usize,
+    /// 写入日志文件失败并转入死信文件的条目数
+    #[serde(default)]
+    pub dead_lettered_writes: u64,
     pub last_reset: chrono::DateTime<chrono::Utc>,
 }
 
@@ -195,6 +231,8 @@ impl Default for AuditConfig {
             buffer_size: 10000,
             realtime_monitoring: true,
             security_thresholds: SecurityThresholds::default(),
+            event_id_prefix: default_event_id_prefix(),
+            reuse_request_id: false,
         }
     }
 }
@@ -218,9 +256,16 @@ impl AuditLogManager {
             config,
             statistics: AuditStatistics::default(),
             ip_statistics: HashMap::new(),
+            dead_letter: None,
         }
     }
 
+    /// 写入日志文件失败时将条目保存到死信文件，由后台任务重新投递
+    pub fn with_dead_letter(mut self, dead_letter: Arc<DeadLetterStore>) -> Self {
+        self.dead_letter = Some(dead_letter);
+        self
+    }
+
     /// 记录API调用
     pub async fn log_api_call(
         &mut self,
@@ -232,19 +277,48 @@ impl AuditLogManager {
         duration_ms: u64,
         result: AuditResult,
     ) -> Result<(), GeminiProxyError> {
-        let mut metadata = HashMap::new();
+        self.log_api_call_record(ApiCallRecord {
+            source_ip,
+            user_id,
+            method: method.to_string(),
+            resource: resource.to_string(),
+            status_code,
+            duration_ms,
+            result,
+            request_id: None,
+            metadata: HashMap::new(),
+        })
+        .await
+    }
+
+    /// 记录API调用（可附带额外的元数据，例如流量分类）
+    pub async fn log_api_call_record(&mut self, record: ApiCallRecord) -> Result<(), GeminiProxyError> {
+        let ApiCallRecord {
+            source_ip,
+            user_id,
+            method,
+            resource,
+            status_code,
+            duration_ms,
+            result,
+            request_id,
+            mut metadata,
+        } = record;
         metadata.insert("user_agent".to_string(), "gemini-proxy".to_string());
+        if let Some(request_id) = &request_id {
+            metadata.insert("request_id".to_string(), request_id.clone());
+        }
         
         let entry = AuditLogEntry {
-            id: self.generate_event_id(),
+            id: self.event_id_for(request_id.as_deref()),
             timestamp: chrono::Utc::now(),
             event_type: AuditEventType::ApiCall,
             severity: if status_code >= 400 { AuditSeverity::Warning } else { AuditSeverity::Info },
             source_ip: Some(source_ip),
             user_identifier: user_id,
             action: format!("{} {}", method, resource),
-            resource: resource.to_string(),
-            method: Some(method.to_string()),
+            resource,
+            method: Some(method),
             status_code: Some(status_code),
             duration_ms: Some(duration_ms),
             metadata,
@@ -434,12 +508,42 @@ impl AuditLogManager {
 
         // 写入文件（如果启用）
         if self.config.file_output_enabled {
-            self.write_to_file(&entry).await?;
+            if let Err(e) = self.write_to_file(&entry).await {
+                return self.dead_letter_entry(&entry, e).await;
+            }
         }
 
         Ok(())
     }
 
+    /// 将写入失败的条目保存到死信文件；未配置死信文件或保存失败时返回原始错误
+    async fn dead_letter_entry(&mut self, entry: &AuditLogEntry, error: GeminiProxyError) -> Result<(), GeminiProxyError> {
+        let dead_letter = match &self.dead_letter {
+            Some(dead_letter) => dead_letter.clone(),
+            None => return Err(error),
+        };
+        let payload = serde_json::to_string(entry)
+            .map_err(|e| GeminiProxyError::storage(format!("序列化日志失败: {}", e)))?;
+        let dead_letter_entry = DeadLetterEntry::new(
+            DeadLetterSource::Audit,
+            &self.config.log_file_path,
+            payload,
+            error.to_string(),
+        );
+
+        match dead_letter.record(dead_letter_entry).await {
+            Ok(()) => {
+                self.statistics.dead_lettered_writes += 1;
+                tracing::warn!("审计日志写入失败，已转入死信文件 {}: {}", dead_letter.path().display(), error);
+                Ok(())
+            }
+            Err(e) => {
+                tracing::error!("审计日志写入失败且无法写入死信文件: {}", e);
+                Err(error)
+            }
+        }
+    }
+
     /// 写入文件
     async fn write_to_file(&self, entry: &AuditLogEntry) -> Result<(), GeminiProxyError> {
         use tokio::io::AsyncWriteExt;
@@ -466,6 +570,28 @@ impl AuditLogManager {
         Ok(())
     }
 
+    /// 停机前落盘：同步日志文件并重新投递死信条目，返回仍未投递的死信条目数
+    pub async fn flush(&self) -> Result<usize, GeminiProxyError> {
+        if self.config.file_output_enabled && Path::new(&self.config.log_file_path).exists() {
+            let file = tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(&self.config.log_file_path)
+                .await
+                .map_err(|e| GeminiProxyError::storage(format!("打开日志文件失败: {}", e)))?;
+            file.sync_all()
+                .await
+                .map_err(|e| GeminiProxyError::storage(format!("同步日志文件失败: {}", e)))?;
+        }
+        match &self.dead_letter {
+            Some(dead_letter) => dead_letter
+                .retry()
+                .await
+                .map(|outcome| outcome.remaining)
+                .map_err(GeminiProxyError::storage),
+            None => Ok(0),
+        }
+    }
+
     /// 更新统计信息
     fn update_statistics(&mut self, entry: &AuditLogEntry) {
         self.statistics.total_events += 1;
@@ -539,7 +665,15 @@ impl AuditLogManager {
 
     /// 生成事件ID
     fn generate_event_id(&self) -> String {
-        format!("audit_{}", uuid::Uuid::new_v4().to_string())
+        format!("{}{}", self.config.event_id_prefix, uuid::Uuid::new_v4())
+    }
+
+    /// 生成事件ID：启用请求ID复用且存在请求ID时直接使用请求ID
+    fn event_id_for(&self, request_id: Option<&str>) -> String {
+        match request_id {
+            Some(request_id) if self.config.reuse_request_id && !request_id.is_empty() => request_id.to_string(),
+            _ => self.generate_event_id(),
+        }
     }
 
     /// 获取统计信息
@@ -694,4 +828,102 @@ mod tests {
         assert_eq!(config_logs.len(), 1);
         assert_eq!(config_logs[0].action, "配置变更: update");
     }
-}
\ No newline at end of file
+    fn api_call_record(request_id: Option<&str>) -> ApiCallRecord {
+        ApiCallRecord {
+            source_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            user_id: None,
+            method: "POST".to_string(),
+            resource: "/v1beta/models/gemini-1.5-flash:generateContent".to_string(),
+            status_code: 200,
+            duration_ms: 42,
+            result: AuditResult::Success,
+            request_id: request_id.map(str::to_string),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_id_reuses_request_id() {
+        let config = AuditConfig {
+            reuse_request_id: true,
+            ..AuditConfig::default()
+        };
+        let mut manager = AuditLogManager::new(config);
+
+        manager.log_api_call_record(api_call_record(Some("req-1234"))).await.unwrap();
+
+        let logs = manager.get_logs_by_type(AuditEventType::ApiCall, 10);
+        assert_eq!(logs[0].id, "req-1234");
+        assert_eq!(logs[0].metadata.get("request_id").map(String::as_str), Some("req-1234"));
+    }
+
+    #[tokio::test]
+    async fn test_event_id_prefix() {
+        let config = AuditConfig {
+            event_id_prefix: "gp-".to_string(),
+            ..AuditConfig::default()
+        };
+        let mut manager = AuditLogManager::new(config);
+
+        // 未启用复用时，即使存在请求 ID 也生成新的事件 ID
+        manager.log_api_call_record(api_call_record(Some("req-1234"))).await.unwrap();
+
+        let logs = manager.get_logs_by_type(AuditEventType::ApiCall, 10);
+        assert!(logs[0].id.starts_with("gp-"));
+        assert_ne!(logs[0].id, "req-1234");
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_goes_to_dead_letter_and_retry_drains_it() {
+        let dir = std::env::temp_dir().join(format!("gemini-proxy-audit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // 日志目录位置被普通文件占用，写入必然失败
+        let blocker = dir.join("logs");
+        std::fs::write(&blocker, "").unwrap();
+        let log_path = blocker.join("audit.log");
+
+        let dead_letter = Arc::new(DeadLetterStore::new(dir.join("dead_letter.jsonl"), 100));
+        let mut manager = AuditLogManager::new(AuditConfig {
+            log_file_path: log_path.to_string_lossy().to_string(),
+            ..AuditConfig::default()
+        })
+        .with_dead_letter(dead_letter.clone());
+
+        manager.log_config_change(None, Some("admin".to_string()), "server.port", "8080", "8443", "update").await.unwrap();
+
+        assert_eq!(manager.get_statistics().dead_lettered_writes, 1);
+        let entries = dead_letter.entries().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source, DeadLetterSource::Audit);
+        assert_eq!(entries[0].target, log_path);
+
+        // 仍然失败时保留条目并累加尝试次数
+        let outcome = dead_letter.retry().await.unwrap();
+        assert_eq!((outcome.delivered, outcome.remaining), (0, 1));
+        assert_eq!(dead_letter.entries().await.unwrap()[0].attempts, 2);
+
+        std::fs::remove_file(&blocker).unwrap();
+        let outcome = dead_letter.retry().await.unwrap();
+        assert_eq!((outcome.delivered, outcome.remaining), (1, 0));
+        assert!(dead_letter.entries().await.unwrap().is_empty());
+
+        let written: AuditLogEntry = serde_json::from_str(std::fs::read_to_string(&log_path).unwrap().trim()).unwrap();
+        assert_eq!(written.resource, "server.port");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_without_dead_letter_returns_error() {
+        let dir = std::env::temp_dir().join(format!("gemini-proxy-audit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("logs"), "").unwrap();
+
+        let mut manager = AuditLogManager::new(AuditConfig {
+            log_file_path: dir.join("logs").join("audit.log").to_string_lossy().to_string(),
+            ..AuditConfig::default()
+        });
+
+        assert!(manager.log_config_change(None, None, "server.port", "8080", "8443", "update").await.is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -21,6 +21,18 @@ pub enum ThreatLevel {
     Critical,
 }
 
+impl ThreatLevel {
+    /// 报告中显示的级别名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            ThreatLevel::Critical => "严重",
+            ThreatLevel::High => "高",
+            ThreatLevel::Medium => "中",
+            ThreatLevel::Low => "低",
+        }
+    }
+}
+
 /// 安全问题类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SecurityIssueType {
@@ -67,25 +79,7 @@ pub struct SecurityConfigValidator;
 impl SecurityConfigValidator {
     /// 全面的安全配置检查
     pub fn validate_security(config: &ProxyConfig) -> Result<SecurityAuditReport, GeminiProxyError> {
-        let mut issues = Vec::new();
-
-        // 认证安全检查
-        Self::check_authentication_security(&config.auth, &mut issues);
-        
-        // TLS 安全检查
-        Self::check_tls_security(config, &mut issues);
-        
-        // API 密钥安全检查
-        Self::check_api_key_security(config, &mut issues);
-        
-        // 网络配置安全检查
-        Self::check_network_security(config, &mut issues);
-        
-        // 日志安全检查
-        Self::check_logging_security(config, &mut issues);
-
-        // 生成安全审计报告
-        let report = SecurityAuditReport::new(issues);
+        let report = Self::audit_security(config);
         
         // 如果有严重安全问题，返回错误
         if report.has_critical_issues() {
@@ -107,6 +101,29 @@ impl SecurityConfigValidator {
         Ok(report)
     }
 
+    /// 生成完整的安全审计报告，严重问题也只记录在报告中而不返回错误
+    pub fn audit_security(config: &ProxyConfig) -> SecurityAuditReport {
+        let mut issues = Vec::new();
+
+        // 认证安全检查
+        Self::check_authentication_security(&config.auth, &mut issues);
+        
+        // TLS 安全检查
+        Self::check_tls_security(config, &mut issues);
+        
+        // API 密钥安全检查
+        Self::check_api_key_security(config, &mut issues);
+        
+        // 网络配置安全检查
+        Self::check_network_security(config, &mut issues);
+        
+        // 日志安全检查
+        Self::check_logging_security(config, &mut issues);
+
+        // 生成安全审计报告
+        SecurityAuditReport::new(issues)
+    }
+
     /// 检查认证配置安全性
     fn check_authentication_security(auth: &AuthConfig, issues: &mut Vec<SecurityIssue>) {
         if auth.enabled {
@@ -330,6 +347,20 @@ impl SecurityConfigValidator {
                 });
             }
         }
+
+        // API 服务器提供配置、密钥与权重等可修改的管理接口，明文 HTTP 会暴露管理员令牌
+        if config.metrics.enabled && !config.metrics.api_tls_enabled() {
+            issues.push(SecurityIssue {
+                id: "TLS_003".to_string(),
+                issue_type: SecurityIssueType::NetworkSecurity,
+                threat_level: ThreatLevel::High,
+                description: "API服务器以明文HTTP提供可修改配置的管理接口".to_string(),
+                affected_field: "metrics.tls".to_string(),
+                remediation: "为API服务器启用 metrics.tls，并设置 metrics.require_tls: true 禁止明文启动".to_string(),
+                cwe_id: Some(319), // CWE-319: Cleartext Transmission of Sensitive Information
+                impact: "管理员令牌与配置变更在网络传输中可能被窃听或篡改".to_string(),
+            });
+        }
     }
 
     /// 检查API密钥安全性
@@ -494,12 +525,7 @@ impl SecurityAuditReport {
             plan.push(format!(
                 "{}. [{}] {} - {} (字段: {})",
                 i + 1,
-                match issue.threat_level {
-                    ThreatLevel::Critical => "严重",
-                    ThreatLevel::High => "高",
-                    ThreatLevel::Medium => "中",
-                    ThreatLevel::Low => "低",
-                },
+                issue.threat_level.label(),
                 issue.description,
                 issue.remediation,
                 issue.affected_field
@@ -543,7 +569,7 @@ impl SecuritySummary {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{ProxyConfig, ServerConfig, GeminiConfig, AuthConfig, MetricsConfig, TlsConfig, ApiKeyConfig};
+    use crate::config::{ProxyConfig, ServerConfig, GeminiConfig, AuthConfig, MetricsConfig, TlsConfig, ApiKeyConfig, AuthFailureMode};
 
     fn create_insecure_config() -> ProxyConfig {
         ProxyConfig {
@@ -552,6 +578,12 @@ mod tests {
                 port: 8080,
                 workers: 4,
                 max_connections: 1000,
+                max_header_bytes: 64 * 1024,
+                max_header_count: 100,
+                max_inflight_requests: 0,
+                max_request_body_bytes: 10 * 1024 * 1024,
+                max_response_buffer_bytes: 10 * 1024 * 1024,
+                shutdown_grace_period_seconds: 30,
                 tls: TlsConfig {
                     enabled: false,
                     cert_path: "".to_string(),
@@ -565,9 +597,57 @@ mod tests {
                     key: "your-gemini-api-key-here".to_string(),
                     weight: 100,
                     max_requests_per_minute: 60,
+                    tenant: None,
+                    tags: Vec::new(),
+                    region: None,
+                    weight_min: None,
+                    weight_max: None,
+                    preferred_models: Vec::new(),
+                    allowed_models: Vec::new(),
+                    name: None,
+                    description: None,
                 }],
                 base_url: "https://generativelanguage.googleapis.com".to_string(),
                 timeout_seconds: 30,
+                request_timeout_seconds: None,
+                stream_timeout_seconds: 600,
+                stream_idle_timeout_seconds: 60,
+                key_stickiness_window_ms: 0,
+                key_soft_limit_ratio: 0.0,
+                preferred_model_boost: 3.0,
+                status_rewrites: vec![],
+                key_source: None,
+                model_costs: Default::default(),
+                model_override: Default::default(),
+                traffic_classes: vec![],
+                response_cache: Default::default(),
+                dedup: Default::default(),
+                warm_pool: Default::default(),
+                min_healthy_keys: 1,
+                shed_responses: Default::default(),
+                disabled_keys: vec![],
+                request_id: Default::default(),
+                account_quota_per_minute: None,
+                context_preflight: Default::default(),
+                weight_change: Default::default(),
+                route_allowlist: Default::default(),
+                required_headers: Vec::new(),
+                strip_response_fields: Vec::new(),
+                hash_routing: Default::default(),
+                region_routing: Default::default(),
+                soft_failure: Default::default(),
+                error_codes: Default::default(),
+                weight_bounds: Default::default(),
+                request_record: Default::default(),
+                response_headers: Default::default(),
+                adaptive_rate_limit: Default::default(),
+                failover: Default::default(),
+                key_cooldown: Default::default(),
+                failure_penalty: Default::default(),
+                model_fallback_chain: Default::default(),
+                circuit_breaker: Default::default(),
+                streaming_buffer: Default::default(),
+                scheduling: Default::default(),
             },
             auth: AuthConfig {
                 enabled: true,
@@ -577,14 +657,26 @@ mod tests {
                 token_expiry_hours: 48, // 过长
                 refresh_token_enabled: true,
                 session_timeout_minutes: 180, // 过长
+                session_max_lifetime_minutes: 0,
                 max_login_attempts: 20, // 过高
                 lockout_duration_minutes: 1, // 过短
+                failure_mode: AuthFailureMode::Closed,
+                verification_cache_ttl_seconds: 30,
+                verification_cache_max_entries: 10_000,
+                route_policies: Default::default(),
+                api_keys: Default::default(),
             },
             metrics: MetricsConfig {
                 enabled: false, // 未启用监控
                 prometheus_port: 9090,
                 tls: None,
+                require_tls: false,
+                business: Default::default(),
             },
+            health: Default::default(),
+            audit: Default::default(),
+            security: Default::default(),
+            persistence: Default::default(),
         }
     }
 
@@ -618,6 +710,27 @@ mod tests {
         assert!(issues.is_empty());
     }
 
+    #[test]
+    fn test_plain_http_api_server_flagged() {
+        let api_tls_issue = |config: &ProxyConfig| {
+            SecurityConfigValidator::audit_security(config)
+                .issues
+                .into_iter()
+                .find(|issue| issue.id == "TLS_003")
+        };
+        let mut config = create_insecure_config();
+        config.metrics.enabled = true;
+        assert_eq!(api_tls_issue(&config).unwrap().threat_level, ThreatLevel::High);
+
+        config.metrics.tls = Some(TlsConfig {
+            enabled: true,
+            cert_path: "certs/api-cert.pem".to_string(),
+            key_path: "certs/api-key.pem".to_string(),
+            acme: None,
+        });
+        assert!(api_tls_issue(&config).is_none());
+    }
+
     #[test]
     fn test_security_score_calculation() {
         let issues = vec![
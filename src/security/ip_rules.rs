@@ -0,0 +1,243 @@
+// src/security/ip_rules.rs
+//! 客户端 IP 访问规则
+//!
+//! `security.ip_rules` 配置允许与拒绝的 CIDR 列表（也可写单个地址），启动时解析。
+//! 拒绝列表优先；允许列表非空时只有其中的地址可以访问代理。同时支持 IPv4 与 IPv6，
+//! IPv4 映射的 IPv6 地址（`::ffff:a.b.c.d`）按 IPv4 地址匹配。
+//! `security.trusted_proxies` 列出受信任的反向代理，只有来自这些地址的请求才采用 `X-Forwarded-For`
+
+use crate::config::IpRulesConfig;
+use std::net::IpAddr;
+
+/// 拒绝原因（写入审计日志元数据）
+pub const IP_DENIED_REASON: &str = "ip_denied";
+
+/// CIDR 网段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// 解析 `10.0.0.0/8`、`2001:db8::/32` 或单个地址；主机位必须为 0
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let (address, prefix_len) = match value.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (value, None),
+        };
+        let network: IpAddr = address.parse().map_err(|_| format!("无效的 IP 地址: {}", address))?;
+        let max_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| format!("无效的前缀长度: {}（应为 0-{}）", prefix_len, max_len))?,
+            None => max_len,
+        };
+
+        let cidr = Self { network, prefix_len };
+        if cidr.masked(network) != network {
+            return Err(format!("{} 的主机位不为 0", value));
+        }
+        Ok(cidr)
+    }
+
+    /// 地址是否属于该网段（不同地址族不匹配）
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(_), ip @ IpAddr::V4(_)) | (IpAddr::V6(_), ip @ IpAddr::V6(_)) => self.masked(ip) == self.network,
+            _ => false,
+        }
+    }
+
+    fn masked(&self, ip: IpAddr) -> IpAddr {
+        match ip {
+            IpAddr::V4(v4) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len)).unwrap_or(0);
+                IpAddr::V4((u32::from(v4) & mask).into())
+            }
+            IpAddr::V6(v6) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len)).unwrap_or(0);
+                IpAddr::V6((u128::from(v6) & mask).into())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// 访问判定
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpDecision {
+    Allowed,
+    /// 命中拒绝列表中的网段
+    Denied(IpCidr),
+    /// 允许列表非空且未命中
+    NotAllowlisted,
+}
+
+/// 解析后的 IP 访问规则
+#[derive(Debug, Clone, Default)]
+pub struct IpRules {
+    allow: Vec<IpCidr>,
+    deny: Vec<IpCidr>,
+}
+
+impl IpRules {
+    /// 解析配置中的 CIDR，任一条目无效时返回错误
+    pub fn from_config(config: &IpRulesConfig) -> Result<Self, String> {
+        let parse = |field: &str, entries: &[String]| {
+            entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| IpCidr::parse(entry).map_err(|e| format!("security.ip_rules.{}[{}]: {}", field, i, e)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            allow: parse("allow", &config.allow)?,
+            deny: parse("deny", &config.deny)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// 判定客户端地址；无法获取地址（例如 Unix 套接字）时仅在配置了允许列表时拒绝
+    pub fn evaluate(&self, ip: Option<IpAddr>) -> IpDecision {
+        let Some(ip) = ip else {
+            return if self.allow.is_empty() {
+                IpDecision::Allowed
+            } else {
+                IpDecision::NotAllowlisted
+            };
+        };
+        if let Some(rule) = self.deny.iter().find(|cidr| cidr.contains(ip)) {
+            return IpDecision::Denied(*rule);
+        }
+        if self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip)) {
+            IpDecision::Allowed
+        } else {
+            IpDecision::NotAllowlisted
+        }
+    }
+}
+
+/// 受信任的反向代理
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<IpCidr>,
+}
+
+impl TrustedProxies {
+    /// 解析 `security.trusted_proxies`，任一条目无效时返回错误
+    pub fn from_config(entries: &[String]) -> Result<Self, String> {
+        let ranges = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| IpCidr::parse(entry).map_err(|e| format!("security.trusted_proxies[{}]: {}", i, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { ranges })
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// 确定客户端地址：对端不是受信任的代理时直接使用对端地址，客户端伪造的 `X-Forwarded-For` 不生效；
+    /// 否则从 `X-Forwarded-For` 右侧向左跳过受信任的代理，取第一个不受信任的地址
+    pub fn client_ip(&self, peer: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.is_trusted(peer) {
+            return Some(peer);
+        }
+        let mut client = peer;
+        for hop in forwarded_for.unwrap_or_default().rsplit(',') {
+            match hop.trim().parse::<IpAddr>() {
+                Ok(ip) if self.is_trusted(ip) => client = ip,
+                Ok(ip) => return Some(ip),
+                Err(_) => break,
+            }
+        }
+        Some(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(allow: &[&str], deny: &[&str]) -> IpRules {
+        IpRules::from_config(&IpRulesConfig {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn test_deny_takes_priority_over_allowlist() {
+        let rules = rules(&["10.0.0.0/8", "2001:db8::/32"], &["10.1.0.0/16", "2001:db8:bad::1"]);
+        assert_eq!(rules.evaluate(ip("10.2.3.4")), IpDecision::Allowed);
+        assert_eq!(rules.evaluate(ip("::ffff:10.2.3.4")), IpDecision::Allowed);
+        assert_eq!(rules.evaluate(ip("2001:db8:1::5")), IpDecision::Allowed);
+        assert_eq!(
+            rules.evaluate(ip("10.1.9.9")),
+            IpDecision::Denied(IpCidr::parse("10.1.0.0/16").unwrap())
+        );
+        assert!(matches!(rules.evaluate(ip("2001:db8:bad::1")), IpDecision::Denied(_)));
+        assert_eq!(rules.evaluate(ip("192.168.1.1")), IpDecision::NotAllowlisted);
+        assert_eq!(rules.evaluate(ip("2001:db9::1")), IpDecision::NotAllowlisted);
+        assert_eq!(rules.evaluate(None), IpDecision::NotAllowlisted);
+
+        // 只有拒绝列表时其余地址放行
+        let deny_only = self::rules(&[], &["0.0.0.0/0"]);
+        assert!(matches!(deny_only.evaluate(ip("8.8.8.8")), IpDecision::Denied(_)));
+        assert_eq!(deny_only.evaluate(ip("::1")), IpDecision::Allowed);
+        assert_eq!(deny_only.evaluate(None), IpDecision::Allowed);
+    }
+
+    #[test]
+    fn test_malformed_cidrs_rejected() {
+        for entry in ["10.0.0.0/33", "10.0.0.1/8", "2001:db8::/129", "not-an-ip", "10.0.0.0/", "fe80::/abc"] {
+            assert!(IpCidr::parse(entry).is_err(), "{} 应被拒绝", entry);
+        }
+        let err = IpRules::from_config(&IpRulesConfig {
+            allow: vec!["10.0.0.0/8".to_string()],
+            deny: vec!["1.2.3.4/40".to_string()],
+        })
+        .unwrap_err();
+        assert!(err.starts_with("security.ip_rules.deny[0]"));
+        assert_eq!(IpCidr::parse("0.0.0.0/0").unwrap().to_string(), "0.0.0.0/0");
+    }
+
+    #[test]
+    fn test_forwarded_for_only_honoured_from_trusted_proxies() {
+        let trusted = TrustedProxies::from_config(&["10.0.0.0/8".to_string()]).unwrap();
+        // 对端不受信任时忽略客户端伪造的请求头
+        assert_eq!(trusted.client_ip(ip("203.0.113.9"), Some("198.51.100.1")), ip("203.0.113.9"));
+        // 经过受信任代理时跳过代理链，取最右侧不受信任的地址
+        assert_eq!(trusted.client_ip(ip("10.0.0.2"), Some("198.51.100.1, 203.0.113.9, 10.0.0.5")), ip("203.0.113.9"));
+        assert_eq!(trusted.client_ip(ip("10.0.0.2"), None), ip("10.0.0.2"));
+        assert_eq!(trusted.client_ip(None, Some("198.51.100.1")), None);
+
+        // 未配置受信任代理时总是使用对端地址
+        let none = TrustedProxies::default();
+        assert_eq!(none.client_ip(ip("10.0.0.2"), Some("198.51.100.1")), ip("10.0.0.2"));
+        assert!(TrustedProxies::from_config(&["10.0.0.1/8".to_string()]).is_err());
+    }
+}
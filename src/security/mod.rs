@@ -6,6 +6,7 @@
 pub mod config_security;
 pub mod key_management;
 pub mod audit_logging;
+pub mod ip_rules;
 
 pub use config_security::*;
 pub use audit_logging::*;
\ No newline at end of file
@@ -0,0 +1,306 @@
+// src/utils/acme_dns.rs
+//! ACME DNS-01 验证
+//!
+//! DNS-01 通过在 `_acme-challenge.<域名>` 放置 TXT 记录证明域名所有权，不需要监听 80 端口，
+//! 也是签发通配符证书的唯一方式。记录由 [`DnsProvider`] 调用服务商 API 创建，
+//! 经 DNS-over-HTTPS 查询确认在公共 DNS 上可见后才通知 ACME 服务端验证，验证结束后删除
+
+use crate::config::{AcmeDnsConfig, CloudflareDnsConfig, DnsProviderKind};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const CHALLENGE_PREFIX: &str = "_acme-challenge";
+const CLOUDFLARE_API: &str = "https://api.cloudflare.com/client/v4";
+/// 用于确认记录生效的 DNS-over-HTTPS 解析服务（JSON 格式）
+const DOH_RESOLVER: &str = "https://cloudflare-dns.com/dns-query";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// TXT 记录类型编号
+const TXT_RECORD_TYPE: u64 = 16;
+
+/// DNS 服务商；方法均为阻塞调用，应在阻塞线程池中执行
+pub trait DnsProvider: Send + Sync {
+    /// 创建 TXT 记录，返回用于删除的记录 ID
+    fn create_txt_record(&self, name: &str, value: &str) -> Result<String, String>;
+
+    /// 删除 TXT 记录
+    fn delete_txt_record(&self, record_id: &str) -> Result<(), String>;
+
+    /// 查询公共 DNS 上的 TXT 记录值
+    fn lookup_txt(&self, name: &str) -> Result<Vec<String>, String> {
+        doh_lookup_txt(name)
+    }
+}
+
+/// 按配置创建 DNS 服务商
+pub fn dns_provider(config: &AcmeDnsConfig) -> Result<Arc<dyn DnsProvider>, String> {
+    match config.provider {
+        DnsProviderKind::Cloudflare => {
+            let cloudflare = config.cloudflare.clone().ok_or("使用 Cloudflare 时必须配置 cloudflare 凭据")?;
+            Ok(Arc::new(CloudflareDns::new(cloudflare)))
+        }
+    }
+}
+
+/// 域名的验证记录名称，通配符域名与其基础域名使用同一名称
+pub fn challenge_record_name(domain: &str) -> String {
+    format!("{}.{}", CHALLENGE_PREFIX, domain.trim_start_matches("*."))
+}
+
+/// 创建全部验证记录（名称与值）并等待生效，返回记录 ID；任一步失败时删除已创建的记录
+pub fn place_records(
+    provider: &dyn DnsProvider,
+    records: &[(String, String)],
+    config: &AcmeDnsConfig,
+) -> Result<Vec<String>, String> {
+    let mut record_ids = Vec::with_capacity(records.len());
+    let result = records
+        .iter()
+        .try_for_each(|(name, value)| {
+            record_ids.push(provider.create_txt_record(name, value)?);
+            tracing::info!("已创建 ACME 验证记录 {}", name);
+            Ok::<(), String>(())
+        })
+        .and_then(|()| {
+            records.iter().try_for_each(|(name, value)| {
+                wait_for_propagation(
+                    provider,
+                    name,
+                    value,
+                    Duration::from_secs(config.propagation_timeout_seconds),
+                    Duration::from_secs(config.propagation_poll_interval_seconds),
+                )
+            })
+        });
+
+    match result {
+        Ok(()) => Ok(record_ids),
+        Err(e) => {
+            remove_records(provider, &record_ids);
+            Err(e)
+        }
+    }
+}
+
+/// 删除验证记录，失败只记录警告（残留的 TXT 记录不影响后续验证）
+pub fn remove_records(provider: &dyn DnsProvider, record_ids: &[String]) {
+    for record_id in record_ids {
+        if let Err(e) = provider.delete_txt_record(record_id) {
+            tracing::warn!("删除 ACME 验证记录 {} 失败: {}", record_id, e);
+        }
+    }
+}
+
+/// 轮询直到记录值在公共 DNS 上可见，超时返回错误
+fn wait_for_propagation(
+    provider: &dyn DnsProvider,
+    name: &str,
+    value: &str,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match provider.lookup_txt(name) {
+            Ok(values) if values.iter().any(|v| v == value) => {
+                tracing::info!("ACME 验证记录 {} 已生效", name);
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) => tracing::debug!("查询 TXT 记录 {} 失败: {}", name, e),
+        }
+        if Instant::now() + interval > deadline {
+            return Err(format!("TXT 记录 {} 在 {} 秒内未生效", name, timeout.as_secs()));
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+/// Cloudflare DNS（API 令牌需具有 `Zone.DNS:Edit` 权限）
+pub struct CloudflareDns {
+    config: CloudflareDnsConfig,
+    agent: ureq::Agent,
+}
+
+impl CloudflareDns {
+    pub fn new(config: CloudflareDnsConfig) -> Self {
+        Self {
+            config,
+            agent: ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build(),
+        }
+    }
+
+    fn records_url(&self) -> String {
+        format!("{}/zones/{}/dns_records", CLOUDFLARE_API, self.config.zone_id)
+    }
+
+    fn authorization(&self) -> String {
+        format!("Bearer {}", self.config.api_token)
+    }
+}
+
+impl DnsProvider for CloudflareDns {
+    fn create_txt_record(&self, name: &str, value: &str) -> Result<String, String> {
+        let response = self
+            .agent
+            .post(&self.records_url())
+            .set("Authorization", &self.authorization())
+            .send_json(serde_json::json!({
+                "type": "TXT",
+                "name": name,
+                "content": value,
+                "ttl": 120,
+            }))
+            .map_err(cloudflare_error)?;
+        let body: Value = response.into_json().map_err(|e| e.to_string())?;
+        cloudflare_record_id(&body)
+    }
+
+    fn delete_txt_record(&self, record_id: &str) -> Result<(), String> {
+        self.agent
+            .delete(&format!("{}/{}", self.records_url(), record_id))
+            .set("Authorization", &self.authorization())
+            .call()
+            .map(|_| ())
+            .map_err(cloudflare_error)
+    }
+}
+
+fn cloudflare_error(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(status, response) => {
+            format!("Cloudflare API 返回 {}: {}", status, response.into_string().unwrap_or_default())
+        }
+        e => e.to_string(),
+    }
+}
+
+/// 从创建记录的响应中取出记录 ID
+fn cloudflare_record_id(body: &Value) -> Result<String, String> {
+    if body["success"].as_bool() != Some(true) {
+        return Err(format!("Cloudflare API 调用失败: {}", body["errors"]));
+    }
+    body["result"]["id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Cloudflare 响应缺少记录 ID".to_string())
+}
+
+/// 通过 DNS-over-HTTPS 查询 TXT 记录
+fn doh_lookup_txt(name: &str) -> Result<Vec<String>, String> {
+    let response = ureq::get(DOH_RESOLVER)
+        .timeout(HTTP_TIMEOUT)
+        .query("name", name)
+        .query("type", "TXT")
+        .set("Accept", "application/dns-json")
+        .call()
+        .map_err(|e| e.to_string())?;
+    let body: Value = response.into_json().map_err(|e| e.to_string())?;
+    Ok(txt_answers(&body))
+}
+
+/// 解析 DNS JSON 应答中的 TXT 值（去掉外层引号）
+fn txt_answers(body: &Value) -> Vec<String> {
+    body["Answer"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|answer| answer["type"].as_u64() == Some(TXT_RECORD_TYPE))
+        .filter_map(|answer| answer["data"].as_str())
+        .map(|data| data.trim_matches('"').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// 记录创建后经过若干次查询才可见的服务商
+    #[derive(Default)]
+    struct FakeDns {
+        records: Mutex<Vec<(String, String, String)>>,
+        lookups_until_visible: usize,
+        lookups: Mutex<usize>,
+    }
+
+    impl DnsProvider for FakeDns {
+        fn create_txt_record(&self, name: &str, value: &str) -> Result<String, String> {
+            let mut records = self.records.lock().unwrap();
+            let id = format!("rec-{}", records.len());
+            records.push((id.clone(), name.to_string(), value.to_string()));
+            Ok(id)
+        }
+
+        fn delete_txt_record(&self, record_id: &str) -> Result<(), String> {
+            self.records.lock().unwrap().retain(|(id, _, _)| id != record_id);
+            Ok(())
+        }
+
+        fn lookup_txt(&self, name: &str) -> Result<Vec<String>, String> {
+            let mut lookups = self.lookups.lock().unwrap();
+            *lookups += 1;
+            if *lookups <= self.lookups_until_visible {
+                return Ok(Vec::new());
+            }
+            let records = self.records.lock().unwrap();
+            Ok(records.iter().filter(|(_, n, _)| n == name).map(|(_, _, v)| v.clone()).collect())
+        }
+    }
+
+    fn dns_config(timeout_seconds: u64) -> AcmeDnsConfig {
+        AcmeDnsConfig {
+            provider: DnsProviderKind::Cloudflare,
+            cloudflare: None,
+            propagation_timeout_seconds: timeout_seconds,
+            propagation_poll_interval_seconds: 1,
+        }
+    }
+
+    #[test]
+    fn test_records_placed_after_propagation_and_cleaned_up_on_timeout() {
+        // 通配符域名与基础域名共用同一记录名称，两个值都需生效
+        let records = vec![
+            (challenge_record_name("*.example.com"), "proof-wildcard".to_string()),
+            (challenge_record_name("example.com"), "proof-base".to_string()),
+        ];
+        assert_eq!(records[0].0, "_acme-challenge.example.com");
+
+        let provider = FakeDns {
+            lookups_until_visible: 1,
+            ..FakeDns::default()
+        };
+        let record_ids = place_records(&provider, &records, &dns_config(5)).unwrap();
+        assert_eq!(record_ids, vec!["rec-0", "rec-1"]);
+        assert_eq!(*provider.lookups.lock().unwrap(), 3);
+        remove_records(&provider, &record_ids);
+        assert!(provider.records.lock().unwrap().is_empty());
+
+        // 记录始终不可见：超时后删除已创建的记录
+        let provider = FakeDns {
+            lookups_until_visible: usize::MAX,
+            ..FakeDns::default()
+        };
+        let err = place_records(&provider, &records, &dns_config(1)).unwrap_err();
+        assert!(err.contains("_acme-challenge.example.com"));
+        assert!(provider.records.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_cloudflare_and_doh_responses() {
+        let created = serde_json::json!({"success": true, "errors": [], "result": {"id": "372e6795"}});
+        assert_eq!(cloudflare_record_id(&created).unwrap(), "372e6795");
+        let failed = serde_json::json!({"success": false, "errors": [{"code": 9109, "message": "Invalid access token"}]});
+        assert!(cloudflare_record_id(&failed).unwrap_err().contains("Invalid access token"));
+
+        let answer = serde_json::json!({
+            "Status": 0,
+            "Answer": [
+                {"name": "_acme-challenge.example.com.", "type": 5, "data": "alias.example.net."},
+                {"name": "_acme-challenge.example.com.", "type": 16, "data": "\"proof-base\""}
+            ]
+        });
+        assert_eq!(txt_answers(&answer), vec!["proof-base"]);
+        assert!(txt_answers(&serde_json::json!({"Status": 3})).is_empty());
+    }
+}
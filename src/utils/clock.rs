@@ -0,0 +1,38 @@
+// src/utils/clock.rs
+//! 时间窗口计算
+//!
+//! 墙上时钟（`Utc::now()`）可能因 NTP 校正向后跳变，直接相减会得到负时长。
+//! 窗口判断优先使用单调时钟（`Instant`）；只能使用墙上时钟的场景通过这里的函数处理负时长
+
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// `earlier` 到 `now` 经过的时长，时钟回拨导致的负时长钳制为 0
+pub fn elapsed_since(earlier: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (now - earlier).to_std().unwrap_or_default()
+}
+
+/// 从 `started` 开始的窗口在 `now` 时是否已结束
+///
+/// 时钟回拨（`now` 早于 `started`）时视为窗口已结束并由调用方重新开始，
+/// 避免等待时钟追上之前的时间而把密钥锁定在旧窗口内
+pub fn window_expired(started: DateTime<Utc>, now: DateTime<Utc>, window: Duration) -> bool {
+    now < started || elapsed_since(started, now) >= window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backward_clock_step_is_clamped() {
+        let started = Utc::now();
+        let stepped_back = started - chrono::Duration::hours(1);
+
+        assert_eq!(elapsed_since(started, stepped_back), Duration::ZERO);
+        assert!(window_expired(started, stepped_back, Duration::from_secs(60)));
+
+        assert!(!window_expired(started, started + chrono::Duration::seconds(30), Duration::from_secs(60)));
+        assert!(window_expired(started, started + chrono::Duration::seconds(60), Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,97 @@
+// src/utils/concurrency.rs
+//! 有界并发执行工具
+//!
+//! 用于 ACME 域名验证、启动预热等需要并行但又不能压垮上游速率限制的场景
+
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// 以不超过 `limit` 的并发度对每个元素执行 `f`，结果按输入顺序返回
+///
+/// `limit` 为 0 时按 1 处理（即顺序执行）
+pub async fn run_bounded<T, R, F, Fut>(items: Vec<T>, limit: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(limit.max(1)));
+    let f = Arc::new(f);
+
+    let handles: Vec<_> = items
+        .into_iter()
+        .map(|item| {
+            let semaphore = semaphore.clone();
+            let f = f.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await.expect("semaphore closed");
+                f(item).await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(handle.await.expect("bounded task panicked"));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrency_bound_respected_for_multi_domain_renewal() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let domains: Vec<String> = (0..10).map(|i| format!("d{}.example.com", i)).collect();
+        let (in_flight_clone, peak_clone) = (in_flight.clone(), peak.clone());
+
+        // 模拟每个域名的订单验证耗时
+        let results = run_bounded(domains, 3, move |domain| {
+            let in_flight = in_flight_clone.clone();
+            let peak = peak_clone.clone();
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                format!("issued:{}", domain)
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 10);
+        assert_eq!(results[0], "issued:d0.example.com");
+        assert_eq!(results[9], "issued:d9.example.com");
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(peak.load(Ordering::SeqCst) > 1, "应当并行执行");
+    }
+
+    #[tokio::test]
+    async fn test_zero_limit_runs_sequentially() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let (in_flight_clone, peak_clone) = (in_flight.clone(), peak.clone());
+
+        run_bounded((0..4).collect::<Vec<u32>>(), 0, move |_| {
+            let in_flight = in_flight_clone.clone();
+            let peak = peak_clone.clone();
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            }
+        })
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,6 +1,10 @@
 // src/utils/health_check.rs
+use crate::config::ProxyConfig;
+use crate::utils::shutdown::ShutdownState;
+use crate::utils::upstream_probe::{UpstreamProbeStatus, UpstreamProber};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +12,62 @@ pub struct HealthStatus {
     pub status: String,
     pub timestamp: u64,
     pub checks: HashMap<String, CheckResult>,
+    /// 组件版本与启用的模块（仅在 health.verbose 开启时输出）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildInfo>,
+}
+
+/// 与 Cargo.toml 中的 pingora 依赖版本保持一致
+const PINGORA_VERSION: &str = "0.5.0";
+
+/// 构建与运行时组件信息，用于支持诊断
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    pub pingora_version: String,
+    pub tls_backend: String,
+    pub enabled_modules: Vec<String>,
+}
+
+impl BuildInfo {
+    /// 根据配置收集版本信息和已启用的模块
+    pub fn from_config(config: &ProxyConfig) -> Self {
+        let modules = [
+            ("tls", config.server.tls.enabled),
+            ("acme", config.server.tls.acme.as_ref().is_some_and(|acme| acme.enabled)),
+            ("auth", config.auth.enabled),
+            ("metrics", config.metrics.enabled),
+            ("api_tls", config.metrics.tls.as_ref().is_some_and(|tls| tls.enabled)),
+            ("key_source", config.gemini.key_source.is_some()),
+            ("key_stickiness", config.gemini.key_stickiness_window_ms > 0),
+            ("status_rewrites", !config.gemini.status_rewrites.is_empty()),
+            ("model_override", config.gemini.model_override.enabled),
+            ("cost_estimation", !config.gemini.model_costs.is_empty()),
+        ];
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            pingora_version: PINGORA_VERSION.to_string(),
+            tls_backend: "rustls (api) / openssl (acme)".to_string(),
+            enabled_modules: modules
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// 就绪检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessStatus {
+    pub ready: bool,
+    pub healthy_keys: usize,
+    pub min_healthy_keys: usize,
+    pub message: String,
+    /// 最近一次上游连通性探测结果（未启用探测时省略）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<UpstreamProbeStatus>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +82,12 @@ pub struct HealthChecker {
     api_keys_total: usize,
     api_keys_available: usize,
     config_loaded: bool,
+    build_info: Option<BuildInfo>,
+    min_healthy_keys: usize,
+    /// 停机状态，排空期间健康与就绪检查均报告不可用
+    shutdown: Option<Arc<ShutdownState>>,
+    /// 上游连通性探测，启用后就绪检查要求最近一次探测成功
+    upstream_probe: Option<Arc<UpstreamProber>>,
 }
 
 impl HealthChecker {
@@ -30,9 +96,78 @@ impl HealthChecker {
             api_keys_total,
             api_keys_available,
             config_loaded,
+            build_info: None,
+            min_healthy_keys: 1,
+            shutdown: None,
+            upstream_probe: None,
+        }
+    }
+
+    /// 设置就绪检查要求的最少健康密钥数
+    pub fn with_min_healthy_keys(mut self, min_healthy_keys: usize) -> Self {
+        self.min_healthy_keys = min_healthy_keys;
+        self
+    }
+
+    /// 关联停机状态
+    pub fn with_shutdown_state(mut self, shutdown: Arc<ShutdownState>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// 关联上游连通性探测
+    pub fn with_upstream_probe(mut self, upstream_probe: Arc<UpstreamProber>) -> Self {
+        self.upstream_probe = Some(upstream_probe);
+        self
+    }
+
+    /// 是否正在停机排空
+    pub fn is_draining(&self) -> bool {
+        self.shutdown.as_ref().is_some_and(|shutdown| shutdown.is_draining())
+    }
+
+    /// 就绪检查：未在停机排空、配置已加载、健康密钥数不低于阈值，且启用探测时最近一次探测上游可达
+    pub fn check_readiness(&self, healthy_keys: usize) -> ReadinessStatus {
+        let draining = self.is_draining();
+        let upstream = self.upstream_probe.as_ref().and_then(|probe| probe.latest());
+        // 启用探测但尚未完成首次探测时视为未就绪
+        let upstream_ok = match (&self.upstream_probe, &upstream) {
+            (None, _) => true,
+            (Some(_), Some(status)) => status.reachable,
+            (Some(_), None) => false,
+        };
+        let ready = !draining && self.config_loaded && healthy_keys >= self.min_healthy_keys && upstream_ok;
+        let message = if draining {
+            "Server is draining for shutdown".to_string()
+        } else if !self.config_loaded {
+            "Configuration not loaded".to_string()
+        } else if !upstream_ok {
+            match &upstream {
+                Some(status) => format!("Upstream unreachable: {}", status.message),
+                None => "Upstream probe pending".to_string(),
+            }
+        } else {
+            format!(
+                "{} healthy API keys (minimum {})",
+                healthy_keys, self.min_healthy_keys
+            )
+        };
+
+        ReadinessStatus {
+            ready,
+            healthy_keys,
+            min_healthy_keys: self.min_healthy_keys,
+            message,
+            upstream,
         }
     }
 
+    /// 在健康检查输出中附带构建信息（verbose 模式）
+    pub fn with_build_info(mut self, build_info: BuildInfo) -> Self {
+        self.build_info = Some(build_info);
+        self
+    }
+
     pub async fn check_health(&self) -> HealthStatus {
         let mut checks = HashMap::new();
         let mut overall_status = "healthy";
@@ -58,6 +193,19 @@ impl HealthChecker {
         }
         checks.insert("api_keys".to_string(), api_keys_result);
 
+        // 停机排空时整体状态为 draining，负载均衡据此摘除实例
+        if self.is_draining() {
+            overall_status = "draining";
+            checks.insert(
+                "shutdown".to_string(),
+                CheckResult {
+                    status: "draining".to_string(),
+                    message: "Server is draining in-flight requests before shutdown".to_string(),
+                    duration_ms: 0,
+                },
+            );
+        }
+
         HealthStatus {
             status: overall_status.to_string(),
             timestamp: SystemTime::now()
@@ -65,6 +213,7 @@ impl HealthChecker {
                 .unwrap()
                 .as_secs(),
             checks,
+            build: self.build_info.clone(),
         }
     }
 
@@ -138,4 +287,98 @@ impl Default for HealthChecker {
     fn default() -> Self {
         Self::new(0, 0, false)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL_CONFIG: &str = r#"
+server:
+  host: "127.0.0.1"
+  port: 8080
+  workers: 4
+  max_connections: 1000
+  tls:
+    enabled: false
+    cert_path: ""
+    key_path: ""
+gemini:
+  api_keys:
+    - id: "primary"
+      key: "test-key"
+      weight: 100
+      max_requests_per_minute: 60
+  base_url: "https://generativelanguage.googleapis.com"
+  timeout_seconds: 30
+auth:
+  enabled: true
+  jwt_secret: "a-very-long-jwt-secret-for-health-tests"
+  rate_limit_per_minute: 60
+  admin_password: "admin-password"
+  token_expiry_hours: 8
+  refresh_token_enabled: true
+  session_timeout_minutes: 30
+  max_login_attempts: 5
+  lockout_duration_minutes: 15
+metrics:
+  enabled: true
+  prometheus_port: 9090
+"#;
+
+    #[tokio::test]
+    async fn test_verbose_health_includes_versions() {
+        let config: ProxyConfig = serde_yaml::from_str(MINIMAL_CONFIG).unwrap();
+        let checker = HealthChecker::new(1, 1, true).with_build_info(BuildInfo::from_config(&config));
+
+        let json = serde_json::to_value(checker.check_health().await).unwrap();
+
+        let build = &json["build"];
+        assert_eq!(build["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(build["pingora_version"], PINGORA_VERSION);
+        assert!(build["tls_backend"].is_string());
+        let modules = build["enabled_modules"].as_array().unwrap();
+        assert!(modules.iter().any(|m| m == "auth"));
+        assert!(!modules.iter().any(|m| m == "tls"));
+    }
+
+    #[test]
+    fn test_readiness_requires_min_healthy_keys() {
+        let checker = HealthChecker::new(3, 3, true).with_min_healthy_keys(2);
+
+        let status = checker.check_readiness(3);
+        assert!(status.ready);
+        assert!(checker.check_readiness(2).ready);
+
+        let status = checker.check_readiness(1);
+        assert!(!status.ready);
+        assert_eq!(status.healthy_keys, 1);
+        assert_eq!(status.min_healthy_keys, 2);
+
+        assert!(!HealthChecker::new(3, 3, false).check_readiness(3).ready);
+    }
+
+    #[tokio::test]
+    async fn test_draining_reported_in_health_and_readiness() {
+        let shutdown = Arc::new(ShutdownState::new());
+        let checker = HealthChecker::new(1, 1, true).with_shutdown_state(shutdown.clone());
+        assert!(checker.check_readiness(1).ready);
+        assert_eq!(checker.check_health().await.status, "healthy");
+
+        shutdown.begin_draining();
+        assert!(!checker.check_readiness(1).ready);
+        let health = checker.check_health().await;
+        assert_eq!(health.status, "draining");
+        assert_eq!(health.checks["shutdown"].status, "draining");
+    }
+
+    #[tokio::test]
+    async fn test_default_health_omits_versions() {
+        let checker = HealthChecker::new(1, 1, true);
+
+        let json = serde_json::to_value(checker.check_health().await).unwrap();
+
+        assert_eq!(json["status"], "healthy");
+        assert!(json.get("build").is_none());
+    }
+}
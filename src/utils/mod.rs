@@ -1,4 +1,11 @@
 pub mod health_check;
 pub mod tls;
+pub mod acme_dns;
 pub mod performance;
 pub mod error;
+pub mod concurrency;
+pub mod redaction;
+pub mod clock;
+pub mod startup;
+pub mod shutdown;
+pub mod upstream_probe;
@@ -1,4 +1,6 @@
 // src/utils/performance.rs
+use crate::proxy::inflight_limit::InflightLimiter;
+use crate::proxy::warm_pool::{UpstreamConnectionStats, UpstreamWarmPool, WarmPoolStats};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -224,6 +226,16 @@ pub struct PerformanceStats {
     pub connection_usage: f64,
     pub memory_usage_bytes: Option<u64>,
     pub uptime_seconds: u64,
+    /// 代理当前的在途请求数
+    pub inflight_requests: u64,
+    /// 全进程在途请求上限（None 表示不限制）
+    pub max_inflight_requests: Option<u64>,
+    /// 转发请求复用已有上游连接的次数
+    pub upstream_connections_reused: u64,
+    /// 转发请求新建上游连接的次数
+    pub upstream_connections_new: u64,
+    /// 上游连接预热池状态（未启用时为 None）
+    pub warm_pool: Option<WarmPoolStats>,
 }
 
 /// 性能优化工具
@@ -232,6 +244,9 @@ pub struct PerformanceOptimizer {
     performance_monitor: Arc<PerformanceMonitor>,
     connection_monitor: Arc<ConnectionPoolMonitor>,
     memory_monitor: Arc<MemoryMonitor>,
+    inflight_limiter: Option<Arc<InflightLimiter>>,
+    upstream_connections: Option<Arc<UpstreamConnectionStats>>,
+    warm_pool: Option<Arc<UpstreamWarmPool>>,
 }
 
 impl PerformanceOptimizer {
@@ -241,14 +256,37 @@ impl PerformanceOptimizer {
             performance_monitor: Arc::new(PerformanceMonitor::new()),
             connection_monitor: Arc::new(ConnectionPoolMonitor::new(max_connections)),
             memory_monitor: Arc::new(MemoryMonitor::new()),
+            inflight_limiter: None,
+            upstream_connections: None,
+            warm_pool: None,
         }
     }
 
+    /// 关联代理的全进程在途请求上限，统计中输出当前在途请求数
+    pub fn with_inflight_limiter(mut self, inflight_limiter: Arc<InflightLimiter>) -> Self {
+        self.inflight_limiter = Some(inflight_limiter);
+        self
+    }
+
+    /// 关联代理的上游连接复用计数
+    pub fn with_upstream_connection_stats(mut self, stats: Arc<UpstreamConnectionStats>) -> Self {
+        self.upstream_connections = Some(stats);
+        self
+    }
+
+    /// 关联上游连接预热池，统计中输出预热连接数与命中情况
+    pub fn with_warm_pool(mut self, warm_pool: Arc<UpstreamWarmPool>) -> Self {
+        self.warm_pool = Some(warm_pool);
+        self
+    }
+
     pub async fn get_performance_stats(&self) -> PerformanceStats {
         let memory_usage = self.memory_monitor.get_current_memory_usage();
         if let Some(usage) = memory_usage {
             self.memory_monitor.update_memory_usage(usage);
         }
+        let (connections_reused, connections_new) =
+            self.upstream_connections.as_ref().map_or((0, 0), |stats| stats.counts());
 
         PerformanceStats {
             qps: self.performance_monitor.get_qps(),
@@ -259,6 +297,11 @@ impl PerformanceOptimizer {
             connection_usage: self.connection_monitor.get_connection_usage(),
             memory_usage_bytes: memory_usage,
             uptime_seconds: self.performance_monitor.get_uptime().as_secs(),
+            inflight_requests: self.inflight_limiter.as_ref().map_or(0, |l| l.in_flight() as u64),
+            max_inflight_requests: self.inflight_limiter.as_ref().and_then(|l| l.max()).map(|max| max as u64),
+            upstream_connections_reused: connections_reused,
+            upstream_connections_new: connections_new,
+            warm_pool: self.warm_pool.as_ref().map(|pool| pool.stats()),
         }
     }
 
@@ -0,0 +1,106 @@
+// src/utils/redaction.rs
+//! 敏感信息脱敏
+//!
+//! 对外输出配置或诊断数据（例如支持包）时统一使用 [`Redactor`]：
+//! 按字段名屏蔽密钥、密码、令牌等字段，并把文本中出现的已知密钥值替换为掩码
+
+use crate::config::ProxyConfig;
+use serde_json::Value;
+
+/// 脱敏后的占位符
+pub const REDACTED: &str = "***REDACTED***";
+
+/// 视为敏感字段的字段名片段（不区分大小写）
+const SENSITIVE_FIELD_PATTERNS: &[&str] = &["secret", "password", "token", "api_key", "apikey", "authorization"];
+
+/// 判断字段名是否敏感
+pub fn is_sensitive_field(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name == "key" || SENSITIVE_FIELD_PATTERNS.iter().any(|pattern| name.contains(pattern))
+}
+
+/// 脱敏器：持有配置中的全部已知密钥值
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    /// 收集配置中的密钥值（API 密钥、JWT 密钥、管理员密码）
+    pub fn from_config(config: &ProxyConfig) -> Self {
+        let mut secrets: Vec<String> = config
+            .gemini
+            .api_keys
+            .iter()
+            .map(|key| key.key.clone())
+            .chain([config.auth.jwt_secret.clone(), config.auth.admin_password.clone()])
+            .filter(|secret| !secret.is_empty())
+            .collect();
+        // 先替换较长的值，避免较短的密钥是较长密钥的子串时留下残余
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        secrets.dedup();
+        Self { secrets }
+    }
+
+    /// 替换文本中出现的已知密钥值
+    pub fn redact_str(&self, text: &str) -> String {
+        self.secrets
+            .iter()
+            .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), REDACTED))
+    }
+
+    /// 返回脱敏后的配置副本
+    pub fn redact_config(&self, config: &ProxyConfig) -> ProxyConfig {
+        let mut config = config.clone();
+        for key in &mut config.gemini.api_keys {
+            key.key = REDACTED.to_string();
+        }
+        config.auth.jwt_secret = REDACTED.to_string();
+        config.auth.admin_password = REDACTED.to_string();
+        config
+    }
+
+    /// 递归脱敏 JSON：敏感字段整体屏蔽，其余字符串替换已知密钥值
+    pub fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (name, field) in map.iter_mut() {
+                    if is_sensitive_field(name) && field.is_string() {
+                        *field = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_json(field);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_json(item)),
+            Value::String(text) => *text = self.redact_str(text),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_json_masks_fields_and_known_values() {
+        let redactor = Redactor {
+            secrets: vec!["AIzaSy-super-secret".to_string()],
+        };
+        let mut value = serde_json::json!({
+            "key_id": "primary",
+            "jwt_secret": "anything",
+            "nested": [{"message": "invalid key AIzaSy-super-secret rejected"}],
+            "max_output_tokens": 5,
+        });
+
+        redactor.redact_json(&mut value);
+
+        assert_eq!(value["key_id"], "primary");
+        assert_eq!(value["jwt_secret"], REDACTED);
+        assert_eq!(value["nested"][0]["message"], format!("invalid key {} rejected", REDACTED));
+        // 只屏蔽字符串值，令牌数等数值字段保持不变
+        assert_eq!(value["max_output_tokens"], 5);
+    }
+}
@@ -0,0 +1,165 @@
+// src/utils/shutdown.rs
+//! 优雅停机
+//!
+//! 收到 SIGTERM/SIGINT 后先进入排空状态：`/health` 与 `/health/ready` 返回 503 让负载均衡摘除实例，
+//! 代理对新请求返回 `draining` 卸载响应并关闭连接；随后最多等待 `server.shutdown_grace_period_seconds`
+//! 让在途请求完成，再落盘审计日志，最后交给 Pingora 关闭监听与运行时。
+//! 持久化存储每次写入都已同步落盘，停机时无需额外刷新。SIGQUIT（平滑升级）保持 Pingora 的默认行为
+
+use crate::proxy::inflight_limit::InflightLimiter;
+use crate::security::{AuditResult, SharedAuditLog};
+use async_trait::async_trait;
+use pingora::server::{ShutdownSignal, ShutdownSignalWatch};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::Instant;
+
+/// 排空期间检查在途请求数的间隔
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 停机状态，由代理、健康检查与信号处理共享
+#[derive(Debug, Default)]
+pub struct ShutdownState {
+    draining: AtomicBool,
+}
+
+impl ShutdownState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 进入排空状态（不可撤销）
+    pub fn begin_draining(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    /// 是否正在停机排空
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+}
+
+/// 等待在途请求全部完成，最多等待 `grace_period`，返回超时后仍在途的请求数
+pub async fn wait_for_drain(inflight: &InflightLimiter, grace_period: Duration, poll_interval: Duration) -> usize {
+    let deadline = Instant::now() + grace_period;
+    loop {
+        let in_flight = inflight.in_flight();
+        if in_flight == 0 || Instant::now() >= deadline {
+            return in_flight;
+        }
+        tokio::time::sleep(poll_interval.min(deadline - Instant::now())).await;
+    }
+}
+
+/// 替换 Pingora 默认信号处理的停机流程：排空在途请求并落盘后再通知 Pingora 退出
+pub struct DrainingShutdown {
+    state: Arc<ShutdownState>,
+    inflight: Arc<InflightLimiter>,
+    grace_period: Duration,
+    audit_log: Option<SharedAuditLog>,
+}
+
+impl DrainingShutdown {
+    pub fn new(state: Arc<ShutdownState>, inflight: Arc<InflightLimiter>, grace_period: Duration) -> Self {
+        Self {
+            state,
+            inflight,
+            grace_period,
+            audit_log: None,
+        }
+    }
+
+    /// 停机前记录审计事件并同步审计日志
+    pub fn with_audit_log(mut self, audit_log: SharedAuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 排空在途请求；排空期间再次收到 SIGTERM/SIGINT 时立即停止等待
+    async fn drain(&self, signal_name: &str) {
+        tracing::info!(
+            "收到 {}，开始排空 {} 个在途请求（最多等待 {} 秒）",
+            signal_name,
+            self.inflight.in_flight(),
+            self.grace_period.as_secs()
+        );
+        self.state.begin_draining();
+
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        let mut interrupt = signal(SignalKind::interrupt()).unwrap();
+        let remaining = tokio::select! {
+            remaining = wait_for_drain(&self.inflight, self.grace_period, DRAIN_POLL_INTERVAL) => remaining,
+            _ = terminate.recv() => self.inflight.in_flight(),
+            _ = interrupt.recv() => self.inflight.in_flight(),
+        };
+        if remaining > 0 {
+            tracing::warn!("排空结束时仍有 {} 个在途请求，将被中止", remaining);
+        } else {
+            tracing::info!("在途请求已全部完成");
+        }
+
+        if let Some(audit_log) = &self.audit_log {
+            let mut audit_log = audit_log.lock().await;
+            let details = format!("信号 {}，中止在途请求 {} 个", signal_name, remaining);
+            let result = if remaining == 0 { AuditResult::Success } else { AuditResult::Failure };
+            if let Err(e) = audit_log.log_system_operation("优雅停机", "server", result, Some(details)).await {
+                tracing::warn!("记录审计日志失败: {}", e);
+            }
+            match audit_log.flush().await {
+                Ok(0) => {}
+                Ok(pending) => tracing::warn!("仍有 {} 条审计死信记录未投递", pending),
+                Err(e) => tracing::warn!("同步审计日志失败: {}", e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ShutdownSignalWatch for DrainingShutdown {
+    async fn recv(&self) -> ShutdownSignal {
+        let mut upgrade = signal(SignalKind::quit()).unwrap();
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        let mut interrupt = signal(SignalKind::interrupt()).unwrap();
+
+        let signal_name = tokio::select! {
+            _ = upgrade.recv() => return ShutdownSignal::GracefulUpgrade,
+            _ = terminate.recv() => "SIGTERM",
+            _ = interrupt.recv() => "SIGINT",
+        };
+        self.drain(signal_name).await;
+        ShutdownSignal::GracefulTerminate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_in_flight_requests_up_to_grace_period() {
+        let inflight = InflightLimiter::new(0);
+        let poll = Duration::from_millis(5);
+
+        // 在宽限期内完成的请求会被等到
+        let permit = inflight.try_acquire().unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            drop(permit);
+        });
+        assert_eq!(wait_for_drain(&inflight, Duration::from_secs(5), poll).await, 0);
+        release.await.unwrap();
+
+        // 超过宽限期仍未完成时返回剩余的在途请求数
+        let _stuck = inflight.try_acquire().unwrap();
+        let started = Instant::now();
+        assert_eq!(wait_for_drain(&inflight, Duration::from_millis(50), poll).await, 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let state = ShutdownState::new();
+        assert!(!state.is_draining());
+        state.begin_draining();
+        assert!(state.is_draining());
+    }
+}
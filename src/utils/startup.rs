@@ -0,0 +1,133 @@
+// src/utils/startup.rs
+//! 启动时的外部依赖检查
+//!
+//! 外部依赖（例如外部密钥来源）在启动时不可用时，按配置决定行为：`block` 模式阻塞启动并按间隔重试，
+//! 重试耗尽后返回错误由调用方退出；`degraded` 模式只尝试一次，失败时记录哪些功能处于降级状态后继续启动，
+//! 由依赖自身的后台任务在恢复后补齐
+
+use crate::config::{StartupDependencyConfig, StartupMode};
+use crate::error::{GeminiProxyError, Result};
+use std::future::Future;
+use std::time::Duration;
+
+/// 检查启动依赖
+///
+/// 依赖可用时返回 `Some`，降级启动时返回 `None`，阻塞模式重试耗尽时返回错误
+pub async fn await_dependency<T, F, Fut>(
+    name: &str,
+    degraded_features: &str,
+    config: &StartupDependencyConfig,
+    mut check: F,
+) -> Result<Option<T>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let max_attempts = match config.mode {
+        StartupMode::Block => config.max_attempts.max(1),
+        StartupMode::Degraded => 1,
+    };
+
+    let mut attempt = 1;
+    loop {
+        match check().await {
+            Ok(value) => {
+                if attempt > 1 {
+                    tracing::info!(dependency = name, attempt, "启动依赖已可用");
+                }
+                return Ok(Some(value));
+            }
+            Err(e) if attempt < max_attempts => {
+                tracing::warn!(
+                    dependency = name,
+                    attempt,
+                    max_attempts,
+                    "启动依赖不可用，{}ms 后重试: {}",
+                    config.retry_interval_ms,
+                    e
+                );
+                tokio::time::sleep(Duration::from_millis(config.retry_interval_ms)).await;
+                attempt += 1;
+            }
+            Err(e) if config.mode == StartupMode::Block => {
+                return Err(GeminiProxyError::config_with_context(
+                    format!("启动依赖 {} 在 {} 次尝试后仍不可用: {}", name, attempt, e),
+                    name,
+                    "startup",
+                ));
+            }
+            Err(e) => {
+                tracing::warn!(dependency = name, degraded = degraded_features, "启动依赖不可用，以降级模式继续启动: {}", e);
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_balancer::key_source::{DirectoryKeySource, KeySourceWatcher};
+    use crate::load_balancer::UnifiedKeyManager;
+    use std::sync::Arc;
+
+    fn config(mode: StartupMode) -> StartupDependencyConfig {
+        StartupDependencyConfig {
+            mode,
+            max_attempts: 3,
+            retry_interval_ms: 1,
+        }
+    }
+
+    fn unavailable_key_source() -> (tempfile::TempDir, KeySourceWatcher) {
+        let dir = tempfile::tempdir().unwrap();
+        let source = Arc::new(DirectoryKeySource::new(dir.path().join("missing"), 100, 60));
+        let watcher = KeySourceWatcher::new(source, Arc::new(UnifiedKeyManager::new(vec![])), vec![]);
+        (dir, watcher)
+    }
+
+    #[tokio::test]
+    async fn test_block_mode_retries_then_fails() {
+        let (_dir, watcher) = unavailable_key_source();
+        let mut attempts = 0;
+        let result = await_dependency("key_source", "外部密钥", &config(StartupMode::Block), || {
+            attempts += 1;
+            watcher.refresh()
+        })
+        .await;
+
+        assert_eq!(attempts, 3);
+        assert!(result.unwrap_err().to_string().contains("3 次尝试"));
+
+        // 依赖在重试期间恢复时继续启动
+        let mut attempts = 0;
+        let result = await_dependency("notifier", "告警通知", &config(StartupMode::Block), || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt >= 2 {
+                    Ok(attempt)
+                } else {
+                    Err(GeminiProxyError::network("connection refused"))
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_degraded_mode_proceeds_without_dependency() {
+        let (_dir, watcher) = unavailable_key_source();
+        let mut attempts = 0;
+        let result = await_dependency("key_source", "外部密钥", &config(StartupMode::Degraded), || {
+            attempts += 1;
+            watcher.refresh()
+        })
+        .await;
+
+        // 只尝试一次，不阻塞启动
+        assert_eq!(attempts, 1);
+        assert!(result.unwrap().is_none());
+    }
+}
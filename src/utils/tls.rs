@@ -1,6 +1,8 @@
 // src/utils/tls.rs
-use crate::config::AcmeConfig;
+use crate::config::{AcmeChallengeType, AcmeConfig};
 use crate::proxy::acme_service::AcmeChallengeState;
+use crate::utils::acme_dns::{challenge_record_name, dns_provider, place_records, remove_records};
+use crate::utils::concurrency::run_bounded;
 use acme_lib::persist::FilePersist;
 use acme_lib::{Directory, DirectoryUrl};
 use openssl::x509::X509;
@@ -93,19 +95,36 @@ pub async fn manage_acme_certificate(
         }
 
         let auths = ord.authorizations()?;
-        let chall = auths[0].http_challenge();
-
-        let token = chall.http_token();
-        // acme-lib 0.5.2: The key authorization is the token for HTTP challenge
-        let key_auth = token.to_string();
+        if config.challenge == AcmeChallengeType::Dns01 {
+            complete_dns_challenges(config, &auths).await?;
+            ord.refresh()?;
+            continue;
+        }
+        let challenges: Vec<_> = auths.iter().map(|auth| auth.http_challenge()).collect();
 
         {
             let mut state = challenge_state.write().unwrap();
-            state.insert(token.to_string(), key_auth.to_string());
-            tracing::info!("ACME challenge token set for domain validation.");
+            for chall in &challenges {
+                let token = chall.http_token();
+                // acme-lib 0.5.2: The key authorization is the token for HTTP challenge
+                let key_auth = token.to_string();
+                state.insert(token.to_string(), key_auth);
+            }
+            tracing::info!("ACME challenge tokens set for {} domain(s).", challenges.len());
+        }
+
+        // 多个域名的验证以有界并发执行，避免触发 ACME 服务端速率限制
+        let results = run_bounded(challenges, config.max_concurrent_validations, |chall| async move {
+            match tokio::task::spawn_blocking(move || chall.validate(5000).map_err(|e| e.to_string())).await {
+                Ok(result) => result,
+                Err(e) => Err(e.to_string()),
+            }
+        })
+        .await;
+        for result in results {
+            result?;
         }
 
-        chall.validate(5000)?;
         ord.refresh()?;
         tokio::time::sleep(Duration::from_secs(2)).await;
     };
@@ -122,6 +141,42 @@ pub async fn manage_acme_certificate(
     Ok(())
 }
 
+/// DNS-01 验证：放置全部 TXT 记录并等待生效，再以有界并发通知 ACME 服务端验证，最后删除记录
+async fn complete_dns_challenges(
+    config: &AcmeConfig,
+    auths: &[acme_lib::order::Auth<FilePersist>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dns_config = config.dns.clone().ok_or("DNS-01 验证缺少 dns 配置")?;
+    let provider = dns_provider(&dns_config)?;
+
+    let pending: Vec<_> = auths.iter().filter(|auth| auth.need_challenge()).collect();
+    let records: Vec<(String, String)> = pending
+        .iter()
+        .map(|auth| (challenge_record_name(auth.domain_name()), auth.dns_challenge().dns_proof()))
+        .collect();
+    let challenges: Vec<_> = pending.iter().map(|auth| auth.dns_challenge()).collect();
+
+    let placing = provider.clone();
+    let record_ids = tokio::task::spawn_blocking(move || place_records(placing.as_ref(), &records, &dns_config))
+        .await??;
+    tracing::info!("ACME DNS records propagated for {} domain(s).", record_ids.len());
+
+    let results = run_bounded(challenges, config.max_concurrent_validations, |chall| async move {
+        match tokio::task::spawn_blocking(move || chall.validate(5000).map_err(|e| e.to_string())).await {
+            Ok(result) => result,
+            Err(e) => Err(e.to_string()),
+        }
+    })
+    .await;
+
+    // 无论验证是否成功都删除记录
+    tokio::task::spawn_blocking(move || remove_records(provider.as_ref(), &record_ids)).await?;
+    for result in results {
+        result?;
+    }
+    Ok(())
+}
+
 const RENEW_BEFORE_DAYS: i32 = 30;
 
 fn needs_renewal(cert_path: &str) -> Result<bool, Box<dyn std::error::Error>> {
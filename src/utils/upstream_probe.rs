@@ -0,0 +1,198 @@
+// src/utils/upstream_probe.rs
+//! 上游连通性探测
+//!
+//! 定期用一个当前可用的密钥向 Gemini 发送轻量的模型列表请求，缓存最近一次结果供 `/health/ready` 使用。
+//! 连接失败、超时或上游返回 5xx 视为不可达；其他状态码说明上游可达（密钥本身的问题由健康密钥数反映）。
+//! 没有可用密钥时跳过探测，保留上一次结果
+
+use crate::config::UpstreamProbeConfig;
+use crate::load_balancer::UnifiedKeyManager;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// 最近一次探测结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamProbeStatus {
+    pub reachable: bool,
+    pub checked_at: DateTime<Utc>,
+    pub latency_ms: u64,
+    /// 探测使用的密钥
+    pub key_id: String,
+    pub message: String,
+}
+
+/// 发送探测请求，返回上游状态码；方法为阻塞调用，在阻塞线程池中执行
+pub trait ProbeTransport: Send + Sync {
+    fn get(&self, url: &str, api_key: &str, timeout: Duration) -> Result<u16, String>;
+}
+
+/// 基于 ureq 的探测请求
+pub struct HttpProbeTransport;
+
+impl ProbeTransport for HttpProbeTransport {
+    fn get(&self, url: &str, api_key: &str, timeout: Duration) -> Result<u16, String> {
+        match ureq::get(url).timeout(timeout).set("x-goog-api-key", api_key).call() {
+            Ok(response) => Ok(response.status()),
+            Err(ureq::Error::Status(status, _)) => Ok(status),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// 探测请求的完整地址；`base_url` 可以是 `host:port` 或带协议的地址
+pub fn probe_url(base_url: &str, path: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    if base.starts_with("http://") || base.starts_with("https://") {
+        format!("{}{}", base, path)
+    } else {
+        format!("https://{}{}", base, path)
+    }
+}
+
+/// 上游探测器，由后台任务定期执行，健康检查读取缓存的结果
+pub struct UpstreamProber {
+    url: String,
+    interval: Duration,
+    timeout: Duration,
+    key_manager: Arc<UnifiedKeyManager>,
+    transport: Arc<dyn ProbeTransport>,
+    latest: RwLock<Option<UpstreamProbeStatus>>,
+}
+
+impl UpstreamProber {
+    pub fn new(config: &UpstreamProbeConfig, base_url: &str, key_manager: Arc<UnifiedKeyManager>) -> Self {
+        Self {
+            url: probe_url(base_url, &config.path),
+            interval: Duration::from_secs(config.interval_seconds),
+            timeout: Duration::from_secs(config.timeout_seconds),
+            key_manager,
+            transport: Arc::new(HttpProbeTransport),
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// 替换探测请求的发送方式
+    #[cfg(test)]
+    pub fn with_transport(mut self, transport: Arc<dyn ProbeTransport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// 最近一次探测结果（尚未探测时为 None）
+    pub fn latest(&self) -> Option<UpstreamProbeStatus> {
+        self.latest.read().unwrap().clone()
+    }
+
+    /// 执行一次探测并缓存结果；没有可用密钥时跳过
+    pub async fn probe_once(&self) -> Option<UpstreamProbeStatus> {
+        let key = self
+            .key_manager
+            .get_key_states()
+            .await
+            .into_iter()
+            .find(|key| key.is_available())?;
+
+        let transport = self.transport.clone();
+        let url = self.url.clone();
+        let api_key = key.key.clone();
+        let timeout = self.timeout;
+        let started = Instant::now();
+        let result = tokio::task::spawn_blocking(move || transport.get(&url, &api_key, timeout))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+
+        let (reachable, message) = match result {
+            Ok(status) if status >= 500 => (false, format!("上游返回 HTTP {}", status)),
+            Ok(status) => (true, format!("上游返回 HTTP {}", status)),
+            Err(e) => (false, format!("上游不可达: {}", e)),
+        };
+        if !reachable {
+            tracing::warn!("上游探测失败（密钥 {}）: {}", key.id, message);
+        }
+        let status = UpstreamProbeStatus {
+            reachable,
+            checked_at: Utc::now(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            key_id: key.id,
+            message,
+        };
+        *self.latest.write().unwrap() = Some(status.clone());
+        Some(status)
+    }
+
+    /// 按配置的间隔持续探测
+    pub async fn run(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            self.probe_once().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_balancer::key_manager::ApiKey;
+    use std::sync::Mutex;
+
+    /// 依次返回预设结果的探测请求
+    struct ScriptedTransport {
+        results: Mutex<Vec<Result<u16, String>>>,
+        calls: Mutex<Vec<(String, String)>>,
+    }
+
+    impl ProbeTransport for ScriptedTransport {
+        fn get(&self, url: &str, api_key: &str, _timeout: Duration) -> Result<u16, String> {
+            self.calls.lock().unwrap().push((url.to_string(), api_key.to_string()));
+            self.results.lock().unwrap().remove(0)
+        }
+    }
+
+    fn api_key(id: &str) -> ApiKey {
+        ApiKey {
+            key: format!("secret-{}", id),
+            ..ApiKey::for_test(id)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_uses_available_key_and_reports_unreachable_upstream() {
+        let key_manager = Arc::new(UnifiedKeyManager::new(vec![api_key("primary")]));
+        let transport = Arc::new(ScriptedTransport {
+            results: Mutex::new(vec![Ok(200), Ok(503), Err("connection refused".to_string())]),
+            calls: Mutex::new(Vec::new()),
+        });
+        let prober = UpstreamProber::new(
+            &UpstreamProbeConfig::default(),
+            "generativelanguage.googleapis.com:443",
+            key_manager.clone(),
+        )
+        .with_transport(transport.clone());
+        assert!(prober.latest().is_none());
+
+        assert!(prober.probe_once().await.unwrap().reachable);
+        assert_eq!(
+            transport.calls.lock().unwrap()[0],
+            (
+                "https://generativelanguage.googleapis.com:443/v1beta/models?pageSize=1".to_string(),
+                "secret-primary".to_string()
+            )
+        );
+        assert!(!prober.probe_once().await.unwrap().reachable);
+        let failed = prober.probe_once().await.unwrap();
+        assert!(!failed.reachable);
+        assert!(failed.message.contains("connection refused"));
+        assert!(!prober.latest().unwrap().reachable);
+
+        // 没有可用密钥时跳过探测，保留上一次结果
+        for _ in 0..3 {
+            key_manager.mark_key_failed_with_status("primary", 500).await;
+        }
+        assert!(prober.probe_once().await.is_none());
+        assert_eq!(transport.calls.lock().unwrap().len(), 3);
+        assert!(prober.latest().is_some());
+    }
+}